@@ -18,10 +18,21 @@
 
 use std::time::Duration;
 
-use paperback_core::shamir::Dealer;
+use paperback_core::{gf::GfElem, shamir::Dealer};
 
 use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
 use rand::{distributions::Standard, Rng};
+use rayon::ThreadPoolBuilder;
+
+fn benchmark_gf_mul(c: &mut Criterion) {
+    let mut rng = rand::thread_rng();
+    let xs = (0..1024)
+        .map(|_| GfElem::new_rand(&mut rng))
+        .collect::<Vec<_>>();
+    c.bench_function("gf GfElem::mul 1024", |b| {
+        b.iter(|| xs.iter().fold(GfElem::ONE, |acc, &x| black_box(acc * x)))
+    });
+}
 
 fn benchmark_dealer_next_shard(c: &mut Criterion) {
     let mut group = c.benchmark_group("shamir Dealer::next_shard");
@@ -30,7 +41,7 @@ fn benchmark_dealer_next_shard(c: &mut Criterion) {
             .sample_iter(Standard)
             .take(1 << 12)
             .collect::<Vec<u8>>();
-        let dealer = Dealer::new(quorum_size, &vec);
+        let dealer = Dealer::new(quorum_size, &vec).unwrap();
         group.measurement_time(Duration::new(60, 0));
         group.throughput(Throughput::Bytes(vec.len() as u64));
         group.bench_with_input(format!("N={:03}", quorum_size), &dealer, |b, dealer| {
@@ -47,9 +58,9 @@ fn benchmark_recover_secret(c: &mut Criterion) {
             .sample_iter(Standard)
             .take(1 << 12)
             .collect::<Vec<u8>>();
-        let dealer = Dealer::new(quorum_size, &vec);
+        let dealer = Dealer::new(quorum_size, &vec).unwrap();
         let shards = (0..quorum_size)
-            .map(|_| dealer.next_shard())
+            .map(|_| dealer.next_shard().unwrap())
             .collect::<Vec<_>>();
         group.throughput(Throughput::Bytes(vec.len() as u64));
         group.measurement_time(Duration::new(40 + quorum_size as u64, 0));
@@ -62,9 +73,48 @@ fn benchmark_recover_secret(c: &mut Criterion) {
     group.finish()
 }
 
+// Compare the rayon-parallelised dealer and recovery on large secrets against
+// the same code run on a single thread, to show the speedup from the "rayon"
+// feature (building with --no-default-features performs comparably to the
+// single-threaded case, without the pool overhead).
+fn benchmark_parallel_speedup(c: &mut Criterion) {
+    let mut group = c.benchmark_group("shamir parallel speedup");
+    let vec = rand::thread_rng()
+        .sample_iter(Standard)
+        .take(1 << 20)
+        .collect::<Vec<u8>>();
+    let quorum_size = 10;
+    let dealer = Dealer::new(quorum_size, &vec).unwrap();
+    let shards = (0..quorum_size)
+        .map(|_| dealer.next_shard().unwrap())
+        .collect::<Vec<_>>();
+    group.throughput(Throughput::Bytes(vec.len() as u64));
+    group.sample_size(20);
+    // A thread count of zero lets rayon pick (one thread per CPU).
+    for (name, threads) in [("serial", 1), ("parallel", 0)] {
+        let pool = ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .expect("build rayon thread pool");
+        group.bench_with_input(
+            format!("Dealer::next_shard 1MiB {name}"),
+            &dealer,
+            |b, dealer| b.iter(|| pool.install(|| dealer.next_shard())),
+        );
+        group.bench_with_input(
+            format!("Dealer::recover().secret() 1MiB {name}"),
+            &shards,
+            |b, shards| {
+                b.iter(|| pool.install(|| black_box(Dealer::recover(shards).unwrap()).secret()))
+            },
+        );
+    }
+    group.finish()
+}
+
 criterion_group! {
     name = benches;
     config = Criterion::default().sample_size(250);
-    targets = benchmark_dealer_next_shard, benchmark_recover_secret
+    targets = benchmark_gf_mul, benchmark_dealer_next_shard, benchmark_recover_secret, benchmark_parallel_speedup
 }
 criterion_main!(benches);
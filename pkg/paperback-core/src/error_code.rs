@@ -0,0 +1,203 @@
+/*
+ * paperback: paper backup generator suitable for long-term storage
+ * Copyright (C) 2018-2022 Aleksa Sarai <cyphar@cyphar.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Stable error codes for consumers which cannot match on Rust error types.
+//!
+//! Every error variant in this crate has a numeric code and a string
+//! identifier (returned by the `code()` method of each error type), which
+//! will never be changed or reused once released. The codes are grouped by
+//! error type:
+//!
+//! | Range     | Error type                    |
+//! |-----------|-------------------------------|
+//! | `1xx`     | [`gf::Error`]                 |
+//! | `2xx`     | Shamir secret sharing         |
+//! | `3xx`     | [`v0::Error`]                 |
+//! | `4xx`     | [`v0::armor::ArmorError`]     |
+//! | `5xx`     | [`v0::pdf::Error`]            |
+//! | `6xx`     | [`v0::bech32m::Bech32Error`]  |
+//! | `7xx`     | [`v0::ParseError`]            |
+//! | `9xx`     | Reserved for `paperback-ffi`  |
+//!
+//! Errors which wrap another error from this crate return the code of the
+//! wrapped error.
+//!
+//! [`gf::Error`]: crate::gf::Error
+//! [`v0::Error`]: crate::v0::Error
+//! [`v0::armor::ArmorError`]: crate::v0::armor::ArmorError
+//! [`v0::pdf::Error`]: crate::v0::pdf::Error
+//! [`v0::bech32m::Bech32Error`]: crate::v0::bech32m::Bech32Error
+//! [`v0::ParseError`]: crate::v0::ParseError
+
+use std::fmt::Display;
+
+use serde::Serialize;
+
+/// The stable code of an error.
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq, Serialize)]
+pub struct ErrorCode {
+    /// Numeric code.
+    pub code: u32,
+    /// String identifier (in `kebab-case`).
+    pub id: &'static str,
+}
+
+impl ErrorCode {
+    pub(crate) const fn new(code: u32, id: &'static str) -> Self {
+        Self { code, id }
+    }
+
+    /// Describe the error `err` (which must have this code) as a JSON object
+    /// of the form `{"code": 301, "id": "...", "message": "..."}`.
+    pub fn to_json(&self, err: &dyn Display) -> String {
+        #[derive(Serialize)]
+        struct JsonError<'a> {
+            #[serde(flatten)]
+            code: &'a ErrorCode,
+            message: String,
+        }
+
+        serde_json::to_string(&JsonError {
+            code: self,
+            message: err.to_string(),
+        })
+        .expect("serialising an error cannot fail")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        gf, shamir,
+        v0::{self, armor::ArmorError, bech32m::Bech32Error, pdf, ParseError, ParseSection},
+    };
+
+    use std::collections::HashSet;
+
+    #[test]
+    fn error_codes_unique() {
+        let codes = [
+            gf::Error::NumPointsMismatch {
+                needed: 1,
+                num_points: 2,
+            }
+            .code(),
+            gf::Error::NonInvertiblePoint.code(),
+            shamir::Error::FieldExhausted(0).code(),
+            shamir::Error::ZeroX.code(),
+            shamir::Error::DuplicateX(1).code(),
+            shamir::Error::NoShards.code(),
+            shamir::Error::InconsistentShards("").code(),
+            shamir::Error::WrongShardCount {
+                needed: 2,
+                given: 1,
+            }
+            .code(),
+            shamir::Error::Uncorrectable { max_errors: 1 }.code(),
+            shamir::Error::Io(std::io::ErrorKind::Other.into()).code(),
+            shamir::Error::ZeroWeight.code(),
+            shamir::Error::Validation(shamir::ValidationError::default()).code(),
+            shamir::Error::ZeroThreshold.code(),
+            v0::Error::InvariantViolation("").code(),
+            v0::Error::MissingCapability("").code(),
+            v0::Error::AeadEncryption(aead::Error).code(),
+            v0::Error::AeadDecryption(aead::Error).code(),
+            v0::Error::ShardSecretDecode("".into()).code(),
+            v0::Error::ShardIdDecode(multibase::Error::InvalidBaseString).code(),
+            v0::Error::PrivateKeyDecode(ed25519_dalek::SignatureError::new()).code(),
+            v0::Error::InvalidPadding("").code(),
+            v0::Error::InvalidCompression("".into()).code(),
+            v0::Error::Bip39(bip39::ErrorKind::InvalidChecksum).code(),
+            v0::Error::InvalidSegment("").code(),
+            v0::Error::Io(std::io::ErrorKind::Other.into()).code(),
+            v0::Error::Slip39("".into()).code(),
+            v0::Error::Other("".into()).code(),
+            ArmorError::MissingChecksum { line: 1 }.code(),
+            ArmorError::InvalidEncoding {
+                line: 1,
+                reason: "".into(),
+            }
+            .code(),
+            ArmorError::LineTooLong { line: 1 }.code(),
+            ArmorError::ChecksumMismatch { line: 1 }.code(),
+            ArmorError::DataAfterEnd { line: 1 }.code(),
+            ArmorError::Truncated.code(),
+            pdf::Error::MismatchedQrCode.code(),
+            pdf::Error::MissingQrSegment { idx: 0 }.code(),
+            pdf::Error::WrongPaperbackVersion { version: 0 }.code(),
+            pdf::Error::ParseRawData("".into()).code(),
+            pdf::Error::ParseQrData("".into()).code(),
+            pdf::Error::GenerateQr(qrcode::types::QrError::DataTooLong).code(),
+            pdf::Error::TooManyCodes("".into()).code(),
+            pdf::Error::ParseSvg(printpdf::SvgParseError::InternalError).code(),
+            pdf::Error::GeneratePdf(printpdf::Error::Io(std::io::ErrorKind::Other.into())).code(),
+            pdf::Error::Io(std::io::ErrorKind::Other.into()).code(),
+            pdf::Error::SelfTest("".into()).code(),
+            pdf::Error::OtherError("".into()).code(),
+            Bech32Error::InvalidEncoding {
+                segment: 1,
+                reason: "".into(),
+            }
+            .code(),
+            Bech32Error::ChecksumMismatch { segment: 1 }.code(),
+            Bech32Error::OutOfOrder {
+                segment: 1,
+                found: 2,
+            }
+            .code(),
+            Bech32Error::InconsistentCount { segment: 1 }.code(),
+            Bech32Error::Truncated {
+                expected: 2,
+                found: 1,
+            }
+            .code(),
+            ParseError::from("")
+                .in_section(ParseSection::Encoding)
+                .code(),
+            ParseError::from("").in_section(ParseSection::Header).code(),
+            ParseError::from("")
+                .in_section(ParseSection::Payload)
+                .code(),
+            ParseError::from("")
+                .in_section(ParseSection::Checksum)
+                .code(),
+            ParseError::from("")
+                .in_section(ParseSection::Signature)
+                .code(),
+        ];
+        let numbers = codes.iter().map(|code| code.code).collect::<HashSet<_>>();
+        let ids = codes.iter().map(|code| code.id).collect::<HashSet<_>>();
+        assert_eq!(numbers.len(), codes.len());
+        assert_eq!(ids.len(), codes.len());
+    }
+
+    #[test]
+    fn error_code_wrapped() {
+        let err = v0::Error::from(shamir::Error::from(gf::Error::NonInvertiblePoint));
+        assert_eq!(err.code(), gf::Error::NonInvertiblePoint.code());
+    }
+
+    #[test]
+    fn error_code_json() {
+        let err = v0::Error::InvalidPadding("bad padding");
+        let json: serde_json::Value = serde_json::from_str(&err.code().to_json(&err)).unwrap();
+        assert_eq!(json["code"], err.code().code);
+        assert_eq!(json["id"], "invalid-padding");
+        assert_eq!(json["message"], err.to_string());
+    }
+}
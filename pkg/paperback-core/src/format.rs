@@ -0,0 +1,305 @@
+/*
+ * paperback: paper backup generator suitable for long-term storage
+ * Copyright (C) 2018-2022 Aleksa Sarai <cyphar@cyphar.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use crate::v0::{
+    self,
+    wire::{FromWire, ParseError, ParseSection, ToWire},
+};
+
+use std::{fmt, str::FromStr};
+
+use unsigned_varint::nom as varuint_nom;
+
+/// A version of the paperback wire format.
+///
+/// Every format version starts its main document with the same version
+/// varint (including the feature flags in the upper bits), so the version of
+/// a document can be determined before the rest of it is parsed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum FormatVersion {
+    /// The initial wire format, implemented by [`v0`](crate::v0).
+    V0,
+}
+
+impl FormatVersion {
+    /// The format version used for new backups by default.
+    pub const LATEST: Self = Self::V0;
+
+    /// All format versions supported by this version of paperback.
+    pub const ALL: &'static [Self] = &[Self::V0];
+
+    /// The version number stored in the wire format.
+    pub fn number(self) -> u32 {
+        match self {
+            Self::V0 => 0,
+        }
+    }
+
+    /// The format version with the given version number, if it is supported.
+    pub fn from_number(number: u32) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|version| version.number() == number)
+    }
+
+    /// Determine the format version of a serialised main document, without
+    /// parsing the rest of the document.
+    pub fn detect<B: AsRef<[u8]>>(main_document: B) -> Result<Self, ParseError> {
+        let input = main_document.as_ref();
+        let (_, version) = varuint_nom::u32(input)
+            .map_err(ParseError::expecting(
+                ParseSection::Header,
+                "a format version",
+            ))
+            .map_err(|err| err.locate(input.len()))?;
+        let number = v0::format_version(version);
+        Self::from_number(number).ok_or_else(|| {
+            let supported = Self::ALL
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(", ");
+            ParseError::new(
+                ParseSection::Header,
+                input,
+                format!("a supported format version ({})", supported),
+                format!("version {}", number),
+            )
+            .locate(input.len())
+        })
+    }
+}
+
+impl fmt::Display for FormatVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "v{}", self.number())
+    }
+}
+
+impl FromStr for FormatVersion {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "latest" {
+            return Ok(Self::LATEST);
+        }
+        s.strip_prefix('v')
+            .unwrap_or(s)
+            .parse::<u32>()
+            .ok()
+            .and_then(Self::from_number)
+            .ok_or_else(|| format!("unsupported format version '{}'", s))
+    }
+}
+
+/// Shards of a newly-created backup, together with the codewords needed to
+/// decrypt each of them.
+pub type EncryptedShards<F> = Vec<(<F as Format>::EncryptedKeyShard, Vec<String>)>;
+
+/// The operations every version of the paperback wire format provides, so
+/// that callers can create and recover backups without depending on the
+/// types of a particular version.
+pub trait Format {
+    /// The version of the wire format implemented.
+    const VERSION: FormatVersion;
+
+    /// The main document of a backup.
+    type MainDocument: FromWire + ToWire + Clone + fmt::Debug;
+
+    /// An encrypted key shard of a backup.
+    type EncryptedKeyShard: FromWire + ToWire + Clone + fmt::Debug;
+
+    /// Errors returned while creating or recovering a backup.
+    type Error: std::error::Error + Send + Sync + 'static;
+
+    /// Create a backup of `secret` which requires `quorum_size` of the
+    /// `num_shards` key shards to recover.
+    fn create<B: AsRef<[u8]>>(
+        quorum_size: u32,
+        num_shards: u32,
+        secret: B,
+    ) -> Result<(Self::MainDocument, EncryptedShards<Self>), Self::Error>;
+
+    /// Recover the secret stored in `main_document` using a quorum of
+    /// encrypted key shards and their codewords.
+    fn recover<I>(main_document: Self::MainDocument, shards: I) -> Result<Vec<u8>, Self::Error>
+    where
+        I: IntoIterator<Item = (Self::EncryptedKeyShard, Vec<String>)>;
+
+    /// The human-readable identifier of a main document.
+    fn document_id(main_document: &Self::MainDocument) -> String;
+
+    /// The number of key shards required to recover a main document.
+    fn quorum_size(main_document: &Self::MainDocument) -> u32;
+}
+
+/// The initial paperback wire format (see [`v0`](crate::v0)).
+#[derive(Clone, Copy, Debug)]
+pub struct V0;
+
+impl Format for V0 {
+    const VERSION: FormatVersion = FormatVersion::V0;
+
+    type MainDocument = v0::MainDocument;
+    type EncryptedKeyShard = v0::EncryptedKeyShard;
+    type Error = v0::Error;
+
+    fn create<B: AsRef<[u8]>>(
+        quorum_size: u32,
+        num_shards: u32,
+        secret: B,
+    ) -> Result<(Self::MainDocument, EncryptedShards<Self>), Self::Error> {
+        let backup = v0::Backup::new(quorum_size, secret)?;
+        let shards = backup
+            .next_shards(num_shards)?
+            .iter()
+            .map(v0::KeyShard::encrypt)
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok((backup.main_document().clone(), shards))
+    }
+
+    fn recover<I>(main_document: Self::MainDocument, shards: I) -> Result<Vec<u8>, Self::Error>
+    where
+        I: IntoIterator<Item = (Self::EncryptedKeyShard, Vec<String>)>,
+    {
+        let mut quorum = v0::UntrustedQuorum::new();
+        quorum.main_document(main_document);
+        for (shard, codewords) in shards {
+            let shard = shard
+                .decrypt(codewords)
+                .map_err(|err| v0::Error::Other(format!("failed to decrypt key shard: {}", err)))?;
+            quorum.push_shard(shard);
+        }
+        quorum
+            .validate()
+            .map_err(|err| v0::Error::Other(err.message))?
+            .recover_document()
+    }
+
+    fn document_id(main_document: &Self::MainDocument) -> String {
+        main_document.id()
+    }
+
+    fn quorum_size(main_document: &Self::MainDocument) -> u32 {
+        main_document.quorum_size()
+    }
+}
+
+/// A main document of any supported format version, decoded by dispatching
+/// on the version stored at the start of the document.
+#[derive(Clone, Debug)]
+pub enum AnyMainDocument {
+    V0(v0::MainDocument),
+}
+
+impl AnyMainDocument {
+    /// The format version of the main document.
+    pub fn version(&self) -> FormatVersion {
+        match self {
+            Self::V0(_) => FormatVersion::V0,
+        }
+    }
+
+    /// The human-readable identifier of the main document.
+    pub fn id(&self) -> String {
+        match self {
+            Self::V0(main_document) => V0::document_id(main_document),
+        }
+    }
+
+    /// The number of key shards required to recover the main document.
+    pub fn quorum_size(&self) -> u32 {
+        match self {
+            Self::V0(main_document) => V0::quorum_size(main_document),
+        }
+    }
+}
+
+impl From<v0::MainDocument> for AnyMainDocument {
+    fn from(main_document: v0::MainDocument) -> Self {
+        Self::V0(main_document)
+    }
+}
+
+impl ToWire for AnyMainDocument {
+    fn to_wire(&self) -> Vec<u8> {
+        match self {
+            Self::V0(main_document) => main_document.to_wire(),
+        }
+    }
+}
+
+impl FromWire for AnyMainDocument {
+    fn from_wire_partial(input: &[u8]) -> Result<(&[u8], Self), ParseError> {
+        match FormatVersion::detect(input)? {
+            FormatVersion::V0 => {
+                let (input, main_document) = v0::MainDocument::from_wire_partial(input)?;
+                Ok((input, Self::V0(main_document)))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn roundtrip<F: Format>() {
+        let secret = b"a secret stored in any format version";
+        let (main_document, shards) = F::create(2, 3, secret).unwrap();
+        assert_eq!(F::quorum_size(&main_document), 2);
+        assert_eq!(shards.len(), 3);
+
+        let main_document = F::MainDocument::from_wire(main_document.to_wire()).unwrap();
+        let recovered = F::recover(main_document, shards.into_iter().skip(1)).unwrap();
+        assert_eq!(recovered, secret);
+    }
+
+    #[test]
+    fn format_roundtrip() {
+        roundtrip::<V0>();
+    }
+
+    #[test]
+    fn detect_version() {
+        let (main_document, _) = V0::create(2, 2, b"secret").unwrap();
+        let wire = main_document.to_wire();
+        assert_eq!(FormatVersion::detect(&wire), Ok(FormatVersion::V0));
+
+        let any = AnyMainDocument::from_wire(&wire).unwrap();
+        assert_eq!(any.version(), FormatVersion::V0);
+        assert_eq!(any.id(), main_document.id());
+        assert_eq!(any.to_wire(), wire);
+
+        // Unknown versions are rejected before the rest of the document.
+        let err = AnyMainDocument::from_wire([0x7f, 0xff, 0xff]).unwrap_err();
+        assert_eq!(err.section(), ParseSection::Header);
+        assert_eq!(err.offset(), Some(0));
+        assert_eq!(err.found(), "version 127");
+    }
+
+    #[test]
+    fn parse_format_version() {
+        assert_eq!("v0".parse(), Ok(FormatVersion::V0));
+        assert_eq!("0".parse(), Ok(FormatVersion::V0));
+        assert_eq!("latest".parse(), Ok(FormatVersion::LATEST));
+        assert!("v1".parse::<FormatVersion>().is_err());
+        assert_eq!(FormatVersion::V0.to_string(), "v0");
+    }
+}
@@ -24,6 +24,9 @@ extern crate quickcheck;
 #[macro_use]
 extern crate quickcheck_macros;
 
+/// Optional data parallelism.
+mod par;
+
 /// Implementation of Shamir Secret Sharing.
 #[cfg(not(feature = "donotuse_expose_internal_modules"))]
 mod shamir;
@@ -33,6 +36,17 @@ mod shamir;
 #[cfg(feature = "donotuse_expose_internal_modules")]
 pub mod shamir;
 
+// The finite-field layer has a stable public API, even though the rest of the
+// Shamir implementation is internal.
+pub use shamir::gf;
+
+/// Progress reporting for long-running operations.
+pub mod progress;
+
+/// Stable error codes.
+pub mod error_code;
+pub use error_code::ErrorCode;
+
 /// Initial version of paperback wire format types.
 ///
 /// This module also includes all of the necessary code to serialise and
@@ -41,3 +55,8 @@ pub mod v0;
 
 /// Re-export of the newest paperback wire format types.
 pub use v0 as latest;
+
+/// Registry of wire format versions, and version-independent access to
+/// documents of any supported version.
+pub mod format;
+pub use format::{Format, FormatVersion};
@@ -0,0 +1,205 @@
+/*
+ * paperback: paper backup generator suitable for long-term storage
+ * Copyright (C) 2018-2022 Aleksa Sarai <cyphar@cyphar.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Progress reporting for long-running operations.
+//!
+//! Sharing or recovering a large secret with a high threshold (and rendering
+//! a large number of documents) can take minutes. Operations run inside
+//! [`with_progress`] report how far along they are to its callback, including
+//! the work they spread across worker threads. Outside of [`with_progress`],
+//! progress is not reported at all.
+
+use std::{
+    cell::RefCell,
+    fmt,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+/// The kind of long-running operation whose progress is being reported.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Stage {
+    /// Generating the polynomials used to shard a secret (see
+    /// `Dealer::new`).
+    Dealing,
+    /// Interpolating the polynomials of a secret from a quorum of shards (see
+    /// `Dealer::recover`).
+    Recovering,
+    /// Rendering the pages of documents.
+    Rendering,
+}
+
+impl fmt::Display for Stage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Dealing => "dealing",
+            Self::Recovering => "recovering",
+            Self::Rendering => "rendering",
+        })
+    }
+}
+
+/// A report of how far along an operation is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Progress {
+    /// The operation being run.
+    pub stage: Stage,
+    /// The number of steps of the operation which have completed.
+    pub done: u64,
+    /// The total number of steps of the operation.
+    pub total: u64,
+}
+
+impl Progress {
+    /// The fraction (from `0.0` to `1.0`) of the operation which has
+    /// completed.
+    pub fn fraction(&self) -> f64 {
+        match self.total {
+            0 => 1.0,
+            total => self.done as f64 / total as f64,
+        }
+    }
+
+    /// Returns whether the operation has completed.
+    pub fn is_complete(&self) -> bool {
+        self.done >= self.total
+    }
+}
+
+type Callback = Arc<dyn Fn(Progress) + Send + Sync>;
+
+thread_local! {
+    static CALLBACK: RefCell<Option<Callback>> = const { RefCell::new(None) };
+}
+
+// Restores the previous callback when with_progress returns (or unwinds).
+struct Restore(Option<Callback>);
+
+impl Drop for Restore {
+    fn drop(&mut self) {
+        let previous = self.0.take();
+        CALLBACK.with(|callback| *callback.borrow_mut() = previous);
+    }
+}
+
+/// Run `operation`, calling `progress` as each long-running operation started
+/// by it (on this thread) makes progress.
+///
+/// Each operation first reports that none of its steps have completed, and
+/// finally that all of them have. Steps which complete on worker threads are
+/// reported from those threads, so `progress` must be thread-safe. To avoid
+/// slowing down operations with many small steps, progress is only reported
+/// when the completed percentage changes.
+pub fn with_progress<T, P, F>(progress: P, operation: F) -> T
+where
+    P: Fn(Progress) + Send + Sync + 'static,
+    F: FnOnce() -> T,
+{
+    let previous = CALLBACK.with(|callback| callback.borrow_mut().replace(Arc::new(progress)));
+    let _restore = Restore(previous);
+    operation()
+}
+
+/// Tracks the progress of a single operation, reporting it to the callback of
+/// the enclosing [`with_progress`] (if any).
+///
+/// A `Tracker` can be shared between threads, so that steps completed on
+/// worker threads are counted.
+pub struct Tracker {
+    callback: Option<Callback>,
+    stage: Stage,
+    total: u64,
+    done: AtomicU64,
+}
+
+impl Tracker {
+    /// Start tracking an operation with `total` steps.
+    pub fn start(stage: Stage, total: usize) -> Self {
+        let tracker = Self {
+            callback: CALLBACK.with(|callback| callback.borrow().clone()),
+            stage,
+            total: total as u64,
+            done: AtomicU64::new(0),
+        };
+        tracker.report(0);
+        tracker
+    }
+
+    /// Record that one more step of the operation has completed.
+    pub fn step(&self) {
+        if self.callback.is_none() {
+            return;
+        }
+        let done = self.done.fetch_add(1, Ordering::Relaxed) + 1;
+        // Only report when the percentage changes.
+        if done * 100 / self.total.max(1) != (done - 1) * 100 / self.total.max(1)
+            || done == self.total
+        {
+            self.report(done);
+        }
+    }
+
+    fn report(&self, done: u64) {
+        if let Some(ref callback) = self.callback {
+            callback(Progress {
+                stage: self.stage,
+                done,
+                total: self.total,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::sync::Mutex;
+
+    #[test]
+    fn progress_reported() {
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let tracker = with_progress(
+            {
+                let reports = Arc::clone(&reports);
+                move |progress| reports.lock().unwrap().push(progress)
+            },
+            || {
+                let tracker = Tracker::start(Stage::Dealing, 1000);
+                (0..1000).for_each(|_| tracker.step());
+                tracker
+            },
+        );
+        let reports = reports.lock().unwrap();
+        // Only changes in the percentage are reported.
+        assert_eq!(reports.len(), 101);
+        assert_eq!(reports[0].done, 0);
+        assert!(reports.last().unwrap().is_complete());
+        assert!(reports
+            .iter()
+            .all(|progress| progress.stage == Stage::Dealing && progress.total == 1000));
+
+        // Trackers started outside of with_progress report nothing.
+        drop(tracker);
+        let tracker = Tracker::start(Stage::Recovering, 10);
+        tracker.step();
+        assert_eq!(reports.len(), 101);
+    }
+}
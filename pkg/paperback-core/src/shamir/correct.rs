@@ -0,0 +1,234 @@
+/*
+ * paperback: paper backup generator suitable for long-term storage
+ * Copyright (C) 2018-2022 Aleksa Sarai <cyphar@cyphar.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Error-locating decoding of shards with the [Berlekamp–Welch
+//! algorithm][bw].
+//!
+//! Shamir shares are a Reed–Solomon codeword, so given `n >= k + 2e` points on
+//! a polynomial of degree `k - 1` (where `k` is the threshold), up to `e`
+//! corrupted points can be located (and thus excluded) without knowing which
+//! points are correct in advance.
+//!
+//! [bw]: https://en.wikipedia.org/wiki/Berlekamp%E2%80%93Welch_algorithm
+
+use crate::shamir::{gf::GfElem, gf128::Gf128Elem, gf64::Gf64Elem};
+
+use std::ops::{Add, Div, Mul, Sub};
+
+/// The field operations needed for Berlekamp–Welch decoding.
+pub(super) trait Field:
+    Copy + PartialEq + Add<Output = Self> + Sub<Output = Self> + Mul<Output = Self> + Div<Output = Self>
+{
+    const ZERO: Self;
+    const ONE: Self;
+}
+
+impl Field for GfElem {
+    const ZERO: Self = GfElem::ZERO;
+    const ONE: Self = GfElem::ONE;
+}
+
+impl Field for Gf64Elem {
+    const ZERO: Self = Gf64Elem::ZERO;
+    const ONE: Self = Gf64Elem::ONE;
+}
+
+impl Field for Gf128Elem {
+    const ZERO: Self = Gf128Elem::ZERO;
+    const ONE: Self = Gf128Elem::ONE;
+}
+
+/// Returns the maximum number of corrupted points which can be located given
+/// `n` points on a polynomial of degree `k - 1`.
+pub(super) fn max_errors(k: usize, n: usize) -> usize {
+    n.saturating_sub(k) / 2
+}
+
+/// Locate the corrupted points among `points`, which should all lie on a
+/// polynomial of degree `k - 1`. Returns the indices of the corrupted points,
+/// or `None` if there are more than `max_errors(k, points.len())` of them.
+///
+/// All `x` values must be distinct, and `points.len() >= k >= 1`.
+pub(super) fn locate_errors<F: Field>(k: usize, points: &[(F, F)]) -> Option<Vec<usize>> {
+    let n = points.len();
+    let e = max_errors(k, n);
+
+    // We need to find polynomials Q(x) of degree < k + e and E(x) (the error
+    // locator) which is monic of degree e, such that
+    //
+    //   Q(x_j) = y_j E(x_j)    for all j
+    //
+    // which is a linear system of n equations in the k + 2e unknown
+    // coefficients (q_0, ..., q_{k+e-1}, e_0, ..., e_{e-1}):
+    //
+    //   \sum_i q_i x_j^i - y_j \sum_{i<e} e_i x_j^i = y_j x_j^e
+    let num_q = k + e;
+    let num_unknowns = num_q + e;
+    let mut rows = points
+        .iter()
+        .map(|&(x, y)| {
+            let powers = std::iter::successors(Some(F::ONE), |&p| Some(p * x))
+                .take(num_q + 1)
+                .collect::<Vec<_>>();
+            let mut row = Vec::with_capacity(num_unknowns + 1);
+            row.extend_from_slice(&powers[..num_q]);
+            // Subtraction is the same as addition in GF(2^n), but we spell it
+            // out for clarity.
+            row.extend(powers[..e].iter().map(|&p| F::ZERO - y * p));
+            row.push(y * powers[e]);
+            row
+        })
+        .collect::<Vec<_>>();
+    let solution = solve(&mut rows, num_unknowns)?;
+    let (q, e_coeffs) = solution.split_at(num_q);
+
+    // P(x) = Q(x) / E(x), which must divide exactly if there are at most e
+    // errors.
+    let mut locator = e_coeffs.to_vec();
+    locator.push(F::ONE);
+    let poly = divide_exact(q, &locator)?;
+
+    let errors = points
+        .iter()
+        .enumerate()
+        .filter(|&(_, &(x, y))| evaluate(&poly, x) != y)
+        .map(|(j, _)| j)
+        .collect::<Vec<_>>();
+    match errors.len() <= e {
+        true => Some(errors),
+        false => None,
+    }
+}
+
+// Solve the augmented linear system (with num_unknowns columns followed by the
+// right-hand side) with Gauss-Jordan elimination. If the system is
+// underdetermined, free variables are set to zero. Returns None if the system
+// is inconsistent.
+fn solve<F: Field>(rows: &mut [Vec<F>], num_unknowns: usize) -> Option<Vec<F>> {
+    let mut pivots = Vec::new();
+    let mut rank = 0;
+    for col in 0..num_unknowns {
+        let Some(pivot) = (rank..rows.len()).find(|&r| rows[r][col] != F::ZERO) else {
+            continue;
+        };
+        rows.swap(rank, pivot);
+
+        let inv = F::ONE / rows[rank][col];
+        rows[rank].iter_mut().for_each(|v| *v = *v * inv);
+        let pivot_row = rows[rank].clone();
+        for (r, row) in rows.iter_mut().enumerate() {
+            let factor = row[col];
+            if r != rank && factor != F::ZERO {
+                row.iter_mut()
+                    .zip(&pivot_row)
+                    .skip(col)
+                    .for_each(|(v, &p)| *v = *v - factor * p);
+            }
+        }
+        pivots.push(col);
+        rank += 1;
+    }
+
+    // Any remaining rows are all-zero, so their right-hand side must be too.
+    if rows[rank..].iter().any(|row| row[num_unknowns] != F::ZERO) {
+        return None;
+    }
+
+    let mut solution = vec![F::ZERO; num_unknowns];
+    for (row, &col) in rows.iter().zip(&pivots) {
+        solution[col] = row[num_unknowns];
+    }
+    Some(solution)
+}
+
+// Divide the polynomial a by the monic polynomial b (both with coefficients in
+// increasing degree), returning None if there is a remainder.
+fn divide_exact<F: Field>(a: &[F], b: &[F]) -> Option<Vec<F>> {
+    let deg_b = b.len() - 1;
+    let mut rem = a.to_vec();
+    let mut quot = vec![F::ZERO; a.len().saturating_sub(deg_b).max(1)];
+    for deg in (deg_b..a.len()).rev() {
+        let coeff = rem[deg];
+        quot[deg - deg_b] = coeff;
+        for (t, &bt) in b.iter().enumerate() {
+            rem[deg - deg_b + t] = rem[deg - deg_b + t] - coeff * bt;
+        }
+    }
+    match rem.iter().all(|&r| r == F::ZERO) {
+        true => Some(quot),
+        false => None,
+    }
+}
+
+fn evaluate<F: Field>(poly: &[F], x: F) -> F {
+    poly.iter()
+        .rev()
+        .fold(F::ZERO, |acc, &coeff| acc * x + coeff)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::shamir::gf::{EvaluablePolynomial, GfPolynomial};
+
+    #[test]
+    fn locate_errors_gf32() {
+        let k = 3;
+        let poly = GfPolynomial::new_rand(k as u32 - 1, &mut rand::thread_rng());
+        let mut points = (1..=9u32)
+            .map(GfElem::from)
+            .map(|x| (x, poly.evaluate(x)))
+            .collect::<Vec<_>>();
+
+        // No errors.
+        assert_eq!(locate_errors(k, &points), Some(vec![]));
+
+        // Up to (9 - 3) / 2 = 3 errors can be located.
+        points[1].1 += GfElem::ONE;
+        points[4].1 += GfElem::from(1234);
+        points[8].1 *= GfElem::from(7);
+        assert_eq!(locate_errors(k, &points), Some(vec![1, 4, 8]));
+
+        // But not four.
+        points[6].1 += GfElem::from(99);
+        assert_eq!(locate_errors(k, &points), None);
+    }
+
+    #[test]
+    fn locate_errors_gf64() {
+        // y = 5 + 3x + x^2
+        let poly = [Gf64Elem::from(5), Gf64Elem::from(3), Gf64Elem::ONE];
+        let mut points = (1..=5u64)
+            .map(Gf64Elem::from)
+            .map(|x| (x, evaluate(&poly, x)))
+            .collect::<Vec<_>>();
+        points[2].1 += Gf64Elem::from(u64::MAX);
+        assert_eq!(locate_errors(3, &points), Some(vec![2]));
+    }
+
+    #[test]
+    fn locate_errors_threshold_one() {
+        let mut points = (1..=3u32)
+            .map(|x| (GfElem::from(x), GfElem::from(42)))
+            .collect::<Vec<_>>();
+        assert_eq!(locate_errors(1, &points), Some(vec![]));
+        points[0].1 = GfElem::from(43);
+        assert_eq!(locate_errors(1, &points), Some(vec![0]));
+    }
+}
@@ -16,24 +16,70 @@
  * along with this program.  If not, see <https://www.gnu.org/licenses/>.
  */
 
-use crate::shamir::{
-    gf::{EvaluablePolynomial, GfBarycentric, GfElem, GfElemPrimitive, GfPolynomial},
-    shard::Shard,
-    Error,
+use crate::{
+    par::*,
+    progress::{Stage, Tracker},
+    shamir::{
+        correct,
+        gf::{EvaluablePolynomial, GfBarycentric, GfElem, GfElemPrimitive, GfPolynomial},
+        gf128::{EvaluablePolynomial128, Gf128Barycentric, Gf128Elem, Gf128Polynomial},
+        gf64::{EvaluablePolynomial64, Gf64Barycentric, Gf64Elem, Gf64Polynomial},
+        shard::{PartialShard, Shard, ShardYs, WeightedShard},
+        Error, FieldSize, ValidationError, Violation,
+    },
+    v0::ShardId,
 };
 
-use std::mem;
+use std::{
+    collections::HashSet,
+    fmt,
+    ops::Range,
+    sync::{Arc, Mutex},
+};
 
-use rayon::prelude::*;
+use itertools::Itertools;
+use rand::{CryptoRng, RngCore};
+use subtle::ConstantTimeEq;
+use zeroize::{Zeroize, Zeroizing};
 
 /// Factory to share a secret using [Shamir Secret Sharing][sss].
 ///
 /// [sss]: https://en.wikipedia.org/wiki/Shamir%27s_Secret_Sharing
+///
+/// The `Dealer` keeps track of the `x` values of every `Shard` it has issued,
+/// so that it never issues the same `Shard` twice. Clones of a `Dealer` share
+/// this set (they hold the same polynomials, so a `Shard` issued by one clone
+/// is identical to the same `Shard` issued by any other clone).
 #[derive(Clone, Debug)]
 pub struct Dealer {
-    polys: Vec<Box<dyn EvaluablePolynomial>>,
+    polys: Polynomials,
     secret_len: usize,
-    threshold: GfElemPrimitive,
+    threshold: u32,
+    issued: Arc<Mutex<HashSet<GfElem>>>,
+}
+
+// One polynomial per chunk of the secret, in the field the secret is shared in.
+#[derive(Clone)]
+enum Polynomials {
+    Bits32(Vec<Box<dyn EvaluablePolynomial>>),
+    Bits64(Vec<Box<dyn EvaluablePolynomial64>>),
+    Bits128(Vec<Box<dyn EvaluablePolynomial128>>),
+}
+
+// The constants of the polynomials are the secret, so only describe their
+// shape (so that a Dealer can be Debug-printed without leaking the secret).
+impl fmt::Debug for Polynomials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (field_size, len) = match self {
+            Self::Bits32(polys) => (FieldSize::Bits32, polys.len()),
+            Self::Bits64(polys) => (FieldSize::Bits64, polys.len()),
+            Self::Bits128(polys) => (FieldSize::Bits128, polys.len()),
+        };
+        f.debug_struct("Polynomials")
+            .field("field_size", &field_size)
+            .field("len", &len)
+            .finish()
+    }
 }
 
 impl Dealer {
@@ -45,74 +91,386 @@ impl Dealer {
     }
 
     /// Construct a new `Dealer` to shard the `secret`, requiring at least
-    /// `threshold` shards to reconstruct the secret.
-    pub fn new<B: AsRef<[u8]>>(threshold: u32, secret: B) -> Self {
-        assert!(threshold > 0, "must at least have a threshold of one");
-        let k = threshold - 1;
+    /// `threshold` shards to reconstruct the secret. `threshold` must be
+    /// non-zero.
+    ///
+    /// The polynomials generated are reported as [`Stage::Dealing`] progress
+    /// (see [`crate::progress`]).
+    pub fn new<B: AsRef<[u8]>>(threshold: u32, secret: B) -> Result<Self, Error> {
+        Self::new_with_field_size(threshold, secret, FieldSize::default())
+    }
+
+    /// Construct a new `Dealer` to shard the `secret` in the given field,
+    /// requiring at least `threshold` shards to reconstruct the secret.
+    pub fn new_with_field_size<B: AsRef<[u8]>>(
+        threshold: u32,
+        secret: B,
+        field_size: FieldSize,
+    ) -> Result<Self, Error> {
+        let k = threshold.checked_sub(1).ok_or(Error::ZeroThreshold)?;
         let secret = secret.as_ref();
-        let polys = secret
-            // Generate &[u32] from &[u8], by chunking into sets of four.
-            .par_chunks(mem::size_of::<GfElemPrimitive>())
-            .map(GfElem::from_bytes)
-            // Generate a random polynomial with the value as the constant.
-            .map(|x0| {
-                let mut poly = GfPolynomial::new_rand(k, &mut rand::thread_rng());
-                *poly.constant_mut() = x0;
-                Box::new(poly) as Box<dyn EvaluablePolynomial>
-            })
-            .collect::<Vec<_>>();
-        Dealer {
+        // Split the secret into field-element-sized chunks, and generate a
+        // random polynomial with each chunk as the constant.
+        let chunks = secret.par_chunks(field_size.elem_len());
+        let progress = Tracker::start(Stage::Dealing, chunks.len());
+        let polys = match field_size {
+            FieldSize::Bits32 => Polynomials::Bits32(
+                chunks
+                    .map(GfElem::from_bytes)
+                    .map(|x0| {
+                        let mut poly = GfPolynomial::new_rand(k, &mut rand::thread_rng());
+                        *poly.constant_mut() = x0;
+                        progress.step();
+                        Box::new(poly) as Box<dyn EvaluablePolynomial>
+                    })
+                    .collect(),
+            ),
+            FieldSize::Bits64 => Polynomials::Bits64(
+                chunks
+                    .map(Gf64Elem::from_bytes)
+                    .map(|x0| {
+                        let mut poly = Gf64Polynomial::new_rand(k, &mut rand::thread_rng());
+                        *poly.constant_mut() = x0;
+                        progress.step();
+                        Box::new(poly) as Box<dyn EvaluablePolynomial64>
+                    })
+                    .collect(),
+            ),
+            FieldSize::Bits128 => Polynomials::Bits128(
+                chunks
+                    .map(Gf128Elem::from_bytes)
+                    .map(|x0| {
+                        let mut poly = Gf128Polynomial::new_rand(k, &mut rand::thread_rng());
+                        *poly.constant_mut() = x0;
+                        progress.step();
+                        Box::new(poly) as Box<dyn EvaluablePolynomial128>
+                    })
+                    .collect(),
+            ),
+        };
+        Ok(Dealer {
             polys,
             threshold,
             secret_len: secret.len(),
+            issued: Default::default(),
+        })
+    }
+
+    /// Equivalent to `Dealer::new`, except that the polynomials are
+    /// generated using `rng` rather than `rand::thread_rng`.
+    ///
+    /// This allows callers to use a hardware RNG, or a seeded RNG to produce
+    /// reproducible deals (for test vectors). The polynomials are generated
+    /// serially, so the same `rng` state always produces the same `Dealer`.
+    /// `rng` must be a cryptographically secure RNG -- a predictable `rng`
+    /// reveals the secret to anyone holding a single `Shard`.
+    pub fn new_with_rng<B, R>(threshold: u32, secret: B, rng: &mut R) -> Result<Self, Error>
+    where
+        B: AsRef<[u8]>,
+        R: CryptoRng + RngCore + ?Sized,
+    {
+        Self::new_with_field_size_and_rng(threshold, secret, FieldSize::default(), rng)
+    }
+
+    /// Equivalent to `Dealer::new_with_field_size`, except that the
+    /// polynomials are generated using `rng` (see `Dealer::new_with_rng`).
+    pub fn new_with_field_size_and_rng<B, R>(
+        threshold: u32,
+        secret: B,
+        field_size: FieldSize,
+        rng: &mut R,
+    ) -> Result<Self, Error>
+    where
+        B: AsRef<[u8]>,
+        R: CryptoRng + RngCore + ?Sized,
+    {
+        let k = threshold.checked_sub(1).ok_or(Error::ZeroThreshold)?;
+        let secret = secret.as_ref();
+        let chunks = secret.chunks(field_size.elem_len());
+        let progress = Tracker::start(Stage::Dealing, chunks.len());
+        let polys = match field_size {
+            FieldSize::Bits32 => Polynomials::Bits32(
+                chunks
+                    .map(|chunk| {
+                        let mut poly = GfPolynomial::new_rand(k, &mut *rng);
+                        *poly.constant_mut() = GfElem::from_bytes(chunk);
+                        progress.step();
+                        Box::new(poly) as Box<dyn EvaluablePolynomial>
+                    })
+                    .collect(),
+            ),
+            FieldSize::Bits64 => Polynomials::Bits64(
+                chunks
+                    .map(|chunk| {
+                        let mut poly = Gf64Polynomial::new_rand(k, &mut *rng);
+                        *poly.constant_mut() = Gf64Elem::from_bytes(chunk);
+                        progress.step();
+                        Box::new(poly) as Box<dyn EvaluablePolynomial64>
+                    })
+                    .collect(),
+            ),
+            FieldSize::Bits128 => Polynomials::Bits128(
+                chunks
+                    .map(|chunk| {
+                        let mut poly = Gf128Polynomial::new_rand(k, &mut *rng);
+                        *poly.constant_mut() = Gf128Elem::from_bytes(chunk);
+                        progress.step();
+                        Box::new(poly) as Box<dyn EvaluablePolynomial128>
+                    })
+                    .collect(),
+            ),
+        };
+        Ok(Dealer {
+            polys,
+            threshold,
+            secret_len: secret.len(),
+            issued: Default::default(),
+        })
+    }
+
+    /// Construct a new `Dealer` for the same secret (in the same field), with
+    /// freshly generated polynomials requiring `threshold` shards to
+    /// reconstruct the secret.
+    ///
+    /// `Shard`s issued by the new `Dealer` cannot be combined with `Shard`s
+    /// issued by this one.
+    pub fn redeal(&self, threshold: u32) -> Result<Self, Error> {
+        Self::new_with_field_size(
+            threshold,
+            Zeroizing::new(self.secret()),
+            self.ys_field_size(),
+        )
+    }
+
+    fn ys_field_size(&self) -> FieldSize {
+        match &self.polys {
+            Polynomials::Bits32(_) => FieldSize::Bits32,
+            Polynomials::Bits64(_) => FieldSize::Bits64,
+            Polynomials::Bits128(_) => FieldSize::Bits128,
         }
     }
 
     /// Get the secret value stored by the `Dealer`.
+    ///
+    /// The caller is responsible for wiping the returned secret once it is no
+    /// longer needed.
     pub fn secret(&self) -> Vec<u8> {
-        let mut secret = self
-            .polys
-            .par_iter()
-            .map(|poly| poly.constant())
-            .flat_map(|x| x.to_bytes())
-            .collect::<Vec<_>>();
+        // Avoid GfElem::to_bytes so that no temporary copies of the secret are
+        // left behind on the heap.
+        let mut secret = match &self.polys {
+            Polynomials::Bits32(polys) => polys
+                .par_iter()
+                .flat_map_iter(|poly| poly.constant().inner().to_le_bytes())
+                .collect::<Vec<_>>(),
+            Polynomials::Bits64(polys) => polys
+                .par_iter()
+                .flat_map_iter(|poly| poly.constant().inner().to_le_bytes())
+                .collect::<Vec<_>>(),
+            Polynomials::Bits128(polys) => polys
+                .par_iter()
+                .flat_map_iter(|poly| poly.constant().inner().to_le_bytes())
+                .collect::<Vec<_>>(),
+        };
 
         // Cannot call .take() on rayon::iter::FlatMap, so do it the
-        // old-fashioned way instead.
-        secret.drain(self.secret_len..);
+        // old-fashioned way instead. The truncated bytes are only the zero
+        // padding of the final element.
+        secret.truncate(self.secret_len);
         secret
     }
 
     /// Generate a new `Shard` for the secret.
     ///
-    /// NOTE: The `x` value is calculated randomly, which means that there is a
-    ///       small chance that two separate calls to `Dealer::shard` will
-    ///       generate the same `Shard`. It is up to the caller to be sure that
-    ///       they have enough *unique* shards to reconstruct the secret.
-    // TODO: I'm not convinced the chances of collision are low enough...
-    pub fn next_shard(&self) -> Shard {
-        let mut x = GfElem::ZERO;
-        while x == GfElem::ZERO {
-            x = GfElem::new_rand(&mut rand::thread_rng());
+    /// The `x` value is chosen randomly from the `x` values which have not
+    /// already been issued by this `Dealer` (or any of its clones), so every
+    /// call is guaranteed to return a unique `Shard`. Once every non-zero `x`
+    /// value in the field has been issued, `Error::FieldExhausted` is
+    /// returned.
+    pub fn next_shard(&self) -> Result<Shard, Error> {
+        Ok(self
+            .next_shards(1)?
+            .pop()
+            .expect("next_shards(1) must return one shard"))
+    }
+
+    /// Generate `n` new `Shard`s for the secret.
+    ///
+    /// The returned `Shard`s are unique, and distinct from any `Shard`
+    /// previously issued by this `Dealer` (or any of its clones). If there
+    /// are fewer than `n` unissued `x` values left, `Error::FieldExhausted` is
+    /// returned and no `Shard`s are issued.
+    pub fn next_shards(&self, n: u32) -> Result<Vec<Shard>, Error> {
+        self.next_shards_with_rng(n, &mut rand::thread_rng())
+    }
+
+    /// Equivalent to `Dealer::next_shard`, except that the `x` value is
+    /// chosen using `rng` rather than `rand::thread_rng`.
+    pub fn next_shard_with_rng<R: CryptoRng + RngCore + ?Sized>(
+        &self,
+        rng: &mut R,
+    ) -> Result<Shard, Error> {
+        Ok(self
+            .next_shards_with_rng(1, rng)?
+            .pop()
+            .expect("next_shards_with_rng(1) must return one shard"))
+    }
+
+    /// Equivalent to `Dealer::next_shards`, except that the `x` values are
+    /// chosen using `rng` rather than `rand::thread_rng`.
+    pub fn next_shards_with_rng<R: CryptoRng + RngCore + ?Sized>(
+        &self,
+        n: u32,
+        rng: &mut R,
+    ) -> Result<Vec<Shard>, Error> {
+        self.validate()?;
+        let xs = {
+            let mut issued = self.issued.lock().expect("issued x values lock poisoned");
+            allocate_xs(&mut issued, n, GfElemPrimitive::MAX, || {
+                GfElem::new_rand(&mut *rng).into()
+            })?
+        };
+        xs.iter()
+            .map(|&x| self.issue(x))
+            .collect::<Result<Vec<_>, _>>()
+            .inspect_err(|_| self.release(&xs))
+    }
+
+    /// Generate a new `WeightedShard` for the secret, made up of `weight` new
+    /// `Shard`s (see `Dealer::next_shards`). A `weight` of 0 is rejected with
+    /// `Error::ZeroWeight`.
+    pub fn next_weighted_shard(&self, weight: u32) -> Result<WeightedShard, Error> {
+        self.next_weighted_shard_with_rng(weight, &mut rand::thread_rng())
+    }
+
+    /// Equivalent to `Dealer::next_weighted_shard`, except that the `x` values
+    /// are chosen using `rng` rather than `rand::thread_rng`.
+    pub fn next_weighted_shard_with_rng<R: CryptoRng + RngCore + ?Sized>(
+        &self,
+        weight: u32,
+        rng: &mut R,
+    ) -> Result<WeightedShard, Error> {
+        if weight == 0 {
+            return Err(Error::ZeroWeight);
         }
-        self.shard(x).expect("non x=0 shard should've been created")
+        Ok(WeightedShard {
+            group: None,
+            shards: self.next_shards_with_rng(weight, rng)?,
+        })
+    }
+
+    /// Generate a new `Shard` for the secret at a caller-chosen `x` value.
+    ///
+    /// This allows shards to be bound to stable identifiers (such as
+    /// custodian numbers), so that re-dealing the same secret produces shards
+    /// at the same coordinates. Unlike `Dealer::shard`, the `x` value must not
+    /// have been issued already by this `Dealer` (or any of its clones).
+    pub fn shard_at(&self, x: GfElem) -> Result<Shard, Error> {
+        if x == GfElem::ZERO {
+            return Err(Error::ZeroX);
+        }
+        self.validate()?;
+        if !self
+            .issued
+            .lock()
+            .expect("issued x values lock poisoned")
+            .insert(x)
+        {
+            return Err(Error::DuplicateX(x.into()));
+        }
+        self.issue(x).inspect_err(|_| self.release(&[x]))
     }
 
     /// Generate a `Shard` for the secret using the given `x` value.
-    pub fn shard(&self, x: GfElem) -> Option<Shard> {
+    ///
+    /// The `x` value is marked as issued, so later calls to
+    /// `Dealer::next_shard` will not return the same `Shard`. If the `Shard`
+    /// cannot be issued, the `x` value is left as it was. An `x` value of 0 is
+    /// rejected with `Error::ZeroX`.
+    pub fn shard(&self, x: GfElem) -> Result<Shard, Error> {
         if x == GfElem::ZERO {
-            return None;
-        }
-        let ys = self
-            .polys
-            .par_iter()
-            .map(|poly| {
-                let y = poly.evaluate(x);
-                assert!(self.threshold == 1 || y != poly.constant());
-                y
-            })
-            .collect::<Vec<_>>();
-        Some(Shard {
+            return Err(Error::ZeroX);
+        }
+        self.validate()?;
+        let newly_issued = self
+            .issued
+            .lock()
+            .expect("issued x values lock poisoned")
+            .insert(x);
+        self.issue(x).inspect_err(|_| {
+            if newly_issued {
+                self.release(&[x]);
+            }
+        })
+    }
+
+    /// Check the internal invariants of the `Dealer`, returning a report of
+    /// every invariant which does not hold.
+    ///
+    /// This is checked before issuing any `Shard`, so that a corrupted
+    /// `Dealer` is detected rather than issuing bad `Shard`s.
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        let mut report = ValidationError::new();
+        let expected = self.secret_len.div_ceil(self.ys_field_size().elem_len());
+        let found = match &self.polys {
+            Polynomials::Bits32(polys) => polys.len(),
+            Polynomials::Bits64(polys) => polys.len(),
+            Polynomials::Bits128(polys) => polys.len(),
+        };
+        if expected != found {
+            report.push(Violation::PolynomialCount { expected, found });
+        }
+        report.into_result()
+    }
+
+    // Mark the x values as no longer issued, after failing to issue their
+    // shards. No shard was handed out, so they can be issued later.
+    fn release(&self, xs: &[GfElem]) {
+        let mut issued = self.issued.lock().expect("issued x values lock poisoned");
+        xs.iter().for_each(|x| {
+            issued.remove(x);
+        });
+    }
+
+    // Create the Shard at the (non-zero, already issued) x, checking that no y
+    // value is equal to the chunk of the secret it shares (with a threshold of
+    // 1, every y value is the chunk itself).
+    fn issue(&self, x: GfElem) -> Result<Shard, Error> {
+        fn check<Y: ConstantTimeEq>(
+            report: &mut ValidationError,
+            x: GfElem,
+            constants: impl Iterator<Item = Y>,
+            ys: &[Y],
+        ) {
+            constants
+                .zip(ys)
+                .enumerate()
+                .filter(|(_, (constant, y))| bool::from(constant.ct_eq(y)))
+                .for_each(|(chunk, _)| {
+                    report.push(Violation::ShardRevealsSecret { x: x.into(), chunk });
+                });
+        }
+
+        let ys = self.evaluate(x);
+        let mut report = ValidationError::new();
+        if self.threshold > 1 {
+            match (&self.polys, &ys) {
+                (Polynomials::Bits32(polys), ShardYs::Bits32(ys)) => {
+                    check(&mut report, x, polys.iter().map(|p| p.constant()), ys)
+                }
+                (Polynomials::Bits64(polys), ShardYs::Bits64(ys)) => {
+                    check(&mut report, x, polys.iter().map(|p| p.constant()), ys)
+                }
+                (Polynomials::Bits128(polys), ShardYs::Bits128(ys)) => {
+                    check(&mut report, x, polys.iter().map(|p| p.constant()), ys)
+                }
+                // Dealer::evaluate always returns y values in the field of the
+                // polynomials.
+                _ => report.push(Violation::FieldMismatch),
+            }
+        }
+        report.into_result()?;
+
+        Ok(Shard {
             x,
             ys,
             threshold: self.threshold,
@@ -120,52 +478,578 @@ impl Dealer {
         })
     }
 
+    // Compute the y values of the shard at x, without marking it as issued.
+    fn evaluate(&self, x: GfElem) -> ShardYs {
+        match &self.polys {
+            Polynomials::Bits32(polys) => {
+                ShardYs::Bits32(polys.par_iter().map(|poly| poly.evaluate(x)).collect())
+            }
+            Polynomials::Bits64(polys) => ShardYs::Bits64(
+                polys
+                    .par_iter()
+                    .map(|poly| poly.evaluate(x.into()))
+                    .collect(),
+            ),
+            Polynomials::Bits128(polys) => ShardYs::Bits128(
+                polys
+                    .par_iter()
+                    .map(|poly| poly.evaluate(x.into()))
+                    .collect(),
+            ),
+        }
+    }
+
     /// Reconstruct an entire `Dealer` from a *unique* set of `Shard`s.
     ///
-    /// The caller must pass exactly the correct number of shards.
+    /// The caller must pass exactly the correct number of consistent shards
+    /// (with the same threshold and secret length), otherwise an error is
+    /// returned.
     ///
     /// This operation is significantly slower than `recover_secret`, so it
     /// should only be used if it is necessary to construct additional shards
     /// with `Dealer::next_shard`.
+    ///
+    /// The polynomials interpolated are reported as [`Stage::Recovering`]
+    /// progress (see [`crate::progress`]).
     pub fn recover<S: AsRef<[Shard]>>(shards: S) -> Result<Self, Error> {
         let shards = shards.as_ref();
-        assert!(!shards.is_empty(), "must be provided at least one shard");
-
+        Self::check_shards(shards)?;
         let threshold = shards[0].threshold();
-        let polys_len = shards[0].ys.len();
-        let secret_len = shards[0].secret_len;
+        if shards.len() != threshold as usize {
+            return Err(Error::WrongShardCount {
+                needed: threshold,
+                given: shards.len(),
+            });
+        }
+        Self::check_unique(shards)?;
+        Self::interpolate(shards)
+    }
 
-        // TODO: Implement this consistency checking more nicely.
-        for shard in shards {
-            assert!(shard.threshold() == threshold, "shards must be consistent");
-            assert!(shard.ys.len() == polys_len, "shards must be consistent");
-            assert!(shard.secret_len == secret_len, "shards must be consistent");
+    /// Reconstruct an entire `Dealer` from *at least* `threshold` unique
+    /// `Shard`s, using any surplus shards to cross-verify the others.
+    ///
+    /// Every shard must agree on the polynomials defined by any `threshold`
+    /// of them, so a forged or corrupted shard is detected (and excluded) as
+    /// long as at least one other surplus shard is honest. The returned
+    /// `ShardConsistency` lists which shards agreed with the recovered
+    /// `Dealer`. If no surplus shard agrees with any subset (so the honest
+    /// shards cannot be identified), `Error::InconsistentShards` is returned.
+    pub fn recover_verified<S: AsRef<[Shard]>>(
+        shards: S,
+    ) -> Result<(Self, ShardConsistency), Error> {
+        let shards = shards.as_ref();
+        Self::check_shards(shards)?;
+        let threshold = shards[0].threshold() as usize;
+        if shards.len() < threshold {
+            return Err(Error::WrongShardCount {
+                needed: threshold as u32,
+                given: shards.len(),
+            });
         }
+        let xs = Self::check_unique(shards)?;
 
-        assert!(
-            shards.len() == threshold as usize,
-            "must have exactly {} shards",
-            threshold
-        );
+        // Interpolate from each subset of threshold shards in turn, and keep
+        // the dealer which the most shards agree with. Any subset made up of
+        // only honest shards will be in total agreement, so the common case
+        // only needs a single interpolation.
+        let mut best: Option<(Self, Vec<bool>)> = None;
+        for subset in Self::cross_check_subsets(shards.len(), threshold) {
+            let subset = subset
+                .iter()
+                .map(|&i| shards[i].clone())
+                .collect::<Vec<_>>();
+            let dealer = Self::interpolate(&subset)?;
+            let agrees = shards
+                .iter()
+                .map(|shard| bool::from(dealer.evaluate(shard.x).ct_eq(&shard.ys)))
+                .collect::<Vec<_>>();
+            let num_agree = agrees.iter().filter(|&&ok| ok).count();
+            if best.as_ref().map_or(true, |(_, best)| {
+                num_agree > best.iter().filter(|&&ok| ok).count()
+            }) {
+                best = Some((dealer, agrees));
+            }
+            if num_agree == shards.len() {
+                break;
+            }
+        }
+        let (dealer, agrees) = best.expect("at least one subset must be checked");
 
-        let polys = (0..polys_len)
+        // The threshold shards used for interpolation trivially agree with
+        // themselves, so unless some other shard also agreed we have no way
+        // of knowing which shards are the honest ones.
+        if shards.len() > threshold && agrees.iter().filter(|&&ok| ok).count() == threshold {
+            return Err(Error::InconsistentShards(
+                "no subset of shards is corroborated by any other shard",
+            ));
+        }
+
+        let (consistent, inconsistent): (Vec<_>, Vec<_>) =
+            shards.iter().zip(agrees).partition(|&(_, ok)| ok);
+        let consistency = ShardConsistency {
+            consistent: consistent.into_iter().map(|(s, _)| s.id()).collect(),
+            inconsistent: inconsistent.into_iter().map(|(s, _)| s.id()).collect(),
+        };
+        *dealer.issued.lock().expect("issued x values lock poisoned") = xs;
+
+        Ok((dealer, consistency))
+    }
+
+    /// Reconstruct an entire `Dealer` from `threshold + 2e` unique `Shard`s,
+    /// locating (and excluding) up to `e` corrupted shards with the
+    /// Berlekamp–Welch algorithm.
+    ///
+    /// Unlike `Dealer::recover_verified`, this finds the corrupted shards
+    /// directly rather than by trial and error, and succeeds so long as no
+    /// more than half of the surplus shards are corrupted. The returned
+    /// `ShardConsistency` lists the corrupted shards as inconsistent. If too
+    /// many shards are corrupted, `Error::Uncorrectable` is returned.
+    pub fn recover_corrected<S: AsRef<[Shard]>>(
+        shards: S,
+    ) -> Result<(Self, ShardConsistency), Error> {
+        let shards = shards.as_ref();
+        Self::check_shards(shards)?;
+        let threshold = shards[0].threshold() as usize;
+        if shards.len() < threshold {
+            return Err(Error::WrongShardCount {
+                needed: threshold as u32,
+                given: shards.len(),
+            });
+        }
+        let xs = Self::check_unique(shards)?;
+        let max_errors = correct::max_errors(threshold, shards.len());
+
+        // Each polynomial is corrected independently, and a shard is corrupt
+        // if any of its y values are.
+        let bad = (0..shards[0].ys.len())
             .into_par_iter()
             .map(|i| {
-                let xs = shards.iter().map(|s| s.x);
-                let ys = shards.iter().map(|s| s.ys[i]);
+                let errors = match shards[0].field_size() {
+                    FieldSize::Bits32 => correct::locate_errors(
+                        threshold,
+                        &shards
+                            .iter()
+                            .map(|s| match &s.ys {
+                                ShardYs::Bits32(ys) => (s.x, ys[i]),
+                                _ => unreachable!("field sizes checked above"),
+                            })
+                            .collect::<Vec<_>>(),
+                    ),
+                    FieldSize::Bits64 => correct::locate_errors(
+                        threshold,
+                        &shards
+                            .iter()
+                            .map(|s| match &s.ys {
+                                ShardYs::Bits64(ys) => (s.x.into(), ys[i]),
+                                _ => unreachable!("field sizes checked above"),
+                            })
+                            .collect::<Vec<_>>(),
+                    ),
+                    FieldSize::Bits128 => correct::locate_errors(
+                        threshold,
+                        &shards
+                            .iter()
+                            .map(|s| match &s.ys {
+                                ShardYs::Bits128(ys) => (s.x.into(), ys[i]),
+                                _ => unreachable!("field sizes checked above"),
+                            })
+                            .collect::<Vec<_>>(),
+                    ),
+                };
+                errors.ok_or(Error::Uncorrectable { max_errors })
+            })
+            .collect::<Result<Vec<_>, _>>()?
+            .into_iter()
+            .flatten()
+            .collect::<HashSet<_>>();
+        if bad.len() > max_errors {
+            return Err(Error::Uncorrectable { max_errors });
+        }
+
+        let (good, corrupted): (Vec<_>, Vec<_>) = shards
+            .iter()
+            .enumerate()
+            .partition(|(j, _)| !bad.contains(j));
+        let good = good.into_iter().map(|(_, s)| s.clone()).collect::<Vec<_>>();
+        let dealer = Self::interpolate(&good[..threshold])?;
+        *dealer.issued.lock().expect("issued x values lock poisoned") = xs;
 
-                let points = xs.zip(ys).collect::<Vec<_>>();
-                GfBarycentric::recover(threshold - 1, points.as_slice())
-                    .map(|poly| Box::new(poly) as Box<dyn EvaluablePolynomial>)
+        let consistency = ShardConsistency {
+            consistent: good.iter().map(Shard::id).collect(),
+            inconsistent: corrupted.into_iter().map(|(_, s)| s.id()).collect(),
+        };
+        Ok((dealer, consistency))
+    }
+
+    /// Recover as much of the secret as possible from *at least* `threshold`
+    /// unique `PartialShard`s, some of which may be missing chunks.
+    ///
+    /// Each chunk of the secret is recovered independently, from the first
+    /// `threshold` shards which have that chunk. Chunks which are missing from
+    /// too many shards are left as zeroes, and their byte ranges are reported
+    /// by `PartialSecret::missing`, rather than failing the whole recovery.
+    ///
+    /// No `Dealer` can be reconstructed from incomplete polynomials, so the
+    /// recovered secret cannot be cross-checked. If every chunk is readable
+    /// from `threshold` shards, `Dealer::recover_verified` should be
+    /// preferred.
+    pub fn recover_partial<S: AsRef<[PartialShard]>>(shards: S) -> Result<PartialSecret, Error> {
+        let shards = shards.as_ref();
+        let full = shards
+            .iter()
+            .map(|partial| partial.shard.clone())
+            .collect::<Vec<_>>();
+        Self::check_shards(&full)?;
+        let threshold = full[0].threshold() as usize;
+        if shards.len() < threshold {
+            return Err(Error::WrongShardCount {
+                needed: threshold as u32,
+                given: shards.len(),
+            });
+        }
+        Self::check_unique(&full)?;
+        let secret_len = full[0].secret_len;
+        let field_size = full[0].field_size();
+
+        let chunks = (0..full[0].ys.len())
+            .into_par_iter()
+            .map(|i| {
+                let present = shards
+                    .iter()
+                    .filter(|partial| !partial.is_missing(i))
+                    .take(threshold)
+                    .map(|partial| &partial.shard)
+                    .collect::<Vec<_>>();
+                if present.len() < threshold {
+                    return Ok(None);
+                }
+                let chunk = match field_size {
+                    FieldSize::Bits32 => {
+                        let points = Zeroizing::new(
+                            present
+                                .iter()
+                                .map(|s| match &s.ys {
+                                    ShardYs::Bits32(ys) => (s.x, ys[i]),
+                                    _ => unreachable!("field sizes checked above"),
+                                })
+                                .collect::<Vec<_>>(),
+                        );
+                        let poly = GfBarycentric::recover(threshold as u32 - 1, points.as_slice())?;
+                        poly.constant().inner().to_le_bytes().to_vec()
+                    }
+                    FieldSize::Bits64 => {
+                        let points = Zeroizing::new(
+                            present
+                                .iter()
+                                .map(|s| match &s.ys {
+                                    ShardYs::Bits64(ys) => (s.x.into(), ys[i]),
+                                    _ => unreachable!("field sizes checked above"),
+                                })
+                                .collect::<Vec<_>>(),
+                        );
+                        let poly =
+                            Gf64Barycentric::recover(threshold as u32 - 1, points.as_slice())?;
+                        poly.constant().inner().to_le_bytes().to_vec()
+                    }
+                    FieldSize::Bits128 => {
+                        let points = Zeroizing::new(
+                            present
+                                .iter()
+                                .map(|s| match &s.ys {
+                                    ShardYs::Bits128(ys) => (s.x.into(), ys[i]),
+                                    _ => unreachable!("field sizes checked above"),
+                                })
+                                .collect::<Vec<_>>(),
+                        );
+                        let poly =
+                            Gf128Barycentric::recover(threshold as u32 - 1, points.as_slice())?;
+                        poly.constant().inner().to_le_bytes().to_vec()
+                    }
+                };
+                Ok(Some(Zeroizing::new(chunk)))
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        let elem_len = field_size.elem_len();
+        let mut secret = Vec::with_capacity(chunks.len() * elem_len);
+        let mut missing: Vec<Range<usize>> = Vec::new();
+        for chunk in chunks {
+            match chunk {
+                Some(chunk) => secret.extend_from_slice(&chunk),
+                None => {
+                    let start = secret.len();
+                    secret.resize(start + elem_len, 0);
+                    let end = secret.len().min(secret_len);
+                    // Merge adjacent missing chunks into a single range.
+                    match missing.last_mut() {
+                        Some(last) if last.end == start => last.end = end,
+                        _ => missing.push(start..end),
+                    }
+                }
+            }
+        }
+        secret.truncate(secret_len);
+        Ok(PartialSecret { secret, missing })
+    }
+
+    // Upper bound on the number of subsets tried by recover_verified, to
+    // avoid a combinatorial explosion with large numbers of bad shards.
+    const MAX_CROSS_CHECK_SUBSETS: usize = 64;
+
+    // The subsets of threshold (of n) shards tried by recover_verified. The
+    // first n subsets are windows of consecutive shards (wrapping around), so
+    // that every shard is left out of at least one subset even when there are
+    // too many subsets to try them all. They are followed by the remaining
+    // subsets, up to MAX_CROSS_CHECK_SUBSETS in total.
+    fn cross_check_subsets(n: usize, threshold: usize) -> impl Iterator<Item = Vec<usize>> {
+        (0..n)
+            .map(move |start| {
+                (start..start + threshold)
+                    .map(|i| i % n)
+                    .sorted()
+                    .collect::<Vec<_>>()
             })
-            .collect::<Result<Vec<_>, _>>()?;
+            .chain((0..n).combinations(threshold))
+            .unique()
+            .take(Self::MAX_CROSS_CHECK_SUBSETS.max(n))
+    }
+
+    // Make sure the shards are non-empty and agree on their parameters.
+    fn check_shards(shards: &[Shard]) -> Result<(), Error> {
+        let first = shards.first().ok_or(Error::NoShards)?;
+
+        let threshold = first.threshold();
+        let polys_len = first.ys.len();
+        let secret_len = first.secret_len;
+        let field_size = first.field_size();
+
+        if threshold == 0 {
+            return Err(Error::InconsistentShards("threshold must be non-zero"));
+        }
+        if polys_len != secret_len.div_ceil(field_size.elem_len()) {
+            return Err(Error::InconsistentShards(
+                "shard data length doesn't match secret length",
+            ));
+        }
+        for shard in shards {
+            if shard.field_size() != field_size {
+                return Err(Error::InconsistentShards(
+                    "shards have different field sizes",
+                ));
+            }
+            if shard.threshold() != threshold {
+                return Err(Error::InconsistentShards(
+                    "shards have different thresholds",
+                ));
+            }
+            if shard.ys.len() != polys_len {
+                return Err(Error::InconsistentShards(
+                    "shards have different data lengths",
+                ));
+            }
+            if shard.secret_len != secret_len {
+                return Err(Error::InconsistentShards(
+                    "shards have different secret lengths",
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    // Make sure no two shards have the same x value, returning the set of x
+    // values.
+    fn check_unique(shards: &[Shard]) -> Result<HashSet<GfElem>, Error> {
+        let mut xs = HashSet::new();
+        if let Some(shard) = shards.iter().find(|shard| !xs.insert(shard.x)) {
+            return Err(Error::DuplicateX(shard.x.into()));
+        }
+        Ok(xs)
+    }
+
+    // Interpolate the polynomials from a checked set of exactly threshold
+    // unique shards.
+    fn interpolate(shards: &[Shard]) -> Result<Self, Error> {
+        let first = &shards[0];
+        let threshold = first.threshold();
+        let polys_len = first.ys.len();
+        let secret_len = first.secret_len;
+        let field_size = first.field_size();
 
-        Ok(Self {
+        let progress = Tracker::start(Stage::Recovering, polys_len);
+        let polys = match field_size {
+            FieldSize::Bits32 => Polynomials::Bits32(
+                (0..polys_len)
+                    .into_par_iter()
+                    .map(|i| {
+                        let points = Zeroizing::new(
+                            shards
+                                .iter()
+                                .map(|s| match &s.ys {
+                                    ShardYs::Bits32(ys) => (s.x, ys[i]),
+                                    _ => unreachable!("field sizes checked above"),
+                                })
+                                .collect::<Vec<_>>(),
+                        );
+                        let poly = GfBarycentric::recover(threshold - 1, points.as_slice())
+                            .map(|poly| Box::new(poly) as Box<dyn EvaluablePolynomial>);
+                        progress.step();
+                        poly
+                    })
+                    .collect::<Result<Vec<_>, _>>()?,
+            ),
+            FieldSize::Bits64 => Polynomials::Bits64(
+                (0..polys_len)
+                    .into_par_iter()
+                    .map(|i| {
+                        let points = Zeroizing::new(
+                            shards
+                                .iter()
+                                .map(|s| match &s.ys {
+                                    ShardYs::Bits64(ys) => (s.x.into(), ys[i]),
+                                    _ => unreachable!("field sizes checked above"),
+                                })
+                                .collect::<Vec<_>>(),
+                        );
+                        let poly = Gf64Barycentric::recover(threshold - 1, points.as_slice())
+                            .map(|poly| Box::new(poly) as Box<dyn EvaluablePolynomial64>);
+                        progress.step();
+                        poly
+                    })
+                    .collect::<Result<Vec<_>, _>>()?,
+            ),
+            FieldSize::Bits128 => Polynomials::Bits128(
+                (0..polys_len)
+                    .into_par_iter()
+                    .map(|i| {
+                        let points = Zeroizing::new(
+                            shards
+                                .iter()
+                                .map(|s| match &s.ys {
+                                    ShardYs::Bits128(ys) => (s.x.into(), ys[i]),
+                                    _ => unreachable!("field sizes checked above"),
+                                })
+                                .collect::<Vec<_>>(),
+                        );
+                        let poly = Gf128Barycentric::recover(threshold - 1, points.as_slice())
+                            .map(|poly| Box::new(poly) as Box<dyn EvaluablePolynomial128>);
+                        progress.step();
+                        poly
+                    })
+                    .collect::<Result<Vec<_>, _>>()?,
+            ),
+        };
+
+        let dealer = Self {
             polys,
             secret_len,
             threshold,
-        })
+            // The shards we recovered from already exist.
+            issued: Arc::new(Mutex::new(shards.iter().map(|s| s.x).collect())),
+        };
+        dealer.validate()?;
+        Ok(dealer)
+    }
+}
+
+/// The result of cross-verifying a set of `Shard`s with
+/// `Dealer::recover_verified`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ShardConsistency {
+    consistent: Vec<ShardId>,
+    inconsistent: Vec<ShardId>,
+}
+
+impl ShardConsistency {
+    /// Identifiers of the shards which agreed with the recovered secret.
+    pub fn consistent(&self) -> &[ShardId] {
+        &self.consistent
     }
+
+    /// Identifiers of the shards which disagreed with the recovered secret,
+    /// and are thus either forged or corrupted.
+    pub fn inconsistent(&self) -> &[ShardId] {
+        &self.inconsistent
+    }
+
+    /// Returns whether every shard agreed with the recovered secret.
+    pub fn is_consistent(&self) -> bool {
+        self.inconsistent.is_empty()
+    }
+
+    // Merge the results of cross-verifying another set of shards.
+    pub(super) fn extend(&mut self, other: ShardConsistency) {
+        self.consistent.extend(other.consistent);
+        self.inconsistent.extend(other.inconsistent);
+    }
+}
+
+/// A secret recovered with `Dealer::recover_partial`, which may be missing
+/// some byte ranges.
+#[derive(Clone, PartialEq, Eq)]
+pub struct PartialSecret {
+    secret: Vec<u8>,
+    missing: Vec<Range<usize>>,
+}
+
+impl fmt::Debug for PartialSecret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PartialSecret")
+            .field("len", &self.secret.len())
+            .field("missing", &self.missing)
+            .finish()
+    }
+}
+
+impl Drop for PartialSecret {
+    fn drop(&mut self) {
+        self.secret.zeroize();
+    }
+}
+
+impl PartialSecret {
+    /// The recovered secret. Bytes within the `missing` ranges are zero.
+    pub fn secret(&self) -> &[u8] {
+        &self.secret
+    }
+
+    /// The (non-overlapping, sorted) byte ranges of the secret which could not
+    /// be recovered.
+    pub fn missing(&self) -> &[Range<usize>] {
+        &self.missing
+    }
+
+    /// Returns whether the entire secret was recovered.
+    pub fn is_complete(&self) -> bool {
+        self.missing.is_empty()
+    }
+}
+
+/// Allocate `n` distinct `x` values in `1..=max` which are not in `issued`,
+/// starting the search for each at `start()` (so that a random `start` gives
+/// random `x` values).
+pub(super) fn allocate_xs<F: FnMut() -> GfElemPrimitive>(
+    issued: &mut HashSet<GfElem>,
+    n: u32,
+    max: GfElemPrimitive,
+    mut start: F,
+) -> Result<Vec<GfElem>, Error> {
+    if issued.len().saturating_add(n as usize) > max as usize {
+        return Err(Error::FieldExhausted(max));
+    }
+    Ok((0..n)
+        .map(|_| {
+            // Probe linearly (wrapping around and skipping zero) rather than
+            // re-rolling, so that allocation terminates promptly even when
+            // nearly every x value has been issued.
+            let mut x = start() % max;
+            loop {
+                x = x % max + 1;
+                if issued.insert(GfElem::from(x)) {
+                    return GfElem::from(x);
+                }
+            }
+        })
+        .collect())
 }
 
 #[cfg(test)]
@@ -183,7 +1067,7 @@ mod test {
         if n < 1 || n > 4096 {
             return TestResult::discard();
         }
-        let dealer = Dealer::new(n.into(), &secret);
+        let dealer = Dealer::new(n.into(), &secret).unwrap();
         TestResult::from_bool(secret == dealer.secret())
     }
 
@@ -203,10 +1087,10 @@ mod test {
             return TestResult::discard();
         }
 
-        let dealer = Dealer::new(n.into(), &secret);
+        let dealer = Dealer::new(n.into(), &secret).unwrap();
         let shards = (0..(n - 1))
             .map(|_| {
-                let mut shard = dealer.next_shard();
+                let mut shard = dealer.next_shard().unwrap();
                 shard.threshold -= 1;
                 // Ensure shard IDs are always ID_LENGTH.
                 assert_eq!(shard.id().len(), Shard::ID_LENGTH);
@@ -228,10 +1112,10 @@ mod test {
             return TestResult::discard();
         }
 
-        let dealer = Dealer::new(n.into(), &secret);
+        let dealer = Dealer::new(n.into(), &secret).unwrap();
         let shards = (0..n)
             .map(|_| {
-                let shard = dealer.next_shard();
+                let shard = dealer.next_shard().unwrap();
                 // Ensure shard IDs are always ID_LENGTH.
                 assert_eq!(shard.id().len(), Shard::ID_LENGTH);
                 shard
@@ -259,10 +1143,10 @@ mod test {
         if n < 2 || n > RECOVER_UPPER || secret.len() < 1 || test_xs.contains(&GfElem::ZERO) {
             return TestResult::discard();
         }
-        let dealer = Dealer::new(n.into(), secret);
+        let dealer = Dealer::new(n.into(), secret).unwrap();
         let shards = (0..(n - 1))
             .map(|_| {
-                let mut shard = dealer.next_shard();
+                let mut shard = dealer.next_shard().unwrap();
                 shard.threshold -= 1;
                 // Ensure shard IDs are always ID_LENGTH.
                 assert_eq!(shard.id().len(), Shard::ID_LENGTH);
@@ -280,8 +1164,8 @@ mod test {
 
         println!(
             "GF(0) => dealer: {:?}, recovered_dealer: {:?}",
-            dealer.shard(GfElem::ZERO),
-            recovered_dealer.shard(GfElem::ZERO)
+            dealer.shard(GfElem::ZERO).ok(),
+            recovered_dealer.shard(GfElem::ZERO).ok()
         );
 
         TestResult::from_bool(
@@ -291,7 +1175,8 @@ mod test {
                     // If it is a shard x value then it will match, otherwise it
                     // should not match (because it's the wrong polynomial).
                     .all(|&x| {
-                        shard_xs.contains(&x) == (dealer.shard(x) == recovered_dealer.shard(x))
+                        shard_xs.contains(&x)
+                            == (dealer.shard(x).ok() == recovered_dealer.shard(x).ok())
                     }),
         )
     }
@@ -307,10 +1192,10 @@ mod test {
         if n < 1 || n > RECOVER_UPPER || test_xs.contains(&GfElem::ZERO) {
             return TestResult::discard();
         }
-        let dealer = Dealer::new(n.into(), secret);
+        let dealer = Dealer::new(n.into(), secret).unwrap();
         let shards = (0..n)
             .map(|_| {
-                let shard = dealer.next_shard();
+                let shard = dealer.next_shard().unwrap();
                 // Ensure shard IDs are always ID_LENGTH.
                 assert_eq!(shard.id().len(), Shard::ID_LENGTH);
                 shard
@@ -322,7 +1207,524 @@ mod test {
             dealer.secret() == recovered_dealer.secret()
                 && test_xs
                     .iter()
-                    .all(|&x| dealer.shard(x) == recovered_dealer.shard(x)),
+                    .all(|&x| dealer.shard(x).ok() == recovered_dealer.shard(x).ok()),
+        )
+    }
+
+    #[test]
+    fn next_shard_unique() {
+        let dealer = Dealer::new(3, b"secret").unwrap();
+        let clone = dealer.clone();
+        let mut xs = (0..500)
+            .map(|i| match i % 2 {
+                0 => dealer.next_shard().unwrap().x,
+                _ => clone.next_shard().unwrap().x,
+            })
+            .collect::<Vec<_>>();
+        xs.sort_by_key(|&x| GfElemPrimitive::from(x));
+        xs.dedup();
+        assert_eq!(xs.len(), 500);
+
+        // Shards recovered from are already issued.
+        let shards = (0..3)
+            .map(|_| dealer.next_shard().unwrap())
+            .collect::<Vec<_>>();
+        let recovered = Dealer::recover(&shards).unwrap();
+        for shard in shards {
+            assert!(recovered.issued.lock().unwrap().contains(&shard.x));
+        }
+    }
+
+    #[test]
+    fn next_shards_unique() {
+        let dealer = Dealer::new(3, b"secret").unwrap();
+        let issued = dealer.next_shards(5).unwrap();
+        let batch = dealer.next_shards(100).unwrap();
+        let mut xs = issued
+            .iter()
+            .chain(batch.iter())
+            .map(|shard| GfElemPrimitive::from(shard.x))
+            .collect::<Vec<_>>();
+        xs.sort();
+        xs.dedup();
+        assert_eq!(xs.len(), 105);
+        assert!(dealer.next_shards(0).unwrap().is_empty());
+        assert_eq!(Dealer::recover(&batch[..3]).unwrap().secret(), b"secret");
+    }
+
+    #[test]
+    fn zero_threshold() {
+        assert!(matches!(
+            Dealer::new(0, b"secret"),
+            Err(Error::ZeroThreshold)
+        ));
+        assert!(matches!(
+            Dealer::new_with_rng(0, b"secret", &mut rand::thread_rng()),
+            Err(Error::ZeroThreshold)
+        ));
+        assert!(matches!(
+            Dealer::new(2, b"secret").unwrap().redeal(0),
+            Err(Error::ZeroThreshold)
+        ));
+    }
+
+    #[test]
+    fn new_with_rng_deterministic() {
+        use rand::SeedableRng;
+        use rand_chacha::ChaCha20Rng;
+
+        for field_size in [FieldSize::Bits32, FieldSize::Bits64, FieldSize::Bits128] {
+            let deal = |seed| {
+                let mut rng = ChaCha20Rng::seed_from_u64(seed);
+                let dealer =
+                    Dealer::new_with_field_size_and_rng(3, b"secret data", field_size, &mut rng)
+                        .unwrap();
+                let mut shards = vec![dealer.next_shard_with_rng(&mut rng).unwrap()];
+                shards.extend(dealer.next_shards_with_rng(4, &mut rng).unwrap());
+                shards
+            };
+            let shards = deal(1);
+            assert_eq!(shards, deal(1));
+            assert_ne!(shards, deal(2));
+            assert_eq!(
+                Dealer::recover(&shards[2..]).unwrap().secret(),
+                b"secret data"
+            );
+        }
+
+        let mut rng = ChaCha20Rng::seed_from_u64(0);
+        let dealer = Dealer::new_with_rng(2, b"secret", &mut rng).unwrap();
+        let shards = dealer.next_shards_with_rng(2, &mut rng).unwrap();
+        assert_eq!(Dealer::recover(&shards).unwrap().secret(), b"secret");
+    }
+
+    #[test]
+    fn recover_partial() {
+        for field_size in [FieldSize::Bits32, FieldSize::Bits64, FieldSize::Bits128] {
+            let secret = (0..60u8).collect::<Vec<_>>();
+            let dealer = Dealer::new_with_field_size(3, &secret, field_size).unwrap();
+            let shards = dealer
+                .next_shards(4)
+                .unwrap()
+                .into_iter()
+                .map(PartialShard::new)
+                .collect::<Vec<_>>();
+            let elem_len = field_size.elem_len();
+            let last = shards[0].num_chunks() - 1;
+
+            // With every chunk readable, the whole secret is recovered.
+            let recovered = Dealer::recover_partial(&shards[..3]).unwrap();
+            assert!(recovered.is_complete());
+            assert_eq!(recovered.secret(), secret);
+
+            // Chunks missing from two of four shards are unrecoverable, but
+            // the rest of the secret is intact.
+            let mut damaged = shards.clone();
+            damaged[0]
+                .mark_missing(1)
+                .mark_missing(2)
+                .mark_missing(last);
+            damaged[1].mark_missing(1).mark_missing(last);
+            let recovered = Dealer::recover_partial(&damaged).unwrap();
+            assert_eq!(
+                recovered.missing(),
+                [elem_len..2 * elem_len, last * elem_len..secret.len()]
+            );
+            for (i, (&got, &want)) in recovered.secret().iter().zip(&secret).enumerate() {
+                match recovered.missing().iter().any(|r| r.contains(&i)) {
+                    true => assert_eq!(got, 0),
+                    false => assert_eq!(got, want),
+                }
+            }
+
+            // Adjacent missing chunks are reported as a single range.
+            let mut damaged = shards[..3].to_vec();
+            damaged[0].mark_missing(0).mark_missing(1);
+            let recovered = Dealer::recover_partial(&damaged).unwrap();
+            assert_eq!(recovered.missing().len(), 1);
+            assert_eq!(recovered.missing()[0], 0..2 * elem_len);
+            assert_eq!(&recovered.secret()[2 * elem_len..], &secret[2 * elem_len..]);
+
+            assert!(matches!(
+                Dealer::recover_partial(&shards[..2]),
+                Err(Error::WrongShardCount { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn allocate_xs_exhausted() {
+        const MAX: GfElemPrimitive = 16;
+        let mut issued = HashSet::new();
+        let mut starts = (0..).map(|i| i * 7);
+        let xs = allocate_xs(&mut issued, 10, MAX, || starts.next().unwrap()).unwrap();
+        assert!(xs.iter().all(|&x| x != GfElem::ZERO));
+        assert!(xs.iter().all(|&x| GfElemPrimitive::from(x) <= MAX));
+
+        // Requests which cannot be satisfied don't issue anything.
+        assert!(matches!(
+            allocate_xs(&mut issued, 7, MAX, || 0),
+            Err(Error::FieldExhausted(MAX))
+        ));
+        assert_eq!(issued.len(), 10);
+
+        allocate_xs(&mut issued, 6, MAX, || 0).unwrap();
+        assert_eq!(issued.len(), MAX as usize);
+        assert!(matches!(
+            allocate_xs(&mut issued, 1, MAX, || 0),
+            Err(Error::FieldExhausted(MAX))
+        ));
+    }
+
+    #[test]
+    fn shard_at_validation() {
+        let dealer = Dealer::new(2, b"secret").unwrap();
+        assert!(matches!(dealer.shard_at(GfElem::ZERO), Err(Error::ZeroX)));
+
+        let shards = (1..=3)
+            .map(|n| dealer.shard_at(GfElem::from(n)).unwrap())
+            .collect::<Vec<_>>();
+        assert!(matches!(
+            dealer.clone().shard_at(GfElem::from(2)),
+            Err(Error::DuplicateX(2))
+        ));
+        // Randomly allocated shards never collide with chosen ones.
+        for shard in dealer.next_shards(50).unwrap() {
+            assert!(!shards.iter().any(|s| s.x == shard.x));
+        }
+
+        // Re-dealing the same secret gives shards at the same coordinates.
+        let redealt = Dealer::new(2, b"secret").unwrap();
+        let redealt_shards = (1..=2)
+            .map(|n| redealt.shard_at(GfElem::from(n)).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            redealt_shards.iter().map(Shard::id).collect::<Vec<_>>(),
+            shards[..2].iter().map(Shard::id).collect::<Vec<_>>()
+        );
+        assert_eq!(Dealer::recover(redealt_shards).unwrap().secret(), b"secret");
+
+        // A failed shard_at does not leave its x value marked as issued.
+        let mut corrupted = dealer.clone();
+        if let Polynomials::Bits32(polys) = &mut corrupted.polys {
+            polys.pop();
+        }
+        assert!(matches!(
+            corrupted.shard_at(GfElem::from(4)),
+            Err(Error::Validation(_))
+        ));
+        dealer.shard_at(GfElem::from(4)).unwrap();
+    }
+
+    #[test]
+    fn validate_corrupted_dealer() {
+        let dealer = Dealer::new(2, b"secret").unwrap();
+        dealer.validate().unwrap();
+        assert!(dealer.shard(GfElem::ZERO).is_err());
+
+        // A missing polynomial.
+        let mut corrupted = dealer.clone();
+        if let Polynomials::Bits32(polys) = &mut corrupted.polys {
+            polys.pop();
+        }
+        assert!(matches!(
+            corrupted.next_shard(),
+            Err(Error::Validation(report)) if report.violations() == [
+                Violation::PolynomialCount {
+                    expected: 2,
+                    found: 1
+                }
+            ]
+        ));
+
+        // A polynomial whose value at x is the secret chunk itself.
+        let mut corrupted = Dealer::new(2, b"secret").unwrap();
+        if let Polynomials::Bits32(polys) = &mut corrupted.polys {
+            let constant = polys[1].constant();
+            polys[1] =
+                Box::new(GfPolynomial::from_coefficients(vec![constant, GfElem::ZERO]).unwrap());
+        }
+        assert!(matches!(
+            corrupted.shard_at(GfElem::from(7)),
+            Err(Error::Validation(report))
+                if report.violations() == [Violation::ShardRevealsSecret { x: 7, chunk: 1 }]
+        ));
+    }
+
+    #[test]
+    fn failed_issue_releases_xs() {
+        let dealer = Dealer::new(2, b"secret").unwrap();
+        let issued = || dealer.issued.lock().unwrap().clone();
+
+        // Clones share the issued x values, so failures of a corrupted clone
+        // must not leave x values marked as issued for the original.
+        let mut missing = dealer.clone();
+        if let Polynomials::Bits32(polys) = &mut missing.polys {
+            polys.pop();
+        }
+        // A polynomial whose value at every x is the secret chunk itself.
+        let mut revealing = dealer.clone();
+        if let Polynomials::Bits32(polys) = &mut revealing.polys {
+            let constant = polys[1].constant();
+            polys[1] =
+                Box::new(GfPolynomial::from_coefficients(vec![constant, GfElem::ZERO]).unwrap());
+        }
+
+        for corrupted in [&missing, &revealing] {
+            assert!(matches!(
+                corrupted.next_shards(5),
+                Err(Error::Validation(_))
+            ));
+            assert!(matches!(
+                corrupted.shard(GfElem::from(3)),
+                Err(Error::Validation(_))
+            ));
+            assert!(matches!(
+                corrupted.shard_at(GfElem::from(4)),
+                Err(Error::Validation(_))
+            ));
+            assert!(issued().is_empty());
+        }
+        dealer.shard_at(GfElem::from(3)).unwrap();
+        dealer.shard_at(GfElem::from(4)).unwrap();
+
+        // An x value which was already issued stays issued.
+        assert!(revealing.shard(GfElem::from(3)).is_err());
+        assert_eq!(issued(), HashSet::from([GfElem::from(3), GfElem::from(4)]));
+        assert_eq!(dealer.next_shards(3).unwrap().len(), 3);
+        assert_eq!(issued().len(), 5);
+    }
+
+    #[test]
+    fn recover_invalid_shards() {
+        let dealer = Dealer::new(3, b"secret").unwrap();
+        let shards = dealer.next_shards(4).unwrap();
+
+        assert!(matches!(
+            Dealer::recover(Vec::<Shard>::new()),
+            Err(Error::NoShards)
+        ));
+        assert!(matches!(
+            Dealer::recover(&shards[..2]),
+            Err(Error::WrongShardCount {
+                needed: 3,
+                given: 2
+            })
+        ));
+        assert!(matches!(
+            Dealer::recover(&shards),
+            Err(Error::WrongShardCount {
+                needed: 3,
+                given: 4
+            })
+        ));
+        assert!(matches!(
+            Dealer::recover([shards[0].clone(), shards[1].clone(), shards[0].clone()]),
+            Err(Error::DuplicateX(_))
+        ));
+
+        let mut bad = shards[..3].to_vec();
+        bad[1].threshold = 2;
+        assert!(matches!(
+            Dealer::recover(&bad),
+            Err(Error::InconsistentShards(_))
+        ));
+        let mut bad = shards[..3].to_vec();
+        if let ShardYs::Bits32(ys) = &mut bad[2].ys {
+            ys.pop();
+        }
+        assert!(matches!(
+            Dealer::recover(&bad),
+            Err(Error::InconsistentShards(_))
+        ));
+        let mut bad = shards[..3].to_vec();
+        bad[0].secret_len += 100;
+        assert!(matches!(
+            Dealer::recover(&bad),
+            Err(Error::InconsistentShards(_))
+        ));
+        let mut bad = shards[..3].to_vec();
+        bad.iter_mut().for_each(|shard| shard.threshold = 0);
+        assert!(matches!(
+            Dealer::recover(&bad),
+            Err(Error::InconsistentShards(_))
+        ));
+
+        assert_eq!(Dealer::recover(&shards[1..]).unwrap().secret(), b"secret");
+
+        let dealer64 = Dealer::new_with_field_size(3, b"secret", FieldSize::Bits64).unwrap();
+        let mut mixed = shards[..2].to_vec();
+        mixed.push(dealer64.next_shard().unwrap());
+        assert!(matches!(
+            Dealer::recover(&mixed),
+            Err(Error::InconsistentShards(_))
+        ));
+    }
+
+    #[test]
+    fn recover_verified() {
+        let dealer = Dealer::new(3, b"cross-verified secret").unwrap();
+        let shards = dealer.next_shards(5).unwrap();
+
+        // Exactly threshold shards cannot be cross-verified, but are trivially
+        // consistent.
+        let (recovered, consistency) = Dealer::recover_verified(&shards[..3]).unwrap();
+        assert_eq!(recovered.secret(), b"cross-verified secret");
+        assert!(consistency.is_consistent());
+        assert!(matches!(
+            Dealer::recover_verified(&shards[..2]),
+            Err(Error::WrongShardCount { .. })
+        ));
+
+        let (recovered, consistency) = Dealer::recover_verified(&shards).unwrap();
+        assert_eq!(recovered.secret(), b"cross-verified secret");
+        assert!(consistency.is_consistent());
+        assert_eq!(consistency.consistent().len(), 5);
+
+        // Corrupt one of the shards used for the first interpolation.
+        let mut bad = shards.clone();
+        if let ShardYs::Bits32(ys) = &mut bad[1].ys {
+            ys[0] += GfElem::ONE;
+        }
+        let (recovered, consistency) = Dealer::recover_verified(&bad).unwrap();
+        assert_eq!(recovered.secret(), b"cross-verified secret");
+        assert_eq!(consistency.inconsistent(), [bad[1].id()]);
+        assert_eq!(consistency.consistent().len(), 4);
+        // The recovered dealer must not re-issue any of the existing shards.
+        let issued = recovered.issued.lock().unwrap();
+        assert!(bad.iter().all(|shard| issued.contains(&shard.x)));
+        drop(issued);
+
+        // With only one surplus shard, a bad shard can be detected but not
+        // identified.
+        assert!(matches!(
+            Dealer::recover_verified(&bad[..4]),
+            Err(Error::InconsistentShards(_))
+        ));
+    }
+
+    #[test]
+    fn recover_verified_many_subsets() {
+        // Every one of the first MAX_CROSS_CHECK_SUBSETS subsets (in
+        // lexicographic order) of 3-of-13 shards contains the first shard.
+        let dealer = Dealer::new(3, b"cross-verified secret").unwrap();
+        let mut shards = dealer.next_shards(13).unwrap();
+        if let ShardYs::Bits32(ys) = &mut shards[0].ys {
+            ys[0] += GfElem::ONE;
+        }
+
+        let (recovered, consistency) = Dealer::recover_verified(&shards).unwrap();
+        assert_eq!(recovered.secret(), b"cross-verified secret");
+        assert_eq!(consistency.inconsistent(), [shards[0].id()]);
+        assert_eq!(consistency.consistent().len(), 12);
+    }
+
+    #[test]
+    fn recover_corrected() {
+        for field_size in [FieldSize::Bits32, FieldSize::Bits64, FieldSize::Bits128] {
+            let secret = b"a secret which spans several polynomials";
+            let dealer = Dealer::new_with_field_size(3, secret, field_size).unwrap();
+            let shards = dealer.next_shards(7).unwrap();
+
+            let (recovered, consistency) = Dealer::recover_corrected(&shards).unwrap();
+            assert_eq!(recovered.secret(), secret);
+            assert!(consistency.is_consistent());
+
+            // Corrupt two shards (the maximum for 3-of-7) in different
+            // polynomials.
+            let mut bad = shards.clone();
+            for (j, i) in [(0, 0), (5, 2)] {
+                match &mut bad[j].ys {
+                    ShardYs::Bits32(ys) => ys[i] += GfElem::from(0x10),
+                    ShardYs::Bits64(ys) => ys[i] += Gf64Elem::from(0x10),
+                    ShardYs::Bits128(ys) => ys[i] += Gf128Elem::from(0x10),
+                }
+            }
+            let (recovered, consistency) = Dealer::recover_corrected(&bad).unwrap();
+            assert_eq!(recovered.secret(), secret);
+            assert_eq!(consistency.inconsistent(), [bad[0].id(), bad[5].id()]);
+            assert_eq!(consistency.consistent().len(), 5);
+
+            // A third corrupted shard is too many.
+            match &mut bad[3].ys {
+                ShardYs::Bits32(ys) => ys[1] += GfElem::from(0x10),
+                ShardYs::Bits64(ys) => ys[1] += Gf64Elem::from(0x10),
+                ShardYs::Bits128(ys) => ys[1] += Gf128Elem::from(0x10),
+            }
+            assert!(matches!(
+                Dealer::recover_corrected(&bad),
+                Err(Error::Uncorrectable { max_errors: 2 })
+            ));
+        }
+    }
+
+    #[test]
+    fn redeal() {
+        for field_size in [FieldSize::Bits32, FieldSize::Bits64, FieldSize::Bits128] {
+            let secret = b"redealt secret";
+            let old = Dealer::new_with_field_size(2, secret, field_size).unwrap();
+            let old_shards = old.next_shards(3).unwrap();
+
+            let recovered = Dealer::recover(&old_shards[..2]).unwrap();
+            let new = recovered.redeal(4).unwrap();
+            assert_eq!(new.threshold(), 4);
+            assert_eq!(new.secret(), secret);
+
+            let new_shards = new.next_shards(7).unwrap();
+            assert!(new_shards.iter().all(|s| s.field_size() == field_size));
+            assert_eq!(Dealer::recover(&new_shards[3..]).unwrap().secret(), secret);
+            assert!(Dealer::recover(&new_shards[..3]).is_err());
+
+            // Old shards cannot be mixed into the new quorum.
+            assert!(Dealer::recover([&new_shards[..3], &old_shards[..1]].concat()).is_err());
+        }
+    }
+
+    #[quickcheck]
+    fn gf64_roundtrip(n: u8, secret: Vec<u8>) -> TestResult {
+        if n < 1 || n > RECOVER_UPPER {
+            return TestResult::discard();
+        }
+        let dealer = Dealer::new_with_field_size(n.into(), &secret, FieldSize::Bits64).unwrap();
+        let shards = dealer.next_shards(n.into()).unwrap();
+        assert!(shards
+            .iter()
+            .all(|shard| shard.field_size() == FieldSize::Bits64
+                && shard.ys.len() == secret.len().div_ceil(8)));
+
+        let recovered = Dealer::recover(&shards).unwrap();
+        let extra = recovered.next_shard().unwrap();
+        TestResult::from_bool(
+            recovered.secret() == secret
+                && dealer.shard(extra.x).unwrap() == extra
+                && Dealer::recover([&shards[1..], &[extra]].concat())
+                    .unwrap()
+                    .secret()
+                    == secret,
+        )
+    }
+
+    #[quickcheck]
+    fn gf128_roundtrip(n: u8, secret: Vec<u8>) -> TestResult {
+        if n < 1 || n > RECOVER_UPPER {
+            return TestResult::discard();
+        }
+        let dealer = Dealer::new_with_field_size(n.into(), &secret, FieldSize::Bits128).unwrap();
+        let shards = dealer.next_shards(n.into()).unwrap();
+        assert!(shards
+            .iter()
+            .all(|shard| shard.field_size() == FieldSize::Bits128
+                && shard.ys.len() == secret.len().div_ceil(16)));
+
+        let recovered = Dealer::recover(&shards).unwrap();
+        let extra = recovered.next_shard().unwrap();
+        TestResult::from_bool(
+            recovered.secret() == secret
+                && dealer.shard(extra.x).unwrap() == extra
+                && Dealer::recover([&shards[1..], &[extra]].concat())
+                    .unwrap()
+                    .secret()
+                    == secret,
         )
     }
 }
@@ -16,15 +16,62 @@
  * along with this program.  If not, see <https://www.gnu.org/licenses/>.
  */
 
+//! Arithmetic in `GF(2^32)` and polynomials over it.
+//!
+//! This is the finite-field layer underneath paperback's Shamir Secret Sharing
+//! implementation, and is exposed (as `paperback_core::gf`) for use by other
+//! tools. The API of this module follows semver: breaking changes to the types,
+//! operations or the field's characteristic polynomial will only be made in a
+//! new major version of `paperback-core`.
+//!
+//! ```
+//! use paperback_core::gf::{EvaluablePolynomial, GfBarycentric, GfElem, GfPolynomial};
+//!
+//! // f(x) = 3 + 2x + x^2
+//! let poly = GfPolynomial::from_coefficients(vec![
+//!     GfElem::from(3),
+//!     GfElem::from(2),
+//!     GfElem::ONE,
+//! ])
+//! .unwrap();
+//!
+//! // Interpolate the polynomial from three points on it.
+//! let points = [1, 2, 3]
+//!     .map(GfElem::from)
+//!     .map(|x| (x, poly.evaluate(x)));
+//! let recovered = GfBarycentric::recover(poly.degree(), points).unwrap();
+//! assert_eq!(recovered.constant(), GfElem::from(3));
+//! assert_eq!(GfPolynomial::recover(2, points).unwrap(), poly);
+//! ```
+//!
+//! ## Security ##
+//! Field arithmetic (addition, multiplication, inversion and division) is
+//! constant-time with respect to the values of the elements, with the
+//! exception that inverting (or dividing by) an element reveals whether it is
+//! zero. Exponentiation is constant-time with respect to the base but not the
+//! exponent, and `PartialEq` is not constant-time -- use
+//! [`ConstantTimeEq`](subtle::ConstantTimeEq) to compare secret elements.
+//! Polynomials wipe their coefficients (and [`GfBarycentric`] its points) when
+//! dropped.
+//!
+//! Interpolation is not constant-time with respect to the `x` values of the
+//! points, which are public in Shamir Secret Sharing.
+
 use std::{
     cmp, fmt, mem,
     ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub, SubAssign},
 };
 
+use crate::ErrorCode;
+
 use itertools::Itertools;
 use rand::{CryptoRng, RngCore};
+use subtle::{Choice, ConstantTimeEq};
+use zeroize::Zeroize;
 
+/// Errors returned by interpolation.
 #[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
 pub enum Error {
     #[error(
         "wrong number of points for interpolation: polynomial needs {needed} but was given {num_points}"
@@ -35,17 +82,32 @@ pub enum Error {
     NonInvertiblePoint,
 }
 
+impl Error {
+    /// The stable code of this error.
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Self::NumPointsMismatch { .. } => ErrorCode::new(100, "num-points-mismatch"),
+            Self::NonInvertiblePoint => ErrorCode::new(101, "non-invertible-point"),
+        }
+    }
+}
+
 /// Primitive uint type for GfElems.
+///
+/// This is only the in-memory (and serialised) representation of a field
+/// element, and integer arithmetic on it is *not* field arithmetic. Values
+/// should be converted to a [`GfElem`] before doing any maths with them, and
+/// non-element quantities (such as polynomial degrees or shard thresholds)
+/// are plain `u32`s rather than `GfElemPrimitive`s.
 pub type GfElemPrimitive = u32;
 
 /// A field element of `GF(2^32)`, with characteristic polynomial
 /// `x^32 + x^22 + x^2 + x^1 + 1`.
 ///
-/// This is a home-brew implementation of GF mathematics that hopefully runs in
-/// constant-enough time. It appears there are no clearly-good-to-use
-/// implementations of `GF(2^n)` fields (and `GF(2^8)` is not suitable for our
-/// purposes).
-// NOTE: PartialEq is not timing-safe.
+/// This is a home-brew implementation of GF mathematics. It appears there are
+/// no clearly-good-to-use implementations of `GF(2^n)` fields (and `GF(2^8)`
+/// is not suitable for our purposes).
+// NOTE: PartialEq is not timing-safe, use ConstantTimeEq for secret values.
 #[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
 pub struct GfElem(GfElemPrimitive);
 
@@ -69,6 +131,7 @@ impl GfElem {
     /// Multiplicative identity.
     pub const ONE: GfElem = GfElem(1);
 
+    /// Generate a uniformly random field element.
     pub fn new_rand<R: CryptoRng + RngCore + ?Sized>(r: &mut R) -> Self {
         Self(r.next_u32())
     }
@@ -81,6 +144,9 @@ impl GfElem {
         Self(v)
     }
 
+    /// Parse a field element from the first (up to) 4 bytes of `bytes` in
+    /// little-endian order, returning the remaining bytes. If fewer than 4
+    /// bytes are provided, the missing high-order bytes are treated as zero.
     pub fn from_bytes_partial(bytes: &[u8]) -> (Self, &[u8]) {
         let len = cmp::min(bytes.len(), mem::size_of::<GfElemPrimitive>());
 
@@ -95,18 +161,24 @@ impl GfElem {
         )
     }
 
+    /// Parse a field element from (up to) 4 bytes in little-endian order.
+    ///
+    /// # Panics
+    /// Panics if more than 4 bytes are provided.
     pub fn from_bytes<B: AsRef<[u8]>>(bytes: B) -> Self {
         let (elem, remain) = Self::from_bytes_partial(bytes.as_ref());
         assert!(remain.is_empty());
         elem
     }
 
+    /// Serialise the field element as 4 bytes in little-endian order.
     pub fn to_bytes(self) -> Vec<u8> {
         self.0.to_le_bytes().to_vec()
     }
 
-    // NOTE: Definitely not constant-time.
-    #[allow(dead_code)]
+    /// Raise the field element to the power `n`.
+    ///
+    /// This is constant-time with respect to `self`, but **not** `n`.
     pub fn pow(self, mut n: usize) -> Self {
         // Multiplication is not really cheap, so we optimise it by doing it
         // with an O(log(n)) worst case rather than the obvious O(n).
@@ -122,129 +194,92 @@ impl GfElem {
         result
     }
 
-    // Implementation of Euclidean division for GF(2) polynomials (a = bq + r),
-    // useful for computing the inverses with the Extended Euclid GCD Algorithm.
-    // Returns (q, r). If carry is set, a is treated like (a + x^32).
-    fn polynomial_div(
-        a: GfElemPrimitive,
-        b: GfElemPrimitive,
-        carry: bool,
-    ) -> (GfElemPrimitive, GfElemPrimitive) {
-        // Don't call me for division by zero. This code would loop forever.
-        assert_ne!(b, 0, "tried to divide by zero in internal function");
-
-        fn msb(p: GfElemPrimitive) -> isize {
-            // TODO: Use u32::BITS.
-            32 - (p.leading_zeros() as isize)
-        }
-
-        let (mut q, mut r) = (0, a);
-        let bmsb = msb(b);
-
-        // The "carry" is only used for the first EEA iteration where you're
-        // dividing Self::POLYNOMIAL.
-        if carry {
-            let shift = 33 /* 32 + 1 */ - bmsb;
-            if shift < 32 {
-                q ^= 1 << shift; // q += s
-                r ^= b << shift; // r -= s*b (= b*x^(deg(r)-d))
-            }
-        }
+    // Carry-less multiplication of two GF(2) polynomials of degree < 32.
+    //
+    // Rather than iterating over the bits of one operand, both operands are
+    // split into four groups of every fourth bit, and the groups are
+    // multiplied with ordinary integer multiplication (16 multiplies in total,
+    // with no branches or table lookups, so this is constant-time on any CPU
+    // with a constant-time multiplier). Each group has at most 8 bits set, so
+    // every sum of partial products is at most 8 and its carries only spill
+    // into the three "holes" above it, which are masked out. This is the same
+    // trick used by BearSSL's constant-time GHASH.
+    //   <https://www.bearssl.org/constanttime.html#ghash-for-gcm>
+    fn clmul(a: GfElemPrimitive, b: GfElemPrimitive) -> u64 {
+        const M0: u64 = 0x1111_1111_1111_1111;
+        const M1: u64 = M0 << 1;
+        const M2: u64 = M0 << 2;
+        const M3: u64 = M0 << 3;
+
+        let (a, b) = (a as u64, b as u64);
+        let (a0, a1, a2, a3) = (a & M0, a & M1, a & M2, a & M3);
+        let (b0, b1, b2, b3) = (b & M0, b & M1, b & M2, b & M3);
+        let z0 = (a0 * b0) ^ (a1 * b3) ^ (a2 * b2) ^ (a3 * b1);
+        let z1 = (a0 * b1) ^ (a1 * b0) ^ (a2 * b3) ^ (a3 * b2);
+        let z2 = (a0 * b2) ^ (a1 * b1) ^ (a2 * b0) ^ (a3 * b3);
+        let z3 = (a0 * b3) ^ (a1 * b2) ^ (a2 * b1) ^ (a3 * b0);
+        (z0 & M0) | (z1 & M1) | (z2 & M2) | (z3 & M3)
+    }
 
-        let mut rmsb = msb(r);
-        while rmsb >= bmsb {
-            // Because rd is the degree, we know that lc (1 << (rd-1)) is 1.
-            let shift = rmsb - bmsb; // lc/c * x^(deg(r)-d) (= x^(deg(r)-d))
-            q ^= 1 << shift; // q += s
-            r ^= b << shift; // r -= s*b (= b*x^(deg(r)-d))
-            rmsb = msb(r);
+    fn polynomial_mul(a: GfElemPrimitive, b: GfElemPrimitive) -> GfElemPrimitive {
+        // x^32 = TRUNC_POLYNOMIAL (mod POLYNOMIAL), so fold the bits above x^32
+        // down using shifts (TRUNC_POLYNOMIAL only has four terms). The
+        // product has degree at most 62, and since TRUNC_POLYNOMIAL has degree
+        // 22 each fold shrinks the overflow by 10 bits, so four folds always
+        // leave a fully-reduced element.
+        let mut p = Self::clmul(a, b);
+        for _ in 0..4 {
+            let hi = p >> 32;
+            p = (p & 0xffff_ffff) ^ (hi << 22) ^ (hi << 2) ^ (hi << 1) ^ hi;
         }
-
-        (q, r)
+        p as GfElemPrimitive
     }
 
-    fn polynomial_mul(mut a: GfElemPrimitive, mut b: GfElemPrimitive) -> GfElemPrimitive {
-        // A modified and hopefully-constant-time implementation of Russian
-        // Peasant Multiplication which avoids branching by using masks instead.
-        //   <https://en.wikipedia.org/wiki/Finite_field_arithmetic#D_programming_example>
-        let mut p: GfElemPrimitive = 0;
-        for _ in 0..32 {
-            let mask = ((a >> 31) & 1).wrapping_neg() as u64;
-            p ^= a & (b & 1).wrapping_neg();
-            a = (((a as u64) << 1) ^ (Self::POLYNOMIAL & mask)) as GfElemPrimitive;
-            b >>= 1;
+    /// Compute the multiplicative inverse of the field element, or `None` if
+    /// the element is zero.
+    ///
+    /// This is constant-time, apart from revealing whether the element is
+    /// zero.
+    pub fn inverse(self) -> Option<Self> {
+        // The multiplicative group of GF(2^32) has order 2^32-1, so by
+        // Lagrange's theorem a^(2^32-1) = 1 and thus a^(2^32-2) = a^-1. Unlike
+        // the Extended Euclid Algorithm, exponentiation by a fixed power
+        // performs the same operations for every element.
+        match self {
+            Self::ZERO => None,
+            a => Some(a.pow(GfElemPrimitive::MAX as usize - 1)),
         }
-        p
     }
+}
 
-    pub fn inverse(self) -> Option<Self> {
-        let a = self.0;
+// The reduction in GfElem::polynomial_mul hard-codes the terms of the
+// characteristic polynomial.
+const _: () = assert!(
+    GfElem::POLYNOMIAL == (1 << 32) | GfElem::TRUNC_POLYNOMIAL as u64
+        && GfElem::TRUNC_POLYNOMIAL == (1 << 22) | (1 << 2) | (1 << 1) | 1
+);
 
-        // We cannot invert 0 for obvious reasons.
-        if a == 0 {
-            return None;
-        }
+impl ConstantTimeEq for GfElem {
+    fn ct_eq(&self, other: &Self) -> Choice {
+        self.0.ct_eq(&other.0)
+    }
+}
 
-        // This is an implementation of the Extended Euclid Algorithm, in order
-        // to get the multiplicative inverse of self. The full description of
-        // why this works can be found on Wikipedia[1]. The short version is
-        // that you can extend the GCD algorithm with some extra bookkeeping and
-        // this allows you to extract the coefficients of Bézout's identity
-        //
-        //     ns + at = gcd(a, n)
-        //
-        // but in modular arithmetic, if you take n as the prime field as the
-        // ring of integers modulo n, then a and n must be coprime giving us:
-        //
-        //     ns + at = 1
-        //     at = 1 (mod n)
-        //
-        // which means that if we can find t, we have the modular multiplicative
-        // inverse of a -- and the GCD algorithm (or rather the EEA algorithm)
-        // can be used to find t in a fairly efficient manner.
-        //
-        // Note that the above is straight-forward for regular modular
-        // arithmetic, but we're in GF(2^32) which is a simple algebraic field
-        // expansion of GF(2). However, luckily the algorithm is pretty much the
-        // same in GF(2^32), except that:
-        //
-        //  * The p we take is the characteristic polynomial (irreducible
-        //    polynomials are like prime numbers in polynomial fields).
-        //  * We use polynomial definitions of the addition, multiplication, and
-        //    Euclidian division operations.
-        //
-        // [1]: <https://en.wikipedia.org/wiki/Extended_Euclidean_algorithm>
-
-        // Technically this algorithm can be cleanly done entirely in the loop,
-        // but becasue we first need to divide the characteristic polynomial
-        // (which is by definition larger than u32), it's much cleaner to do the
-        // first iteration outside.
-        let (q1, r1) = Self::polynomial_div(Self::TRUNC_POLYNOMIAL, a, true);
-
-        let (mut t, mut newt) = (1, q1); // (0, 1) -> (1, 0 - q1 * 1)
-        let (mut r, mut newr) = (a, r1); // (P, a) -> (a, P - q1 * a) -> (a, r1)
-
-        while newr != 0 {
-            // The trick with updating newr is to notice that (r - qi*newr) is
-            // undoing part of the polynomial_div calculation and you can just
-            // reuse that result. Recall that by definition (r = qi*newr + ri).
-            //
-            //    newr := r - qi*newr
-            //          = r - (r - ri)    [r = qi*newr + ri => r - ri = q*newr]
-            //          = ri
-            let (qi, ri) = Self::polynomial_div(r, newr, false);
-
-            // (t, newt) = (newt, t - qi * newt)
-            (t, newt) = (newt, t ^ Self::polynomial_mul(qi, newt));
-
-            // (r, newr) = (newr, r - qi * newr) = (newr, ri)
-            (r, newr) = (newr, ri);
-        }
+impl Zeroize for GfElem {
+    fn zeroize(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl From<GfElemPrimitive> for GfElem {
+    fn from(v: GfElemPrimitive) -> Self {
+        Self(v)
+    }
+}
 
-        // If gcd(a, Self::POLYNOMIAL) != 1, that means the polynomial is
-        // not an irreducible polynomial of order (at least) 32 in GF(2).
-        assert_eq!(r, 1, "Self::POLYNOMIAL not irreducible in GF(2)!");
-        Some(Self(t))
+impl From<GfElem> for GfElemPrimitive {
+    fn from(elem: GfElem) -> Self {
+        elem.0
     }
 }
 
@@ -331,12 +366,31 @@ impl DivAssign for GfElem {
     }
 }
 
+// Number of coefficients (or interpolation points) of a degree-n polynomial.
+// This saturates rather than overflowing, which is fine since no polynomial
+// could possibly have that many coefficients.
+pub(super) fn num_coefficients(n: u32) -> usize {
+    usize::try_from(n)
+        .ok()
+        .and_then(|n| n.checked_add(1))
+        .unwrap_or(usize::MAX)
+}
+
+// Degree of a polynomial with the given number of coefficients.
+pub(super) fn degree_of(num_coefficients: usize) -> u32 {
+    num_coefficients
+        .checked_sub(1)
+        .and_then(|n| u32::try_from(n).ok())
+        .expect("polynomial must have between 1 and 2^32 coefficients")
+}
+
+/// A polynomial in `GF(2^32)` which can be evaluated at any point.
 pub trait EvaluablePolynomial: Send + Sync + fmt::Debug {
     /// Evaluate the polynomial at a given `x` value.
     fn evaluate(&self, x: GfElem) -> GfElem;
 
     /// Return the degree of the polynomial (the largest power of x).
-    fn degree(&self) -> GfElemPrimitive;
+    fn degree(&self) -> u32;
 
     /// Retreive the constant term of the polynomial.
     ///
@@ -363,11 +417,32 @@ impl Clone for Box<dyn EvaluablePolynomial> {
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct GfPolynomial(Vec<GfElem>);
 
+impl Drop for GfPolynomial {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
 impl GfPolynomial {
-    pub fn new_rand<R: CryptoRng + RngCore + ?Sized>(n: GfElemPrimitive, r: &mut R) -> Self {
-        let k = n + 1;
+    /// Construct a polynomial from its coefficients, in *increasing* degree
+    /// (`x^0, x^1, ..., x^n`). Returns `None` if no coefficients were given.
+    pub fn from_coefficients(coeffs: Vec<GfElem>) -> Option<Self> {
+        match coeffs.is_empty() {
+            true => None,
+            false => Some(Self(coeffs)),
+        }
+    }
+
+    /// The coefficients of the polynomial, in *increasing* degree (`x^0, x^1,
+    /// ..., x^n`).
+    pub fn coefficients(&self) -> &[GfElem] {
+        &self.0
+    }
+
+    /// Generate a random polynomial of degree `n`, with no zero coefficients.
+    pub fn new_rand<R: CryptoRng + RngCore + ?Sized>(n: u32, r: &mut R) -> Self {
         Self(
-            (0..k)
+            (0..=n)
                 .map(|_| {
                     // We must make sure that none of the coefficients are zero
                     // elements. This is purely an abundance of caution, but it
@@ -405,15 +480,14 @@ impl GfPolynomial {
     /// in most cases).
     ///
     /// [lagrange]: https://en.wikipedia.org/wiki/Lagrange_polynomial
-    // TODO: Add a warning for using this.
-    #[allow(unused)]
-    pub fn recover<P: AsRef<[GfPoint]>>(n: GfElemPrimitive, points: P) -> Result<Self, Error> {
+    pub fn recover<P: AsRef<[GfPoint]>>(n: u32, points: P) -> Result<Self, Error> {
         let points = points.as_ref();
         let k = points.len();
         // TODO: Handle n+1 > k points.
-        if k != (n + 1) as usize {
+        let needed = num_coefficients(n);
+        if k != needed {
             return Err(Error::NumPointsMismatch {
-                needed: (n + 1) as usize,
+                needed,
                 num_points: k,
             });
         }
@@ -504,11 +578,8 @@ impl EvaluablePolynomial for GfPolynomial {
             .expect("polynomial has at least one term")
     }
 
-    fn degree(&self) -> GfElemPrimitive {
-        match self.0.len() {
-            0 => panic!("GfPolynomial must have at least one element"),
-            n => (n - 1) as GfElemPrimitive,
-        }
+    fn degree(&self) -> u32 {
+        degree_of(self.0.len())
     }
 
     fn constant(&self) -> GfElem {
@@ -579,6 +650,13 @@ pub struct GfBarycentric {
     ws: Vec<GfElem>, // barycentric weights (w_j = \prod_{i=0;i!=j}^k (x_j - x_i))
 }
 
+impl Drop for GfBarycentric {
+    fn drop(&mut self) {
+        // Only the y values are secret.
+        self.ys.zeroize();
+    }
+}
+
 impl EvaluablePolynomial for GfBarycentric {
     fn evaluate(&self, x: GfElem) -> GfElem {
         // The following calculations will divide by zero if the requested x is
@@ -628,8 +706,8 @@ impl EvaluablePolynomial for GfBarycentric {
         numerator / denominator
     }
 
-    fn degree(&self) -> GfElemPrimitive {
-        self.ws.len() as GfElemPrimitive - 1
+    fn degree(&self) -> u32 {
+        degree_of(self.ws.len())
     }
 
     // TODO: Should we cache the constant term and have an optimised constant()
@@ -642,13 +720,18 @@ impl EvaluablePolynomial for GfBarycentric {
 }
 
 impl GfBarycentric {
-    pub fn recover<P: AsRef<[GfPoint]>>(n: GfElemPrimitive, points: P) -> Result<Self, Error> {
+    /// Compute the barycentric form of the polynomial of degree `n` in
+    /// `GF(2^32)` which passes through the given set of points.
+    ///
+    /// Exactly `n + 1` points with distinct `x` values must be provided.
+    pub fn recover<P: AsRef<[GfPoint]>>(n: u32, points: P) -> Result<Self, Error> {
         let points = points.as_ref();
         let k = points.len();
         // TODO: Handle n+1 > k points.
-        if k != (n + 1) as usize {
+        let needed = num_coefficients(n);
+        if k != needed {
             return Err(Error::NumPointsMismatch {
-                needed: (n + 1) as usize,
+                needed,
                 num_points: k,
             });
         }
@@ -722,6 +805,43 @@ mod test {
         (a * b) == (b * a)
     }
 
+    // Straightforward shift-and-add multiplication, to check the optimised
+    // GfElem::polynomial_mul against.
+    fn reference_mul(mut a: GfElemPrimitive, mut b: GfElemPrimitive) -> GfElemPrimitive {
+        let mut p = 0;
+        while b != 0 {
+            if b & 1 == 1 {
+                p ^= a;
+            }
+            let carry = a >> 31 == 1;
+            a <<= 1;
+            if carry {
+                a ^= GfElem::TRUNC_POLYNOMIAL;
+            }
+            b >>= 1;
+        }
+        p
+    }
+
+    #[quickcheck]
+    fn mul_matches_reference(a: GfElemPrimitive, b: GfElemPrimitive) -> bool {
+        GfElem::polynomial_mul(a, b) == reference_mul(a, b)
+    }
+
+    #[test]
+    fn mul_matches_reference_extremes() {
+        let values = [0, 1, 2, 0x8000_0000, 0xffff_ffff, 0xdead_beef, 0x0040_0007];
+        for a in values {
+            for b in values {
+                assert_eq!(
+                    GfElem::polynomial_mul(a, b),
+                    reference_mul(a, b),
+                    "{a:#x} * {b:#x}"
+                );
+            }
+        }
+    }
+
     #[quickcheck]
     fn add_commutativity(a: GfElem, b: GfElem, c: GfElem) -> bool {
         ((a + b) + c) == (a + (b + c))
@@ -775,6 +895,18 @@ mod test {
         }
     }
 
+    #[test]
+    fn mul_inverse_extremes() {
+        for a in [1, 2, 0x8000_0000, GfElemPrimitive::MAX].map(GfElem::from) {
+            assert_eq!(a * a.inverse().unwrap(), GfElem::ONE, "{a:?}");
+        }
+    }
+
+    #[quickcheck]
+    fn ct_eq_matches_eq(a: GfElem, b: GfElem) -> bool {
+        bool::from(a.ct_eq(&b)) == (a == b) && bool::from(a.ct_eq(&a))
+    }
+
     #[quickcheck]
     fn div_inverse(a: GfElem) -> bool {
         match (a, a.inverse()) {
@@ -878,4 +1010,20 @@ mod test {
                 .all(|&x| interpolated_poly.evaluate(x) == poly.evaluate(x)),
         )
     }
+
+    #[test]
+    fn polynomial_recover_huge_degree() {
+        // The number of points for a degree-(2^32-1) polynomial must not
+        // overflow a u32.
+        let points = [(GfElem::ONE, GfElem::ONE)];
+        assert!(matches!(
+            GfPolynomial::recover(u32::MAX, points),
+            Err(Error::NumPointsMismatch { num_points: 1, .. })
+        ));
+        assert!(matches!(
+            GfBarycentric::recover(u32::MAX, points),
+            Err(Error::NumPointsMismatch { num_points: 1, .. })
+        ));
+        assert_eq!(GfPolynomial::new_rand(0, &mut OsRng).degree(), 0);
+    }
 }
@@ -0,0 +1,544 @@
+/*
+ * paperback: paper backup generator suitable for long-term storage
+ * Copyright (C) 2018-2022 Aleksa Sarai <cyphar@cyphar.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+//! Arithmetic in `GF(2^128)`, used to shard very large secrets with a quarter
+//! as many polynomials (and thus interpolations) per byte as `GF(2^32)`.
+//!
+//! As with [`gf64`](super::gf64), only the `y` values of a shard live in
+//! `GF(2^128)` -- `x` values are still `GF(2^32)` elements (which are embedded
+//! into `GF(2^128)` as the polynomials of degree less than 32).
+//!
+//! ## Security ##
+//! The same constant-time guarantees as [`gf`] apply. The field polynomial is
+//! the one used by AES-GCM, but elements are stored in the natural bit order
+//! (not GCM's reflected order), so this is not interchangeable with GHASH.
+
+use std::{
+    cmp, fmt, mem,
+    ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub, SubAssign},
+};
+
+use crate::shamir::gf::{self, Error, GfElem};
+
+use rand::{CryptoRng, RngCore};
+use subtle::{Choice, ConstantTimeEq};
+use zeroize::Zeroize;
+
+/// Primitive uint type for Gf128Elems.
+pub type Gf128ElemPrimitive = u128;
+
+/// A field element of `GF(2^128)`, with characteristic polynomial
+/// `x^128 + x^7 + x^2 + x^1 + 1`.
+// NOTE: PartialEq is not timing-safe, use ConstantTimeEq for secret values.
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
+pub struct Gf128Elem(Gf128ElemPrimitive);
+
+impl Gf128Elem {
+    // The degree-128 polynomial used by AES-GCM, which is irreducible in
+    // GF(2), with the x^128 term omitted.
+    //
+    // x^128 + x^7 + x^2 + x^1 + 1
+    const TRUNC_POLYNOMIAL: Gf128ElemPrimitive = 0b1000_0111;
+
+    /// Additive identity.
+    pub const ZERO: Gf128Elem = Gf128Elem(0);
+
+    /// Multiplicative identity.
+    pub const ONE: Gf128Elem = Gf128Elem(1);
+
+    /// Generate a uniformly random field element.
+    pub fn new_rand<R: CryptoRng + RngCore + ?Sized>(r: &mut R) -> Self {
+        let mut bytes = [0u8; mem::size_of::<Gf128ElemPrimitive>()];
+        r.fill_bytes(&mut bytes);
+        let elem = Self(Gf128ElemPrimitive::from_le_bytes(bytes));
+        bytes.zeroize();
+        elem
+    }
+
+    pub(crate) fn inner(&self) -> Gf128ElemPrimitive {
+        self.0
+    }
+
+    pub(crate) fn from_inner(v: Gf128ElemPrimitive) -> Self {
+        Self(v)
+    }
+
+    /// Parse a field element from the first (up to) 16 bytes of `bytes` in
+    /// little-endian order, returning the remaining bytes. If fewer than 16
+    /// bytes are provided, the missing high-order bytes are treated as zero.
+    pub fn from_bytes_partial(bytes: &[u8]) -> (Self, &[u8]) {
+        let len = cmp::min(bytes.len(), mem::size_of::<Gf128ElemPrimitive>());
+
+        // Pad with zeroes.
+        let mut padded = [0u8; mem::size_of::<Gf128ElemPrimitive>()];
+        padded[..len].copy_from_slice(&bytes[..len]);
+
+        (
+            Gf128Elem(Gf128ElemPrimitive::from_le_bytes(padded)),
+            &bytes[len..],
+        )
+    }
+
+    /// Parse a field element from (up to) 16 bytes in little-endian order.
+    ///
+    /// # Panics
+    /// Panics if more than 16 bytes are provided.
+    pub fn from_bytes<B: AsRef<[u8]>>(bytes: B) -> Self {
+        let (elem, remain) = Self::from_bytes_partial(bytes.as_ref());
+        assert!(remain.is_empty());
+        elem
+    }
+
+    /// Serialise the field element as 16 bytes in little-endian order.
+    pub fn to_bytes(self) -> Vec<u8> {
+        self.0.to_le_bytes().to_vec()
+    }
+
+    /// Raise the field element to the power `n`.
+    ///
+    /// This is constant-time with respect to `self`, but **not** `n`.
+    pub fn pow(self, mut n: u128) -> Self {
+        let mut mult = self;
+        let mut result = Self::ONE;
+        while n != 0 {
+            if n & 1 == 1 {
+                result *= mult;
+            }
+            mult *= mult;
+            n >>= 1;
+        }
+        result
+    }
+
+    // Carry-less multiplication of two 64-bit GF(2) polynomials, using masks
+    // rather than branches (in the same style as Gf64Elem::clmul).
+    fn clmul64(a: u64, b: u64) -> u128 {
+        let mut p = 0u128;
+        for i in 0..64 {
+            let mask = (((b >> i) & 1) as u128).wrapping_neg();
+            p ^= ((a as u128) << i) & mask;
+        }
+        p
+    }
+
+    // Carry-less multiplication of two 128-bit GF(2) polynomials, returning
+    // the (high, low) halves of the product. Uses Karatsuba to need only three
+    // 64-bit multiplications.
+    fn clmul(a: Gf128ElemPrimitive, b: Gf128ElemPrimitive) -> (u128, u128) {
+        let (a1, a0) = ((a >> 64) as u64, a as u64);
+        let (b1, b0) = ((b >> 64) as u64, b as u64);
+        let lo = Self::clmul64(a0, b0);
+        let hi = Self::clmul64(a1, b1);
+        let mid = Self::clmul64(a0 ^ a1, b0 ^ b1) ^ lo ^ hi;
+        (hi ^ (mid >> 64), lo ^ (mid << 64))
+    }
+
+    // Multiply by TRUNC_POLYNOMIAL (x^7 + x^2 + x + 1), returning the (high,
+    // low) halves of the product. The shifts are by constants, so this is
+    // constant-time.
+    fn mul_trunc(v: u128) -> (u128, u128) {
+        const _: () = assert!(Gf128Elem::TRUNC_POLYNOMIAL == (1 << 7) | (1 << 2) | (1 << 1) | 1);
+        let hi = (v >> 127) ^ (v >> 126) ^ (v >> 121);
+        let lo = v ^ (v << 1) ^ (v << 2) ^ (v << 7);
+        (hi, lo)
+    }
+
+    fn polynomial_mul(a: Gf128ElemPrimitive, b: Gf128ElemPrimitive) -> Gf128ElemPrimitive {
+        let (hi, lo) = Self::clmul(a, b);
+        // x^128 = TRUNC_POLYNOMIAL (mod POLYNOMIAL), so fold the high half
+        // down. Since TRUNC_POLYNOMIAL has degree 7, the first fold leaves at
+        // most 7 bits above x^128, and the second fold cannot overflow.
+        let (hi, folded) = Self::mul_trunc(hi);
+        let (_, refolded) = Self::mul_trunc(hi);
+        lo ^ folded ^ refolded
+    }
+
+    /// Compute the multiplicative inverse of the field element, or `None` if
+    /// the element is zero.
+    ///
+    /// This is constant-time, apart from revealing whether the element is
+    /// zero.
+    pub fn inverse(self) -> Option<Self> {
+        // The multiplicative group of GF(2^128) has order 2^128-1, so by
+        // Lagrange's theorem a^(2^128-1) = 1 and thus a^(2^128-2) = a^-1.
+        match self {
+            Self::ZERO => None,
+            a => Some(a.pow(u128::MAX - 1)),
+        }
+    }
+}
+
+impl ConstantTimeEq for Gf128Elem {
+    fn ct_eq(&self, other: &Self) -> Choice {
+        self.0.ct_eq(&other.0)
+    }
+}
+
+impl Zeroize for Gf128Elem {
+    fn zeroize(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl From<Gf128ElemPrimitive> for Gf128Elem {
+    fn from(v: Gf128ElemPrimitive) -> Self {
+        Self(v)
+    }
+}
+
+impl From<Gf128Elem> for Gf128ElemPrimitive {
+    fn from(elem: Gf128Elem) -> Self {
+        elem.0
+    }
+}
+
+impl From<GfElem> for Gf128Elem {
+    fn from(elem: GfElem) -> Self {
+        Self(elem.inner().into())
+    }
+}
+
+#[cfg(test)]
+impl quickcheck::Arbitrary for Gf128Elem {
+    fn arbitrary(g: &mut quickcheck::Gen) -> Self {
+        Self(Gf128ElemPrimitive::arbitrary(g))
+    }
+
+    fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+        Box::new(self.0.shrink().map(Self))
+    }
+}
+
+impl Add for Gf128Elem {
+    type Output = Self;
+    fn add(mut self, rhs: Self) -> Self::Output {
+        self += rhs;
+        self
+    }
+}
+
+impl AddAssign for Gf128Elem {
+    fn add_assign(&mut self, rhs: Self) {
+        // In GF(2^n), addition is XOR.
+        #![allow(clippy::suspicious_op_assign_impl)]
+        self.0 ^= rhs.0;
+    }
+}
+
+impl Sub for Gf128Elem {
+    type Output = Self;
+    fn sub(mut self, rhs: Self) -> Self::Output {
+        self -= rhs;
+        self
+    }
+}
+
+impl SubAssign for Gf128Elem {
+    fn sub_assign(&mut self, rhs: Self) {
+        // Every element is its own additive inverse in GF(2^n).
+        #![allow(clippy::suspicious_op_assign_impl)]
+        *self += rhs;
+    }
+}
+
+impl Neg for Gf128Elem {
+    type Output = Self;
+    fn neg(self) -> Self::Output {
+        self
+    }
+}
+
+impl Mul for Gf128Elem {
+    type Output = Self;
+    fn mul(mut self, rhs: Self) -> Self::Output {
+        self *= rhs;
+        self
+    }
+}
+
+impl MulAssign for Gf128Elem {
+    fn mul_assign(&mut self, rhs: Self) {
+        self.0 = Self::polynomial_mul(self.0, rhs.0);
+    }
+}
+
+impl Div for Gf128Elem {
+    type Output = Self;
+    fn div(mut self, rhs: Self) -> Self::Output {
+        self /= rhs;
+        self
+    }
+}
+
+impl DivAssign for Gf128Elem {
+    fn div_assign(&mut self, rhs: Self) {
+        #![allow(clippy::suspicious_op_assign_impl)]
+        *self *= rhs.inverse().expect("rhs cannot be inverted")
+    }
+}
+
+/// A polynomial in `GF(2^128)` which can be evaluated at any point.
+pub trait EvaluablePolynomial128: Send + Sync + fmt::Debug {
+    /// Evaluate the polynomial at a given `x` value.
+    fn evaluate(&self, x: Gf128Elem) -> Gf128Elem;
+
+    /// Retreive the constant term of the polynomial.
+    fn constant(&self) -> Gf128Elem {
+        self.evaluate(Gf128Elem::ZERO)
+    }
+
+    #[doc(hidden)]
+    fn box_clone(&self) -> Box<dyn EvaluablePolynomial128>;
+}
+
+impl Clone for Box<dyn EvaluablePolynomial128> {
+    fn clone(&self) -> Self {
+        self.box_clone()
+    }
+}
+
+/// A polynomial in `GF(2^128)`.
+// The coefficients are in *increasing* degree (x^0, x^1, ..., x^n).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Gf128Polynomial(Vec<Gf128Elem>);
+
+impl Drop for Gf128Polynomial {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl Gf128Polynomial {
+    /// Generate a random polynomial of degree `n`, with no zero coefficients.
+    pub fn new_rand<R: CryptoRng + RngCore + ?Sized>(n: u32, r: &mut R) -> Self {
+        Self(
+            (0..=n)
+                .map(|_| {
+                    // See GfPolynomial::new_rand.
+                    let mut elem = Gf128Elem::ZERO;
+                    while elem == Gf128Elem::ZERO {
+                        elem = Gf128Elem::new_rand(r);
+                    }
+                    elem
+                })
+                .collect::<Vec<_>>(),
+        )
+    }
+
+    /// Yield a mutable reference to the constant term of the polynomial.
+    pub fn constant_mut(&mut self) -> &mut Gf128Elem {
+        self.0
+            .first_mut()
+            .expect("Gf128Polynomial must have at least one element")
+    }
+}
+
+impl EvaluablePolynomial128 for Gf128Polynomial {
+    fn evaluate(&self, x: Gf128Elem) -> Gf128Elem {
+        // Horner's method (see GfPolynomial::evaluate).
+        self.0
+            .iter()
+            .rev()
+            .copied()
+            .reduce(|acc, coeff| coeff + x * acc)
+            .expect("polynomial has at least one term")
+    }
+
+    fn constant(&self) -> Gf128Elem {
+        *self
+            .0
+            .first()
+            .expect("Gf128Polynomial must have at least one element")
+    }
+
+    fn box_clone(&self) -> Box<dyn EvaluablePolynomial128> {
+        Box::new((*self).clone())
+    }
+}
+
+/// A barycentric-form representation of a Lagrange-interpolated polynomial in
+/// `GF(2^128)`. See `GfBarycentric` for details.
+#[derive(Clone, Debug)]
+pub struct Gf128Barycentric {
+    xs: Vec<Gf128Elem>, // x co-ordinates
+    ys: Vec<Gf128Elem>, // y co-ordinates
+    ws: Vec<Gf128Elem>, // barycentric weights
+}
+
+impl Drop for Gf128Barycentric {
+    fn drop(&mut self) {
+        // Only the y values are secret.
+        self.ys.zeroize();
+    }
+}
+
+impl EvaluablePolynomial128 for Gf128Barycentric {
+    fn evaluate(&self, x: Gf128Elem) -> Gf128Elem {
+        if let Some((_, &y)) = self.xs.iter().zip(&self.ys).find(|&(&xi, _)| xi == x) {
+            return y;
+        }
+
+        let sum_terms = self
+            .xs
+            .iter()
+            .zip(&self.ws)
+            .map(|(&xj, &wj)| wj / (x - xj))
+            .collect::<Vec<_>>();
+
+        let numerator = sum_terms
+            .iter()
+            .zip(&self.ys)
+            .map(|(&t, &yj)| t * yj)
+            .reduce(Gf128Elem::add)
+            .expect("barycentric form has at least one term");
+
+        let denominator = sum_terms
+            .iter()
+            .copied()
+            .reduce(Gf128Elem::add)
+            .expect("barycentric form has at least one term");
+
+        numerator / denominator
+    }
+
+    fn box_clone(&self) -> Box<dyn EvaluablePolynomial128> {
+        Box::new((*self).clone())
+    }
+}
+
+impl Gf128Barycentric {
+    /// Compute the barycentric form of the polynomial of degree `n` in
+    /// `GF(2^128)` which passes through the given set of points.
+    ///
+    /// Exactly `n + 1` points with distinct `x` values must be provided.
+    pub fn recover<P: AsRef<[(Gf128Elem, Gf128Elem)]>>(n: u32, points: P) -> Result<Self, Error> {
+        let points = points.as_ref();
+        let needed = gf::num_coefficients(n);
+        if points.len() != needed {
+            return Err(Error::NumPointsMismatch {
+                needed,
+                num_points: points.len(),
+            });
+        }
+        let (xs, ys): (Vec<_>, Vec<_>) = points.iter().copied().unzip();
+
+        // w_j = 1 / \prod_{i=0,i!=j}^k (x_j - x_i)
+        let ws = xs
+            .iter()
+            .enumerate()
+            .map(|(j, &xj)| {
+                xs.iter()
+                    .enumerate()
+                    .filter(|&(i, _)| i != j)
+                    .map(|(_, &xi)| xj - xi)
+                    .reduce(Mul::mul)
+                    .map_or(Some(Gf128Elem::ONE), Gf128Elem::inverse)
+                    .ok_or(Error::NonInvertiblePoint)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self { xs, ys, ws })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use quickcheck::TestResult;
+
+    // Bit-by-bit shift-and-add multiplication, to check polynomial_mul.
+    fn mul_reference(a: Gf128Elem, b: Gf128Elem) -> Gf128Elem {
+        let (mut a, mut b, mut p) = (a.0, b.0, 0);
+        while b != 0 {
+            if b & 1 == 1 {
+                p ^= a;
+            }
+            let carry = a >> 127;
+            a <<= 1;
+            if carry == 1 {
+                a ^= Gf128Elem::TRUNC_POLYNOMIAL;
+            }
+            b >>= 1;
+        }
+        Gf128Elem(p)
+    }
+
+    #[quickcheck]
+    fn mul_matches_reference(a: Gf128Elem, b: Gf128Elem) -> bool {
+        a * b == mul_reference(a, b)
+    }
+
+    #[test]
+    fn mul_matches_reference_extremes() {
+        let extremes = [0, 1, 2, u128::MAX, u128::MAX - 1, 1 << 127, (1 << 127) | 1].map(Gf128Elem);
+        for &a in &extremes {
+            for &b in &extremes {
+                assert_eq!(a * b, mul_reference(a, b), "{:?} * {:?}", a, b);
+            }
+        }
+    }
+
+    #[quickcheck]
+    fn mul_associativity(a: Gf128Elem, b: Gf128Elem, c: Gf128Elem) -> bool {
+        (a * b) * c == a * (b * c)
+    }
+
+    #[quickcheck]
+    fn mul_commutativity(a: Gf128Elem, b: Gf128Elem) -> bool {
+        a * b == b * a
+    }
+
+    #[quickcheck]
+    fn distributivity(a: Gf128Elem, b: Gf128Elem, c: Gf128Elem) -> bool {
+        a * (b + c) == a * b + a * c
+    }
+
+    #[quickcheck]
+    fn mul_inverse(a: Gf128Elem) -> TestResult {
+        match a.inverse() {
+            None => TestResult::from_bool(a == Gf128Elem::ZERO),
+            Some(inv) => TestResult::from_bool(a * inv == Gf128Elem::ONE),
+        }
+    }
+
+    #[test]
+    fn reduction() {
+        // x^127 * x = x^128 = x^7 + x^2 + x + 1.
+        assert_eq!(
+            Gf128Elem(1 << 127) * Gf128Elem(2),
+            Gf128Elem(Gf128Elem::TRUNC_POLYNOMIAL)
+        );
+    }
+
+    #[quickcheck]
+    fn barycentric_recover(coeffs: Vec<Gf128Elem>, x: Gf128Elem) -> TestResult {
+        if coeffs.is_empty() || coeffs.len() > 16 {
+            return TestResult::discard();
+        }
+        let poly = Gf128Polynomial(coeffs);
+        let n = gf::degree_of(poly.0.len());
+        let points = (1..=poly.0.len() as u128)
+            .map(Gf128Elem)
+            .map(|x| (x, poly.evaluate(x)))
+            .collect::<Vec<_>>();
+        let recovered = Gf128Barycentric::recover(n, points).unwrap();
+        TestResult::from_bool(
+            recovered.constant() == poly.constant() && recovered.evaluate(x) == poly.evaluate(x),
+        )
+    }
+}
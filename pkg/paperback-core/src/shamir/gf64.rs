@@ -0,0 +1,488 @@
+/*
+ * paperback: paper backup generator suitable for long-term storage
+ * Copyright (C) 2018-2022 Aleksa Sarai <cyphar@cyphar.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Arithmetic in `GF(2^64)`, used to shard large secrets with half as many
+//! polynomials (and thus interpolations) per byte as `GF(2^32)`.
+//!
+//! Only the `y` values of a shard live in `GF(2^64)` -- `x` values are still
+//! `GF(2^32)` elements (which are embedded into `GF(2^64)` as the polynomials
+//! of degree less than 32), so shard identifiers are the same in both fields.
+//!
+//! ## Security ##
+//! The same constant-time guarantees as [`gf`] apply. Multiplication uses a
+//! masked carry-less multiply rather than `PCLMULQDQ`, because the intrinsics
+//! require `unsafe` which is forbidden in this crate.
+
+use std::{
+    cmp, fmt, mem,
+    ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub, SubAssign},
+};
+
+use crate::shamir::gf::{self, Error, GfElem};
+
+use rand::{CryptoRng, RngCore};
+use subtle::{Choice, ConstantTimeEq};
+use zeroize::Zeroize;
+
+/// Primitive uint type for Gf64Elems.
+pub type Gf64ElemPrimitive = u64;
+
+/// A field element of `GF(2^64)`, with characteristic polynomial
+/// `x^64 + x^4 + x^3 + x^1 + 1`.
+// NOTE: PartialEq is not timing-safe, use ConstantTimeEq for secret values.
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
+pub struct Gf64Elem(Gf64ElemPrimitive);
+
+impl Gf64Elem {
+    // The smallest degree-64 polynomial which is irreducible in GF(2), with
+    // the x^64 term omitted.
+    //
+    // x^64 + x^4 + x^3 + x^1 + 1
+    const TRUNC_POLYNOMIAL: Gf64ElemPrimitive = 0b1_1011;
+
+    /// Additive identity.
+    pub const ZERO: Gf64Elem = Gf64Elem(0);
+
+    /// Multiplicative identity.
+    pub const ONE: Gf64Elem = Gf64Elem(1);
+
+    /// Generate a uniformly random field element.
+    pub fn new_rand<R: CryptoRng + RngCore + ?Sized>(r: &mut R) -> Self {
+        Self(r.next_u64())
+    }
+
+    pub(crate) fn inner(&self) -> Gf64ElemPrimitive {
+        self.0
+    }
+
+    pub(crate) fn from_inner(v: Gf64ElemPrimitive) -> Self {
+        Self(v)
+    }
+
+    /// Parse a field element from the first (up to) 8 bytes of `bytes` in
+    /// little-endian order, returning the remaining bytes. If fewer than 8
+    /// bytes are provided, the missing high-order bytes are treated as zero.
+    pub fn from_bytes_partial(bytes: &[u8]) -> (Self, &[u8]) {
+        let len = cmp::min(bytes.len(), mem::size_of::<Gf64ElemPrimitive>());
+
+        // Pad with zeroes.
+        let mut padded = [0u8; mem::size_of::<Gf64ElemPrimitive>()];
+        padded[..len].copy_from_slice(&bytes[..len]);
+
+        (
+            Gf64Elem(Gf64ElemPrimitive::from_le_bytes(padded)),
+            &bytes[len..],
+        )
+    }
+
+    /// Parse a field element from (up to) 8 bytes in little-endian order.
+    ///
+    /// # Panics
+    /// Panics if more than 8 bytes are provided.
+    pub fn from_bytes<B: AsRef<[u8]>>(bytes: B) -> Self {
+        let (elem, remain) = Self::from_bytes_partial(bytes.as_ref());
+        assert!(remain.is_empty());
+        elem
+    }
+
+    /// Serialise the field element as 8 bytes in little-endian order.
+    pub fn to_bytes(self) -> Vec<u8> {
+        self.0.to_le_bytes().to_vec()
+    }
+
+    /// Raise the field element to the power `n`.
+    ///
+    /// This is constant-time with respect to `self`, but **not** `n`.
+    pub fn pow(self, mut n: u64) -> Self {
+        let mut mult = self;
+        let mut result = Self::ONE;
+        while n != 0 {
+            if n & 1 == 1 {
+                result *= mult;
+            }
+            mult *= mult;
+            n >>= 1;
+        }
+        result
+    }
+
+    // Carry-less multiplication of two GF(2) polynomials, using masks rather
+    // than branches (in the same style as GfElem::polynomial_mul).
+    fn clmul(a: Gf64ElemPrimitive, b: Gf64ElemPrimitive) -> u128 {
+        let mut p = 0u128;
+        for i in 0..64 {
+            let mask = (((b >> i) & 1) as u128).wrapping_neg();
+            p ^= ((a as u128) << i) & mask;
+        }
+        p
+    }
+
+    fn polynomial_mul(a: Gf64ElemPrimitive, b: Gf64ElemPrimitive) -> Gf64ElemPrimitive {
+        let p = Self::clmul(a, b);
+        let (hi, lo) = ((p >> 64) as u64, p as u64);
+        // x^64 = TRUNC_POLYNOMIAL (mod POLYNOMIAL), so fold the high half down.
+        // Since TRUNC_POLYNOMIAL has degree 4, the first fold leaves at most 4
+        // bits above x^64, and the second fold cannot overflow.
+        let folded = Self::clmul(hi, Self::TRUNC_POLYNOMIAL);
+        let (hi, lo) = ((folded >> 64) as u64, lo ^ folded as u64);
+        lo ^ Self::clmul(hi, Self::TRUNC_POLYNOMIAL) as u64
+    }
+
+    /// Compute the multiplicative inverse of the field element, or `None` if
+    /// the element is zero.
+    ///
+    /// This is constant-time, apart from revealing whether the element is
+    /// zero.
+    pub fn inverse(self) -> Option<Self> {
+        // The multiplicative group of GF(2^64) has order 2^64-1, so by
+        // Lagrange's theorem a^(2^64-1) = 1 and thus a^(2^64-2) = a^-1.
+        match self {
+            Self::ZERO => None,
+            a => Some(a.pow(u64::MAX - 1)),
+        }
+    }
+}
+
+impl ConstantTimeEq for Gf64Elem {
+    fn ct_eq(&self, other: &Self) -> Choice {
+        self.0.ct_eq(&other.0)
+    }
+}
+
+impl Zeroize for Gf64Elem {
+    fn zeroize(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl From<Gf64ElemPrimitive> for Gf64Elem {
+    fn from(v: Gf64ElemPrimitive) -> Self {
+        Self(v)
+    }
+}
+
+impl From<Gf64Elem> for Gf64ElemPrimitive {
+    fn from(elem: Gf64Elem) -> Self {
+        elem.0
+    }
+}
+
+impl From<GfElem> for Gf64Elem {
+    fn from(elem: GfElem) -> Self {
+        Self(elem.inner().into())
+    }
+}
+
+#[cfg(test)]
+impl quickcheck::Arbitrary for Gf64Elem {
+    fn arbitrary(g: &mut quickcheck::Gen) -> Self {
+        Self(Gf64ElemPrimitive::arbitrary(g))
+    }
+
+    fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+        Box::new(self.0.shrink().map(Self))
+    }
+}
+
+impl Add for Gf64Elem {
+    type Output = Self;
+    fn add(mut self, rhs: Self) -> Self::Output {
+        self += rhs;
+        self
+    }
+}
+
+impl AddAssign for Gf64Elem {
+    fn add_assign(&mut self, rhs: Self) {
+        // In GF(2^n), addition is XOR.
+        #![allow(clippy::suspicious_op_assign_impl)]
+        self.0 ^= rhs.0;
+    }
+}
+
+impl Sub for Gf64Elem {
+    type Output = Self;
+    fn sub(mut self, rhs: Self) -> Self::Output {
+        self -= rhs;
+        self
+    }
+}
+
+impl SubAssign for Gf64Elem {
+    fn sub_assign(&mut self, rhs: Self) {
+        // Every element is its own additive inverse in GF(2^n).
+        #![allow(clippy::suspicious_op_assign_impl)]
+        *self += rhs;
+    }
+}
+
+impl Neg for Gf64Elem {
+    type Output = Self;
+    fn neg(self) -> Self::Output {
+        self
+    }
+}
+
+impl Mul for Gf64Elem {
+    type Output = Self;
+    fn mul(mut self, rhs: Self) -> Self::Output {
+        self *= rhs;
+        self
+    }
+}
+
+impl MulAssign for Gf64Elem {
+    fn mul_assign(&mut self, rhs: Self) {
+        self.0 = Self::polynomial_mul(self.0, rhs.0);
+    }
+}
+
+impl Div for Gf64Elem {
+    type Output = Self;
+    fn div(mut self, rhs: Self) -> Self::Output {
+        self /= rhs;
+        self
+    }
+}
+
+impl DivAssign for Gf64Elem {
+    fn div_assign(&mut self, rhs: Self) {
+        #![allow(clippy::suspicious_op_assign_impl)]
+        *self *= rhs.inverse().expect("rhs cannot be inverted")
+    }
+}
+
+/// A polynomial in `GF(2^64)` which can be evaluated at any point.
+pub trait EvaluablePolynomial64: Send + Sync + fmt::Debug {
+    /// Evaluate the polynomial at a given `x` value.
+    fn evaluate(&self, x: Gf64Elem) -> Gf64Elem;
+
+    /// Retreive the constant term of the polynomial.
+    fn constant(&self) -> Gf64Elem {
+        self.evaluate(Gf64Elem::ZERO)
+    }
+
+    #[doc(hidden)]
+    fn box_clone(&self) -> Box<dyn EvaluablePolynomial64>;
+}
+
+impl Clone for Box<dyn EvaluablePolynomial64> {
+    fn clone(&self) -> Self {
+        self.box_clone()
+    }
+}
+
+/// A polynomial in `GF(2^64)`.
+// The coefficients are in *increasing* degree (x^0, x^1, ..., x^n).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Gf64Polynomial(Vec<Gf64Elem>);
+
+impl Drop for Gf64Polynomial {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl Gf64Polynomial {
+    /// Generate a random polynomial of degree `n`, with no zero coefficients.
+    pub fn new_rand<R: CryptoRng + RngCore + ?Sized>(n: u32, r: &mut R) -> Self {
+        Self(
+            (0..=n)
+                .map(|_| {
+                    // See GfPolynomial::new_rand.
+                    let mut elem = Gf64Elem::ZERO;
+                    while elem == Gf64Elem::ZERO {
+                        elem = Gf64Elem::new_rand(r);
+                    }
+                    elem
+                })
+                .collect::<Vec<_>>(),
+        )
+    }
+
+    /// Yield a mutable reference to the constant term of the polynomial.
+    pub fn constant_mut(&mut self) -> &mut Gf64Elem {
+        self.0
+            .first_mut()
+            .expect("Gf64Polynomial must have at least one element")
+    }
+}
+
+impl EvaluablePolynomial64 for Gf64Polynomial {
+    fn evaluate(&self, x: Gf64Elem) -> Gf64Elem {
+        // Horner's method (see GfPolynomial::evaluate).
+        self.0
+            .iter()
+            .rev()
+            .copied()
+            .reduce(|acc, coeff| coeff + x * acc)
+            .expect("polynomial has at least one term")
+    }
+
+    fn constant(&self) -> Gf64Elem {
+        *self
+            .0
+            .first()
+            .expect("Gf64Polynomial must have at least one element")
+    }
+
+    fn box_clone(&self) -> Box<dyn EvaluablePolynomial64> {
+        Box::new((*self).clone())
+    }
+}
+
+/// A barycentric-form representation of a Lagrange-interpolated polynomial in
+/// `GF(2^64)`. See `GfBarycentric` for details.
+#[derive(Clone, Debug)]
+pub struct Gf64Barycentric {
+    xs: Vec<Gf64Elem>, // x co-ordinates
+    ys: Vec<Gf64Elem>, // y co-ordinates
+    ws: Vec<Gf64Elem>, // barycentric weights
+}
+
+impl Drop for Gf64Barycentric {
+    fn drop(&mut self) {
+        // Only the y values are secret.
+        self.ys.zeroize();
+    }
+}
+
+impl EvaluablePolynomial64 for Gf64Barycentric {
+    fn evaluate(&self, x: Gf64Elem) -> Gf64Elem {
+        if let Some((_, &y)) = self.xs.iter().zip(&self.ys).find(|&(&xi, _)| xi == x) {
+            return y;
+        }
+
+        let sum_terms = self
+            .xs
+            .iter()
+            .zip(&self.ws)
+            .map(|(&xj, &wj)| wj / (x - xj))
+            .collect::<Vec<_>>();
+
+        let numerator = sum_terms
+            .iter()
+            .zip(&self.ys)
+            .map(|(&t, &yj)| t * yj)
+            .reduce(Gf64Elem::add)
+            .expect("barycentric form has at least one term");
+
+        let denominator = sum_terms
+            .iter()
+            .copied()
+            .reduce(Gf64Elem::add)
+            .expect("barycentric form has at least one term");
+
+        numerator / denominator
+    }
+
+    fn box_clone(&self) -> Box<dyn EvaluablePolynomial64> {
+        Box::new((*self).clone())
+    }
+}
+
+impl Gf64Barycentric {
+    /// Compute the barycentric form of the polynomial of degree `n` in
+    /// `GF(2^64)` which passes through the given set of points.
+    ///
+    /// Exactly `n + 1` points with distinct `x` values must be provided.
+    pub fn recover<P: AsRef<[(Gf64Elem, Gf64Elem)]>>(n: u32, points: P) -> Result<Self, Error> {
+        let points = points.as_ref();
+        let needed = gf::num_coefficients(n);
+        if points.len() != needed {
+            return Err(Error::NumPointsMismatch {
+                needed,
+                num_points: points.len(),
+            });
+        }
+        let (xs, ys): (Vec<_>, Vec<_>) = points.iter().copied().unzip();
+
+        // w_j = 1 / \prod_{i=0,i!=j}^k (x_j - x_i)
+        let ws = xs
+            .iter()
+            .enumerate()
+            .map(|(j, &xj)| {
+                xs.iter()
+                    .enumerate()
+                    .filter(|&(i, _)| i != j)
+                    .map(|(_, &xi)| xj - xi)
+                    .reduce(Mul::mul)
+                    .map_or(Some(Gf64Elem::ONE), Gf64Elem::inverse)
+                    .ok_or(Error::NonInvertiblePoint)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self { xs, ys, ws })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use quickcheck::TestResult;
+
+    #[quickcheck]
+    fn mul_associativity(a: Gf64Elem, b: Gf64Elem, c: Gf64Elem) -> bool {
+        (a * b) * c == a * (b * c)
+    }
+
+    #[quickcheck]
+    fn mul_commutativity(a: Gf64Elem, b: Gf64Elem) -> bool {
+        a * b == b * a
+    }
+
+    #[quickcheck]
+    fn distributivity(a: Gf64Elem, b: Gf64Elem, c: Gf64Elem) -> bool {
+        a * (b + c) == a * b + a * c
+    }
+
+    #[quickcheck]
+    fn mul_inverse(a: Gf64Elem) -> TestResult {
+        match a.inverse() {
+            None => TestResult::from_bool(a == Gf64Elem::ZERO),
+            Some(inv) => TestResult::from_bool(a * inv == Gf64Elem::ONE),
+        }
+    }
+
+    #[test]
+    fn reduction() {
+        // x^63 * x = x^64 = x^4 + x^3 + x + 1.
+        assert_eq!(
+            Gf64Elem(1 << 63) * Gf64Elem(2),
+            Gf64Elem(Gf64Elem::TRUNC_POLYNOMIAL)
+        );
+    }
+
+    #[quickcheck]
+    fn barycentric_recover(coeffs: Vec<Gf64Elem>, x: Gf64Elem) -> TestResult {
+        if coeffs.is_empty() || coeffs.len() > 16 {
+            return TestResult::discard();
+        }
+        let poly = Gf64Polynomial(coeffs);
+        let n = gf::degree_of(poly.0.len());
+        let points = (1..=poly.0.len() as u64)
+            .map(Gf64Elem)
+            .map(|x| (x, poly.evaluate(x)))
+            .collect::<Vec<_>>();
+        let recovered = Gf64Barycentric::recover(n, points).unwrap();
+        TestResult::from_bool(
+            recovered.constant() == poly.constant() && recovered.evaluate(x) == poly.evaluate(x),
+        )
+    }
+}
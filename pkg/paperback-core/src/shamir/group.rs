@@ -0,0 +1,244 @@
+/*
+ * paperback: paper backup generator suitable for long-term storage
+ * Copyright (C) 2018-2022 Aleksa Sarai <cyphar@cyphar.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Two-level sharing of a secret between groups of holders, similar to
+//! [SLIP-39][slip39].
+//!
+//! [slip39]: https://github.com/satoshilabs/slips/blob/master/slip-0039.md
+//!
+//! Every group is given one `Shard` of the secret (the group's share), which
+//! is then itself shared between the members of the group with a
+//! [`GroupDealer`]. Recovering the secret requires enough members of each of
+//! at least `threshold` groups -- for example, 3-of-5 members of 2-of-3
+//! groups.
+
+use crate::{
+    shamir::{
+        gf::GfElem,
+        shard::{Shard, ShardGroup, WeightedShard},
+        Dealer, Error, ShardConsistency,
+    },
+    v0::{FromWire, ToWire},
+};
+
+use rand::{CryptoRng, RngCore};
+use std::collections::HashMap;
+use zeroize::Zeroizing;
+
+/// Factory to share one group's share of a secret between the members of the
+/// group (see [`Dealer::next_group`]).
+#[derive(Clone, Debug)]
+pub struct GroupDealer {
+    group: ShardGroup,
+    dealer: Dealer,
+}
+
+impl GroupDealer {
+    /// Returns the group the member `WeightedShard`s belong to.
+    pub fn group(&self) -> &ShardGroup {
+        &self.group
+    }
+
+    /// Returns the number of *unique* member `Shard`s required to recover the
+    /// group's share of the secret.
+    pub fn member_threshold(&self) -> u32 {
+        self.dealer.threshold()
+    }
+
+    /// Generate a new member `WeightedShard` (of weight 1) for the group.
+    pub fn next_shard(&self) -> Result<WeightedShard, Error> {
+        self.next_weighted_shard(1)
+    }
+
+    /// Generate a new member `WeightedShard` for the group, which counts as
+    /// `weight` members towards the member threshold.
+    pub fn next_weighted_shard(&self, weight: u32) -> Result<WeightedShard, Error> {
+        self.next_weighted_shard_with_rng(weight, &mut rand::thread_rng())
+    }
+
+    /// Equivalent to `GroupDealer::next_weighted_shard`, except that the `x`
+    /// values are chosen using `rng` rather than `rand::thread_rng`.
+    pub fn next_weighted_shard_with_rng<R: CryptoRng + RngCore + ?Sized>(
+        &self,
+        weight: u32,
+        rng: &mut R,
+    ) -> Result<WeightedShard, Error> {
+        Ok(WeightedShard {
+            group: Some(self.group),
+            ..self.dealer.next_weighted_shard_with_rng(weight, rng)?
+        })
+    }
+}
+
+impl Dealer {
+    /// Generate a new group of holders, who share a new `Shard` of the secret
+    /// between them such that at least `member_threshold` of them are
+    /// required to recover it.
+    ///
+    /// The returned `GroupDealer` issues member `WeightedShard`s whose
+    /// threshold is the threshold of this `Dealer` (the number of groups
+    /// required), so groups can be mixed freely with each other but not with
+    /// ungrouped shards.
+    pub fn next_group(&self, member_threshold: u32) -> Result<GroupDealer, Error> {
+        self.next_group_with_rng(member_threshold, &mut rand::thread_rng())
+    }
+
+    /// Equivalent to `Dealer::next_group`, except that the group's `x` value
+    /// and the member polynomials are generated using `rng` (see
+    /// `Dealer::new_with_rng`).
+    pub fn next_group_with_rng<R: CryptoRng + RngCore + ?Sized>(
+        &self,
+        member_threshold: u32,
+        rng: &mut R,
+    ) -> Result<GroupDealer, Error> {
+        if member_threshold == 0 {
+            return Err(Error::InconsistentShards(
+                "member threshold must be non-zero",
+            ));
+        }
+        let share = self.next_shard_with_rng(rng)?;
+        let dealer = Dealer::new_with_rng(member_threshold, Zeroizing::new(share.to_wire()), rng)?;
+        Ok(GroupDealer {
+            group: ShardGroup {
+                x: share.x,
+                threshold: self.threshold(),
+            },
+            dealer,
+        })
+    }
+
+    /// Reconstruct an entire `Dealer` from the member `WeightedShard`s of at
+    /// least `threshold` groups (see `Dealer::next_group`).
+    ///
+    /// The share of each group is recovered (and cross-verified, see
+    /// `Dealer::recover_verified`) from its members, and groups without
+    /// enough members are ignored. The returned `ShardConsistency` lists the
+    /// member `Shard`s of every group which was used.
+    pub fn recover_grouped<S: AsRef<[WeightedShard]>>(
+        shards: S,
+    ) -> Result<(Self, ShardConsistency), Error> {
+        let shards = shards.as_ref();
+        let threshold = shards
+            .first()
+            .ok_or(Error::NoShards)?
+            .group()
+            .ok_or(Error::InconsistentShards(
+                "shard is not a member of a group",
+            ))?
+            .threshold();
+
+        let mut groups: HashMap<GfElem, Vec<Shard>> = HashMap::new();
+        for shard in shards {
+            let group = shard.group().ok_or(Error::InconsistentShards(
+                "shard is not a member of a group",
+            ))?;
+            if group.threshold() != threshold {
+                return Err(Error::InconsistentShards(
+                    "shards have different group thresholds",
+                ));
+            }
+            groups
+                .entry(group.x)
+                .or_default()
+                .extend_from_slice(shard.shards());
+        }
+
+        let mut shares = Vec::new();
+        let mut consistency = ShardConsistency::default();
+        for (x, members) in groups {
+            if members.len() < members[0].threshold() as usize {
+                continue;
+            }
+            let (dealer, members) = Self::recover_verified(members)?;
+            let share = Shard::from_wire(Zeroizing::new(dealer.secret()).as_slice())
+                .map_err(|_| Error::InconsistentShards("group share is corrupted"))?;
+            if share.x != x || share.threshold() != threshold {
+                return Err(Error::InconsistentShards(
+                    "group share doesn't match its group",
+                ));
+            }
+            shares.push(share);
+            consistency.extend(members);
+        }
+        if shares.len() < threshold as usize {
+            return Err(Error::WrongShardCount {
+                needed: threshold,
+                given: shares.len(),
+            });
+        }
+
+        let (dealer, _) = Self::recover_verified(shares)?;
+        Ok((dealer, consistency))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn group_recover() {
+        let secret = b"the quick brown fox jumps over the lazy dog";
+        let dealer = Dealer::new(2, secret).unwrap();
+        let groups = (0..3)
+            .map(|_| dealer.next_group(3).unwrap())
+            .collect::<Vec<_>>();
+        let members = groups
+            .iter()
+            .map(|group| {
+                (0..5)
+                    .map(|_| group.next_shard().unwrap())
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        assert!(members
+            .iter()
+            .flatten()
+            .all(|shard| shard.threshold() == 2 && shard.member_threshold() == 3));
+
+        // Two complete groups are enough, even with an incomplete third group.
+        let shards = [&members[0][..3], &members[2][1..4], &members[1][..2]].concat();
+        let (recovered, consistency) = Dealer::recover_grouped(&shards).unwrap();
+        assert_eq!(recovered.secret(), secret);
+        assert_eq!(consistency.consistent().len(), 6);
+
+        // One complete group is not.
+        let shards = [&members[0][..], &members[1][..2]].concat();
+        assert!(Dealer::recover_grouped(&shards).is_err());
+
+        // Ungrouped shards cannot be mixed in.
+        let mut shards = [&members[0][..3], &members[1][..2]].concat();
+        shards.push(dealer.next_shard().unwrap().into());
+        assert!(Dealer::recover_grouped(&shards).is_err());
+    }
+
+    #[test]
+    fn group_weighted_members() {
+        let secret = b"secret";
+        let dealer = Dealer::new(2, secret).unwrap();
+        let group1 = dealer.next_group(3).unwrap();
+        let group2 = dealer.next_group(1).unwrap();
+        let shards = [
+            group1.next_weighted_shard(2).unwrap(),
+            group1.next_shard().unwrap(),
+            group2.next_shard().unwrap(),
+        ];
+        let (recovered, _) = Dealer::recover_grouped(shards).unwrap();
+        assert_eq!(recovered.secret(), secret);
+    }
+}
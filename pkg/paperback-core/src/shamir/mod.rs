@@ -18,24 +18,148 @@
 
 //! This package implements a Shamir Secret Sharing scheme in GF(2^32), meaning
 //! that the data is split up into 4-byte chunks (and all x and y values are
-//! 32-bit integers).
+//! 32-bit integers). Large secrets can instead be shared in GF(2^64) or
+//! GF(2^128) (see [`FieldSize`]), where the data is split into 8-byte or
+//! 16-byte chunks but x values are still 32-bit integers.
+//!
+//! ## Weighted Shards ##
+//! A `Dealer` can also issue a [`WeightedShard`] (see
+//! [`Dealer::next_weighted_shard`]), which bundles several `Shard`s for a
+//! single holder so that they count as more than one `Shard` towards the
+//! threshold. Recovery works on the underlying `Shard`s of every
+//! `WeightedShard`, so holders of different weights can be freely combined.
+//!
+//! ## Groups ##
+//! A `Dealer` can also deal each of its `Shard`s to a group of holders rather
+//! than a single holder (see [`Dealer::next_group`]), in the style of
+//! [SLIP-39][slip39]. The secret can then be recovered from enough members of
+//! each of at least `threshold` groups (see [`Dealer::recover_grouped`]).
+//!
+//! [slip39]: https://github.com/satoshilabs/slips/blob/master/slip-0039.md
 //!
 //! ## Security ##
-//! **This implementation is not remotely constant time and has not been
-//! reviewed by any cryptographers. This was implemented by me from scratch
+//! The field arithmetic and the comparisons of secret values are constant
+//! time (see [`gf`] for the exact guarantees), and shares and secrets are
+//! wiped when dropped. **However, this implementation has not been reviewed
+//! by any cryptographers. This was implemented by me from scratch
 //! because there was no alternative crate implementing the necessary
 //! algorithms. Of the few SSS crates I found, all had security bugs and none
 //! provided for 32-bit x-values which is a requirement of paperback's design.**
 
+use crate::ErrorCode;
+
+mod correct;
 mod dealer;
-mod gf;
+pub mod gf;
+pub mod gf128;
+pub mod gf64;
+mod group;
 pub(crate) mod shard;
+mod stream;
+mod validation;
+
+pub use dealer::{Dealer, PartialSecret, ShardConsistency};
+pub use group::GroupDealer;
+pub use shard::{PartialShard, Shard, ShardGroup, WeightedShard};
+pub use stream::{StreamedShard, StreamingDealer};
+pub use validation::{ValidationError, Violation};
+
+/// The field in which the `y` values of a `Shard` are computed.
+///
+/// `GF(2^64)` halves (and `GF(2^128)` quarters) the number of polynomials (and
+/// thus interpolations) per byte of secret, at the cost of slightly larger
+/// shards for short secrets.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum FieldSize {
+    /// `GF(2^32)`, compatible with all versions of paperback.
+    #[default]
+    Bits32,
+    /// `GF(2^64)`.
+    Bits64,
+    /// `GF(2^128)`.
+    Bits128,
+}
+
+impl FieldSize {
+    /// The number of bits in an element of the field.
+    pub fn bits(self) -> u32 {
+        match self {
+            Self::Bits32 => 32,
+            Self::Bits64 => 64,
+            Self::Bits128 => 128,
+        }
+    }
+
+    /// The number of bytes of secret stored in each `y` value.
+    pub(crate) fn elem_len(self) -> usize {
+        self.bits() as usize / 8
+    }
 
-pub use dealer::Dealer;
-pub use shard::Shard;
+    pub(crate) fn from_bits(bits: u32) -> Option<Self> {
+        match bits {
+            32 => Some(Self::Bits32),
+            64 => Some(Self::Bits64),
+            128 => Some(Self::Bits128),
+            _ => None,
+        }
+    }
+}
 
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
     #[error("lagrange interpolation failed: {0}")]
     LagrangeError(#[from] gf::Error),
+
+    #[error("all {0} possible shard x values have already been issued")]
+    FieldExhausted(gf::GfElemPrimitive),
+
+    #[error("shard x value must be non-zero")]
+    ZeroX,
+
+    #[error("weighted shard weight must be non-zero")]
+    ZeroWeight,
+
+    #[error("threshold must be non-zero")]
+    ZeroThreshold,
+
+    #[error("a shard has already been issued with x value {0}")]
+    DuplicateX(gf::GfElemPrimitive),
+
+    #[error("no shards were provided")]
+    NoShards,
+
+    #[error("inconsistent shards: {0}")]
+    InconsistentShards(&'static str),
+
+    #[error("wrong number of shards: {needed} are required but {given} were given")]
+    WrongShardCount { needed: u32, given: usize },
+
+    #[error("more than {max_errors} shards are corrupted, so they cannot be corrected")]
+    Uncorrectable { max_errors: usize },
+
+    #[error("failed to read or write shard data: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("dealer cannot safely issue the shard: {0}")]
+    Validation(#[from] ValidationError),
+}
+
+impl Error {
+    /// The stable code of this error.
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Self::LagrangeError(err) => err.code(),
+            Self::FieldExhausted(_) => ErrorCode::new(200, "field-exhausted"),
+            Self::ZeroX => ErrorCode::new(201, "zero-x"),
+            Self::DuplicateX(_) => ErrorCode::new(202, "duplicate-x"),
+            Self::NoShards => ErrorCode::new(203, "no-shards"),
+            Self::InconsistentShards(_) => ErrorCode::new(204, "inconsistent-shards"),
+            Self::WrongShardCount { .. } => ErrorCode::new(205, "wrong-shard-count"),
+            Self::Uncorrectable { .. } => ErrorCode::new(206, "uncorrectable"),
+            Self::Io(_) => ErrorCode::new(208, "shard-io"),
+            Self::ZeroWeight => ErrorCode::new(209, "zero-weight"),
+            Self::Validation(_) => ErrorCode::new(210, "validation-failed"),
+            Self::ZeroThreshold => ErrorCode::new(211, "zero-threshold"),
+        }
+    }
 }
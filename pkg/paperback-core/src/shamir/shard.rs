@@ -17,11 +17,20 @@
  */
 
 use crate::{
-    shamir::gf::{GfElem, GfElemPrimitive},
-    v0::{FromWire, ShardId, ToWire},
+    shamir::{
+        gf::{GfElem, GfElemPrimitive},
+        gf128::Gf128Elem,
+        gf64::Gf64Elem,
+        FieldSize,
+    },
+    v0::{FromWire, ParseError, ParseSection, ShardId, ToWire},
 };
 
+use std::{fmt, ops::Range};
+
+use subtle::{Choice, ConstantTimeEq};
 use unsigned_varint::{encode as varuint_encode, nom as varuint_nom};
+use zeroize::Zeroize;
 
 /// Piece of a secret which has been sharded with [Shamir Secret Sharing][sss].
 ///
@@ -29,9 +38,66 @@ use unsigned_varint::{encode as varuint_encode, nom as varuint_nom};
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct Shard {
     pub(super) x: GfElem,
-    pub(super) ys: Vec<GfElem>,
+    pub(super) ys: ShardYs,
     pub(super) secret_len: usize,
-    pub(super) threshold: GfElemPrimitive,
+    pub(super) threshold: u32,
+}
+
+/// The `y` values of a `Shard`, in the field the secret was shared in.
+#[derive(Clone, Eq, PartialEq)]
+pub(super) enum ShardYs {
+    Bits32(Vec<GfElem>),
+    Bits64(Vec<Gf64Elem>),
+    Bits128(Vec<Gf128Elem>),
+}
+
+// The y values are parts of the secret, so only describe their shape.
+impl fmt::Debug for ShardYs {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ShardYs")
+            .field("field_size", &self.field_size())
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+impl Drop for ShardYs {
+    fn drop(&mut self) {
+        match self {
+            Self::Bits32(ys) => ys.zeroize(),
+            Self::Bits64(ys) => ys.zeroize(),
+            Self::Bits128(ys) => ys.zeroize(),
+        }
+    }
+}
+
+impl ConstantTimeEq for ShardYs {
+    fn ct_eq(&self, other: &Self) -> Choice {
+        match (self, other) {
+            (Self::Bits32(a), Self::Bits32(b)) => a.as_slice().ct_eq(b.as_slice()),
+            (Self::Bits64(a), Self::Bits64(b)) => a.as_slice().ct_eq(b.as_slice()),
+            (Self::Bits128(a), Self::Bits128(b)) => a.as_slice().ct_eq(b.as_slice()),
+            _ => Choice::from(0),
+        }
+    }
+}
+
+impl ShardYs {
+    pub(super) fn len(&self) -> usize {
+        match self {
+            Self::Bits32(ys) => ys.len(),
+            Self::Bits64(ys) => ys.len(),
+            Self::Bits128(ys) => ys.len(),
+        }
+    }
+
+    pub(super) fn field_size(&self) -> FieldSize {
+        match self {
+            Self::Bits32(_) => FieldSize::Bits32,
+            Self::Bits64(_) => FieldSize::Bits64,
+            Self::Bits128(_) => FieldSize::Bits128,
+        }
+    }
 }
 
 impl Shard {
@@ -50,6 +116,222 @@ impl Shard {
     pub fn threshold(&self) -> u32 {
         self.threshold
     }
+
+    /// Returns the field in which the secret was shared.
+    pub fn field_size(&self) -> FieldSize {
+        self.ys.field_size()
+    }
+
+    /// Returns the number of `y` values (field elements) in the `Shard`, one
+    /// for each chunk of the secret.
+    pub fn num_elements(&self) -> usize {
+        self.ys.len()
+    }
+
+    /// Returns the largest possible `to_wire` length of any sister `Shard`.
+    ///
+    /// The x and y values are varuint-encoded, so their encoded length depends
+    /// on their (random) values. All other fields are identical for every
+    /// sister shard.
+    pub(crate) fn max_wire_len(&self) -> usize {
+        let max_u32_len = varuint_encode::u32_buffer().len();
+        let max_u64_len = varuint_encode::u64_buffer().len();
+        let max_u128_len = varuint_encode::u128_buffer().len();
+        let u32_len =
+            |v: GfElemPrimitive| varuint_encode::u32(v, &mut varuint_encode::u32_buffer()).len();
+        let u64_len = |v: u64| varuint_encode::u64(v, &mut varuint_encode::u64_buffer()).len();
+        let u128_len = |v: u128| varuint_encode::u128(v, &mut varuint_encode::u128_buffer()).len();
+        let ys_slack = match &self.ys {
+            ShardYs::Bits32(ys) => ys
+                .iter()
+                .map(|v| max_u32_len - u32_len(v.inner()))
+                .sum::<usize>(),
+            ShardYs::Bits64(ys) => ys
+                .iter()
+                .map(|v| max_u64_len - u64_len(v.inner()))
+                .sum::<usize>(),
+            ShardYs::Bits128(ys) => ys
+                .iter()
+                .map(|v| max_u128_len - u128_len(v.inner()))
+                .sum::<usize>(),
+        };
+        let slack = max_u32_len - u32_len(self.x.inner()) + ys_slack;
+        self.to_wire().len() + slack
+    }
+}
+
+/// A `Shard` which is missing some of its `y` values (because part of the
+/// shard was unreadable), for recovery with `Dealer::recover_partial`.
+///
+/// The secret is shared in independent chunks of `FieldSize` bytes, and each
+/// `y` value of a shard is the share of one chunk. Chunks which are missing
+/// from too many shards cannot be recovered, but the rest of the secret can.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PartialShard {
+    pub(super) shard: Shard,
+    pub(super) missing: Vec<bool>,
+}
+
+impl From<Shard> for PartialShard {
+    fn from(shard: Shard) -> Self {
+        Self::new(shard)
+    }
+}
+
+impl PartialShard {
+    /// Wrap a `Shard`, with none of its chunks marked as missing.
+    pub fn new(shard: Shard) -> Self {
+        let missing = vec![false; shard.num_elements()];
+        Self { shard, missing }
+    }
+
+    /// Returns the number of chunks the secret was shared in.
+    pub fn num_chunks(&self) -> usize {
+        self.missing.len()
+    }
+
+    /// Returns the byte range of the secret shared by the given chunk.
+    ///
+    /// The range of the last chunk may extend past the end of the secret (if
+    /// the secret length is not a multiple of the chunk size).
+    pub fn chunk_range(&self, chunk: usize) -> Range<usize> {
+        let elem_len = self.shard.field_size().elem_len();
+        chunk * elem_len..(chunk + 1) * elem_len
+    }
+
+    /// Mark the `y` value of the given chunk as unreadable, so that it is not
+    /// used for recovery.
+    pub fn mark_missing(&mut self, chunk: usize) -> &mut Self {
+        assert!(
+            chunk < self.num_chunks(),
+            "chunk {} out of range for shard with {} chunks",
+            chunk,
+            self.num_chunks()
+        );
+        self.missing[chunk] = true;
+        self
+    }
+
+    /// Returns whether the `y` value of the given chunk is unreadable.
+    pub fn is_missing(&self, chunk: usize) -> bool {
+        self.missing[chunk]
+    }
+
+    /// Returns the underlying `Shard`.
+    pub fn shard(&self) -> &Shard {
+        &self.shard
+    }
+}
+
+/// The group a grouped `WeightedShard` belongs to (see
+/// [`Dealer::next_group`](crate::shamir::Dealer::next_group)).
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct ShardGroup {
+    pub(super) x: GfElem,
+    pub(super) threshold: u32,
+}
+
+impl ShardGroup {
+    /// Returns the *unique* identifier of the group, which is the identifier
+    /// of the group's share of the secret.
+    pub fn id(&self) -> ShardId {
+        multibase::encode(multibase::Base::Base32Z, &self.x.to_bytes())
+    }
+
+    /// Returns the number of groups required to recover the stored secret.
+    pub fn threshold(&self) -> u32 {
+        self.threshold
+    }
+}
+
+/// A set of sister `Shard`s (with distinct `x` values) given to a single
+/// holder, so that the holder counts as `weight` shards towards the threshold.
+///
+/// Weighted shards allow policies which a plain threshold cannot express. For
+/// example, "any 3 family members, or 1 family member and my lawyer" is a
+/// threshold of 3, where each family member holds a `WeightedShard` of weight
+/// 1 and the lawyer holds one of weight 2.
+///
+/// A `WeightedShard` can also be a member of a [`ShardGroup`], in which case
+/// its `Shard`s only recover the group's share of the secret.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct WeightedShard {
+    pub(super) group: Option<ShardGroup>,
+    pub(super) shards: Vec<Shard>,
+}
+
+impl From<Shard> for WeightedShard {
+    fn from(shard: Shard) -> Self {
+        Self {
+            group: None,
+            shards: vec![shard],
+        }
+    }
+}
+
+impl WeightedShard {
+    /// Returns the identifier of the `WeightedShard`, which is the identifier
+    /// of its first `Shard`.
+    pub fn id(&self) -> ShardId {
+        self.primary().id()
+    }
+
+    /// Returns the number of `Shard`s the `WeightedShard` counts as towards
+    /// the threshold.
+    pub fn weight(&self) -> u32 {
+        self.shards.len() as u32
+    }
+
+    /// Returns the total weight of sister `WeightedShard`s required to recover
+    /// the stored secret. For grouped shards, this is the number of groups
+    /// required (see `WeightedShard::member_threshold`).
+    pub fn threshold(&self) -> u32 {
+        match self.group {
+            Some(group) => group.threshold(),
+            None => self.primary().threshold(),
+        }
+    }
+
+    /// Returns the group the `WeightedShard` is a member of, if any.
+    pub fn group(&self) -> Option<&ShardGroup> {
+        self.group.as_ref()
+    }
+
+    /// Returns the total weight of sister `WeightedShard`s in the same group
+    /// required to recover the group's share of the secret. For ungrouped
+    /// shards, this is the same as `WeightedShard::threshold`.
+    pub fn member_threshold(&self) -> u32 {
+        self.primary().threshold()
+    }
+
+    /// Returns the first `Shard` of the `WeightedShard`. All of the `Shard`s
+    /// of a `WeightedShard` share the same field and threshold.
+    pub fn primary(&self) -> &Shard {
+        &self.shards[0]
+    }
+
+    /// Returns the underlying `Shard`s.
+    pub fn shards(&self) -> &[Shard] {
+        &self.shards
+    }
+
+    /// Returns the largest possible `to_wire` length of any sister
+    /// `WeightedShard` of the same weight (in any group, for grouped shards).
+    pub(crate) fn max_wire_len(&self) -> usize {
+        let group_slack = match self.group {
+            Some(group) => {
+                varuint_encode::u32_buffer().len()
+                    - varuint_encode::u32(group.x.inner(), &mut varuint_encode::u32_buffer()).len()
+            }
+            None => 0,
+        };
+        let slack = self
+            .shards
+            .iter()
+            .map(|shard| shard.max_wire_len() - shard.to_wire().len())
+            .sum::<usize>();
+        self.to_wire().len() + group_slack + slack
+    }
 }
 
 pub fn parse_id(id: ShardId) -> Result<GfElem, multibase::Error> {
@@ -57,10 +339,27 @@ pub fn parse_id(id: ShardId) -> Result<GfElem, multibase::Error> {
     Ok(GfElem::from_bytes(data))
 }
 
+// Tag of the (0, tag) prefix of a weighted shard with more than one shard.
+// Tags which are the bit size of a field specify the field.
+const WEIGHTED_TAG: u8 = 2;
+
+// Tag of the (0, tag) prefix of a weighted shard which is a member of a group.
+const GROUP_TAG: u8 = 3;
+
 impl ToWire for Shard {
     fn to_wire(&self) -> Vec<u8> {
         let mut bytes = vec![];
 
+        // Optional fields are negotiated with (0, tag) prefixes, in increasing
+        // tag order. A real shard never has x = 0, so shards without optional
+        // fields are encoded exactly as they were before any were supported.
+        if self.field_size() != FieldSize::default() {
+            bytes.push(0);
+            varuint_encode::u32(self.field_size().bits(), &mut varuint_encode::u32_buffer())
+                .iter()
+                .for_each(|b| bytes.push(*b));
+        }
+
         // Encode x-value.
         varuint_encode::u32(self.x.inner(), &mut varuint_encode::u32_buffer())
             .iter()
@@ -70,10 +369,27 @@ impl ToWire for Shard {
         varuint_encode::usize(self.ys.len(), &mut varuint_encode::usize_buffer())
             .iter()
             .copied()
-            .chain(self.ys.iter().flat_map(|y| {
-                varuint_encode::u32(y.inner(), &mut varuint_encode::u32_buffer()).to_owned()
-            }))
             .for_each(|b| bytes.push(b));
+        match &self.ys {
+            ShardYs::Bits32(ys) => ys
+                .iter()
+                .flat_map(|y| {
+                    varuint_encode::u32(y.inner(), &mut varuint_encode::u32_buffer()).to_owned()
+                })
+                .for_each(|b| bytes.push(b)),
+            ShardYs::Bits64(ys) => ys
+                .iter()
+                .flat_map(|y| {
+                    varuint_encode::u64(y.inner(), &mut varuint_encode::u64_buffer()).to_owned()
+                })
+                .for_each(|b| bytes.push(b)),
+            ShardYs::Bits128(ys) => ys
+                .iter()
+                .flat_map(|y| {
+                    varuint_encode::u128(y.inner(), &mut varuint_encode::u128_buffer()).to_owned()
+                })
+                .for_each(|b| bytes.push(b)),
+        }
 
         // Encode threshold.
         varuint_encode::u32(self.threshold, &mut varuint_encode::u32_buffer())
@@ -90,20 +406,50 @@ impl ToWire for Shard {
 }
 
 impl FromWire for Shard {
-    fn from_wire_partial(input: &[u8]) -> Result<(&[u8], Self), String> {
-        use nom::{combinator::complete, multi::many_m_n, IResult};
+    fn from_wire_partial(input: &[u8]) -> Result<(&[u8], Self), ParseError> {
+        use nom::{
+            bytes::complete::tag,
+            combinator::{complete, map_opt, verify},
+            multi::many_m_n,
+            sequence::preceded,
+            IResult,
+        };
 
         fn parse(input: &[u8]) -> IResult<&[u8], Shard> {
+            // An explicit GF(2^32) prefix is not permitted, so that each shard
+            // has exactly one encoding.
+            let (input, field_size) = match input.first() {
+                Some(0) => preceded(
+                    tag([0]),
+                    verify(
+                        map_opt(varuint_nom::u32, FieldSize::from_bits),
+                        |&field_size| field_size != FieldSize::default(),
+                    ),
+                )(input)?,
+                _ => (input, FieldSize::default()),
+            };
+
             let (input, x) = varuint_nom::u32(input)?;
             let x = GfElem::from_inner(x);
 
             let (input, ys_length) = varuint_nom::usize(input)?;
-            let (input, ys) = many_m_n(ys_length, ys_length, varuint_nom::u32)(input)?;
-            let ys = ys
-                .iter()
-                .copied()
-                .map(GfElem::from_inner)
-                .collect::<Vec<_>>();
+            let (input, ys) = match field_size {
+                FieldSize::Bits32 => {
+                    let (input, ys) = many_m_n(ys_length, ys_length, varuint_nom::u32)(input)?;
+                    let ys = ys.into_iter().map(GfElem::from_inner).collect();
+                    (input, ShardYs::Bits32(ys))
+                }
+                FieldSize::Bits64 => {
+                    let (input, ys) = many_m_n(ys_length, ys_length, varuint_nom::u64)(input)?;
+                    let ys = ys.into_iter().map(Gf64Elem::from_inner).collect();
+                    (input, ShardYs::Bits64(ys))
+                }
+                FieldSize::Bits128 => {
+                    let (input, ys) = many_m_n(ys_length, ys_length, varuint_nom::u128)(input)?;
+                    let ys = ys.into_iter().map(Gf128Elem::from_inner).collect();
+                    (input, ShardYs::Bits128(ys))
+                }
+            };
 
             let (input, threshold) = varuint_nom::u32(input)?;
             let (input, secret_len) = varuint_nom::usize(input)?;
@@ -120,24 +466,152 @@ impl FromWire for Shard {
         }
         let mut parse = complete(parse);
 
-        let (input, shard) = parse(input).map_err(|err| format!("{:?}", err))?;
+        let (input, shard) =
+            parse(input).map_err(ParseError::expecting(ParseSection::Payload, "shard data"))?;
 
         Ok((input, shard))
     }
 }
 
+impl ToWire for WeightedShard {
+    fn to_wire(&self) -> Vec<u8> {
+        let mut bytes = vec![];
+
+        // Grouped shards are prefixed with (0, GROUP_TAG), the group's x value
+        // and the number of groups required. Older versions reject them as an
+        // unknown field size.
+        if let Some(group) = self.group {
+            bytes.extend_from_slice(&[0, GROUP_TAG]);
+            varuint_encode::u32(group.x.inner(), &mut varuint_encode::u32_buffer())
+                .iter()
+                .chain(varuint_encode::u32(
+                    group.threshold,
+                    &mut varuint_encode::u32_buffer(),
+                ))
+                .for_each(|b| bytes.push(*b));
+        }
+
+        // A weighted shard of weight 1 is encoded exactly as its only shard,
+        // so that it can be read by older versions of paperback. Heavier
+        // shards are prefixed with (0, WEIGHTED_TAG) and the number of extra
+        // shards, which older versions reject as an unknown field size.
+        let (primary, extra) = self
+            .shards
+            .split_first()
+            .expect("weighted shard must contain at least one shard");
+        if !extra.is_empty() {
+            bytes.extend_from_slice(&[0, WEIGHTED_TAG]);
+            varuint_encode::usize(extra.len(), &mut varuint_encode::usize_buffer())
+                .iter()
+                .for_each(|b| bytes.push(*b));
+        }
+
+        // Encode shards.
+        bytes.append(&mut primary.to_wire());
+        for shard in extra {
+            bytes.append(&mut shard.to_wire());
+        }
+
+        bytes
+    }
+}
+
+impl FromWire for WeightedShard {
+    fn from_wire_partial(input: &[u8]) -> Result<(&[u8], Self), ParseError> {
+        use nom::{
+            bytes::complete::tag,
+            combinator::{complete, map, opt},
+            sequence::{pair, preceded},
+            IResult,
+        };
+
+        fn parse(input: &[u8]) -> IResult<&[u8], (Option<ShardGroup>, Option<usize>)> {
+            let (input, group) = opt(preceded(
+                tag([0, GROUP_TAG]),
+                map(
+                    pair(map(varuint_nom::u32, GfElem::from_inner), varuint_nom::u32),
+                    |(x, threshold)| ShardGroup { x, threshold },
+                ),
+            ))(input)?;
+            let (input, num_extra) =
+                opt(preceded(tag([0, WEIGHTED_TAG]), varuint_nom::usize))(input)?;
+
+            Ok((input, (group, num_extra)))
+        }
+        let mut parse = complete(parse);
+
+        let (rest, (group, num_extra)) = parse(input).map_err(ParseError::expecting(
+            ParseSection::Payload,
+            "a shard group and weight",
+        ))?;
+        if group.map_or(false, |group| group.x == GfElem::ZERO) {
+            return Err(ParseError::new(
+                ParseSection::Payload,
+                input,
+                "a shard group with a non-zero x value",
+                "a zero x value",
+            ));
+        }
+        if num_extra == Some(0) {
+            return Err(ParseError::new(
+                ParseSection::Payload,
+                input,
+                "a weighted shard prefix for several shards",
+                "a weighted shard prefix for a single shard",
+            ));
+        }
+        let mut input = rest;
+        let mut shards = Vec::new();
+        for _ in 0..=num_extra.unwrap_or_default() {
+            let (rest, shard) = Shard::from_wire_partial(input)?;
+            shards.push(shard);
+            input = rest;
+        }
+
+        Ok((input, WeightedShard { group, shards }))
+    }
+}
+
 #[cfg(test)]
 impl quickcheck::Arbitrary for Shard {
     fn arbitrary(g: &mut quickcheck::Gen) -> Self {
+        // x = 0 is never issued (and is reserved for the field prefix).
+        let mut x = GfElem::arbitrary(g);
+        while x == GfElem::ZERO {
+            x = GfElem::arbitrary(g);
+        }
+        let ys = match u8::arbitrary(g) % 3 {
+            0 => ShardYs::Bits32((0..g.size()).map(|_| GfElem::arbitrary(g)).collect()),
+            1 => ShardYs::Bits64((0..g.size()).map(|_| Gf64Elem::arbitrary(g)).collect()),
+            _ => ShardYs::Bits128((0..g.size()).map(|_| Gf128Elem::arbitrary(g)).collect()),
+        };
         Self {
-            x: GfElem::arbitrary(g),
-            ys: (0..g.size()).map(|_| GfElem::arbitrary(g)).collect(),
+            x,
+            ys,
             secret_len: usize::arbitrary(g),
             threshold: u32::arbitrary(g),
         }
     }
 }
 
+#[cfg(test)]
+impl quickcheck::Arbitrary for WeightedShard {
+    fn arbitrary(g: &mut quickcheck::Gen) -> Self {
+        Self {
+            group: match bool::arbitrary(g) {
+                true => Some(ShardGroup {
+                    x: GfElem::from_inner(u32::arbitrary(g).max(1)),
+                    threshold: u32::arbitrary(g),
+                }),
+                false => None,
+            },
+            shards: (0..=u8::arbitrary(g) % 3)
+                .map(|_| Shard::arbitrary(g))
+                .collect(),
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -147,4 +621,70 @@ mod test {
         let shard2 = Shard::from_wire(&shard.to_wire()).unwrap();
         shard == shard2
     }
+
+    #[quickcheck]
+    fn shard_max_wire_len(shard: Shard) -> bool {
+        shard.to_wire().len() <= shard.max_wire_len()
+    }
+
+    #[quickcheck]
+    fn weighted_shard_bytes_roundtrip(shard: WeightedShard) -> bool {
+        let shard2 = WeightedShard::from_wire(shard.to_wire()).unwrap();
+        shard == shard2
+    }
+
+    #[quickcheck]
+    fn weighted_shard_single_encoding(shard: Shard) -> bool {
+        WeightedShard::from(shard.clone()).to_wire() == shard.to_wire()
+    }
+
+    #[test]
+    fn shard_ys_ct_eq() {
+        let ys = ShardYs::Bits32(vec![GfElem::from(1), GfElem::from(2)]);
+        assert!(bool::from(ys.ct_eq(&ys.clone())));
+        assert!(!bool::from(ys.ct_eq(&ShardYs::Bits32(vec![
+            GfElem::from(1),
+            GfElem::from(3)
+        ]))));
+        assert!(!bool::from(
+            ys.ct_eq(&ShardYs::Bits32(vec![GfElem::from(1)]))
+        ));
+        assert!(!bool::from(ys.ct_eq(&ShardYs::Bits64(vec![
+            Gf64Elem::from(1),
+            Gf64Elem::from(2)
+        ]))));
+    }
+
+    #[test]
+    fn shard_field_prefix() {
+        let shard = Shard {
+            x: GfElem::from(7),
+            ys: ShardYs::Bits32(vec![GfElem::from(1)]),
+            secret_len: 3,
+            threshold: 2,
+        };
+        // GF(2^32) shards have no prefix, and an explicit one is rejected.
+        let wire = shard.to_wire();
+        assert_eq!(wire[0], 7);
+        assert!(Shard::from_wire([&[0, 32][..], &wire].concat()).is_err());
+        // Unknown fields are rejected.
+        assert!(Shard::from_wire([&[0, 129, 1][..], &wire].concat()).is_err());
+
+        let shard = Shard {
+            ys: ShardYs::Bits64(vec![Gf64Elem::from(u64::MAX)]),
+            ..shard
+        };
+        let wire = shard.to_wire();
+        assert_eq!(wire[..3], [0, 64, 7]);
+        assert_eq!(Shard::from_wire(wire).unwrap(), shard);
+
+        // GF(2^128) shards have a two-byte prefix (128 as a varuint).
+        let shard128 = Shard {
+            ys: ShardYs::Bits128(vec![Gf128Elem::from(u128::MAX)]),
+            ..shard
+        };
+        let wire128 = shard128.to_wire();
+        assert_eq!(wire128[..4], [0, 128, 1, 7]);
+        assert_eq!(Shard::from_wire(wire128).unwrap(), shard128);
+    }
 }
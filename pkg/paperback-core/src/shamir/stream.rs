@@ -0,0 +1,447 @@
+/*
+ * paperback: paper backup generator suitable for long-term storage
+ * Copyright (C) 2018-2022 Aleksa Sarai <cyphar@cyphar.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Sharding of secrets which are too large to hold in memory.
+//!
+//! A [`StreamingDealer`] reads the secret incrementally and writes the `y`
+//! values of every shard to a separate stream as it goes, generating (and
+//! discarding) one polynomial per field element of the secret, so memory use
+//! is bounded regardless of the size of the secret. The shards are identical
+//! to those produced by a [`Dealer`], except that their `y` values are stored
+//! separately from the rest of the shard (see [`StreamedShard`]).
+
+use crate::{
+    par::*,
+    shamir::{
+        dealer::allocate_xs,
+        gf::{EvaluablePolynomial, GfElem, GfElemPrimitive, GfPolynomial},
+        gf128::{EvaluablePolynomial128, Gf128Elem, Gf128Polynomial},
+        gf64::{EvaluablePolynomial64, Gf64Elem, Gf64Polynomial},
+        shard::{Shard, ShardYs},
+        Dealer, Error, FieldSize,
+    },
+};
+
+use std::{
+    collections::HashSet,
+    io::{self, ErrorKind, Read, Write},
+};
+
+// Number of bytes of secret processed (in parallel) at a time.
+const BLOCK_SIZE: usize = 64 * 1024;
+
+// Fill as much of buf as possible, returning less than buf.len() only once the
+// reader has reached EOF.
+fn read_full<R: Read>(reader: &mut R, buf: &mut [u8]) -> io::Result<usize> {
+    let mut len = 0;
+    while len < buf.len() {
+        match reader.read(&mut buf[len..]) {
+            Ok(0) => break,
+            Ok(n) => len += n,
+            Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(len)
+}
+
+/// The parameters of a `Shard` dealt by a [`StreamingDealer`]. The `y` values
+/// of the shard are stored separately, as a stream of little-endian field
+/// elements.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct StreamedShard {
+    x: GfElem,
+    secret_len: usize,
+    threshold: u32,
+    field_size: FieldSize,
+}
+
+impl StreamedShard {
+    /// Returns the *unique* identifier of the shard (see [`Shard::id`]).
+    pub fn id(&self) -> String {
+        multibase::encode(multibase::Base::Base32Z, self.x.to_bytes())
+    }
+
+    /// Returns the number of *unique* sister shards required to recover the
+    /// stored secret.
+    pub fn threshold(&self) -> u32 {
+        self.threshold
+    }
+
+    /// Returns the length of the stored secret.
+    pub fn secret_len(&self) -> usize {
+        self.secret_len
+    }
+
+    /// Returns the field in which the secret was shared.
+    pub fn field_size(&self) -> FieldSize {
+        self.field_size
+    }
+
+    /// Returns the length of the stream of `y` values of the shard.
+    pub fn ys_len(&self) -> usize {
+        self.secret_len.div_ceil(self.field_size.elem_len()) * self.field_size.elem_len()
+    }
+
+    /// Combine the shard with its `y` values into a regular `Shard`. This
+    /// requires the entire stream of `y` values to be held in memory.
+    pub fn into_shard(self, ys: &[u8]) -> Result<Shard, Error> {
+        if ys.len() != self.ys_len() {
+            return Err(Error::InconsistentShards(
+                "shard data length doesn't match secret length",
+            ));
+        }
+        let ys = match self.field_size {
+            FieldSize::Bits32 => ShardYs::Bits32(ys.chunks(4).map(GfElem::from_bytes).collect()),
+            FieldSize::Bits64 => ShardYs::Bits64(ys.chunks(8).map(Gf64Elem::from_bytes).collect()),
+            FieldSize::Bits128 => {
+                ShardYs::Bits128(ys.chunks(16).map(Gf128Elem::from_bytes).collect())
+            }
+        };
+        Ok(Shard {
+            x: self.x,
+            ys,
+            secret_len: self.secret_len,
+            threshold: self.threshold,
+        })
+    }
+}
+
+/// Factory to share a secret read from a stream using [Shamir Secret
+/// Sharing][sss], in bounded memory. See the [module documentation](self).
+///
+/// [sss]: https://en.wikipedia.org/wiki/Shamir%27s_Secret_Sharing
+///
+/// Unlike a [`Dealer`], the polynomials are not kept after they have been
+/// evaluated, so every shard must be dealt at once (with
+/// [`StreamingDealer::deal`]).
+#[derive(Debug)]
+pub struct StreamingDealer<R> {
+    threshold: u32,
+    field_size: FieldSize,
+    secret: R,
+}
+
+impl<R: Read> StreamingDealer<R> {
+    /// Deal one shard into each of the `ys` writers, reading the secret until
+    /// EOF. The `y` values are written in small pieces, so the writers should
+    /// be buffered.
+    pub fn deal<W: Write>(mut self, ys: &mut [W]) -> Result<Vec<StreamedShard>, Error> {
+        let k = self.threshold.checked_sub(1).ok_or(Error::ZeroThreshold)?;
+        let field_size = self.field_size;
+        let elem_len = field_size.elem_len();
+        let xs = {
+            let mut rng = rand::thread_rng();
+            allocate_xs(
+                &mut HashSet::new(),
+                ys.len() as u32,
+                GfElemPrimitive::MAX,
+                || GfElem::new_rand(&mut rng).into(),
+            )?
+        };
+
+        let mut secret_len = 0;
+        let mut block = vec![0; BLOCK_SIZE];
+        loop {
+            let len = read_full(&mut self.secret, &mut block)?;
+            if len == 0 {
+                break;
+            }
+            secret_len += len;
+
+            // The y values of each chunk for every shard, in shard order.
+            let chunk_ys = block[..len]
+                .par_chunks(elem_len)
+                .map(|chunk| match field_size {
+                    FieldSize::Bits32 => {
+                        let mut poly = GfPolynomial::new_rand(k, &mut rand::thread_rng());
+                        *poly.constant_mut() = GfElem::from_bytes(chunk);
+                        xs.iter()
+                            .flat_map(|&x| poly.evaluate(x).to_bytes())
+                            .collect::<Vec<_>>()
+                    }
+                    FieldSize::Bits64 => {
+                        let mut poly = Gf64Polynomial::new_rand(k, &mut rand::thread_rng());
+                        *poly.constant_mut() = Gf64Elem::from_bytes(chunk);
+                        xs.iter()
+                            .flat_map(|&x| poly.evaluate(x.into()).to_bytes())
+                            .collect::<Vec<_>>()
+                    }
+                    FieldSize::Bits128 => {
+                        let mut poly = Gf128Polynomial::new_rand(k, &mut rand::thread_rng());
+                        *poly.constant_mut() = Gf128Elem::from_bytes(chunk);
+                        xs.iter()
+                            .flat_map(|&x| poly.evaluate(x.into()).to_bytes())
+                            .collect::<Vec<_>>()
+                    }
+                })
+                .collect::<Vec<_>>();
+            for (idx, writer) in ys.iter_mut().enumerate() {
+                let shard_ys = chunk_ys
+                    .iter()
+                    .flat_map(|ys| &ys[idx * elem_len..(idx + 1) * elem_len])
+                    .copied()
+                    .collect::<Vec<_>>();
+                writer.write_all(&shard_ys)?;
+            }
+
+            if len < block.len() {
+                break;
+            }
+        }
+
+        Ok(xs
+            .into_iter()
+            .map(|x| StreamedShard {
+                x,
+                secret_len,
+                threshold: self.threshold,
+                field_size,
+            })
+            .collect())
+    }
+}
+
+impl Dealer {
+    /// Construct a new [`StreamingDealer`] to shard the secret read from
+    /// `secret`, requiring at least `threshold` shards to reconstruct the
+    /// secret.
+    pub fn new_streaming<R: Read>(threshold: u32, secret: R) -> StreamingDealer<R> {
+        Self::new_streaming_with_field_size(threshold, secret, FieldSize::default())
+    }
+
+    /// Construct a new [`StreamingDealer`] to shard the secret read from
+    /// `secret` in the given field, requiring at least `threshold` shards to
+    /// reconstruct the secret.
+    pub fn new_streaming_with_field_size<R: Read>(
+        threshold: u32,
+        secret: R,
+        field_size: FieldSize,
+    ) -> StreamingDealer<R> {
+        StreamingDealer {
+            threshold,
+            field_size,
+            secret,
+        }
+    }
+
+    /// Recover the secret from exactly `threshold` unique [`StreamedShard`]s
+    /// and the corresponding streams of `y` values, writing it to `secret`
+    /// in bounded memory.
+    ///
+    /// Unlike [`Dealer::recover_verified`], surplus shards cannot be used to
+    /// detect corrupted shards.
+    pub fn recover_streaming<R: Read, W: Write>(
+        shards: &[StreamedShard],
+        ys: &mut [R],
+        mut secret: W,
+    ) -> Result<(), Error> {
+        let first = shards.first().ok_or(Error::NoShards)?;
+        if shards.len() != first.threshold as usize {
+            return Err(Error::WrongShardCount {
+                needed: first.threshold,
+                given: shards.len(),
+            });
+        }
+        if ys.len() != shards.len() {
+            return Err(Error::InconsistentShards(
+                "every shard must have a stream of y values",
+            ));
+        }
+        if shards.iter().any(|shard| {
+            (shard.threshold, shard.secret_len, shard.field_size)
+                != (first.threshold, first.secret_len, first.field_size)
+        }) {
+            return Err(Error::InconsistentShards(
+                "shards have different parameters",
+            ));
+        }
+        let mut xs = HashSet::new();
+        for shard in shards {
+            if shard.x == GfElem::ZERO {
+                return Err(Error::ZeroX);
+            }
+            if !xs.insert(shard.x) {
+                return Err(Error::DuplicateX(shard.x.into()));
+            }
+        }
+
+        // The Lagrange basis polynomials evaluated at x = 0, which are the
+        // weights of each shard's y values in the secret.
+        let xs = shards.iter().map(|shard| shard.x).collect::<Vec<_>>();
+        let others = |j: usize| {
+            xs.iter()
+                .enumerate()
+                .filter(move |&(m, _)| m != j)
+                .map(|(_, &xm)| xm)
+        };
+        let weights = (0..xs.len())
+            .map(|j| others(j).fold(GfElem::ONE, |w, xm| w * xm / (xm - xs[j])))
+            .collect::<Vec<_>>();
+        let weights64 = (0..xs.len())
+            .map(|j| {
+                let xj = Gf64Elem::from(xs[j]);
+                others(j)
+                    .map(Gf64Elem::from)
+                    .fold(Gf64Elem::ONE, |w, xm| w * xm / (xm - xj))
+            })
+            .collect::<Vec<_>>();
+        // Inversions in GF(2^128) are comparatively expensive, so only compute
+        // these weights if they are needed.
+        let weights128 = match first.field_size {
+            FieldSize::Bits128 => (0..xs.len())
+                .map(|j| {
+                    let xj = Gf128Elem::from(xs[j]);
+                    others(j)
+                        .map(Gf128Elem::from)
+                        .fold(Gf128Elem::ONE, |w, xm| w * xm / (xm - xj))
+                })
+                .collect::<Vec<_>>(),
+            _ => Vec::new(),
+        };
+
+        let elem_len = first.field_size.elem_len();
+        let mut remaining = first.secret_len;
+        let mut blocks = vec![vec![0; BLOCK_SIZE]; shards.len()];
+        while remaining > 0 {
+            let len = remaining.min(BLOCK_SIZE);
+            let ys_len = len.div_ceil(elem_len) * elem_len;
+            for (reader, block) in ys.iter_mut().zip(&mut blocks) {
+                reader.read_exact(&mut block[..ys_len])?;
+            }
+            let chunk = (0..ys_len / elem_len)
+                .into_par_iter()
+                .flat_map_iter(|i| {
+                    let ys = blocks
+                        .iter()
+                        .map(|block| &block[i * elem_len..(i + 1) * elem_len]);
+                    match first.field_size {
+                        FieldSize::Bits32 => ys
+                            .zip(&weights)
+                            .fold(GfElem::ZERO, |acc, (y, &w)| acc + GfElem::from_bytes(y) * w)
+                            .to_bytes(),
+                        FieldSize::Bits64 => ys
+                            .zip(&weights64)
+                            .fold(Gf64Elem::ZERO, |acc, (y, &w)| {
+                                acc + Gf64Elem::from_bytes(y) * w
+                            })
+                            .to_bytes(),
+                        FieldSize::Bits128 => ys
+                            .zip(&weights128)
+                            .fold(Gf128Elem::ZERO, |acc, (y, &w)| {
+                                acc + Gf128Elem::from_bytes(y) * w
+                            })
+                            .to_bytes(),
+                    }
+                })
+                .collect::<Vec<_>>();
+            secret.write_all(&chunk[..len])?;
+            remaining -= len;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn deal(
+        threshold: u32,
+        n: usize,
+        secret: &[u8],
+        field_size: FieldSize,
+    ) -> Vec<(StreamedShard, Vec<u8>)> {
+        let mut ys = vec![Vec::new(); n];
+        let shards = Dealer::new_streaming_with_field_size(threshold, secret, field_size)
+            .deal(&mut ys)
+            .unwrap();
+        shards.into_iter().zip(ys).collect()
+    }
+
+    #[quickcheck]
+    fn streaming_roundtrip(threshold: u8, secret: Vec<u8>) -> bool {
+        let threshold = u32::from(threshold % 8) + 1;
+        [FieldSize::Bits32, FieldSize::Bits64, FieldSize::Bits128]
+            .into_iter()
+            .all(|field_size| {
+                let dealt = deal(threshold, threshold as usize + 1, &secret, field_size);
+                let (shards, ys): (Vec<_>, Vec<_>) = dealt[1..].iter().cloned().unzip();
+                let mut readers = ys.iter().map(|ys| &ys[..]).collect::<Vec<_>>();
+                let mut recovered = Vec::new();
+                Dealer::recover_streaming(&shards, &mut readers, &mut recovered).unwrap();
+                recovered == secret
+            })
+    }
+
+    #[test]
+    fn streaming_large_secret() {
+        // Spans several blocks, with a partial element at the end.
+        let secret = (0..3 * BLOCK_SIZE + 5)
+            .map(|i| (i % 251) as u8)
+            .collect::<Vec<_>>();
+        let dealt = deal(3, 5, &secret, FieldSize::Bits32);
+        assert!(dealt.iter().all(|(shard, ys)| ys.len() == shard.ys_len()));
+
+        let (shards, ys): (Vec<_>, Vec<_>) = dealt[2..].iter().cloned().unzip();
+        let mut readers = ys.iter().map(|ys| &ys[..]).collect::<Vec<_>>();
+        let mut recovered = Vec::new();
+        Dealer::recover_streaming(&shards, &mut readers, &mut recovered).unwrap();
+        assert_eq!(recovered, secret);
+
+        // Streamed shards are ordinary shards.
+        let shards = dealt
+            .into_iter()
+            .take(3)
+            .map(|(shard, ys)| shard.into_shard(&ys).unwrap())
+            .collect::<Vec<_>>();
+        let dealer = Dealer::recover(&shards).unwrap();
+        assert_eq!(dealer.secret(), secret);
+        assert_eq!(shards[0].id().len(), Shard::ID_LENGTH);
+    }
+
+    #[test]
+    fn streaming_wrong_shards() {
+        let dealt = deal(2, 3, b"secret", FieldSize::Bits32);
+        let (shards, ys): (Vec<_>, Vec<_>) = dealt.iter().cloned().unzip();
+        let mut readers = ys.iter().map(|ys| &ys[..]).collect::<Vec<_>>();
+        assert!(matches!(
+            Dealer::recover_streaming(&shards[..1], &mut readers[..1], Vec::new()),
+            Err(Error::WrongShardCount { .. })
+        ));
+        assert!(matches!(
+            Dealer::recover_streaming(
+                &[shards[0].clone(), shards[0].clone()],
+                &mut readers[..2],
+                Vec::new()
+            ),
+            Err(Error::DuplicateX(_))
+        ));
+        // Truncated y values.
+        let mut readers = [&ys[0][..4], &ys[1][..]];
+        assert!(matches!(
+            Dealer::recover_streaming(&shards[..2], &mut readers, Vec::new()),
+            Err(Error::Io(_))
+        ));
+        // A zero threshold cannot be dealt.
+        assert!(matches!(
+            Dealer::new_streaming(0, &b"secret"[..]).deal(&mut [Vec::<u8>::new()]),
+            Err(Error::ZeroThreshold)
+        ));
+    }
+}
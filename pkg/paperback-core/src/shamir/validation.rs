@@ -0,0 +1,102 @@
+/*
+ * paperback: paper backup generator suitable for long-term storage
+ * Copyright (C) 2018-2022 Aleksa Sarai <cyphar@cyphar.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use crate::shamir::gf::GfElemPrimitive;
+
+use std::fmt;
+
+/// An internal invariant of a `Dealer` which did not hold.
+///
+/// Most of these can only be caused by a bug or by corrupted memory, and are
+/// checked in all builds (see [`Dealer::validate`](super::Dealer::validate)).
+/// The exception is `Violation::ShardRevealsSecret`, which happens by chance
+/// (with a probability of about `2^-32` for each chunk of the secret, in the
+/// smallest field) and only means that no shard can be issued at that `x`, so
+/// retrying with a different `x` (such as another `Dealer::next_shard`) works.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Violation {
+    /// The `y` value of the shard at `x` is equal to the chunk of the secret
+    /// it shares, so the shard would reveal that chunk.
+    ShardRevealsSecret { x: GfElemPrimitive, chunk: usize },
+    /// The number of polynomials does not match the length of the secret.
+    PolynomialCount { expected: usize, found: usize },
+    /// The `y` values of a shard are in a different field to the polynomials.
+    FieldMismatch,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ShardRevealsSecret { x, chunk } => {
+                write!(f, "shard at x={} reveals chunk {} of the secret", x, chunk)
+            }
+            Self::PolynomialCount { expected, found } => write!(
+                f,
+                "expected {} polynomials for the secret but found {}",
+                expected, found
+            ),
+            Self::FieldMismatch => write!(f, "shard y values are in the wrong field"),
+        }
+    }
+}
+
+/// A report of every internal invariant which did not hold.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ValidationError {
+    violations: Vec<Violation>,
+}
+
+impl ValidationError {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn push(&mut self, violation: Violation) -> &mut Self {
+        self.violations.push(violation);
+        self
+    }
+
+    /// Returns `Ok(())` if no invariants were violated.
+    pub(crate) fn into_result(self) -> Result<(), Self> {
+        match self.violations.is_empty() {
+            true => Ok(()),
+            false => Err(self),
+        }
+    }
+
+    /// The invariants which did not hold.
+    pub fn violations(&self) -> &[Violation] {
+        &self.violations
+    }
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            self.violations
+                .iter()
+                .map(Violation::to_string)
+                .collect::<Vec<_>>()
+                .join("; ")
+        )
+    }
+}
+
+impl std::error::Error for ValidationError {}
@@ -0,0 +1,296 @@
+/*
+ * paperback: paper backup generator suitable for long-term storage
+ * Copyright (C) 2018-2022 Aleksa Sarai <cyphar@cyphar.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Moving small pieces of a workflow across an air gap as paired printable
+//! documents.
+//!
+//! The online machine creates an [`AirGapRequest`] (such as a request for new
+//! key shards), which is printed and scanned on the offline machine. After
+//! carrying out the operation, the offline machine produces an
+//! [`AirGapResponse`] (such as an approval listing the new key shards), which
+//! is printed and scanned back on the online machine. Responses are bound to
+//! their request, so a response cannot be replayed against another request.
+//!
+//! The request and response contents are specific to each [`Operation`], and
+//! are described by a pair of [`Payload`] types.
+
+use crate::v0::{
+    multihash_short_id, DocumentId, EncryptedKeyShard, Error, FromWire, ToWire, CHECKSUM_ALGORITHM,
+    CHECKSUM_MULTIBASE,
+};
+
+use multihash::{Multihash, MultihashDigest};
+use rand::{rngs::OsRng, RngCore};
+use std::fmt;
+
+pub(crate) const AIRGAP_NONCE_LENGTH: usize = 16;
+
+/// An operation which can be carried out across an air gap.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Operation {
+    /// Create new key shards for an existing backup.
+    ExpandShards,
+}
+
+impl Operation {
+    pub(crate) fn id(&self) -> u32 {
+        match self {
+            Self::ExpandShards => 0,
+        }
+    }
+
+    pub(crate) fn from_id(id: u32) -> Option<Self> {
+        match id {
+            0 => Some(Self::ExpandShards),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ExpandShards => write!(f, "expand-shards"),
+        }
+    }
+}
+
+/// The contents of an [`AirGapRequest`] or [`AirGapResponse`] for a
+/// particular [`Operation`].
+pub trait Payload: ToWire + FromWire {
+    const OPERATION: Operation;
+}
+
+fn decode_payload<P: Payload>(operation: Operation, payload: &[u8]) -> Result<P, Error> {
+    if operation != P::OPERATION {
+        return Err(Error::Other(format!(
+            "air-gap transfer is for '{}' not '{}'",
+            operation,
+            P::OPERATION
+        )));
+    }
+    P::from_wire(payload)
+        .map_err(|err| Error::Other(format!("failed to parse {} payload: {}", operation, err)))
+}
+
+/// A request created on the online side of an air gap.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AirGapRequest {
+    pub(super) operation: Operation,
+    pub(super) nonce: [u8; AIRGAP_NONCE_LENGTH],
+    pub(super) payload: Vec<u8>,
+}
+
+impl AirGapRequest {
+    pub const ID_LENGTH: usize = 8;
+
+    pub fn new<P: Payload>(payload: &P) -> Self {
+        // The nonce makes sure that identical requests still have distinct
+        // checksums, so responses cannot be replayed.
+        let mut nonce = [0u8; AIRGAP_NONCE_LENGTH];
+        OsRng.fill_bytes(&mut nonce);
+        Self {
+            operation: P::OPERATION,
+            nonce,
+            payload: payload.to_wire(),
+        }
+    }
+
+    pub fn operation(&self) -> Operation {
+        self.operation
+    }
+
+    pub fn payload<P: Payload>(&self) -> Result<P, Error> {
+        decode_payload(self.operation, &self.payload)
+    }
+
+    pub fn checksum(&self) -> Multihash {
+        CHECKSUM_ALGORITHM.digest(&self.to_wire())
+    }
+
+    pub fn checksum_string(&self) -> String {
+        multibase::encode(CHECKSUM_MULTIBASE, self.checksum().to_bytes())
+    }
+
+    pub fn id(&self) -> String {
+        multihash_short_id(self.checksum(), Self::ID_LENGTH)
+    }
+}
+
+/// A response to an [`AirGapRequest`], created on the offline side of an air
+/// gap.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AirGapResponse {
+    pub(super) request_chksum: Multihash,
+    pub(super) operation: Operation,
+    pub(super) payload: Vec<u8>,
+}
+
+impl AirGapResponse {
+    pub fn new<P: Payload>(request: &AirGapRequest, payload: &P) -> Result<Self, Error> {
+        if request.operation != P::OPERATION {
+            return Err(Error::Other(format!(
+                "cannot respond to '{}' request with '{}' payload",
+                request.operation,
+                P::OPERATION
+            )));
+        }
+        Ok(Self {
+            request_chksum: request.checksum(),
+            operation: P::OPERATION,
+            payload: payload.to_wire(),
+        })
+    }
+
+    pub fn operation(&self) -> Operation {
+        self.operation
+    }
+
+    pub fn checksum(&self) -> Multihash {
+        CHECKSUM_ALGORITHM.digest(&self.to_wire())
+    }
+
+    pub fn checksum_string(&self) -> String {
+        multibase::encode(CHECKSUM_MULTIBASE, self.checksum().to_bytes())
+    }
+
+    /// The identifier of the request this is a response to.
+    pub fn request_id(&self) -> String {
+        multihash_short_id(self.request_chksum, AirGapRequest::ID_LENGTH)
+    }
+
+    /// Verify that this is a response to the given request.
+    pub fn verify(&self, request: &AirGapRequest) -> Result<(), Error> {
+        if self.request_chksum != request.checksum() {
+            return Err(Error::InvariantViolation(
+                "air-gap response is for a different request",
+            ));
+        }
+        if self.operation != request.operation {
+            return Err(Error::InvariantViolation(
+                "air-gap response is for a different operation",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Verify that this is a response to the given request, and return the
+    /// response payload.
+    pub fn payload<P: Payload>(&self, request: &AirGapRequest) -> Result<P, Error> {
+        self.verify(request)?;
+        decode_payload(self.operation, &self.payload)
+    }
+}
+
+/// [`Operation::ExpandShards`] request payload, asking for new key shards to
+/// be created for a document.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExpandShardsRequest {
+    pub document_id: DocumentId,
+    pub num_shards: u32,
+}
+
+impl Payload for ExpandShardsRequest {
+    const OPERATION: Operation = Operation::ExpandShards;
+}
+
+/// [`Operation::ExpandShards`] response payload, listing the key shards that
+/// were created.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExpandShardsApproval {
+    pub(super) shard_chksums: Vec<Multihash>,
+}
+
+impl ExpandShardsApproval {
+    pub fn new(shards: &[EncryptedKeyShard]) -> Self {
+        Self {
+            shard_chksums: shards.iter().map(EncryptedKeyShard::checksum).collect(),
+        }
+    }
+
+    pub fn shard_checksum_strings(&self) -> Vec<String> {
+        self.shard_chksums
+            .iter()
+            .map(|chksum| multibase::encode(CHECKSUM_MULTIBASE, chksum.to_bytes()))
+            .collect()
+    }
+}
+
+impl Payload for ExpandShardsApproval {
+    const OPERATION: Operation = Operation::ExpandShards;
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::v0::Backup;
+
+    fn expand_request() -> AirGapRequest {
+        AirGapRequest::new(&ExpandShardsRequest {
+            document_id: "abcdefgh".into(),
+            num_shards: 3,
+        })
+    }
+
+    #[test]
+    fn airgap_roundtrip() {
+        let request = AirGapRequest::from_wire(expand_request().to_wire()).unwrap();
+        assert_eq!(request.operation(), Operation::ExpandShards);
+        assert_eq!(
+            request.payload::<ExpandShardsRequest>().unwrap(),
+            ExpandShardsRequest {
+                document_id: "abcdefgh".into(),
+                num_shards: 3,
+            }
+        );
+
+        let backup = Backup::new(2, b"secret").unwrap();
+        let shards = (0..3)
+            .map(|_| backup.next_shard().unwrap().encrypt().unwrap().0)
+            .collect::<Vec<_>>();
+        let approval = ExpandShardsApproval::new(&shards);
+        let response = AirGapResponse::new(&request, &approval).unwrap();
+        let response = AirGapResponse::from_wire(response.to_wire()).unwrap();
+        assert_eq!(response.request_id(), request.id());
+        assert_eq!(
+            response.payload::<ExpandShardsApproval>(&request).unwrap(),
+            approval
+        );
+        assert_eq!(
+            approval.shard_checksum_strings(),
+            shards
+                .iter()
+                .map(EncryptedKeyShard::checksum_string)
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn airgap_response_bound_to_request() {
+        let request = expand_request();
+        let response = AirGapResponse::new(&request, &ExpandShardsApproval::new(&[])).unwrap();
+
+        // Identical requests have different nonces.
+        let other = expand_request();
+        assert_ne!(request.id(), other.id());
+        assert!(response.verify(&other).is_err());
+        assert!(response.payload::<ExpandShardsApproval>(&other).is_err());
+    }
+}
@@ -0,0 +1,132 @@
+/*
+ * paperback: paper backup generator suitable for long-term storage
+ * Copyright (C) 2018-2022 Aleksa Sarai <cyphar@cyphar.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Registry of the cryptographic algorithms used by each format version.
+//!
+//! Paperback backups can sit in a drawer for decades, and the algorithms they
+//! were created with may well be considered weak by the time they are
+//! recovered. Each algorithm has a "review after" date (chosen conservatively
+//! when it was adopted), after which recovering or inspecting a document
+//! which uses it produces a [`Warning::AlgorithmSunset`] suggesting that the
+//! backup be migrated to a newer format.
+
+use crate::v0::{format_version, Warning, Warnings};
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A cryptographic algorithm used by a paperback format version.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Algorithm {
+    /// Human-readable name of the algorithm.
+    pub name: &'static str,
+    /// Which parameters of the algorithm are used.
+    pub parameters: &'static str,
+    /// The year the algorithm (with these parameters) was adopted.
+    pub adopted: u32,
+    /// Unix timestamp after which documents using the algorithm should be
+    /// reviewed and migrated.
+    pub review_after: u64,
+}
+
+impl Algorithm {
+    /// Whether the review date for the algorithm has passed at `now` (a Unix
+    /// timestamp).
+    pub fn is_sunset(&self, now: u64) -> bool {
+        now >= self.review_after
+    }
+}
+
+// 2040-01-01T00:00:00Z.
+const REVIEW_2040: u64 = 2_208_988_800;
+// 2045-01-01T00:00:00Z.
+const REVIEW_2045: u64 = 2_366_841_600;
+
+// The algorithms used by format version 0. Entries must never be removed once
+// a version has been released, but the review dates may be brought forward if
+// an algorithm is weakened.
+static V0_ALGORITHMS: [Algorithm; 3] = [
+    Algorithm {
+        name: "ChaCha20-Poly1305",
+        parameters: "v1 (96-bit nonce)",
+        adopted: 2020,
+        review_after: REVIEW_2040,
+    },
+    Algorithm {
+        name: "Ed25519",
+        parameters: "v1",
+        adopted: 2020,
+        review_after: REVIEW_2040,
+    },
+    Algorithm {
+        name: "BLAKE2b",
+        parameters: "v1 (256-bit digest)",
+        adopted: 2020,
+        review_after: REVIEW_2045,
+    },
+];
+
+/// The algorithms used by documents with the given version, or `None` if the
+/// format version is not known to this version of paperback.
+pub fn for_version(version: u32) -> Option<&'static [Algorithm]> {
+    match format_version(version) {
+        0 => Some(&V0_ALGORITHMS),
+        _ => None,
+    }
+}
+
+/// Check the algorithms used by documents with the given version, returning a
+/// [`Warning::AlgorithmSunset`] for each algorithm past its review date at
+/// the given time.
+pub fn audit(version: u32, now: SystemTime) -> Warnings {
+    let now = now
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    for_version(version)
+        .unwrap_or_default()
+        .iter()
+        .filter(|algorithm| algorithm.is_sunset(now))
+        .map(Warning::AlgorithmSunset)
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::time::Duration;
+
+    #[test]
+    fn audit_review_dates() {
+        let at = |secs| UNIX_EPOCH + Duration::from_secs(secs);
+
+        assert!(audit(0, at(REVIEW_2040 - 1)).is_empty());
+        assert_eq!(
+            audit(0, at(REVIEW_2040)).into_iter().collect::<Vec<_>>(),
+            vec![
+                Warning::AlgorithmSunset(&V0_ALGORITHMS[0]),
+                Warning::AlgorithmSunset(&V0_ALGORITHMS[1]),
+            ]
+        );
+        assert_eq!(audit(0, at(REVIEW_2045)).len(), 3);
+        // Version flags don't change the algorithms used.
+        assert_eq!(audit(1 << 31, at(REVIEW_2045)).len(), 3);
+        // Unknown versions are reported separately.
+        assert!(audit(1, at(REVIEW_2045)).is_empty());
+    }
+}
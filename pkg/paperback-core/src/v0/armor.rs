@@ -0,0 +1,469 @@
+/*
+ * paperback: paper backup generator suitable for long-term storage
+ * Copyright (C) 2018-2022 Aleksa Sarai <cyphar@cyphar.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Line-oriented ASCII armor for data which has to be typed in by hand.
+//!
+//! Data is split into lines of [`LINE_BYTES`] bytes, each of which is encoded
+//! as z-base-32 in 4-character words separated by `-`, followed by a checksum
+//! for that line (this is the armored form of `b"paperback armor"`):
+//!
+//! ```text
+//! qboz-y3m1-cjos-g43y-cf3g-4551 #rp5y
+//! ```
+//!
+//! The checksum (a CRC-16 of the line number, whether the line is the final
+//! line, and the line data) means that typos are caught on the line where they
+//! were made, and that missing, reordered or truncated lines are detected.
+//!
+//! Both encoding ([`encode_lines`], [`ArmorWriter`]) and decoding
+//! ([`ArmorDecoder`], [`ArmorReader`]) are streaming, so very large payloads
+//! never need to be held in memory as a single string.
+
+use crate::ErrorCode;
+
+use std::io::{self, BufRead, Read, Write};
+
+use multibase::Base;
+
+/// Number of bytes of data encoded on each line (24 z-base-32 characters).
+pub const LINE_BYTES: usize = 15;
+
+const WORD_LENGTH: usize = 4;
+const WORD_SEPARATOR: char = '-';
+const CHECKSUM_MARKER: char = '#';
+// 6 words of 4 characters, separated by "-".
+const DATA_WIDTH: usize = 6 * WORD_LENGTH + 5;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ArmorError {
+    #[error("line {line}: missing '{}' line checksum", CHECKSUM_MARKER)]
+    MissingChecksum { line: usize },
+
+    #[error("line {line}: invalid encoding: {reason}")]
+    InvalidEncoding { line: usize, reason: String },
+
+    #[error("line {line}: too much data on line")]
+    LineTooLong { line: usize },
+
+    #[error("line {line}: checksum mismatch (typo, or lines out of order?)")]
+    ChecksumMismatch { line: usize },
+
+    #[error("line {line}: unexpected data after final line")]
+    DataAfterEnd { line: usize },
+
+    #[error("armored data is truncated (final line missing)")]
+    Truncated,
+}
+
+impl ArmorError {
+    /// The stable code of this error.
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Self::MissingChecksum { .. } => ErrorCode::new(400, "armor-missing-checksum"),
+            Self::InvalidEncoding { .. } => ErrorCode::new(401, "armor-invalid-encoding"),
+            Self::LineTooLong { .. } => ErrorCode::new(402, "armor-line-too-long"),
+            Self::ChecksumMismatch { .. } => ErrorCode::new(403, "armor-checksum-mismatch"),
+            Self::DataAfterEnd { .. } => ErrorCode::new(404, "armor-data-after-end"),
+            Self::Truncated => ErrorCode::new(405, "armor-truncated"),
+        }
+    }
+}
+
+impl From<ArmorError> for io::Error {
+    fn from(err: ArmorError) -> Self {
+        io::Error::new(io::ErrorKind::InvalidData, err)
+    }
+}
+
+// CRC-16/CCITT-FALSE.
+fn crc16(data: impl IntoIterator<Item = u8>) -> u16 {
+    data.into_iter().fold(0xffff, |mut crc, byte| {
+        crc ^= (byte as u16) << 8;
+        for _ in 0..8 {
+            crc = match crc & 0x8000 {
+                0 => crc << 1,
+                _ => (crc << 1) ^ 0x1021,
+            };
+        }
+        crc
+    })
+}
+
+fn line_checksum(idx: usize, data: &[u8], last: bool) -> [u8; 2] {
+    let header = (idx as u32).to_le_bytes().into_iter().chain([last as u8]);
+    crc16(header.chain(data.iter().copied())).to_be_bytes()
+}
+
+/// Encode a single line of armored data. `idx` is the zero-based line number,
+/// and `last` must be set for the final line.
+fn encode_line(idx: usize, data: &[u8], last: bool) -> String {
+    assert!(data.len() <= LINE_BYTES, "armor line data too long");
+
+    let encoded = Base::Base32Z.encode(data);
+    let mut line = encoded
+        .as_bytes()
+        .chunks(WORD_LENGTH)
+        .map(String::from_utf8_lossy)
+        .collect::<Vec<_>>()
+        .join(&WORD_SEPARATOR.to_string());
+    // Pad short lines so that the checksums line up.
+    let padding = DATA_WIDTH - line.len();
+    line.push_str(&WORD_SEPARATOR.to_string().repeat(padding));
+
+    line.push(' ');
+    line.push(CHECKSUM_MARKER);
+    line.push_str(&Base::Base32Z.encode(line_checksum(idx, data, last)));
+    line
+}
+
+/// Lazily encode `data` as armored lines.
+///
+/// The empty input is encoded as a single (empty) final line.
+pub fn encode_lines(data: &[u8]) -> impl Iterator<Item = String> + '_ {
+    let num_lines = data.len().div_ceil(LINE_BYTES).max(1);
+    (0..num_lines).map(move |idx| {
+        let start = idx * LINE_BYTES;
+        let end = (start + LINE_BYTES).min(data.len());
+        encode_line(idx, &data[start..end], idx + 1 == num_lines)
+    })
+}
+
+/// Returns whether the given text looks like armored data (rather than a plain
+/// multibase string).
+pub fn is_armored<S: AsRef<str>>(text: S) -> bool {
+    text.as_ref().contains(CHECKSUM_MARKER)
+}
+
+/// An [`io::Write`] adapter which armors everything written to it, writing
+/// each line (terminated with `\n`) to the inner writer as soon as it is
+/// complete.
+///
+/// [`ArmorWriter::finish`] must be called once all data has been written, in
+/// order to write the final line.
+pub struct ArmorWriter<W: Write> {
+    inner: W,
+    buffer: Vec<u8>,
+    line: usize,
+}
+
+impl<W: Write> ArmorWriter<W> {
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            buffer: Vec::with_capacity(2 * LINE_BYTES),
+            line: 0,
+        }
+    }
+
+    fn write_line(&mut self, data: &[u8], last: bool) -> io::Result<()> {
+        writeln!(self.inner, "{}", encode_line(self.line, data, last))?;
+        self.line += 1;
+        Ok(())
+    }
+
+    /// Write the final line and return the inner writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        let data = std::mem::take(&mut self.buffer);
+        self.write_line(&data, true)?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+impl<W: Write> Write for ArmorWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        // Always keep at least one byte buffered, because we don't know which
+        // line is the final line until finish() is called.
+        while self.buffer.len() > LINE_BYTES {
+            let data = self.buffer.drain(..LINE_BYTES).collect::<Vec<_>>();
+            self.write_line(&data, false)?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// A line-by-line decoder for armored data.
+#[derive(Clone, Debug, Default)]
+pub struct ArmorDecoder {
+    // Number of lines (including blank lines) seen so far.
+    line_no: usize,
+    // Number of data lines decoded so far.
+    idx: usize,
+    finished: bool,
+}
+
+impl ArmorDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns whether the final line has been decoded.
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Decode the next line of armored data, returning the data on that line.
+    /// Blank lines are ignored.
+    ///
+    /// If the line is invalid, the decoder is left unchanged so that the
+    /// corrected line can be pushed in its place (for instance, when the data
+    /// is being typed in interactively).
+    pub fn push_line<S: AsRef<str>>(&mut self, line: S) -> Result<Vec<u8>, ArmorError> {
+        let line_no = self.line_no + 1;
+
+        let line = line.as_ref().trim();
+        if line.is_empty() {
+            self.line_no = line_no;
+            return Ok(vec![]);
+        }
+        if self.finished {
+            return Err(ArmorError::DataAfterEnd { line: line_no });
+        }
+
+        let (data, checksum) = line
+            .split_once(CHECKSUM_MARKER)
+            .ok_or(ArmorError::MissingChecksum { line: line_no })?;
+        let invalid = |err: multibase::Error| ArmorError::InvalidEncoding {
+            line: line_no,
+            reason: err.to_string(),
+        };
+        let data = data
+            .chars()
+            .filter(|&ch| !ch.is_whitespace() && ch != WORD_SEPARATOR)
+            .collect::<String>();
+        let data = Base::Base32Z.decode(data).map_err(invalid)?;
+        let checksum = Base::Base32Z.decode(checksum.trim()).map_err(invalid)?;
+        if data.len() > LINE_BYTES {
+            return Err(ArmorError::LineTooLong { line: line_no });
+        }
+
+        // Short lines must be the final line, but full lines may be either.
+        let last = if checksum == line_checksum(self.idx, &data, true) {
+            true
+        } else if data.len() == LINE_BYTES && checksum == line_checksum(self.idx, &data, false) {
+            false
+        } else {
+            return Err(ArmorError::ChecksumMismatch { line: line_no });
+        };
+
+        self.line_no = line_no;
+        self.idx += 1;
+        self.finished = last;
+        Ok(data)
+    }
+
+    /// Check that the armored data was complete.
+    pub fn finish(self) -> Result<(), ArmorError> {
+        match self.finished {
+            true => Ok(()),
+            false => Err(ArmorError::Truncated),
+        }
+    }
+}
+
+/// Decode a complete armored string.
+pub fn decode<S: AsRef<str>>(text: S) -> Result<Vec<u8>, ArmorError> {
+    let mut decoder = ArmorDecoder::new();
+    let mut data = vec![];
+    for line in text.as_ref().lines() {
+        data.append(&mut decoder.push_line(line)?);
+    }
+    decoder.finish()?;
+    Ok(data)
+}
+
+/// An [`io::Read`] adapter which decodes armored data from the inner reader
+/// one line at a time.
+///
+/// Reading returns an error of kind [`io::ErrorKind::InvalidData`] (wrapping
+/// an [`ArmorError`]) if the armored data is corrupt or truncated.
+pub struct ArmorReader<R: BufRead> {
+    inner: R,
+    decoder: ArmorDecoder,
+    line: String,
+    pending: Vec<u8>,
+    pos: usize,
+    eof: bool,
+}
+
+impl<R: BufRead> ArmorReader<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            decoder: ArmorDecoder::new(),
+            line: String::new(),
+            pending: vec![],
+            pos: 0,
+            eof: false,
+        }
+    }
+}
+
+impl<R: BufRead> Read for ArmorReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        // Keep reading lines until we have some data (or hit the end). Lines
+        // after the final line must still be read to check they are blank.
+        while self.pos == self.pending.len() && !self.eof {
+            self.line.clear();
+            if self.inner.read_line(&mut self.line)? == 0 {
+                self.eof = true;
+                if !self.decoder.is_finished() {
+                    return Err(ArmorError::Truncated.into());
+                }
+                break;
+            }
+            self.pending = self.decoder.push_line(&self.line)?;
+            self.pos = 0;
+        }
+
+        let n = buf.len().min(self.pending.len() - self.pos);
+        buf[..n].copy_from_slice(&self.pending[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn armor(data: &[u8]) -> String {
+        let mut writer = ArmorWriter::new(vec![]);
+        writer.write_all(data).unwrap();
+        String::from_utf8(writer.finish().unwrap()).unwrap()
+    }
+
+    #[quickcheck]
+    fn armor_roundtrip(data: Vec<u8>) -> bool {
+        let text = armor(&data);
+
+        let mut decoded = vec![];
+        ArmorReader::new(text.as_bytes())
+            .read_to_end(&mut decoded)
+            .unwrap();
+
+        decoded == data && decode(&text).unwrap() == data
+    }
+
+    #[quickcheck]
+    fn armor_writer_matches_iterator(data: Vec<u8>, chunk_size: usize) -> bool {
+        let mut writer = ArmorWriter::new(vec![]);
+        for chunk in data.chunks(chunk_size.max(1)) {
+            writer.write_all(chunk).unwrap();
+        }
+        let text = String::from_utf8(writer.finish().unwrap()).unwrap();
+
+        text.lines().map(str::to_string).collect::<Vec<_>>()
+            == encode_lines(&data).collect::<Vec<_>>()
+    }
+
+    #[test]
+    fn armor_line_layout() {
+        let lines = encode_lines(&[0xaa; 2 * LINE_BYTES + 1]).collect::<Vec<_>>();
+        assert_eq!(lines.len(), 3);
+        for line in &lines {
+            let (data, checksum) = line.split_once(" #").unwrap();
+            assert_eq!(data.len(), DATA_WIDTH, "{:?}", line);
+            assert_eq!(checksum.len(), WORD_LENGTH, "{:?}", line);
+        }
+        assert!(is_armored(&lines[0]));
+
+        assert_eq!(
+            encode_lines(b"paperback armor").collect::<Vec<_>>(),
+            vec!["qboz-y3m1-cjos-g43y-cf3g-4551 #rp5y"]
+        );
+        assert!(!is_armored(Base::Base32Z.encode([0xaa; 16])));
+    }
+
+    #[test]
+    fn armor_detects_typo() {
+        let text = armor(&[0x42; 3 * LINE_BYTES]);
+        let mut lines = text.lines().map(str::to_string).collect::<Vec<_>>();
+        // Swap one character on the second line for another valid character.
+        let ch = lines[1].remove(5);
+        lines[1].insert(5, if ch == 'y' { 'b' } else { 'y' });
+        assert!(matches!(
+            decode(lines.join("\n")),
+            Err(ArmorError::ChecksumMismatch { line: 2 })
+        ));
+    }
+
+    #[test]
+    fn armor_decoder_retry() {
+        let data = (0..2 * LINE_BYTES as u8).collect::<Vec<_>>();
+        let text = armor(&data);
+        let lines = text.lines().collect::<Vec<_>>();
+        let mut typo = lines[1].to_string();
+        let ch = typo.remove(5);
+        typo.insert(5, if ch == 'y' { 'b' } else { 'y' });
+
+        let mut decoder = ArmorDecoder::new();
+        let mut decoded = decoder.push_line(lines[0]).unwrap();
+        assert!(matches!(
+            decoder.push_line(&typo),
+            Err(ArmorError::ChecksumMismatch { line: 2 })
+        ));
+        // The mistyped line can be re-entered.
+        decoded.append(&mut decoder.push_line(lines[1]).unwrap());
+        decoder.finish().unwrap();
+        assert_eq!(decoded, data);
+    }
+
+    #[test]
+    fn armor_detects_reordering() {
+        let text = armor(&(0..3 * LINE_BYTES as u8).collect::<Vec<_>>());
+        let mut lines = text.lines().collect::<Vec<_>>();
+        lines.swap(0, 1);
+        assert!(matches!(
+            decode(lines.join("\n")),
+            Err(ArmorError::ChecksumMismatch { line: 1 })
+        ));
+    }
+
+    #[test]
+    fn armor_detects_truncation() {
+        // A multiple of LINE_BYTES, so every line is full.
+        let text = armor(&[0x13; 3 * LINE_BYTES]);
+        let lines = text.lines().collect::<Vec<_>>();
+        assert!(matches!(
+            decode(lines[..2].join("\n")),
+            Err(ArmorError::Truncated)
+        ));
+
+        let mut decoded = vec![];
+        let err = ArmorReader::new(lines[..2].join("\n").as_bytes())
+            .read_to_end(&mut decoded)
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn armor_detects_trailing_data() {
+        let text = armor(b"hello");
+        let first = text.lines().next().unwrap();
+        assert!(matches!(
+            decode(format!("{}\n\n{}", first, first)),
+            Err(ArmorError::DataAfterEnd { line: 3 })
+        ));
+    }
+}
@@ -17,63 +17,471 @@
  */
 
 use crate::{
-    shamir::Dealer,
+    shamir::{Dealer, Error as ShamirError, FieldSize, GroupDealer, WeightedShard},
     v0::{
-        ChaChaPolyKey, ChaChaPolyNonce, Error, KeyShard, KeyShardBuilder, MainDocument,
-        MainDocumentBuilder, MainDocumentMeta, ShardSecret, ToWire, PAPERBACK_VERSION,
+        chunked::SegmentSealer, AeadAlgorithm, AeadMode, ChaChaPolyKey, ChaChaPolyNonce,
+        Compression, CustodianInfo, Error, KeyShard, KeyShardBuilder, MainDocument,
+        MainDocumentBuilder, MainDocumentMeta, PaddingScheme, PqSigningKey, RecoveryConstraint,
+        SecretBundle, ShardSecret, TextEncoding, ToWire, Warning, Warnings, AEAD_ALGORITHM_FLAG,
+        BECH32M_TEXT_FLAG, BUNDLE_FLAG, CBOR_PAYLOAD_FLAG, CHUNKED_AEAD_FLAG, COMPRESSION_FLAG,
+        CREATED_AT_FLAG, GENERATION_FLAG, HYBRID_SIGNATURE_FLAG, PADDING_FLAG, PAPERBACK_VERSION,
+        RECOVERY_CONSTRAINTS_FLAG, REVOKED_IDENTITIES_FLAG, TEST_RUN_FLAG,
     },
 };
 
-use aead::{Aead, NewAead, Payload};
-use chacha20poly1305::ChaCha20Poly1305;
-use ed25519_dalek::SigningKey;
-use rand::{rngs::OsRng, RngCore};
+use std::{
+    collections::BTreeMap,
+    io::{ErrorKind, Read},
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use ed25519_dalek::{SigningKey, VerifyingKey};
+use rand::{rngs::OsRng, CryptoRng, RngCore, SeedableRng};
+use rand_chacha::ChaCha20Rng;
 
 pub struct Backup {
     main_document: MainDocument,
     dealer: Dealer,
     id_keypair: SigningKey,
+    warnings: Warnings,
+    // Only set for deterministic backups (see BackupBuilder::seed).
+    rng: Option<Mutex<ChaCha20Rng>>,
 }
 
-impl Backup {
-    // XXX: This internal API is a bit ugly...
-    fn inner_new(quorum_size: u32, secret: &[u8], sealed: bool) -> Result<Self, Error> {
-        // Generate identity keypair.
-        let id_keypair = SigningKey::generate(&mut OsRng);
+/// Configuration for creating a new [`Backup`].
+#[derive(Clone, Debug)]
+pub struct BackupBuilder {
+    quorum_size: u32,
+    sealed: bool,
+    padding: PaddingScheme,
+    compression: Compression,
+    constraints: Vec<RecoveryConstraint>,
+    test_run: bool,
+    field_size: FieldSize,
+    aead_mode: AeadMode,
+    aead_algorithm: AeadAlgorithm,
+    hybrid_signatures: bool,
+    cbor_payload: bool,
+    // Only set for backups of a SecretBundle (see BackupBuilder::build_bundle).
+    bundle: bool,
+    // Only set when re-dealing an existing backup (see Quorum::redeal).
+    generation: u32,
+    created_at: Option<u64>,
+    text_encoding: TextEncoding,
+    id_keypair: Option<SigningKey>,
+    pq_keypair: Option<PqSigningKey>,
+    // Only set when rotating the identity of an existing backup (see
+    // Quorum::rotate_identity).
+    revoked_identities: Vec<VerifyingKey>,
+    // Only set when generating test vectors.
+    seed: Option<[u8; 32]>,
+}
+
+impl BackupBuilder {
+    pub fn new(quorum_size: u32) -> Self {
+        Self {
+            quorum_size,
+            sealed: false,
+            padding: PaddingScheme::None,
+            compression: Compression::None,
+            constraints: Vec::new(),
+            test_run: false,
+            field_size: FieldSize::default(),
+            aead_mode: AeadMode::default(),
+            aead_algorithm: AeadAlgorithm::default(),
+            hybrid_signatures: false,
+            cbor_payload: false,
+            bundle: false,
+            generation: 0,
+            created_at: None,
+            text_encoding: TextEncoding::default(),
+            id_keypair: None,
+            pq_keypair: None,
+            revoked_identities: Vec::new(),
+            seed: None,
+        }
+    }
+
+    /// Create a sealed backup, which cannot have new key shards created after
+    /// the initial backup.
+    pub fn sealed(&mut self, sealed: bool) -> &mut Self {
+        self.sealed = sealed;
+        self
+    }
+
+    /// Pad the secret before encryption to hide its length.
+    pub fn padding(&mut self, padding: PaddingScheme) -> &mut Self {
+        self.padding = padding;
+        self
+    }
+
+    /// Compress the secret before padding and encryption. Use
+    /// [`Compression::best_for`] to pick the most effective scheme.
+    pub fn compression(&mut self, compression: Compression) -> &mut Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Embed a (non-binding) recovery constraint hint in the main document.
+    pub fn constraint(&mut self, constraint: RecoveryConstraint) -> &mut Self {
+        self.constraints.push(constraint);
+        self
+    }
+
+    /// Mark the backup (and all of its key shards) as a test run. Test-run
+    /// documents are watermarked when rendered, and cannot be combined with
+    /// real documents.
+    pub fn test_run(&mut self, test_run: bool) -> &mut Self {
+        self.test_run = test_run;
+        self
+    }
+
+    /// Shard the secret in a larger field, which requires fewer interpolations
+    /// to recover. Shards in `GF(2^64)` or `GF(2^128)` cannot be read by older
+    /// versions of paperback.
+    pub fn field_size(&mut self, field_size: FieldSize) -> &mut Self {
+        self.field_size = field_size;
+        self
+    }
+
+    /// Encrypt the secret in independently-decryptable segments, allowing
+    /// large secrets to be decrypted in parallel and recovered piecemeal.
+    /// Chunked documents cannot be read by older versions of paperback.
+    pub fn aead_mode(&mut self, aead_mode: AeadMode) -> &mut Self {
+        self.aead_mode = aead_mode;
+        self
+    }
+
+    /// Encrypt the secret with the given AEAD algorithm. Documents using an
+    /// algorithm other than the default cannot be read by older versions of
+    /// paperback.
+    pub fn aead_algorithm(&mut self, aead_algorithm: AeadAlgorithm) -> &mut Self {
+        self.aead_algorithm = aead_algorithm;
+        self
+    }
+
+    /// Sign the main document with both Ed25519 and ML-DSA-65, so that it
+    /// cannot be forged even if Ed25519 is broken (see
+    /// [`MainDocument::is_hybrid_signed`]). Key shards are still only signed
+    /// with Ed25519, as ML-DSA-65 signatures are too large to fit in their QR
+    /// code.
+    ///
+    /// Hybrid-signed main documents are roughly 5 KiB larger than other main
+    /// documents, and cannot be read by older versions of paperback.
+    pub fn hybrid_signatures(&mut self, hybrid_signatures: bool) -> &mut Self {
+        self.hybrid_signatures = hybrid_signatures;
+        self
+    }
+
+    /// Serialise the main document metadata and the contents of the key
+    /// shards as deterministic CBOR maps, so that fields added by newer
+    /// versions of paperback can be skipped (and preserved) by older ones
+    /// rather than making the document unreadable. Documents with CBOR
+    /// payloads cannot be read by versions of paperback from before this
+    /// option was added.
+    pub fn cbor_payload(&mut self, cbor_payload: bool) -> &mut Self {
+        self.cbor_payload = cbor_payload;
+        self
+    }
+
+    /// Record the time the backup was created in the (signed) main document
+    /// metadata. Documents with a creation time cannot be read by older
+    /// versions of paperback.
+    pub fn created_at(&mut self, created_at: SystemTime) -> &mut Self {
+        self.created_at = Some(
+            created_at
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+        );
+        self
+    }
+
+    /// Print the text fallback of each QR code in the given encoding.
+    /// [`TextEncoding::Bech32m`] includes a checksum in every line, so typos
+    /// are located before recovery is attempted. Documents using it cannot be
+    /// read by older versions of paperback.
+    pub fn text_encoding(&mut self, text_encoding: TextEncoding) -> &mut Self {
+        self.text_encoding = text_encoding;
+        self
+    }
+
+    // Keep the creation time of an existing backup.
+    pub(super) fn created_at_timestamp(&mut self, created_at: Option<u64>) -> &mut Self {
+        self.created_at = created_at;
+        self
+    }
+
+    // Mark the secret as an encoded SecretBundle.
+    pub(super) fn bundle(&mut self, bundle: bool) -> &mut Self {
+        self.bundle = bundle;
+        self
+    }
+
+    // Create the backup as the given generation of an existing backup.
+    pub(super) fn generation(&mut self, generation: u32) -> &mut Self {
+        self.generation = generation;
+        self
+    }
+
+    // Sign the backup with an existing identity rather than a fresh one.
+    pub(super) fn id_keypair(&mut self, id_keypair: SigningKey) -> &mut Self {
+        self.id_keypair = Some(id_keypair);
+        self
+    }
+
+    // Sign hybrid-signed backups with an existing ML-DSA-65 key rather than a
+    // fresh one.
+    pub(super) fn pq_keypair(&mut self, pq_keypair: PqSigningKey) -> &mut Self {
+        self.pq_keypair = Some(pq_keypair);
+        self
+    }
+
+    // Record the identities which the backup's identity replaced.
+    pub(super) fn revoked_identities(
+        &mut self,
+        revoked_identities: Vec<VerifyingKey>,
+    ) -> &mut Self {
+        self.revoked_identities = revoked_identities;
+        self
+    }
+
+    // Derive all of the randomness used by the backup (including the x values
+    // of its key shards) from seed, so that the same inputs always produce
+    // the same documents. This is completely insecure, and must only be used
+    // to generate test vectors.
+    pub(super) fn seed(&mut self, seed: [u8; 32]) -> &mut Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    pub fn build<B: AsRef<[u8]>>(&self, secret: B) -> Result<Backup, Error> {
+        let secret = secret.as_ref();
+        self.build_with(self.aead_mode, |meta, doc_key, doc_nonce, aad| {
+            meta.aead_mode.encrypt(
+                meta.aead_algorithm,
+                doc_key,
+                doc_nonce,
+                aad,
+                &meta.padding.pad(&meta.compression.compress(secret)),
+            )
+        })
+    }
+
+    /// Create a backup of several labelled secrets, which are all recovered
+    /// together from the same quorum (see [`Quorum::recover_bundle`]).
+    /// Documents containing a bundle cannot be read by older versions of
+    /// paperback.
+    ///
+    /// [`Quorum::recover_bundle`]: crate::v0::Quorum::recover_bundle
+    pub fn build_bundle(&self, bundle: &SecretBundle) -> Result<Backup, Error> {
+        if bundle.is_empty() {
+            return Err(Error::Other("secret bundle must not be empty".into()));
+        }
+        self.clone().bundle(true).build(bundle.to_wire())
+    }
+
+    /// Create a backup of a secret of unknown length read from `reader`
+    /// (such as a pipe), without buffering the whole secret in memory.
+    ///
+    /// The secret is compressed, padded and encrypted as it is read, so only
+    /// a single segment of plaintext is held in memory at a time. The secret
+    /// is always encrypted with [`AeadMode::Chunked`] (using
+    /// [`AeadMode::DEFAULT_SEGMENT_SIZE`] unless a segment size was
+    /// configured with [`BackupBuilder::aead_mode`]), because a single AEAD
+    /// message cannot be sealed until the whole secret has been read.
+    pub fn build_from_reader<R: Read>(&self, mut reader: R) -> Result<Backup, Error> {
+        let aead_mode = match self.aead_mode {
+            AeadMode::Single => AeadMode::chunked(),
+            aead_mode => aead_mode,
+        };
+        self.build_with(aead_mode, |meta, doc_key, doc_nonce, aad| {
+            let segment_size = match meta.aead_mode {
+                AeadMode::Chunked { segment_size } => segment_size,
+                AeadMode::Single => unreachable!("streamed backups are always chunked"),
+            };
+            let mut sealer =
+                SegmentSealer::new(meta.aead_algorithm, doc_key, doc_nonce, aad, segment_size)?;
+            let mut compressor = meta.compression.compressor();
+            let mut compressed_len = 0;
+            let mut buffer = vec![0; segment_size as usize];
+            loop {
+                let n = match reader.read(&mut buffer) {
+                    Ok(0) => break,
+                    Ok(n) => n,
+                    Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+                    Err(err) => return Err(err.into()),
+                };
+                let compressed = compressor.update(&buffer[..n]);
+                compressed_len += compressed.len();
+                sealer.update(&compressed)?;
+            }
+            let compressed = compressor.finish();
+            compressed_len += compressed.len();
+            sealer.update(&compressed)?;
+            sealer.update(&meta.padding.padding(compressed_len))?;
+            sealer.finish()
+        })
+    }
+
+    // Construct the backup, using encrypt to produce the main document
+    // ciphertext from the document metadata, key, nonce and AAD.
+    fn build_with<F>(&self, aead_mode: AeadMode, encrypt: F) -> Result<Backup, Error>
+    where
+        F: FnOnce(
+            &MainDocumentMeta,
+            &ChaChaPolyKey,
+            &ChaChaPolyNonce,
+            &[u8],
+        ) -> Result<Vec<u8>, Error>,
+    {
+        // Check the quorum size before encrypting (or reading) the secret.
+        if self.quorum_size == 0 {
+            return Err(ShamirError::ZeroThreshold.into());
+        }
+        match self.seed {
+            None => self.build_with_rng(aead_mode, encrypt, &mut OsRng, None),
+            Some(seed) => {
+                // Use a separate stream for the dealer, so the key shards
+                // can be generated lazily.
+                let mut dealer_rng = ChaCha20Rng::from_seed(seed);
+                dealer_rng.set_stream(1);
+                let mut rng = ChaCha20Rng::from_seed(seed);
+                self.build_with_rng(aead_mode, encrypt, &mut rng, Some(dealer_rng))
+            }
+        }
+    }
+
+    // Construct the backup, using rng to generate the identity, key and nonce
+    // and dealer_rng (if set) for the dealer, rather than the thread-local
+    // CSPRNG.
+    fn build_with_rng<F, R>(
+        &self,
+        aead_mode: AeadMode,
+        encrypt: F,
+        rng: &mut R,
+        mut dealer_rng: Option<ChaCha20Rng>,
+    ) -> Result<Backup, Error>
+    where
+        F: FnOnce(
+            &MainDocumentMeta,
+            &ChaChaPolyKey,
+            &ChaChaPolyNonce,
+            &[u8],
+        ) -> Result<Vec<u8>, Error>,
+        R: CryptoRng + RngCore,
+    {
+        // Generate identity keypair (unless we are re-using one).
+        let id_keypair = self
+            .id_keypair
+            .clone()
+            .unwrap_or_else(|| SigningKey::generate(rng));
+        let pq_keypair = match self.hybrid_signatures {
+            false => None,
+            true => Some(
+                self.pq_keypair
+                    .clone()
+                    .unwrap_or_else(|| PqSigningKey::generate(rng)),
+            ),
+        };
 
         // Generate key and nonce.
         let mut doc_key = ChaChaPolyKey::default();
-        OsRng.fill_bytes(&mut doc_key);
+        rng.fill_bytes(&mut doc_key);
         let mut doc_nonce = ChaChaPolyNonce::default();
-        OsRng.fill_bytes(&mut doc_nonce);
+        rng.fill_bytes(&mut doc_nonce);
 
         // Construct shard secret and serialise it.
         let shard_secret = {
             ShardSecret {
                 doc_key,
-                id_keypair: match sealed {
+                id_keypair: match self.sealed {
                     false => Some(id_keypair.clone()),
                     true => None,
                 },
+                pq_keypair: match self.sealed {
+                    false => pq_keypair.clone(),
+                    true => None,
+                },
             }
             .to_wire()
         };
 
         // Construct the MainDocument.
         let main_document_meta = MainDocumentMeta {
-            version: PAPERBACK_VERSION,
-            quorum_size,
+            version: PAPERBACK_VERSION
+                | match self.test_run {
+                    false => 0,
+                    true => TEST_RUN_FLAG,
+                }
+                | match aead_mode {
+                    AeadMode::Single => 0,
+                    AeadMode::Chunked { .. } => CHUNKED_AEAD_FLAG,
+                }
+                | match self.generation {
+                    0 => 0,
+                    _ => GENERATION_FLAG,
+                }
+                | match self.bundle {
+                    false => 0,
+                    true => BUNDLE_FLAG,
+                }
+                | match self.created_at {
+                    None => 0,
+                    Some(_) => CREATED_AT_FLAG,
+                }
+                | match self.text_encoding {
+                    TextEncoding::Armor => 0,
+                    TextEncoding::Bech32m => BECH32M_TEXT_FLAG,
+                }
+                | match self.aead_algorithm {
+                    AeadAlgorithm::ChaCha20Poly1305 => 0,
+                    _ => AEAD_ALGORITHM_FLAG,
+                }
+                | match pq_keypair {
+                    None => 0,
+                    Some(_) => HYBRID_SIGNATURE_FLAG,
+                }
+                | match self.revoked_identities.len() {
+                    0 => 0,
+                    _ => REVOKED_IDENTITIES_FLAG,
+                }
+                | match self.cbor_payload {
+                    false => 0,
+                    true => CBOR_PAYLOAD_FLAG,
+                }
+                | match self.constraints.len() {
+                    0 => 0,
+                    _ => RECOVERY_CONSTRAINTS_FLAG,
+                }
+                | match self.compression {
+                    Compression::None => 0,
+                    _ => COMPRESSION_FLAG,
+                }
+                | match self.padding {
+                    PaddingScheme::None => 0,
+                    _ => PADDING_FLAG,
+                },
+            quorum_size: self.quorum_size,
+            padding: self.padding,
+            compression: self.compression,
+            constraints: self.constraints.clone(),
+            aead_mode,
+            generation: self.generation,
+            created_at: self.created_at,
+            aead_algorithm: self.aead_algorithm,
+            revoked_identities: self.revoked_identities.clone(),
+            extensions: BTreeMap::new(),
         };
 
-        // Encrypt the contents.
-        let aead = ChaCha20Poly1305::new(&doc_key);
-        let payload = Payload {
-            msg: secret,
-            aad: &main_document_meta.aad(&id_keypair.verifying_key()),
-        };
-        let ciphertext = aead
-            .encrypt(&doc_nonce, payload)
-            .map_err(Error::AeadEncryption)?;
+        // Encrypt the (compressed and padded) contents.
+        let ciphertext = encrypt(
+            &main_document_meta,
+            &doc_key,
+            &doc_nonce,
+            &main_document_meta.aad(&id_keypair.verifying_key()),
+        )?;
 
         // Continue MainDocument construction.
         let main_document = MainDocumentBuilder {
@@ -81,40 +489,171 @@ impl Backup {
             nonce: doc_nonce,
             ciphertext,
         }
-        .sign(&id_keypair);
+        .sign(&id_keypair, pq_keypair.as_ref());
 
         // Construct SSS dealer.
-        let dealer = Dealer::new(quorum_size, shard_secret);
+        let dealer = match &mut dealer_rng {
+            None => Dealer::new_with_field_size(self.quorum_size, shard_secret, self.field_size),
+            Some(rng) => Dealer::new_with_field_size_and_rng(
+                self.quorum_size,
+                shard_secret,
+                self.field_size,
+                rng,
+            ),
+        }?;
+
+        let mut warnings = Warnings::new();
+        if self.quorum_size == 1 {
+            warnings.push(Warning::WeakParameters(
+                "a quorum size of 1 allows any single key shard holder to recover the secret"
+                    .into(),
+            ));
+        }
 
         Ok(Backup {
             main_document,
             dealer,
             id_keypair,
+            warnings,
+            rng: dealer_rng.map(Mutex::new),
         })
     }
+}
 
-    // TODO: Implement this as a BackupBuilder rather than two builder init
-    //       functions.
-
+impl Backup {
     pub fn new<B: AsRef<[u8]>>(quorum_size: u32, secret: B) -> Result<Self, Error> {
-        Self::inner_new(quorum_size, secret.as_ref(), false)
+        BackupBuilder::new(quorum_size).build(secret)
     }
 
     pub fn new_sealed<B: AsRef<[u8]>>(quorum_size: u32, secret: B) -> Result<Self, Error> {
-        Self::inner_new(quorum_size, secret.as_ref(), true)
+        BackupBuilder::new(quorum_size).sealed(true).build(secret)
     }
 
     pub fn main_document(&self) -> &MainDocument {
         &self.main_document
     }
 
+    /// Returns any warnings about the parameters used to create this backup.
+    pub fn warnings(&self) -> &Warnings {
+        &self.warnings
+    }
+
     pub fn next_shard(&self) -> Result<KeyShard, Error> {
         // Extend new shard.
+        Ok(self
+            .next_shards(1)?
+            .pop()
+            .expect("next_shards(1) must return one shard"))
+    }
+
+    /// Generate `n` new key shards, which are guaranteed to be unique (and
+    /// distinct from any shard previously generated for this backup).
+    pub fn next_shards(&self, n: u32) -> Result<Vec<KeyShard>, Error> {
+        let shards = match &self.rng {
+            None => self.dealer.next_shards(n)?,
+            Some(rng) => self
+                .dealer
+                .next_shards_with_rng(n, &mut *rng.lock().expect("rng lock poisoned"))?,
+        };
+        Ok(shards
+            .into_iter()
+            .map(|shard| self.sign_shard(shard.into()))
+            .collect())
+    }
+
+    /// Generate a new key shard which counts as `weight` key shards towards
+    /// the quorum size, for holders who should need fewer other holders to
+    /// recover the secret.
+    ///
+    /// Key shards with a weight larger than 1 are larger than other key shards
+    /// (so their weight can be told from their size), and cannot be read by
+    /// older versions of paperback.
+    pub fn next_weighted_shard(&self, weight: u32) -> Result<KeyShard, Error> {
+        let shard = match &self.rng {
+            None => self.dealer.next_weighted_shard(weight)?,
+            Some(rng) => self.dealer.next_weighted_shard_with_rng(
+                weight,
+                &mut *rng.lock().expect("rng lock poisoned"),
+            )?,
+        };
+        Ok(self.sign_shard(shard))
+    }
+
+    /// Create a new group of key shard holders, where the key shards of any
+    /// `member_quorum_size` members of the group together count as one key
+    /// shard towards the quorum size (in the style of SLIP-39). Key shards
+    /// for members of the group are generated with
+    /// [`Backup::next_group_shards`].
+    ///
+    /// Key shards of different groups can be combined, but grouped key shards
+    /// cannot be combined with ungrouped key shards of the same backup (and
+    /// cannot be read by older versions of paperback).
+    pub fn next_group(&self, member_quorum_size: u32) -> Result<GroupDealer, Error> {
+        Ok(match &self.rng {
+            None => self.dealer.next_group(member_quorum_size)?,
+            Some(rng) => self.dealer.next_group_with_rng(
+                member_quorum_size,
+                &mut *rng.lock().expect("rng lock poisoned"),
+            )?,
+        })
+    }
+
+    /// Generate `n` new key shards for members of `group`, which must have
+    /// been created by [`Backup::next_group`] on this backup.
+    pub fn next_group_shards(&self, group: &GroupDealer, n: u32) -> Result<Vec<KeyShard>, Error> {
+        (0..n)
+            .map(|_| {
+                let shard = match &self.rng {
+                    None => group.next_shard()?,
+                    Some(rng) => group.next_weighted_shard_with_rng(
+                        1,
+                        &mut *rng.lock().expect("rng lock poisoned"),
+                    )?,
+                };
+                Ok(self.sign_shard(shard))
+            })
+            .collect()
+    }
+
+    /// Record details about the holder of `shard` (which must be a key shard
+    /// of this backup) in the key shard, returning the re-signed key shard.
+    /// The details are printed on the key shard PDF, and cannot be changed
+    /// without invalidating the key shard's signature.
+    ///
+    /// Key shards are padded with room for the largest possible custodian
+    /// details (see `KeyShard::padded_len`), so key shards with custodian
+    /// details are the same size as other key shards once encrypted. They
+    /// cannot be read by older versions of paperback.
+    pub fn assign_custodian(
+        &self,
+        shard: &KeyShard,
+        custodian: CustodianInfo,
+    ) -> Result<KeyShard, Error> {
+        if shard.document_checksum() != self.main_document.checksum() {
+            return Err(Error::Other(format!(
+                "key shard {} does not belong to document {}",
+                shard.id(),
+                self.main_document.id()
+            )));
+        }
+        custodian.validate().map_err(Error::Other)?;
         Ok(KeyShardBuilder {
+            custodian,
+            ..shard.inner.clone()
+        }
+        .sign(&self.id_keypair))
+    }
+
+    fn sign_shard(&self, shard: WeightedShard) -> KeyShard {
+        KeyShardBuilder {
             version: self.main_document.inner.meta.version,
             doc_chksum: self.main_document.checksum(),
-            shard: self.dealer.next_shard(),
+            shard,
+            custodian: CustodianInfo::default(),
+            expansion: None,
+            expansion_authorisation: None,
+            extensions: BTreeMap::new(),
         }
-        .sign(&self.id_keypair))
+        .sign(&self.id_keypair)
     }
 }
@@ -0,0 +1,404 @@
+/*
+ * paperback: paper backup generator suitable for long-term storage
+ * Copyright (C) 2018-2022 Aleksa Sarai <cyphar@cyphar.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! [Bech32m] text encoding with per-segment checksums.
+//!
+//! This is an alternative to [`armor`](crate::v0::armor) for the text
+//! fallback printed next to each QR code. Data is split into segments of
+//! [`SEGMENT_BYTES`] bytes, each of which is encoded as a separate Bech32m
+//! string (with the human-readable part `pb`) containing the segment number,
+//! the total number of segments and the segment data (this is the encoded
+//! form of `b"paperback"`):
+//!
+//! ```text
+//! pb1qqqqqqtsv9cx2unzv93kke2qn9w
+//! ```
+//!
+//! The Bech32m checksum of each segment is guaranteed to detect up to four
+//! typos in that segment, so mistakes are caught (and their segment located)
+//! before any cryptographic recovery is attempted.
+//!
+//! [Bech32m]: https://github.com/bitcoin/bips/blob/master/bip-0350.mediawiki
+
+use crate::{v0::armor, ErrorCode};
+
+/// Number of bytes of data encoded in each segment (41 characters).
+pub const SEGMENT_BYTES: usize = 16;
+
+const HRP: &str = "pb";
+const SEPARATOR: char = '1';
+const CHARSET: &[u8; 32] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+const CHECKSUM_CHARS: usize = 6;
+const BECH32M_CONST: u32 = 0x2bc8_30a3;
+// Segment number and total number of segments (both u16).
+const HEADER_BYTES: usize = 4;
+
+/// The encoding of the text fallback printed next to each QR code.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TextEncoding {
+    /// Line-oriented z-base-32 (see [`armor`]).
+    #[default]
+    Armor,
+    /// Bech32m segments (see [`encode_segments`]). Documents using this
+    /// encoding cannot be read by older versions of paperback.
+    Bech32m,
+}
+
+impl TextEncoding {
+    /// Encode `data` as lines of text in this encoding.
+    pub fn encode_lines(self, data: &[u8]) -> Vec<String> {
+        match self {
+            Self::Armor => armor::encode_lines(data).collect(),
+            Self::Bech32m => encode_segments(data),
+        }
+    }
+
+    /// Decode text produced by [`TextEncoding::encode_lines`] (with the lines
+    /// separated by newlines).
+    pub fn decode<S: AsRef<str>>(self, text: S) -> Result<Vec<u8>, String> {
+        match self {
+            Self::Armor => armor::decode(text).map_err(|err| err.to_string()),
+            Self::Bech32m => decode(text).map_err(|err| err.to_string()),
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Bech32Error {
+    #[error("segment {segment}: invalid encoding: {reason}")]
+    InvalidEncoding { segment: usize, reason: String },
+
+    #[error("segment {segment}: checksum mismatch (typo?)")]
+    ChecksumMismatch { segment: usize },
+
+    #[error("segment {segment}: found segment {found} (segments out of order?)")]
+    OutOfOrder { segment: usize, found: usize },
+
+    #[error("segment {segment}: inconsistent number of segments")]
+    InconsistentCount { segment: usize },
+
+    #[error("data is truncated ({found} of {expected} segments)")]
+    Truncated { expected: usize, found: usize },
+}
+
+impl Bech32Error {
+    /// The stable code of this error.
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Self::InvalidEncoding { .. } => ErrorCode::new(600, "bech32-invalid-encoding"),
+            Self::ChecksumMismatch { .. } => ErrorCode::new(601, "bech32-checksum-mismatch"),
+            Self::OutOfOrder { .. } => ErrorCode::new(602, "bech32-out-of-order"),
+            Self::InconsistentCount { .. } => ErrorCode::new(603, "bech32-inconsistent-count"),
+            Self::Truncated { .. } => ErrorCode::new(604, "bech32-truncated"),
+        }
+    }
+}
+
+fn polymod(values: impl IntoIterator<Item = u8>) -> u32 {
+    const GEN: [u32; 5] = [
+        0x3b6a_57b2,
+        0x2650_8e6d,
+        0x1ea1_19fa,
+        0x3d42_33dd,
+        0x2a14_62b3,
+    ];
+    values.into_iter().fold(1, |chk, value| {
+        let top = chk >> 25;
+        let chk = ((chk & 0x1ff_ffff) << 5) ^ value as u32;
+        GEN.iter()
+            .enumerate()
+            .filter(|(i, _)| (top >> i) & 1 != 0)
+            .fold(chk, |chk, (_, gen)| chk ^ gen)
+    })
+}
+
+fn hrp_expand(hrp: &str) -> impl Iterator<Item = u8> + '_ {
+    hrp.bytes()
+        .map(|b| b >> 5)
+        .chain([0])
+        .chain(hrp.bytes().map(|b| b & 31))
+}
+
+// Regroup bits from `from`-bit to `to`-bit values, padding the final value
+// with zero bits. Returns the regrouped values and the number of input bits
+// left over in the (padded) final value.
+fn convert_bits(data: &[u8], from: u32, to: u32) -> (Vec<u8>, u32) {
+    let mut acc: u32 = 0;
+    let mut bits = 0;
+    let mut output = Vec::with_capacity((data.len() * from as usize).div_ceil(to as usize));
+    for &value in data {
+        acc = (acc << from) | value as u32;
+        bits += from;
+        while bits >= to {
+            bits -= to;
+            output.push(((acc >> bits) & ((1 << to) - 1)) as u8);
+        }
+        acc &= (1 << bits) - 1;
+    }
+    if bits > 0 {
+        output.push((acc << (to - bits)) as u8);
+    }
+    (output, bits)
+}
+
+fn encode_segment(idx: usize, num_segments: usize, data: &[u8]) -> String {
+    let mut payload = Vec::with_capacity(HEADER_BYTES + data.len());
+    payload.extend_from_slice(&(idx as u16).to_be_bytes());
+    payload.extend_from_slice(&(num_segments as u16).to_be_bytes());
+    payload.extend_from_slice(data);
+    let (values, _) = convert_bits(&payload, 8, 5);
+
+    let checksum = polymod(
+        hrp_expand(HRP)
+            .chain(values.iter().copied())
+            .chain([0; CHECKSUM_CHARS]),
+    ) ^ BECH32M_CONST;
+
+    let mut segment = HRP.to_string();
+    segment.push(SEPARATOR);
+    segment.extend(values.iter().map(|&v| CHARSET[v as usize] as char));
+    segment.extend(
+        (0..CHECKSUM_CHARS)
+            .rev()
+            .map(|i| CHARSET[((checksum >> (5 * i)) & 31) as usize] as char),
+    );
+    segment
+}
+
+/// Encode `data` as Bech32m segments.
+///
+/// The empty input is encoded as a single (empty) segment.
+///
+/// # Panics
+///
+/// Panics if `data` needs more than `u16::MAX` segments.
+pub fn encode_segments(data: &[u8]) -> Vec<String> {
+    let num_segments = data.len().div_ceil(SEGMENT_BYTES).max(1);
+    assert!(
+        num_segments <= u16::MAX as usize,
+        "too much data for bech32m segments"
+    );
+    (0..num_segments)
+        .map(|idx| {
+            let start = idx * SEGMENT_BYTES;
+            let end = (start + SEGMENT_BYTES).min(data.len());
+            encode_segment(idx, num_segments, &data[start..end])
+        })
+        .collect()
+}
+
+/// Returns whether the given text looks like Bech32m segments (rather than
+/// armored data or a plain multibase string).
+pub fn is_bech32m<S: AsRef<str>>(text: S) -> bool {
+    text.as_ref()
+        .split_whitespace()
+        .next()
+        .map_or(false, |first| {
+            first
+                .to_lowercase()
+                .starts_with(&format!("{}{}", HRP, SEPARATOR))
+        })
+}
+
+// Decode a single segment, returning (idx, num_segments, data).
+fn decode_segment(segment: usize, text: &str) -> Result<(usize, usize, Vec<u8>), Bech32Error> {
+    let invalid = |reason: &str| Bech32Error::InvalidEncoding {
+        segment,
+        reason: reason.to_string(),
+    };
+
+    if text.chars().any(|c| c.is_ascii_lowercase()) && text.chars().any(|c| c.is_ascii_uppercase())
+    {
+        return Err(invalid("mixed-case segment"));
+    }
+    let text = text.to_lowercase();
+    let (hrp, data) = text
+        .rsplit_once(SEPARATOR)
+        .ok_or_else(|| invalid("missing separator"))?;
+    if hrp != HRP {
+        return Err(invalid("unknown prefix"));
+    }
+    let values = data
+        .bytes()
+        .map(|b| CHARSET.iter().position(|&c| c == b).map(|v| v as u8))
+        .collect::<Option<Vec<_>>>()
+        .ok_or_else(|| invalid("invalid character"))?;
+    if values.len() < CHECKSUM_CHARS {
+        return Err(invalid("segment too short"));
+    }
+    if polymod(hrp_expand(hrp).chain(values.iter().copied())) != BECH32M_CONST {
+        return Err(Bech32Error::ChecksumMismatch { segment });
+    }
+
+    let values = &values[..values.len() - CHECKSUM_CHARS];
+    let (mut payload, leftover) = convert_bits(values, 5, 8);
+    // Any left over bits are the (zero) padding from the 5-bit encoding.
+    if leftover > 4 || (leftover > 0 && payload.pop() != Some(0)) {
+        return Err(invalid("invalid padding"));
+    }
+    if payload.len() < HEADER_BYTES {
+        return Err(invalid("segment too short"));
+    }
+    let data = payload.split_off(HEADER_BYTES);
+    let idx = u16::from_be_bytes([payload[0], payload[1]]) as usize;
+    let num_segments = u16::from_be_bytes([payload[2], payload[3]]) as usize;
+    Ok((idx, num_segments, data))
+}
+
+/// Check a single segment (the `segment`-th, counting from 1) of the text
+/// produced by [`encode_segments`], returning whether it is the final segment.
+/// This lets a mistyped (or misread) segment be located and corrected before
+/// the rest of the segments have been entered.
+pub fn check_segment<S: AsRef<str>>(segment: usize, text: S) -> Result<bool, Bech32Error> {
+    let (idx, num_segments, _) = decode_segment(segment, text.as_ref())?;
+    if idx + 1 != segment {
+        return Err(Bech32Error::OutOfOrder {
+            segment,
+            found: idx + 1,
+        });
+    }
+    if idx >= num_segments {
+        return Err(Bech32Error::InconsistentCount { segment });
+    }
+    Ok(segment == num_segments)
+}
+
+/// Decode Bech32m segments (separated by whitespace) produced by
+/// [`encode_segments`].
+pub fn decode<S: AsRef<str>>(text: S) -> Result<Vec<u8>, Bech32Error> {
+    let mut output = Vec::new();
+    let mut num_segments = None;
+    let mut found = 0;
+    for (idx, text) in text.as_ref().split_whitespace().enumerate() {
+        let segment = idx + 1;
+        let (segment_idx, segment_count, data) = decode_segment(segment, text)?;
+        if segment_idx != idx {
+            return Err(Bech32Error::OutOfOrder {
+                segment,
+                found: segment_idx + 1,
+            });
+        }
+        if *num_segments.get_or_insert(segment_count) != segment_count || idx >= segment_count {
+            return Err(Bech32Error::InconsistentCount { segment });
+        }
+        output.extend(data);
+        found = segment;
+    }
+    match num_segments {
+        Some(expected) if expected == found => Ok(output),
+        expected => Err(Bech32Error::Truncated {
+            expected: expected.unwrap_or(1),
+            found,
+        }),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use quickcheck_macros::quickcheck;
+
+    #[test]
+    fn bech32m_test_vectors() {
+        // Valid Bech32m strings from BIP-350.
+        for vector in [
+            "pb1qqqqqqtsv9cx2unzv93kke2qn9w",
+            "a1lqfn3a",
+            "abcdef1l7aum6echk45nj3s0wdvt2fg8x9yrzpqzd3ryx",
+            "split1checkupstagehandshakeupstreamerranterredcaperredlc445v",
+            "?1v759aa",
+        ] {
+            let (hrp, data) = vector.rsplit_once(SEPARATOR).unwrap();
+            let values = data
+                .bytes()
+                .map(|b| CHARSET.iter().position(|&c| c == b).unwrap() as u8);
+            assert_eq!(
+                polymod(hrp_expand(hrp).chain(values)),
+                BECH32M_CONST,
+                "{}",
+                vector
+            );
+        }
+    }
+
+    #[test]
+    fn bech32m_check_segment() {
+        let segments = encode_segments(&[0x42; 3 * SEGMENT_BYTES - 1]);
+        assert_eq!(segments.len(), 3);
+        assert!(!check_segment(1, &segments[0]).unwrap());
+        assert!(check_segment(3, &segments[2]).unwrap());
+        assert!(matches!(
+            check_segment(2, &segments[2]),
+            Err(Bech32Error::OutOfOrder {
+                segment: 2,
+                found: 3
+            })
+        ));
+        let typo = segments[1].replacen('q', "p", 1);
+        assert!(check_segment(2, typo).is_err());
+    }
+
+    #[test]
+    fn bech32m_example() {
+        assert_eq!(
+            encode_segments(b"paperback"),
+            vec!["pb1qqqqqqtsv9cx2unzv93kke2qn9w"]
+        );
+    }
+
+    #[quickcheck]
+    fn bech32m_roundtrip(data: Vec<u8>) -> bool {
+        let segments = encode_segments(&data);
+        is_bech32m(&segments[0]) && decode(segments.join("\n")).unwrap() == data
+    }
+
+    #[test]
+    fn bech32m_typo_located() {
+        let data = (0..40).collect::<Vec<u8>>();
+        let mut segments = encode_segments(&data);
+        assert_eq!(segments.len(), 3);
+        assert!(segments.iter().all(|s| s.len() <= 41));
+
+        let typo = segments[1].replacen('q', "p", 1);
+        assert!(matches!(
+            decode(
+                [&segments[0], &typo, &segments[2]]
+                    .map(String::as_str)
+                    .join(" ")
+            ),
+            Err(Bech32Error::ChecksumMismatch { segment: 2 })
+        ));
+        // Uppercase segments are fine (but not mixed-case ones).
+        segments[0] = segments[0].to_uppercase();
+        assert_eq!(decode(segments.join(" ")).unwrap(), data);
+
+        segments.swap(1, 2);
+        assert!(matches!(
+            decode(segments.join(" ")),
+            Err(Bech32Error::OutOfOrder { segment: 2, .. })
+        ));
+        assert!(matches!(
+            decode(&segments[0]),
+            Err(Bech32Error::Truncated {
+                expected: 3,
+                found: 1
+            })
+        ));
+    }
+}
@@ -0,0 +1,171 @@
+/*
+ * paperback: paper backup generator suitable for long-term storage
+ * Copyright (C) 2018-2022 Aleksa Sarai <cyphar@cyphar.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Storing several independent secrets in a single backup.
+//!
+//! A [`SecretBundle`] is a keyed container of labelled secrets (such as a LUKS
+//! key, a password manager export and a PGP key). The encoded bundle is backed
+//! up in place of a single secret (see [`BackupBuilder::build_bundle`]), so all
+//! of the secrets are protected by the same key shards and are recovered
+//! together from the same quorum (see [`Quorum::recover_bundle`]).
+//!
+//! [`BackupBuilder::build_bundle`]: crate::v0::BackupBuilder::build_bundle
+//! [`Quorum::recover_bundle`]: crate::v0::Quorum::recover_bundle
+
+use crate::v0::Error;
+
+/// A single labelled secret in a [`SecretBundle`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SecretEntry {
+    pub(super) label: String,
+    pub(super) data: Vec<u8>,
+}
+
+impl SecretEntry {
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+/// An ordered set of secrets, each identified by a unique label.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SecretBundle {
+    pub(super) entries: Vec<SecretEntry>,
+}
+
+impl SecretBundle {
+    /// The maximum length (in bytes) of an entry label.
+    pub const MAX_LABEL_LENGTH: usize = 255;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Check whether label can be added to the bundle.
+    pub(super) fn check_label(&self, label: &str) -> Result<(), String> {
+        if label.is_empty() {
+            return Err("secret label must not be empty".into());
+        }
+        if label.len() > Self::MAX_LABEL_LENGTH {
+            return Err(format!(
+                "secret label '{}' is longer than {} bytes",
+                label,
+                Self::MAX_LABEL_LENGTH
+            ));
+        }
+        if label.chars().any(char::is_control) {
+            return Err(format!(
+                "secret label {:?} must not contain control characters",
+                label
+            ));
+        }
+        if self.get(label).is_some() {
+            return Err(format!("duplicate secret label '{}'", label));
+        }
+        Ok(())
+    }
+
+    /// Add a secret to the bundle. Labels must be non-empty, must not
+    /// contain control characters, and must be unique within the bundle.
+    pub fn push<S: Into<String>, B: Into<Vec<u8>>>(
+        &mut self,
+        label: S,
+        data: B,
+    ) -> Result<&mut Self, Error> {
+        let label = label.into();
+        self.check_label(&label).map_err(Error::Other)?;
+        self.entries.push(SecretEntry {
+            label,
+            data: data.into(),
+        });
+        Ok(self)
+    }
+
+    /// Returns the secret with the given label.
+    pub fn get(&self, label: &str) -> Option<&[u8]> {
+        self.entries
+            .iter()
+            .find(|entry| entry.label == label)
+            .map(SecretEntry::data)
+    }
+
+    /// The secrets in the bundle, in the order they were added.
+    pub fn entries(&self) -> &[SecretEntry] {
+        &self.entries
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+#[cfg(test)]
+impl quickcheck::Arbitrary for SecretBundle {
+    fn arbitrary(g: &mut quickcheck::Gen) -> Self {
+        let mut bundle = Self::new();
+        for (idx, data) in Vec::<Vec<u8>>::arbitrary(g).into_iter().enumerate() {
+            bundle.push(format!("secret-{}", idx), data).unwrap();
+        }
+        bundle
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::v0::{FromWire, ToWire};
+
+    #[quickcheck]
+    fn bundle_roundtrip(bundle: SecretBundle) -> bool {
+        SecretBundle::from_wire(bundle.to_wire()).unwrap() == bundle
+    }
+
+    #[test]
+    fn bundle_labels() {
+        let mut bundle = SecretBundle::new();
+        bundle.push("luks", b"luks key".to_vec()).unwrap();
+        bundle.push("pgp", b"pgp key".to_vec()).unwrap();
+        assert!(bundle.push("luks", b"another key".to_vec()).is_err());
+        assert!(bundle.push("", b"unlabelled".to_vec()).is_err());
+        assert!(bundle.push("new\nline", b"bad label".to_vec()).is_err());
+        assert!(bundle
+            .push("x".repeat(SecretBundle::MAX_LABEL_LENGTH + 1), vec![])
+            .is_err());
+
+        assert_eq!(bundle.len(), 2);
+        assert_eq!(bundle.get("pgp"), Some(&b"pgp key"[..]));
+        assert_eq!(bundle.get("gpg"), None);
+        assert_eq!(
+            bundle
+                .entries()
+                .iter()
+                .map(SecretEntry::label)
+                .collect::<Vec<_>>(),
+            ["luks", "pgp"]
+        );
+    }
+}
@@ -0,0 +1,338 @@
+/*
+ * paperback: paper backup generator suitable for long-term storage
+ * Copyright (C) 2018-2022 Aleksa Sarai <cyphar@cyphar.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Multi-operator signing of the artifacts created during a key ceremony.
+//!
+//! A [`Manifest`] lists the checksums of the main document and every key shard
+//! created for a backup. Each operator present when the backup is created can
+//! sign the manifest hash with their personal Ed25519 key (the same kind of key
+//! used by [`custody`](crate::v0::custody) holders), and all of the signatures
+//! are stored in the manifest so that auditors can later verify who attested
+//! to the creation of the backup.
+//!
+//! Shard holders can also use the manifest to check the health of their own
+//! key shard (see [`Manifest::check_shard`]) without access to any other part
+//! of the backup.
+
+use crate::v0::{
+    multihash_short_id, DocumentId, EncryptedKeyShard, Error, MainDocument, ShardId, Type,
+    CHECKSUM_ALGORITHM, CHECKSUM_MULTIBASE,
+};
+
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use multihash::{Multihash, MultihashDigest};
+use serde::Serialize;
+use unsigned_varint::encode as varuint_encode;
+
+/// An operator's signature of a [`Manifest`] hash.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OperatorSignature {
+    pub(super) operator: VerifyingKey,
+    pub(super) signature: Signature,
+}
+
+impl OperatorSignature {
+    pub fn operator(&self) -> &VerifyingKey {
+        &self.operator
+    }
+}
+
+/// A list of the artifacts created for a backup, signed by the operators
+/// present when it was created.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Manifest {
+    pub(super) document_chksum: Multihash,
+    pub(super) shard_chksums: Vec<Multihash>,
+    pub(super) signatures: Vec<OperatorSignature>,
+}
+
+impl Manifest {
+    // Domain separation for the manifest hash, so operator signatures cannot
+    // be confused with any other paperback signature.
+    const HASH_CONTEXT: &'static [u8] = b"paperback-manifest-v0";
+
+    /// Create an (unsigned) manifest for a main document and its key shards.
+    pub fn new(main_document: &MainDocument, shards: &[EncryptedKeyShard]) -> Self {
+        Self {
+            document_chksum: main_document.checksum(),
+            shard_chksums: shards.iter().map(EncryptedKeyShard::checksum).collect(),
+            signatures: vec![],
+        }
+    }
+
+    /// The id of the main document listed in the manifest.
+    pub fn document_id(&self) -> String {
+        multihash_short_id(self.document_chksum, MainDocument::ID_LENGTH)
+    }
+
+    pub fn document_checksum(&self) -> &Multihash {
+        &self.document_chksum
+    }
+
+    pub fn document_checksum_string(&self) -> String {
+        multibase::encode(CHECKSUM_MULTIBASE, self.document_chksum.to_bytes())
+    }
+
+    pub fn shard_checksums(&self) -> &[Multihash] {
+        &self.shard_chksums
+    }
+
+    pub fn shard_checksum_strings(&self) -> Vec<String> {
+        self.shard_chksums
+            .iter()
+            .map(|chksum| multibase::encode(CHECKSUM_MULTIBASE, chksum.to_bytes()))
+            .collect()
+    }
+
+    /// The hash signed by operators, which covers every artifact checksum in
+    /// the manifest (but not the signatures).
+    pub fn hash(&self) -> Multihash {
+        let mut bytes = Self::HASH_CONTEXT.to_vec();
+        bytes.extend_from_slice(&self.document_chksum.to_bytes());
+        bytes.extend_from_slice(varuint_encode::usize(
+            self.shard_chksums.len(),
+            &mut varuint_encode::usize_buffer(),
+        ));
+        for chksum in &self.shard_chksums {
+            bytes.extend_from_slice(&chksum.to_bytes());
+        }
+        CHECKSUM_ALGORITHM.digest(&bytes)
+    }
+
+    pub fn hash_string(&self) -> String {
+        multibase::encode(CHECKSUM_MULTIBASE, self.hash().to_bytes())
+    }
+
+    /// Sign the manifest hash with an operator's key. If the operator has
+    /// already signed the manifest, their signature is replaced.
+    pub fn sign(&mut self, operator_key: &SigningKey) -> &mut Self {
+        let operator = operator_key.verifying_key();
+        let signature = operator_key.sign(&self.hash().to_bytes());
+        self.signatures.retain(|sig| sig.operator != operator);
+        self.signatures.push(OperatorSignature {
+            operator,
+            signature,
+        });
+        self
+    }
+
+    pub fn signatures(&self) -> &[OperatorSignature] {
+        &self.signatures
+    }
+
+    /// Verify every operator signature in the manifest.
+    pub fn verify(&self) -> Result<(), Error> {
+        let hash = self.hash().to_bytes();
+        self.signatures.iter().try_for_each(|sig| {
+            sig.operator
+                .verify(&hash, &sig.signature)
+                .map_err(|_| Error::InvariantViolation("manifest operator signature is invalid"))
+        })
+    }
+
+    /// Verify that the manifest lists the given main document.
+    pub fn verify_document(&self, main_document: &MainDocument) -> Result<(), Error> {
+        match main_document.checksum() == self.document_chksum {
+            true => Ok(()),
+            false => Err(Error::InvariantViolation(
+                "manifest refers to a different main document",
+            )),
+        }
+    }
+
+    /// Verify that the manifest lists the given key shard.
+    pub fn verify_shard(&self, shard: &EncryptedKeyShard) -> Result<(), Error> {
+        match self.shard_chksums.contains(&shard.checksum()) {
+            true => Ok(()),
+            false => Err(Error::InvariantViolation(
+                "manifest does not list the key shard",
+            )),
+        }
+    }
+
+    /// Check the health of a single key shard against the manifest, as a shard
+    /// holder would. If the shard's codewords are provided, the shard is also
+    /// decrypted to check its signature and which document it belongs to.
+    ///
+    /// A single key shard cannot be used to recover anything, so this is safe
+    /// to run on a machine that is not trusted with the secret.
+    pub fn check_shard(
+        &self,
+        shard: &EncryptedKeyShard,
+        codewords: Option<&[String]>,
+    ) -> ShardHealth {
+        ShardHealth {
+            checksum: shard.checksum_string(),
+            listed: self.verify_shard(shard).is_ok(),
+            manifest_signers: self.signatures.len(),
+            manifest_signatures_valid: self.verify().is_ok(),
+            decrypted: codewords.map(|codewords| {
+                shard.decrypt(codewords).map(|shard| {
+                    let document_id = shard.document_id();
+                    DecryptedShardHealth {
+                        id: shard.id(),
+                        matches_document: shard.document_checksum() == self.document_chksum,
+                        document_id,
+                        quorum_size: shard.quorum_size(),
+                        test_run: shard.is_test_run(),
+                        signature_valid: matches!(Type::from(shard), Type::KeyShard(_)),
+                    }
+                })
+            }),
+        }
+    }
+}
+
+/// The results of a key shard holder checking their key shard against a
+/// [`Manifest`] (see [`Manifest::check_shard`]).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ShardHealth {
+    /// Checksum of the (encrypted) key shard.
+    pub checksum: String,
+    /// Whether the manifest lists the key shard.
+    pub listed: bool,
+    /// How many operators signed the manifest.
+    pub manifest_signers: usize,
+    /// Whether every operator signature in the manifest is valid.
+    pub manifest_signatures_valid: bool,
+    /// The results of checking the decrypted key shard, if codewords were
+    /// provided (or why the key shard could not be decrypted).
+    pub decrypted: Option<Result<DecryptedShardHealth, String>>,
+}
+
+impl ShardHealth {
+    /// Returns whether every check that was run passed.
+    pub fn is_healthy(&self) -> bool {
+        self.listed
+            && self.manifest_signatures_valid
+            && match &self.decrypted {
+                None => true,
+                Some(Ok(shard)) => shard.matches_document && shard.signature_valid,
+                Some(Err(_)) => false,
+            }
+    }
+}
+
+/// The results of checking a decrypted key shard against a [`Manifest`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct DecryptedShardHealth {
+    pub id: ShardId,
+    pub document_id: DocumentId,
+    pub quorum_size: u32,
+    /// Whether the key shard was created by a test run.
+    pub test_run: bool,
+    /// Whether the key shard belongs to the main document in the manifest.
+    pub matches_document: bool,
+    /// Whether the key shard's identity signature is valid.
+    pub signature_valid: bool,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::v0::{Backup, FromWire, ToWire};
+
+    use rand::rngs::OsRng;
+
+    fn manifest() -> (Backup, Vec<EncryptedKeyShard>, Manifest) {
+        let backup = Backup::new(2, b"secret").unwrap();
+        let shards = (0..3)
+            .map(|_| backup.next_shard().unwrap().encrypt().unwrap().0)
+            .collect::<Vec<_>>();
+        let manifest = Manifest::new(backup.main_document(), &shards);
+        (backup, shards, manifest)
+    }
+
+    #[test]
+    fn manifest_sign_verify() {
+        let (backup, shards, mut manifest) = manifest();
+        let (alice, bob) = (
+            SigningKey::generate(&mut OsRng),
+            SigningKey::generate(&mut OsRng),
+        );
+
+        let hash = manifest.hash();
+        manifest.sign(&alice).sign(&bob).sign(&alice);
+        // Signatures do not change the manifest hash.
+        assert_eq!(manifest.hash(), hash);
+        assert_eq!(
+            manifest
+                .signatures()
+                .iter()
+                .map(|sig| *sig.operator())
+                .collect::<Vec<_>>(),
+            vec![bob.verifying_key(), alice.verifying_key()]
+        );
+
+        let manifest = Manifest::from_wire(manifest.to_wire()).unwrap();
+        manifest.verify().unwrap();
+        manifest.verify_document(backup.main_document()).unwrap();
+        for shard in &shards {
+            manifest.verify_shard(shard).unwrap();
+        }
+
+        let other = Backup::new(2, b"secret").unwrap();
+        assert!(manifest.verify_document(other.main_document()).is_err());
+        let other_shard = other.next_shard().unwrap().encrypt().unwrap().0;
+        assert!(manifest.verify_shard(&other_shard).is_err());
+    }
+
+    #[test]
+    fn manifest_check_shard() {
+        let backup = Backup::new(2, b"secret").unwrap();
+        let (shard, codewords) = backup.next_shard().unwrap().encrypt().unwrap();
+        let mut manifest = Manifest::new(backup.main_document(), std::slice::from_ref(&shard));
+        manifest.sign(&SigningKey::generate(&mut OsRng));
+
+        let health = manifest.check_shard(&shard, None);
+        assert!(health.is_healthy());
+        assert_eq!(health.manifest_signers, 1);
+        assert_eq!(health.decrypted, None);
+
+        let health = manifest.check_shard(&shard, Some(&codewords));
+        assert!(health.is_healthy());
+        let decrypted = health.decrypted.unwrap().unwrap();
+        assert_eq!(decrypted.document_id, backup.main_document().id());
+        assert_eq!(decrypted.quorum_size, 2);
+
+        // Wrong codewords.
+        let (_, other_codewords) = backup.next_shard().unwrap().encrypt().unwrap();
+        let health = manifest.check_shard(&shard, Some(&other_codewords));
+        assert!(matches!(health.decrypted, Some(Err(_))));
+        assert!(!health.is_healthy());
+
+        // A key shard from a different backup.
+        let other = Backup::new(2, b"secret").unwrap();
+        let (other_shard, other_codewords) = other.next_shard().unwrap().encrypt().unwrap();
+        let health = manifest.check_shard(&other_shard, Some(&other_codewords));
+        assert!(!health.listed);
+        assert!(!health.decrypted.unwrap().unwrap().matches_document);
+    }
+
+    #[test]
+    fn manifest_tampered() {
+        let (_, _, mut manifest) = manifest();
+        manifest.sign(&SigningKey::generate(&mut OsRng));
+
+        // Dropping a shard from the manifest invalidates the signatures.
+        manifest.shard_chksums.pop();
+        assert!(manifest.verify().is_err());
+    }
+}
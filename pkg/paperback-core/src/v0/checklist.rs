@@ -0,0 +1,200 @@
+/*
+ * paperback: paper backup generator suitable for long-term storage
+ * Copyright (C) 2018-2022 Aleksa Sarai <cyphar@cyphar.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Timestamped checklists of the steps taken during a recovery.
+//!
+//! Some compliance regimes require recoveries to follow a documented
+//! procedure, with a record of when each step was completed. A
+//! [`RecoveryChecklist`] records each step as it happens and produces a
+//! plain-text report which lists the completed steps (with timestamps) and any
+//! required steps which were not completed.
+
+use crate::v0::constraints::format_datetime;
+
+use std::{
+    fmt,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// A step of a recovery ceremony.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ChecklistStep {
+    /// The main document was presented.
+    DocumentPresented { id: String, checksum: String },
+    /// A key shard was presented.
+    ShardPresented { id: String, checksum: String },
+    /// The key shards were validated as a quorum for the main document.
+    QuorumValidated,
+    /// The recovered secret was written out.
+    SecretOutput { destination: String },
+    /// The recovery materials were destroyed.
+    MaterialsDestroyed,
+}
+
+impl ChecklistStep {
+    // Descriptions of the steps which must be completed for the ceremony to
+    // be complete, indexed by ChecklistStep::index().
+    const REQUIRED: [&'static str; 5] = [
+        "main document presented",
+        "key shards presented",
+        "quorum validated",
+        "secret output",
+        "recovery materials destroyed",
+    ];
+
+    fn index(&self) -> usize {
+        match self {
+            Self::DocumentPresented { .. } => 0,
+            Self::ShardPresented { .. } => 1,
+            Self::QuorumValidated => 2,
+            Self::SecretOutput { .. } => 3,
+            Self::MaterialsDestroyed => 4,
+        }
+    }
+}
+
+impl fmt::Display for ChecklistStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::DocumentPresented { id, checksum } => {
+                write!(f, "main document {} presented (checksum {})", id, checksum)
+            }
+            Self::ShardPresented { id, checksum } => {
+                write!(f, "key shard {} presented (checksum {})", id, checksum)
+            }
+            Self::QuorumValidated => write!(f, "quorum validated"),
+            Self::SecretOutput { destination } => write!(f, "secret output to {}", destination),
+            Self::MaterialsDestroyed => write!(f, "recovery materials destroyed"),
+        }
+    }
+}
+
+/// A timestamped record of the steps taken during a recovery.
+#[derive(Clone, Debug, Default)]
+pub struct RecoveryChecklist {
+    entries: Vec<(u64, ChecklistStep)>,
+}
+
+impl RecoveryChecklist {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Record that `step` was completed at time `now`.
+    pub fn record(&mut self, step: ChecklistStep, now: SystemTime) -> &mut Self {
+        let now = now
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        self.entries.push((now, step));
+        self
+    }
+
+    /// The steps recorded so far, in order.
+    pub fn steps(&self) -> impl Iterator<Item = &ChecklistStep> {
+        self.entries.iter().map(|(_, step)| step)
+    }
+
+    fn missing(&self) -> impl Iterator<Item = &'static str> + '_ {
+        ChecklistStep::REQUIRED
+            .into_iter()
+            .enumerate()
+            .filter(|(idx, _)| !self.steps().any(|step| step.index() == *idx))
+            .map(|(_, description)| description)
+    }
+
+    /// Returns whether every required step has been completed.
+    pub fn is_complete(&self) -> bool {
+        self.missing().next().is_none()
+    }
+
+    /// Produce a plain-text ceremony report.
+    pub fn report(&self) -> String {
+        let mut report = String::from("paperback recovery ceremony report\n\n");
+        for (timestamp, step) in &self.entries {
+            report.push_str(&format!("[x] {}  {}\n", format_datetime(*timestamp), step));
+        }
+        for description in self.missing() {
+            report.push_str(&format!("[ ] {:20}  {}\n", "(not completed)", description));
+        }
+        report.push_str(match self.is_complete() {
+            true => "\nceremony complete\n",
+            false => "\nceremony INCOMPLETE\n",
+        });
+        report
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::time::Duration;
+
+    #[test]
+    fn checklist_report() {
+        let start = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let mut checklist = RecoveryChecklist::new();
+        checklist
+            .record(
+                ChecklistStep::DocumentPresented {
+                    id: "abcdefgh".into(),
+                    checksum: "hijklmno".into(),
+                },
+                start,
+            )
+            .record(
+                ChecklistStep::ShardPresented {
+                    id: "12345678".into(),
+                    checksum: "pqrstuvw".into(),
+                },
+                start + Duration::from_secs(61),
+            )
+            .record(
+                ChecklistStep::QuorumValidated,
+                start + Duration::from_secs(62),
+            );
+        assert!(!checklist.is_complete());
+
+        let report = checklist.report();
+        assert!(report.contains(
+            "[x] 2023-11-14T22:13:20Z  main document abcdefgh presented (checksum hijklmno)\n"
+        ));
+        assert!(report.contains("[x] 2023-11-14T22:14:21Z  key shard 12345678 presented"));
+        assert!(report.contains("[ ] (not completed)       secret output\n"));
+        assert!(report.contains("[ ] (not completed)       recovery materials destroyed\n"));
+        assert!(report.ends_with("ceremony INCOMPLETE\n"));
+
+        checklist
+            .record(
+                ChecklistStep::SecretOutput {
+                    destination: "secret.txt".into(),
+                },
+                start + Duration::from_secs(3600),
+            )
+            .record(
+                ChecklistStep::MaterialsDestroyed,
+                start + Duration::from_secs(7200),
+            );
+        assert!(checklist.is_complete());
+        let report = checklist.report();
+        assert!(!report.contains("[ ]"));
+        assert!(report.contains("[x] 2023-11-15T00:13:20Z  recovery materials destroyed\n"));
+        assert!(report.ends_with("ceremony complete\n"));
+    }
+}
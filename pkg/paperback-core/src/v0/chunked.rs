@@ -0,0 +1,409 @@
+/*
+ * paperback: paper backup generator suitable for long-term storage
+ * Copyright (C) 2018-2022 Aleksa Sarai <cyphar@cyphar.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use crate::{
+    par::*,
+    v0::{cipher::Cipher, AeadAlgorithm, ChaChaPolyKey, ChaChaPolyNonce, Error},
+};
+
+use aead::Payload;
+
+/// How the (compressed and padded) secret is encrypted in the main document.
+///
+/// `Chunked` splits the plaintext into fixed-size segments which are each
+/// sealed with their own nonce using the [STREAM construction][stream], so
+/// that large payloads can be decrypted in parallel, and individual segments
+/// can be verified and recovered without decrypting the whole document.
+/// Truncating, reordering or dropping segments is detected because the
+/// segment index (and whether the segment is the last one) is part of each
+/// segment's nonce.
+///
+/// Chunked documents set a flag in their format version, so older versions of
+/// paperback will refuse to load them.
+///
+/// [stream]: https://eprint.iacr.org/2015/189.pdf
+#[derive(Copy, Clone, Debug, Default, Hash, PartialEq, Eq)]
+pub enum AeadMode {
+    /// The entire plaintext is sealed as a single AEAD message.
+    #[default]
+    Single,
+    /// The plaintext is sealed in segments of `segment_size` bytes (the final
+    /// segment may be shorter).
+    Chunked { segment_size: u32 },
+}
+
+impl AeadMode {
+    /// A reasonable segment size for `AeadMode::Chunked`.
+    pub const DEFAULT_SEGMENT_SIZE: u32 = 64 * 1024;
+
+    // Length of the tag appended to each sealed segment (the same for every
+    // AeadAlgorithm).
+    const TAG_LENGTH: usize = 16;
+
+    // Length of the part of the document nonce used as the STREAM nonce
+    // prefix. The remaining bytes are the big-endian segment index and the
+    // last-segment flag.
+    const NONCE_PREFIX_LENGTH: usize = 7;
+
+    /// `AeadMode::Chunked` with the default segment size.
+    pub fn chunked() -> Self {
+        Self::Chunked {
+            segment_size: Self::DEFAULT_SEGMENT_SIZE,
+        }
+    }
+
+    /// Returns the number of independently-decryptable segments in a
+    /// ciphertext of length `ciphertext_len`.
+    pub fn num_segments(&self, ciphertext_len: usize) -> usize {
+        match self {
+            Self::Single => 1,
+            Self::Chunked { segment_size } => {
+                // Even an empty plaintext is sealed as a single segment.
+                let sealed_size = *segment_size as usize + Self::TAG_LENGTH;
+                ciphertext_len.div_ceil(sealed_size).max(1)
+            }
+        }
+    }
+
+    /// Returns the length of the plaintext sealed in a ciphertext of length
+    /// `ciphertext_len`, which (like the ciphertext length) is not secret.
+    pub fn plaintext_len(&self, ciphertext_len: usize) -> usize {
+        ciphertext_len.saturating_sub(self.num_segments(ciphertext_len) * Self::TAG_LENGTH)
+    }
+
+    fn segment_nonce(
+        nonce: &ChaChaPolyNonce,
+        index: usize,
+        last: bool,
+    ) -> Result<ChaChaPolyNonce, Error> {
+        let index = u32::try_from(index)
+            .map_err(|_| Error::InvalidSegment("too many segments in ciphertext"))?;
+        let mut segment_nonce = *nonce;
+        segment_nonce[Self::NONCE_PREFIX_LENGTH..Self::NONCE_PREFIX_LENGTH + 4]
+            .copy_from_slice(&index.to_be_bytes());
+        segment_nonce[Self::NONCE_PREFIX_LENGTH + 4] = last as u8;
+        Ok(segment_nonce)
+    }
+
+    fn sealed_size(segment_size: u32) -> Result<usize, Error> {
+        match segment_size {
+            0 => Err(Error::InvalidSegment("segment size must be non-zero")),
+            size => Ok(size as usize + Self::TAG_LENGTH),
+        }
+    }
+
+    pub(crate) fn encrypt(
+        &self,
+        algorithm: AeadAlgorithm,
+        key: &ChaChaPolyKey,
+        nonce: &ChaChaPolyNonce,
+        aad: &[u8],
+        plaintext: &[u8],
+    ) -> Result<Vec<u8>, Error> {
+        let aead = algorithm.cipher(key);
+        match self {
+            Self::Single => aead
+                .encrypt(
+                    nonce,
+                    Payload {
+                        msg: plaintext,
+                        aad,
+                    },
+                )
+                .map_err(Error::AeadEncryption),
+            Self::Chunked { segment_size } => {
+                Self::sealed_size(*segment_size)?;
+                let segments = match plaintext.is_empty() {
+                    true => vec![plaintext],
+                    false => plaintext.chunks(*segment_size as usize).collect(),
+                };
+                let last = segments.len() - 1;
+                Ok(segments
+                    .into_par_iter()
+                    .enumerate()
+                    .map(|(index, msg)| {
+                        let nonce = Self::segment_nonce(nonce, index, index == last)?;
+                        aead.encrypt(&nonce, Payload { msg, aad })
+                            .map_err(Error::AeadEncryption)
+                    })
+                    .collect::<Result<Vec<_>, _>>()?
+                    .concat())
+            }
+        }
+    }
+
+    pub(crate) fn decrypt(
+        &self,
+        algorithm: AeadAlgorithm,
+        key: &ChaChaPolyKey,
+        nonce: &ChaChaPolyNonce,
+        aad: &[u8],
+        ciphertext: &[u8],
+    ) -> Result<Vec<u8>, Error> {
+        match self {
+            Self::Single => algorithm
+                .cipher(key)
+                .decrypt(
+                    nonce,
+                    Payload {
+                        msg: ciphertext,
+                        aad,
+                    },
+                )
+                .map_err(Error::AeadDecryption),
+            Self::Chunked { .. } => Ok((0..self.num_segments(ciphertext.len()))
+                .into_par_iter()
+                .map(|index| self.decrypt_segment(algorithm, key, nonce, aad, ciphertext, index))
+                .collect::<Result<Vec<_>, _>>()?
+                .concat()),
+        }
+    }
+
+    /// Decrypt (and authenticate) only the segment with the given index. For
+    /// `AeadMode::Single`, the only segment is the whole plaintext.
+    pub(crate) fn decrypt_segment(
+        &self,
+        algorithm: AeadAlgorithm,
+        key: &ChaChaPolyKey,
+        nonce: &ChaChaPolyNonce,
+        aad: &[u8],
+        ciphertext: &[u8],
+        index: usize,
+    ) -> Result<Vec<u8>, Error> {
+        let segment_size = match self {
+            Self::Single if index == 0 => {
+                return self.decrypt(algorithm, key, nonce, aad, ciphertext)
+            }
+            Self::Single => return Err(Error::InvalidSegment("segment index out of range")),
+            Self::Chunked { segment_size } => *segment_size,
+        };
+        let sealed_size = Self::sealed_size(segment_size)?;
+        let num_segments = self.num_segments(ciphertext.len());
+        if index >= num_segments {
+            return Err(Error::InvalidSegment("segment index out of range"));
+        }
+        let start = index * sealed_size;
+        let end = ciphertext.len().min(start + sealed_size);
+        let nonce = Self::segment_nonce(nonce, index, index == num_segments - 1)?;
+        algorithm
+            .cipher(key)
+            .decrypt(
+                &nonce,
+                Payload {
+                    msg: &ciphertext[start..end],
+                    aad,
+                },
+            )
+            .map_err(Error::AeadDecryption)
+    }
+}
+
+/// Seals a plaintext of unknown length with [`AeadMode::Chunked`] as it
+/// arrives, holding at most one segment of plaintext at a time. The resulting
+/// ciphertext is identical to sealing the whole plaintext at once with
+/// [`AeadMode::encrypt`].
+pub(crate) struct SegmentSealer {
+    aead: Cipher,
+    nonce: ChaChaPolyNonce,
+    aad: Vec<u8>,
+    segment_size: usize,
+    index: usize,
+    // Plaintext which has not been sealed yet. A full segment is only sealed
+    // once more plaintext arrives, because the last segment is sealed
+    // differently.
+    pending: Vec<u8>,
+    ciphertext: Vec<u8>,
+}
+
+impl SegmentSealer {
+    pub(crate) fn new(
+        algorithm: AeadAlgorithm,
+        key: &ChaChaPolyKey,
+        nonce: &ChaChaPolyNonce,
+        aad: &[u8],
+        segment_size: u32,
+    ) -> Result<Self, Error> {
+        AeadMode::sealed_size(segment_size)?;
+        Ok(Self {
+            aead: algorithm.cipher(key),
+            nonce: *nonce,
+            aad: aad.to_vec(),
+            segment_size: segment_size as usize,
+            index: 0,
+            pending: Vec::with_capacity(segment_size as usize),
+            ciphertext: Vec::new(),
+        })
+    }
+
+    fn seal(&mut self, len: usize, last: bool) -> Result<(), Error> {
+        let nonce = AeadMode::segment_nonce(&self.nonce, self.index, last)?;
+        let sealed = self
+            .aead
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: &self.pending[..len],
+                    aad: &self.aad,
+                },
+            )
+            .map_err(Error::AeadEncryption)?;
+        self.ciphertext.extend_from_slice(&sealed);
+        self.pending.drain(..len);
+        self.index += 1;
+        Ok(())
+    }
+
+    /// Append `data` to the plaintext, sealing any segments which are known
+    /// not to be the last segment.
+    pub(crate) fn update(&mut self, mut data: &[u8]) -> Result<(), Error> {
+        while !data.is_empty() {
+            if self.pending.len() == self.segment_size {
+                self.seal(self.segment_size, false)?;
+            }
+            let take = data.len().min(self.segment_size - self.pending.len());
+            self.pending.extend_from_slice(&data[..take]);
+            data = &data[take..];
+        }
+        Ok(())
+    }
+
+    /// Seal the last segment, returning the complete ciphertext.
+    pub(crate) fn finish(mut self) -> Result<Vec<u8>, Error> {
+        self.seal(self.pending.len(), true)?;
+        Ok(self.ciphertext)
+    }
+}
+
+#[cfg(test)]
+impl quickcheck::Arbitrary for AeadMode {
+    fn arbitrary(g: &mut quickcheck::Gen) -> Self {
+        match bool::arbitrary(g) {
+            false => Self::Single,
+            true => Self::Chunked {
+                segment_size: u32::from(u8::arbitrary(g)) + 1,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use rand::RngCore;
+
+    fn key_nonce() -> (ChaChaPolyKey, ChaChaPolyNonce) {
+        let mut key = ChaChaPolyKey::default();
+        rand::thread_rng().fill_bytes(&mut key);
+        let mut nonce = ChaChaPolyNonce::default();
+        rand::thread_rng().fill_bytes(&mut nonce);
+        (key, nonce)
+    }
+
+    #[quickcheck]
+    fn aead_roundtrip(
+        mode: AeadMode,
+        algorithm: AeadAlgorithm,
+        plaintext: Vec<u8>,
+        aad: Vec<u8>,
+    ) -> bool {
+        let (key, nonce) = key_nonce();
+        let ciphertext = mode
+            .encrypt(algorithm, &key, &nonce, &aad, &plaintext)
+            .unwrap();
+        mode.decrypt(algorithm, &key, &nonce, &aad, &ciphertext)
+            .unwrap()
+            == plaintext
+    }
+
+    #[quickcheck]
+    fn aead_algorithm_mismatch(mode: AeadMode, plaintext: Vec<u8>) -> bool {
+        let (key, nonce) = key_nonce();
+        let ciphertext = mode
+            .encrypt(
+                AeadAlgorithm::Aes256GcmSiv,
+                &key,
+                &nonce,
+                b"aad",
+                &plaintext,
+            )
+            .unwrap();
+        mode.decrypt(
+            AeadAlgorithm::ChaCha20Poly1305,
+            &key,
+            &nonce,
+            b"aad",
+            &ciphertext,
+        )
+        .is_err()
+    }
+
+    #[quickcheck]
+    fn segment_sealer(algorithm: AeadAlgorithm, segment_size: u8, parts: Vec<Vec<u8>>) -> bool {
+        let segment_size = u32::from(segment_size) + 1;
+        let (key, nonce) = key_nonce();
+        let mut sealer = SegmentSealer::new(algorithm, &key, &nonce, b"aad", segment_size).unwrap();
+        for part in &parts {
+            sealer.update(part).unwrap();
+        }
+        let mode = AeadMode::Chunked { segment_size };
+        sealer.finish().unwrap()
+            == mode
+                .encrypt(algorithm, &key, &nonce, b"aad", &parts.concat())
+                .unwrap()
+    }
+
+    #[test]
+    fn chunked_segments() {
+        let (key, nonce) = key_nonce();
+        let algorithm = AeadAlgorithm::default();
+        let mode = AeadMode::Chunked { segment_size: 4 };
+        let plaintext = b"0123456789";
+        let ciphertext = mode
+            .encrypt(algorithm, &key, &nonce, b"aad", plaintext)
+            .unwrap();
+        assert_eq!(mode.num_segments(ciphertext.len()), 3);
+        assert_eq!(mode.plaintext_len(ciphertext.len()), plaintext.len());
+
+        // Random access to individual segments.
+        assert_eq!(
+            mode.decrypt_segment(algorithm, &key, &nonce, b"aad", &ciphertext, 1)
+                .unwrap(),
+            b"4567"
+        );
+        assert_eq!(
+            mode.decrypt_segment(algorithm, &key, &nonce, b"aad", &ciphertext, 2)
+                .unwrap(),
+            b"89"
+        );
+        assert!(matches!(
+            mode.decrypt_segment(algorithm, &key, &nonce, b"aad", &ciphertext, 3),
+            Err(Error::InvalidSegment(_))
+        ));
+
+        // Truncation (dropping the last segment) is detected.
+        assert!(mode
+            .decrypt(algorithm, &key, &nonce, b"aad", &ciphertext[..2 * 20])
+            .is_err());
+        // So is reordering segments.
+        let swapped = [&ciphertext[20..40], &ciphertext[..20], &ciphertext[40..]].concat();
+        assert!(mode
+            .decrypt(algorithm, &key, &nonce, b"aad", &swapped)
+            .is_err());
+    }
+}
@@ -0,0 +1,124 @@
+/*
+ * paperback: paper backup generator suitable for long-term storage
+ * Copyright (C) 2018-2022 Aleksa Sarai <cyphar@cyphar.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use crate::v0::{ChaChaPolyKey, ChaChaPolyNonce};
+
+use aead::{Aead, NewAead, Payload};
+use aes_gcm_siv::Aes256GcmSiv;
+use chacha20poly1305::ChaCha20Poly1305;
+
+/// The AEAD algorithm used to encrypt the secret in the main document.
+///
+/// Documents encrypted with anything other than the default algorithm set a
+/// flag in their format version (and store the algorithm identifier in their
+/// metadata), so that older versions of paperback refuse to load them. Key
+/// shards are always encrypted with ChaCha20-Poly1305.
+#[derive(Copy, Clone, Debug, Default, Hash, PartialEq, Eq)]
+pub enum AeadAlgorithm {
+    /// ChaCha20-Poly1305 ([RFC 8439][rfc8439]).
+    ///
+    /// [rfc8439]: https://datatracker.ietf.org/doc/html/rfc8439
+    #[default]
+    ChaCha20Poly1305,
+    /// AES-256-GCM-SIV ([RFC 8452][rfc8452]), which is resistant to nonce
+    /// misuse and is hardware-accelerated on most modern CPUs.
+    ///
+    /// [rfc8452]: https://datatracker.ietf.org/doc/html/rfc8452
+    Aes256GcmSiv,
+}
+
+impl AeadAlgorithm {
+    /// All supported algorithms.
+    pub const ALL: [Self; 2] = [Self::ChaCha20Poly1305, Self::Aes256GcmSiv];
+
+    pub(crate) fn id(&self) -> u32 {
+        match self {
+            Self::ChaCha20Poly1305 => 0,
+            Self::Aes256GcmSiv => 1,
+        }
+    }
+
+    pub(crate) fn from_id(id: u32) -> Option<Self> {
+        match id {
+            0 => Some(Self::ChaCha20Poly1305),
+            1 => Some(Self::Aes256GcmSiv),
+            _ => None,
+        }
+    }
+
+    /// The name of the algorithm, as used in policies and on the command line.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::ChaCha20Poly1305 => "chacha20-poly1305",
+            Self::Aes256GcmSiv => "aes-256-gcm-siv",
+        }
+    }
+
+    /// Look up an algorithm by its [`AeadAlgorithm::name`].
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|algorithm| algorithm.name() == name)
+    }
+
+    pub(crate) fn cipher(&self, key: &ChaChaPolyKey) -> Cipher {
+        match self {
+            Self::ChaCha20Poly1305 => Cipher::ChaCha20Poly1305(ChaCha20Poly1305::new(key)),
+            Self::Aes256GcmSiv => Cipher::Aes256GcmSiv(Aes256GcmSiv::new(key)),
+        }
+    }
+}
+
+/// An [`AeadAlgorithm`] initialised with a key. Both algorithms use 256-bit
+/// keys, 96-bit nonces and 128-bit tags, so documents share the same key and
+/// nonce types regardless of the algorithm.
+pub(crate) enum Cipher {
+    ChaCha20Poly1305(ChaCha20Poly1305),
+    Aes256GcmSiv(Aes256GcmSiv),
+}
+
+impl Cipher {
+    pub(crate) fn encrypt(
+        &self,
+        nonce: &ChaChaPolyNonce,
+        payload: Payload,
+    ) -> Result<Vec<u8>, aead::Error> {
+        match self {
+            Self::ChaCha20Poly1305(aead) => aead.encrypt(nonce, payload),
+            Self::Aes256GcmSiv(aead) => aead.encrypt(nonce, payload),
+        }
+    }
+
+    pub(crate) fn decrypt(
+        &self,
+        nonce: &ChaChaPolyNonce,
+        payload: Payload,
+    ) -> Result<Vec<u8>, aead::Error> {
+        match self {
+            Self::ChaCha20Poly1305(aead) => aead.decrypt(nonce, payload),
+            Self::Aes256GcmSiv(aead) => aead.decrypt(nonce, payload),
+        }
+    }
+}
+
+#[cfg(test)]
+impl quickcheck::Arbitrary for AeadAlgorithm {
+    fn arbitrary(g: &mut quickcheck::Gen) -> Self {
+        *g.choose(&Self::ALL).unwrap()
+    }
+}
@@ -0,0 +1,382 @@
+/*
+ * paperback: paper backup generator suitable for long-term storage
+ * Copyright (C) 2018-2022 Aleksa Sarai <cyphar@cyphar.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Correction of mistakes made when entering key shard codewords.
+//!
+//! Codewords are typed in by hand (usually from a printed or handwritten
+//! copy), so the most common mistakes are hitting a neighbouring key and
+//! misreading similar-looking letters. When the codewords fail their built-in
+//! checksum, [`suggest`] uses a small error model of such mistakes to find the
+//! most likely intended codewords which *do* pass the checksum.
+
+use crate::v0::{KeyShardCodewords, CODEWORD_LANGUAGE};
+
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashSet},
+};
+
+use bip39::Mnemonic;
+use once_cell::sync::Lazy;
+
+/// The maximum number of suggestions returned by [`suggest`].
+pub const MAX_SUGGESTIONS: usize = 3;
+
+// Costs of each kind of mistake, roughly proportional to the negative
+// log-likelihood of the mistake (so a lower cost is a more likely mistake).
+const COST_SUBSTITUTE: u32 = 10;
+const COST_INSERT: u32 = 8;
+const COST_DELETE: u32 = 8;
+const COST_TRANSPOSE: u32 = 5;
+// Typing a doubled letter once (or a single letter twice).
+const COST_DOUBLED: u32 = 4;
+// Neighbouring keys on a QWERTY keyboard.
+const COST_KEYBOARD: u32 = 4;
+// Only writing down the first few letters of a codeword (which is enough to
+// uniquely identify it, as the first four letters of every word are unique).
+const COST_TRUNCATED: u32 = 2;
+
+// Lower bound on the cost of any mistake which changes the length of a word
+// (the cheapest multi-letter glyph confusion).
+const MIN_LENGTH_CHANGE_COST: u32 = 3;
+
+// Words further than this from what was typed are not considered.
+const MAX_WORD_COST: u32 = 20;
+// Number of candidates considered for each word.
+const MAX_WORD_CANDIDATES: usize = 6;
+// Upper bound on the number of phrases checked by suggest().
+const MAX_PHRASES: usize = 50_000;
+
+const KEYBOARD_ROWS: [&str; 3] = ["qwertyuiop", "asdfghjkl", "zxcvbnm"];
+
+// Letters (or groups of letters) which are easily confused when reading a
+// printed or handwritten copy, with the cost of each confusion.
+const GLYPH_CONFUSIONS: &[(&str, &str, u32)] = &[
+    ("rn", "m", 3),
+    ("cl", "d", 3),
+    ("vv", "w", 3),
+    ("ii", "u", 4),
+    ("i", "l", 3),
+    ("i", "j", 4),
+    ("l", "t", 5),
+    ("a", "o", 4),
+    ("a", "u", 5),
+    ("c", "e", 4),
+    ("e", "o", 5),
+    ("b", "h", 5),
+    ("h", "n", 4),
+    ("n", "u", 4),
+    ("u", "v", 3),
+    ("v", "y", 5),
+    ("g", "q", 4),
+    ("g", "y", 5),
+    ("f", "t", 5),
+    ("r", "v", 6),
+];
+
+// A confusion of the letters `from` (as typed) for `to`, and its cost.
+type Confusion = (&'static [u8], &'static [u8], u32);
+
+// The multi-letter entries of GLYPH_CONFUSIONS, in both directions.
+static MULTI_LETTER_CONFUSIONS: Lazy<Vec<Confusion>> = Lazy::new(|| {
+    GLYPH_CONFUSIONS
+        .iter()
+        .filter(|(a, b, _)| a.len() + b.len() > 2)
+        .flat_map(|&(a, b, cost)| {
+            [
+                (a.as_bytes(), b.as_bytes(), cost),
+                (b.as_bytes(), a.as_bytes(), cost),
+            ]
+        })
+        .collect()
+});
+
+fn keyboard_position(c: u8) -> Option<(i32, i32)> {
+    KEYBOARD_ROWS.iter().enumerate().find_map(|(row, keys)| {
+        keys.bytes()
+            .position(|k| k == c)
+            .map(|col| (row as i32, col as i32))
+    })
+}
+
+// Substitution costs between every pair of (lowercase) letters.
+static SUBSTITUTION_COSTS: Lazy<[[u32; 26]; 26]> = Lazy::new(|| {
+    let mut costs = [[0; 26]; 26];
+    for (typed, row) in (b'a'..=b'z').zip(costs.iter_mut()) {
+        for (intended, cost) in (b'a'..=b'z').zip(row.iter_mut()) {
+            *cost = letter_substitution_cost(typed, intended);
+        }
+    }
+    costs
+});
+
+fn substitution_cost(typed: u8, intended: u8) -> u32 {
+    match (typed, intended) {
+        _ if typed == intended => 0,
+        (b'a'..=b'z', b'a'..=b'z') => {
+            SUBSTITUTION_COSTS[(typed - b'a') as usize][(intended - b'a') as usize]
+        }
+        _ => COST_SUBSTITUTE,
+    }
+}
+
+fn letter_substitution_cost(typed: u8, intended: u8) -> u32 {
+    if typed == intended {
+        return 0;
+    }
+    let glyph_cost = GLYPH_CONFUSIONS
+        .iter()
+        .filter(|(a, b, _)| {
+            let (a, b) = (a.as_bytes(), b.as_bytes());
+            a.len() == 1
+                && b.len() == 1
+                && ((a[0], b[0]) == (typed, intended) || (b[0], a[0]) == (typed, intended))
+        })
+        .map(|&(_, _, cost)| cost)
+        .min();
+    // Keys on adjacent rows are offset by about half a key, so a key
+    // neighbours the key directly below it and the one to its left.
+    let keyboard_cost = match (keyboard_position(typed), keyboard_position(intended)) {
+        (Some((r1, c1)), Some((r2, c2))) => match (r1 - r2, c1 - c2) {
+            (0, -1 | 1) | (1, 0 | 1) | (-1, 0 | -1) => Some(COST_KEYBOARD),
+            _ => None,
+        },
+        _ => None,
+    };
+    glyph_cost
+        .into_iter()
+        .chain(keyboard_cost)
+        .min()
+        .unwrap_or(COST_SUBSTITUTE)
+}
+
+/// The cost of the mistakes needed to turn `intended` into `typed`, using a
+/// weighted Damerau–Levenshtein (optimal string alignment) distance with
+/// extra operations for multi-letter glyph confusions and doubled letters.
+fn mistake_cost(typed: &str, intended: &str) -> u32 {
+    let (t, w) = (typed.as_bytes(), intended.as_bytes());
+    if t.len() >= 4 && w.len() > t.len() && w.starts_with(t) {
+        return COST_TRUNCATED;
+    }
+    // Every mistake changes the length by at most one letter, and the
+    // cheapest mistake which does so is a multi-letter glyph confusion.
+    let min_cost = t.len().abs_diff(w.len()) as u32 * MIN_LENGTH_CHANGE_COST;
+    if min_cost > MAX_WORD_COST {
+        return min_cost;
+    }
+
+    // d[i][j] is the cost of turning w[..j] into t[..i].
+    let mut d = vec![vec![u32::MAX; w.len() + 1]; t.len() + 1];
+    d[0][0] = 0;
+    for i in 0..=t.len() {
+        for j in 0..=w.len() {
+            let mut best = d[i][j];
+            if i > 0 {
+                let cost = match i > 1 && t[i - 1] == t[i - 2] {
+                    true => COST_DOUBLED,
+                    false => COST_INSERT,
+                };
+                best = best.min(d[i - 1][j].saturating_add(cost));
+            }
+            if j > 0 {
+                let cost = match j > 1 && w[j - 1] == w[j - 2] {
+                    true => COST_DOUBLED,
+                    false => COST_DELETE,
+                };
+                best = best.min(d[i][j - 1].saturating_add(cost));
+            }
+            if i > 0 && j > 0 {
+                let cost = substitution_cost(t[i - 1], w[j - 1]);
+                best = best.min(d[i - 1][j - 1].saturating_add(cost));
+            }
+            if i > 1 && j > 1 && t[i - 1] == w[j - 2] && t[i - 2] == w[j - 1] {
+                best = best.min(d[i - 2][j - 2].saturating_add(COST_TRANSPOSE));
+            }
+            for &(from, to, cost) in MULTI_LETTER_CONFUSIONS.iter() {
+                if t[..i].ends_with(from) && w[..j].ends_with(to) {
+                    best = best.min(d[i - from.len()][j - to.len()].saturating_add(cost));
+                }
+            }
+            d[i][j] = best;
+        }
+    }
+    d[t.len()][w.len()]
+}
+
+/// Returns the most likely intended words (with their costs) for a typed word,
+/// cheapest first.
+fn word_candidates(typed: &str) -> Vec<(&'static str, u32)> {
+    let mut candidates = CODEWORD_LANGUAGE
+        .wordlist()
+        .get_words_by_prefix("")
+        .iter()
+        .map(|&word| (word, mistake_cost(typed, word)))
+        .filter(|&(_, cost)| cost <= MAX_WORD_COST)
+        .collect::<Vec<_>>();
+    candidates.sort_by_key(|&(word, cost)| (cost, word));
+    candidates.truncate(MAX_WORD_CANDIDATES);
+    candidates
+}
+
+/// Returns whether the codewords are valid (they are all known words and pass
+/// the checksum).
+pub fn is_valid<A: AsRef<[String]>>(codewords: A) -> bool {
+    let phrase = codewords.as_ref().join(" ").to_lowercase();
+    Mnemonic::validate(&phrase, CODEWORD_LANGUAGE).is_ok()
+}
+
+/// Suggest up to [`MAX_SUGGESTIONS`] corrections of mistyped codewords which
+/// pass the codeword checksum, most likely first.
+///
+/// If the codewords are already valid, no suggestions are returned. A
+/// suggestion passing the checksum is not a guarantee that it is correct, so
+/// suggestions must be confirmed by the user (and decrypting the key shard
+/// will still fail if the wrong suggestion is chosen).
+pub fn suggest<A: AsRef<[String]>>(codewords: A) -> Vec<KeyShardCodewords> {
+    let codewords = codewords.as_ref();
+    if codewords.is_empty() || is_valid(codewords) {
+        return vec![];
+    }
+    let candidates = codewords
+        .iter()
+        .map(|word| word_candidates(&word.to_lowercase()))
+        .collect::<Vec<_>>();
+    if candidates.iter().any(Vec::is_empty) {
+        return vec![];
+    }
+
+    // Enumerate phrases in order of increasing total cost. Each state is the
+    // index of the chosen candidate for every word, and its successors bump
+    // one index at or after the last bumped position (so that every state is
+    // reached exactly once).
+    let cost = |state: &[usize]| -> u32 {
+        state
+            .iter()
+            .zip(&candidates)
+            .map(|(&idx, words)| words[idx].1)
+            .sum()
+    };
+    let mut heap = BinaryHeap::new();
+    let start = vec![0; candidates.len()];
+    heap.push(Reverse((cost(&start), 0, start)));
+
+    let mut suggestions = Vec::new();
+    let mut seen = HashSet::new();
+    let mut checked = 0;
+    while let Some(Reverse((_, min_pos, state))) = heap.pop() {
+        let phrase = state
+            .iter()
+            .zip(&candidates)
+            .map(|(&idx, words)| words[idx].0.to_string())
+            .collect::<Vec<_>>();
+        if is_valid(&phrase) && seen.insert(phrase.clone()) {
+            suggestions.push(phrase);
+            if suggestions.len() == MAX_SUGGESTIONS {
+                break;
+            }
+        }
+        checked += 1;
+        if checked >= MAX_PHRASES {
+            break;
+        }
+        for pos in min_pos..state.len() {
+            if state[pos] + 1 < candidates[pos].len() {
+                let mut next = state.clone();
+                next[pos] += 1;
+                heap.push(Reverse((cost(&next), pos, next)));
+            }
+        }
+    }
+    suggestions
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn words(phrase: &str) -> Vec<String> {
+        phrase.split_whitespace().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn mistake_costs() {
+        assert_eq!(mistake_cost("abandon", "abandon"), 0);
+        // Neighbouring keys are cheaper than arbitrary substitutions.
+        assert!(mistake_cost("absnt", "absent") < mistake_cost("abxnt", "absent"));
+        assert_eq!(substitution_cost(b's', b'a'), COST_KEYBOARD);
+        assert_eq!(substitution_cost(b'u', b'v'), 3);
+        assert_eq!(substitution_cost(b'q', b'p'), COST_SUBSTITUTE);
+        // Glyph confusions, including multi-letter ones.
+        assert_eq!(mistake_cost("rnarble", "marble"), 3);
+        assert_eq!(
+            mistake_cost("rnarble", "marble"),
+            mistake_cost("marble", "rnarble")
+        );
+        assert_eq!(mistake_cost("clash", "dash"), 3);
+        assert_eq!(mistake_cost("lce", "ice"), 3);
+        assert_eq!(mistake_cost("cabgage", "cabbage"), COST_KEYBOARD);
+        assert_eq!(mistake_cost("cabbgae", "cabbage"), COST_TRANSPOSE);
+        assert_eq!(mistake_cost("cabage", "cabbage"), COST_DOUBLED);
+        assert_eq!(mistake_cost("caabbage", "cabbage"), COST_DOUBLED);
+        assert_eq!(mistake_cost("abso", "absorb"), COST_TRUNCATED);
+    }
+
+    #[test]
+    fn suggest_corrections() {
+        for seed in 0..8u8 {
+            let entropy = (0..32u8)
+                .map(|i| i.wrapping_mul(37).wrapping_add(seed.wrapping_mul(101)))
+                .collect::<Vec<_>>();
+            let codewords = words(
+                Mnemonic::from_entropy(&entropy, CODEWORD_LANGUAGE)
+                    .unwrap()
+                    .phrase(),
+            );
+            assert!(is_valid(&codewords));
+            assert!(suggest(&codewords).is_empty());
+
+            // Introduce a glyph confusion in one word and a keyboard slip in
+            // another.
+            let mut typo = codewords.clone();
+            typo[3] = typo[3].replacen('m', "rn", 1).replacen('a', "o", 1);
+            typo[17] = match typo[17].pop() {
+                Some('a') => typo[17].clone() + "s",
+                Some(c) => typo[17].clone() + &c.to_string() + &c.to_string(),
+                None => unreachable!("codewords are not empty"),
+            };
+            if is_valid(&typo) {
+                continue;
+            }
+            let suggestions = suggest(&typo);
+            assert!(suggestions.len() <= MAX_SUGGESTIONS);
+            assert!(suggestions.iter().all(is_valid));
+            assert!(
+                suggestions.contains(&codewords),
+                "{:?} not in {:?}",
+                codewords,
+                suggestions
+            );
+        }
+    }
+
+    #[test]
+    fn suggest_hopeless() {
+        assert!(suggest(words("zzzzzzzz qqqqqq")).is_empty());
+        assert!(suggest(Vec::<String>::new()).is_empty());
+    }
+}
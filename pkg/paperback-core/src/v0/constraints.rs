@@ -0,0 +1,284 @@
+/*
+ * paperback: paper backup generator suitable for long-term storage
+ * Copyright (C) 2018-2022 Aleksa Sarai <cyphar@cyphar.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Non-binding hints about the circumstances under which a secret should be
+//! recovered.
+//!
+//! Constraints are stored in the (authenticated) main document metadata, so
+//! they cannot be modified without invalidating the document. However,
+//! paperback cannot *enforce* them -- anyone with a quorum of key shards can
+//! recover the secret regardless of what the constraints say.
+
+use crate::v0::{Error, Warning, Warnings};
+
+use std::{
+    fmt,
+    str::FromStr,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// A recovery constraint hint embedded in a main document.
+///
+/// The textual form (used by [`FromStr`] and [`Display`](fmt::Display)) is
+/// `<kind>=<value>`, where dates are given as `YYYY-MM-DD` (in UTC).
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+pub enum RecoveryConstraint {
+    /// The secret should not be recovered before this date (in seconds since
+    /// the Unix epoch). Textual form: `not-before=YYYY-MM-DD`.
+    NotBefore(u64),
+    /// The constraints should be reviewed (and the backup re-created if they
+    /// no longer apply) before this date. Textual form: `review-by=YYYY-MM-DD`.
+    ReviewBy(u64),
+    /// The named party should be present during recovery. Textual form:
+    /// `requires-presence=<party>`.
+    RequiresPresence(String),
+    /// Recovery should take place at the given location. Textual form:
+    /// `location=<place>`.
+    Location(String),
+}
+
+impl RecoveryConstraint {
+    pub(crate) fn id(&self) -> u32 {
+        match self {
+            Self::NotBefore(_) => 0,
+            Self::ReviewBy(_) => 1,
+            Self::RequiresPresence(_) => 2,
+            Self::Location(_) => 3,
+        }
+    }
+
+    /// Returns whether the constraint is stale at the given time (in seconds
+    /// since the Unix epoch), meaning it should be reviewed.
+    ///
+    /// A `not-before` constraint is stale once its date has passed (the
+    /// constraint no longer restricts anything), and a `review-by` constraint
+    /// is stale once its review date has passed.
+    pub fn is_stale(&self, now: u64) -> bool {
+        match self {
+            Self::NotBefore(date) | Self::ReviewBy(date) => now >= *date,
+            Self::RequiresPresence(_) | Self::Location(_) => false,
+        }
+    }
+}
+
+// Convert a (proleptic Gregorian) civil date to days since the Unix epoch.
+// See <http://howardhinnant.github.io/date_algorithms.html#days_from_civil>.
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let mp = (month as i64 + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+// Convert days since the Unix epoch to a (proleptic Gregorian) civil date.
+// See <http://howardhinnant.github.io/date_algorithms.html#civil_from_days>.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+fn parse_date(date: &str) -> Result<u64, Error> {
+    let invalid = || Error::Other(format!("invalid date '{}' (expected YYYY-MM-DD)", date));
+    let parts = date
+        .split('-')
+        .map(|part| part.parse::<u32>().map_err(|_| invalid()))
+        .collect::<Result<Vec<_>, _>>()?;
+    let (year, month, day) = match parts[..] {
+        [year, month, day] => (year as i64, month, day),
+        _ => return Err(invalid()),
+    };
+    if year < 1970 || !(1..=12).contains(&month) || day == 0 {
+        return Err(invalid());
+    }
+    let days = days_from_civil(year, month, day);
+    // Reject dates like 2023-02-30 by making sure they roundtrip.
+    if civil_from_days(days) != (year, month, day) {
+        return Err(invalid());
+    }
+    Ok(days as u64 * SECONDS_PER_DAY)
+}
+
+/// Format a timestamp (in seconds since the Unix epoch) as a `YYYY-MM-DD`
+/// UTC date.
+pub fn format_date(timestamp: u64) -> String {
+    let (year, month, day) = civil_from_days((timestamp / SECONDS_PER_DAY) as i64);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// Format a timestamp (in seconds since the Unix epoch) as an RFC 3339 UTC
+/// date and time.
+pub fn format_datetime(timestamp: u64) -> String {
+    let seconds = timestamp % SECONDS_PER_DAY;
+    format!(
+        "{}T{:02}:{:02}:{:02}Z",
+        format_date(timestamp),
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+impl fmt::Display for RecoveryConstraint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotBefore(date) => write!(f, "not-before={}", format_date(*date)),
+            Self::ReviewBy(date) => write!(f, "review-by={}", format_date(*date)),
+            Self::RequiresPresence(party) => write!(f, "requires-presence={}", party),
+            Self::Location(place) => write!(f, "location={}", place),
+        }
+    }
+}
+
+impl FromStr for RecoveryConstraint {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, value) = s.split_once('=').ok_or_else(|| {
+            Error::Other(format!(
+                "invalid recovery constraint '{}' (expected <kind>=<value>)",
+                s
+            ))
+        })?;
+        if value.is_empty() {
+            return Err(Error::Other(format!(
+                "recovery constraint '{}' has an empty value",
+                kind
+            )));
+        }
+        match kind {
+            "not-before" => Ok(Self::NotBefore(parse_date(value)?)),
+            "review-by" => Ok(Self::ReviewBy(parse_date(value)?)),
+            "requires-presence" => Ok(Self::RequiresPresence(value.to_string())),
+            "location" => Ok(Self::Location(value.to_string())),
+            _ => Err(Error::Other(format!(
+                "unknown recovery constraint kind '{}'",
+                kind
+            ))),
+        }
+    }
+}
+
+/// Check a set of recovery constraints for staleness at the given time,
+/// returning a [`Warning::StaleConstraint`] for each stale constraint.
+pub fn audit(constraints: &[RecoveryConstraint], now: SystemTime) -> Warnings {
+    let now = now
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    constraints
+        .iter()
+        .filter(|c| c.is_stale(now))
+        .cloned()
+        .map(Warning::StaleConstraint)
+        .collect()
+}
+
+#[cfg(test)]
+impl quickcheck::Arbitrary for RecoveryConstraint {
+    fn arbitrary(g: &mut quickcheck::Gen) -> Self {
+        match u8::arbitrary(g) % 4 {
+            0 => Self::NotBefore(u64::arbitrary(g)),
+            1 => Self::ReviewBy(u64::arbitrary(g)),
+            2 => Self::RequiresPresence(String::arbitrary(g)),
+            _ => Self::Location(String::arbitrary(g)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::time::Duration;
+
+    #[test]
+    fn constraint_parse_roundtrip() {
+        for s in [
+            "not-before=2030-01-01",
+            "review-by=2024-02-29",
+            "requires-presence=executor",
+            "location=Sydney, Australia",
+        ] {
+            let constraint: RecoveryConstraint = s.parse().unwrap();
+            assert_eq!(constraint.to_string(), s);
+        }
+        assert_eq!(
+            "not-before=1970-01-02"
+                .parse::<RecoveryConstraint>()
+                .unwrap(),
+            RecoveryConstraint::NotBefore(SECONDS_PER_DAY)
+        );
+    }
+
+    #[test]
+    fn constraint_parse_invalid() {
+        for s in [
+            "not-before",
+            "not-before=",
+            "not-before=2023-02-30",
+            "not-before=2023-13-01",
+            "not-before=tomorrow",
+            "open-after=2030-01-01",
+        ] {
+            assert!(s.parse::<RecoveryConstraint>().is_err(), "{}", s);
+        }
+    }
+
+    #[quickcheck]
+    fn date_roundtrip(days: u32) -> bool {
+        let timestamp = days as u64 * SECONDS_PER_DAY;
+        parse_date(&format_date(timestamp)).unwrap() == timestamp
+    }
+
+    #[test]
+    fn constraint_audit() {
+        let constraints = [
+            "not-before=2030-01-01",
+            "review-by=2025-06-01",
+            "requires-presence=executor",
+        ]
+        .iter()
+        .map(|s| s.parse().unwrap())
+        .collect::<Vec<RecoveryConstraint>>();
+
+        let before = UNIX_EPOCH + Duration::from_secs(parse_date("2025-01-01").unwrap());
+        assert!(audit(&constraints, before).is_empty());
+
+        let after = UNIX_EPOCH + Duration::from_secs(parse_date("2031-01-01").unwrap());
+        assert_eq!(
+            audit(&constraints, after).into_iter().collect::<Vec<_>>(),
+            vec![
+                Warning::StaleConstraint(constraints[0].clone()),
+                Warning::StaleConstraint(constraints[1].clone()),
+            ]
+        );
+    }
+}
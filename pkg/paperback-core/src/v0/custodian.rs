@@ -0,0 +1,160 @@
+/*
+ * paperback: paper backup generator suitable for long-term storage
+ * Copyright (C) 2018-2022 Aleksa Sarai <cyphar@cyphar.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Free-text details about who holds a key shard.
+//!
+//! A [`CustodianInfo`] is stored inside a key shard (see
+//! [`Backup::assign_custodian`]) and printed on its PDF, to help whoever is
+//! collecting key shards during recovery find (and contact) their holders.
+//! The details are covered by the key shard's signature, so they cannot be
+//! changed without invalidating the key shard.
+//!
+//! [`Backup::assign_custodian`]: crate::v0::Backup::assign_custodian
+
+use crate::v0::Error;
+
+use serde::Deserialize;
+
+/// Details about the holder of a key shard. Every field is optional.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq, Hash)]
+#[serde(default, deny_unknown_fields)]
+pub struct CustodianInfo {
+    /// The name of the holder.
+    pub name: Option<String>,
+    /// A hint for how to contact the holder.
+    pub contact: Option<String>,
+    /// Any other note about the key shard.
+    pub note: Option<String>,
+}
+
+impl CustodianInfo {
+    /// The maximum length (in bytes) of each field, so that the details fit
+    /// on a single line of the key shard PDF.
+    pub const MAX_FIELD_LENGTH: usize = 64;
+
+    /// Parse a JSON list of custodians, such as:
+    ///
+    /// ```json
+    /// [
+    ///   { "name": "alice", "contact": "alice@example.com" },
+    ///   { "name": "bob", "note": "safe deposit box 42" }
+    /// ]
+    /// ```
+    pub fn list_from_json<S: AsRef<str>>(json: S) -> Result<Vec<Self>, Error> {
+        let custodians: Vec<Self> = serde_json::from_str(json.as_ref())
+            .map_err(|err| Error::Other(format!("invalid custodian list: {}", err)))?;
+        for custodian in &custodians {
+            custodian.validate().map_err(Error::Other)?;
+        }
+        Ok(custodians)
+    }
+
+    /// Returns whether none of the fields are set.
+    pub fn is_empty(&self) -> bool {
+        self.fields().all(|(_, value)| value.is_none())
+    }
+
+    // The custodian details with the largest encoding: every field set, and as
+    // long as possible.
+    pub(super) fn largest() -> Self {
+        let field = || Some("x".repeat(Self::MAX_FIELD_LENGTH));
+        Self {
+            name: field(),
+            contact: field(),
+            note: field(),
+        }
+    }
+
+    // The name and value of each field.
+    pub(super) fn fields(&self) -> impl Iterator<Item = (&'static str, Option<&str>)> {
+        [
+            ("name", self.name.as_deref()),
+            ("contact", self.contact.as_deref()),
+            ("note", self.note.as_deref()),
+        ]
+        .into_iter()
+    }
+
+    pub(super) fn validate(&self) -> Result<(), String> {
+        for (field, value) in self.fields() {
+            let value = match value {
+                Some(value) => value,
+                None => continue,
+            };
+            if value.is_empty() {
+                return Err(format!("custodian {} must not be empty", field));
+            }
+            if value.len() > Self::MAX_FIELD_LENGTH {
+                return Err(format!(
+                    "custodian {} '{}' is longer than {} bytes",
+                    field,
+                    value,
+                    Self::MAX_FIELD_LENGTH
+                ));
+            }
+            if value.chars().any(char::is_control) {
+                return Err(format!(
+                    "custodian {} {:?} must not contain control characters",
+                    field, value
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+impl quickcheck::Arbitrary for CustodianInfo {
+    fn arbitrary(g: &mut quickcheck::Gen) -> Self {
+        let mut field = || match bool::arbitrary(g) {
+            false => None,
+            true => Some(format!("{:x}", u64::arbitrary(g))),
+        };
+        Self {
+            name: field(),
+            contact: field(),
+            note: field(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn custodian_list_from_json() {
+        let custodians = CustodianInfo::list_from_json(
+            r#"[{"name": "alice", "contact": "alice@example.com"}, {}, {"note": "box 42"}]"#,
+        )
+        .unwrap();
+        assert_eq!(custodians.len(), 3);
+        assert_eq!(custodians[0].name.as_deref(), Some("alice"));
+        assert!(custodians[1].is_empty());
+        assert_eq!(custodians[2].note.as_deref(), Some("box 42"));
+
+        assert!(CustodianInfo::list_from_json(r#"[{"nmae": "alice"}]"#).is_err());
+        assert!(CustodianInfo::list_from_json(r#"[{"name": ""}]"#).is_err());
+        assert!(CustodianInfo::list_from_json(r#"[{"note": "two\nlines"}]"#).is_err());
+        assert!(CustodianInfo::list_from_json(format!(
+            r#"[{{"name": "{}"}}]"#,
+            "x".repeat(CustodianInfo::MAX_FIELD_LENGTH + 1)
+        ))
+        .is_err());
+    }
+}
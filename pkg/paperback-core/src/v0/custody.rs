@@ -0,0 +1,789 @@
+/*
+ * paperback: paper backup generator suitable for long-term storage
+ * Copyright (C) 2018-2022 Aleksa Sarai <cyphar@cyphar.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Shard ownership by holders identified by public keys.
+//!
+//! Each holder has an Ed25519 keypair, and the public keys of all holders are
+//! listed in a small [`HolderDirectory`]. Key shards can then be additionally
+//! encrypted to their holder's key (so that a stolen shard is useless without
+//! the holder's key, even with the codewords), and holders can sign
+//! [`CustodyReceipt`]s to give a verifiable chain-of-custody for each shard.
+//!
+//! The owner of a backup can also periodically check that holders still have
+//! their key shards, by issuing a [`PossessionChallenge`] which the holder
+//! answers (using only their printed key shard) with a short
+//! [`PossessionResponse`]. The response is checked against a
+//! [`PossessionVerifier`] the owner computed when the shards were created.
+//!
+//! Key shards can also be collected by a courier who is only partially
+//! trusted. Each holder seals their key shard to the coordinator's key and
+//! signs an [`EnvelopeProof`] stating which key shard is inside, and the
+//! courier carries the envelopes and proofs as a [`CourierBatch`]. The
+//! coordinator can then check that every expected key shard has arrived (and
+//! that no envelope was swapped) before opening any of the envelopes.
+
+use crate::v0::{
+    ChaChaPolyKey, ChaChaPolyNonce, EncryptedKeyShard, Error, FromWire, ToWire, CHECKSUM_ALGORITHM,
+    CHECKSUM_MULTIBASE,
+};
+
+use aead::{Aead, NewAead, Payload};
+use chacha20poly1305::ChaCha20Poly1305;
+use curve25519_dalek::montgomery::MontgomeryPoint;
+use ed25519_dalek::{Signature, Signer, Verifier};
+use multihash::{Multihash, MultihashDigest};
+use rand::{rngs::OsRng, RngCore};
+use serde::Deserialize;
+use std::{fmt, str::FromStr};
+
+pub use ed25519_dalek::{SigningKey, VerifyingKey};
+
+const KEY_MULTIBASE: multibase::Base = multibase::Base::Base32Z;
+
+/// Generate a new holder keypair.
+pub fn generate_secret_key() -> SigningKey {
+    SigningKey::generate(&mut OsRng)
+}
+
+/// Encode a holder's public key in the form used by [`HolderDirectory`].
+pub fn encode_public_key(key: &VerifyingKey) -> String {
+    multibase::encode(KEY_MULTIBASE, key.as_bytes())
+}
+
+/// Decode a holder's public key in the form used by [`HolderDirectory`].
+pub fn decode_public_key<S: AsRef<str>>(key: S) -> Result<VerifyingKey, Error> {
+    let (_, bytes) = multibase::decode(key)
+        .map_err(|err| Error::Other(format!("invalid public key: {}", err)))?;
+    let bytes = bytes
+        .try_into()
+        .map_err(|_| Error::Other("invalid public key: wrong length".into()))?;
+    VerifyingKey::from_bytes(&bytes)
+        .map_err(|err| Error::Other(format!("invalid public key: {}", err)))
+}
+
+/// Encode a holder's secret key.
+pub fn encode_secret_key(key: &SigningKey) -> String {
+    multibase::encode(KEY_MULTIBASE, key.as_bytes())
+}
+
+/// Decode a holder's secret key.
+pub fn decode_secret_key<S: AsRef<str>>(key: S) -> Result<SigningKey, Error> {
+    let (_, bytes) = multibase::decode(key)
+        .map_err(|err| Error::Other(format!("invalid secret key: {}", err)))?;
+    let bytes: [u8; ed25519_dalek::SECRET_KEY_LENGTH] = bytes
+        .try_into()
+        .map_err(|_| Error::Other("invalid secret key: wrong length".into()))?;
+    Ok(SigningKey::from_bytes(&bytes))
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+struct RawHolder {
+    name: String,
+    public_key: String,
+}
+
+/// A shard holder, identified by their public key.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(try_from = "RawHolder")]
+pub struct Holder {
+    pub name: String,
+    pub public_key: VerifyingKey,
+}
+
+impl TryFrom<RawHolder> for Holder {
+    type Error = Error;
+
+    fn try_from(raw: RawHolder) -> Result<Self, Self::Error> {
+        Ok(Self {
+            public_key: decode_public_key(&raw.public_key)?,
+            name: raw.name,
+        })
+    }
+}
+
+/// A directory of shard holders, stored as a JSON document:
+///
+/// ```json
+/// {
+///   "holders": [
+///     { "name": "alice", "public-key": "h<z-base32 ed25519 public key>" }
+///   ]
+/// }
+/// ```
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct HolderDirectory {
+    pub holders: Vec<Holder>,
+}
+
+impl HolderDirectory {
+    pub fn from_json<S: AsRef<str>>(json: S) -> Result<Self, Error> {
+        let directory: Self = serde_json::from_str(json.as_ref())
+            .map_err(|err| Error::Other(format!("invalid holder directory: {}", err)))?;
+        for (idx, holder) in directory.holders.iter().enumerate() {
+            if directory.holders[..idx]
+                .iter()
+                .any(|h| h.name == holder.name || h.public_key == holder.public_key)
+            {
+                return Err(Error::Other(format!(
+                    "invalid holder directory: duplicate holder '{}'",
+                    holder.name
+                )));
+            }
+        }
+        Ok(directory)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Holder> {
+        self.holders.iter().find(|h| h.name == name)
+    }
+
+    pub fn find_key(&self, public_key: &VerifyingKey) -> Option<&Holder> {
+        self.holders.iter().find(|h| &h.public_key == public_key)
+    }
+}
+
+/// An [`EncryptedKeyShard`] which has been additionally encrypted to a
+/// holder's public key, using an ephemeral X25519 key exchange with the
+/// holder's (birationally-mapped) Ed25519 key.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SealedKeyShard {
+    pub(super) holder: VerifyingKey,
+    pub(super) ephemeral: [u8; 32],
+    pub(super) nonce: ChaChaPolyNonce,
+    pub(super) ciphertext: Vec<u8>,
+}
+
+fn seal_key(
+    shared: &MontgomeryPoint,
+    ephemeral: &[u8; 32],
+    holder: &VerifyingKey,
+) -> ChaChaPolyKey {
+    let mut bytes = shared.as_bytes().to_vec();
+    bytes.extend_from_slice(ephemeral);
+    bytes.extend_from_slice(holder.as_bytes());
+    let mut key = ChaChaPolyKey::default();
+    key.copy_from_slice(CHECKSUM_ALGORITHM.digest(&bytes).digest());
+    key
+}
+
+impl SealedKeyShard {
+    /// The public key of the holder this shard is sealed to.
+    pub fn holder(&self) -> &VerifyingKey {
+        &self.holder
+    }
+
+    pub fn seal(shard: &EncryptedKeyShard, holder: &VerifyingKey) -> Result<Self, Error> {
+        let mut ephemeral_secret = [0u8; 32];
+        OsRng.fill_bytes(&mut ephemeral_secret);
+        let ephemeral = MontgomeryPoint::mul_base_clamped(ephemeral_secret).to_bytes();
+        let shared = holder.to_montgomery().mul_clamped(ephemeral_secret);
+
+        let mut nonce = ChaChaPolyNonce::default();
+        OsRng.fill_bytes(&mut nonce);
+
+        let aead = ChaCha20Poly1305::new(&seal_key(&shared, &ephemeral, holder));
+        let ciphertext = aead
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: &shard.to_wire(),
+                    aad: holder.as_bytes(),
+                },
+            )
+            .map_err(Error::AeadEncryption)?;
+
+        Ok(Self {
+            holder: *holder,
+            ephemeral,
+            nonce,
+            ciphertext,
+        })
+    }
+
+    pub fn open(&self, holder_key: &SigningKey) -> Result<EncryptedKeyShard, Error> {
+        if holder_key.verifying_key() != self.holder {
+            return Err(Error::InvariantViolation(
+                "key shard is sealed to a different holder",
+            ));
+        }
+        let shared = MontgomeryPoint(self.ephemeral).mul_clamped(holder_key.to_scalar_bytes());
+
+        let aead = ChaCha20Poly1305::new(&seal_key(&shared, &self.ephemeral, &self.holder));
+        let wire_shard = aead
+            .decrypt(
+                &self.nonce,
+                Payload {
+                    msg: &self.ciphertext,
+                    aad: self.holder.as_bytes(),
+                },
+            )
+            .map_err(Error::AeadDecryption)?;
+
+        EncryptedKeyShard::from_wire(wire_shard).map_err(Error::ShardSecretDecode)
+    }
+}
+
+/// A statement signed by a holder about a particular key shard (such as "I
+/// received this shard"), forming a chain-of-custody for the shard.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CustodyReceipt {
+    pub(super) shard_chksum: Multihash,
+    pub(super) holder: VerifyingKey,
+    pub(super) note: String,
+    pub(super) signature: Signature,
+}
+
+impl CustodyReceipt {
+    // Domain separation for receipt signatures, so they cannot be confused
+    // with any other paperback signature.
+    const SIGNATURE_CONTEXT: &'static [u8] = b"paperback-custody-receipt-v0";
+
+    fn signable_bytes(shard_chksum: &Multihash, holder: &VerifyingKey, note: &str) -> Vec<u8> {
+        let mut bytes = Self::SIGNATURE_CONTEXT.to_vec();
+        bytes.extend_from_slice(&shard_chksum.to_bytes());
+        bytes.extend_from_slice(holder.as_bytes());
+        bytes.extend_from_slice(note.as_bytes());
+        bytes
+    }
+
+    pub fn sign<S: Into<String>>(
+        shard: &EncryptedKeyShard,
+        holder_key: &SigningKey,
+        note: S,
+    ) -> Self {
+        let shard_chksum = shard.checksum();
+        let holder = holder_key.verifying_key();
+        let note = note.into();
+        let signature = holder_key.sign(&Self::signable_bytes(&shard_chksum, &holder, &note));
+        Self {
+            shard_chksum,
+            holder,
+            note,
+            signature,
+        }
+    }
+
+    pub fn holder(&self) -> &VerifyingKey {
+        &self.holder
+    }
+
+    pub fn note(&self) -> &str {
+        &self.note
+    }
+
+    /// Verify that the receipt was signed by its holder and refers to the
+    /// given key shard.
+    pub fn verify(&self, shard: &EncryptedKeyShard) -> Result<(), Error> {
+        if shard.checksum() != self.shard_chksum {
+            return Err(Error::InvariantViolation(
+                "custody receipt refers to a different key shard",
+            ));
+        }
+        self.holder
+            .verify(
+                &Self::signable_bytes(&self.shard_chksum, &self.holder, &self.note),
+                &self.signature,
+            )
+            .map_err(|_| Error::InvariantViolation("custody receipt signature is invalid"))
+    }
+}
+
+/// A holder's signed statement that an envelope (a [`SealedKeyShard`]
+/// addressed to the coordinator) contains a particular key shard.
+///
+/// The proof only contains checksums, so it can be checked by anyone without
+/// opening the envelope or learning anything about the key shard's contents.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EnvelopeProof {
+    pub(super) envelope_chksum: Multihash,
+    pub(super) shard_chksum: Multihash,
+    pub(super) holder: VerifyingKey,
+    pub(super) signature: Signature,
+}
+
+fn envelope_checksum(envelope: &SealedKeyShard) -> Multihash {
+    CHECKSUM_ALGORITHM.digest(&envelope.to_wire())
+}
+
+impl EnvelopeProof {
+    // Domain separation for envelope proof signatures, so they cannot be
+    // confused with any other paperback signature.
+    const SIGNATURE_CONTEXT: &'static [u8] = b"paperback-envelope-proof-v0";
+
+    fn signable_bytes(
+        envelope_chksum: &Multihash,
+        shard_chksum: &Multihash,
+        holder: &VerifyingKey,
+    ) -> Vec<u8> {
+        let mut bytes = Self::SIGNATURE_CONTEXT.to_vec();
+        bytes.extend_from_slice(&envelope_chksum.to_bytes());
+        bytes.extend_from_slice(&shard_chksum.to_bytes());
+        bytes.extend_from_slice(holder.as_bytes());
+        bytes
+    }
+
+    /// Seal a key shard to the coordinator, and sign a proof of the
+    /// envelope's contents with the holder's key.
+    pub fn seal(
+        shard: &EncryptedKeyShard,
+        holder_key: &SigningKey,
+        coordinator: &VerifyingKey,
+    ) -> Result<(SealedKeyShard, Self), Error> {
+        let envelope = SealedKeyShard::seal(shard, coordinator)?;
+        let envelope_chksum = envelope_checksum(&envelope);
+        let shard_chksum = shard.checksum();
+        let holder = holder_key.verifying_key();
+        let signature = holder_key.sign(&Self::signable_bytes(
+            &envelope_chksum,
+            &shard_chksum,
+            &holder,
+        ));
+        Ok((
+            envelope,
+            Self {
+                envelope_chksum,
+                shard_chksum,
+                holder,
+                signature,
+            },
+        ))
+    }
+
+    /// The public key of the holder who signed the proof.
+    pub fn holder(&self) -> &VerifyingKey {
+        &self.holder
+    }
+
+    /// The checksum of the key shard inside the envelope.
+    pub fn shard_checksum(&self) -> &Multihash {
+        &self.shard_chksum
+    }
+
+    /// The checksum of the key shard inside the envelope, in the same format
+    /// as [`EncryptedKeyShard::checksum_string`].
+    pub fn shard_checksum_string(&self) -> String {
+        multibase::encode(CHECKSUM_MULTIBASE, self.shard_chksum.to_bytes())
+    }
+
+    /// Verify that the proof was signed by its holder and refers to the given
+    /// envelope.
+    pub fn verify(&self, envelope: &SealedKeyShard) -> Result<(), Error> {
+        if envelope_checksum(envelope) != self.envelope_chksum {
+            return Err(Error::InvariantViolation(
+                "envelope proof refers to a different envelope",
+            ));
+        }
+        self.holder
+            .verify(
+                &Self::signable_bytes(&self.envelope_chksum, &self.shard_chksum, &self.holder),
+                &self.signature,
+            )
+            .map_err(|_| Error::InvariantViolation("envelope proof signature is invalid"))
+    }
+}
+
+/// A set of envelopes (with their [`EnvelopeProof`]s) collected from holders
+/// by a courier.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CourierBatch {
+    pub(super) envelopes: Vec<(SealedKeyShard, EnvelopeProof)>,
+}
+
+impl CourierBatch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add an envelope collected from a holder to the batch.
+    pub fn push(&mut self, envelope: SealedKeyShard, proof: EnvelopeProof) -> &mut Self {
+        self.envelopes.push((envelope, proof));
+        self
+    }
+
+    /// Add all of the envelopes in another batch to this batch.
+    pub fn extend(&mut self, other: CourierBatch) -> &mut Self {
+        self.envelopes.extend(other.envelopes);
+        self
+    }
+
+    /// The envelopes in the batch, in the order they were collected.
+    pub fn envelopes(&self) -> &[(SealedKeyShard, EnvelopeProof)] {
+        &self.envelopes
+    }
+
+    pub fn len(&self) -> usize {
+        self.envelopes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.envelopes.is_empty()
+    }
+
+    /// Check, without opening any envelope, that the batch contains exactly
+    /// the `expected` key shards, each in a correctly-signed envelope sealed
+    /// to `coordinator`.
+    ///
+    /// This does not check who signed each proof -- use
+    /// [`HolderDirectory::find_key`] with [`EnvelopeProof::holder`] for that.
+    pub fn verify(&self, expected: &[Multihash], coordinator: &VerifyingKey) -> Result<(), Error> {
+        let mut seen = Vec::with_capacity(self.envelopes.len());
+        for (envelope, proof) in &self.envelopes {
+            if envelope.holder() != coordinator {
+                return Err(Error::InvariantViolation(
+                    "envelope is sealed to a different coordinator",
+                ));
+            }
+            proof.verify(envelope)?;
+            if !expected.contains(proof.shard_checksum()) {
+                return Err(Error::Other(format!(
+                    "envelope contains unexpected key shard {}",
+                    proof.shard_checksum_string()
+                )));
+            }
+            if seen.contains(&proof.shard_checksum()) {
+                return Err(Error::Other(format!(
+                    "key shard {} is in more than one envelope",
+                    proof.shard_checksum_string()
+                )));
+            }
+            seen.push(proof.shard_checksum());
+        }
+
+        let missing = expected
+            .iter()
+            .filter(|chksum| !seen.contains(chksum))
+            .map(|chksum| multibase::encode(CHECKSUM_MULTIBASE, chksum.to_bytes()))
+            .collect::<Vec<_>>();
+        match missing.len() {
+            0 => Ok(()),
+            _ => Err(Error::Other(format!(
+                "batch is missing key shards [{}]",
+                missing.join(" ")
+            ))),
+        }
+    }
+
+    /// Open every envelope in the batch with the coordinator's key, checking
+    /// that each envelope contains the key shard its proof claims.
+    ///
+    /// [`CourierBatch::verify`] should be used first, so that a tampered batch
+    /// is detected before any envelope is opened.
+    pub fn open(&self, coordinator_key: &SigningKey) -> Result<Vec<EncryptedKeyShard>, Error> {
+        self.envelopes
+            .iter()
+            .map(|(envelope, proof)| {
+                let shard = envelope.open(coordinator_key)?;
+                match &shard.checksum() == proof.shard_checksum() {
+                    true => Ok(shard),
+                    false => Err(Error::InvariantViolation(
+                        "envelope does not contain the key shard its proof claims",
+                    )),
+                }
+            })
+            .collect()
+    }
+}
+
+/// Length of a [`PossessionChallenge`] in bytes.
+const CHALLENGE_LENGTH: usize = 16;
+/// Length of a [`PossessionResponse`] in bytes (16 z-base-32 characters).
+const RESPONSE_LENGTH: usize = 10;
+
+/// A random challenge issued to a holder to prove they still have their key
+/// shard.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PossessionChallenge([u8; CHALLENGE_LENGTH]);
+
+impl PossessionChallenge {
+    pub fn new() -> Self {
+        let mut challenge = [0u8; CHALLENGE_LENGTH];
+        OsRng.fill_bytes(&mut challenge);
+        Self(challenge)
+    }
+}
+
+impl Default for PossessionChallenge {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Display for PossessionChallenge {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", multibase::encode(KEY_MULTIBASE, self.0))
+    }
+}
+
+impl FromStr for PossessionChallenge {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (_, bytes) = multibase::decode(s.trim())
+            .map_err(|err| Error::Other(format!("invalid challenge: {}", err)))?;
+        Ok(Self(bytes.try_into().map_err(|_| {
+            Error::Other("invalid challenge: wrong length".into())
+        })?))
+    }
+}
+
+/// A holder's response to a [`PossessionChallenge`].
+///
+/// The response is short enough to be read out or typed in by hand, and does
+/// not reveal anything about the key shard's contents.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PossessionResponse([u8; RESPONSE_LENGTH]);
+
+impl PossessionResponse {
+    /// Compute the response to a challenge using the holder's key shard.
+    pub fn new(shard: &EncryptedKeyShard, challenge: &PossessionChallenge) -> Self {
+        Self::from_key(&PossessionVerifier::possession_key(shard), challenge)
+    }
+
+    fn from_key(key: &Multihash, challenge: &PossessionChallenge) -> Self {
+        let mut bytes = PossessionVerifier::RESPONSE_CONTEXT.to_vec();
+        bytes.extend_from_slice(key.digest());
+        bytes.extend_from_slice(&challenge.0);
+        let mut response = [0u8; RESPONSE_LENGTH];
+        response.copy_from_slice(&CHECKSUM_ALGORITHM.digest(&bytes).digest()[..RESPONSE_LENGTH]);
+        Self(response)
+    }
+}
+
+impl fmt::Display for PossessionResponse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Split into 4-character words to make it easier to read out.
+        let encoded = multibase::Base::Base32Z.encode(self.0);
+        let words = encoded
+            .as_bytes()
+            .chunks(4)
+            .map(String::from_utf8_lossy)
+            .collect::<Vec<_>>();
+        write!(f, "{}", words.join("-"))
+    }
+}
+
+impl FromStr for PossessionResponse {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let stripped = s
+            .chars()
+            .filter(|&ch| !ch.is_whitespace() && ch != '-')
+            .collect::<String>();
+        let bytes = multibase::Base::Base32Z
+            .decode(stripped)
+            .map_err(|err| Error::Other(format!("invalid response: {}", err)))?;
+        Ok(Self(bytes.try_into().map_err(|_| {
+            Error::Other("invalid response: wrong length".into())
+        })?))
+    }
+}
+
+/// Owner-side information used to check [`PossessionResponse`]s for a key
+/// shard, which must be computed from the key shard when it is created.
+///
+/// The verifier does not reveal anything about the key shard's contents, but
+/// anyone with the verifier can forge responses, so it should be kept private
+/// by the owner.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PossessionVerifier {
+    pub(super) shard_chksum: Multihash,
+    pub(super) key: Multihash,
+}
+
+impl PossessionVerifier {
+    // Domain separation for the possession key and responses.
+    const KEY_CONTEXT: &'static [u8] = b"paperback-possession-key-v0";
+    const RESPONSE_CONTEXT: &'static [u8] = b"paperback-possession-response-v0";
+
+    fn possession_key(shard: &EncryptedKeyShard) -> Multihash {
+        let mut bytes = Self::KEY_CONTEXT.to_vec();
+        bytes.append(&mut shard.to_wire());
+        CHECKSUM_ALGORITHM.digest(&bytes)
+    }
+
+    pub fn new(shard: &EncryptedKeyShard) -> Self {
+        Self {
+            shard_chksum: shard.checksum(),
+            key: Self::possession_key(shard),
+        }
+    }
+
+    /// The checksum of the key shard this verifier is for.
+    pub fn shard_checksum(&self) -> &Multihash {
+        &self.shard_chksum
+    }
+
+    /// The checksum of the key shard this verifier is for, in the same format
+    /// as [`EncryptedKeyShard::checksum_string`].
+    pub fn shard_checksum_string(&self) -> String {
+        multibase::encode(CHECKSUM_MULTIBASE, self.shard_chksum.to_bytes())
+    }
+
+    /// Check a holder's response to the given challenge.
+    pub fn verify(
+        &self,
+        challenge: &PossessionChallenge,
+        response: &PossessionResponse,
+    ) -> Result<(), Error> {
+        let expected = PossessionResponse::from_key(&self.key, challenge);
+        // Avoid leaking how much of the response was correct.
+        let diff = expected
+            .0
+            .iter()
+            .zip(&response.0)
+            .fold(0, |acc, (a, b)| acc | (a ^ b));
+        match diff {
+            0 => Ok(()),
+            _ => Err(Error::InvariantViolation(
+                "possession response does not match challenge",
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::v0::Backup;
+
+    fn encrypted_shard() -> EncryptedKeyShard {
+        let backup = Backup::new(2, b"secret").unwrap();
+        backup.next_shard().unwrap().encrypt().unwrap().0
+    }
+
+    #[test]
+    fn sealed_shard_roundtrip() {
+        let shard = encrypted_shard();
+        let holder_key = SigningKey::generate(&mut OsRng);
+        let other_key = SigningKey::generate(&mut OsRng);
+
+        let sealed = SealedKeyShard::seal(&shard, &holder_key.verifying_key()).unwrap();
+        let sealed = SealedKeyShard::from_wire(sealed.to_wire()).unwrap();
+
+        assert_eq!(sealed.open(&holder_key).unwrap(), shard);
+        assert!(sealed.open(&other_key).is_err());
+    }
+
+    #[test]
+    fn custody_receipt_verify() {
+        let shard = encrypted_shard();
+        let holder_key = SigningKey::generate(&mut OsRng);
+
+        let receipt = CustodyReceipt::sign(&shard, &holder_key, "received by alice");
+        let receipt = CustodyReceipt::from_wire(receipt.to_wire()).unwrap();
+        assert_eq!(receipt.note(), "received by alice");
+        receipt.verify(&shard).unwrap();
+        assert!(receipt.verify(&encrypted_shard()).is_err());
+
+        let mut forged = receipt.clone();
+        forged.note = "received by mallory".into();
+        assert!(forged.verify(&shard).is_err());
+    }
+
+    #[test]
+    fn courier_batch_verify() {
+        let shards = [encrypted_shard(), encrypted_shard(), encrypted_shard()];
+        let expected = shards[..2]
+            .iter()
+            .map(EncryptedKeyShard::checksum)
+            .collect::<Vec<_>>();
+        let holder_key = SigningKey::generate(&mut OsRng);
+        let coordinator_key = SigningKey::generate(&mut OsRng);
+        let coordinator = coordinator_key.verifying_key();
+
+        let mut batch = CourierBatch::new();
+        for shard in &shards[..2] {
+            let (envelope, proof) = EnvelopeProof::seal(shard, &holder_key, &coordinator).unwrap();
+            batch.push(envelope, proof);
+        }
+        let batch = CourierBatch::from_wire(batch.to_wire()).unwrap();
+        batch.verify(&expected, &coordinator).unwrap();
+        assert_eq!(batch.open(&coordinator_key).unwrap(), shards[..2]);
+
+        // Missing key shards are detected.
+        let mut partial = CourierBatch::new();
+        let (envelope, proof) = batch.envelopes()[0].clone();
+        partial.push(envelope.clone(), proof.clone());
+        assert!(partial.verify(&expected, &coordinator).is_err());
+
+        // Duplicated envelopes are detected.
+        let mut duplicated = batch.clone();
+        duplicated.push(envelope, proof);
+        assert!(duplicated.verify(&expected, &coordinator).is_err());
+
+        // Swapping an envelope invalidates its proof.
+        let mut swapped = batch.clone();
+        swapped.envelopes[0].0 = EnvelopeProof::seal(&shards[2], &holder_key, &coordinator)
+            .unwrap()
+            .0;
+        assert!(swapped.verify(&expected, &coordinator).is_err());
+
+        // Unexpected key shards are detected.
+        let mut extra = batch.clone();
+        let (envelope, proof) = EnvelopeProof::seal(&shards[2], &holder_key, &coordinator).unwrap();
+        extra.push(envelope, proof);
+        assert!(extra.verify(&expected, &coordinator).is_err());
+
+        // Envelopes must be sealed to the coordinator.
+        let other = SigningKey::generate(&mut OsRng).verifying_key();
+        assert!(batch.verify(&expected, &other).is_err());
+    }
+
+    #[test]
+    fn holder_directory_parse() {
+        let alice = SigningKey::generate(&mut OsRng).verifying_key();
+        let json = format!(
+            r#"{{"holders": [{{"name": "alice", "public-key": "{}"}}]}}"#,
+            encode_public_key(&alice)
+        );
+        let directory = HolderDirectory::from_json(&json).unwrap();
+        assert_eq!(directory.get("alice").unwrap().public_key, alice);
+        assert_eq!(directory.find_key(&alice).unwrap().name, "alice");
+
+        let duplicate = format!(
+            r#"{{"holders": [{{"name": "alice", "public-key": "{0}"}}, {{"name": "bob", "public-key": "{0}"}}]}}"#,
+            encode_public_key(&alice)
+        );
+        assert!(HolderDirectory::from_json(duplicate).is_err());
+    }
+
+    #[test]
+    fn possession_challenge_response() {
+        let shard = encrypted_shard();
+        let verifier = PossessionVerifier::new(&shard);
+        let verifier = PossessionVerifier::from_wire(verifier.to_wire()).unwrap();
+        assert_eq!(verifier.shard_checksum(), &shard.checksum());
+
+        let challenge = PossessionChallenge::new();
+        let challenge: PossessionChallenge = challenge.to_string().parse().unwrap();
+        let response = PossessionResponse::new(&shard, &challenge);
+        let response: PossessionResponse = response.to_string().parse().unwrap();
+        assert_eq!(response.to_string().len(), 19 /* 4*4+3 */);
+        verifier.verify(&challenge, &response).unwrap();
+
+        // Responses are bound to the challenge and the shard.
+        assert!(verifier
+            .verify(&PossessionChallenge::new(), &response)
+            .is_err());
+        let other = PossessionResponse::new(&encrypted_shard(), &challenge);
+        assert!(verifier.verify(&challenge, &other).is_err());
+    }
+}
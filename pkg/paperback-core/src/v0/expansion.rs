@@ -0,0 +1,156 @@
+/*
+ * paperback: paper backup generator suitable for long-term storage
+ * Copyright (C) 2018-2022 Aleksa Sarai <cyphar@cyphar.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Records of key shards minted after a backup was created.
+//!
+//! A key shard minted by expanding a quorum (see [`Quorum::expand_shard`])
+//! stores a [`ShardExpansion`], recording when it was minted and which key
+//! shards formed the quorum used to mint it. The record is covered by the key
+//! shard's signature and printed on its PDF, so anyone auditing a backup can
+//! tell which key shards were put into circulation after the fact.
+//!
+//! The record is also counter-signed with the document key recovered by the
+//! quorum (using HMAC-SHA256), which only a real quorum of the backup can do.
+//! Later recoveries recover the same document key and check the
+//! counter-signature of every minted key shard (see
+//! [`Quorum::audit_expansions`]), so a key shard whose record was forged (or
+//! moved from another key shard) is reported.
+//!
+//! [`Quorum::expand_shard`]: crate::v0::Quorum::expand_shard
+//! [`Quorum::audit_expansions`]: crate::v0::Quorum::audit_expansions
+
+use crate::v0::{ChaChaPolyKey, Error, ShardId, ToWire};
+
+use hmac::{Hmac, Mac};
+use multihash::Multihash;
+use sha2::Sha256;
+
+/// Length of the counter-signature of an expansion record.
+pub(crate) const AUTHORISATION_LENGTH: usize = 32;
+
+// Domain separation for counter-signatures, since the document key is also
+// used to encrypt the main document.
+const AUTHORISATION_CONTEXT: &[u8] = b"paperback-v0 shard expansion";
+
+/// How a key shard was minted by expanding a quorum.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ShardExpansion {
+    /// When the key shard was minted (in seconds since the Unix epoch).
+    pub expanded_at: u64,
+    /// The key shards which formed the quorum used to mint the key shard.
+    pub quorum: Vec<ShardId>,
+}
+
+impl ShardExpansion {
+    /// The maximum length of each listed key shard ID.
+    pub const MAX_SHARD_ID_LENGTH: usize = 16;
+
+    pub(super) fn validate(&self) -> Result<(), String> {
+        if self.quorum.is_empty() {
+            return Err("shard expansion must list the key shards of its quorum".into());
+        }
+        for id in &self.quorum {
+            if id.is_empty() || id.len() > Self::MAX_SHARD_ID_LENGTH {
+                return Err(format!("invalid key shard id {:?} in shard expansion", id));
+            }
+            multibase::decode(id)
+                .map_err(|err| format!("invalid key shard id {:?}: {}", id, err))?;
+        }
+        Ok(())
+    }
+
+    // The counter-signature covers the document, the minted key shard and the
+    // record itself, so it cannot be moved to another key shard.
+    fn authorisation_mac(
+        &self,
+        doc_key: &ChaChaPolyKey,
+        doc_chksum: &Multihash,
+        shard_id: &str,
+    ) -> Result<Hmac<Sha256>, Error> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(doc_key)
+            .map_err(|_| Error::InvariantViolation("hmac-sha256 rejected document key"))?;
+        mac.update(AUTHORISATION_CONTEXT);
+        mac.update(&doc_chksum.to_bytes());
+        mac.update(&(shard_id.len() as u64).to_le_bytes());
+        mac.update(shard_id.as_bytes());
+        mac.update(&self.to_wire());
+        Ok(mac)
+    }
+
+    // Counter-sign the record of minting the key shard `shard_id`.
+    pub(super) fn authorise(
+        &self,
+        doc_key: &ChaChaPolyKey,
+        doc_chksum: &Multihash,
+        shard_id: &str,
+    ) -> Result<[u8; AUTHORISATION_LENGTH], Error> {
+        let mut authorisation = [0u8; AUTHORISATION_LENGTH];
+        authorisation.copy_from_slice(
+            &self
+                .authorisation_mac(doc_key, doc_chksum, shard_id)?
+                .finalize()
+                .into_bytes(),
+        );
+        Ok(authorisation)
+    }
+
+    // Check the counter-signature of the record of minting `shard_id`.
+    pub(super) fn verify(
+        &self,
+        doc_key: &ChaChaPolyKey,
+        doc_chksum: &Multihash,
+        shard_id: &str,
+        authorisation: &[u8; AUTHORISATION_LENGTH],
+    ) -> Result<bool, Error> {
+        Ok(self
+            .authorisation_mac(doc_key, doc_chksum, shard_id)?
+            .verify_slice(authorisation)
+            .is_ok())
+    }
+}
+
+/// The result of checking the expansion record of a minted key shard against
+/// the document key recovered by a quorum (see [`Quorum::audit_expansions`]).
+///
+/// [`Quorum::audit_expansions`]: crate::v0::Quorum::audit_expansions
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExpansionAudit {
+    /// The minted key shard.
+    pub shard_id: ShardId,
+    /// The expansion record of the key shard.
+    pub expansion: ShardExpansion,
+    /// Whether the record was counter-signed with the document key. Records
+    /// of key shards minted by older versions of paperback are never
+    /// counter-signed.
+    pub authorised: bool,
+}
+
+#[cfg(test)]
+impl quickcheck::Arbitrary for ShardExpansion {
+    fn arbitrary(g: &mut quickcheck::Gen) -> Self {
+        let num_shards = 1 + usize::arbitrary(g) % 4;
+        Self {
+            expanded_at: u64::arbitrary(g),
+            quorum: (0..num_shards)
+                .map(|_| {
+                    multibase::encode(multibase::Base::Base32Z, u32::arbitrary(g).to_le_bytes())
+                })
+                .collect(),
+        }
+    }
+}
@@ -0,0 +1,399 @@
+/*
+ * paperback: paper backup generator suitable for long-term storage
+ * Copyright (C) 2018-2022 Aleksa Sarai <cyphar@cyphar.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Batch verification of many backups at once, for compliance reviews.
+//!
+//! A [`Fleet`] collects the stored manifests and escrowed main documents of
+//! any number of backups, and produces a [`FleetReport`] listing the health
+//! of each backup (signatures, format versions and expired constraints) as
+//! CSV or JSON. Only public documents are needed, so nothing can be recovered
+//! from the inputs.
+
+use crate::v0::{
+    algorithms, ceremony::Manifest, constraints, format_version, is_test_run, AeadMode, DocumentId,
+    MainDocument, Type, Warning, PAPERBACK_VERSION,
+};
+
+use std::{collections::BTreeMap, time::SystemTime};
+
+use serde::Serialize;
+
+/// A collection of backups to verify together.
+#[derive(Clone, Debug, Default)]
+pub struct Fleet {
+    backups: BTreeMap<DocumentId, StoredBackup>,
+    unreadable: Vec<(String, String)>,
+}
+
+#[derive(Clone, Debug, Default)]
+struct StoredBackup {
+    manifest: Option<(String, Manifest)>,
+    main_document: Option<(String, MainDocument)>,
+    issues: Vec<String>,
+}
+
+impl Fleet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a stored manifest, read from `source` (such as a file name).
+    pub fn add_manifest<S: Into<String>>(&mut self, source: S, manifest: Manifest) -> &mut Self {
+        let source = source.into();
+        let backup = self.backups.entry(manifest.document_id()).or_default();
+        match &backup.manifest {
+            Some((other, _)) => backup.issues.push(format!(
+                "duplicate manifest {} (already read {})",
+                source, other
+            )),
+            None => backup.manifest = Some((source, manifest)),
+        }
+        self
+    }
+
+    /// Add an escrowed main document, read from `source` (such as a file
+    /// name).
+    pub fn add_main_document<S: Into<String>>(
+        &mut self,
+        source: S,
+        main_document: MainDocument,
+    ) -> &mut Self {
+        let source = source.into();
+        let backup = self.backups.entry(main_document.id()).or_default();
+        match &backup.main_document {
+            Some((other, _)) => backup.issues.push(format!(
+                "duplicate main document {} (already read {})",
+                source, other
+            )),
+            None => backup.main_document = Some((source, main_document)),
+        }
+        self
+    }
+
+    /// Record an input which could not be read as either a manifest or a main
+    /// document, so that it is not silently left out of the report.
+    pub fn add_unreadable<S: Into<String>, R: Into<String>>(
+        &mut self,
+        source: S,
+        reason: R,
+    ) -> &mut Self {
+        self.unreadable.push((source.into(), reason.into()));
+        self
+    }
+
+    /// Verify every backup in the fleet, checking for expired recovery
+    /// constraints as of `now`.
+    pub fn report(&self, now: SystemTime) -> FleetReport {
+        let mut entries = self
+            .backups
+            .iter()
+            .map(|(document_id, backup)| backup.verify(document_id, now))
+            .collect::<Vec<_>>();
+        entries.extend(self.unreadable.iter().map(|(source, reason)| FleetEntry {
+            issues: vec![format!("{} is unreadable: {}", source, reason)],
+            ..Default::default()
+        }));
+        FleetReport { entries }
+    }
+}
+
+impl StoredBackup {
+    fn verify(&self, document_id: &DocumentId, now: SystemTime) -> FleetEntry {
+        let mut entry = FleetEntry {
+            document_id: Some(document_id.clone()),
+            issues: self.issues.clone(),
+            ..Default::default()
+        };
+
+        match &self.manifest {
+            None => entry.issues.push("no manifest".into()),
+            Some((source, manifest)) => {
+                entry.manifest = Some(source.clone());
+                entry.operator_signatures = manifest.signatures().len();
+                let valid = manifest.verify().is_ok();
+                entry.manifest_signatures_valid = Some(valid);
+                if !valid {
+                    entry
+                        .issues
+                        .push("invalid manifest operator signature".into());
+                }
+            }
+        }
+
+        match &self.main_document {
+            None => entry.issues.push("no main document".into()),
+            Some((source, main_document)) => {
+                entry.main_document = Some(source.clone());
+
+                let valid = matches!(Type::from(main_document.clone()), Type::MainDocument(_));
+                entry.document_signature_valid = Some(valid);
+                if !valid {
+                    entry.issues.push("invalid main document signature".into());
+                }
+                if let Some((_, manifest)) = &self.manifest {
+                    let listed = manifest.verify_document(main_document).is_ok();
+                    entry.listed_in_manifest = Some(listed);
+                    if !listed {
+                        entry
+                            .issues
+                            .push("manifest lists a different main document".into());
+                    }
+                }
+
+                let version = format_version(main_document.version());
+                entry.format_version = Some(version);
+                if version != PAPERBACK_VERSION {
+                    entry
+                        .issues
+                        .push(Warning::FormatVersion { version }.to_string());
+                }
+                let cipher_suite = main_document.aead_algorithm().name();
+                entry.algorithms = Some(match main_document.aead_mode() {
+                    AeadMode::Single => [cipher_suite, "ed25519", "blake2b-256"].join("+"),
+                    AeadMode::Chunked { .. } => {
+                        [cipher_suite, "stream", "ed25519", "blake2b-256"].join("+")
+                    }
+                });
+                if is_test_run(main_document.version()) {
+                    entry.issues.push(Warning::TestRun.to_string());
+                }
+                entry.issues.extend(
+                    algorithms::audit(main_document.version(), now)
+                        .iter()
+                        .map(Warning::to_string),
+                );
+
+                entry.expired = constraints::audit(main_document.constraints(), now)
+                    .into_iter()
+                    .filter_map(|warning| match warning {
+                        Warning::StaleConstraint(constraint) => Some(constraint.to_string()),
+                        _ => None,
+                    })
+                    .collect();
+                if !entry.expired.is_empty() {
+                    entry.issues.push(format!(
+                        "expired recovery constraints: {}",
+                        entry.expired.join(", ")
+                    ));
+                }
+            }
+        }
+
+        entry.healthy = entry.issues.is_empty();
+        entry
+    }
+}
+
+/// The health of a single backup in a [`FleetReport`].
+///
+/// Checks which could not be run (because the relevant document was not
+/// provided) are `None`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct FleetEntry {
+    /// The document ID of the backup (`None` for unreadable inputs).
+    pub document_id: Option<DocumentId>,
+    /// Where the manifest was read from.
+    pub manifest: Option<String>,
+    /// Where the main document was read from.
+    pub main_document: Option<String>,
+    /// The number of operators who signed the manifest.
+    pub operator_signatures: usize,
+    pub manifest_signatures_valid: Option<bool>,
+    pub document_signature_valid: Option<bool>,
+    /// Whether the manifest lists this exact main document.
+    pub listed_in_manifest: Option<bool>,
+    pub format_version: Option<u32>,
+    /// The algorithms used by the main document.
+    pub algorithms: Option<String>,
+    /// Recovery constraints which have expired and should be reviewed.
+    pub expired: Vec<String>,
+    /// Every problem found with the backup.
+    pub issues: Vec<String>,
+    /// Whether no problems were found.
+    pub healthy: bool,
+}
+
+/// The results of verifying a [`Fleet`].
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct FleetReport {
+    pub entries: Vec<FleetEntry>,
+}
+
+impl FleetReport {
+    const CSV_HEADER: [&'static str; 12] = [
+        "document-id",
+        "manifest",
+        "main-document",
+        "operator-signatures",
+        "manifest-signatures-valid",
+        "document-signature-valid",
+        "listed-in-manifest",
+        "format-version",
+        "algorithms",
+        "expired",
+        "healthy",
+        "issues",
+    ];
+
+    /// Returns whether every backup in the report is healthy.
+    pub fn is_healthy(&self) -> bool {
+        self.entries.iter().all(|entry| entry.healthy)
+    }
+
+    /// Format the report as CSV (RFC 4180), with one row per backup.
+    pub fn to_csv(&self) -> String {
+        fn opt<T: ToString>(value: &Option<T>) -> String {
+            value.as_ref().map(T::to_string).unwrap_or_default()
+        }
+
+        let mut csv = csv_row(Self::CSV_HEADER.iter().map(|s| s.to_string()));
+        for entry in &self.entries {
+            csv += &csv_row([
+                opt(&entry.document_id),
+                opt(&entry.manifest),
+                opt(&entry.main_document),
+                entry.operator_signatures.to_string(),
+                opt(&entry.manifest_signatures_valid),
+                opt(&entry.document_signature_valid),
+                opt(&entry.listed_in_manifest),
+                opt(&entry.format_version),
+                opt(&entry.algorithms),
+                entry.expired.join("; "),
+                entry.healthy.to_string(),
+                entry.issues.join("; "),
+            ]);
+        }
+        csv
+    }
+
+    /// Format the report as a JSON object of the form `{"entries": [...]}`.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("serialising a fleet report cannot fail")
+    }
+}
+
+fn csv_row<I: IntoIterator<Item = String>>(fields: I) -> String {
+    let fields = fields
+        .into_iter()
+        .map(|field| match field.contains([',', '"', '\r', '\n']) {
+            true => format!("\"{}\"", field.replace('"', "\"\"")),
+            false => field,
+        })
+        .collect::<Vec<_>>();
+    fields.join(",") + "\r\n"
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::v0::{Backup, BackupBuilder, RecoveryConstraint};
+
+    use ed25519_dalek::SigningKey;
+    use rand::rngs::OsRng;
+
+    fn backup(builder: &BackupBuilder) -> (MainDocument, Manifest) {
+        let backup = builder.build(b"secret").unwrap();
+        let shards = (0..3)
+            .map(|_| backup.next_shard().unwrap().encrypt().unwrap().0)
+            .collect::<Vec<_>>();
+        let mut manifest = Manifest::new(backup.main_document(), &shards);
+        manifest.sign(&SigningKey::generate(&mut OsRng));
+        (backup.main_document().clone(), manifest)
+    }
+
+    #[test]
+    fn fleet_report() {
+        let (good_doc, good_manifest) = backup(&BackupBuilder::new(2));
+        let (expired_doc, expired_manifest) =
+            backup(BackupBuilder::new(2).constraint(RecoveryConstraint::ReviewBy(1000)));
+        let (orphan_doc, _) = backup(&BackupBuilder::new(2));
+
+        let mut fleet = Fleet::new();
+        fleet
+            .add_manifest("good.txt", good_manifest)
+            .add_main_document("good-doc.txt", good_doc.clone())
+            .add_manifest("expired.txt", expired_manifest)
+            .add_main_document("expired-doc.txt", expired_doc.clone())
+            .add_main_document("orphan-doc.txt", orphan_doc.clone())
+            .add_main_document("orphan-copy.txt", orphan_doc.clone())
+            .add_unreadable("junk.txt", "not a paperback document");
+        let report = fleet.report(SystemTime::now());
+        assert!(!report.is_healthy());
+        assert_eq!(report.entries.len(), 4);
+
+        let entry = |id: &DocumentId| {
+            report
+                .entries
+                .iter()
+                .find(|e| e.document_id.as_ref() == Some(id))
+                .unwrap()
+        };
+        let good = entry(&good_doc.id());
+        assert!(good.healthy, "{:?}", good.issues);
+        assert_eq!(good.operator_signatures, 1);
+        assert_eq!(good.manifest_signatures_valid, Some(true));
+        assert_eq!(good.document_signature_valid, Some(true));
+        assert_eq!(good.listed_in_manifest, Some(true));
+
+        let expired = entry(&expired_doc.id());
+        assert!(!expired.healthy);
+        assert_eq!(expired.expired, ["review-by=1970-01-01"]);
+
+        let orphan = entry(&orphan_doc.id());
+        assert!(!orphan.healthy);
+        assert_eq!(orphan.listed_in_manifest, None);
+        assert_eq!(orphan.issues.len(), 2); // Duplicate and no manifest.
+
+        let junk = report.entries.last().unwrap();
+        assert_eq!(junk.document_id, None);
+        assert_eq!(
+            junk.issues,
+            ["junk.txt is unreadable: not a paperback document"]
+        );
+
+        // Every row has the same number of columns as the header.
+        let csv = report.to_csv();
+        assert_eq!(csv.lines().count(), 5);
+        assert!(csv.starts_with("document-id,manifest,"));
+        let json: serde_json::Value = serde_json::from_str(&report.to_json()).unwrap();
+        assert_eq!(json["entries"].as_array().unwrap().len(), 4);
+    }
+
+    #[test]
+    fn fleet_unsigned_manifest_only() {
+        let backup = Backup::new(2, b"secret").unwrap();
+        let manifest = Manifest::new(backup.main_document(), &[]);
+        let mut fleet = Fleet::new();
+        fleet.add_manifest("manifest.txt", manifest);
+        let report = fleet.report(SystemTime::now());
+        let entry = &report.entries[0];
+        assert_eq!(entry.operator_signatures, 0);
+        assert_eq!(entry.manifest_signatures_valid, Some(true));
+        assert_eq!(entry.issues, ["no main document"]);
+    }
+
+    #[test]
+    fn csv_quoting() {
+        assert_eq!(
+            csv_row(["a".into(), "b,c".into(), "say \"hi\"".into()]),
+            "a,\"b,c\",\"say \"\"hi\"\"\"\r\n"
+        );
+    }
+}
@@ -0,0 +1,99 @@
+/*
+ * paperback: paper backup generator suitable for long-term storage
+ * Copyright (C) 2018-2022 Aleksa Sarai <cyphar@cyphar.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Byte-wise polynomial interpolation over GF(2^8).
+//!
+//! This is used by both [SLIP-39](crate::v0::slip39) (whose secret sharing
+//! scheme is defined over GF(2^8)) and the outer Reed-Solomon code over
+//! multi-part QR codes (see [`pdf`](crate::v0::pdf)). Paperback's own secret
+//! sharing uses the larger fields in [`shamir`](crate::shamir).
+
+use once_cell::sync::Lazy;
+
+/// The number of distinct x-coordinates in GF(2^8).
+pub(super) const NUM_POINTS: usize = 256;
+
+// GF(2^8) with the Rijndael polynomial, using x+1 as the generator.
+static GF256_TABLES: Lazy<([u8; 255], [u8; 256])> = Lazy::new(|| {
+    let mut exp = [0u8; 255];
+    let mut log = [0u8; 256];
+    let mut poly: u16 = 1;
+    for (i, exp) in exp.iter_mut().enumerate() {
+        *exp = poly as u8;
+        log[poly as usize] = i as u8;
+        poly = (poly << 1) ^ poly;
+        if poly & 0x100 != 0 {
+            poly ^= 0x11B;
+        }
+    }
+    (exp, log)
+});
+
+/// Evaluate (byte-wise) the polynomial defined by the given points (which must
+/// have distinct x-coordinates and values of the same length) at `x`.
+pub(super) fn interpolate(points: &[(u8, &[u8])], x: u8) -> Vec<u8> {
+    if let Some((_, value)) = points.iter().find(|(point_x, _)| *point_x == x) {
+        return value.to_vec();
+    }
+    let (exp, log) = &*GF256_TABLES;
+
+    let log_product: i32 = points
+        .iter()
+        .map(|(point_x, _)| log[(point_x ^ x) as usize] as i32)
+        .sum();
+
+    let mut result = vec![0u8; points[0].1.len()];
+    for (point_x, value) in points {
+        let log_basis = (log_product
+            - log[(point_x ^ x) as usize] as i32
+            - points
+                .iter()
+                .filter(|(other_x, _)| other_x != point_x)
+                .map(|(other_x, _)| log[(point_x ^ other_x) as usize] as i32)
+                .sum::<i32>())
+        .rem_euclid(255);
+        for (out, &byte) in result.iter_mut().zip(value.iter()) {
+            if byte != 0 {
+                *out ^= exp[((log[byte as usize] as i32 + log_basis) % 255) as usize];
+            }
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use quickcheck::TestResult;
+    use quickcheck_macros::quickcheck;
+
+    #[quickcheck]
+    fn interpolate_consistent(a: Vec<u8>, b: Vec<u8>, c: Vec<u8>, x: u8) -> TestResult {
+        if (1..=3).contains(&x) {
+            return TestResult::discard();
+        }
+        let len = a.len().min(b.len()).min(c.len());
+        let (a, b, c) = (&a[..len], &b[..len], &c[..len]);
+
+        // Any three points on the polynomial through (1, a), (2, b) and
+        // (3, c) define the same polynomial.
+        let d = interpolate(&[(1, a), (2, b), (3, c)], x);
+        TestResult::from_bool(interpolate(&[(2, b), (3, c), (x, &d)], 1) == a)
+    }
+}
@@ -0,0 +1,176 @@
+/*
+ * paperback: paper backup generator suitable for long-term storage
+ * Copyright (C) 2018-2022 Aleksa Sarai <cyphar@cyphar.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Hybrid post-quantum signatures of the main document.
+//!
+//! Backups which are meant to survive for decades may outlive Ed25519, so the
+//! main document can optionally be signed with both Ed25519 and ML-DSA-65
+//! ([FIPS 204][fips204]). Both signatures cover the same bytes (which include
+//! both public keys), and a hybrid-signed main document is only accepted if
+//! both signatures are valid -- so a forgery requires breaking both schemes.
+//!
+//! Key shards are still only signed with Ed25519, as an ML-DSA-65 signature
+//! (3309 bytes) would not fit in the single QR code of a key shard. Key
+//! shards are bound to the (hybrid-signed) main document by its checksum.
+//!
+//! The ML-DSA-65 private key is stored in the shard secret as the 32-byte seed
+//! it is generated from, so that unsealed backups can be re-dealt with the
+//! same hybrid identity.
+//!
+//! [fips204]: https://csrc.nist.gov/pubs/fips/204/final
+
+use ml_dsa::{EncodedSignature, EncodedVerifyingKey, KeyGen, MlDsa65, Signature, B32};
+use rand::{CryptoRng, RngCore};
+use zeroize::Zeroize;
+
+/// Length of the seed an ML-DSA-65 keypair is generated from.
+pub(crate) const SEED_LENGTH: usize = 32;
+
+/// Length of an encoded ML-DSA-65 public key.
+pub(crate) const PUBLIC_KEY_LENGTH: usize = 1952;
+
+/// Length of an encoded ML-DSA-65 signature.
+pub(crate) const SIGNATURE_LENGTH: usize = 3309;
+
+// Context string for all ML-DSA-65 signatures, so that they cannot be confused
+// with signatures from any other protocol using the same key.
+const SIGNATURE_CONTEXT: &[u8] = b"paperback-v0";
+
+/// An ML-DSA-65 signing key, stored as the seed it is generated from.
+#[derive(Clone)]
+pub(crate) struct PqSigningKey([u8; SEED_LENGTH]);
+
+impl std::fmt::Debug for PqSigningKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("PqSigningKey").field(&"<redacted>").finish()
+    }
+}
+
+impl Drop for PqSigningKey {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl PqSigningKey {
+    pub(crate) fn generate<R: CryptoRng + RngCore + ?Sized>(rng: &mut R) -> Self {
+        let mut seed = [0u8; SEED_LENGTH];
+        rng.fill_bytes(&mut seed);
+        Self(seed)
+    }
+
+    pub(crate) fn from_seed(seed: [u8; SEED_LENGTH]) -> Self {
+        Self(seed)
+    }
+
+    pub(crate) fn seed(&self) -> &[u8; SEED_LENGTH] {
+        &self.0
+    }
+
+    fn keypair(&self) -> ml_dsa::KeyPair<MlDsa65> {
+        MlDsa65::key_gen_internal(&B32::from(self.0))
+    }
+
+    pub(crate) fn verifying_key(&self) -> PqVerifyingKey {
+        PqVerifyingKey(self.keypair().verifying_key().encode().to_vec())
+    }
+
+    pub(crate) fn sign(&self, message: &[u8]) -> PqSignature {
+        let signature = self
+            .keypair()
+            .signing_key()
+            .sign_deterministic(message, SIGNATURE_CONTEXT)
+            .expect("signature context should be shorter than 255 bytes");
+        PqSignature(signature.encode().to_vec())
+    }
+}
+
+/// An encoded ML-DSA-65 public key.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct PqVerifyingKey(Vec<u8>);
+
+impl PqVerifyingKey {
+    pub(crate) fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        (bytes.len() == PUBLIC_KEY_LENGTH).then(|| Self(bytes.to_vec()))
+    }
+
+    pub(crate) fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Returns whether `signature` is a valid signature of `message`.
+    pub(crate) fn verify(&self, message: &[u8], signature: &PqSignature) -> bool {
+        let public_key = match EncodedVerifyingKey::<MlDsa65>::try_from(&self.0[..]) {
+            Ok(encoded) => ml_dsa::VerifyingKey::<MlDsa65>::decode(&encoded),
+            Err(_) => return false,
+        };
+        let signature = match EncodedSignature::<MlDsa65>::try_from(&signature.0[..]) {
+            Ok(encoded) => match Signature::<MlDsa65>::decode(&encoded) {
+                Some(signature) => signature,
+                None => return false,
+            },
+            Err(_) => return false,
+        };
+        public_key.verify_with_context(message, SIGNATURE_CONTEXT, &signature)
+    }
+}
+
+/// An encoded ML-DSA-65 signature.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct PqSignature(Vec<u8>);
+
+impl PqSignature {
+    pub(crate) fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        (bytes.len() == SIGNATURE_LENGTH).then(|| Self(bytes.to_vec()))
+    }
+
+    pub(crate) fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+/// The post-quantum half of the identity of a hybrid-signed main document.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct PqIdentity {
+    pub(crate) public_key: PqVerifyingKey,
+    pub(crate) signature: PqSignature,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn pq_sign_verify() {
+        let key = PqSigningKey::generate(&mut rand::thread_rng());
+        let public_key = key.verifying_key();
+        let signature = key.sign(b"paperback");
+
+        assert_eq!(public_key.as_bytes().len(), PUBLIC_KEY_LENGTH);
+        assert_eq!(signature.as_bytes().len(), SIGNATURE_LENGTH);
+        assert!(public_key.verify(b"paperback", &signature));
+        assert!(!public_key.verify(b"paperbacc", &signature));
+
+        // The same seed always produces the same keypair.
+        let key2 = PqSigningKey::from_seed(*key.seed());
+        assert_eq!(key2.verifying_key(), public_key);
+
+        let other = PqSigningKey::generate(&mut rand::thread_rng());
+        assert!(!other.verifying_key().verify(b"paperback", &signature));
+    }
+}
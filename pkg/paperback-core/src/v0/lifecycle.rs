@@ -0,0 +1,284 @@
+/*
+ * paperback: paper backup generator suitable for long-term storage
+ * Copyright (C) 2018-2022 Aleksa Sarai <cyphar@cyphar.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Lifecycle states of a backup, changed by quorum-signed [`Amendment`]s.
+//!
+//! A backup is [`LifecycleState::Active`] when it is created. Once it has been
+//! replaced by another backup (such as the next generation created by
+//! [`Quorum::redeal`](crate::v0::Quorum::redeal)) or should no longer be used
+//! at all, a quorum of its key shards can sign an [`Amendment`] marking it as
+//! [`LifecycleState::SupersededBy`] the new document or
+//! [`LifecycleState::Revoked`]. Amendments are printed and stored alongside
+//! the main document, so that someone who finds several generations of paper
+//! can tell which one is authoritative.
+//!
+//! Amendments are signed with the document's identity key, which can only be
+//! recovered from a quorum of key shards of an unsealed backup. Sealed backups
+//! cannot be amended.
+
+use crate::v0::{
+    multihash_short_id, DocumentId, Error, MainDocument, ToWire, CHECKSUM_ALGORITHM,
+    CHECKSUM_MULTIBASE,
+};
+
+use ed25519_dalek::{Signature, Signer, SigningKey};
+use multihash::{Multihash, MultihashDigest};
+use std::fmt;
+
+/// The lifecycle state of a backup.
+#[derive(Clone, Debug, Default, Hash, PartialEq, Eq)]
+pub enum LifecycleState {
+    /// The backup is the authoritative copy of the secret.
+    #[default]
+    Active,
+    /// The backup has been replaced by the backup with the given document ID,
+    /// which should be used instead.
+    SupersededBy(DocumentId),
+    /// The backup must no longer be used. Revocation is final -- a revoked
+    /// backup cannot be amended back into any other state.
+    Revoked,
+}
+
+impl LifecycleState {
+    pub(crate) fn id(&self) -> u32 {
+        match self {
+            Self::Active => 0,
+            Self::SupersededBy(_) => 1,
+            Self::Revoked => 2,
+        }
+    }
+}
+
+impl fmt::Display for LifecycleState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Active => write!(f, "active"),
+            Self::SupersededBy(id) => write!(f, "superseded by {}", id),
+            Self::Revoked => write!(f, "revoked"),
+        }
+    }
+}
+
+/// A statement, signed by the identity key of a backup, changing the
+/// [`LifecycleState`] of the backup.
+///
+/// Every amendment has a sequence number, and the amendment with the largest
+/// sequence number determines the current state of the backup (see
+/// [`current_state`]).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Amendment {
+    pub(super) doc_chksum: Multihash,
+    pub(super) sequence: u32,
+    pub(super) state: LifecycleState,
+    pub(super) signature: Signature,
+}
+
+impl Amendment {
+    // Domain separation for amendment signatures, so they cannot be confused
+    // with any other paperback signature.
+    const SIGNATURE_CONTEXT: &'static [u8] = b"paperback-amendment-v0";
+
+    fn signable_bytes(doc_chksum: &Multihash, sequence: u32, state: &LifecycleState) -> Vec<u8> {
+        let mut bytes = Self::SIGNATURE_CONTEXT.to_vec();
+        bytes.extend_from_slice(&doc_chksum.to_bytes());
+        bytes.extend_from_slice(&sequence.to_le_bytes());
+        bytes.append(&mut state.to_wire());
+        bytes
+    }
+
+    pub(crate) fn sign(
+        doc_chksum: Multihash,
+        sequence: u32,
+        state: LifecycleState,
+        id_keypair: &SigningKey,
+    ) -> Self {
+        let signature = id_keypair.sign(&Self::signable_bytes(&doc_chksum, sequence, &state));
+        Self {
+            doc_chksum,
+            sequence,
+            state,
+            signature,
+        }
+    }
+
+    pub fn checksum(&self) -> Multihash {
+        CHECKSUM_ALGORITHM.digest(&self.to_wire())
+    }
+
+    pub fn checksum_string(&self) -> String {
+        multibase::encode(CHECKSUM_MULTIBASE, self.checksum().to_bytes())
+    }
+
+    /// The ID of the document this amendment applies to.
+    pub fn document_id(&self) -> DocumentId {
+        multihash_short_id(self.doc_chksum, MainDocument::ID_LENGTH)
+    }
+
+    pub fn sequence(&self) -> u32 {
+        self.sequence
+    }
+
+    pub fn state(&self) -> &LifecycleState {
+        &self.state
+    }
+
+    /// Verify that the amendment applies to the given main document and was
+    /// signed by its identity key.
+    pub fn verify(&self, main_document: &MainDocument) -> Result<(), Error> {
+        if main_document.checksum() != self.doc_chksum {
+            return Err(Error::InvariantViolation(
+                "amendment refers to a different document",
+            ));
+        }
+        main_document
+            .identity
+            .id_public_key
+            .verify_strict(
+                &Self::signable_bytes(&self.doc_chksum, self.sequence, &self.state),
+                &self.signature,
+            )
+            .map_err(|_| Error::InvariantViolation("amendment signature is invalid"))
+    }
+}
+
+/// Compute the current [`LifecycleState`] of a main document from its
+/// amendments.
+///
+/// Every amendment must apply to the document and have a valid signature. The
+/// amendment with the largest sequence number wins, except that a revocation
+/// is final. Two different amendments with the same (largest) sequence number
+/// are rejected, since there is no way to tell which one is authoritative.
+pub fn current_state<'a, I>(
+    main_document: &MainDocument,
+    amendments: I,
+) -> Result<LifecycleState, Error>
+where
+    I: IntoIterator<Item = &'a Amendment>,
+{
+    let amendments = amendments.into_iter().collect::<Vec<_>>();
+    for amendment in &amendments {
+        amendment.verify(main_document)?;
+    }
+    if amendments
+        .iter()
+        .any(|amendment| amendment.state == LifecycleState::Revoked)
+    {
+        return Ok(LifecycleState::Revoked);
+    }
+
+    let latest = match amendments.iter().map(|amendment| amendment.sequence).max() {
+        Some(sequence) => sequence,
+        None => return Ok(LifecycleState::Active),
+    };
+    let mut states = amendments
+        .iter()
+        .filter(|amendment| amendment.sequence == latest)
+        .map(|amendment| &amendment.state);
+    let state = states
+        .next()
+        .expect("largest sequence number must have an amendment");
+    if states.any(|other| other != state) {
+        return Err(Error::Other(format!(
+            "conflicting amendments with sequence number {}",
+            latest
+        )));
+    }
+    Ok(state.clone())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::v0::{Backup, FromWire, UntrustedQuorum};
+
+    fn amend(backup: &Backup, sequence: u32, state: LifecycleState) -> Amendment {
+        let mut quorum = UntrustedQuorum::new();
+        quorum.push_shard(backup.next_shard().unwrap());
+        quorum.push_shard(backup.next_shard().unwrap());
+        quorum.validate().unwrap().amend(sequence, state).unwrap()
+    }
+
+    #[test]
+    fn amendment_roundtrip() {
+        let backup = Backup::new(2, b"secret").unwrap();
+        let main_document = backup.main_document();
+        let amendment = amend(&backup, 1, LifecycleState::SupersededBy("abcdefgh".into()));
+        let amendment = Amendment::from_wire(amendment.to_wire()).unwrap();
+
+        assert_eq!(amendment.document_id(), main_document.id());
+        assert_eq!(
+            amendment.state(),
+            &LifecycleState::SupersededBy("abcdefgh".into())
+        );
+        amendment.verify(main_document).unwrap();
+
+        let other = Backup::new(2, b"secret").unwrap();
+        assert!(amendment.verify(other.main_document()).is_err());
+
+        let mut forged = amendment.clone();
+        forged.state = LifecycleState::Active;
+        assert!(forged.verify(main_document).is_err());
+    }
+
+    #[test]
+    fn lifecycle_current_state() {
+        let backup = Backup::new(2, b"secret").unwrap();
+        let main_document = backup.main_document();
+        let superseded = amend(&backup, 1, LifecycleState::SupersededBy("abcdefgh".into()));
+        let reactivated = amend(&backup, 2, LifecycleState::Active);
+        let revoked = amend(&backup, 3, LifecycleState::Revoked);
+
+        assert_eq!(
+            current_state(main_document, []).unwrap(),
+            LifecycleState::Active
+        );
+        assert_eq!(
+            current_state(main_document, [&superseded]).unwrap(),
+            LifecycleState::SupersededBy("abcdefgh".into())
+        );
+        assert_eq!(
+            current_state(main_document, [&reactivated, &superseded]).unwrap(),
+            LifecycleState::Active
+        );
+        // Revocation is final, even with a later amendment.
+        let mut later = amend(&backup, 4, LifecycleState::Active);
+        assert_eq!(
+            current_state(main_document, [&superseded, &revoked, &later]).unwrap(),
+            LifecycleState::Revoked
+        );
+
+        // Conflicting or forged amendments are rejected.
+        let conflicting = amend(&backup, 1, LifecycleState::Active);
+        assert!(current_state(main_document, [&superseded, &conflicting]).is_err());
+        later.sequence = 5;
+        assert!(current_state(main_document, [&later]).is_err());
+    }
+
+    #[test]
+    fn amend_sealed() {
+        let backup = Backup::new_sealed(2, b"secret").unwrap();
+        let mut quorum = UntrustedQuorum::new();
+        quorum.push_shard(backup.next_shard().unwrap());
+        quorum.push_shard(backup.next_shard().unwrap());
+        assert!(quorum
+            .validate()
+            .unwrap()
+            .amend(1, LifecycleState::Revoked)
+            .is_err());
+    }
+}
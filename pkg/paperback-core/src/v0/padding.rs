@@ -0,0 +1,159 @@
+/*
+ * paperback: paper backup generator suitable for long-term storage
+ * Copyright (C) 2018-2022 Aleksa Sarai <cyphar@cyphar.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use crate::v0::Error;
+
+/// Length-hiding padding applied to the secret before it is encrypted.
+///
+/// Without padding, the size of the printed main document reveals the size of
+/// the secret (a 32-byte key looks very different to a 100KB document). The
+/// scheme is stored in the (authenticated) main document metadata, and the
+/// padding is stripped during recovery.
+///
+/// All schemes other than `None` use ISO/IEC 7816-4 padding (a single `0x80`
+/// byte followed by zero bytes), so the original length of the secret is only
+/// stored inside the ciphertext.
+#[derive(Copy, Clone, Debug, Default, Hash, PartialEq, Eq)]
+pub enum PaddingScheme {
+    /// No padding. The ciphertext length is equal to the secret length.
+    #[default]
+    None,
+    /// [Padmé][padme] padding, which leaks at most `O(log log L)` bits of
+    /// information about the length and has at most 12% overhead.
+    ///
+    /// [padme]: https://lbarman.ch/blog/padme/
+    Padme,
+    /// Pad up to the next power of two (with a minimum of
+    /// `PaddingScheme::MIN_BUCKET_SIZE` bytes).
+    PowerOfTwo,
+}
+
+impl PaddingScheme {
+    /// The smallest padded size used by `PaddingScheme::PowerOfTwo`.
+    pub const MIN_BUCKET_SIZE: usize = 64;
+
+    const PADDING_START: u8 = 0x80;
+
+    pub(crate) fn id(&self) -> u32 {
+        match self {
+            Self::None => 0,
+            Self::Padme => 1,
+            Self::PowerOfTwo => 2,
+        }
+    }
+
+    pub(crate) fn from_id(id: u32) -> Option<Self> {
+        match id {
+            0 => Some(Self::None),
+            1 => Some(Self::Padme),
+            2 => Some(Self::PowerOfTwo),
+            _ => None,
+        }
+    }
+
+    fn padme(len: usize) -> usize {
+        if len < 2 {
+            return len;
+        }
+        // E = floor(log2(L)), S = floor(log2(E)) + 1
+        let e = usize::BITS - 1 - len.leading_zeros();
+        let s = u32::BITS - e.leading_zeros();
+        let mask = (1usize << (e - s)) - 1;
+        (len + mask) & !mask
+    }
+
+    /// Returns the length of the padded plaintext for a secret of length
+    /// `len`.
+    pub fn padded_len(&self, len: usize) -> usize {
+        match self {
+            Self::None => len,
+            // Make space for the padding start byte.
+            Self::Padme => Self::padme(len + 1),
+            Self::PowerOfTwo => (len + 1).next_power_of_two().max(Self::MIN_BUCKET_SIZE),
+        }
+    }
+
+    pub(crate) fn pad(&self, secret: &[u8]) -> Vec<u8> {
+        let mut padded = Vec::with_capacity(self.padded_len(secret.len()));
+        padded.extend_from_slice(secret);
+        padded.append(&mut self.padding(secret.len()));
+        padded
+    }
+
+    /// Returns the padding appended to a secret of length `len`.
+    pub(crate) fn padding(&self, len: usize) -> Vec<u8> {
+        if *self == Self::None {
+            return Vec::new();
+        }
+        let mut padding = vec![0; self.padded_len(len) - len];
+        padding[0] = Self::PADDING_START;
+        padding
+    }
+
+    pub(crate) fn unpad(&self, mut padded: Vec<u8>) -> Result<Vec<u8>, Error> {
+        if *self == Self::None {
+            return Ok(padded);
+        }
+        let start = padded
+            .iter()
+            .rposition(|&b| b != 0)
+            .ok_or(Error::InvalidPadding("no padding start byte found"))?;
+        if padded[start] != Self::PADDING_START {
+            return Err(Error::InvalidPadding("padding start byte is incorrect"));
+        }
+        padded.truncate(start);
+        Ok(padded)
+    }
+}
+
+#[cfg(test)]
+impl quickcheck::Arbitrary for PaddingScheme {
+    fn arbitrary(g: &mut quickcheck::Gen) -> Self {
+        *g.choose(&[Self::None, Self::Padme, Self::PowerOfTwo])
+            .expect("choose from non-empty slice")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[quickcheck]
+    fn padding_roundtrip(scheme: PaddingScheme, secret: Vec<u8>) -> bool {
+        let padded = scheme.pad(&secret);
+        padded.len() == scheme.padded_len(secret.len()) && scheme.unpad(padded).unwrap() == secret
+    }
+
+    #[quickcheck]
+    fn padding_hides_length(secret: Vec<u8>) -> bool {
+        // Padmé has a maximum overhead of 12%, while power-of-two buckets have
+        // a maximum overhead of 100%.
+        let len = secret.len() + 1;
+        let padme = PaddingScheme::Padme.padded_len(secret.len());
+        let pow2 = PaddingScheme::PowerOfTwo.padded_len(secret.len());
+        padme >= len && padme <= len + len / 8 + 1 && pow2 >= len && pow2.is_power_of_two()
+    }
+
+    #[test]
+    fn padme_known_values() {
+        // Values computed by hand from the algorithm in the Padmé paper.
+        for (len, padded) in [(1, 1), (9, 10), (100, 104), (1000, 1024), (1025, 1088)] {
+            assert_eq!(PaddingScheme::padme(len), padded, "padme({})", len);
+        }
+    }
+}
@@ -0,0 +1,187 @@
+/*
+ * paperback: paper backup generator suitable for long-term storage
+ * Copyright (C) 2018-2022 Aleksa Sarai <cyphar@cyphar.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Hardening of key shards with a memorised passphrase.
+//!
+//! Normally a key shard can be decrypted by anyone holding the printed shard
+//! (with its codewords), so stealing a quorum of papers is enough to recover
+//! the secret. A key shard can optionally be hardened with a passphrase, in
+//! which case the shard key is derived with Argon2id from both the passphrase
+//! and the key encoded by the codewords:
+//!
+//! ```text
+//! shard_key = Argon2id(password = passphrase, salt, secret = codeword key)
+//! ```
+//!
+//! The Argon2id parameters and salt are stored unencrypted at the start of
+//! the encrypted key shard, so the cost of guessing the passphrase can be
+//! tuned for each backup without needing to be remembered.
+
+use crate::v0::{ChaChaPolyKey, Error};
+
+use argon2::{Algorithm, Argon2, Params, Version};
+use rand::{CryptoRng, RngCore};
+
+/// Length of the random salt used to derive a passphrase-hardened shard key.
+pub const SALT_LENGTH: usize = 16;
+
+/// The largest Argon2id memory cost (4 GiB), in KiB, which is accepted.
+///
+/// The parameters are read from the (untrusted) key shard before the
+/// passphrase is checked, so they are bounded to stop a forged key shard from
+/// making recovery use an unbounded amount of memory or time.
+pub const MAX_MEMORY_KIB: u32 = 4 * 1024 * 1024;
+
+/// The largest number of Argon2id passes which is accepted.
+pub const MAX_ITERATIONS: u32 = 64;
+
+/// The largest number of Argon2id lanes which is accepted (the largest number
+/// supported by Argon2id).
+pub const MAX_PARALLELISM: u32 = Params::MAX_P_COST;
+
+/// Tunable Argon2id parameters for deriving a passphrase-hardened shard key.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Argon2Params {
+    /// Memory used by each derivation, in KiB.
+    pub memory_kib: u32,
+    /// Number of passes over the memory.
+    pub iterations: u32,
+    /// Number of lanes which can be computed in parallel.
+    pub parallelism: u32,
+}
+
+impl Default for Argon2Params {
+    /// The second recommended option of RFC 9106 (64 MiB, 3 passes and 4
+    /// lanes), which is suitable for most machines.
+    fn default() -> Self {
+        Self {
+            memory_kib: 64 * 1024,
+            iterations: 3,
+            parallelism: 4,
+        }
+    }
+}
+
+impl Argon2Params {
+    /// Check that the parameters are within the bounds accepted by paperback
+    /// (see [`MAX_MEMORY_KIB`], [`MAX_ITERATIONS`] and [`MAX_PARALLELISM`]),
+    /// returning a description of the first parameter which is not.
+    pub(crate) fn check_bounds(&self) -> Result<(), String> {
+        if self.memory_kib > MAX_MEMORY_KIB {
+            Err(format!(
+                "argon2id memory of {} KiB is larger than the maximum of {} KiB",
+                self.memory_kib, MAX_MEMORY_KIB
+            ))
+        } else if self.iterations > MAX_ITERATIONS {
+            Err(format!(
+                "argon2id iterations of {} is larger than the maximum of {}",
+                self.iterations, MAX_ITERATIONS
+            ))
+        } else if !(1..=MAX_PARALLELISM).contains(&self.parallelism) {
+            Err(format!(
+                "argon2id parallelism of {} is not between 1 and {}",
+                self.parallelism, MAX_PARALLELISM
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn to_params(self) -> Result<Params, String> {
+        self.check_bounds()?;
+        Params::new(
+            self.memory_kib,
+            self.iterations,
+            self.parallelism,
+            Some(ChaChaPolyKey::default().len()),
+        )
+        .map_err(|err| format!("invalid argon2id parameters: {}", err))
+    }
+
+    /// Check that the parameters are accepted by Argon2id, and are within the
+    /// bounds accepted by paperback.
+    pub fn validate(&self) -> Result<(), Error> {
+        self.to_params().map(|_| ()).map_err(Error::Other)
+    }
+}
+
+#[cfg(test)]
+impl quickcheck::Arbitrary for Argon2Params {
+    fn arbitrary(g: &mut quickcheck::Gen) -> Self {
+        // Parameters outside the bounds cannot be parsed.
+        Self {
+            memory_kib: u32::arbitrary(g) % (MAX_MEMORY_KIB + 1),
+            iterations: u32::arbitrary(g) % (MAX_ITERATIONS + 1),
+            parallelism: 1 + u32::arbitrary(g) % MAX_PARALLELISM,
+        }
+    }
+}
+
+/// The passphrase header of a hardened key shard.
+#[derive(Clone, Debug)]
+#[cfg_attr(test, derive(PartialEq, Eq))]
+pub(crate) struct PassphraseHeader {
+    pub(crate) params: Argon2Params,
+    pub(crate) salt: [u8; SALT_LENGTH],
+}
+
+impl PassphraseHeader {
+    pub(crate) fn generate<R: CryptoRng + RngCore + ?Sized>(
+        params: Argon2Params,
+        rng: &mut R,
+    ) -> Result<Self, Error> {
+        params.validate()?;
+        let mut salt = [0u8; SALT_LENGTH];
+        rng.fill_bytes(&mut salt);
+        Ok(Self { params, salt })
+    }
+
+    /// Derive the shard key from the passphrase and the key encoded by the
+    /// codewords.
+    pub(crate) fn derive_key(
+        &self,
+        passphrase: &[u8],
+        codeword_key: &ChaChaPolyKey,
+    ) -> Result<ChaChaPolyKey, String> {
+        let argon2 = Argon2::new_with_secret(
+            codeword_key,
+            Algorithm::Argon2id,
+            Version::V0x13,
+            self.params.to_params()?,
+        )
+        .map_err(|err| format!("invalid argon2id parameters: {}", err))?;
+
+        let mut shard_key = ChaChaPolyKey::default();
+        argon2
+            .hash_password_into(passphrase, &self.salt, &mut shard_key)
+            .map_err(|err| format!("failed to derive shard key: {}", err))?;
+        Ok(shard_key)
+    }
+}
+
+#[cfg(test)]
+impl quickcheck::Arbitrary for PassphraseHeader {
+    fn arbitrary(g: &mut quickcheck::Gen) -> Self {
+        let mut salt = [0u8; SALT_LENGTH];
+        salt.iter_mut().for_each(|b| *b = u8::arbitrary(g));
+        Self {
+            params: Argon2Params::arbitrary(g),
+            salt,
+        }
+    }
+}
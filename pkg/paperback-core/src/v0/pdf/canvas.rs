@@ -0,0 +1,1019 @@
+/*
+ * paperback: paper backup generator suitable for long-term storage
+ * Copyright (C) 2018-2022 Aleksa Sarai <cyphar@cyphar.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Pages which documents are drawn onto.
+//!
+//! The layout code in [`generate`](super::generate) draws every page through
+//! a [`Canvas`], whose methods mirror the subset of `PdfLayerReference` it
+//! uses. This way each [`OutputFormat`](super::OutputFormat) is produced by
+//! exactly the same layout code, and so has identical content.
+
+use crate::v0::pdf::Error;
+
+use printpdf::*;
+
+use std::cell::RefCell;
+
+// Default DPI of SVGs drawn without one (the same as printpdf's default).
+const DEFAULT_SVG_DPI: f64 = 300.0;
+
+const FONT_ROBOTOSLAB: &[u8] = include_bytes!("fonts/RobotoSlab-Regular.ttf");
+const FONT_B612MONO: &[u8] = include_bytes!("fonts/B612Mono-Regular.ttf");
+const FONT_B612MONO_BOLD: &[u8] = include_bytes!("fonts/B612Mono-Bold.ttf");
+
+/// The fonts embedded in rendered documents.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) enum Font {
+    Text,
+    Monospace,
+    MonospaceBold,
+}
+
+impl Font {
+    pub(super) const ALL: [Font; 3] = [Self::Text, Self::Monospace, Self::MonospaceBold];
+
+    pub(super) fn data(self) -> &'static [u8] {
+        match self {
+            Self::Text => FONT_ROBOTOSLAB,
+            Self::Monospace => FONT_B612MONO,
+            Self::MonospaceBold => FONT_B612MONO_BOLD,
+        }
+    }
+
+    /// The width of text written in this font, including the character
+    /// spacing (in points) added after every character.
+    pub(super) fn text_width(self, text: &str, font_size: f64, character_spacing: f64) -> Mm {
+        let face = ttf_parser::Face::parse(self.data(), 0).expect("embedded fonts must be valid");
+        let units = text
+            .chars()
+            .filter_map(|ch| face.glyph_index(ch))
+            .filter_map(|glyph| face.glyph_hor_advance(glyph))
+            .map(f64::from)
+            .sum::<f64>();
+        let spacing = character_spacing * text.chars().count() as f64;
+        Pt(units / face.units_per_em() as f64 * font_size + spacing).into()
+    }
+
+    /// The largest font size (no larger than `font_size`) at which text fits
+    /// within `max_width`.
+    pub(super) fn fit_text(
+        self,
+        text: &str,
+        font_size: f64,
+        character_spacing: f64,
+        max_width: Mm,
+    ) -> f64 {
+        let width = self.text_width(text, font_size, character_spacing);
+        if width <= max_width {
+            return font_size;
+        }
+        let spacing = self.text_width(text, 0.0, character_spacing);
+        let glyphs = width - spacing;
+        font_size * ((max_width - spacing) / glyphs).max(0.0)
+    }
+
+    /// Split text into lines (at whitespace) which fit within `max_width`.
+    /// Words which are too long for a line on their own are not split.
+    pub(super) fn wrap_text(
+        self,
+        text: &str,
+        font_size: f64,
+        character_spacing: f64,
+        max_width: Mm,
+    ) -> Vec<String> {
+        let mut lines = Vec::new();
+        let mut line = String::new();
+        for word in text.split_whitespace() {
+            let candidate = match line.is_empty() {
+                true => word.to_string(),
+                false => format!("{} {}", line, word),
+            };
+            if !line.is_empty()
+                && self.text_width(&candidate, font_size, character_spacing) > max_width
+            {
+                lines.push(std::mem::replace(&mut line, word.to_string()));
+            } else {
+                line = candidate;
+            }
+        }
+        if !line.is_empty() {
+            lines.push(line);
+        }
+        lines
+    }
+
+    // CSS font-family and font-weight of the font.
+    fn css(self) -> (&'static str, &'static str) {
+        match self {
+            Self::Text => ("Roboto Slab", "normal"),
+            Self::Monospace => ("B612 Mono", "normal"),
+            Self::MonospaceBold => ("B612 Mono", "bold"),
+        }
+    }
+}
+
+/// The pages of a document being drawn.
+pub(super) trait Canvas: Sized {
+    type Output;
+
+    /// Create a new blank page, embedding the given fonts.
+    fn new(title: String, size: (Mm, Mm), fonts: &[Font]) -> Result<Self, Error>;
+
+    /// Start drawing on a new blank page of the same size, following the
+    /// pages drawn so far.
+    fn add_page(&mut self);
+
+    /// Finish drawing the document.
+    fn finish(self) -> Result<Self::Output, Error>;
+
+    fn begin_text_section(&self);
+    fn end_text_section(&self);
+    fn set_font(&self, font: &Font, font_size: f64);
+    fn set_line_height(&self, height: f64);
+    fn set_word_spacing(&self, spacing: f64);
+    fn set_character_spacing(&self, spacing: f64);
+    fn set_text_rendering_mode(&self, mode: TextRenderingMode);
+    fn set_text_cursor(&self, x: Mm, y: Mm);
+    fn set_text_matrix(&self, matrix: TextMatrix);
+    fn write_text<S: Into<String>>(&self, text: S, font: &Font);
+    fn add_line_break(&self);
+
+    fn set_fill_color(&self, colour: Color);
+    fn set_outline_color(&self, colour: Color);
+    fn set_line_dash_pattern(&self, pattern: LineDashPattern);
+    fn add_shape(&self, line: Line);
+
+    /// Draw into a region of the page, with the origin of the coordinates
+    /// used by `draw` moved to the bottom-left corner of the region.
+    fn with_origin<T, F: FnOnce(&Self) -> T>(&self, origin: (Mm, Mm), draw: F) -> T;
+
+    /// Draw an SVG image. The transform is computed from the dimensions of
+    /// the image.
+    fn add_svg<F: FnOnce((Px, Px)) -> SvgTransform>(
+        &self,
+        svg: &str,
+        transform: F,
+    ) -> Result<(), Error>;
+
+    /// Draw a QR code (rendered as an SVG image).
+    fn add_qr_code<F: FnOnce((Px, Px)) -> SvgTransform>(
+        &self,
+        svg: &str,
+        transform: F,
+    ) -> Result<(), Error> {
+        self.add_svg(svg, transform)
+    }
+}
+
+/// A page of a PDF document.
+pub(super) struct PdfCanvas {
+    doc: PdfDocumentReference,
+    size: (Mm, Mm),
+    layer: PdfLayerReference,
+    fonts: Vec<(Font, IndirectFontRef)>,
+}
+
+impl PdfCanvas {
+    fn font(&self, font: &Font) -> &IndirectFontRef {
+        self.fonts
+            .iter()
+            .find(|(loaded, _)| loaded == font)
+            .map(|(_, font_ref)| font_ref)
+            .expect("font must be embedded when creating the page")
+    }
+}
+
+impl Canvas for PdfCanvas {
+    type Output = PdfDocumentReference;
+
+    fn new(title: String, (width, height): (Mm, Mm), fonts: &[Font]) -> Result<Self, Error> {
+        let (doc, page1, layer1) = PdfDocument::new(title, width, height, "Layer 1");
+        let fonts = fonts
+            .iter()
+            .map(|font| Ok((*font, doc.add_external_font(font.data())?)))
+            .collect::<Result<Vec<_>, Error>>()?;
+        let layer = doc.get_page(page1).get_layer(layer1);
+        Ok(Self {
+            doc,
+            size: (width, height),
+            layer,
+            fonts,
+        })
+    }
+
+    fn add_page(&mut self) {
+        let (width, height) = self.size;
+        let (page, layer) = self.doc.add_page(width, height, "Layer 1");
+        self.layer = self.doc.get_page(page).get_layer(layer);
+    }
+
+    fn finish(self) -> Result<Self::Output, Error> {
+        self.doc.check_for_errors()?;
+        Ok(self.doc)
+    }
+
+    fn begin_text_section(&self) {
+        self.layer.begin_text_section()
+    }
+
+    fn end_text_section(&self) {
+        self.layer.end_text_section()
+    }
+
+    fn set_font(&self, font: &Font, font_size: f64) {
+        self.layer.set_font(self.font(font), font_size)
+    }
+
+    fn set_line_height(&self, height: f64) {
+        self.layer.set_line_height(height)
+    }
+
+    fn set_word_spacing(&self, spacing: f64) {
+        self.layer.set_word_spacing(spacing)
+    }
+
+    fn set_character_spacing(&self, spacing: f64) {
+        self.layer.set_character_spacing(spacing)
+    }
+
+    fn set_text_rendering_mode(&self, mode: TextRenderingMode) {
+        self.layer.set_text_rendering_mode(mode)
+    }
+
+    fn set_text_cursor(&self, x: Mm, y: Mm) {
+        self.layer.set_text_cursor(x, y)
+    }
+
+    fn set_text_matrix(&self, matrix: TextMatrix) {
+        self.layer.set_text_matrix(matrix)
+    }
+
+    fn write_text<S: Into<String>>(&self, text: S, font: &Font) {
+        self.layer.write_text(text, self.font(font))
+    }
+
+    fn add_line_break(&self) {
+        self.layer.add_line_break()
+    }
+
+    fn set_fill_color(&self, colour: Color) {
+        self.layer.set_fill_color(colour)
+    }
+
+    fn set_outline_color(&self, colour: Color) {
+        self.layer.set_outline_color(colour)
+    }
+
+    fn set_line_dash_pattern(&self, pattern: LineDashPattern) {
+        self.layer.set_line_dash_pattern(pattern)
+    }
+
+    fn add_shape(&self, line: Line) {
+        self.layer.add_shape(line)
+    }
+
+    fn with_origin<T, F: FnOnce(&Self) -> T>(&self, (x, y): (Mm, Mm), draw: F) -> T {
+        self.layer.save_graphics_state();
+        self.layer.set_ctm(CurTransMat::Translate(x, y));
+        let result = draw(self);
+        self.layer.restore_graphics_state();
+        result
+    }
+
+    fn add_svg<F: FnOnce((Px, Px)) -> SvgTransform>(
+        &self,
+        svg: &str,
+        transform: F,
+    ) -> Result<(), Error> {
+        let svg = Svg::parse(svg)?.into_xobject(&self.layer);
+        let transform = transform((svg.width, svg.height));
+        svg.add_to_layer(&self.layer, transform);
+        Ok(())
+    }
+}
+
+// The drawing state of an SVG page, mirroring the PDF graphics and text
+// state. Positions are in points, with the origin at the bottom-left of the
+// page (as in PDF).
+struct SvgState {
+    body: String,
+    fill_colour: Color,
+    outline_colour: Color,
+    dash_pattern: LineDashPattern,
+    font: Font,
+    font_size: f64,
+    line_height: f64,
+    character_spacing: f64,
+    rendering_mode: TextRenderingMode,
+    // Linear part of the text matrix, and the start of the current line.
+    text_matrix: [f64; 4],
+    line_start: (f64, f64),
+    // Whether a <text> element for the current line is open.
+    in_line: bool,
+}
+
+impl SvgState {
+    fn close_line(&mut self) {
+        if self.in_line {
+            self.body.push_str("</text>");
+            self.in_line = false;
+        }
+    }
+
+    // Move the start of the line by (x, y) in text space.
+    fn move_line(&mut self, (x, y): (f64, f64)) {
+        self.close_line();
+        let [a, b, c, d] = self.text_matrix;
+        self.line_start.0 += a * x + c * y;
+        self.line_start.1 += b * x + d * y;
+    }
+}
+
+/// The pages of an SVG document, each of which is a separate SVG image.
+pub(super) struct SvgCanvas {
+    title: String,
+    size: (Mm, Mm),
+    fonts: Vec<Font>,
+    pages: Vec<String>,
+    state: RefCell<SvgState>,
+}
+
+impl SvgCanvas {
+    // Convert a point in PDF coordinates into SVG coordinates.
+    fn flip(&self, (x, y): (f64, f64)) -> (f64, f64) {
+        (x, Pt::from(self.size.1).0 - y)
+    }
+
+    // Wrap the body of a page in an SVG image.
+    fn page_svg(&self, body: &str) -> String {
+        let (width, height) = self.size;
+        let mut svg = format!(
+            r##"<svg xmlns="http://www.w3.org/2000/svg" width="{}mm" height="{}mm" viewBox="0 0 {} {}" xml:space="preserve"><title>{}</title>"##,
+            width.0,
+            height.0,
+            Pt::from(width).0,
+            Pt::from(height).0,
+            escape_xml(&self.title),
+        );
+        // Embed the same fonts as the PDF, so the page looks the same even
+        // if the fonts are not installed.
+        svg.push_str("<defs><style>");
+        for font in &self.fonts {
+            let (family, weight) = font.css();
+            let data = multibase::encode(multibase::Base::Base64Pad, font.data());
+            svg.push_str(&format!(
+                r#"@font-face{{font-family:"{}";font-weight:{};src:url("data:font/ttf;base64,{}");}}"#,
+                family,
+                weight,
+                // Strip the multibase prefix.
+                &data[1..],
+            ));
+        }
+        svg.push_str("</style></defs>");
+        svg.push_str(body);
+        svg.push_str("</svg>\n");
+        svg
+    }
+}
+
+fn css_colour(colour: &Color) -> String {
+    let (r, g, b) = match colour {
+        Color::Rgb(Rgb { r, g, b, .. }) => (*r, *g, *b),
+        Color::Greyscale(Greyscale { percent, .. }) => (*percent, *percent, *percent),
+        Color::Cmyk(Cmyk { c, m, y, k, .. }) | Color::SpotColor(SpotColor { c, m, y, k, .. }) => (
+            (1.0 - c) * (1.0 - k),
+            (1.0 - m) * (1.0 - k),
+            (1.0 - y) * (1.0 - k),
+        ),
+    };
+    let channel = |value: f64| (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+    format!("#{:02x}{:02x}{:02x}", channel(r), channel(g), channel(b))
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+// Look up a numeric attribute of the root element of an SVG image.
+fn svg_dimension(svg: &str, name: &str) -> Result<f64, Error> {
+    let tag = svg
+        .find("<svg")
+        .map(|start| &svg[start..])
+        .and_then(|tag| tag.find('>').map(|end| &tag[..end]))
+        .ok_or_else(|| Error::OtherError("svg image has no <svg> element".into()))?;
+    tag.split_whitespace()
+        .find_map(|attr| {
+            attr.strip_prefix(name)?
+                .strip_prefix("=\"")?
+                .split('"')
+                .next()
+        })
+        .map(|value| value.trim_end_matches("px"))
+        .and_then(|value| value.parse().ok())
+        .ok_or_else(|| Error::OtherError(format!("svg image has no valid {} attribute", name)))
+}
+
+impl Canvas for SvgCanvas {
+    type Output = Vec<String>;
+
+    fn new(title: String, size: (Mm, Mm), fonts: &[Font]) -> Result<Self, Error> {
+        Ok(Self {
+            title,
+            size,
+            fonts: fonts.to_vec(),
+            pages: Vec::new(),
+            state: RefCell::new(SvgState {
+                body: String::new(),
+                fill_colour: Color::Greyscale(Greyscale::new(0.0, None)),
+                outline_colour: Color::Greyscale(Greyscale::new(0.0, None)),
+                dash_pattern: LineDashPattern::default(),
+                font: Font::Text,
+                font_size: 0.0,
+                line_height: 0.0,
+                character_spacing: 0.0,
+                rendering_mode: TextRenderingMode::Fill,
+                text_matrix: [1.0, 0.0, 0.0, 1.0],
+                line_start: (0.0, 0.0),
+                in_line: false,
+            }),
+        })
+    }
+
+    fn add_page(&mut self) {
+        let body = {
+            let state = self.state.get_mut();
+            state.close_line();
+            std::mem::take(&mut state.body)
+        };
+        let page = self.page_svg(&body);
+        self.pages.push(page);
+    }
+
+    fn finish(mut self) -> Result<Self::Output, Error> {
+        self.add_page();
+        Ok(self.pages)
+    }
+
+    fn begin_text_section(&self) {
+        let mut state = self.state.borrow_mut();
+        state.close_line();
+        state.text_matrix = [1.0, 0.0, 0.0, 1.0];
+        state.line_start = (0.0, 0.0);
+    }
+
+    fn end_text_section(&self) {
+        self.state.borrow_mut().close_line()
+    }
+
+    fn set_font(&self, font: &Font, font_size: f64) {
+        let mut state = self.state.borrow_mut();
+        state.font = *font;
+        state.font_size = font_size;
+    }
+
+    fn set_line_height(&self, height: f64) {
+        self.state.borrow_mut().line_height = height
+    }
+
+    // PDF viewers only apply word spacing to single-byte encoded spaces, and
+    // the embedded fonts are two-byte encoded, so it has no effect.
+    fn set_word_spacing(&self, _: f64) {}
+
+    fn set_character_spacing(&self, spacing: f64) {
+        self.state.borrow_mut().character_spacing = spacing
+    }
+
+    fn set_text_rendering_mode(&self, mode: TextRenderingMode) {
+        self.state.borrow_mut().rendering_mode = mode
+    }
+
+    fn set_text_cursor(&self, x: Mm, y: Mm) {
+        self.state
+            .borrow_mut()
+            .move_line((Pt::from(x).0, Pt::from(y).0))
+    }
+
+    fn set_text_matrix(&self, matrix: TextMatrix) {
+        let (linear, origin) = match matrix {
+            TextMatrix::Rotate(angle) => {
+                let (sin, cos) = angle.to_radians().sin_cos();
+                ([cos, sin, -sin, cos], (0.0, 0.0))
+            }
+            TextMatrix::Translate(x, y) => ([1.0, 0.0, 0.0, 1.0], (x.0, y.0)),
+            TextMatrix::TranslateRotate(x, y, angle) => {
+                let (sin, cos) = angle.to_radians().sin_cos();
+                ([cos, sin, -sin, cos], (x.0, y.0))
+            }
+            TextMatrix::Raw([a, b, c, d, e, f]) => ([a, b, c, d], (e, f)),
+        };
+        let mut state = self.state.borrow_mut();
+        state.close_line();
+        state.text_matrix = linear;
+        state.line_start = origin;
+    }
+
+    // As in PDF, text is drawn in the font set with set_font (the font given
+    // here is only used to encode the text).
+    fn write_text<S: Into<String>>(&self, text: S, _: &Font) {
+        let mut state = self.state.borrow_mut();
+        if !state.in_line {
+            let (x, y) = self.flip(state.line_start);
+            // Text space has y pointing up, so flip it around the baseline.
+            let [a, b, c, d] = state.text_matrix;
+            let element = format!(
+                r#"<text transform="matrix({} {} {} {} {} {})">"#,
+                a, -b, -c, d, x, y
+            );
+            state.body.push_str(&element);
+            state.in_line = true;
+        }
+
+        let (fill, stroke) = match state.rendering_mode {
+            TextRenderingMode::Stroke | TextRenderingMode::StrokeClip => {
+                ("none".to_string(), css_colour(&state.outline_colour))
+            }
+            TextRenderingMode::FillStroke | TextRenderingMode::FillStrokeClip => (
+                css_colour(&state.fill_colour),
+                css_colour(&state.outline_colour),
+            ),
+            TextRenderingMode::Invisible | TextRenderingMode::Clip => {
+                ("none".to_string(), "none".to_string())
+            }
+            TextRenderingMode::Fill | TextRenderingMode::FillClip => {
+                (css_colour(&state.fill_colour), "none".to_string())
+            }
+        };
+        let (family, weight) = state.font.css();
+        let tspan = format!(
+            r#"<tspan font-family="{}" font-weight="{}" font-size="{}" letter-spacing="{}" fill="{}" stroke="{}">{}</tspan>"#,
+            family,
+            weight,
+            state.font_size,
+            state.character_spacing,
+            fill,
+            stroke,
+            escape_xml(&text.into()),
+        );
+        state.body.push_str(&tspan);
+    }
+
+    fn add_line_break(&self) {
+        let mut state = self.state.borrow_mut();
+        let line_height = state.line_height;
+        state.move_line((0.0, -line_height));
+    }
+
+    fn set_fill_color(&self, colour: Color) {
+        self.state.borrow_mut().fill_colour = colour
+    }
+
+    fn set_outline_color(&self, colour: Color) {
+        self.state.borrow_mut().outline_colour = colour
+    }
+
+    fn set_line_dash_pattern(&self, pattern: LineDashPattern) {
+        self.state.borrow_mut().dash_pattern = pattern
+    }
+
+    fn add_shape(&self, line: Line) {
+        let mut state = self.state.borrow_mut();
+        let mut path = String::new();
+        for (idx, (point, _)) in line.points.iter().enumerate() {
+            let (x, y) = self.flip((point.x.0, point.y.0));
+            path.push_str(&format!("{}{} {} ", if idx == 0 { "M" } else { "L" }, x, y));
+        }
+        if line.is_closed {
+            path.push('Z');
+        }
+
+        let fill = match line.has_fill {
+            true => css_colour(&state.fill_colour),
+            false => "none".to_string(),
+        };
+        let stroke = match line.has_stroke {
+            true => css_colour(&state.outline_colour),
+            false => "none".to_string(),
+        };
+        let pattern = &state.dash_pattern;
+        let dashes = [
+            pattern.dash_1,
+            pattern.gap_1,
+            pattern.dash_2,
+            pattern.gap_2,
+            pattern.dash_3,
+            pattern.gap_3,
+        ]
+        .iter()
+        .flatten()
+        .map(|len| len.to_string())
+        .collect::<Vec<_>>();
+        let dash_array = match dashes.is_empty() {
+            true => "none".to_string(),
+            false => dashes.join(" "),
+        };
+        let element = format!(
+            r#"<path d="{}" fill="{}" stroke="{}" stroke-width="1" stroke-dasharray="{}" stroke-dashoffset="{}"/>"#,
+            path.trim_end(),
+            fill,
+            stroke,
+            dash_array,
+            pattern.offset,
+        );
+        state.body.push_str(&element);
+    }
+
+    fn with_origin<T, F: FnOnce(&Self) -> T>(&self, (x, y): (Mm, Mm), draw: F) -> T {
+        {
+            let mut state = self.state.borrow_mut();
+            state.close_line();
+            // SVG coordinates are flipped, so moving the origin up the page
+            // moves it towards the top of the image.
+            state.body.push_str(&format!(
+                r#"<g transform="translate({} {})">"#,
+                Pt::from(x).0,
+                -Pt::from(y).0
+            ));
+        }
+        let result = draw(self);
+        let mut state = self.state.borrow_mut();
+        state.close_line();
+        state.body.push_str("</g>");
+        result
+    }
+
+    fn add_svg<F: FnOnce((Px, Px)) -> SvgTransform>(
+        &self,
+        svg: &str,
+        transform: F,
+    ) -> Result<(), Error> {
+        let (width, height) = (svg_dimension(svg, "width")?, svg_dimension(svg, "height")?);
+        let transform = transform((Px(width as usize), Px(height as usize)));
+        // Rotated images are never drawn, so transform.rotate is ignored.
+        let dpi = transform.dpi.unwrap_or(DEFAULT_SVG_DPI);
+        let (scale_x, scale_y) = (
+            transform.scale_x.unwrap_or(1.0) * 72.0 / dpi,
+            transform.scale_y.unwrap_or(1.0) * 72.0 / dpi,
+        );
+        // The translation is of the bottom-left corner of the image.
+        let (x, y) = self.flip((
+            Pt::from(transform.translate_x.unwrap_or(Mm(0.0))).0,
+            Pt::from(transform.translate_y.unwrap_or(Mm(0.0))).0 + height * scale_y,
+        ));
+        // Strip the XML declaration so the image can be nested.
+        let image = &svg[svg.find("<svg").unwrap_or(0)..];
+
+        let mut state = self.state.borrow_mut();
+        state.close_line();
+        state.body.push_str(&format!(
+            r#"<g transform="translate({} {}) scale({} {})">{}</g>"#,
+            x,
+            y,
+            scale_x,
+            scale_y,
+            image.trim_end()
+        ));
+        Ok(())
+    }
+}
+
+/// Collects the QR codes drawn on every page (and nothing else), as SVG
+/// images along with their printed width.
+pub(super) struct CodesCanvas {
+    codes: RefCell<Vec<(String, Mm)>>,
+}
+
+impl Canvas for CodesCanvas {
+    type Output = Vec<(String, Mm)>;
+
+    fn new(_: String, _: (Mm, Mm), _: &[Font]) -> Result<Self, Error> {
+        Ok(Self {
+            codes: RefCell::new(Vec::new()),
+        })
+    }
+
+    fn add_page(&mut self) {}
+
+    fn finish(self) -> Result<Self::Output, Error> {
+        Ok(self.codes.into_inner())
+    }
+
+    fn begin_text_section(&self) {}
+    fn end_text_section(&self) {}
+    fn set_font(&self, _: &Font, _: f64) {}
+    fn set_line_height(&self, _: f64) {}
+    fn set_word_spacing(&self, _: f64) {}
+    fn set_character_spacing(&self, _: f64) {}
+    fn set_text_rendering_mode(&self, _: TextRenderingMode) {}
+    fn set_text_cursor(&self, _: Mm, _: Mm) {}
+    fn set_text_matrix(&self, _: TextMatrix) {}
+    fn write_text<S: Into<String>>(&self, _: S, _: &Font) {}
+    fn add_line_break(&self) {}
+
+    fn set_fill_color(&self, _: Color) {}
+    fn set_outline_color(&self, _: Color) {}
+    fn set_line_dash_pattern(&self, _: LineDashPattern) {}
+    fn add_shape(&self, _: Line) {}
+
+    fn with_origin<T, F: FnOnce(&Self) -> T>(&self, _: (Mm, Mm), draw: F) -> T {
+        draw(self)
+    }
+
+    fn add_svg<F: FnOnce((Px, Px)) -> SvgTransform>(&self, _: &str, _: F) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn add_qr_code<F: FnOnce((Px, Px)) -> SvgTransform>(
+        &self,
+        svg: &str,
+        transform: F,
+    ) -> Result<(), Error> {
+        let width = svg_dimension(svg, "width")?;
+        let transform = transform((
+            Px(width as usize),
+            Px(svg_dimension(svg, "height")? as usize),
+        ));
+        let dpi = transform.dpi.unwrap_or(DEFAULT_SVG_DPI);
+        let printed_width = Pt(width * 72.0 / dpi * transform.scale_x.unwrap_or(1.0));
+        self.codes
+            .borrow_mut()
+            .push((svg.to_string(), printed_width.into()));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Font;
+    use crate::v0::{
+        pdf::{
+            generate::MAIN_DOCUMENT_CODES_PER_PAGE,
+            qr::{self, Joiner, Part, PartType},
+            KeyShardSheet, Language, MemorySink, OutputFormat, PaperSize, RecoveryInstructions,
+            RenderOptions, Symbology, ToPdf,
+        },
+        BackupBuilder, FromWire, ToWire,
+    };
+
+    use printpdf::{Mm, Pt};
+
+    #[test]
+    fn text_metrics() {
+        // Every B612 Mono glyph is 0.65em wide.
+        let width = Pt::from(Font::Monospace.text_width("abcd", 10.0, 1.0));
+        assert!((width.0 - 4.0 * (6.5 + 1.0)).abs() < 1e-6);
+
+        let max_width = Font::Monospace.text_width("ab cd", 10.0, 1.0);
+        assert_eq!(
+            Font::Monospace.wrap_text("ab cd ef gh ijklmnop", 10.0, 1.0, max_width),
+            ["ab cd", "ef gh", "ijklmnop"]
+        );
+        assert_eq!(
+            Font::Monospace.fit_text("ab cd", 10.0, 1.0, max_width),
+            10.0
+        );
+        let font_size = Font::Monospace.fit_text("abcd", 10.0, 1.0, Mm::from(Pt(4.0 * 4.25)));
+        assert!((font_size - 5.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn render_paper_sizes() {
+        let backup = BackupBuilder::new(2).build(b"secret").unwrap();
+        let (shard, codewords) = backup.next_shard().unwrap().encrypt().unwrap();
+        for (paper_size, dimensions) in [
+            (PaperSize::Letter, r#"width="215.9mm" height="279.4mm""#),
+            (PaperSize::MIN, r#"width="105mm" height="148mm""#),
+        ] {
+            let options = RenderOptions {
+                paper_size: Some(paper_size),
+                ..Default::default()
+            };
+            for page in backup
+                .main_document()
+                .to_svg_with_options(&options)
+                .unwrap()
+                .iter()
+                .chain(&(&shard, &codewords).to_svg_with_options(&options).unwrap())
+            {
+                assert!(page.contains(dimensions), "{:?}", paper_size);
+            }
+        }
+
+        let options = RenderOptions {
+            paper_size: Some(PaperSize::Custom {
+                width: 100,
+                height: 100,
+            }),
+            ..Default::default()
+        };
+        assert!(backup
+            .main_document()
+            .to_pdf_with_options(&options)
+            .is_err());
+    }
+
+    #[test]
+    fn render_main_document_pages() {
+        let secret = (0..12_000)
+            .map(|_| rand::random::<u8>())
+            .collect::<Vec<_>>();
+        let backup = BackupBuilder::new(2).build(&secret).unwrap();
+        let main_document = backup.main_document();
+        let options = RenderOptions::default();
+
+        let (_, datas) = qr::generate_codes(
+            PartType::MainDocumentData,
+            main_document.to_wire(),
+            &options,
+        )
+        .unwrap();
+        let pages = main_document.to_svg_with_options(&options).unwrap();
+        assert!(pages.len() > 1);
+        assert_eq!(
+            pages.len(),
+            datas.len().div_ceil(MAIN_DOCUMENT_CODES_PER_PAGE)
+        );
+        assert!(pages[1].contains("Page 2 of"));
+        assert!(main_document.to_pdf_with_options(&options).is_ok());
+
+        // The codes of every page can be scanned in any order.
+        let mut joiner = Joiner::new();
+        for data in datas.iter().rev().skip(1) {
+            joiner.add_part(Part::from_wire(data).unwrap()).unwrap();
+        }
+        assert_eq!(joiner.missing(), vec![0]);
+        joiner
+            .add_part(Part::from_wire(&datas[0]).unwrap())
+            .unwrap();
+        assert_eq!(joiner.combine_parts().unwrap(), main_document.to_wire());
+    }
+
+    #[test]
+    fn render_data_matrix() {
+        let backup = BackupBuilder::new(2).build(b"secret").unwrap();
+        let (shard, codewords) = backup.next_shard().unwrap().encrypt().unwrap();
+        let options = RenderOptions {
+            symbology: Symbology::DataMatrix,
+            ..Default::default()
+        };
+        // The symbology is recorded in the header of every document.
+        for page in backup
+            .main_document()
+            .to_svg_with_options(&options)
+            .unwrap()
+            .iter()
+            .chain(&(&shard, &codewords).to_svg_with_options(&options).unwrap())
+        {
+            assert!(page.contains("paperback-v0 datamatrix"));
+        }
+        assert!(!backup
+            .main_document()
+            .to_svg_with_options(&RenderOptions::default())
+            .unwrap()[0]
+            .contains("datamatrix"));
+    }
+
+    #[test]
+    fn render_language() {
+        let backup = BackupBuilder::new(2).build(b"secret").unwrap();
+        let main_document = backup.main_document();
+        let (shard, codewords) = backup.next_shard().unwrap().encrypt().unwrap();
+        let options = RenderOptions {
+            language: Language::German,
+            ..Default::default()
+        };
+        let page = &main_document.to_svg_with_options(&options).unwrap()[0];
+        assert!(page.contains("Hauptdokument"));
+        // The machine-readable data is the same in every language.
+        assert!(page.contains(&main_document.id()));
+        let page = &(&shard, &codewords).to_svg_with_options(&options).unwrap()[0];
+        assert!(page.contains("Schlüsselanteil"));
+        assert!(page.contains(&codewords[0]));
+    }
+
+    #[test]
+    fn render_key_shard_sheet() {
+        let backup = BackupBuilder::new(2).build(b"secret").unwrap();
+        let shards = (0..3)
+            .map(|_| {
+                let shard = backup.next_shard().unwrap();
+                (shard.id(), shard.encrypt().unwrap())
+            })
+            .collect::<Vec<_>>();
+
+        let options = RenderOptions::default();
+        assert_eq!(KeyShardSheet::capacity(&options).unwrap(), 2);
+        let letter = RenderOptions {
+            paper_size: Some(PaperSize::Letter),
+            ..Default::default()
+        };
+        assert_eq!(KeyShardSheet::capacity(&letter).unwrap(), 2);
+        let a5 = RenderOptions {
+            paper_size: Some(PaperSize::A5),
+            ..Default::default()
+        };
+        assert_eq!(KeyShardSheet::capacity(&a5).unwrap(), 1);
+
+        let mut sheet = KeyShardSheet::new();
+        for (_, (shard, codewords)) in &shards[..2] {
+            sheet.push(shard, codewords, None);
+        }
+        let pages = sheet.to_svg_with_options(&options).unwrap();
+        assert_eq!(pages.len(), 1);
+        // A4 turned sideways, with each key shard in its own region.
+        assert!(pages[0].contains(r#"width="297mm" height="210mm""#));
+        for (shard_id, _) in &shards[..2] {
+            assert!(pages[0].contains(shard_id.as_str()));
+        }
+
+        let (_, (shard, codewords)) = &shards[2];
+        sheet.push(shard, codewords, None);
+        assert!(sheet.to_svg_with_options(&options).is_err());
+    }
+
+    #[test]
+    fn render_recovery_instructions() {
+        let backup = BackupBuilder::new(3).build(b"secret").unwrap();
+        let instructions = RecoveryInstructions::from(backup.main_document());
+        assert_eq!(instructions.quorum_size(), 3);
+
+        let page = &instructions
+            .to_svg_with_options(&RenderOptions::default())
+            .unwrap()[0];
+        assert!(page.contains(&format!(">{}</tspan>", instructions.document_id())));
+        assert!(page.contains(">paperback-cli recover --interactive secret.txt</tspan>"));
+        assert!(page.contains("key-shard-3.png"));
+        // There is nothing to scan on the instructions.
+        assert_eq!(page.matches("<g transform=").count(), 0);
+
+        // The instructions are shrunk to fit on smaller pages.
+        for paper_size in [PaperSize::A5, PaperSize::Letter] {
+            let options = RenderOptions {
+                paper_size: Some(paper_size),
+                language: Language::German,
+                ..Default::default()
+            };
+            let page = &instructions.to_svg_with_options(&options).unwrap()[0];
+            assert!(page.contains("Wiederherstellungsanleitung"));
+        }
+    }
+
+    #[test]
+    fn render_svg() {
+        let backup = BackupBuilder::new(2).build(b"secret").unwrap();
+        let main_document = backup.main_document();
+        let shard = backup.next_shard().unwrap();
+        let shard_id = shard.id();
+        let (shard, codewords) = shard.encrypt().unwrap();
+        let options = RenderOptions {
+            output_format: OutputFormat::Svg,
+            ..Default::default()
+        };
+
+        let mut sink = MemorySink::new();
+        main_document
+            .render_to(&mut sink, "main_document.pdf", &options)
+            .unwrap();
+        (&shard, &codewords)
+            .render_to(&mut sink, "key_shard.pdf", &options)
+            .unwrap();
+        let names = sink
+            .artifacts()
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["main_document.svg", "key_shard.svg"]);
+
+        // Each page has the same text and codes as the PDF.
+        let page = std::str::from_utf8(sink.get("main_document.svg").unwrap()).unwrap();
+        assert!(page.starts_with("<svg ") && page.ends_with("</svg>\n"));
+        assert!(page.contains(&format!(">{}</tspan>", main_document.id())));
+        // The data codes and the checksum code.
+        assert!(page.matches("<g transform=").count() >= 2);
+
+        let page = std::str::from_utf8(sink.get("key_shard.svg").unwrap()).unwrap();
+        assert!(page.contains(&format!(">{}</tspan>", shard_id)));
+        for codeword in codewords.iter() {
+            assert!(page.contains(&format!(">{}</tspan>", codeword)));
+        }
+        // Both codes and the scissors.
+        assert_eq!(page.matches("<g transform=").count(), 3);
+    }
+}
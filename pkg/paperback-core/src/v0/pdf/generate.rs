@@ -16,17 +16,265 @@
  * along with this program.  If not, see <https://www.gnu.org/licenses/>.
  */
 
-use crate::v0::{
-    pdf::{qr, qr::PartType, Error},
-    EncryptedKeyShard, KeyShardCodewords, MainDocument, ToWire,
+use crate::{
+    progress::{Stage, Tracker},
+    v0::{
+        airgap::{AirGapRequest, AirGapResponse},
+        ceremony::Manifest,
+        constraints,
+        pdf::{
+            canvas::{Canvas, CodesCanvas, Font, PdfCanvas, SvgCanvas},
+            i18n::Language,
+            qr,
+            qr::{Code, PartType},
+            raster, CodeLayout, Error, RecoveryEffort, RenderSink, QRCODE_MULTIBASE,
+        },
+        DocumentId, EncryptedKeyShard, KeyShard, KeyShardCodewords, MainDocument, SpecReference,
+        TextEncoding, ToWire, Warning, Warnings,
+    },
 };
 
-use multibase::Base;
 use printpdf::*;
-use qrcode::render::svg;
+
+/// Options controlling how documents are rendered.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RenderOptions {
+    /// Pad the encoded key shard data to (at least) this many bytes. All
+    /// shards of a document are already padded to the same size, but setting a
+    /// fixed size also hides the size of the secret's shards across different
+    /// documents.
+    ///
+    /// Key shards are not repadded when they are rendered, so this must be
+    /// passed to `KeyShard::encrypt_padded` when the shards are encrypted (so
+    /// that checksums and self-tests are computed for the printed shards).
+    pub key_shard_size: Option<usize>,
+    /// The visual style to render documents in.
+    pub style: RenderStyle,
+    /// Add this many Reed-Solomon parity codes to data which is split across
+    /// several QR codes (the main document and air-gap documents), so that it
+    /// can still be recovered if up to this many of the codes are unreadable.
+    /// Documents with parity codes cannot be read by older versions of
+    /// paperback.
+    pub parity_codes: usize,
+    /// The file format documents are written in by [`ToPdf::render_to`].
+    pub output_format: OutputFormat,
+    /// The paper size documents are laid out for. By default, key shards are
+    /// laid out for A5 and all other documents for A4.
+    pub paper_size: Option<PaperSize>,
+    /// The language the human-readable text of main documents and key shards
+    /// is printed in.
+    pub language: Language,
+    /// The symbology (type of barcode) of all codes.
+    pub symbology: Symbology,
+    /// The error correction level of all QR codes.
+    pub qr_error_correction: QrErrorCorrection,
+    /// The largest QR code version (from 1 to 40) used for data which is split
+    /// across several QR codes, which is split into more (smaller) codes to
+    /// fit. Data which is always stored in a single QR code (such as key
+    /// shards) uses whatever version it needs.
+    pub max_qr_version: Option<u8>,
+}
+
+/// The file format of rendered documents.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// A single PDF file for each document.
+    #[default]
+    Pdf,
+    /// An SVG image for each page of the document, with the same content as
+    /// the PDF.
+    Svg,
+    /// A PNG image for each page of the document (with the same content as
+    /// the PDF), at the given resolution in dots per inch.
+    Png { dpi: u32 },
+    /// A PNG image for each QR code of the document (at its printed size),
+    /// at the given resolution in dots per inch. This is intended for thermal
+    /// printers and similar workflows which only print the codes.
+    PngCodes { dpi: u32 },
+}
+
+/// The symbology (type of barcode) data is printed in. The symbology is
+/// printed in the header of each document, next to the paperback version.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Symbology {
+    /// QR codes.
+    #[default]
+    Qr,
+    /// Data Matrix (ECC 200) codes, which some scanners read more reliably
+    /// than large QR codes, and which tolerate different kinds of damage. The
+    /// QR error correction level and maximum version don't apply to them.
+    DataMatrix,
+}
+
+impl Symbology {
+    // The paperback version and symbology printed in document headers.
+    fn header_label(self) -> &'static str {
+        match self {
+            Self::Qr => "paperback-v0",
+            Self::DataMatrix => "paperback-v0 datamatrix",
+        }
+    }
+}
+
+/// The error correction level of QR codes. Codes with a higher level can be
+/// read despite more damage (or poorer printing), but hold less data.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum QrErrorCorrection {
+    /// Roughly 7% of each code can be restored.
+    Low,
+    /// Roughly 15% of each code can be restored.
+    #[default]
+    Medium,
+    /// Roughly 25% of each code can be restored.
+    Quartile,
+    /// Roughly 30% of each code can be restored.
+    High,
+}
+
+/// The paper size documents are laid out for.
+///
+/// The margins, QR codes and text of documents are fitted to the page, but
+/// documents are not readable on paper smaller than A6.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PaperSize {
+    /// ISO A4 (210mm x 297mm).
+    A4,
+    /// ISO A5 (148mm x 210mm).
+    A5,
+    /// US Letter (8.5in x 11in).
+    Letter,
+    /// A custom paper size, with the width and height in millimetres.
+    Custom { width: u32, height: u32 },
+}
+
+impl PaperSize {
+    /// The smallest paper size (A6) documents can be laid out for.
+    pub const MIN: Self = Self::Custom {
+        width: 105,
+        height: 148,
+    };
+
+    /// The width and height of the paper.
+    pub fn dimensions(self) -> (Mm, Mm) {
+        match self {
+            Self::A4 => (Mm(210.0), Mm(297.0)),
+            Self::A5 => (Mm(148.0), Mm(210.0)),
+            Self::Letter => (Mm(215.9), Mm(279.4)),
+            Self::Custom { width, height } => (Mm(width as f64), Mm(height as f64)),
+        }
+    }
+
+    /// Returns whether documents can be laid out for this paper size (it must
+    /// be at least as large as [`PaperSize::MIN`]).
+    pub fn is_supported(self) -> bool {
+        let ((width, height), (min_width, min_height)) =
+            (self.dimensions(), Self::MIN.dimensions());
+        width >= min_width && height >= min_height
+    }
+
+    // The blank space left around the edges of the page.
+    fn margin(self) -> Mm {
+        match self {
+            // US printers commonly can't print within 1/4in of the edge.
+            Self::Letter => Mm(6.35),
+            _ => Mm(5.0),
+        }
+    }
+}
 
 pub trait ToPdf {
-    fn to_pdf(&self) -> Result<PdfDocumentReference, Error>;
+    fn to_pdf_with_options(&self, options: &RenderOptions) -> Result<PdfDocumentReference, Error>;
+
+    fn to_pdf(&self) -> Result<PdfDocumentReference, Error> {
+        self.to_pdf_with_options(&RenderOptions::default())
+    }
+
+    /// Render the document as SVG images, one for each page.
+    fn to_svg_with_options(&self, options: &RenderOptions) -> Result<Vec<String>, Error>;
+
+    /// Render the document as PNG images, one for each page, at the given
+    /// resolution (in dots per inch).
+    fn to_png_with_options(
+        &self,
+        options: &RenderOptions,
+        dpi: u32,
+    ) -> Result<Vec<Vec<u8>>, Error> {
+        self.to_svg_with_options(options)?
+            .iter()
+            .map(|page| raster::page_to_png(page, dpi))
+            .collect()
+    }
+
+    /// Render each QR code of the document as a PNG image (at its printed
+    /// size), at the given resolution (in dots per inch).
+    fn qr_codes_to_png_with_options(
+        &self,
+        options: &RenderOptions,
+        dpi: u32,
+    ) -> Result<Vec<Vec<u8>>, Error>;
+
+    /// Returns any warnings about rendering this document with the given
+    /// options (such as pages being close to full).
+    fn warnings(&self, _options: &RenderOptions) -> Warnings {
+        Warnings::new()
+    }
+
+    /// Render the document in `options.output_format` and write it to `sink`
+    /// with the given file name. Images are written with the extension of the
+    /// name replaced, and the page (or code) number added if there are
+    /// several.
+    fn render_to(
+        &self,
+        sink: &mut dyn RenderSink,
+        name: &str,
+        options: &RenderOptions,
+    ) -> Result<(), Error> {
+        match options.output_format {
+            OutputFormat::Pdf => {
+                sink.write_artifact(name, &self.to_pdf_with_options(options)?.save_to_bytes()?)
+            }
+            OutputFormat::Svg => {
+                let pages = self.to_svg_with_options(options)?;
+                write_images(
+                    sink,
+                    name,
+                    ("page", "svg"),
+                    pages.iter().map(String::as_bytes),
+                )
+            }
+            OutputFormat::Png { dpi } => {
+                let pages = self.to_png_with_options(options, dpi)?;
+                write_images(sink, name, ("page", "png"), pages.iter().map(Vec::as_slice))
+            }
+            OutputFormat::PngCodes { dpi } => {
+                let codes = self.qr_codes_to_png_with_options(options, dpi)?;
+                write_images(sink, name, ("code", "png"), codes.iter().map(Vec::as_slice))
+            }
+        }
+    }
+}
+
+// Write the images rendered for the document name, numbering them (as the
+// given kind of image) if there are several.
+fn write_images<'a, I>(
+    sink: &mut dyn RenderSink,
+    name: &str,
+    (kind, extension): (&str, &str),
+    images: I,
+) -> Result<(), Error>
+where
+    I: ExactSizeIterator<Item = &'a [u8]>,
+{
+    let stem = name.strip_suffix(".pdf").unwrap_or(name);
+    let num_images = images.len();
+    for (idx, image) in images.enumerate() {
+        let name = match num_images {
+            1 => format!("{}.{}", stem, extension),
+            _ => format!("{}-{}{}.{}", stem, kind, idx + 1, extension),
+        };
+        sink.write_artifact(&name, image)?;
+    }
+    Ok(())
 }
 
 // TODO: Use azul-text-layout or some other text layout library to reduce the
@@ -79,6 +327,30 @@ mod colours {
         icc_profile: None,
     });
 
+    // #2c5f9f
+    pub(super) const AIRGAP_TRIM: Color = Color::Rgb(Rgb {
+        r: 0.17255,
+        g: 0.37255,
+        b: 0.62353,
+        icc_profile: None,
+    });
+
+    // #4d4d4d
+    pub(super) const MANIFEST_TRIM: Color = Color::Rgb(Rgb {
+        r: 0.3,
+        g: 0.3,
+        b: 0.3,
+        icc_profile: None,
+    });
+
+    // #ffb3b3
+    pub(super) const TEST_RUN_WATERMARK: Color = Color::Rgb(Rgb {
+        r: 1.0,
+        g: 0.7,
+        b: 0.7,
+        icc_profile: None,
+    });
+
     // #2c9f2c
     pub(super) const KEY_SHARD_TRIM: Color = Color::Rgb(Rgb {
         r: 0.17255,
@@ -88,15 +360,116 @@ mod colours {
     });
 }
 
+/// The visual style in which documents are rendered.
+///
+/// QR codes are always drawn with solid modules and at least the standard
+/// quiet zone in every style, so that they remain decodable.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RenderStyle {
+    /// Coloured trim with solid banners.
+    #[default]
+    Standard,
+    /// Minimise toner use. Banners and the test-run watermark are drawn as
+    /// outlines, and the light modules of QR codes are left unprinted.
+    InkSaver,
+    /// Maximise contrast for poor scanners and aged paper. Everything is
+    /// printed in black, and QR codes are surrounded by a wider quiet zone.
+    HighContrast,
+}
+
+// The colours and shape styling used to render documents in a RenderStyle.
+struct Palette {
+    black: Color,
+    grey: Color,
+    light_grey: Color,
+    white: Color,
+    main_document_trim: Color,
+    airgap_trim: Color,
+    manifest_trim: Color,
+    key_shard_trim: Color,
+    test_run_watermark: Color,
+    // Draw banners and the test-run watermark as outlines rather than filled
+    // shapes.
+    outline_shapes: bool,
+    // Fill the light modules (and quiet zone) of QR codes with white.
+    fill_qr_background: bool,
+    // Blank space left around each QR code (in addition to its own quiet
+    // zone), as a fraction of the space available for the code.
+    qr_inset: f64,
+}
+
+impl RenderStyle {
+    fn palette(self) -> Palette {
+        let standard = Palette {
+            black: colours::BLACK,
+            grey: colours::GREY,
+            light_grey: colours::LIGHT_GREY,
+            white: colours::WHITE,
+            main_document_trim: colours::MAIN_DOCUMENT_TRIM,
+            airgap_trim: colours::AIRGAP_TRIM,
+            manifest_trim: colours::MANIFEST_TRIM,
+            key_shard_trim: colours::KEY_SHARD_TRIM,
+            test_run_watermark: colours::TEST_RUN_WATERMARK,
+            outline_shapes: false,
+            fill_qr_background: true,
+            qr_inset: 0.0,
+        };
+        match self {
+            Self::Standard => standard,
+            Self::InkSaver => Palette {
+                outline_shapes: true,
+                fill_qr_background: false,
+                ..standard
+            },
+            Self::HighContrast => Palette {
+                grey: colours::BLACK,
+                light_grey: colours::BLACK,
+                main_document_trim: colours::BLACK,
+                airgap_trim: colours::BLACK,
+                manifest_trim: colours::BLACK,
+                key_shard_trim: colours::BLACK,
+                qr_inset: 0.04,
+                ..standard
+            },
+        }
+    }
+}
+
+impl Palette {
+    // Render a code as an SVG in this palette's style.
+    fn qr_svg(&self, code: &Code) -> String {
+        code.to_svg(self.fill_qr_background)
+    }
+
+    // Transform placing a QR code SVG of the given dimensions in the size x
+    // size square with its bottom-left corner at (x, y), inset by qr_inset.
+    fn qr_transform(&self, (x, y): (Mm, Mm), size: Mm, (width, height): (Px, Px)) -> SvgTransform {
+        let inset = size * self.qr_inset;
+        let inner_size = size - inset * 2.0;
+        SvgTransform {
+            translate_x: Some(x + inset),
+            translate_y: Some(y + inset),
+            dpi: Some(SVG_DPI),
+            scale_x: Some(inner_size / Mm::from(width.into_pt(SVG_DPI))),
+            scale_y: Some(inner_size / Mm::from(height.into_pt(SVG_DPI))),
+            ..Default::default()
+        }
+    }
+}
+
+// Height of a banner (excluding its margin).
+const BANNER_HEIGHT: Mm = Mm(9.0);
+
 struct Text<'a> {
     inner: &'a str,
     colour: Color,
-    font: &'a IndirectFontRef,
+    font: &'a Font,
     font_size: Pt,
 }
 
-fn banner(
-    layer: &PdfLayerReference,
+fn banner<C: Canvas>(
+    layer: &C,
+    palette: &Palette,
     mut top: Mm,
     (width, margin, banner_margin): (Mm, Mm, Mm),
     header: Text<'_>,
@@ -105,7 +478,6 @@ fn banner(
 ) -> Mm {
     //let header = header.inner.as_ref();
 
-    const BANNER_HEIGHT: Mm = Mm(9.0);
     top -= banner_margin;
 
     // Dashed line box where the QR code would go.
@@ -118,12 +490,20 @@ fn banner(
     let line = Line {
         points,
         is_closed: true,
-        has_fill: true,
-        has_stroke: false,
+        has_fill: !palette.outline_shapes,
+        has_stroke: palette.outline_shapes,
         is_clipping_path: false,
     };
 
-    layer.set_fill_color(banner_colour);
+    // Outlined banners have their text in the banner colour, since there is
+    // no solid background for it to contrast with.
+    let text_colour = |colour: Color| match palette.outline_shapes {
+        true => banner_colour.clone(),
+        false => colour,
+    };
+    layer.set_fill_color(banner_colour.clone());
+    layer.set_outline_color(banner_colour.clone());
+    layer.set_line_dash_pattern(LineDashPattern::default());
     layer.add_shape(line);
 
     // Add header text.
@@ -132,19 +512,33 @@ fn banner(
         layer.set_font(header.font, header.font_size.0);
         layer.set_line_height(header.font_size.0);
         layer.set_word_spacing(1.2);
-        layer.set_character_spacing(1.0);
+        layer.set_character_spacing(CHARACTER_SPACING);
         layer.set_text_rendering_mode(TextRenderingMode::Fill);
 
         layer.set_text_cursor(
             margin,
             top - (BANNER_HEIGHT + Pt(header.font_size.0).into()) / 2.0,
         );
-        layer.set_fill_color(header.colour);
+        layer.set_fill_color(text_colour(header.colour));
         layer.write_text(header.inner, header.font);
 
-        // Add description.
+        // Add description, shrunk to fit in the rest of the banner.
         if let Some(description) = description {
-            layer.set_font(description.font, description.font_size.0);
+            let available = width
+                - margin * 2.0
+                - header
+                    .font
+                    .text_width(header.inner, header.font_size.0, CHARACTER_SPACING)
+                - description
+                    .font
+                    .text_width("  ", description.font_size.0, CHARACTER_SPACING);
+            let font_size = description.font.fit_text(
+                description.inner,
+                description.font_size.0,
+                CHARACTER_SPACING,
+                available,
+            );
+            layer.set_font(description.font, font_size);
             layer.write_text("  ", description.font);
             layer.write_text(description.inner, description.font);
         }
@@ -154,50 +548,95 @@ fn banner(
     BANNER_HEIGHT + banner_margin
 }
 
-fn qr_with_fallback<D: AsRef<[u8]>>(
-    layer: &PdfLayerReference,
+// The left edge of the (right-aligned) title and format version in the top
+// right corner of a page.
+fn title_x(page: &Page, title: &str, options: &RenderOptions) -> Mm {
+    let title_width = Font::Text.text_width(title, 20.0, CHARACTER_SPACING);
+    let version_width =
+        Font::Monospace.text_width(options.symbology.header_label(), 10.0, CHARACTER_SPACING);
+    // Can't use std::cmp::max sadly.
+    let width = if title_width > version_width {
+        title_width
+    } else {
+        version_width
+    };
+    page.width - page.margin - width
+}
+
+// Draw a diagonal watermark across the page, marking it as a test run. This
+// should be drawn before anything else, so that it doesn't obscure the codes.
+fn test_run_watermark<C: Canvas>(
+    layer: &C,
+    options: &RenderOptions,
+    (width, height): (Mm, Mm),
+    font: &Font,
+) {
+    let palette = options.style.palette();
+    let watermark = options.language.message("test-run-watermark", &[]);
+    // Rough average glyph width (in ems) of the watermark text.
+    const GLYPH_WIDTH: f64 = 0.6;
+
+    let angle = height.0.atan2(width.0);
+    let diagonal = Mm((width.0.powi(2) + height.0.powi(2)).sqrt());
+    // Span most of the diagonal, centred on the page.
+    let text_width = diagonal * 0.8;
+    let font_size = Pt::from(text_width / (watermark.chars().count() as f64 * GLYPH_WIDTH));
+    let (x, y) = (
+        (width - text_width * angle.cos()) / 2.0,
+        (height - text_width * angle.sin()) / 2.0,
+    );
+
+    layer.begin_text_section();
+    {
+        layer.set_font(font, font_size.0);
+        if palette.outline_shapes {
+            layer.set_outline_color(palette.test_run_watermark.clone());
+            layer.set_text_rendering_mode(TextRenderingMode::Stroke);
+        } else {
+            layer.set_fill_color(palette.test_run_watermark.clone());
+        }
+        layer.set_text_matrix(TextMatrix::TranslateRotate(
+            x.into(),
+            y.into(),
+            angle.to_degrees(),
+        ));
+        layer.write_text(&watermark, font);
+        layer.set_text_rendering_mode(TextRenderingMode::Fill);
+    }
+    layer.end_text_section();
+}
+
+fn qr_with_fallback<C: Canvas, D: AsRef<[u8]>>(
+    layer: &C,
+    options: &RenderOptions,
     top: Mm,
-    (width, margin, qr_fraction): (Mm, Mm, f64),
-    data: D,
-    font: &IndirectFontRef,
+    (width, margin, qr_size): (Mm, Mm, Mm),
+    (data, encoding): (D, TextEncoding),
+    font: &Font,
     font_size: f64,
 ) -> Result<Mm, Error> {
     const DATA_MARGIN: Mm = Mm(3.0);
 
     let data = data.as_ref();
     // Can't use std::cmp::min sadly.
-    let qr_size = if top - margin < width * qr_fraction {
+    let qr_size = if top - margin < qr_size {
         top - margin
     } else {
-        width * qr_fraction
+        qr_size
     };
 
-    // TODO: Use azul-text-layout for this function so that we get line wrapping
-    // done for us, as well as being able to use the computed text dimensions to
-    // vertically center and horizontally right-adjust the fallback text.
-
-    let data_lines = multibase::encode(Base::Base32Z, data)
-        // Split the encoded version into 4-char words.
-        .into_bytes()
-        .chunks(4)
-        .map(String::from_utf8_lossy)
-        .collect::<Vec<_>>()
-        // Split the words into rows for printing.
-        .chunks(8)
-        // Join the words with "-". This is to work around the fact that
-        // printpdf appears to generate PDFs such that horizontally-written
-        // words get selected as if they were columns (breaking copy-and-paste
-        // for these data sections).
-        .map(|ws| ws.join("-"))
-        .map(|mut line| match line.len() {
-            39 /* 4*8+7 */ => line, // Line is the right length.
-            l @ 0..=38 => { // Line needs to be padded.
-                line.push_str(&"-".repeat(39-l));
-                line
-            },
-            _ => unreachable!(), // Not possible given how this string was constructed.
-        })
-        .collect::<Vec<String>>();
+    // The armored words are joined with "-". This is to work around the fact
+    // that printpdf appears to generate PDFs such that horizontally-written
+    // words get selected as if they were columns (breaking copy-and-paste for
+    // these data sections). Bech32m segments are a single word per line.
+    let data_lines = encoding.encode_lines(data);
+
+    // Shrink the text so that the longest line fits next to the code.
+    let (qr_x, data_x) = (margin, margin + qr_size + margin);
+    let font_size = data_lines
+        .iter()
+        .map(|line| font.fit_text(line, font_size, CHARACTER_SPACING, width - data_x - margin))
+        .fold(font_size, f64::min);
 
     let data_height: Mm = Pt(font_size + (font_size + 2.0) * data_lines.len() as f64).into();
     let padded_data_height = data_height + DATA_MARGIN * 2.0;
@@ -212,26 +651,13 @@ fn qr_with_fallback<D: AsRef<[u8]>>(
         total_height / 2.0 + qr_size / 2.0,
         total_height / 2.0 - data_height / 2.0 + Mm::from(Pt(font_size)),
     );
-    let (qr_x, data_x) = (margin, margin + qr_size + margin);
 
     // Display svg.
-    let qr_svg = Svg::parse(&qr::generate_one_code(data)?.render::<svg::Color>().build())?
-        .into_xobject(layer);
-    let (scale_x, scale_y) = (
-        qr_size / Mm::from(qr_svg.width.into_pt(SVG_DPI)),
-        qr_size / Mm::from(qr_svg.height.into_pt(SVG_DPI)),
-    );
-    qr_svg.add_to_layer(
-        layer,
-        SvgTransform {
-            translate_x: Some(qr_x),
-            translate_y: Some(top - qr_y),
-            dpi: Some(SVG_DPI),
-            scale_x: Some(scale_x),
-            scale_y: Some(scale_y),
-            ..Default::default()
-        },
-    );
+    let palette = options.style.palette();
+    let qr_svg = palette.qr_svg(&qr::generate_one_code(data, options)?);
+    layer.add_qr_code(&qr_svg, |dimensions| {
+        palette.qr_transform((qr_x, top - qr_y), qr_size, dimensions)
+    })?;
 
     // Display the fallback text.
     layer.begin_text_section();
@@ -239,11 +665,11 @@ fn qr_with_fallback<D: AsRef<[u8]>>(
         layer.set_font(font, font_size - 2.0);
         layer.set_line_height(font_size - 2.0 + 2.0);
         layer.set_word_spacing(1.2);
-        layer.set_character_spacing(1.0);
+        layer.set_character_spacing(CHARACTER_SPACING);
         layer.set_text_rendering_mode(TextRenderingMode::Fill);
 
         layer.set_text_cursor(data_x, top - data_y);
-        layer.set_fill_color(colours::LIGHT_GREY);
+        layer.set_fill_color(palette.light_grey.clone());
         layer.write_text("text fallback if barcode scanning fails", font);
     }
     layer.end_text_section();
@@ -252,16 +678,16 @@ fn qr_with_fallback<D: AsRef<[u8]>>(
         layer.set_font(font, font_size);
         layer.set_line_height(font_size + 2.0);
         layer.set_word_spacing(1.2);
-        layer.set_character_spacing(1.0);
+        layer.set_character_spacing(CHARACTER_SPACING);
         layer.set_text_rendering_mode(TextRenderingMode::Fill);
 
         layer.set_text_cursor(data_x, top - data_y);
         layer.add_line_break();
         for (i, line) in data_lines.iter().enumerate() {
             if i % 2 == 0 {
-                layer.set_fill_color(colours::BLACK);
+                layer.set_fill_color(palette.black.clone());
             } else {
-                layer.set_fill_color(colours::GREY);
+                layer.set_fill_color(palette.grey.clone());
             }
             layer.write_text(line, font);
             layer.add_line_break();
@@ -272,63 +698,310 @@ fn qr_with_fallback<D: AsRef<[u8]>>(
     Ok(total_height)
 }
 
-const A4_WIDTH: Mm = Mm(210.0);
-const A4_HEIGHT: Mm = Mm(297.0);
-const A4_MARGIN: Mm = Mm(5.0);
-const QR_MARGIN: Mm = Mm(5.0);
+// The page (and its margins) a document is laid out on.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(super) struct Page {
+    pub(super) width: Mm,
+    pub(super) height: Mm,
+    pub(super) margin: Mm,
+}
+
+impl Page {
+    pub(super) fn new(paper_size: PaperSize) -> Self {
+        let (width, height) = paper_size.dimensions();
+        Self {
+            width,
+            height,
+            margin: paper_size.margin(),
+        }
+    }
 
-const FONT_ROBOTOSLAB: &[u8] = include_bytes!("fonts/RobotoSlab-Regular.ttf");
-const FONT_B612MONO: &[u8] = include_bytes!("fonts/B612Mono-Regular.ttf");
-const FONT_B612MONO_BOLD: &[u8] = include_bytes!("fonts/B612Mono-Bold.ttf");
+    // The page for a document which is laid out for the default paper size,
+    // unless another paper size was requested.
+    pub(super) fn for_document(options: &RenderOptions, default: PaperSize) -> Result<Self, Error> {
+        let paper_size = options.paper_size.unwrap_or(default);
+        if !paper_size.is_supported() {
+            let (width, height) = paper_size.dimensions();
+            return Err(Error::OtherError(format!(
+                "paper size {}x{}mm is smaller than A6",
+                width.0, height.0
+            )));
+        }
+        Ok(Self::new(paper_size))
+    }
+
+    // The size of each code in a grid of data codes with the given number of
+    // rows starting at top, leaving room for the checksum section below it.
+    pub(super) fn data_code_size(&self, top: Mm, rows: usize) -> Mm {
+        let by_width = (self.width - self.margin * 2.0) / MAIN_DOCUMENT_CODES_PER_ROW;
+        let by_height =
+            (self.height - top - self.margin - CHECKSUM_SECTION_MIN_HEIGHT) / rows.max(1) as f64;
+        // Can't use std::cmp::min sadly.
+        if by_height < by_width {
+            by_height
+        } else {
+            by_width
+        }
+    }
+
+    // The size of the key shard data and checksum codes.
+    pub(super) fn key_shard_code_size(&self) -> Mm {
+        let by_width = self.width * KEY_SHARD_QR_FRACTION;
+        let by_height = (self.height - KEY_SHARD_FIXED_HEIGHT) / 2.0;
+        // Can't use std::cmp::min sadly.
+        if by_height < by_width {
+            by_height
+        } else {
+            by_width
+        }
+    }
+}
+
+// The character spacing (in points) of all text.
+const CHARACTER_SPACING: f64 = 1.0;
+
+const QR_MARGIN: Mm = Mm(5.0);
+// Height reserved below the data codes for the checksum banner and (at least)
+// a small checksum code.
+const CHECKSUM_SECTION_MIN_HEIGHT: Mm = Mm(34.0);
+
+/// The number of data codes printed on each page of a main document (in a
+/// 3x3 grid). Code `idx` (see [`Joiner::missing`](super::qr::Joiner::missing))
+/// is printed on page `idx / MAIN_DOCUMENT_CODES_PER_PAGE`.
+pub const MAIN_DOCUMENT_CODES_PER_PAGE: usize = 9;
+pub(super) const MAIN_DOCUMENT_CODES_PER_ROW: f64 = 3.0;
+// Fraction of the page width used by the main document checksum code.
+pub(super) const MAIN_DOCUMENT_CHECKSUM_QR_FRACTION: f64 = 0.18;
+// Typical distance from the top of the main document to its data codes (used
+// when previewing the layout, where the header text isn't laid out).
+pub(super) const MAIN_DOCUMENT_DATA_TOP: Mm = Mm(48.0);
+// Warn once the main document is more than 80% full.
+const MAIN_DOCUMENT_WARN_PERCENT: usize = 80;
 
 impl ToPdf for MainDocument {
-    fn to_pdf(&self) -> Result<PdfDocumentReference, Error> {
-        // Generate QR codes to embed in the PDF.
-        let (data_qrs, data_qr_datas) =
-            qr::generate_codes(PartType::MainDocumentData, self.to_wire())?;
-        let data_qrs = data_qrs
+    fn warnings(&self, options: &RenderOptions) -> Warnings {
+        let used = self.to_wire().len();
+        // Invalid versions are reported when rendering.
+        let code_capacity = qr::max_data_length(options).unwrap_or(qr::MAX_DATA_LENGTH);
+        // Main documents which don't fit on one page continue on another
+        // page, so warn when the last page is close to full.
+        let pages = (used.div_ceil(code_capacity) + options.parity_codes)
+            .div_ceil(MAIN_DOCUMENT_CODES_PER_PAGE)
+            .max(1);
+        let capacity = (pages * MAIN_DOCUMENT_CODES_PER_PAGE).saturating_sub(options.parity_codes)
+            * code_capacity;
+        let mut warnings = Warnings::new();
+        if used * 100 > capacity * MAIN_DOCUMENT_WARN_PERCENT {
+            warnings.push(Warning::NearCapacity {
+                what: "main document",
+                used,
+                capacity,
+            });
+        }
+        warnings
+    }
+
+    fn to_pdf_with_options(&self, options: &RenderOptions) -> Result<PdfDocumentReference, Error> {
+        self.draw::<PdfCanvas>(options)
+    }
+
+    fn to_svg_with_options(&self, options: &RenderOptions) -> Result<Vec<String>, Error> {
+        self.draw::<SvgCanvas>(options)
+    }
+
+    fn qr_codes_to_png_with_options(
+        &self,
+        options: &RenderOptions,
+        dpi: u32,
+    ) -> Result<Vec<Vec<u8>>, Error> {
+        raster::codes_to_png(self.draw::<CodesCanvas>(options)?, dpi)
+    }
+}
+
+impl MainDocument {
+    /// The text encoded in each of the data QR codes printed on the main
+    /// document, in the order they are printed.
+    pub fn qr_code_data(&self, options: &RenderOptions) -> Result<Vec<String>, Error> {
+        let (_, datas) = qr::generate_codes(PartType::MainDocumentData, self.to_wire(), options)?;
+        Ok(datas
+            .iter()
+            .map(|data| multibase::encode(QRCODE_MULTIBASE, data))
+            .collect())
+    }
+
+    /// The layout of each of the data QR codes printed on the main document,
+    /// in the same order as [`MainDocument::qr_code_data`]. Unlike
+    /// [`Renderer::preview`](super::Renderer::preview), the sizes are those
+    /// of the codes as they are actually drawn on the page.
+    pub fn qr_code_layouts(&self, options: &RenderOptions) -> Result<Vec<CodeLayout>, Error> {
+        let palette = options.style.palette();
+        let printed = self.draw::<CodesCanvas>(options)?;
+        let (codes, parts) =
+            qr::generate_codes(PartType::MainDocumentData, self.to_wire(), options)?;
+        codes
             .iter()
-            .map(|code| code.render::<svg::Color>().build())
-            .map(|svg| Svg::parse(&svg))
-            .collect::<Result<Vec<_>, _>>()?;
+            .zip(&parts)
+            .map(|(code, part)| {
+                let svg = palette.qr_svg(code);
+                let (_, size) = printed
+                    .iter()
+                    .find(|(printed, _)| *printed == svg)
+                    .ok_or_else(|| {
+                        Error::OtherError("data code missing from rendered main document".into())
+                    })?;
+                Ok(CodeLayout::new(code, *size, part.len()))
+            })
+            .collect()
+    }
+
+    fn draw<C: Canvas>(&self, options: &RenderOptions) -> Result<C::Output, Error> {
+        let palette = options.style.palette();
 
-        // Construct an A4 PDF.
-        let (doc, page1, layer1) = PdfDocument::new(
+        // Generate QR codes to embed in the pages.
+        let (data_qrs, _) =
+            qr::generate_codes(PartType::MainDocumentData, self.to_wire(), options)?;
+        let data_qrs = data_qrs
+            .iter()
+            .map(|code| palette.qr_svg(code))
+            .collect::<Vec<_>>();
+
+        // Construct the pages (A4 by default). Documents with more codes than
+        // fit on one page continue on further pages -- every code records its
+        // index, so the codes can be scanned in any order.
+        let page = Page::for_document(options, PaperSize::A4)?;
+        let mut current_layer = C::new(
             format!("Paperback Main Document {}", self.id()),
-            A4_WIDTH,
-            A4_HEIGHT,
-            "Layer 1",
-        );
+            (page.width, page.height),
+            &[Font::Monospace, Font::Text],
+        )?;
+        let num_pages = data_qrs.len().div_ceil(MAIN_DOCUMENT_CODES_PER_PAGE).max(1);
+        let progress = Tracker::start(Stage::Rendering, num_pages);
+        for (page_idx, page_qrs) in data_qrs.chunks(MAIN_DOCUMENT_CODES_PER_PAGE).enumerate() {
+            if page_idx > 0 {
+                current_layer.add_page();
+            }
+            self.draw_page(
+                current_layer,
+                options,
+                &page,
+                (page_idx, num_pages),
+                (page_idx * MAIN_DOCUMENT_CODES_PER_PAGE, data_qrs.len()),
+                page_qrs,
+            )?;
+            progress.step();
+        }
 
-        let monospace_font = doc.add_external_font(FONT_B612MONO)?;
-        let text_font = doc.add_external_font(FONT_ROBOTOSLAB)?;
+        current_layer.finish()
+    }
 
-        let current_page = doc.get_page(page1);
-        let current_layer = current_page.get_layer(layer1);
+    // Draw one page of the main document, containing data_qrs (the codes
+    // starting at index first_code of the num_codes codes).
+    fn draw_page<C: Canvas>(
+        &self,
+        current_layer: &C,
+        options: &RenderOptions,
+        page: &Page,
+        (page_idx, num_pages): (usize, usize),
+        (first_code, num_codes): (usize, usize),
+        data_qrs: &[String],
+    ) -> Result<(), Error> {
+        let palette = options.style.palette();
+        let (monospace_font, text_font) = (Font::Monospace, Font::Text);
+
+        if self.is_test_run() {
+            test_run_watermark(
+                current_layer,
+                options,
+                (page.width, page.height),
+                &text_font,
+            );
+        }
 
-        let mut current_y = A4_MARGIN + Pt(10.0).into();
+        let mut current_y = page.margin + Pt(10.0).into();
+
+        let language = options.language;
+        let dates_line = match (self.created_at(), self.review_by()) {
+            (Some(created_at), Some(review_by)) => Some(language.message(
+                "created-review-by",
+                &[
+                    ("created", &constraints::format_datetime(created_at)),
+                    ("review", &constraints::format_date(review_by)),
+                ],
+            )),
+            (Some(created_at), None) => Some(language.message(
+                "created",
+                &[("created", &constraints::format_datetime(created_at))],
+            )),
+            (None, Some(review_by)) => Some(language.message(
+                "review-by",
+                &[("review", &constraints::format_date(review_by))],
+            )),
+            (None, None) => None,
+        };
+
+        let revoked_ids = self.revoked_identity_ids();
+        let identity_line = match revoked_ids.len() {
+            0 => None,
+            _ => Some(language.message(
+                "identity-revoked",
+                &[
+                    ("identity", &self.identity_id()),
+                    ("revoked", &revoked_ids.join(", ")),
+                ],
+            )),
+        };
+
+        // Wrap the details to the width of the page.
+        let text_width = page.width - page.margin * 2.0;
+        let description = text_font.wrap_text(
+            &language.message(
+                "main-document-description",
+                &[("quorum", &self.quorum_size())],
+            ),
+            10.0,
+            CHARACTER_SPACING,
+            text_width,
+        );
+        let page_line = match num_pages {
+            1 => None,
+            _ => Some(language.message(
+                "main-document-page",
+                &[
+                    ("page", &(page_idx + 1)),
+                    ("pages", &num_pages),
+                    ("first", &(first_code + 1)),
+                    ("last", &(first_code + data_qrs.len())),
+                    ("total", &num_codes),
+                ],
+            )),
+        };
+        let detail_lines = [dates_line, identity_line, page_line]
+            .iter()
+            .flatten()
+            .flat_map(|line| text_font.wrap_text(line, 8.0, CHARACTER_SPACING, text_width))
+            .collect::<Vec<_>>();
 
         // Header.
         current_layer.begin_text_section();
         {
             current_layer.set_font(&monospace_font, 10.0);
             current_layer.set_word_spacing(1.2);
-            current_layer.set_character_spacing(1.0);
+            current_layer.set_character_spacing(CHARACTER_SPACING);
 
-            current_layer.set_text_cursor(A4_MARGIN, A4_HEIGHT - current_y);
+            current_layer.set_text_cursor(page.margin, page.height - current_y);
 
             // "Document".
             current_layer.set_font(&text_font, 10.0);
-            current_layer.set_fill_color(colours::GREY);
-            current_layer.write_text("Document", &text_font);
-            current_layer.set_fill_color(colours::BLACK);
+            current_layer.set_fill_color(palette.grey.clone());
+            current_layer.write_text(language.message("document", &[]), &text_font);
+            current_layer.set_fill_color(palette.black.clone());
             current_layer.set_line_height(20.0 + 2.0);
             current_layer.add_line_break();
             // <document id>
             current_layer.set_font(&monospace_font, 20.0);
-            current_layer.set_fill_color(colours::MAIN_DOCUMENT_TRIM);
+            current_layer.set_fill_color(palette.main_document_trim.clone());
             current_layer.write_text(self.id(), &monospace_font);
-            current_layer.set_fill_color(colours::BLACK);
+            current_layer.set_fill_color(palette.black.clone());
             current_layer.set_line_height(10.0 + 2.0);
 
             current_layer.add_line_break();
@@ -337,93 +1010,94 @@ impl ToPdf for MainDocument {
             // Details.
             current_layer.set_font(&text_font, 10.0);
             current_layer.set_line_height(10.0 + 2.0);
-            current_layer.write_text(
-                format!(
-                    "This is the main document of a paperback backup. When combined with {} unique",
-                    self.quorum_size()
-                ),
-                &text_font,
-            );
-            current_layer.add_line_break();
-            current_layer.write_text(
-                "key shards, this document can be recovered. In order to recover this document,",
-                &text_font,
-            );
-            current_layer.add_line_break();
-            current_layer.write_text(
-                "download the latest version of paperback from cyphar.com/paperback.",
-                &text_font,
-            );
+            for (idx, line) in description.iter().enumerate() {
+                if idx > 0 {
+                    current_layer.add_line_break();
+                }
+                current_layer.write_text(line, &text_font);
+            }
+
+            // Creation and review dates, identity rotation and page number.
+            current_layer.set_font(&text_font, 8.0);
+            for line in &detail_lines {
+                current_layer.add_line_break();
+                current_layer.write_text(line, &text_font);
+            }
         }
         current_layer.end_text_section();
         current_layer.begin_text_section();
         {
-            // Header. TODO: Right-align this text.
+            // Header.
+            let title = language.message("main-document", &[]);
             current_layer.set_text_cursor(
-                A4_WIDTH - (A4_MARGIN + (Pt(15.0) * 12.0).into()),
-                A4_HEIGHT - (current_y + Pt(10.0).into()),
+                title_x(page, &title, options),
+                page.height - (current_y + Pt(10.0).into()),
             );
             current_layer.set_font(&text_font, 20.0);
-            current_layer.set_fill_color(colours::MAIN_DOCUMENT_TRIM);
-            current_layer.write_text("Main Document", &text_font);
-            current_layer.set_fill_color(colours::BLACK);
+            current_layer.set_fill_color(palette.main_document_trim.clone());
+            current_layer.write_text(title, &text_font);
+            current_layer.set_fill_color(palette.black.clone());
             current_layer.set_line_height(10.0 + 2.0);
             current_layer.add_line_break();
 
             current_layer.set_font(&monospace_font, 10.0);
-            current_layer.set_fill_color(colours::GREY);
-            current_layer.write_text("paperback-v0", &monospace_font);
-            current_layer.set_fill_color(colours::BLACK);
+            current_layer.set_fill_color(palette.grey.clone());
+            current_layer.write_text(options.symbology.header_label(), &monospace_font);
+            current_layer.set_fill_color(palette.black.clone());
             current_layer.set_line_height(10.0 + 2.0);
         }
         current_layer.end_text_section();
-        current_y += (Pt(22.0) + Pt(12.0) * 4.0).into();
-
+        current_y +=
+            (Pt(22.0) + Pt(12.0) * (1 + description.len() + detail_lines.len()) as f64).into();
+
+        let data_description = match options.parity_codes {
+            0 => language.message("data-description", &[]),
+            parity_codes => language.message(
+                "data-description-parity",
+                &[
+                    ("needed", &(num_codes - parity_codes)),
+                    ("total", &num_codes),
+                ],
+            ),
+        };
+        let (document_heading, checksum_heading) = (
+            format!("① {}", language.message("document", &[])),
+            format!("② {}", language.message("checksum", &[])),
+        );
         current_y += banner(
-            &current_layer,
-            A4_HEIGHT - current_y,
-            (A4_WIDTH, A4_MARGIN, Mm(3.0)),
+            current_layer,
+            &palette,
+            page.height - current_y,
+            (page.width, page.margin, Mm(3.0)),
             Text {
-                inner: "① Document",
-                colour: colours::WHITE,
+                inner: &document_heading,
+                colour: palette.white.clone(),
                 font: &text_font,
                 font_size: Pt(10.0),
             },
             Some(Text {
-                inner: "Data section, encrypted with secret key stored in the key shards.",
-                colour: colours::WHITE,
+                inner: &data_description,
+                colour: palette.white.clone(),
                 font: &text_font,
                 font_size: Pt(8.0),
             }),
-            colours::MAIN_DOCUMENT_TRIM,
+            palette.main_document_trim.clone(),
         ) + Mm(2.0);
 
-        // TODO: Get rid of this once we have nice QR code scanning.
-        println!("Main Document:");
-        data_qr_datas
-            .iter()
-            .for_each(|code| println!("{}", multibase::encode(multibase::Base::Base10, code)));
-
-        let mut current_x = A4_MARGIN;
-        let mut data_qr_refs = data_qrs
-            .into_iter()
-            .map(|code| code.into_xobject(&current_layer));
-        for _ in 0..9 {
-            let target_size = (A4_WIDTH - A4_MARGIN * 2.0) / 3.0;
+        let rows = MAIN_DOCUMENT_CODES_PER_PAGE / MAIN_DOCUMENT_CODES_PER_ROW as usize;
+        let target_size = page.data_code_size(current_y, rows);
+        let mut current_x = page.margin;
+        let mut data_qr_refs = data_qrs.iter();
+        for idx in 0..MAIN_DOCUMENT_CODES_PER_PAGE {
             match data_qr_refs.next() {
                 Some(svg) => {
-                    let (width, height) = (svg.width, svg.height);
-                    svg.add_to_layer(
-                        &current_layer,
-                        SvgTransform {
-                            translate_x: Some(current_x),
-                            translate_y: Some(A4_HEIGHT - (current_y + target_size)),
-                            dpi: Some(SVG_DPI),
-                            scale_x: Some(target_size / Mm::from(width.into_pt(SVG_DPI))),
-                            scale_y: Some(target_size / Mm::from(height.into_pt(SVG_DPI))),
-                            ..Default::default()
-                        },
-                    );
+                    current_layer.add_qr_code(svg, |dimensions| {
+                        palette.qr_transform(
+                            (current_x, page.height - (current_y + target_size)),
+                            target_size,
+                            dimensions,
+                        )
+                    })?;
                 }
                 None => {
                     // Dashed line box where the QR code would go.
@@ -431,28 +1105,28 @@ impl ToPdf for MainDocument {
                         (
                             Point::new(
                                 current_x + QR_MARGIN / 2.0,
-                                A4_HEIGHT - (current_y + QR_MARGIN / 2.0),
+                                page.height - (current_y + QR_MARGIN / 2.0),
                             ),
                             false,
                         ),
                         (
                             Point::new(
                                 current_x + target_size - QR_MARGIN / 2.0,
-                                A4_HEIGHT - (current_y + QR_MARGIN / 2.0),
+                                page.height - (current_y + QR_MARGIN / 2.0),
                             ),
                             false,
                         ),
                         (
                             Point::new(
                                 current_x + target_size - QR_MARGIN / 2.0,
-                                A4_HEIGHT - (current_y + target_size - QR_MARGIN / 2.0),
+                                page.height - (current_y + target_size - QR_MARGIN / 2.0),
                             ),
                             false,
                         ),
                         (
                             Point::new(
                                 current_x + QR_MARGIN / 2.0,
-                                A4_HEIGHT - (current_y + target_size - QR_MARGIN / 2.0),
+                                page.height - (current_y + target_size - QR_MARGIN / 2.0),
                             ),
                             false,
                         ),
@@ -470,370 +1144,1530 @@ impl ToPdf for MainDocument {
                     dash_pattern.dash_1 = Some(6);
                     dash_pattern.gap_1 = Some(4);
 
-                    current_layer.set_outline_color(colours::LIGHT_GREY);
+                    current_layer.set_outline_color(palette.light_grey.clone());
                     current_layer.set_line_dash_pattern(dash_pattern);
                     current_layer.add_shape(line);
                 }
             };
             current_x += target_size;
-            if current_x + target_size > A4_WIDTH {
-                current_x = A4_MARGIN;
+            if (idx + 1) % MAIN_DOCUMENT_CODES_PER_ROW as usize == 0 {
+                current_x = page.margin;
                 current_y += target_size;
             }
         }
-        if data_qr_refs.next().is_some() {
-            return Err(Error::TooManyCodes(
-                "only 9 codes allowed in this version of paperback".to_string(),
-            ));
-        }
-
         current_y += banner(
-            &current_layer,
-            A4_HEIGHT - current_y,
-            (A4_WIDTH, A4_MARGIN, Mm(3.0)),
+            current_layer,
+            &palette,
+            page.height - current_y,
+            (page.width, page.margin, Mm(3.0)),
             Text {
-                inner: "② Checksum",
-                colour: colours::WHITE,
+                inner: &checksum_heading,
+                colour: palette.white.clone(),
                 font: &text_font,
                 font_size: Pt(10.0),
             },
             Some(Text {
-                inner: "Verifies the document was scanned correctly. The last 8 characters are the document identifier.",
-                colour: colours::WHITE,
+                inner: &language.message("document-checksum-description", &[]),
+                colour: palette.white.clone(),
                 font: &text_font,
                 font_size: Pt(8.0),
             }),
-            colours::MAIN_DOCUMENT_TRIM,
+            palette.main_document_trim.clone(),
         ) + Mm(2.0);
 
         // Document checksum.
         current_y += qr_with_fallback(
-            &current_layer,
-            A4_HEIGHT - current_y,
-            (A4_WIDTH, A4_MARGIN, 0.18),
-            self.checksum().to_bytes(),
+            current_layer,
+            options,
+            page.height - current_y,
+            (
+                page.width,
+                page.margin,
+                page.width * MAIN_DOCUMENT_CHECKSUM_QR_FRACTION,
+            ),
+            (self.checksum().to_bytes(), self.text_encoding()),
             &monospace_font,
             10.0,
         )?;
 
-        doc.check_for_errors()?;
-        Ok(doc)
+        Ok(())
     }
 }
 
-const A5_WIDTH: Mm = Mm(148.0);
-const A5_HEIGHT: Mm = Mm(210.0);
-const A5_MARGIN: Mm = Mm(5.0);
+// Details about an air-gap transfer document.
+struct AirGapDetails<'a> {
+    kind: &'a str,
+    id: String,
+    description: String,
+    data: Vec<u8>,
+    checksum: Vec<u8>,
+}
 
-const SCISSORS_SVG: &str = include_str!("scissors.svg");
+fn airgap_page<C: Canvas>(
+    details: AirGapDetails<'_>,
+    options: &RenderOptions,
+) -> Result<C::Output, Error> {
+    let palette = options.style.palette();
+    let (data_qrs, _) = qr::generate_codes(PartType::AirGapData, &details.data, options)?;
+    if data_qrs.len() > MAIN_DOCUMENT_CODES_PER_PAGE {
+        return Err(Error::TooManyCodes(format!(
+            "only {} codes allowed in an air-gap {}",
+            MAIN_DOCUMENT_CODES_PER_PAGE,
+            details.kind.to_lowercase()
+        )));
+    }
 
-impl ToPdf for (&EncryptedKeyShard, &KeyShardCodewords) {
-    fn to_pdf(&self) -> Result<PdfDocumentReference, Error> {
-        let (shard, codewords) = self;
-        // TODO: Make this nicer. It's quite ugly we need to decrypt the shard
-        // here just to get the document and shard ids. If we cached them that
-        // would work, but if you just read the shard data from the user you
-        // wouldn't have this information without decrypting it.
-        let decrypted_shard = shard
-            .decrypt(codewords)
-            .map_err(|err| Error::OtherError(format!("failed to decrypt shard: {:?}", err)))?;
-
-        // Construct an A5 PDF.
-        let (doc, page1, layer1) = PdfDocument::new(
-            format!(
-                "Paperback Key Shard {}/{}",
-                decrypted_shard.document_id(),
-                decrypted_shard.id()
-            ),
-            A5_WIDTH,
-            A5_HEIGHT,
-            "Layer 1",
+    // Construct a page (A4 by default).
+    let page = Page::for_document(options, PaperSize::A4)?;
+    let (monospace_font, text_font) = (Font::Monospace, Font::Text);
+    let current_layer = C::new(
+        format!("Paperback Air-Gap {} {}", details.kind, details.id),
+        (page.width, page.height),
+        &[monospace_font, text_font],
+    )?;
+    let description = text_font.wrap_text(
+        &details.description,
+        10.0,
+        CHARACTER_SPACING,
+        page.width - page.margin * 2.0,
+    );
+
+    let mut current_y = page.margin + Pt(10.0).into();
+
+    // Header.
+    current_layer.begin_text_section();
+    {
+        current_layer.set_word_spacing(1.2);
+        current_layer.set_character_spacing(CHARACTER_SPACING);
+        current_layer.set_text_cursor(page.margin, page.height - current_y);
+
+        // "Request" or "Response".
+        current_layer.set_font(&text_font, 10.0);
+        current_layer.set_fill_color(palette.grey.clone());
+        current_layer.write_text(details.kind, &text_font);
+        current_layer.set_line_height(20.0 + 2.0);
+        current_layer.add_line_break();
+        // <id>
+        current_layer.set_font(&monospace_font, 20.0);
+        current_layer.set_fill_color(palette.airgap_trim.clone());
+        current_layer.write_text(&details.id, &monospace_font);
+        current_layer.set_fill_color(palette.black.clone());
+        current_layer.set_line_height(10.0 + 2.0);
+
+        current_layer.add_line_break();
+        current_layer.add_line_break();
+
+        // Details.
+        current_layer.set_font(&text_font, 10.0);
+        for line in &description {
+            current_layer.write_text(line, &text_font);
+            current_layer.add_line_break();
+        }
+    }
+    current_layer.end_text_section();
+    current_layer.begin_text_section();
+    {
+        // Header.
+        let title = format!("Air-Gap {}", details.kind);
+        current_layer.set_text_cursor(
+            title_x(&page, &title, options),
+            page.height - (current_y + Pt(10.0).into()),
         );
+        current_layer.set_font(&text_font, 20.0);
+        current_layer.set_fill_color(palette.airgap_trim.clone());
+        current_layer.write_text(title, &text_font);
+        current_layer.set_fill_color(palette.black.clone());
+        current_layer.set_line_height(10.0 + 2.0);
+        current_layer.add_line_break();
+
+        current_layer.set_font(&monospace_font, 10.0);
+        current_layer.set_fill_color(palette.grey.clone());
+        current_layer.write_text(options.symbology.header_label(), &monospace_font);
+        current_layer.set_fill_color(palette.black.clone());
+    }
+    current_layer.end_text_section();
+    current_y += (Pt(22.0) + Pt(12.0) * (2 + description.len()) as f64).into();
+
+    current_y += banner(
+        &current_layer,
+        &palette,
+        page.height - current_y,
+        (page.width, page.margin, Mm(3.0)),
+        Text {
+            inner: "① Data",
+            colour: palette.white.clone(),
+            font: &text_font,
+            font_size: Pt(10.0),
+        },
+        Some(Text {
+            inner: "Scan every code on the other side of the air gap.",
+            colour: palette.white.clone(),
+            font: &text_font,
+            font_size: Pt(8.0),
+        }),
+        palette.airgap_trim.clone(),
+    ) + Mm(2.0);
+
+    let per_row = MAIN_DOCUMENT_CODES_PER_ROW as usize;
+    let target_size = page.data_code_size(current_y, data_qrs.len().div_ceil(per_row));
+    let mut current_x = page.margin;
+    for (idx, code) in data_qrs.iter().enumerate() {
+        current_layer.add_qr_code(&palette.qr_svg(code), |dimensions| {
+            palette.qr_transform(
+                (current_x, page.height - (current_y + target_size)),
+                target_size,
+                dimensions,
+            )
+        })?;
+        current_x += target_size;
+        if (idx + 1) % per_row == 0 || idx + 1 == data_qrs.len() {
+            current_x = page.margin;
+            current_y += target_size;
+        }
+    }
+
+    current_y += banner(
+        &current_layer,
+        &palette,
+        page.height - current_y,
+        (page.width, page.margin, Mm(3.0)),
+        Text {
+            inner: "② Checksum",
+            colour: palette.white.clone(),
+            font: &text_font,
+            font_size: Pt(10.0),
+        },
+        Some(Text {
+            inner: "Verifies the data was scanned correctly.",
+            colour: palette.white.clone(),
+            font: &text_font,
+            font_size: Pt(8.0),
+        }),
+        palette.airgap_trim.clone(),
+    ) + Mm(2.0);
+
+    qr_with_fallback(
+        &current_layer,
+        options,
+        page.height - current_y,
+        (
+            page.width,
+            page.margin,
+            page.width * MAIN_DOCUMENT_CHECKSUM_QR_FRACTION,
+        ),
+        (&details.checksum, TextEncoding::default()),
+        &monospace_font,
+        10.0,
+    )?;
+
+    current_layer.finish()
+}
+
+impl AirGapRequest {
+    fn airgap_details(&self) -> AirGapDetails<'static> {
+        AirGapDetails {
+            kind: "Request",
+            id: self.id(),
+            description: format!(
+                "This is a paperback '{}' request. Scan it on the offline machine holding the \
+                 key shards, and then scan the response it produces on this machine.",
+                self.operation()
+            ),
+            data: self.to_wire(),
+            checksum: self.checksum().to_bytes(),
+        }
+    }
+}
+
+impl ToPdf for AirGapRequest {
+    fn to_pdf_with_options(&self, options: &RenderOptions) -> Result<PdfDocumentReference, Error> {
+        airgap_page::<PdfCanvas>(self.airgap_details(), options)
+    }
+
+    fn to_svg_with_options(&self, options: &RenderOptions) -> Result<Vec<String>, Error> {
+        airgap_page::<SvgCanvas>(self.airgap_details(), options)
+    }
+
+    fn qr_codes_to_png_with_options(
+        &self,
+        options: &RenderOptions,
+        dpi: u32,
+    ) -> Result<Vec<Vec<u8>>, Error> {
+        let codes = airgap_page::<CodesCanvas>(self.airgap_details(), options)?;
+        raster::codes_to_png(codes, dpi)
+    }
+}
 
-        let monospace_font = doc.add_external_font(FONT_B612MONO)?;
-        let monospace_bold_font = doc.add_external_font(FONT_B612MONO_BOLD)?;
-        let text_font = doc.add_external_font(FONT_ROBOTOSLAB)?;
+impl AirGapResponse {
+    fn airgap_details(&self) -> AirGapDetails<'static> {
+        AirGapDetails {
+            kind: "Response",
+            id: self.request_id(),
+            description: format!(
+                "This is the response to paperback '{}' request {}. Scan it on the online \
+                 machine which created the request.",
+                self.operation(),
+                self.request_id()
+            ),
+            data: self.to_wire(),
+            checksum: self.checksum().to_bytes(),
+        }
+    }
+}
 
-        let current_page = doc.get_page(page1);
-        let current_layer = current_page.get_layer(layer1);
+impl ToPdf for AirGapResponse {
+    fn to_pdf_with_options(&self, options: &RenderOptions) -> Result<PdfDocumentReference, Error> {
+        airgap_page::<PdfCanvas>(self.airgap_details(), options)
+    }
 
-        let mut current_y = A5_MARGIN + Pt(10.0).into();
+    fn to_svg_with_options(&self, options: &RenderOptions) -> Result<Vec<String>, Error> {
+        airgap_page::<SvgCanvas>(self.airgap_details(), options)
+    }
+
+    fn qr_codes_to_png_with_options(
+        &self,
+        options: &RenderOptions,
+        dpi: u32,
+    ) -> Result<Vec<Vec<u8>>, Error> {
+        let codes = airgap_page::<CodesCanvas>(self.airgap_details(), options)?;
+        raster::codes_to_png(codes, dpi)
+    }
+}
+
+// Maximum number of artifact checksum lines on the manifest page (fewer are
+// printed if they don't fit on the page).
+const MANIFEST_MAX_LINES: usize = 40;
+// Font size and line height of the artifact checksum lines.
+const MANIFEST_FONT_SIZE: f64 = 8.0;
+const MANIFEST_LINE_HEIGHT: Pt = Pt(MANIFEST_FONT_SIZE + 3.0);
+
+impl ToPdf for Manifest {
+    fn to_pdf_with_options(&self, options: &RenderOptions) -> Result<PdfDocumentReference, Error> {
+        self.draw::<PdfCanvas>(options)
+    }
+
+    fn to_svg_with_options(&self, options: &RenderOptions) -> Result<Vec<String>, Error> {
+        self.draw::<SvgCanvas>(options)
+    }
+
+    fn qr_codes_to_png_with_options(
+        &self,
+        options: &RenderOptions,
+        dpi: u32,
+    ) -> Result<Vec<Vec<u8>>, Error> {
+        raster::codes_to_png(self.draw::<CodesCanvas>(options)?, dpi)
+    }
+}
+
+impl Manifest {
+    fn draw<C: Canvas>(&self, options: &RenderOptions) -> Result<C::Output, Error> {
+        let palette = options.style.palette();
+
+        // Construct a page (A4 by default).
+        let page = Page::for_document(options, PaperSize::A4)?;
+        let (monospace_font, text_font) = (Font::Monospace, Font::Text);
+        let current_layer = C::new(
+            format!("Paperback Manifest {}", self.document_id()),
+            (page.width, page.height),
+            &[monospace_font, text_font],
+        )?;
+        let description = text_font.wrap_text(
+            "This is the manifest of a paperback backup, listing the checksums of the main \
+             document and every key shard created with it. It contains no secret data.",
+            10.0,
+            CHARACTER_SPACING,
+            page.width - page.margin * 2.0,
+        );
+
+        let mut current_y = page.margin + Pt(10.0).into();
 
         // Header.
         current_layer.begin_text_section();
         {
             current_layer.set_word_spacing(1.2);
-            current_layer.set_character_spacing(1.0);
-
-            current_layer.set_text_cursor(A5_MARGIN, A5_HEIGHT - current_y);
-
-            // "Shard".
-            current_layer.set_font(&text_font, 10.0);
-            current_layer.set_fill_color(colours::GREY);
-            current_layer.write_text("Shard", &text_font);
-            current_layer.set_fill_color(colours::BLACK);
-            current_layer.set_line_height(20.0 + 2.0);
-            current_layer.add_line_break();
-            // <shard id>
-            current_layer.set_font(&monospace_font, 20.0);
-            current_layer.set_fill_color(colours::KEY_SHARD_TRIM);
-            current_layer.write_text(decrypted_shard.id(), &monospace_font);
-            current_layer.set_fill_color(colours::BLACK);
-            current_layer.set_line_height(14.0 + 2.0);
-            current_layer.add_line_break();
+            current_layer.set_character_spacing(CHARACTER_SPACING);
+            current_layer.set_text_cursor(page.margin, page.height - current_y);
 
             // "Document".
             current_layer.set_font(&text_font, 10.0);
-            current_layer.set_fill_color(colours::GREY);
+            current_layer.set_fill_color(palette.grey.clone());
             current_layer.write_text("Document", &text_font);
-            current_layer.set_fill_color(colours::BLACK);
             current_layer.set_line_height(20.0 + 2.0);
             current_layer.add_line_break();
             // <document id>
             current_layer.set_font(&monospace_font, 20.0);
-            current_layer.set_fill_color(colours::MAIN_DOCUMENT_TRIM);
-            current_layer.write_text(decrypted_shard.document_id(), &monospace_font);
-            current_layer.set_fill_color(colours::BLACK);
-        }
-        current_layer.end_text_section();
-        current_layer.begin_text_section();
-        {
-            // Header. TODO: Right-align this text.
-            current_layer.set_text_cursor(
-                A5_WIDTH - (A5_MARGIN + (Pt(15.0) * 8.0).into()),
-                A5_HEIGHT - (current_y + Pt(10.0).into()),
-            );
-            current_layer.set_font(&text_font, 20.0);
-            current_layer.set_fill_color(colours::KEY_SHARD_TRIM);
-            current_layer.write_text("Key Shard", &text_font);
-            current_layer.set_fill_color(colours::BLACK);
+            current_layer.set_fill_color(palette.main_document_trim.clone());
+            current_layer.write_text(self.document_id(), &monospace_font);
+            current_layer.set_fill_color(palette.black.clone());
             current_layer.set_line_height(10.0 + 2.0);
+
+            current_layer.add_line_break();
             current_layer.add_line_break();
 
-            current_layer.set_font(&monospace_font, 10.0);
-            current_layer.set_fill_color(colours::GREY);
-            current_layer.write_text("paperback-v0", &monospace_font);
-            current_layer.set_fill_color(colours::BLACK);
+            // Details.
+            current_layer.set_font(&text_font, 10.0);
+            for (idx, line) in description.iter().enumerate() {
+                if idx > 0 {
+                    current_layer.add_line_break();
+                }
+                current_layer.write_text(line, &text_font);
+            }
         }
         current_layer.end_text_section();
         current_layer.begin_text_section();
         {
+            // Header.
             current_layer.set_text_cursor(
-                A5_MARGIN + Mm(45.0),
-                A5_HEIGHT - (current_y + Pt(12.0 + 20.0 * 2.0 + 16.0 - 12.0 * 2.0).into()),
+                title_x(&page, "Manifest", options),
+                page.height - (current_y + Pt(10.0).into()),
             );
-
-            // Details.
-            current_layer.set_font(&text_font, 10.0);
+            current_layer.set_font(&text_font, 20.0);
+            current_layer.set_fill_color(palette.manifest_trim.clone());
+            current_layer.write_text("Manifest", &text_font);
+            current_layer.set_fill_color(palette.black.clone());
             current_layer.set_line_height(10.0 + 2.0);
-            current_layer.write_text("This is a key shard of a paperback backup.", &text_font);
             current_layer.add_line_break();
-            current_layer.write_text("See cyphar.com/paperback for more details.", &text_font);
+
+            current_layer.set_font(&monospace_font, 10.0);
+            current_layer.set_fill_color(palette.grey.clone());
+            current_layer.write_text(options.symbology.header_label(), &monospace_font);
+            current_layer.set_fill_color(palette.black.clone());
         }
         current_layer.end_text_section();
-        current_y += Mm(25.0);
+        current_y += (Pt(22.0) + Pt(12.0) * (2 + description.len()) as f64).into();
 
         current_y += banner(
             &current_layer,
-            A5_HEIGHT - current_y,
-            (A5_WIDTH, A5_MARGIN, Mm(1.0)),
+            &palette,
+            page.height - current_y,
+            (page.width, page.margin, Mm(3.0)),
             Text {
-                inner: "① Shard",
-                colour: colours::WHITE,
+                inner: "① Format Specification",
+                colour: palette.white.clone(),
                 font: &text_font,
                 font_size: Pt(10.0),
             },
             Some(Text {
-                inner: "Key shard data, encrypted using the codewords.",
-                colour: colours::WHITE,
+                inner: "Format version and checksum of the archived specification, for implementers without paperback.",
+                colour: palette.white.clone(),
                 font: &text_font,
                 font_size: Pt(8.0),
             }),
-            colours::KEY_SHARD_TRIM,
-        );
+            palette.manifest_trim.clone(),
+        ) + Mm(2.0);
 
         current_y += qr_with_fallback(
             &current_layer,
-            A5_HEIGHT - current_y,
-            (A5_WIDTH, A5_MARGIN, 0.3),
-            shard.to_wire(),
+            options,
+            page.height - current_y,
+            (
+                page.width,
+                page.margin,
+                page.width * MAIN_DOCUMENT_CHECKSUM_QR_FRACTION,
+            ),
+            (SpecReference::current().to_wire(), TextEncoding::default()),
             &monospace_font,
-            8.0,
+            10.0,
         )?;
 
         current_y += banner(
             &current_layer,
-            A5_HEIGHT - current_y,
-            (A5_WIDTH, A5_MARGIN, Mm(1.0)),
+            &palette,
+            page.height - current_y,
+            (page.width, page.margin, Mm(3.0)),
             Text {
-                inner: "② Checksum",
-                colour: colours::WHITE,
+                inner: "② Artifacts",
+                colour: palette.white.clone(),
                 font: &text_font,
                 font_size: Pt(10.0),
             },
             Some(Text {
-                inner: "Verifies the key shard was scanned correctly.",
-                colour: colours::WHITE,
+                inner: "Checksums of every document created for this backup.",
+                colour: palette.white.clone(),
                 font: &text_font,
                 font_size: Pt(8.0),
             }),
-            colours::KEY_SHARD_TRIM,
+            palette.manifest_trim.clone(),
+        ) + Mm(2.0);
+
+        let mut lines = vec![format!(
+            "main document  {}",
+            self.document_checksum_string()
+        )];
+        lines.extend(
+            self.shard_checksum_strings()
+                .into_iter()
+                .enumerate()
+                .map(|(idx, chksum)| format!("key shard {:<4} {}", idx + 1, chksum)),
         );
+        // Leave room for the three summary lines below the checksums.
+        let fitting_lines =
+            ((page.height - current_y - page.margin) / Mm::from(MANIFEST_LINE_HEIGHT)) as usize;
+        let max_lines = MANIFEST_MAX_LINES.min(fitting_lines.saturating_sub(3).max(2));
+        if lines.len() > max_lines {
+            let omitted = lines.len() - (max_lines - 1);
+            lines.truncate(max_lines - 1);
+            lines.push(format!("... and {} more key shards", omitted));
+        }
+        lines.push(String::new());
+        lines.push(format!("manifest hash  {}", self.hash_string()));
+        lines.push(format!("signatures     {}", self.signatures().len()));
 
-        current_y += qr_with_fallback(
-            &current_layer,
-            A5_HEIGHT - current_y,
-            (A5_WIDTH, A5_MARGIN, 0.3),
-            shard.checksum().to_bytes(),
-            &monospace_font,
-            8.0,
-        )?;
+        // Shrink the text so that the longest line fits on the page.
+        let font_size = lines
+            .iter()
+            .map(|line| {
+                monospace_font.fit_text(
+                    line,
+                    MANIFEST_FONT_SIZE,
+                    CHARACTER_SPACING,
+                    page.width - page.margin * 2.0,
+                )
+            })
+            .fold(MANIFEST_FONT_SIZE, f64::min);
 
-        // "Cut here" line.
+        current_layer.begin_text_section();
         {
-            let scissors_svg = Svg::parse(SCISSORS_SVG)?;
-            let scissors_svg_ref = scissors_svg.into_xobject(&current_layer);
-
-            // For scissors, scale to the target height.
-            let target_height = Mm(5.0);
-            let scale = target_height / Mm::from(scissors_svg_ref.height.into_pt(SVG_DPI));
-
-            // Dashed line.
-            let points = vec![
-                (
-                    Point::new(Mm(0.0), A5_HEIGHT - (current_y + target_height / 2.0)),
-                    false,
-                ),
-                (
-                    Point::new(A5_WIDTH, A5_HEIGHT - (current_y + target_height / 2.0)),
-                    false,
-                ),
-            ];
-            let line = Line {
-                points,
-                is_closed: false,
-                has_fill: false,
-                has_stroke: true,
-                is_clipping_path: false,
-            };
+            current_layer.set_font(&monospace_font, font_size);
+            current_layer.set_line_height(MANIFEST_LINE_HEIGHT.0);
+            current_layer.set_text_cursor(
+                page.margin,
+                page.height - (current_y + Pt(MANIFEST_FONT_SIZE).into()),
+            );
+            for line in lines {
+                current_layer.write_text(line, &monospace_font);
+                current_layer.add_line_break();
+            }
+        }
+        current_layer.end_text_section();
 
-            let mut dash_pattern = LineDashPattern::default();
-            dash_pattern.dash_1 = Some(6);
-            dash_pattern.gap_1 = Some(4);
+        current_layer.finish()
+    }
+}
 
-            current_layer.set_outline_color(colours::KEY_SHARD_TRIM);
-            current_layer.set_line_dash_pattern(dash_pattern);
-            current_layer.add_shape(line);
+/// A sheet of instructions for recovering a backup, to be stored alongside
+/// its main document. It explains what paperback is, where to get it, which
+/// documents are needed and the exact commands to run, so that the backup can
+/// be recovered by someone who has never used paperback. It contains no secret
+/// data.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RecoveryInstructions {
+    document_id: DocumentId,
+    quorum_size: u32,
+}
 
-            // Scissors.
-            scissors_svg_ref.add_to_layer(
-                &current_layer,
-                SvgTransform {
-                    translate_x: Some(A5_MARGIN),
-                    translate_y: Some(A5_HEIGHT - (current_y + target_height)),
-                    scale_x: Some(scale),
-                    scale_y: Some(scale),
-                    ..Default::default()
-                },
-            );
-            current_y += target_height;
+impl RecoveryInstructions {
+    /// Instructions for recovering the document with the given identifier,
+    /// which needs `quorum_size` key shards to be recovered.
+    pub fn new(document_id: DocumentId, quorum_size: u32) -> Self {
+        Self {
+            document_id,
+            quorum_size,
         }
+    }
 
-        current_y += banner(
-            &current_layer,
-            A5_HEIGHT - current_y,
-            (A5_WIDTH, A5_MARGIN, Mm(1.0)),
-            Text {
-                inner: "③ Codewords",
-                colour: colours::WHITE,
-                font: &text_font,
-                font_size: Pt(10.0),
-            },
-            Some(Text {
-                inner: "Encrypts the key shard data. Can be optionally cut off.",
-                colour: colours::WHITE,
-                font: &text_font,
-                font_size: Pt(8.0),
-            }),
-            colours::KEY_SHARD_TRIM,
+    /// The identifier of the document the instructions are for.
+    pub fn document_id(&self) -> &str {
+        &self.document_id
+    }
+
+    /// The number of key shards needed to recover the document.
+    pub fn quorum_size(&self) -> u32 {
+        self.quorum_size
+    }
+}
+
+impl From<&MainDocument> for RecoveryInstructions {
+    fn from(main_document: &MainDocument) -> Self {
+        Self::new(main_document.id(), main_document.quorum_size())
+    }
+}
+
+// Largest and smallest font size of the recovery instructions (the largest
+// size which fits on the page is used).
+const INSTRUCTIONS_MAX_FONT_SIZE: f64 = 10.0;
+const INSTRUCTIONS_MIN_FONT_SIZE: f64 = 6.0;
+// Indentation of the commands in the recovery instructions.
+const INSTRUCTIONS_COMMAND_INDENT: Mm = Mm(5.0);
+
+// A section of the recovery instructions: a banner heading and the paragraphs
+// below it (commands are printed in the monospace font).
+type InstructionsSection = (String, Vec<(Font, String)>);
+
+impl ToPdf for RecoveryInstructions {
+    fn to_pdf_with_options(&self, options: &RenderOptions) -> Result<PdfDocumentReference, Error> {
+        self.draw::<PdfCanvas>(options)
+    }
+
+    fn to_svg_with_options(&self, options: &RenderOptions) -> Result<Vec<String>, Error> {
+        self.draw::<SvgCanvas>(options)
+    }
+
+    fn qr_codes_to_png_with_options(
+        &self,
+        options: &RenderOptions,
+        dpi: u32,
+    ) -> Result<Vec<Vec<u8>>, Error> {
+        raster::codes_to_png(self.draw::<CodesCanvas>(options)?, dpi)
+    }
+}
+
+impl RecoveryInstructions {
+    fn sections(&self, language: Language) -> Vec<InstructionsSection> {
+        let document: &dyn std::fmt::Display = &self.document_id;
+        let scan_command = format!(
+            "paperback-cli recover --scan main-document.png {} secret.txt",
+            (1..=self.quorum_size)
+                .map(|idx| format!("key-shard-{}.png", idx))
+                .collect::<Vec<_>>()
+                .join(" ")
+        );
+        vec![
+            (
+                format!("① {}", language.message("instructions-what", &[])),
+                vec![(Font::Text, language.message("instructions-what-text", &[]))],
+            ),
+            (
+                format!("② {}", language.message("instructions-needed", &[])),
+                vec![
+                    (
+                        Font::Text,
+                        language.message("instructions-needed-document", &[("document", document)]),
+                    ),
+                    (
+                        Font::Text,
+                        language.message(
+                            "instructions-needed-shards",
+                            &[("quorum", &self.quorum_size)],
+                        ),
+                    ),
+                    (
+                        Font::Text,
+                        language.message("instructions-needed-computer", &[]),
+                    ),
+                ],
+            ),
+            (
+                format!("③ {}", language.message("instructions-software", &[])),
+                vec![(
+                    Font::Text,
+                    language.message(
+                        "instructions-software-text",
+                        &[("version", &SpecReference::current().version())],
+                    ),
+                )],
+            ),
+            (
+                format!("④ {}", language.message("instructions-recover", &[])),
+                vec![
+                    (
+                        Font::Text,
+                        language.message("instructions-recover-interactive", &[]),
+                    ),
+                    (
+                        Font::Monospace,
+                        "paperback-cli recover --interactive secret.txt".to_string(),
+                    ),
+                    (
+                        Font::Text,
+                        language.message("instructions-recover-scan", &[]),
+                    ),
+                    (Font::Monospace, scan_command),
+                    (
+                        Font::Text,
+                        language.message("instructions-recover-check", &[("document", document)]),
+                    ),
+                ],
+            ),
+        ]
+    }
+
+    fn draw<C: Canvas>(&self, options: &RenderOptions) -> Result<C::Output, Error> {
+        let palette = options.style.palette();
+        let language = options.language;
+
+        // Construct a page (A4 by default).
+        let page = Page::for_document(options, PaperSize::A4)?;
+        let (monospace_font, text_font) = (Font::Monospace, Font::Text);
+        let current_layer = C::new(
+            format!("Paperback Recovery Instructions {}", self.document_id),
+            (page.width, page.height),
+            &[monospace_font, text_font],
+        )?;
+        let text_width = page.width - page.margin * 2.0;
+        let title = language.message("recovery-instructions", &[]);
+        let description = text_font.wrap_text(
+            &language.message(
+                "recovery-instructions-description",
+                &[("document", &self.document_id)],
+            ),
+            10.0,
+            CHARACTER_SPACING,
+            text_width,
         );
 
-        current_y = A5_HEIGHT - Mm(30.0);
+        let mut current_y = page.margin + Pt(10.0).into();
 
-        // Shard codewords.
+        // Header.
         current_layer.begin_text_section();
         {
             current_layer.set_word_spacing(1.2);
-            current_layer.set_character_spacing(1.0);
-            current_layer.set_text_cursor(A5_MARGIN, A5_HEIGHT - current_y);
+            current_layer.set_character_spacing(CHARACTER_SPACING);
+            current_layer.set_text_cursor(page.margin, page.height - current_y);
 
-            // "Shard".
+            // "Document".
             current_layer.set_font(&text_font, 10.0);
-            current_layer.set_fill_color(colours::GREY);
-            current_layer.write_text("Shard", &text_font);
-            current_layer.set_fill_color(colours::BLACK);
+            current_layer.set_fill_color(palette.grey.clone());
+            current_layer.write_text(language.message("document", &[]), &text_font);
             current_layer.set_line_height(20.0 + 2.0);
             current_layer.add_line_break();
-            // <shard id>
+            // <document id>
             current_layer.set_font(&monospace_font, 20.0);
-            current_layer.set_fill_color(colours::KEY_SHARD_TRIM);
-            current_layer.write_text(decrypted_shard.id(), &monospace_font);
-            current_layer.set_fill_color(colours::BLACK);
-            current_layer.set_line_height(12.0 + 2.0);
+            current_layer.set_fill_color(palette.main_document_trim.clone());
+            current_layer.write_text(&self.document_id, &monospace_font);
+            current_layer.set_fill_color(palette.black.clone());
+            current_layer.set_line_height(10.0 + 2.0);
+
+            current_layer.add_line_break();
             current_layer.add_line_break();
 
-            // "Document".
+            // Details.
             current_layer.set_font(&text_font, 10.0);
-            current_layer.set_fill_color(colours::GREY);
-            current_layer.write_text("Document", &text_font);
-            current_layer.set_fill_color(colours::BLACK);
-            current_layer.set_line_height(20.0 + 2.0);
-            current_layer.add_line_break();
-            // <document id>
-            current_layer.set_font(&monospace_font, 20.0);
-            current_layer.set_fill_color(colours::MAIN_DOCUMENT_TRIM);
-            current_layer.write_text(decrypted_shard.document_id(), &monospace_font);
-            current_layer.set_fill_color(colours::BLACK);
+            for (idx, line) in description.iter().enumerate() {
+                if idx > 0 {
+                    current_layer.add_line_break();
+                }
+                current_layer.write_text(line, &text_font);
+            }
         }
         current_layer.end_text_section();
         current_layer.begin_text_section();
         {
-            current_layer.set_word_spacing(1.2);
-            current_layer.set_character_spacing(1.0);
+            // Header.
             current_layer.set_text_cursor(
-                A5_MARGIN + Mm(45.0),
-                A5_HEIGHT - (current_y + Pt(5.0).into()),
+                title_x(&page, &title, options),
+                page.height - (current_y + Pt(10.0).into()),
             );
+            current_layer.set_font(&text_font, 20.0);
+            current_layer.set_fill_color(palette.main_document_trim.clone());
+            current_layer.write_text(&title, &text_font);
+            current_layer.set_fill_color(palette.black.clone());
+            current_layer.set_line_height(10.0 + 2.0);
+            current_layer.add_line_break();
 
-            // Codewords.
             current_layer.set_font(&monospace_font, 10.0);
-            current_layer.set_line_height(10.0 + 5.0);
-            for (i, codeword) in codewords.iter().enumerate() {
-                let font = if i % 2 == 0 {
-                    current_layer.set_font(&monospace_font, 10.0);
-                    &monospace_font
-                } else {
-                    current_layer.set_font(&monospace_bold_font, 10.0);
-                    &monospace_bold_font
+            current_layer.set_fill_color(palette.grey.clone());
+            current_layer.write_text(options.symbology.header_label(), &monospace_font);
+            current_layer.set_fill_color(palette.black.clone());
+        }
+        current_layer.end_text_section();
+        current_y += (Pt(22.0) + Pt(12.0) * (2 + description.len()) as f64).into();
+
+        // Wrap the sections with the largest font size which fits on the page.
+        let sections = self.sections(language);
+        let wrap = |font_size: f64| {
+            sections
+                .iter()
+                .map(|(heading, paragraphs)| {
+                    let paragraphs = paragraphs
+                        .iter()
+                        .map(|(font, text)| {
+                            let width = match font {
+                                Font::Monospace => text_width - INSTRUCTIONS_COMMAND_INDENT,
+                                _ => text_width,
+                            };
+                            (
+                                *font,
+                                font.wrap_text(text, font_size, CHARACTER_SPACING, width),
+                            )
+                        })
+                        .collect::<Vec<_>>();
+                    (heading, paragraphs)
+                })
+                .collect::<Vec<_>>()
+        };
+        // Each paragraph is followed by half a line of space.
+        let height = |font_size: f64, sections: &[(_, Vec<(Font, Vec<String>)>)]| {
+            let num_lines = sections
+                .iter()
+                .flat_map(|(_, paragraphs)| paragraphs)
+                .map(|(_, lines)| lines.len() as f64 + 0.5)
+                .sum::<f64>();
+            (BANNER_HEIGHT + Mm(3.0 + 2.0)) * sections.len() as f64
+                + Mm::from(Pt(font_size + 2.0)) * num_lines
+        };
+        let mut font_size = INSTRUCTIONS_MAX_FONT_SIZE;
+        let mut wrapped = wrap(font_size);
+        while height(font_size, &wrapped) > page.height - current_y - page.margin {
+            if font_size <= INSTRUCTIONS_MIN_FONT_SIZE {
+                return Err(Error::OtherError(format!(
+                    "recovery instructions do not fit on a {}x{}mm page",
+                    page.width.0, page.height.0
+                )));
+            }
+            font_size -= 1.0;
+            wrapped = wrap(font_size);
+        }
+        let line_height = Pt(font_size + 2.0);
+
+        for (heading, paragraphs) in wrapped {
+            current_y += banner(
+                &current_layer,
+                &palette,
+                page.height - current_y,
+                (page.width, page.margin, Mm(3.0)),
+                Text {
+                    inner: heading,
+                    colour: palette.white.clone(),
+                    font: &text_font,
+                    font_size: Pt(10.0),
+                },
+                None,
+                palette.main_document_trim.clone(),
+            ) + Mm(2.0);
+
+            for (font, lines) in paragraphs {
+                let indent = match font {
+                    Font::Monospace => INSTRUCTIONS_COMMAND_INDENT,
+                    _ => Mm(0.0),
                 };
-                current_layer.write_text(codeword, font);
-                if i % 5 == 4 {
-                    current_layer.add_line_break();
-                } else {
-                    current_layer.write_text(" ", font);
+                current_layer.begin_text_section();
+                {
+                    current_layer.set_font(&font, font_size);
+                    current_layer.set_line_height(line_height.0);
+                    current_layer.set_text_cursor(
+                        page.margin + indent,
+                        page.height - (current_y + Pt(font_size).into()),
+                    );
+                    for line in &lines {
+                        current_layer.write_text(line, &font);
+                        current_layer.add_line_break();
+                    }
                 }
+                current_layer.end_text_section();
+                current_y += Mm::from(line_height) * (lines.len() as f64 + 0.5);
             }
         }
-        current_layer.end_text_section();
 
-        doc.check_for_errors()?;
-        Ok(doc)
+        current_layer.finish()
+    }
+}
+
+// Fraction of the page width used by the key shard data and checksum codes.
+const KEY_SHARD_QR_FRACTION: f64 = 0.3;
+// Height of a key shard outside of its data and checksum codes (the header,
+// banners, cut line and codewords).
+const KEY_SHARD_FIXED_HEIGHT: Mm = Mm(100.0);
+
+const SCISSORS_SVG: &str = include_str!("scissors.svg");
+
+// Passphrase-hardened shards can only be rendered if the passphrase is given,
+// since the shard needs to be decrypted.
+fn key_shard_page<C: Canvas>(
+    shard: &EncryptedKeyShard,
+    codewords: &KeyShardCodewords,
+    passphrase: Option<&[u8]>,
+    options: &RenderOptions,
+) -> Result<C::Output, Error> {
+    let shard = PreparedKeyShard::new(shard, codewords, passphrase)?;
+
+    // Construct a page (A5 by default).
+    let page = Page::for_document(options, PaperSize::A5)?;
+    let current_layer = C::new(
+        format!(
+            "Paperback Key Shard {}/{}",
+            shard.decrypted.document_id(),
+            shard.decrypted.id()
+        ),
+        (page.width, page.height),
+        &KEY_SHARD_FONTS,
+    )?;
+    draw_key_shard(&current_layer, &page, &shard, options)?;
+    current_layer.finish()
+}
+
+// A key shard ready to be drawn.
+struct PreparedKeyShard<'a> {
+    shard: &'a EncryptedKeyShard,
+    codewords: &'a KeyShardCodewords,
+    // TODO: Make this nicer. It's quite ugly we need to decrypt the shard
+    // here just to get the document and shard ids. If we cached them that
+    // would work, but if you just read the shard data from the user you
+    // wouldn't have this information without decrypting it.
+    decrypted: KeyShard,
+}
+
+impl<'a> PreparedKeyShard<'a> {
+    fn new(
+        shard: &'a EncryptedKeyShard,
+        codewords: &'a KeyShardCodewords,
+        passphrase: Option<&[u8]>,
+    ) -> Result<Self, Error> {
+        let decrypted = match passphrase {
+            Some(passphrase) => shard.decrypt_with_passphrase(codewords, passphrase),
+            None => shard.decrypt(codewords),
+        }
+        .map_err(|err| Error::OtherError(format!("failed to decrypt shard: {:?}", err)))?;
+        Ok(Self {
+            shard,
+            codewords,
+            decrypted,
+        })
+    }
+}
+
+const KEY_SHARD_FONTS: [Font; 3] = [Font::Monospace, Font::MonospaceBold, Font::Text];
+
+// Draw a key shard filling the page (which may be a region of a larger
+// sheet, see KeyShardSheet).
+fn draw_key_shard<C: Canvas>(
+    current_layer: &C,
+    page: &Page,
+    prepared: &PreparedKeyShard<'_>,
+    options: &RenderOptions,
+) -> Result<(), Error> {
+    let (shard, codewords, decrypted_shard) =
+        (prepared.shard, prepared.codewords, &prepared.decrypted);
+    let (palette, language) = (options.style.palette(), options.language);
+    let (monospace_font, monospace_bold_font, text_font) =
+        (Font::Monospace, Font::MonospaceBold, Font::Text);
+
+    if decrypted_shard.is_test_run() {
+        test_run_watermark(
+            current_layer,
+            options,
+            (page.width, page.height),
+            &text_font,
+        );
+    }
+
+    let mut current_y = page.margin + Pt(10.0).into();
+
+    // Header.
+    current_layer.begin_text_section();
+    {
+        current_layer.set_word_spacing(1.2);
+        current_layer.set_character_spacing(CHARACTER_SPACING);
+
+        current_layer.set_text_cursor(page.margin, page.height - current_y);
+
+        // "Shard".
+        current_layer.set_font(&text_font, 10.0);
+        current_layer.set_fill_color(palette.grey.clone());
+        current_layer.write_text(language.message("shard", &[]), &text_font);
+        current_layer.set_fill_color(palette.black.clone());
+        current_layer.set_line_height(20.0 + 2.0);
+        current_layer.add_line_break();
+        // <shard id>
+        current_layer.set_font(&monospace_font, 20.0);
+        current_layer.set_fill_color(palette.key_shard_trim.clone());
+        current_layer.write_text(decrypted_shard.id(), &monospace_font);
+        current_layer.set_fill_color(palette.black.clone());
+        current_layer.set_line_height(14.0 + 2.0);
+        current_layer.add_line_break();
+
+        // "Document".
+        current_layer.set_font(&text_font, 10.0);
+        current_layer.set_fill_color(palette.grey.clone());
+        current_layer.write_text(language.message("document", &[]), &text_font);
+        current_layer.set_fill_color(palette.black.clone());
+        current_layer.set_line_height(20.0 + 2.0);
+        current_layer.add_line_break();
+        // <document id>
+        current_layer.set_font(&monospace_font, 20.0);
+        current_layer.set_fill_color(palette.main_document_trim.clone());
+        current_layer.write_text(decrypted_shard.document_id(), &monospace_font);
+        current_layer.set_fill_color(palette.black.clone());
+    }
+    current_layer.end_text_section();
+    current_layer.begin_text_section();
+    {
+        // Header.
+        let title = language.message("key-shard", &[]);
+        current_layer.set_text_cursor(
+            title_x(page, &title, options),
+            page.height - (current_y + Pt(10.0).into()),
+        );
+        current_layer.set_font(&text_font, 20.0);
+        current_layer.set_fill_color(palette.key_shard_trim.clone());
+        current_layer.write_text(title, &text_font);
+        current_layer.set_fill_color(palette.black.clone());
+        current_layer.set_line_height(10.0 + 2.0);
+        current_layer.add_line_break();
+
+        current_layer.set_font(&monospace_font, 10.0);
+        current_layer.set_fill_color(palette.grey.clone());
+        current_layer.write_text(options.symbology.header_label(), &monospace_font);
+        current_layer.set_fill_color(palette.black.clone());
+    }
+    current_layer.end_text_section();
+
+    // Details, wrapped to the space beside the shard and document ids.
+    let details_x = page.margin + Mm(45.0);
+    let wrap = |text: &str, font_size| {
+        text_font.wrap_text(
+            text,
+            font_size,
+            CHARACTER_SPACING,
+            page.width - page.margin - details_x,
+        )
+    };
+    let intro_lines = ["key-shard-intro", "key-shard-details"]
+        .iter()
+        .flat_map(|id| wrap(&language.message(id, &[]), 10.0))
+        .collect::<Vec<_>>();
+
+    // Custodian details (printed below the other details).
+    let custodian = decrypted_shard.custodian();
+    let custodian_lines = [
+        match (&custodian.name, &custodian.contact) {
+            (Some(name), Some(contact)) => {
+                Some(language.message("held-by-contact", &[("name", name), ("contact", contact)]))
+            }
+            (Some(name), None) => Some(language.message("held-by", &[("name", name)])),
+            (None, Some(contact)) => {
+                Some(language.message("holder-contact", &[("contact", contact)]))
+            }
+            (None, None) => None,
+        },
+        custodian
+            .note
+            .as_ref()
+            .map(|note| language.message("custodian-note", &[("note", note)])),
+        // Key shards minted after the backup was created.
+        decrypted_shard.expansion().map(|expansion| {
+            language.message(
+                "minted-by-expansion",
+                &[
+                    ("date", &constraints::format_date(expansion.expanded_at)),
+                    ("shards", &expansion.quorum.join(" ")),
+                ],
+            )
+        }),
+        // Group membership.
+        decrypted_shard.group_id().map(|group_id| {
+            language.message(
+                "group-member",
+                &[
+                    ("group", &group_id),
+                    ("members", &decrypted_shard.member_quorum_size()),
+                    ("groups", &decrypted_shard.quorum_size()),
+                ],
+            )
+        }),
+    ]
+    .iter()
+    .flatten()
+    .flat_map(|line| wrap(line, 8.0))
+    .collect::<Vec<_>>();
+
+    // Recovery effort.
+    let effort = RecoveryEffort::key_shard(shard, codewords);
+    let effort_lines = wrap(
+        &language.message(
+            "key-shard-effort",
+            &[
+                ("scans", &effort.qr_scans),
+                ("chars", &effort.transcription_chars),
+                ("minutes", &effort.transcription_minutes().ceil()),
+            ],
+        ),
+        8.0,
+    );
+
+    current_layer.begin_text_section();
+    {
+        current_layer.set_text_cursor(
+            details_x,
+            page.height - (current_y + Pt(12.0 + 20.0 * 2.0 + 16.0 - 12.0 * 2.0).into()),
+        );
+
+        // Details.
+        current_layer.set_font(&text_font, 10.0);
+        current_layer.set_line_height(10.0 + 2.0);
+        for line in &intro_lines {
+            current_layer.write_text(line, &text_font);
+            current_layer.add_line_break();
+        }
+
+        // Custodian details, expansion record and group membership.
+        current_layer.set_font(&text_font, 8.0);
+        for line in &custodian_lines {
+            current_layer.write_text(line, &text_font);
+            current_layer.add_line_break();
+        }
+
+        // Recovery effort.
+        current_layer.set_fill_color(palette.grey.clone());
+        for line in &effort_lines {
+            current_layer.write_text(line, &text_font);
+            current_layer.add_line_break();
+        }
+        current_layer.set_fill_color(palette.black.clone());
+    }
+    current_layer.end_text_section();
+    current_y += Mm(25.0);
+    // Make room for the custodian details (and any other wrapped lines).
+    let num_lines = intro_lines.len() + custodian_lines.len() + effort_lines.len();
+    current_y += Mm::from(Pt(10.0 + 2.0)) * num_lines.saturating_sub(3) as f64;
+
+    current_y += banner(
+        current_layer,
+        &palette,
+        page.height - current_y,
+        (page.width, page.margin, Mm(1.0)),
+        Text {
+            inner: &format!("① {}", language.message("shard", &[])),
+            colour: palette.white.clone(),
+            font: &text_font,
+            font_size: Pt(10.0),
+        },
+        Some(Text {
+            inner: &language.message(
+                if shard.is_passphrase_protected() {
+                    "shard-description-passphrase"
+                } else {
+                    "shard-description"
+                },
+                &[],
+            ),
+            colour: palette.white.clone(),
+            font: &text_font,
+            font_size: Pt(8.0),
+        }),
+        palette.key_shard_trim.clone(),
+    );
+
+    current_y += qr_with_fallback(
+        current_layer,
+        options,
+        page.height - current_y,
+        (page.width, page.margin, page.key_shard_code_size()),
+        (shard.to_wire(), decrypted_shard.text_encoding()),
+        &monospace_font,
+        8.0,
+    )?;
+
+    current_y += banner(
+        current_layer,
+        &palette,
+        page.height - current_y,
+        (page.width, page.margin, Mm(1.0)),
+        Text {
+            inner: &format!("② {}", language.message("checksum", &[])),
+            colour: palette.white.clone(),
+            font: &text_font,
+            font_size: Pt(10.0),
+        },
+        Some(Text {
+            inner: &language.message("shard-checksum-description", &[]),
+            colour: palette.white.clone(),
+            font: &text_font,
+            font_size: Pt(8.0),
+        }),
+        palette.key_shard_trim.clone(),
+    );
+
+    current_y += qr_with_fallback(
+        current_layer,
+        options,
+        page.height - current_y,
+        (page.width, page.margin, page.key_shard_code_size()),
+        (shard.checksum().to_bytes(), decrypted_shard.text_encoding()),
+        &monospace_font,
+        8.0,
+    )?;
+
+    // "Cut here" line.
+    {
+        // For scissors, scale to the target height.
+        let target_height = Mm(5.0);
+
+        // Dashed line.
+        let points = vec![
+            (
+                Point::new(Mm(0.0), page.height - (current_y + target_height / 2.0)),
+                false,
+            ),
+            (
+                Point::new(page.width, page.height - (current_y + target_height / 2.0)),
+                false,
+            ),
+        ];
+        let line = Line {
+            points,
+            is_closed: false,
+            has_fill: false,
+            has_stroke: true,
+            is_clipping_path: false,
+        };
+
+        let mut dash_pattern = LineDashPattern::default();
+        dash_pattern.dash_1 = Some(6);
+        dash_pattern.gap_1 = Some(4);
+
+        current_layer.set_outline_color(palette.key_shard_trim.clone());
+        current_layer.set_line_dash_pattern(dash_pattern);
+        current_layer.add_shape(line);
+
+        // Scissors.
+        current_layer.add_svg(SCISSORS_SVG, |(_, height)| {
+            let scale = target_height / Mm::from(height.into_pt(SVG_DPI));
+            SvgTransform {
+                translate_x: Some(page.margin),
+                translate_y: Some(page.height - (current_y + target_height)),
+                scale_x: Some(scale),
+                scale_y: Some(scale),
+                ..Default::default()
+            }
+        })?;
+        current_y += target_height;
+    }
+
+    current_y += banner(
+        current_layer,
+        &palette,
+        page.height - current_y,
+        (page.width, page.margin, Mm(1.0)),
+        Text {
+            inner: &format!("③ {}", language.message("codewords", &[])),
+            colour: palette.white.clone(),
+            font: &text_font,
+            font_size: Pt(10.0),
+        },
+        Some(Text {
+            inner: &language.message("codewords-description", &[]),
+            colour: palette.white.clone(),
+            font: &text_font,
+            font_size: Pt(8.0),
+        }),
+        palette.key_shard_trim.clone(),
+    );
+
+    current_y = page.height - Mm(30.0);
+
+    // Shrink the codewords so that each line of them fits beside the ids.
+    let codeword_lines = codewords
+        .chunks(5)
+        .map(|line| line.join(" "))
+        .collect::<Vec<_>>();
+    let codeword_size = codeword_lines
+        .iter()
+        .map(|line| {
+            monospace_font.fit_text(
+                line,
+                10.0,
+                CHARACTER_SPACING,
+                page.width - page.margin - details_x,
+            )
+        })
+        .fold(10.0, f64::min);
+
+    // Shard codewords.
+    current_layer.begin_text_section();
+    {
+        current_layer.set_word_spacing(1.2);
+        current_layer.set_character_spacing(CHARACTER_SPACING);
+        current_layer.set_text_cursor(page.margin, page.height - current_y);
+
+        // "Shard".
+        current_layer.set_font(&text_font, 10.0);
+        current_layer.set_fill_color(palette.grey.clone());
+        current_layer.write_text(language.message("shard", &[]), &text_font);
+        current_layer.set_fill_color(palette.black.clone());
+        current_layer.set_line_height(20.0 + 2.0);
+        current_layer.add_line_break();
+        // <shard id>
+        current_layer.set_font(&monospace_font, 20.0);
+        current_layer.set_fill_color(palette.key_shard_trim.clone());
+        current_layer.write_text(decrypted_shard.id(), &monospace_font);
+        current_layer.set_fill_color(palette.black.clone());
+        current_layer.set_line_height(12.0 + 2.0);
+        current_layer.add_line_break();
+
+        // "Document".
+        current_layer.set_font(&text_font, 10.0);
+        current_layer.set_fill_color(palette.grey.clone());
+        current_layer.write_text(language.message("document", &[]), &text_font);
+        current_layer.set_fill_color(palette.black.clone());
+        current_layer.set_line_height(20.0 + 2.0);
+        current_layer.add_line_break();
+        // <document id>
+        current_layer.set_font(&monospace_font, 20.0);
+        current_layer.set_fill_color(palette.main_document_trim.clone());
+        current_layer.write_text(decrypted_shard.document_id(), &monospace_font);
+        current_layer.set_fill_color(palette.black.clone());
+    }
+    current_layer.end_text_section();
+    current_layer.begin_text_section();
+    {
+        current_layer.set_word_spacing(1.2);
+        current_layer.set_character_spacing(CHARACTER_SPACING);
+        current_layer.set_text_cursor(details_x, page.height - (current_y + Pt(5.0).into()));
+
+        // Codewords.
+        current_layer.set_font(&monospace_font, codeword_size);
+        current_layer.set_line_height(10.0 + 5.0);
+        for (i, codeword) in codewords.iter().enumerate() {
+            let font = if i % 2 == 0 {
+                current_layer.set_font(&monospace_font, codeword_size);
+                &monospace_font
+            } else {
+                current_layer.set_font(&monospace_bold_font, codeword_size);
+                &monospace_bold_font
+            };
+            current_layer.write_text(codeword, font);
+            if i % 5 == 4 {
+                current_layer.add_line_break();
+            } else {
+                current_layer.write_text(" ", font);
+            }
+        }
+    }
+    current_layer.end_text_section();
+
+    Ok(())
+}
+
+impl ToPdf for (&EncryptedKeyShard, &KeyShardCodewords) {
+    fn to_pdf_with_options(&self, options: &RenderOptions) -> Result<PdfDocumentReference, Error> {
+        let (shard, codewords) = self;
+        key_shard_page::<PdfCanvas>(shard, codewords, None, options)
+    }
+
+    fn to_svg_with_options(&self, options: &RenderOptions) -> Result<Vec<String>, Error> {
+        let (shard, codewords) = self;
+        key_shard_page::<SvgCanvas>(shard, codewords, None, options)
+    }
+
+    fn qr_codes_to_png_with_options(
+        &self,
+        options: &RenderOptions,
+        dpi: u32,
+    ) -> Result<Vec<Vec<u8>>, Error> {
+        let (shard, codewords) = self;
+        let codes = key_shard_page::<CodesCanvas>(shard, codewords, None, options)?;
+        raster::codes_to_png(codes, dpi)
+    }
+}
+
+/// Render a passphrase-hardened key shard. The passphrase is only used to
+/// decrypt the shard, and is not included in the document.
+impl ToPdf for (&EncryptedKeyShard, &KeyShardCodewords, &[u8]) {
+    fn to_pdf_with_options(&self, options: &RenderOptions) -> Result<PdfDocumentReference, Error> {
+        let (shard, codewords, passphrase) = self;
+        key_shard_page::<PdfCanvas>(shard, codewords, Some(*passphrase), options)
+    }
+
+    fn to_svg_with_options(&self, options: &RenderOptions) -> Result<Vec<String>, Error> {
+        let (shard, codewords, passphrase) = self;
+        key_shard_page::<SvgCanvas>(shard, codewords, Some(*passphrase), options)
+    }
+
+    fn qr_codes_to_png_with_options(
+        &self,
+        options: &RenderOptions,
+        dpi: u32,
+    ) -> Result<Vec<Vec<u8>>, Error> {
+        let (shard, codewords, passphrase) = self;
+        let codes = key_shard_page::<CodesCanvas>(shard, codewords, Some(*passphrase), options)?;
+        raster::codes_to_png(codes, dpi)
     }
 }
 
 impl ToPdf for (EncryptedKeyShard, KeyShardCodewords) {
-    fn to_pdf(&self) -> Result<PdfDocumentReference, Error> {
+    fn to_pdf_with_options(&self, options: &RenderOptions) -> Result<PdfDocumentReference, Error> {
+        let (shard, codewords) = self;
+        (shard, codewords).to_pdf_with_options(options)
+    }
+
+    fn to_svg_with_options(&self, options: &RenderOptions) -> Result<Vec<String>, Error> {
         let (shard, codewords) = self;
-        (shard, codewords).to_pdf()
+        (shard, codewords).to_svg_with_options(options)
+    }
+
+    fn qr_codes_to_png_with_options(
+        &self,
+        options: &RenderOptions,
+        dpi: u32,
+    ) -> Result<Vec<Vec<u8>>, Error> {
+        let (shard, codewords) = self;
+        (shard, codewords).qr_codes_to_png_with_options(options, dpi)
+    }
+}
+
+/// Several key shards laid out together on a single sheet of paper (A4 by
+/// default), each in its own region separated by cut lines, to save paper
+/// when printing many key shards. Each region has the same content as a key
+/// shard printed on its own page. Sheets are turned sideways if that fits more
+/// key shards (such as two A5-sized key shards on a landscape A4 sheet).
+#[derive(Default)]
+pub struct KeyShardSheet<'a> {
+    shards: Vec<(
+        &'a EncryptedKeyShard,
+        &'a KeyShardCodewords,
+        Option<&'a [u8]>,
+    )>,
+}
+
+impl<'a> KeyShardSheet<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The number of key shards which fit on a sheet of the paper size given
+    /// in the options.
+    pub fn capacity(options: &RenderOptions) -> Result<usize, Error> {
+        let (_, columns, rows) = sheet_grid(options)?;
+        Ok(columns * rows)
+    }
+
+    /// Add a key shard to the sheet. Passphrase-hardened shards can only be
+    /// added with their passphrase, which is only used to decrypt the shard
+    /// and is not included in the sheet.
+    pub fn push(
+        &mut self,
+        shard: &'a EncryptedKeyShard,
+        codewords: &'a KeyShardCodewords,
+        passphrase: Option<&'a [u8]>,
+    ) {
+        self.shards.push((shard, codewords, passphrase));
+    }
+
+    /// The number of key shards on the sheet.
+    pub fn len(&self) -> usize {
+        self.shards.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.shards.is_empty()
+    }
+
+    fn draw<C: Canvas>(&self, options: &RenderOptions) -> Result<C::Output, Error> {
+        let palette = options.style.palette();
+        let (sheet, columns, rows) = sheet_grid(options)?;
+        if self.shards.len() > columns * rows {
+            return Err(Error::OtherError(format!(
+                "only {} key shards fit on a sheet of this paper size",
+                columns * rows
+            )));
+        }
+        let shards = self
+            .shards
+            .iter()
+            .map(|(shard, codewords, passphrase)| {
+                PreparedKeyShard::new(shard, codewords, *passphrase)
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        let current_layer = C::new(
+            format!(
+                "Paperback Key Shards {}",
+                shards
+                    .iter()
+                    .map(|shard| format!(
+                        "{}/{}",
+                        shard.decrypted.document_id(),
+                        shard.decrypted.id()
+                    ))
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            ),
+            (sheet.width, sheet.height),
+            &KEY_SHARD_FONTS,
+        )?;
+
+        // Each key shard is laid out as if it were printed on its own (smaller)
+        // page, filling the regions from the top-left corner of the sheet.
+        let region = Page {
+            width: sheet.width / columns as f64,
+            height: sheet.height / rows as f64,
+            margin: sheet.margin,
+        };
+        for (idx, shard) in shards.iter().enumerate() {
+            let (column, row) = (idx % columns, idx / columns);
+            let origin = (
+                region.width * column as f64,
+                sheet.height - region.height * (row + 1) as f64,
+            );
+            current_layer.with_origin(origin, |layer| {
+                draw_key_shard(layer, &region, shard, options)
+            })?;
+        }
+
+        // Cut lines between the regions.
+        let mut dash_pattern = LineDashPattern::default();
+        dash_pattern.dash_1 = Some(6);
+        dash_pattern.gap_1 = Some(4);
+        current_layer.set_outline_color(palette.key_shard_trim.clone());
+        current_layer.set_line_dash_pattern(dash_pattern);
+        let columns = (1..columns).map(|column| {
+            let x = region.width * column as f64;
+            (Point::new(x, Mm(0.0)), Point::new(x, sheet.height))
+        });
+        let rows = (1..rows).map(|row| {
+            let y = region.height * row as f64;
+            (Point::new(Mm(0.0), y), Point::new(sheet.width, y))
+        });
+        for (start, end) in columns.chain(rows) {
+            current_layer.add_shape(Line {
+                points: vec![(start, false), (end, false)],
+                is_closed: false,
+                has_fill: false,
+                has_stroke: true,
+                is_clipping_path: false,
+            });
+        }
+
+        current_layer.finish()
+    }
+}
+
+// The smallest region a key shard is laid out in on a sheet. This is a little
+// smaller than A5, so that two key shards fit on US Letter as well as A4.
+const KEY_SHARD_MIN_REGION: (Mm, Mm) = (Mm(135.0), Mm(200.0));
+
+// The sheet (in whichever orientation fits the most key shards), and the number
+// of columns and rows of key shard regions on it.
+fn sheet_grid(options: &RenderOptions) -> Result<(Page, usize, usize), Error> {
+    let portrait = Page::for_document(options, PaperSize::A4)?;
+    let landscape = Page {
+        width: portrait.height,
+        height: portrait.width,
+        ..portrait
+    };
+    let (min_width, min_height) = KEY_SHARD_MIN_REGION;
+    // Paper smaller than a region holds a single key shard.
+    let grid = |sheet: &Page| {
+        (
+            ((sheet.width.0 / min_width.0) as usize).max(1),
+            ((sheet.height.0 / min_height.0) as usize).max(1),
+        )
+    };
+    let ((portrait_columns, portrait_rows), (landscape_columns, landscape_rows)) =
+        (grid(&portrait), grid(&landscape));
+    Ok(
+        match landscape_columns * landscape_rows > portrait_columns * portrait_rows {
+            true => (landscape, landscape_columns, landscape_rows),
+            false => (portrait, portrait_columns, portrait_rows),
+        },
+    )
+}
+
+impl ToPdf for KeyShardSheet<'_> {
+    fn to_pdf_with_options(&self, options: &RenderOptions) -> Result<PdfDocumentReference, Error> {
+        self.draw::<PdfCanvas>(options)
+    }
+
+    fn to_svg_with_options(&self, options: &RenderOptions) -> Result<Vec<String>, Error> {
+        self.draw::<SvgCanvas>(options)
+    }
+
+    fn qr_codes_to_png_with_options(
+        &self,
+        options: &RenderOptions,
+        dpi: u32,
+    ) -> Result<Vec<Vec<u8>>, Error> {
+        raster::codes_to_png(self.draw::<CodesCanvas>(options)?, dpi)
     }
 }
@@ -0,0 +1,189 @@
+/*
+ * paperback: paper backup generator suitable for long-term storage
+ * Copyright (C) 2018-2022 Aleksa Sarai <cyphar@cyphar.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Translations of the human-readable text printed on documents.
+//!
+//! Each language has a catalog of messages (in `locales/`, written in a subset
+//! of the [Fluent](https://projectfluent.org/) syntax) which is embedded at
+//! build time. Only the instructions and labels are translated -- the codes,
+//! text fallback, identifiers and codewords are the same in every language.
+
+use once_cell::sync::Lazy;
+
+use std::{collections::HashMap, fmt::Display};
+
+/// The language the text of documents is printed in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Language {
+    #[default]
+    English,
+    German,
+    French,
+    Spanish,
+}
+
+impl Language {
+    /// Every supported language.
+    pub const ALL: [Self; 4] = [Self::English, Self::German, Self::French, Self::Spanish];
+
+    /// The ISO 639-1 code of the language.
+    pub fn code(self) -> &'static str {
+        match self {
+            Self::English => "en",
+            Self::German => "de",
+            Self::French => "fr",
+            Self::Spanish => "es",
+        }
+    }
+
+    /// The language with the given ISO 639-1 code.
+    pub fn from_code(code: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|language| language.code().eq_ignore_ascii_case(code))
+    }
+
+    fn catalog_source(self) -> &'static str {
+        match self {
+            Self::English => include_str!("locales/en.ftl"),
+            Self::German => include_str!("locales/de.ftl"),
+            Self::French => include_str!("locales/fr.ftl"),
+            Self::Spanish => include_str!("locales/es.ftl"),
+        }
+    }
+
+    /// The message with the given id in this language, with each `{ $name }`
+    /// replaced by the argument of that name. Messages missing from a catalog
+    /// are printed in English.
+    pub(super) fn message(self, id: &str, args: &[(&str, &dyn Display)]) -> String {
+        let text = CATALOGS[&self]
+            .get(id)
+            .or_else(|| CATALOGS[&Self::English].get(id))
+            .unwrap_or_else(|| panic!("message {:?} missing from the english catalog", id));
+        format_message(text, args)
+    }
+}
+
+type Catalog = HashMap<&'static str, String>;
+
+static CATALOGS: Lazy<HashMap<Language, Catalog>> = Lazy::new(|| {
+    Language::ALL
+        .into_iter()
+        .map(|language| (language, parse_catalog(language.catalog_source())))
+        .collect()
+});
+
+// Parse "id = text" messages. Indented lines continue the message above them
+// (joined with a space), and lines starting with "#" are comments.
+fn parse_catalog(source: &'static str) -> Catalog {
+    let mut catalog = Catalog::new();
+    let mut current: Option<&'static str> = None;
+    for line in source.lines() {
+        if line.trim().is_empty() || line.starts_with('#') {
+            current = None;
+        } else if line.starts_with(char::is_whitespace) {
+            let id = current.expect("continuation line without a message");
+            let text = catalog.get_mut(id).expect("current message is in catalog");
+            if !text.is_empty() {
+                text.push(' ');
+            }
+            text.push_str(line.trim());
+        } else {
+            let (id, text) = line.split_once('=').expect("message line without an id");
+            let id = id.trim();
+            catalog.insert(id, text.trim().to_string());
+            current = Some(id);
+        }
+    }
+    catalog
+}
+
+// Replace each "{ $name }" placeable in text with its argument.
+fn format_message(text: &str, args: &[(&str, &dyn Display)]) -> String {
+    let mut message = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('{') {
+        let end = match rest[start..].find('}') {
+            Some(end) => start + end,
+            None => break,
+        };
+        message.push_str(&rest[..start]);
+        let name = rest[start + 1..end].trim().trim_start_matches('$');
+        match args.iter().find(|(arg, _)| *arg == name) {
+            Some((_, value)) => message.push_str(&value.to_string()),
+            None => message.push_str(&rest[start..=end]),
+        }
+        rest = &rest[end + 1..];
+    }
+    message.push_str(rest);
+    message
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // The names of the arguments used by a message.
+    fn placeables(text: &str) -> Vec<&str> {
+        let mut names = text
+            .split('{')
+            .skip(1)
+            .filter_map(|part| part.split_once('}'))
+            .map(|(name, _)| name.trim())
+            .collect::<Vec<_>>();
+        names.sort_unstable();
+        names
+    }
+
+    #[test]
+    fn catalogs_complete() {
+        let english = &CATALOGS[&Language::English];
+        for language in Language::ALL {
+            let catalog = &CATALOGS[&language];
+            let mut ids = catalog.keys().collect::<Vec<_>>();
+            let mut english_ids = english.keys().collect::<Vec<_>>();
+            ids.sort_unstable();
+            english_ids.sort_unstable();
+            assert_eq!(ids, english_ids, "{:?} catalog messages", language);
+            for (id, text) in catalog {
+                assert_eq!(
+                    placeables(text),
+                    placeables(&english[id]),
+                    "{:?} message {:?}",
+                    language,
+                    id
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn format_messages() {
+        assert_eq!(
+            Language::English.message("held-by-contact", &[("name", &"Alice"), ("contact", &42)]),
+            "Held by Alice (42)."
+        );
+        assert!(Language::German
+            .message("main-document-description", &[("quorum", &3)])
+            .contains("Zusammen mit 3 verschiedenen Schlüsselanteilen"));
+        // Unknown arguments are left as-is.
+        assert_eq!(format_message("a { $b } c", &[]), "a { $b } c");
+        assert_eq!(Language::from_code("FR"), Some(Language::French));
+        assert_eq!(Language::from_code("xx"), None);
+    }
+}
@@ -0,0 +1,113 @@
+# German translations of the text printed on paperback documents (see en.ftl).
+
+## Shared by all documents.
+
+document = Dokument
+shard = Anteil
+checksum = Prüfsumme
+test-run-watermark = TEST — NICHT VERWENDEN
+
+## Main document.
+
+main-document = Hauptdokument
+main-document-description =
+    Dies ist das Hauptdokument einer paperback-Sicherung. Zusammen mit
+    { $quorum } verschiedenen Schlüsselanteilen kann dieses Dokument
+    wiederhergestellt werden. Laden Sie dazu die neueste Version von paperback
+    von cyphar.com/paperback herunter.
+created-review-by = Erstellt am { $created }. Zu überprüfen bis { $review }.
+created = Erstellt am { $created }.
+review-by = Zu überprüfen bis { $review }.
+identity-revoked =
+    Identität { $identity }. Widerrufene Identitäten (deren Dokumenten nicht
+    vertrauen): { $revoked }.
+main-document-page =
+    Seite { $page } von { $pages } (Codes { $first } bis { $last } von
+    { $total }). Scannen Sie die Codes aller Seiten, in beliebiger Reihenfolge.
+data-description =
+    Datenbereich, verschlüsselt mit dem geheimen Schlüssel aus den
+    Schlüsselanteilen.
+data-description-parity =
+    Datenbereich, verschlüsselt mit dem geheimen Schlüssel aus den
+    Schlüsselanteilen (beliebige { $needed } von { $total } Codes benötigt).
+document-checksum-description =
+    Bestätigt, dass das Dokument korrekt eingescannt wurde. Die letzten 8 Zeichen
+    sind die Dokumentkennung.
+
+## Key shards.
+
+key-shard = Schlüsselanteil
+key-shard-intro = Dies ist ein Schlüsselanteil einer paperback-Sicherung.
+key-shard-details = Weitere Informationen unter cyphar.com/paperback.
+held-by-contact = Verwahrt von { $name } ({ $contact }).
+held-by = Verwahrt von { $name }.
+holder-contact = Kontakt des Verwahrers: { $contact }.
+custodian-note = Hinweis: { $note }
+minted-by-expansion =
+    Erstellt am { $date } durch Erweiterung der Schlüsselanteile { $shards }.
+group-member =
+    Mitglied der Gruppe { $group } ({ $members } Mitglieder pro Gruppe und
+    { $groups } Gruppen benötigt).
+key-shard-effort =
+    Wiederherstellung: { $scans } QR-Scans oder ca. { $chars } Zeichen
+    (ca. { $minutes } Min.) von Hand.
+shard-description =
+    Daten des Schlüsselanteils, verschlüsselt mit den Codewörtern.
+shard-description-passphrase =
+    Daten des Schlüsselanteils, verschlüsselt mit den Codewörtern und einer
+    auswendig gelernten Passphrase.
+shard-checksum-description =
+    Bestätigt, dass der Schlüsselanteil korrekt eingescannt wurde.
+codewords = Codewörter
+codewords-description =
+    Verschlüsselt die Daten des Schlüsselanteils. Kann abgeschnitten werden.
+
+## Wiederherstellungsanleitung.
+
+recovery-instructions = Wiederherstellungsanleitung
+recovery-instructions-description =
+    Diese Anleitung erklärt, wie das in der paperback-Sicherung { $document }
+    gespeicherte Geheimnis wiederhergestellt wird. Bewahren Sie sie zusammen mit
+    dem Hauptdokument der Sicherung auf. Technische Kenntnisse sind nicht
+    erforderlich.
+instructions-what = Was ist paperback?
+instructions-what-text =
+    Paperback ist ein Programm zur Sicherung von Geheimnissen (etwa Passwörtern
+    oder Schlüsseln) auf Papier. Das Geheimnis ist verschlüsselt auf dem
+    Hauptdokument gedruckt, und der Schlüssel zum Entschlüsseln ist auf
+    Schlüsselanteile aufgeteilt, die von verschiedenen Personen aufbewahrt
+    werden. Weder das Hauptdokument noch ein einzelner Schlüsselanteil reicht
+    allein aus, um das Geheimnis wiederherzustellen.
+instructions-needed = Was Sie benötigen
+instructions-needed-document =
+    Das Hauptdokument der Sicherung, gekennzeichnet mit der Dokumentkennung
+    { $document }.
+instructions-needed-shards =
+    Mindestens { $quorum } verschiedene Schlüsselanteile der Sicherung (jeweils
+    mit derselben Dokumentkennung gekennzeichnet), einschließlich der auf jedem
+    Schlüsselanteil gedruckten Codewörter.
+instructions-needed-computer =
+    Einen Computer (idealerweise ohne Internetverbindung) und einen Scanner oder
+    eine Kamera zum Lesen der Codes. Der unter jedem Code gedruckte Text kann
+    auch von Hand eingegeben werden.
+instructions-software = Die Software besorgen
+instructions-software-text =
+    Laden Sie die neueste Version von paperback von cyphar.com/paperback
+    herunter. Falls sie dort nicht mehr erhältlich ist: Die Dokumente verwenden
+    Version { $version } des paperback-Formats, das in der
+    paperback-Spezifikation beschrieben ist, sodass ein anderes Programm zur
+    Wiederherstellung geschrieben werden kann.
+instructions-recover = Das Geheimnis wiederherstellen
+instructions-recover-interactive =
+    Führen Sie den folgenden Befehl aus und geben Sie das Hauptdokument und jeden
+    Schlüsselanteil ein, wenn Sie dazu aufgefordert werden (indem Sie die Codes
+    scannen oder den Text darunter abtippen). Das Geheimnis wird in die Datei
+    secret.txt geschrieben.
+instructions-recover-scan =
+    Alternativ können Sie das Hauptdokument und die Schlüsselanteile scannen oder
+    fotografieren und die Codes aus den Bildern lesen lassen (alles, was nicht
+    gelesen werden kann, wird abgefragt):
+instructions-recover-check =
+    Prüfen Sie, dass die von paperback angezeigte Dokumentkennung { $document }
+    lautet. Jeder, der das wiederhergestellte Geheimnis (oder die Dokumente)
+    besitzt, kann es verwenden, bewahren Sie sie daher sicher auf.
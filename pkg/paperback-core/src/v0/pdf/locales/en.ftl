@@ -0,0 +1,106 @@
+# English translations of the text printed on paperback documents.
+#
+# Each message is "id = text", with "{ $name }" replaced by the named argument.
+# Indented lines continue the text of the message above them. Every other
+# catalog must have the same messages (and arguments) as this one.
+
+## Shared by all documents.
+
+document = Document
+shard = Shard
+checksum = Checksum
+test-run-watermark = TEST — DO NOT USE
+
+## Main document.
+
+main-document = Main Document
+main-document-description =
+    This is the main document of a paperback backup. When combined with
+    { $quorum } unique key shards, this document can be recovered. In order to
+    recover this document, download the latest version of paperback from
+    cyphar.com/paperback.
+created-review-by = Created { $created }. Review by { $review }.
+created = Created { $created }.
+review-by = Review by { $review }.
+identity-revoked =
+    Identity { $identity }. Revoked identities (do not trust their documents):
+    { $revoked }.
+main-document-page =
+    Page { $page } of { $pages } (codes { $first } to { $last } of { $total }).
+    Scan the codes of every page, in any order.
+data-description = Data section, encrypted with secret key stored in the key shards.
+data-description-parity =
+    Data section, encrypted with secret key stored in the key shards (any
+    { $needed } of { $total } codes needed).
+document-checksum-description =
+    Verifies the document was scanned correctly. The last 8 characters are the
+    document identifier.
+
+## Key shards.
+
+key-shard = Key Shard
+key-shard-intro = This is a key shard of a paperback backup.
+key-shard-details = See cyphar.com/paperback for more details.
+held-by-contact = Held by { $name } ({ $contact }).
+held-by = Held by { $name }.
+holder-contact = Holder contact: { $contact }.
+custodian-note = Note: { $note }
+minted-by-expansion = Minted on { $date } by expanding key shards { $shards }.
+group-member =
+    Member of group { $group } ({ $members } members needed per group,
+    { $groups } groups needed).
+key-shard-effort =
+    Recovery: { $scans } QR scans, or ~{ $chars } characters (~{ $minutes } min)
+    by hand.
+shard-description = Key shard data, encrypted using the codewords.
+shard-description-passphrase =
+    Key shard data, encrypted using the codewords and a memorised passphrase.
+shard-checksum-description = Verifies the key shard was scanned correctly.
+codewords = Codewords
+codewords-description = Encrypts the key shard data. Can be optionally cut off.
+
+## Recovery instructions.
+
+recovery-instructions = Recovery Instructions
+recovery-instructions-description =
+    These instructions explain how to recover the secret stored in the
+    paperback backup { $document }. Keep them together with the main document
+    of the backup. No technical knowledge is needed to follow them.
+instructions-what = What is paperback?
+instructions-what-text =
+    Paperback is a program for backing up secrets (such as passwords or keys)
+    on paper. The secret is encrypted and printed on the main document, and the
+    key needed to decrypt it is split into key shards which are held by
+    different people. Neither the main document nor a single key shard can be
+    used to recover the secret on its own.
+instructions-needed = What you need
+instructions-needed-document =
+    The main document of the backup, labelled with the document identifier
+    { $document }.
+instructions-needed-shards =
+    At least { $quorum } different key shards of the backup (each labelled with
+    the same document identifier), including the codewords printed on each key
+    shard.
+instructions-needed-computer =
+    A computer (ideally one which is not connected to the internet), and a
+    scanner or camera to read the codes. The text printed below each code can
+    also be typed in by hand.
+instructions-software = Getting the software
+instructions-software-text =
+    Download the latest version of paperback from cyphar.com/paperback. If it
+    is no longer available, the documents use version { $version } of the
+    paperback format, which is described in the paperback specification so that
+    another program can be written to recover them.
+instructions-recover = Recovering the secret
+instructions-recover-interactive =
+    Run the following command, and enter the main document and each key shard
+    when asked (by scanning the codes or typing in the text below them). The
+    secret is written to the file secret.txt.
+instructions-recover-scan =
+    Alternatively, scan or photograph the main document and key shards, and
+    read the codes from the images (anything which cannot be read is asked
+    for):
+instructions-recover-check =
+    Check that the document identifier shown by paperback is { $document }.
+    Anyone with the recovered secret (or with the documents) can use it, so
+    keep them safe.
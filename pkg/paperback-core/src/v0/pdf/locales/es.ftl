@@ -0,0 +1,112 @@
+# Spanish translations of the text printed on paperback documents (see en.ftl).
+
+## Shared by all documents.
+
+document = Documento
+shard = Fragmento
+checksum = Suma de verificación
+test-run-watermark = PRUEBA — NO USAR
+
+## Main document.
+
+main-document = Documento principal
+main-document-description =
+    Este es el documento principal de una copia de seguridad de paperback.
+    Combinado con { $quorum } fragmentos de clave distintos, este documento
+    puede recuperarse. Para recuperar este documento, descargue la última
+    versión de paperback desde cyphar.com/paperback.
+created-review-by = Creado el { $created }. Revisar antes del { $review }.
+created = Creado el { $created }.
+review-by = Revisar antes del { $review }.
+identity-revoked =
+    Identidad { $identity }. Identidades revocadas (no confíe en sus
+    documentos): { $revoked }.
+main-document-page =
+    Página { $page } de { $pages } (códigos { $first } a { $last } de
+    { $total }). Escanee los códigos de todas las páginas, en cualquier orden.
+data-description =
+    Sección de datos, cifrada con la clave secreta guardada en los fragmentos de
+    clave.
+data-description-parity =
+    Sección de datos, cifrada con la clave secreta guardada en los fragmentos de
+    clave (se necesitan { $needed } códigos cualesquiera de { $total }).
+document-checksum-description =
+    Verifica que el documento se escaneó correctamente. Los últimos 8 caracteres
+    son el identificador del documento.
+
+## Key shards.
+
+key-shard = Fragmento de clave
+key-shard-intro =
+    Este es un fragmento de clave de una copia de seguridad de paperback.
+key-shard-details = Más información en cyphar.com/paperback.
+held-by-contact = En posesión de { $name } ({ $contact }).
+held-by = En posesión de { $name }.
+holder-contact = Contacto del custodio: { $contact }.
+custodian-note = Nota: { $note }
+minted-by-expansion =
+    Creado el { $date } ampliando los fragmentos de clave { $shards }.
+group-member =
+    Miembro del grupo { $group } (se necesitan { $members } miembros por grupo y
+    { $groups } grupos).
+key-shard-effort =
+    Recuperación: { $scans } escaneos QR, o ~{ $chars } caracteres
+    (~{ $minutes } min) a mano.
+shard-description =
+    Datos del fragmento de clave, cifrados con las palabras clave.
+shard-description-passphrase =
+    Datos del fragmento de clave, cifrados con las palabras clave y una frase de
+    contraseña memorizada.
+shard-checksum-description =
+    Verifica que el fragmento de clave se escaneó correctamente.
+codewords = Palabras clave
+codewords-description =
+    Cifra los datos del fragmento de clave. Se puede recortar.
+
+## Instrucciones de recuperación.
+
+recovery-instructions = Instrucciones de recuperación
+recovery-instructions-description =
+    Estas instrucciones explican cómo recuperar el secreto almacenado en la
+    copia de seguridad de paperback { $document }. Guárdelas junto con el
+    documento principal de la copia de seguridad. No se necesitan conocimientos
+    técnicos para seguirlas.
+instructions-what = ¿Qué es paperback?
+instructions-what-text =
+    Paperback es un programa para hacer copias de seguridad de secretos (como
+    contraseñas o claves) en papel. El secreto está cifrado e impreso en el
+    documento principal, y la clave necesaria para descifrarlo está dividida en
+    fragmentos de clave que guardan distintas personas. Ni el documento
+    principal ni un solo fragmento de clave bastan para recuperar el secreto.
+instructions-needed = Qué necesita
+instructions-needed-document =
+    El documento principal de la copia de seguridad, marcado con el
+    identificador de documento { $document }.
+instructions-needed-shards =
+    Al menos { $quorum } fragmentos de clave distintos de la copia de seguridad
+    (cada uno marcado con el mismo identificador de documento), incluidas las
+    palabras clave impresas en cada fragmento de clave.
+instructions-needed-computer =
+    Un ordenador (idealmente sin conexión a internet), y un escáner o una
+    cámara para leer los códigos. El texto impreso debajo de cada código
+    también se puede escribir a mano.
+instructions-software = Obtener el programa
+instructions-software-text =
+    Descargue la última versión de paperback desde cyphar.com/paperback. Si ya
+    no está disponible, los documentos usan la versión { $version } del
+    formato de paperback, que se describe en la especificación de paperback
+    para que se pueda escribir otro programa que los recupere.
+instructions-recover = Recuperar el secreto
+instructions-recover-interactive =
+    Ejecute el siguiente comando, e introduzca el documento principal y cada
+    fragmento de clave cuando se le pida (escaneando los códigos o escribiendo
+    el texto impreso debajo de ellos). El secreto se escribe en el archivo
+    secret.txt.
+instructions-recover-scan =
+    También puede escanear o fotografiar el documento principal y los
+    fragmentos de clave, y leer los códigos de las imágenes (se le pedirá todo
+    lo que no se pueda leer):
+instructions-recover-check =
+    Compruebe que el identificador de documento que muestra paperback es
+    { $document }. Cualquiera que tenga el secreto recuperado (o los
+    documentos) puede usarlo, así que guárdelos en un lugar seguro.
@@ -0,0 +1,109 @@
+# French translations of the text printed on paperback documents (see en.ftl).
+
+## Shared by all documents.
+
+document = Document
+shard = Part
+checksum = Somme de contrôle
+test-run-watermark = TEST — NE PAS UTILISER
+
+## Main document.
+
+main-document = Document principal
+main-document-description =
+    Ceci est le document principal d'une sauvegarde paperback. Combiné à
+    { $quorum } parts de clé distinctes, ce document peut être restauré. Pour
+    restaurer ce document, téléchargez la dernière version de paperback sur
+    cyphar.com/paperback.
+created-review-by = Créé le { $created }. À vérifier avant le { $review }.
+created = Créé le { $created }.
+review-by = À vérifier avant le { $review }.
+identity-revoked =
+    Identité { $identity }. Identités révoquées (ne faites pas confiance à leurs
+    documents) : { $revoked }.
+main-document-page =
+    Page { $page } sur { $pages } (codes { $first } à { $last } sur { $total }).
+    Scannez les codes de toutes les pages, dans n'importe quel ordre.
+data-description =
+    Section de données, chiffrée avec la clé secrète conservée dans les parts de
+    clé.
+data-description-parity =
+    Section de données, chiffrée avec la clé secrète conservée dans les parts de
+    clé ({ $needed } codes quelconques sur { $total } nécessaires).
+document-checksum-description =
+    Vérifie que le document a été numérisé correctement. Les 8 derniers
+    caractères sont l'identifiant du document.
+
+## Key shards.
+
+key-shard = Part de clé
+key-shard-intro = Ceci est une part de clé d'une sauvegarde paperback.
+key-shard-details = Plus d'informations sur cyphar.com/paperback.
+held-by-contact = Détenue par { $name } ({ $contact }).
+held-by = Détenue par { $name }.
+holder-contact = Contact du détenteur : { $contact }.
+custodian-note = Remarque : { $note }
+minted-by-expansion =
+    Créée le { $date } par extension des parts de clé { $shards }.
+group-member =
+    Membre du groupe { $group } ({ $members } membres nécessaires par groupe,
+    { $groups } groupes nécessaires).
+key-shard-effort =
+    Restauration : { $scans } scans QR, ou ~{ $chars } caractères
+    (~{ $minutes } min) à la main.
+shard-description = Données de la part de clé, chiffrées avec les mots de code.
+shard-description-passphrase =
+    Données de la part de clé, chiffrées avec les mots de code et une phrase
+    secrète mémorisée.
+shard-checksum-description =
+    Vérifie que la part de clé a été numérisée correctement.
+codewords = Mots de code
+codewords-description =
+    Chiffre les données de la part de clé. Peut être découpé si besoin.
+
+## Instructions de restauration.
+
+recovery-instructions = Instructions de restauration
+recovery-instructions-description =
+    Ces instructions expliquent comment restaurer le secret stocké dans la
+    sauvegarde paperback { $document }. Conservez-les avec le document principal
+    de la sauvegarde. Aucune connaissance technique n'est nécessaire pour les
+    suivre.
+instructions-what = Qu'est-ce que paperback ?
+instructions-what-text =
+    Paperback est un programme de sauvegarde de secrets (tels que des mots de
+    passe ou des clés) sur papier. Le secret est chiffré et imprimé sur le
+    document principal, et la clé nécessaire pour le déchiffrer est divisée en
+    parts de clé confiées à différentes personnes. Ni le document principal ni
+    une seule part de clé ne suffisent à restaurer le secret.
+instructions-needed = Ce dont vous avez besoin
+instructions-needed-document =
+    Le document principal de la sauvegarde, portant l'identifiant de document
+    { $document }.
+instructions-needed-shards =
+    Au moins { $quorum } parts de clé distinctes de la sauvegarde (portant
+    chacune le même identifiant de document), avec les mots de code imprimés sur
+    chaque part de clé.
+instructions-needed-computer =
+    Un ordinateur (idéalement non connecté à internet), et un scanner ou un
+    appareil photo pour lire les codes. Le texte imprimé sous chaque code peut
+    aussi être saisi à la main.
+instructions-software = Obtenir le logiciel
+instructions-software-text =
+    Téléchargez la dernière version de paperback sur cyphar.com/paperback. Si
+    elle n'y est plus disponible, les documents utilisent la version
+    { $version } du format paperback, décrit dans la spécification de
+    paperback afin qu'un autre programme puisse être écrit pour les restaurer.
+instructions-recover = Restaurer le secret
+instructions-recover-interactive =
+    Exécutez la commande suivante, et saisissez le document principal et chaque
+    part de clé lorsqu'ils sont demandés (en scannant les codes ou en tapant le
+    texte imprimé en dessous). Le secret est écrit dans le fichier secret.txt.
+instructions-recover-scan =
+    Vous pouvez aussi numériser ou photographier le document principal et les
+    parts de clé, et lire les codes à partir des images (tout ce qui ne peut
+    pas être lu est demandé) :
+instructions-recover-check =
+    Vérifiez que l'identifiant de document affiché par paperback est
+    { $document }. Toute personne disposant du secret restauré (ou des
+    documents) peut l'utiliser, gardez-les donc en lieu sûr.
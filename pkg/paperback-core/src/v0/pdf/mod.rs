@@ -16,10 +16,27 @@
  * along with this program.  If not, see <https://www.gnu.org/licenses/>.
  */
 
+mod canvas;
 pub mod generate;
+mod i18n;
+pub mod preview;
 pub mod qr;
+mod raster;
+pub mod selftest;
+pub mod sink;
+pub mod thumbnail;
 
-pub use generate::ToPdf;
+pub use generate::{
+    KeyShardSheet, OutputFormat, PaperSize, QrErrorCorrection, RecoveryInstructions, RenderOptions,
+    RenderStyle, Symbology, ToPdf,
+};
+pub use i18n::Language;
+pub use preview::{ArtifactLayout, CodeLayout, LayoutReport, RecoveryEffort, Renderer};
+pub use selftest::{self_test, SelfTestReport};
+pub use sink::{DirectorySink, MemorySink, RenderSink, StreamSink, ZipSink};
+pub use thumbnail::Thumbnail;
+
+use crate::{v0::ParseError, ErrorCode};
 
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
@@ -36,7 +53,7 @@ pub enum Error {
     ParseRawData(String),
 
     #[error("qr code data parsing error: {0}")]
-    ParseQrData(String),
+    ParseQrData(ParseError),
 
     #[error("qr code generation error: {0}")]
     GenerateQr(#[from] qrcode::types::QrError),
@@ -50,10 +67,40 @@ pub enum Error {
     #[error("pdf generation error: {0}")]
     GeneratePdf(#[from] printpdf::Error),
 
+    #[error("failed to write rendered artifact: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("backup self-test failed: {0}")]
+    SelfTest(String),
+
+    #[error("failed to rasterise image: {0}")]
+    Rasterise(String),
+
     #[error("miscellaneous error: {0}")]
     OtherError(String),
 }
 
+impl Error {
+    /// The stable code of this error.
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Self::MismatchedQrCode => ErrorCode::new(500, "mismatched-qr-code"),
+            Self::MissingQrSegment { .. } => ErrorCode::new(501, "missing-qr-segment"),
+            Self::WrongPaperbackVersion { .. } => ErrorCode::new(502, "wrong-paperback-version"),
+            Self::ParseRawData(_) => ErrorCode::new(503, "parse-raw-data"),
+            Self::ParseQrData(_) => ErrorCode::new(504, "parse-qr-data"),
+            Self::GenerateQr(_) => ErrorCode::new(505, "generate-qr"),
+            Self::TooManyCodes(_) => ErrorCode::new(506, "too-many-codes"),
+            Self::ParseSvg(_) => ErrorCode::new(507, "parse-svg"),
+            Self::GeneratePdf(_) => ErrorCode::new(508, "generate-pdf"),
+            Self::Io(_) => ErrorCode::new(509, "io"),
+            Self::SelfTest(_) => ErrorCode::new(510, "self-test"),
+            Self::Rasterise(_) => ErrorCode::new(511, "rasterise"),
+            Self::OtherError(_) => ErrorCode::new(599, "pdf-other"),
+        }
+    }
+}
+
 // While counter-intuitive, numerical codes give us almost identical density to
 // binary (which we can't use due to issues with copy-paste, null bytes, and
 // dodgy readers).
@@ -0,0 +1,473 @@
+/*
+ * paperback: paper backup generator suitable for long-term storage
+ * Copyright (C) 2018-2022 Aleksa Sarai <cyphar@cyphar.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use crate::v0::{
+    armor,
+    pdf::{
+        generate::{
+            Page, MAIN_DOCUMENT_CHECKSUM_QR_FRACTION, MAIN_DOCUMENT_CODES_PER_PAGE,
+            MAIN_DOCUMENT_CODES_PER_ROW, MAIN_DOCUMENT_DATA_TOP,
+        },
+        qr::{self, Code, PartType},
+        Error, PaperSize, RenderOptions, Thumbnail, ToPdf,
+    },
+    Backup, EncryptedKeyShard, KeyShardCodewords, ToWire, Warnings,
+};
+
+use std::ops::Add;
+
+use printpdf::Mm;
+
+// The quiet zone added around rendered QR codes (in modules, on each side).
+const QUIET_ZONE_MODULES: usize = 4;
+
+// Printed QR codes with modules of at least this size are reliably scanned by
+// phone cameras and consumer flatbed scanners...
+const RELIABLE_MODULE_SIZE: Mm = Mm(0.5);
+// ... while codes with modules smaller than this frequently fail to scan.
+const UNRELIABLE_MODULE_SIZE: Mm = Mm(0.25);
+
+// Typing speed assumed when estimating how long manual transcription takes.
+// Unlike prose, every character of the armored data has to be read, typed and
+// checked individually.
+const TRANSCRIPTION_CHARS_PER_MINUTE: f64 = 60.0;
+
+/// An estimate of the effort needed to enter printed documents during
+/// recovery, either by scanning their QR codes or by typing them in by hand.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RecoveryEffort {
+    /// The number of QR codes to scan.
+    pub qr_scans: usize,
+    /// The number of characters to type if the QR codes cannot be scanned
+    /// (the armored text printed next to each code, and any codewords).
+    pub transcription_chars: usize,
+}
+
+impl RecoveryEffort {
+    fn new<D: AsRef<[u8]>>(qr_scans: usize, transcribed: &[D], codewords: &[String]) -> Self {
+        let armored_chars = transcribed
+            .iter()
+            .flat_map(|data| armor::encode_lines(data.as_ref()))
+            .map(|line| line.len())
+            .sum::<usize>();
+        let codeword_chars =
+            codewords.iter().map(String::len).sum::<usize>() + codewords.len().saturating_sub(1);
+        Self {
+            qr_scans,
+            transcription_chars: armored_chars + codeword_chars,
+        }
+    }
+
+    /// The effort needed to enter a key shard (including its codewords).
+    pub fn key_shard(shard: &EncryptedKeyShard, codewords: &KeyShardCodewords) -> Self {
+        let (data, checksum) = (shard.to_wire(), shard.checksum().to_bytes());
+        Self::new(2, &[data, checksum], codewords)
+    }
+
+    /// The expected number of minutes needed to transcribe the documents by
+    /// hand.
+    pub fn transcription_minutes(&self) -> f64 {
+        self.transcription_chars as f64 / TRANSCRIPTION_CHARS_PER_MINUTE
+    }
+
+    /// The effort needed to enter `n` documents which each need this effort.
+    pub fn times(self, n: u32) -> Self {
+        Self {
+            qr_scans: self.qr_scans * n as usize,
+            transcription_chars: self.transcription_chars * n as usize,
+        }
+    }
+}
+
+impl Add for RecoveryEffort {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        Self {
+            qr_scans: self.qr_scans + rhs.qr_scans,
+            transcription_chars: self.transcription_chars + rhs.transcription_chars,
+        }
+    }
+}
+
+/// Layout metrics for a single printed QR code.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CodeLayout {
+    /// The QR code version (1 to 40), or 0 for Data Matrix codes.
+    pub version: i16,
+    /// The width of the code in modules (excluding the quiet zone).
+    pub modules: usize,
+    /// The printed width of the code (including the quiet zone).
+    pub size: Mm,
+    /// The number of bytes of data stored in the code.
+    pub data_bytes: usize,
+}
+
+impl CodeLayout {
+    pub(super) fn new(code: &Code, size: Mm, data_bytes: usize) -> Self {
+        Self {
+            version: code.version(),
+            modules: code.width(),
+            size,
+            data_bytes,
+        }
+    }
+
+    /// The printed size of a single module of the code.
+    pub fn module_size(&self) -> Mm {
+        self.size / (self.modules + 2 * QUIET_ZONE_MODULES) as f64
+    }
+
+    /// A heuristic estimate (from `0.0` to `1.0`) of how reliably the printed
+    /// code can be scanned, based on the printed module size.
+    pub fn reliability(&self) -> f64 {
+        let Mm(module) = self.module_size();
+        let (Mm(low), Mm(high)) = (UNRELIABLE_MODULE_SIZE, RELIABLE_MODULE_SIZE);
+        ((module - low) / (high - low)).clamp(0.0, 1.0)
+    }
+}
+
+/// Layout metrics for one printed document.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ArtifactLayout {
+    /// The paper size the document is laid out for.
+    pub paper_size: PaperSize,
+    /// Number of pages needed to print the document.
+    pub pages: usize,
+    /// Every QR code in the document.
+    pub codes: Vec<CodeLayout>,
+    /// The effort needed to enter the document during recovery.
+    pub effort: RecoveryEffort,
+}
+
+impl ArtifactLayout {
+    /// Total number of bytes of data stored in the document's QR codes.
+    pub fn data_bytes(&self) -> usize {
+        self.codes.iter().map(|code| code.data_bytes).sum()
+    }
+
+    /// Bytes of data stored per square centimetre of printed QR code.
+    pub fn density(&self) -> f64 {
+        let area: f64 = self
+            .codes
+            .iter()
+            .map(|code| (code.size.0 / 10.0).powi(2))
+            .sum();
+        match area {
+            a if a > 0.0 => self.data_bytes() as f64 / a,
+            _ => 0.0,
+        }
+    }
+
+    /// A heuristic estimate (from `0.0` to `1.0`) of how reliably the document
+    /// can be scanned. Every code must be scanned, so this is the reliability
+    /// of the least reliable code.
+    pub fn reliability(&self) -> f64 {
+        self.codes
+            .iter()
+            .map(CodeLayout::reliability)
+            .reduce(f64::min)
+            .unwrap_or(1.0)
+    }
+}
+
+/// Layout metrics for the documents of a backup.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LayoutReport {
+    /// The main document.
+    pub main_document: ArtifactLayout,
+    /// Each key shard (all key shards of a backup have the same layout).
+    pub key_shard: ArtifactLayout,
+    /// The number of key shards needed to recover the backup.
+    pub quorum_size: u32,
+    /// Warnings which would be produced when rendering the documents.
+    pub warnings: Warnings,
+}
+
+impl LayoutReport {
+    /// Returns whether the documents can be rendered with this version of
+    /// paperback (each key shard must fit on a single page, while the main
+    /// document can span several pages).
+    pub fn is_renderable(&self) -> bool {
+        self.key_shard.pages == 1
+    }
+
+    /// A heuristic estimate (from `0.0` to `1.0`) of how reliably all of the
+    /// documents can be scanned.
+    pub fn reliability(&self) -> f64 {
+        self.main_document
+            .reliability()
+            .min(self.key_shard.reliability())
+    }
+
+    /// The total effort needed to enter the main document and a quorum of
+    /// key shards during recovery.
+    pub fn recovery_effort(&self) -> RecoveryEffort {
+        self.main_document.effort + self.key_shard.effort.times(self.quorum_size)
+    }
+
+    /// A redacted thumbnail of the main document.
+    pub fn main_document_thumbnail(&self) -> Thumbnail {
+        Thumbnail::main_document(&self.main_document)
+    }
+
+    /// A redacted thumbnail of each key shard.
+    pub fn key_shard_thumbnail(&self) -> Thumbnail {
+        Thumbnail::key_shard(&self.key_shard)
+    }
+}
+
+/// Computes the layout of rendered documents.
+pub struct Renderer;
+
+impl Renderer {
+    /// Compute the layout metrics of the documents that would be rendered for
+    /// a backup with the given options, without producing any files.
+    pub fn preview(backup: &Backup, options: &RenderOptions) -> Result<LayoutReport, Error> {
+        let main_document = backup.main_document();
+
+        // Main document.
+        let (data_codes, data_parts) =
+            qr::generate_codes(PartType::MainDocumentData, main_document.to_wire(), options)?;
+        let paper_size = options.paper_size.unwrap_or(PaperSize::A4);
+        let page = Page::for_document(options, PaperSize::A4)?;
+        let rows = MAIN_DOCUMENT_CODES_PER_PAGE / MAIN_DOCUMENT_CODES_PER_ROW as usize;
+        let data_size = page.data_code_size(MAIN_DOCUMENT_DATA_TOP, rows);
+        let checksum = main_document.checksum().to_bytes();
+        let checksum_size = page.width * MAIN_DOCUMENT_CHECKSUM_QR_FRACTION;
+
+        let mut codes = data_codes
+            .iter()
+            .zip(&data_parts)
+            .map(|(code, part)| CodeLayout::new(code, data_size, part.len()))
+            .collect::<Vec<_>>();
+        codes.push(CodeLayout::new(
+            &qr::generate_one_code(&checksum, options)?,
+            checksum_size,
+            checksum.len(),
+        ));
+        let main_document_layout = ArtifactLayout {
+            paper_size,
+            pages: data_codes
+                .len()
+                .div_ceil(MAIN_DOCUMENT_CODES_PER_PAGE)
+                .max(1),
+            effort: RecoveryEffort::new(codes.len(), &[main_document.to_wire(), checksum], &[]),
+            codes,
+        };
+
+        // Key shards. All shards are padded to the same size, so we just need
+        // to lay out a (throwaway) sample shard.
+        let (shard, codewords) = backup
+            .next_shard()
+            .and_then(|shard| shard.encrypt())
+            .map_err(|err| Error::OtherError(format!("failed to create shard: {}", err)))?;
+        let shard = match options.key_shard_size {
+            Some(target_len) => shard
+                .repad(&codewords, target_len)
+                .map_err(|err| Error::OtherError(format!("failed to pad shard: {}", err)))?,
+            None => shard,
+        };
+        let paper_size = options.paper_size.unwrap_or(PaperSize::A5);
+        let shard_size = Page::for_document(options, PaperSize::A5)?.key_shard_code_size();
+        let (shard_data, shard_checksum) = (shard.to_wire(), shard.checksum().to_bytes());
+        let key_shard_layout = ArtifactLayout {
+            paper_size,
+            pages: 1,
+            codes: vec![
+                CodeLayout::new(
+                    &qr::generate_one_code(&shard_data, options)?,
+                    shard_size,
+                    shard_data.len(),
+                ),
+                CodeLayout::new(
+                    &qr::generate_one_code(&shard_checksum, options)?,
+                    shard_size,
+                    shard_checksum.len(),
+                ),
+            ],
+            effort: RecoveryEffort::key_shard(&shard, &codewords),
+        };
+
+        let mut warnings = main_document.warnings(options);
+        warnings.extend((&shard, &codewords).warnings(options));
+
+        Ok(LayoutReport {
+            main_document: main_document_layout,
+            key_shard: key_shard_layout,
+            quorum_size: main_document.quorum_size(),
+            warnings,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::v0::BackupBuilder;
+
+    #[test]
+    fn preview_layout() {
+        let backup = BackupBuilder::new(3).build(vec![0x42; 1000]).unwrap();
+        let report = Renderer::preview(&backup, &RenderOptions::default()).unwrap();
+
+        assert!(report.is_renderable());
+        assert_eq!(report.main_document.pages, 1);
+        // Data codes plus the checksum code.
+        assert!(report.main_document.codes.len() >= 2);
+        assert!(report.main_document.data_bytes() > 1000);
+        assert_eq!(report.key_shard.codes.len(), 2);
+        assert!(report.reliability() > 0.0 && report.reliability() <= 1.0);
+        assert!(report.main_document.density() > 0.0);
+
+        let shard_effort = report.key_shard.effort;
+        assert_eq!(shard_effort.qr_scans, 2);
+        // At least the 24 codewords have to be typed.
+        assert!(shard_effort.transcription_chars > 24 * 3);
+        assert_eq!(
+            report.main_document.effort.qr_scans,
+            report.main_document.codes.len()
+        );
+        let total = report.recovery_effort();
+        assert_eq!(
+            total.qr_scans,
+            report.main_document.effort.qr_scans + 3 * shard_effort.qr_scans
+        );
+        assert!(total.transcription_minutes() > shard_effort.transcription_minutes() * 3.0);
+    }
+
+    #[test]
+    fn preview_paper_size() {
+        let backup = BackupBuilder::new(2).build(vec![0x42; 1000]).unwrap();
+        let report = Renderer::preview(&backup, &RenderOptions::default()).unwrap();
+        assert_eq!(report.main_document.paper_size, PaperSize::A4);
+        assert_eq!(report.key_shard.paper_size, PaperSize::A5);
+
+        let small = Renderer::preview(
+            &backup,
+            &RenderOptions {
+                paper_size: Some(PaperSize::MIN),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(small.main_document.paper_size, PaperSize::MIN);
+        assert_eq!(small.key_shard.paper_size, PaperSize::MIN);
+        // The codes are shrunk to fit on the smaller page.
+        let code_size = |layout: &ArtifactLayout| layout.codes[0].size;
+        assert!(code_size(&small.main_document) < code_size(&report.main_document));
+        assert!(code_size(&small.key_shard) < code_size(&report.key_shard));
+        for thumbnail in [small.main_document_thumbnail(), small.key_shard_thumbnail()] {
+            assert_eq!(
+                (thumbnail.width, thumbnail.height),
+                PaperSize::MIN.dimensions()
+            );
+            for region in &thumbnail.regions {
+                assert!(region.x + region.width <= thumbnail.width);
+                assert!(region.y + region.height <= thumbnail.height);
+            }
+        }
+    }
+
+    #[test]
+    fn recovery_effort() {
+        let effort = RecoveryEffort::new(
+            1,
+            &[[0u8; 10]],
+            &["abandon".to_string(), "ability".to_string()],
+        );
+        assert_eq!(effort.qr_scans, 1);
+        let armored = armor::encode_lines(&[0; 10])
+            .map(|l| l.len())
+            .sum::<usize>();
+        assert_eq!(
+            effort.transcription_chars,
+            armored + "abandon ability".len()
+        );
+        assert_eq!(effort.times(3).qr_scans, 3);
+        assert_eq!(
+            (effort + effort).transcription_chars,
+            2 * effort.transcription_chars
+        );
+        let slow = RecoveryEffort {
+            qr_scans: 0,
+            transcription_chars: 120,
+        };
+        assert_eq!(slow.transcription_minutes(), 2.0);
+    }
+
+    #[test]
+    fn main_document_code_layouts() {
+        let backup = BackupBuilder::new(2).build(vec![0x42; 1000]).unwrap();
+        let main_document = backup.main_document();
+        let options = RenderOptions::default();
+        let layouts = main_document.qr_code_layouts(&options).unwrap();
+        assert_eq!(
+            layouts.len(),
+            main_document.qr_code_data(&options).unwrap().len()
+        );
+        // On A4, three data codes fill the width inside the margins.
+        for layout in &layouts {
+            assert!((layout.size.0 - 200.0 / 3.0).abs() < 1e-3);
+        }
+
+        // High-contrast documents leave a gap around each code.
+        let inset = main_document
+            .qr_code_layouts(&RenderOptions {
+                style: crate::v0::pdf::RenderStyle::HighContrast,
+                ..Default::default()
+            })
+            .unwrap();
+        assert!((inset[0].size.0 - layouts[0].size.0 * 0.92).abs() < 1e-3);
+    }
+
+    #[test]
+    fn preview_key_shard_size() {
+        let backup = BackupBuilder::new(2).build(b"secret").unwrap();
+        let report = Renderer::preview(&backup, &RenderOptions::default()).unwrap();
+        let padded = Renderer::preview(
+            &backup,
+            &RenderOptions {
+                key_shard_size: Some(1024),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let shard_bytes = |report: &LayoutReport| report.key_shard.codes[0].data_bytes;
+        assert!(shard_bytes(&padded) > shard_bytes(&report));
+        assert!(padded.key_shard.reliability() <= report.key_shard.reliability());
+    }
+
+    #[test]
+    fn code_reliability() {
+        let code = |modules, size| CodeLayout {
+            version: 1,
+            modules,
+            size: Mm(size),
+            data_bytes: 0,
+        };
+        // 0.5mm modules.
+        assert_eq!(code(21, 14.5).reliability(), 1.0);
+        // 0.2mm modules.
+        assert_eq!(code(21, 5.8).reliability(), 0.0);
+        // 0.375mm modules.
+        assert!((code(21, 10.875).reliability() - 0.5).abs() < 1e-9);
+    }
+}
@@ -17,42 +17,75 @@
  */
 
 use crate::v0::{
-    pdf::{Error, QRCODE_MULTIBASE},
-    FromWire, ToWire, PAPERBACK_VERSION,
+    gf256,
+    pdf::{Error, QrErrorCorrection, RenderOptions, Symbology, QRCODE_MULTIBASE},
+    FromWire, ParseError, ParseSection, ToWire, PAPERBACK_VERSION,
 };
 
-use qrcode::QrCode;
+use datamatrix::{DataMatrix, SymbolList};
+use qrcode::{render::svg, types::QrError, EcLevel, QrCode, Version};
+
+use std::fmt::Write;
 use unsigned_varint::encode as varuint_encode;
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub(super) enum PartType {
     MainDocumentData, // 'D'
+    AirGapData,       // 'A'
 }
 
 impl ToWire for PartType {
     fn to_wire(&self) -> Vec<u8> {
         match self {
             Self::MainDocumentData => "D",
+            Self::AirGapData => "A",
         }
         .into()
     }
 }
 
 impl FromWire for PartType {
-    fn from_wire_partial(input: &[u8]) -> Result<(&[u8], Self), String> {
+    fn from_wire_partial(input: &[u8]) -> Result<(&[u8], Self), ParseError> {
         match input.split_first() {
             Some((b'D', input)) => Ok((input, Self::MainDocumentData)),
-            None => Err("".into()), // TODO
-            Some(_) => Err("".into()),
+            Some((b'A', input)) => Ok((input, Self::AirGapData)),
+            found => Err(ParseError::new(
+                ParseSection::Header,
+                input,
+                "a qr code data type ('D' or 'A')",
+                match found {
+                    Some((byte, _)) => format!("byte {:#04x}", byte),
+                    None => "end of input".to_string(),
+                },
+            )),
         }
     }
 }
 
+// Parts of data protected by an outer Reed-Solomon code (see split_data) have
+// this bit set in their version (and store the number of parity parts and the
+// length of the data in their metadata), so that older versions of paperback
+// refuse to join them.
+const PARITY_FLAG: u32 = 1 << 31;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+struct ParityMeta {
+    num_parity_parts: usize,
+    data_len: usize,
+}
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 struct PartMeta {
     version: u32,
     data_type: PartType,
-    num_parts: usize,
+    num_parts: usize,           // number of data parts (excluding parity parts)
+    parity: Option<ParityMeta>, // must be Some iff PARITY_FLAG is set
+}
+
+impl PartMeta {
+    fn total_parts(&self) -> usize {
+        self.num_parts + self.parity.map_or(0, |parity| parity.num_parity_parts)
+    }
 }
 
 impl ToWire for PartMeta {
@@ -74,35 +107,74 @@ impl ToWire for PartMeta {
             &mut varuint_encode::usize_buffer(),
         ));
 
+        // Encode number of parity parts and data length.
+        if let Some(parity) = self.parity {
+            bytes.extend_from_slice(varuint_encode::usize(
+                parity.num_parity_parts,
+                &mut varuint_encode::usize_buffer(),
+            ));
+            bytes.extend_from_slice(varuint_encode::usize(
+                parity.data_len,
+                &mut varuint_encode::usize_buffer(),
+            ));
+        }
+
         bytes
     }
 }
 
 impl FromWire for PartMeta {
-    fn from_wire_partial(input: &[u8]) -> Result<(&[u8], Self), String> {
-        use nom::{combinator::complete, IResult};
+    fn from_wire_partial(input: &[u8]) -> Result<(&[u8], Self), ParseError> {
+        use nom::{combinator::complete, sequence::pair};
         use unsigned_varint::nom as varuint_nom;
 
-        fn parse(input: &[u8]) -> IResult<&[u8], (u32, PartType, usize)> {
-            let (input, version) = varuint_nom::u32(input)?;
-            let (input, data_type) = PartType::from_wire_partial(input).unwrap(); // TODO TODO TODO
-            let (input, num_parts) = varuint_nom::usize(input)?;
-
-            Ok((input, (version, data_type, num_parts)))
+        let start = input;
+        let (input, version) = complete(varuint_nom::u32)(input).map_err(ParseError::expecting(
+            ParseSection::Header,
+            "a qr code version",
+        ))?;
+        let (input, data_type) = PartType::from_wire_partial(input)?;
+        let (input, num_parts) = complete(varuint_nom::usize)(input).map_err(
+            ParseError::expecting(ParseSection::Header, "a qr code part count"),
+        )?;
+        let (input, parity) = match version & PARITY_FLAG {
+            0 => (input, None),
+            _ => {
+                let (input, (num_parity_parts, data_len)) =
+                    complete(pair(varuint_nom::usize, varuint_nom::usize))(input).map_err(
+                        ParseError::expecting(
+                            ParseSection::Header,
+                            "a parity part count and data length",
+                        ),
+                    )?;
+                (
+                    input,
+                    Some(ParityMeta {
+                        num_parity_parts,
+                        data_len,
+                    }),
+                )
+            }
+        };
+
+        let meta = PartMeta {
+            version,
+            data_type,
+            num_parts,
+            parity,
+        };
+
+        if meta.parity.is_some() && (meta.num_parts == 0 || meta.total_parts() > gf256::NUM_POINTS)
+        {
+            return Err(ParseError::new(
+                ParseSection::Header,
+                start,
+                format!("between 1 and {} qr code parts", gf256::NUM_POINTS),
+                format!("{} parts", meta.total_parts()),
+            ));
         }
-        let mut parse = complete(parse);
-
-        let (input, (version, data_type, num_parts)) =
-            parse(input).map_err(|err| format!("{:?}", err))?;
 
-        Ok((
-            input,
-            PartMeta {
-                version,
-                data_type,
-                num_parts,
-            },
-        ))
+        Ok((input, meta))
     }
 }
 
@@ -135,22 +207,20 @@ impl ToWire for Part {
 }
 
 impl FromWire for Part {
-    fn from_wire_partial(input: &[u8]) -> Result<(&[u8], Self), String> {
-        use nom::{bytes::streaming::tag, combinator::complete, IResult};
+    fn from_wire_partial(input: &[u8]) -> Result<(&[u8], Self), ParseError> {
+        use nom::{bytes::complete::tag, combinator::complete};
         use unsigned_varint::nom as varuint_nom;
 
-        fn parse(input: &[u8]) -> IResult<&[u8], (PartMeta, usize, Vec<u8>)> {
-            let (input, _) = tag(b"Pb")(input)?;
-            let (input, meta) = PartMeta::from_wire_partial(input).unwrap(); // TODO TODO TODO
-            let (input, part_idx) = varuint_nom::usize(input)?;
-            // TODO: Is this correct?
-            let (input, data) = (&input[0..0], input.to_vec());
-
-            Ok((input, (meta, part_idx, data)))
-        }
-        let mut parse = complete(parse);
-
-        let (input, (meta, part_idx, data)) = parse(input).map_err(|err| format!("{:?}", err))?;
+        let (input, _) = tag(b"Pb")(input).map_err(ParseError::expecting(
+            ParseSection::Header,
+            "a 'Pb' qr code prefix",
+        ))?;
+        let (input, meta) = PartMeta::from_wire_partial(input)?;
+        let (input, part_idx) = complete(varuint_nom::usize)(input).map_err(
+            ParseError::expecting(ParseSection::Header, "a qr code part index"),
+        )?;
+        // The rest of the qr code is data.
+        let (input, data) = (&input[input.len()..], input.to_vec());
 
         Ok((
             input,
@@ -174,9 +244,32 @@ impl Joiner {
         Self::default()
     }
 
+    /// Returns how many more parts are needed to combine the data. For data
+    /// with an outer Reed-Solomon code, any of the parts can be used.
     pub fn remaining(&self) -> Option<usize> {
-        self.meta
-            .map(|_| self.parts.iter().filter(|v| v.is_none()).count())
+        self.meta.map(|meta| {
+            meta.num_parts
+                .saturating_sub(self.parts.iter().flatten().count())
+        })
+    }
+
+    /// Returns how many data parts (excluding parity parts) the data was
+    /// split into, once any part has been added.
+    pub fn num_parts(&self) -> Option<usize> {
+        self.meta.map(|meta| meta.num_parts)
+    }
+
+    /// Returns the indices (counting from zero) of the parts which have not
+    /// been added yet, once any part has been added. For data with an outer
+    /// Reed-Solomon code this includes the parity parts, though only
+    /// [`Joiner::remaining`] of the missing parts are needed.
+    pub fn missing(&self) -> Vec<usize> {
+        self.parts
+            .iter()
+            .enumerate()
+            .filter(|(_, part)| part.is_none())
+            .map(|(idx, _)| idx)
+            .collect()
     }
 
     pub fn complete(&self) -> bool {
@@ -188,14 +281,14 @@ impl Joiner {
             if meta != part.meta || part.part_idx >= meta.num_parts {
                 return Err(Error::MismatchedQrCode);
             }
-            if part.meta.version != PAPERBACK_VERSION {
+            if part.meta.version & !PARITY_FLAG != PAPERBACK_VERSION {
                 return Err(Error::WrongPaperbackVersion {
                     version: part.meta.version,
                 });
             }
         } else {
             self.meta = Some(part.meta);
-            self.parts = vec![None; part.meta.num_parts];
+            self.parts = vec![None; part.meta.total_parts()];
         }
         if part.part_idx >= self.parts.len() {
             return Err(Error::MismatchedQrCode);
@@ -211,6 +304,11 @@ impl Joiner {
     }
 
     pub fn combine_parts(&self) -> Result<Vec<u8>, Error> {
+        if let Some(meta) = self.meta {
+            if let Some(parity) = meta.parity {
+                return self.recover_parts(meta.num_parts, parity);
+            }
+        }
         let mut data_len = 0usize;
         for (idx, part) in self.parts.iter().enumerate() {
             if let Some(part) = part {
@@ -225,6 +323,48 @@ impl Joiner {
         }
         Ok(bytes)
     }
+
+    // Recover the data from (any) num_parts of the data and parity parts.
+    fn recover_parts(&self, num_parts: usize, parity: ParityMeta) -> Result<Vec<u8>, Error> {
+        let part_size = parity.data_len.div_ceil(num_parts);
+        let points = self
+            .parts
+            .iter()
+            .flatten()
+            .take(num_parts)
+            .map(|part| (part.part_idx as u8, part.data.as_slice()))
+            .collect::<Vec<_>>();
+        if points.len() < num_parts {
+            let idx = self.parts.iter().position(Option::is_none).unwrap_or(0);
+            return Err(Error::MissingQrSegment { idx });
+        }
+        if points.iter().any(|(_, data)| data.len() != part_size) {
+            return Err(Error::MismatchedQrCode);
+        }
+
+        let mut bytes = Vec::with_capacity(num_parts * part_size);
+        for (idx, part) in self.parts[..num_parts].iter().enumerate() {
+            match part {
+                Some(part) => bytes.extend_from_slice(&part.data),
+                None => bytes.append(&mut gf256::interpolate(&points, idx as u8)),
+            }
+        }
+        // Strip the padding of the data parts.
+        Ok(bytes
+            .chunks(part_size.max(1))
+            .zip(data_part_lens(parity.data_len, num_parts))
+            .flat_map(|(part, len)| &part[..len])
+            .copied()
+            .collect())
+    }
+}
+
+// Lengths of the data parts when splitting data_len bytes into num_parts
+// parts (as evenly as possible, so part sizes differ by at most one byte).
+fn data_part_lens(data_len: usize, num_parts: usize) -> impl Iterator<Item = usize> {
+    let (base, extra) = (data_len / num_parts, data_len % num_parts);
+    // The first (len % num_parts) parts get an extra byte.
+    (0..num_parts).map(move |idx| base + usize::from(idx < extra))
 }
 
 const DATA_OVERHEAD: usize = 1 /* multibase header */ +
@@ -232,51 +372,298 @@ const DATA_OVERHEAD: usize = 1 /* multibase header */ +
                              1 /* data type */ +
                              2 * 9 /* 2*varuint length and index */;
 
-// TODO: Make this dynamic based on the error correction mode.
-const MAX_DATA_LENGTH: usize = 926 - DATA_OVERHEAD;
+pub(super) const MAX_DATA_LENGTH: usize = 926 - DATA_OVERHEAD;
+
+impl From<QrErrorCorrection> for EcLevel {
+    fn from(level: QrErrorCorrection) -> Self {
+        match level {
+            QrErrorCorrection::Low => Self::L,
+            QrErrorCorrection::Medium => Self::M,
+            QrErrorCorrection::Quartile => Self::Q,
+            QrErrorCorrection::High => Self::H,
+        }
+    }
+}
+
+/// The most data (excluding the part header) put in each part of data split
+/// across several QR codes, given the maximum QR code version in `options`.
+pub(super) fn max_data_length(options: &RenderOptions) -> Result<usize, Error> {
+    let max_version = match (options.symbology, options.max_qr_version) {
+        (Symbology::Qr, Some(max_version)) => max_version,
+        // The largest Data Matrix code holds more than MAX_DATA_LENGTH.
+        _ => return Ok(MAX_DATA_LENGTH),
+    };
+    if !(1..=40).contains(&max_version) {
+        return Err(QrError::InvalidVersion.into());
+    }
+    let (version, ec_level) = (
+        Version::Normal(max_version.into()),
+        options.qr_error_correction.into(),
+    );
+    // Find the number of digits (the data is encoded in base10) which fit in
+    // a code of this version.
+    let (mut fits, mut too_long) = (0, 7090);
+    while too_long - fits > 1 {
+        let digits = (fits + too_long) / 2;
+        match QrCode::with_version("0".repeat(digits), version, ec_level) {
+            Ok(_) => fits = digits,
+            Err(_) => too_long = digits,
+        }
+    }
+    let max_len = ((fits as f64 * 10f64.log(256.0)) as usize).saturating_sub(DATA_OVERHEAD);
+    if max_len == 0 {
+        return Err(Error::OtherError(format!(
+            "version {} qr codes are too small to hold any data",
+            max_version
+        )));
+    }
+    Ok(max_len.min(MAX_DATA_LENGTH))
+}
+
+// The quiet zone added around rendered Data Matrix codes (in modules, on each
+// side). This is the same as for QR codes, so that codes of both symbologies
+// are laid out the same way.
+const DATA_MATRIX_QUIET_ZONE: usize = 4;
+// The size (in SVG units) of each rendered Data Matrix module.
+const DATA_MATRIX_MODULE_SIZE: usize = 8;
+
+/// A generated code, in the symbology given in the render options.
+pub(super) enum Code {
+    Qr(QrCode),
+    DataMatrix(DataMatrix),
+}
 
-fn split_data<B: AsRef<[u8]>>(data_type: PartType, data: B) -> Vec<Part> {
+impl Code {
+    /// The QR code version, or 0 for Data Matrix codes (which only have a
+    /// size).
+    pub(super) fn version(&self) -> i16 {
+        match self {
+            Self::Qr(code) => match code.version() {
+                Version::Normal(version) | Version::Micro(version) => version,
+            },
+            Self::DataMatrix(_) => 0,
+        }
+    }
+
+    /// The width of the code in modules (excluding the quiet zone).
+    pub(super) fn width(&self) -> usize {
+        match self {
+            Self::Qr(code) => code.width(),
+            Self::DataMatrix(code) => code.bitmap().width(),
+        }
+    }
+
+    /// Render the code (including its quiet zone) as an SVG. Light modules are
+    /// only filled in if `fill_background` is set.
+    pub(super) fn to_svg(&self, fill_background: bool) -> String {
+        match self {
+            Self::Qr(code) => {
+                let mut renderer = code.render::<svg::Color>();
+                if !fill_background {
+                    renderer.light_color(svg::Color("none"));
+                }
+                renderer.build()
+            }
+            Self::DataMatrix(code) => {
+                let bitmap = code.bitmap();
+                let (width, height) = (
+                    (bitmap.width() + 2 * DATA_MATRIX_QUIET_ZONE) * DATA_MATRIX_MODULE_SIZE,
+                    (bitmap.height() + 2 * DATA_MATRIX_QUIET_ZONE) * DATA_MATRIX_MODULE_SIZE,
+                );
+                let mut svg = format!(
+                    r#"<?xml version="1.0" standalone="yes"?><svg xmlns="http://www.w3.org/2000/svg" version="1.1" width="{0}" height="{1}" viewBox="0 0 {0} {1}" shape-rendering="crispEdges">"#,
+                    width, height
+                );
+                if fill_background {
+                    write!(
+                        svg,
+                        r##"<rect x="0" y="0" width="{}" height="{}" fill="#fff"/>"##,
+                        width, height
+                    )
+                    .unwrap();
+                }
+                svg.push_str(r##"<path fill="#000" d=""##);
+                for (x, y) in bitmap.pixels() {
+                    write!(
+                        svg,
+                        "M{} {}h{2}v{2}h-{2}z",
+                        (x + DATA_MATRIX_QUIET_ZONE) * DATA_MATRIX_MODULE_SIZE,
+                        (y + DATA_MATRIX_QUIET_ZONE) * DATA_MATRIX_MODULE_SIZE,
+                        DATA_MATRIX_MODULE_SIZE
+                    )
+                    .unwrap();
+                }
+                svg.push_str(r#""/></svg>"#);
+                svg
+            }
+        }
+    }
+}
+
+// Encode the data as a code in the symbology in options, returning None if it
+// doesn't fit (in a QR code of at most max_version).
+fn encode(
+    data: &[u8],
+    options: &RenderOptions,
+    max_version: Option<u8>,
+) -> Result<Option<Code>, Error> {
+    let data = multibase::encode(QRCODE_MULTIBASE, data);
+    match options.symbology {
+        Symbology::Qr => {
+            match QrCode::with_error_correction_level(data, options.qr_error_correction.into()) {
+                Ok(code) => match (code.version(), max_version) {
+                    (Version::Normal(version), Some(max_version))
+                        if version > max_version.into() =>
+                    {
+                        Ok(None)
+                    }
+                    _ => Ok(Some(Code::Qr(code))),
+                },
+                Err(QrError::DataTooLong) => Ok(None),
+                Err(err) => Err(err.into()),
+            }
+        }
+        // The data is only ever digits, so the only way encoding can fail is
+        // if it doesn't fit in the largest Data Matrix code.
+        Symbology::DataMatrix => Ok(DataMatrix::encode(data.as_bytes(), SymbolList::default())
+            .ok()
+            .map(Code::DataMatrix)),
+    }
+}
+
+/// Split the data into exactly `num_parts` parts, with the data spread as
+/// evenly as possible between them (part sizes differ by at most one byte).
+///
+/// If `num_parity_parts` is non-zero, the data parts are padded to the same
+/// size and followed by that many Reed-Solomon parity parts (the data parts
+/// are the values of a polynomial over GF(2^8) at each part index, and the
+/// parity parts are its values at the following indices), so that the data
+/// can be recovered from any `num_parts` of the parts.
+fn split_data<B: AsRef<[u8]>>(
+    data_type: PartType,
+    data: B,
+    num_parts: usize,
+    num_parity_parts: usize,
+) -> Vec<Part> {
     let data = data.as_ref();
-    let chunks = data.chunks(MAX_DATA_LENGTH).collect::<Vec<_>>();
-    chunks
-        .iter()
+    let meta = PartMeta {
+        version: match num_parity_parts {
+            0 => PAPERBACK_VERSION,
+            _ => PAPERBACK_VERSION | PARITY_FLAG,
+        },
+        data_type,
+        num_parts,
+        parity: match num_parity_parts {
+            0 => None,
+            _ => Some(ParityMeta {
+                num_parity_parts,
+                data_len: data.len(),
+            }),
+        },
+    };
+    let part_size = match meta.parity {
+        None => 0,
+        Some(_) => data.len().div_ceil(num_parts),
+    };
+
+    let mut offset = 0;
+    let mut parts = data_part_lens(data.len(), num_parts)
         .enumerate()
-        .map(|(idx, &chunk)| Part {
-            meta: PartMeta {
-                version: PAPERBACK_VERSION,
-                data_type,
-                num_parts: chunks.len(),
-            },
+        .map(|(idx, len)| {
+            let mut chunk = data[offset..offset + len].to_vec();
+            offset += len;
+            chunk.resize(chunk.len().max(part_size), 0);
+            Part {
+                meta,
+                part_idx: idx,
+                data: chunk,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let points = parts
+        .iter()
+        .map(|part| (part.part_idx as u8, part.data.as_slice()))
+        .collect::<Vec<_>>();
+    let parity_parts = (num_parts..meta.total_parts())
+        .map(|idx| Part {
+            meta,
             part_idx: idx,
-            data: chunk.into(),
+            data: gf256::interpolate(&points, idx as u8),
         })
-        .collect()
+        .collect::<Vec<_>>();
+    parts.extend(parity_parts);
+    parts
 }
 
-pub(super) fn generate_codes<B: AsRef<[u8]>>(
+/// Split the data into the smallest number of balanced parts for which
+/// `encode` succeeds for every part, returning the encoded parts.
+///
+/// We start with the number of parts implied by `max_len`, but because
+/// the part headers are variable-length (and the QR code capacity depends on
+/// the encoded form of the data), a part can still end up being too large. In
+/// that case, rather than failing (or producing an unscannable code) we add
+/// another part and rebalance the data across all of the parts.
+fn balance_parts<B, T, F>(
     data_type: PartType,
     data: B,
-) -> Result<(Vec<QrCode>, Vec<Vec<u8>>), Error> {
-    let codes = split_data(data_type, data)
-        .iter()
-        .map(ToWire::to_wire)
-        .collect::<Vec<_>>();
-    Ok((
-        codes
+    (max_len, num_parity_parts): (usize, usize),
+    mut encode: F,
+) -> Result<Vec<T>, Error>
+where
+    B: AsRef<[u8]>,
+    F: FnMut(&Part) -> Result<Option<T>, Error>,
+{
+    let data = data.as_ref();
+    let min_parts = data.len().div_ceil(max_len).max(1);
+    let max_parts = match num_parity_parts {
+        0 => data.len().max(1),
+        // Every part needs a distinct index in GF(2^8).
+        _ => data
+            .len()
+            .max(1)
+            .min(gf256::NUM_POINTS.saturating_sub(num_parity_parts)),
+    };
+    for num_parts in min_parts..=max_parts {
+        let encoded = split_data(data_type, data, num_parts, num_parity_parts)
             .iter()
-            .map(|data| multibase::encode(QRCODE_MULTIBASE, data))
-            .map(QrCode::new)
-            .collect::<Result<Vec<_>, _>>()?,
-        codes,
-    ))
+            .map(&mut encode)
+            .collect::<Result<Option<Vec<_>>, _>>()?;
+        if let Some(encoded) = encoded {
+            return Ok(encoded);
+        }
+    }
+    Err(Error::TooManyCodes(format!("{:?}", data_type)))
 }
 
-pub(super) fn generate_one_code<B: AsRef<[u8]>>(data: B) -> Result<QrCode, Error> {
+/// Generate the QR codes for `data`, split into as many parts as needed to fit
+/// in codes of the maximum version in `options`. If `options.parity_codes` is
+/// non-zero, that many extra codes are generated so that the data can still be
+/// recovered if up to that many of the codes are lost or damaged.
+pub(super) fn generate_codes<B: AsRef<[u8]>>(
+    data_type: PartType,
+    data: B,
+    options: &RenderOptions,
+) -> Result<(Vec<Code>, Vec<Vec<u8>>), Error> {
+    let max_len = max_data_length(options)?;
+    let parts = balance_parts(data_type, data, (max_len, options.parity_codes), |part| {
+        let wire = part.to_wire();
+        // If the part doesn't fit, try again with more parts.
+        Ok(encode(&wire, options, options.max_qr_version)?.map(|code| (code, wire)))
+    })?;
+    Ok(parts.into_iter().unzip())
+}
+
+pub(super) fn generate_one_code<B: AsRef<[u8]>>(
+    data: B,
+    options: &RenderOptions,
+) -> Result<Code, Error> {
     // NOTE: We don't use a split code for single-QR-code data segments. The
     // reason for this is that the part header takes up space, and it also
     // causes checksums to be encoded differently (meaning that the document ID
-    // would no longer be the last x characters of the hash).
-    Ok(QrCode::new(multibase::encode(QRCODE_MULTIBASE, data))?)
+    // would no longer be the last x characters of the hash). This also means
+    // the maximum version doesn't apply.
+    encode(data.as_ref(), options, None)?.ok_or(Error::GenerateQr(QrError::DataTooLong))
 }
 
 #[cfg(test)]
@@ -287,8 +674,9 @@ mod test {
     use rand::seq::SliceRandom;
 
     #[quickcheck]
-    fn split_join_qr_parts(data: Vec<u8>) -> Result<bool, Error> {
-        let mut parts = split_data(PartType::MainDocumentData, &data);
+    fn split_join_qr_parts(data: Vec<u8>, num_parts: usize) -> Result<bool, Error> {
+        let num_parts = num_parts % 16 + 1;
+        let mut parts = split_data(PartType::MainDocumentData, &data, num_parts, 0);
         let mut joiner = Joiner::new();
 
         parts.shuffle(&mut rand::thread_rng());
@@ -297,4 +685,142 @@ mod test {
         }
         Ok(joiner.combine_parts()? == data)
     }
+
+    #[quickcheck]
+    fn split_join_qr_parts_parity(
+        data: Vec<u8>,
+        num_parts: usize,
+        num_parity_parts: usize,
+    ) -> Result<bool, Error> {
+        let num_parts = num_parts % 16 + 1;
+        let num_parity_parts = num_parity_parts % 4 + 1;
+        let mut parts = split_data(
+            PartType::MainDocumentData,
+            &data,
+            num_parts,
+            num_parity_parts,
+        );
+        let mut joiner = Joiner::new();
+
+        // Lose as many parts as there are parity parts.
+        parts.shuffle(&mut rand::thread_rng());
+        parts.truncate(num_parts);
+        for part in parts {
+            joiner.add_part(Part::from_wire(part.to_wire()).unwrap())?;
+        }
+        Ok(joiner.complete() && joiner.combine_parts()? == data)
+    }
+
+    #[test]
+    fn join_qr_parts_parity_missing() {
+        let data = (0..100).collect::<Vec<u8>>();
+        let parts = split_data(PartType::MainDocumentData, &data, 3, 2);
+        assert_eq!(parts.len(), 5);
+
+        let mut joiner = Joiner::new();
+        for part in parts.into_iter().skip(3) {
+            joiner.add_part(part).unwrap();
+        }
+        assert_eq!(joiner.remaining(), Some(1));
+        assert_eq!(joiner.missing(), vec![0, 1, 2]);
+        assert!(matches!(
+            joiner.combine_parts(),
+            Err(Error::MissingQrSegment { idx: 0 })
+        ));
+    }
+
+    #[quickcheck]
+    fn split_qr_parts_balanced(data: Vec<u8>, num_parts: usize) -> bool {
+        let num_parts = num_parts % 16 + 1;
+        let sizes = split_data(PartType::MainDocumentData, &data, num_parts, 0)
+            .iter()
+            .map(|part| part.data.len())
+            .collect::<Vec<_>>();
+        let (min, max) = (sizes.iter().min().unwrap(), sizes.iter().max().unwrap());
+        sizes.len() == num_parts && max - min <= 1
+    }
+
+    #[test]
+    fn balance_qr_parts_rechunk() {
+        // Pretend the QR codes can only fit MAX_DATA_LENGTH bytes including
+        // headers, so the initial guess of two parts is too large and the data
+        // needs to be rebalanced across three parts.
+        let data = vec![0xaa; 2 * MAX_DATA_LENGTH];
+        let parts = balance_parts(
+            PartType::MainDocumentData,
+            &data,
+            (MAX_DATA_LENGTH, 0),
+            |part| {
+                let wire = part.to_wire();
+                Ok((wire.len() <= MAX_DATA_LENGTH).then_some(wire))
+            },
+        )
+        .unwrap();
+        assert_eq!(parts.len(), 3);
+        assert!(parts.iter().all(|wire| wire.len() <= MAX_DATA_LENGTH));
+    }
+
+    #[test]
+    fn generate_qr_codes_fit() {
+        let data = (0..10_000).map(|i| i as u8).collect::<Vec<_>>();
+        let (codes, parts) =
+            generate_codes(PartType::MainDocumentData, &data, &RenderOptions::default()).unwrap();
+        assert_eq!(codes.len(), parts.len());
+
+        let mut joiner = Joiner::new();
+        for part in parts {
+            joiner.add_part(Part::from_wire(part).unwrap()).unwrap();
+        }
+        assert_eq!(joiner.combine_parts().unwrap(), data);
+    }
+
+    #[test]
+    fn generate_qr_codes_max_version() {
+        let data = (0..2_000).map(|i| i as u8).collect::<Vec<_>>();
+        let options = RenderOptions {
+            qr_error_correction: QrErrorCorrection::High,
+            max_qr_version: Some(10),
+            ..Default::default()
+        };
+        let (codes, parts) = generate_codes(PartType::MainDocumentData, &data, &options).unwrap();
+        let (default_codes, _) =
+            generate_codes(PartType::MainDocumentData, &data, &RenderOptions::default()).unwrap();
+        assert!(codes.len() > default_codes.len());
+        for code in &codes {
+            assert!(matches!(code, Code::Qr(code) if code.error_correction_level() == EcLevel::H));
+            assert!((1..=10).contains(&code.version()));
+        }
+
+        let mut joiner = Joiner::new();
+        for part in parts {
+            joiner.add_part(Part::from_wire(part).unwrap()).unwrap();
+        }
+        assert_eq!(joiner.combine_parts().unwrap(), data);
+
+        for max_qr_version in [0, 1, 41] {
+            let options = RenderOptions {
+                max_qr_version: Some(max_qr_version),
+                ..Default::default()
+            };
+            assert!(generate_codes(PartType::MainDocumentData, &data, &options).is_err());
+        }
+    }
+
+    #[test]
+    fn generate_data_matrix_codes() {
+        let data = (0..5_000).map(|i| i as u8).collect::<Vec<_>>();
+        let options = RenderOptions {
+            symbology: Symbology::DataMatrix,
+            ..Default::default()
+        };
+        let (codes, parts) = generate_codes(PartType::MainDocumentData, &data, &options).unwrap();
+        assert!(codes.iter().all(|code| matches!(code, Code::DataMatrix(_))));
+        assert!(codes[0].to_svg(true).contains(r##"fill="#000""##));
+
+        let mut joiner = Joiner::new();
+        for part in parts {
+            joiner.add_part(Part::from_wire(part).unwrap()).unwrap();
+        }
+        assert_eq!(joiner.combine_parts().unwrap(), data);
+    }
 }
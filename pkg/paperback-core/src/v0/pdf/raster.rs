@@ -0,0 +1,146 @@
+/*
+ * paperback: paper backup generator suitable for long-term storage
+ * Copyright (C) 2018-2022 Aleksa Sarai <cyphar@cyphar.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Rasterising rendered pages and QR codes to PNG images.
+//!
+//! Pages are rasterised from their SVG rendering (see
+//! [`canvas`](super::canvas)), so PNG pages have the same content as the PDF.
+
+use crate::v0::pdf::{canvas::Font, Error};
+
+use once_cell::sync::Lazy;
+use printpdf::Mm;
+use resvg::{tiny_skia, usvg};
+
+use std::sync::Arc;
+
+const MM_PER_INCH: f64 = 25.4;
+const CSS_DPI: f32 = 96.0;
+
+// The fonts used by rendered pages. SVG renderers are not required to load
+// the embedded @font-face fonts, so they are loaded here directly.
+static FONTS: Lazy<Arc<usvg::fontdb::Database>> = Lazy::new(|| {
+    let mut fonts = usvg::fontdb::Database::new();
+    for font in Font::ALL {
+        fonts.load_font_data(font.data().to_vec());
+    }
+    Arc::new(fonts)
+});
+
+fn parse(svg: &str) -> Result<usvg::Tree, Error> {
+    let options = usvg::Options {
+        fontdb: FONTS.clone(),
+        ..Default::default()
+    };
+    usvg::Tree::from_str(svg, &options)
+        .map_err(|err| Error::Rasterise(format!("failed to parse svg: {}", err)))
+}
+
+// Rasterise the image scaled by scale, filling transparent areas with white.
+fn render(tree: &usvg::Tree, scale: f32, dpi: u32) -> Result<Vec<u8>, Error> {
+    let size = tree.size();
+    let (pixel_width, pixel_height) = (
+        (size.width() * scale).ceil() as u32,
+        (size.height() * scale).ceil() as u32,
+    );
+    let mut pixmap = tiny_skia::Pixmap::new(pixel_width, pixel_height).ok_or_else(|| {
+        Error::Rasterise(format!(
+            "invalid image size {}x{} (at {} dpi)",
+            pixel_width, pixel_height, dpi
+        ))
+    })?;
+    pixmap.fill(tiny_skia::Color::WHITE);
+    resvg::render(
+        tree,
+        tiny_skia::Transform::from_scale(scale, scale),
+        &mut pixmap.as_mut(),
+    );
+    pixmap
+        .encode_png()
+        .map_err(|err| Error::Rasterise(format!("failed to encode png: {}", err)))
+}
+
+/// Rasterise an SVG page to a PNG image at the given DPI.
+pub(super) fn page_to_png(svg: &str, dpi: u32) -> Result<Vec<u8>, Error> {
+    // usvg converts the page size (in millimetres) into CSS pixels.
+    render(&parse(svg)?, dpi as f32 / CSS_DPI, dpi)
+}
+
+/// Rasterise each QR code (as collected by a
+/// [`CodesCanvas`](super::canvas::CodesCanvas)) at its printed size and the
+/// given DPI.
+pub(super) fn codes_to_png(codes: Vec<(String, Mm)>, dpi: u32) -> Result<Vec<Vec<u8>>, Error> {
+    codes
+        .iter()
+        .map(|(svg, width)| {
+            let tree = parse(svg)?;
+            let pixel_width = (width.0 / MM_PER_INCH * dpi as f64) as f32;
+            render(&tree, pixel_width / tree.size().width(), dpi)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::v0::{
+        pdf::{MemorySink, OutputFormat, RenderOptions, ToPdf},
+        BackupBuilder,
+    };
+
+    // Width and height of a PNG image (from its IHDR chunk).
+    fn png_size(png: &[u8]) -> (u32, u32) {
+        assert_eq!(png[..8], *b"\x89PNG\r\n\x1a\n");
+        let field = |offset: usize| u32::from_be_bytes(png[offset..offset + 4].try_into().unwrap());
+        (field(16), field(20))
+    }
+
+    #[test]
+    fn render_png() {
+        let backup = BackupBuilder::new(2).build(b"secret").unwrap();
+        let (shard, codewords) = backup.next_shard().unwrap().encrypt().unwrap();
+
+        let mut sink = MemorySink::new();
+        let options = RenderOptions {
+            output_format: OutputFormat::Png { dpi: 50 },
+            ..Default::default()
+        };
+        backup
+            .main_document()
+            .render_to(&mut sink, "main_document.pdf", &options)
+            .unwrap();
+        // A4 at 50 dpi.
+        assert_eq!(png_size(sink.get("main_document.png").unwrap()), (414, 585));
+
+        let options = RenderOptions {
+            output_format: OutputFormat::PngCodes { dpi: 100 },
+            ..Default::default()
+        };
+        (&shard, &codewords)
+            .render_to(&mut sink, "key_shard.pdf", &options)
+            .unwrap();
+        // The data and checksum codes are square, and printed at the same
+        // size.
+        let data_code = png_size(sink.get("key_shard-code1.png").unwrap());
+        assert_eq!(data_code.0, data_code.1);
+        assert_eq!(
+            data_code,
+            png_size(sink.get("key_shard-code2.png").unwrap())
+        );
+        assert_eq!(sink.artifacts().len(), 3);
+    }
+}
@@ -0,0 +1,265 @@
+/*
+ * paperback: paper backup generator suitable for long-term storage
+ * Copyright (C) 2018-2022 Aleksa Sarai <cyphar@cyphar.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! End-to-end self-test of a backup before it is printed.
+//!
+//! The self-test encodes the main document and key shards into the data which
+//! will be printed (the data in each QR code, and the text fallbacks printed
+//! next to them), decodes them again as though they had been entered during
+//! recovery, and then recovers the secret from the decoded documents in
+//! memory. It does not check the rendered documents themselves (the QR code
+//! images or the page layout) -- the printed documents can be checked by
+//! scanning them with `paperback recover --scan`.
+
+use crate::v0::{
+    pdf::{qr::Joiner, Error, RenderOptions, QRCODE_MULTIBASE},
+    DocumentId, EncryptedKeyShard, FromWire, KeyShardCodewords, MainDocument, ShardId,
+    TextEncoding, ToWire, UntrustedQuorum,
+};
+
+use multihash::Multihash;
+
+/// The outcome of a successful [`self_test`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SelfTestReport {
+    /// The ID of the tested main document.
+    pub document_id: DocumentId,
+    /// The number of data QR codes printed on the main document.
+    pub main_document_codes: usize,
+    /// The IDs of the tested key shards.
+    pub shard_ids: Vec<ShardId>,
+    /// The length of the recovered secret (after decompression).
+    pub secret_len: usize,
+}
+
+fn check<S: Into<String>>(ok: bool, message: S) -> Result<(), Error> {
+    match ok {
+        true => Ok(()),
+        false => Err(Error::SelfTest(message.into())),
+    }
+}
+
+// Check that the printed text fallback of a checksum decodes to the checksum.
+fn check_checksum_text(
+    what: &str,
+    checksum: Multihash,
+    encoding: TextEncoding,
+) -> Result<(), Error> {
+    let checksum = checksum.to_bytes();
+    let text = encoding.encode_lines(&checksum).join("\n");
+    let decoded = encoding
+        .decode(text)
+        .map_err(|err| Error::SelfTest(format!("{} checksum text: {}", what, err)))?;
+    check(
+        decoded == checksum,
+        format!("{} checksum text does not match", what),
+    )
+}
+
+// Decode the main document from the data of its printed QR codes, skipping the
+// codes in `skip`.
+fn decode_main_document(codes: &[String], skip: usize) -> Result<MainDocument, Error> {
+    let mut joiner = Joiner::new();
+    for code in &codes[skip..] {
+        joiner.add_qr_part(code)?;
+    }
+    MainDocument::from_wire(joiner.combine_parts()?)
+        .map_err(|err| Error::SelfTest(format!("main document: {}", err)))
+}
+
+/// Simulate the complete recovery of a freshly created backup from the bytes
+/// which will be printed on its documents, without printing anything.
+///
+/// All of the `shards` (with their codewords) are decoded and added to the
+/// quorum, so every key shard is checked. If the key shards are hardened with
+/// a passphrase, it must be given as `passphrase`. If `expected_secret` is
+/// given, the recovered secret must match it (for backups of a bundle, this
+/// is the encoded bundle). The key shards are tested exactly as given, so
+/// they must already be padded as they will be printed.
+pub fn self_test(
+    main_document: &MainDocument,
+    shards: &[(EncryptedKeyShard, KeyShardCodewords)],
+    passphrase: Option<&[u8]>,
+    expected_secret: Option<&[u8]>,
+    options: &RenderOptions,
+) -> Result<SelfTestReport, Error> {
+    // Main document data codes.
+    let codes = main_document.qr_code_data(options)?;
+    let decoded_main = decode_main_document(&codes, 0)?;
+    check(
+        decoded_main.to_wire() == main_document.to_wire(),
+        "decoded main document does not match",
+    )?;
+    // The document must still be readable with all of its parity codes lost.
+    if options.parity_codes > 0 {
+        check(
+            decode_main_document(&codes, options.parity_codes)?.to_wire()
+                == main_document.to_wire(),
+            "main document cannot be recovered from its parity codes",
+        )?;
+    }
+    check_checksum_text(
+        "main document",
+        main_document.checksum(),
+        main_document.text_encoding(),
+    )?;
+
+    let mut quorum = UntrustedQuorum::new();
+    quorum.main_document(decoded_main);
+
+    // Key shards.
+    let mut shard_ids = Vec::with_capacity(shards.len());
+    for (idx, (shard, codewords)) in shards.iter().enumerate() {
+        let what = format!("key shard {}", idx + 1);
+
+        // The key shard's QR code and text fallback.
+        let data = shard.to_wire();
+        let (_, code_data) = multibase::decode(multibase::encode(QRCODE_MULTIBASE, &data))
+            .map_err(|err| Error::SelfTest(format!("{} code: {}", what, err)))?;
+        check(
+            code_data == data,
+            format!("decoded {} does not match", what),
+        )?;
+        let decoded_shard = EncryptedKeyShard::from_wire(code_data)
+            .map_err(|err| Error::SelfTest(format!("{}: {}", what, err)))?;
+        let decoded_shard = match passphrase {
+            Some(passphrase) => decoded_shard.decrypt_with_passphrase(codewords, passphrase),
+            None => decoded_shard.decrypt(codewords),
+        }
+        .map_err(|err| Error::SelfTest(format!("failed to decrypt {}: {}", what, err)))?;
+
+        let encoding = decoded_shard.text_encoding();
+        let text_data = encoding
+            .decode(encoding.encode_lines(&data).join("\n"))
+            .map_err(|err| Error::SelfTest(format!("{} text: {}", what, err)))?;
+        check(text_data == data, format!("{} text does not match", what))?;
+        check_checksum_text(&what, shard.checksum(), encoding)?;
+
+        shard_ids.push(decoded_shard.id());
+        quorum.push_shard(decoded_shard);
+    }
+
+    // Recover the secret.
+    let quorum = quorum
+        .validate()
+        .map_err(|err| Error::SelfTest(format!("quorum failed to validate: {}", err.message)))?;
+    let secret = quorum
+        .recover_document()
+        .map_err(|err| Error::SelfTest(format!("failed to recover secret: {}", err)))?;
+    if let Some(expected_secret) = expected_secret {
+        check(
+            secret == expected_secret,
+            "recovered secret does not match the original secret",
+        )?;
+    }
+
+    Ok(SelfTestReport {
+        document_id: main_document.id(),
+        main_document_codes: codes.len(),
+        shard_ids,
+        secret_len: secret.len(),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::v0::{Argon2Params, BackupBuilder};
+
+    #[test]
+    fn self_test_backup() {
+        let secret = vec![0x42; 2000];
+        let backup = BackupBuilder::new(2).build(&secret).unwrap();
+        let shards = (0..3)
+            .map(|_| backup.next_shard().unwrap().encrypt_padded(512).unwrap())
+            .collect::<Vec<_>>();
+        let options = RenderOptions {
+            parity_codes: 1,
+            ..Default::default()
+        };
+
+        let report = self_test(
+            backup.main_document(),
+            &shards,
+            None,
+            Some(&secret[..]),
+            &options,
+        )
+        .unwrap();
+        assert_eq!(report.document_id, backup.main_document().id());
+        assert_eq!(report.shard_ids.len(), 3);
+        assert_eq!(report.secret_len, secret.len());
+        assert!(report.main_document_codes > 1);
+
+        // A different secret is caught.
+        assert!(matches!(
+            self_test(
+                backup.main_document(),
+                &shards,
+                None,
+                Some(&b"other secret"[..]),
+                &options,
+            ),
+            Err(Error::SelfTest(_))
+        ));
+
+        // As are key shards which cannot be decrypted.
+        let mut shards = shards;
+        shards[0].1.swap(0, 1);
+        assert!(self_test(
+            backup.main_document(),
+            &shards,
+            None,
+            None,
+            &RenderOptions::default(),
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn self_test_passphrase() {
+        let params = Argon2Params {
+            memory_kib: 64,
+            iterations: 1,
+            parallelism: 1,
+        };
+        let backup = BackupBuilder::new(1).build(b"secret").unwrap();
+        let shard = backup.next_shard().unwrap();
+        let shards = [shard
+            .encrypt_padded_with_passphrase(shard.padded_len(), b"passphrase", params)
+            .unwrap()];
+
+        assert!(self_test(
+            backup.main_document(),
+            &shards,
+            Some(&b"passphrase"[..]),
+            Some(&b"secret"[..]),
+            &RenderOptions::default(),
+        )
+        .is_ok());
+        assert!(self_test(
+            backup.main_document(),
+            &shards,
+            None,
+            Some(&b"secret"[..]),
+            &RenderOptions::default(),
+        )
+        .is_err());
+    }
+}
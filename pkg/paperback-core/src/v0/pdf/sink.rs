@@ -0,0 +1,312 @@
+/*
+ * paperback: paper backup generator suitable for long-term storage
+ * Copyright (C) 2018-2022 Aleksa Sarai <cyphar@cyphar.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Destinations for rendered artifacts (PDFs, thumbnails and so on).
+//!
+//! Rendering code writes every artifact to a [`RenderSink`] under a file name
+//! (such as `main_document-<ID>.pdf`), so that the same pipeline can write to
+//! a directory, keep everything in memory (for GUI previews), stream straight
+//! to a printer, or produce a single zip bundle.
+
+use crate::v0::pdf::Error;
+
+use std::{
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+/// A destination for rendered artifacts.
+pub trait RenderSink {
+    /// Write an artifact with the given file name.
+    fn write_artifact(&mut self, name: &str, data: &[u8]) -> Result<(), Error>;
+
+    /// Finish writing artifacts. Sinks which need to write trailing data
+    /// (such as `ZipSink`) do so here, and no more artifacts may be written
+    /// afterwards.
+    fn finish(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+/// Writes each artifact to a file in a directory.
+#[derive(Clone, Debug)]
+pub struct DirectorySink {
+    dir: PathBuf,
+}
+
+impl DirectorySink {
+    pub fn new<P: AsRef<Path>>(dir: P) -> Self {
+        Self {
+            dir: dir.as_ref().to_path_buf(),
+        }
+    }
+}
+
+impl RenderSink for DirectorySink {
+    fn write_artifact(&mut self, name: &str, data: &[u8]) -> Result<(), Error> {
+        Ok(fs::write(self.dir.join(name), data)?)
+    }
+}
+
+/// Keeps every artifact in memory.
+#[derive(Clone, Debug, Default)]
+pub struct MemorySink {
+    artifacts: Vec<(String, Vec<u8>)>,
+}
+
+impl MemorySink {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The artifacts written so far (as `(name, data)` pairs), in the order
+    /// they were written.
+    pub fn artifacts(&self) -> &[(String, Vec<u8>)] {
+        &self.artifacts
+    }
+
+    /// Returns the data of the artifact with the given name.
+    pub fn get(&self, name: &str) -> Option<&[u8]> {
+        self.artifacts
+            .iter()
+            .find(|(artifact, _)| artifact == name)
+            .map(|(_, data)| data.as_slice())
+    }
+
+    pub fn into_artifacts(self) -> Vec<(String, Vec<u8>)> {
+        self.artifacts
+    }
+}
+
+impl RenderSink for MemorySink {
+    fn write_artifact(&mut self, name: &str, data: &[u8]) -> Result<(), Error> {
+        self.artifacts.push((name.to_string(), data.to_vec()));
+        Ok(())
+    }
+}
+
+/// Writes the contents of every artifact, one after another, to a stream
+/// (such as the input of a print spooler or a raw printer device). The names
+/// of the artifacts are discarded.
+#[derive(Debug)]
+pub struct StreamSink<W: Write> {
+    inner: W,
+}
+
+impl<W: Write> StreamSink<W> {
+    pub fn new(inner: W) -> Self {
+        Self { inner }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: Write> RenderSink for StreamSink<W> {
+    fn write_artifact(&mut self, _name: &str, data: &[u8]) -> Result<(), Error> {
+        self.inner.write_all(data)?;
+        Ok(self.inner.flush()?)
+    }
+}
+
+/// Bundles every artifact into a single (uncompressed) zip archive.
+///
+/// Rendered PDFs are already compressed, so the artifacts are stored as-is.
+/// The archive is only complete once `RenderSink::finish` has been called.
+#[derive(Debug)]
+pub struct ZipSink<W: Write> {
+    inner: W,
+    offset: u64,
+    central_directory: Vec<u8>,
+    num_entries: u16,
+    finished: bool,
+}
+
+impl<W: Write> ZipSink<W> {
+    const LOCAL_HEADER_SIGNATURE: u32 = 0x04034b50;
+    const CENTRAL_HEADER_SIGNATURE: u32 = 0x02014b50;
+    const END_OF_CENTRAL_DIRECTORY_SIGNATURE: u32 = 0x06054b50;
+    // Version 2.0 of the format, which is needed for directories (and is the
+    // lowest version most tools bother to check for).
+    const VERSION: u16 = 20;
+    // Bit 11: file names are UTF-8.
+    const FLAGS: u16 = 1 << 11;
+    // 1980-01-01 00:00:00 in MS-DOS format. Artifacts carry no timestamp so
+    // that bundles of the same documents are reproducible.
+    const DOS_TIME: u16 = 0;
+    const DOS_DATE: u16 = (1 << 5) | 1;
+
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            offset: 0,
+            central_directory: Vec::new(),
+            num_entries: 0,
+            finished: false,
+        }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+
+    fn too_large() -> Error {
+        Error::OtherError("too many or too large artifacts for a zip bundle".into())
+    }
+}
+
+impl<W: Write> RenderSink for ZipSink<W> {
+    fn write_artifact(&mut self, name: &str, data: &[u8]) -> Result<(), Error> {
+        if self.finished {
+            return Err(Error::OtherError(
+                "cannot add artifacts to a finished zip bundle".into(),
+            ));
+        }
+        let size = u32::try_from(data.len()).map_err(|_| Self::too_large())?;
+        let name_len = u16::try_from(name.len()).map_err(|_| Self::too_large())?;
+        let offset = u32::try_from(self.offset).map_err(|_| Self::too_large())?;
+        let num_entries = self
+            .num_entries
+            .checked_add(1)
+            .ok_or_else(Self::too_large)?;
+        let crc = crc32(data);
+
+        // Fields common to the local and central headers.
+        let mut common = Vec::new();
+        common.extend_from_slice(&Self::VERSION.to_le_bytes()); // version needed
+        common.extend_from_slice(&Self::FLAGS.to_le_bytes());
+        common.extend_from_slice(&0u16.to_le_bytes()); // stored (no compression)
+        common.extend_from_slice(&Self::DOS_TIME.to_le_bytes());
+        common.extend_from_slice(&Self::DOS_DATE.to_le_bytes());
+        common.extend_from_slice(&crc.to_le_bytes());
+        common.extend_from_slice(&size.to_le_bytes()); // compressed size
+        common.extend_from_slice(&size.to_le_bytes()); // uncompressed size
+        common.extend_from_slice(&name_len.to_le_bytes());
+        common.extend_from_slice(&0u16.to_le_bytes()); // extra field length
+
+        let mut local = Vec::new();
+        local.extend_from_slice(&Self::LOCAL_HEADER_SIGNATURE.to_le_bytes());
+        local.extend_from_slice(&common);
+        local.extend_from_slice(name.as_bytes());
+        self.inner.write_all(&local)?;
+        self.inner.write_all(data)?;
+
+        let central = &mut self.central_directory;
+        central.extend_from_slice(&Self::CENTRAL_HEADER_SIGNATURE.to_le_bytes());
+        central.extend_from_slice(&Self::VERSION.to_le_bytes()); // version made by
+        central.extend_from_slice(&common);
+        central.extend_from_slice(&0u16.to_le_bytes()); // comment length
+        central.extend_from_slice(&0u16.to_le_bytes()); // disk number
+        central.extend_from_slice(&0u16.to_le_bytes()); // internal attributes
+        central.extend_from_slice(&0u32.to_le_bytes()); // external attributes
+        central.extend_from_slice(&offset.to_le_bytes());
+        central.extend_from_slice(name.as_bytes());
+
+        self.offset += (local.len() + data.len()) as u64;
+        self.num_entries = num_entries;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Error> {
+        if self.finished {
+            return Ok(());
+        }
+        let central_size =
+            u32::try_from(self.central_directory.len()).map_err(|_| Self::too_large())?;
+        let central_offset = u32::try_from(self.offset).map_err(|_| Self::too_large())?;
+
+        let mut end = Vec::new();
+        end.extend_from_slice(&Self::END_OF_CENTRAL_DIRECTORY_SIGNATURE.to_le_bytes());
+        end.extend_from_slice(&0u16.to_le_bytes()); // disk number
+        end.extend_from_slice(&0u16.to_le_bytes()); // disk with central directory
+        end.extend_from_slice(&self.num_entries.to_le_bytes()); // entries on this disk
+        end.extend_from_slice(&self.num_entries.to_le_bytes()); // total entries
+        end.extend_from_slice(&central_size.to_le_bytes());
+        end.extend_from_slice(&central_offset.to_le_bytes());
+        end.extend_from_slice(&0u16.to_le_bytes()); // comment length
+
+        self.inner.write_all(&self.central_directory)?;
+        self.inner.write_all(&end)?;
+        self.inner.flush()?;
+        self.finished = true;
+        Ok(())
+    }
+}
+
+// CRC-32 (IEEE 802.3), as used by zip. Artifacts are small, so a bitwise
+// implementation is plenty fast enough.
+fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0u32, |crc, &b| {
+        (0..8).fold(crc ^ b as u32, |crc, _| {
+            (crc >> 1) ^ (0xedb88320 & (crc & 1).wrapping_neg())
+        })
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn crc32_check_value() {
+        assert_eq!(crc32(b"123456789"), 0xcbf43926);
+        assert_eq!(crc32(b""), 0);
+    }
+
+    #[test]
+    fn memory_sink() {
+        let mut sink = MemorySink::new();
+        sink.write_artifact("a.pdf", b"aaa").unwrap();
+        sink.write_artifact("b.svg", b"bb").unwrap();
+        sink.finish().unwrap();
+        assert_eq!(sink.get("b.svg"), Some(&b"bb"[..]));
+        assert_eq!(sink.get("c"), None);
+        assert_eq!(sink.artifacts().len(), 2);
+    }
+
+    #[test]
+    fn zip_sink_layout() {
+        let mut sink = ZipSink::new(Vec::new());
+        sink.write_artifact("a.pdf", b"hello").unwrap();
+        sink.write_artifact("b.pdf", b"").unwrap();
+        sink.finish().unwrap();
+        assert!(sink.write_artifact("c.pdf", b"late").is_err());
+        let zip = sink.into_inner();
+
+        // Local header of the first entry, followed by its data.
+        assert_eq!(zip[..4], 0x04034b50u32.to_le_bytes());
+        assert_eq!(zip[14..18], crc32(b"hello").to_le_bytes());
+        assert_eq!(zip[30..35], *b"a.pdf");
+        assert_eq!(zip[35..40], *b"hello");
+
+        // The end of central directory record points at the central
+        // directory, which lists both entries.
+        let end = &zip[zip.len() - 22..];
+        assert_eq!(end[..4], 0x06054b50u32.to_le_bytes());
+        assert_eq!(end[10..12], 2u16.to_le_bytes());
+        let central_size = u32::from_le_bytes(end[12..16].try_into().unwrap()) as usize;
+        let central_offset = u32::from_le_bytes(end[16..20].try_into().unwrap()) as usize;
+        assert_eq!(central_offset + central_size, zip.len() - 22);
+        assert_eq!(
+            zip[central_offset..central_offset + 4],
+            0x02014b50u32.to_le_bytes()
+        );
+    }
+}
@@ -0,0 +1,311 @@
+/*
+ * paperback: paper backup generator suitable for long-term storage
+ * Copyright (C) 2018-2022 Aleksa Sarai <cyphar@cyphar.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Redacted thumbnails of rendered documents.
+//!
+//! Thumbnails show the layout of each printed page (banners, codes and text)
+//! so that inventory tools can show what a physical document looks like.
+//! They are computed purely from the [`ArtifactLayout`] of a document, so
+//! they never contain any of the (sensitive) printed data -- every code is
+//! drawn as the same stub and text is drawn as grey bars.
+
+use crate::v0::pdf::{
+    generate::{Page, MAIN_DOCUMENT_CODES_PER_PAGE, MAIN_DOCUMENT_CODES_PER_ROW},
+    preview::ArtifactLayout,
+};
+
+use printpdf::Mm;
+
+// These must match the layout in generate.rs.
+const HEADER_HEIGHT: Mm = Mm(30.0);
+const BANNER_HEIGHT: Mm = Mm(9.0);
+const CUT_LINE_HEIGHT: Mm = Mm(8.0);
+const CODEWORDS_HEIGHT: Mm = Mm(25.0);
+
+const MAIN_DOCUMENT_TRIM: &str = "#ff6600";
+const KEY_SHARD_TRIM: &str = "#2c9f2c";
+
+/// The kind of content in a [`Region`] of a thumbnail.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RegionKind {
+    /// Human-readable text (drawn as grey bars).
+    Text,
+    /// A coloured section banner.
+    Banner,
+    /// A QR code (drawn as a stub without any data).
+    Code,
+    /// The text fallback for a QR code (drawn as grey bars).
+    Fallback,
+    /// A "cut here" line.
+    CutLine,
+}
+
+/// A rectangular region of a thumbnail. Coordinates are measured from the
+/// top-left corner of the page.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Region {
+    pub kind: RegionKind,
+    pub x: Mm,
+    pub y: Mm,
+    pub width: Mm,
+    pub height: Mm,
+}
+
+/// A redacted thumbnail of the first page of a printed document.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Thumbnail {
+    /// Width of the page.
+    pub width: Mm,
+    /// Height of the page.
+    pub height: Mm,
+    /// Every region of the page, in drawing order.
+    pub regions: Vec<Region>,
+    trim: &'static str,
+}
+
+impl Thumbnail {
+    fn new((width, height): (Mm, Mm), trim: &'static str) -> Self {
+        Self {
+            width,
+            height,
+            regions: vec![],
+            trim,
+        }
+    }
+
+    fn push(&mut self, kind: RegionKind, (x, y): (Mm, Mm), (width, height): (Mm, Mm)) -> Mm {
+        self.regions.push(Region {
+            kind,
+            x,
+            y,
+            width,
+            height,
+        });
+        height
+    }
+
+    fn banner(&mut self, top: Mm, banner_margin: Mm) -> Mm {
+        self.push(
+            RegionKind::Banner,
+            (Mm(0.0), top + banner_margin),
+            (self.width, BANNER_HEIGHT),
+        ) + banner_margin
+    }
+
+    fn code_with_fallback(&mut self, top: Mm, margin: Mm, size: Mm) -> Mm {
+        self.push(RegionKind::Code, (margin, top), (size, size));
+        self.push(
+            RegionKind::Fallback,
+            (margin + size + margin, top),
+            (self.width - size - margin * 3.0, size),
+        )
+    }
+
+    pub(super) fn main_document(layout: &ArtifactLayout) -> Self {
+        let page = Page::new(layout.paper_size);
+        let mut thumbnail = Self::new((page.width, page.height), MAIN_DOCUMENT_TRIM);
+        let (checksum, data) = layout
+            .codes
+            .split_last()
+            .expect("main document layouts always include a checksum code");
+
+        let mut current_y = page.margin;
+        current_y += thumbnail.push(
+            RegionKind::Text,
+            (page.margin, current_y),
+            (page.width - page.margin * 2.0, HEADER_HEIGHT - page.margin),
+        );
+        current_y += thumbnail.banner(current_y, Mm(3.0));
+
+        let per_row = MAIN_DOCUMENT_CODES_PER_ROW as usize;
+        let data = &data[..data.len().min(MAIN_DOCUMENT_CODES_PER_PAGE)];
+        for row in data.chunks(per_row) {
+            let mut row_height = Mm(0.0);
+            for (idx, code) in row.iter().enumerate() {
+                thumbnail.push(
+                    RegionKind::Code,
+                    (page.margin + code.size * idx as f64, current_y),
+                    (code.size, code.size),
+                );
+                if code.size > row_height {
+                    row_height = code.size;
+                }
+            }
+            current_y += row_height;
+        }
+
+        current_y += thumbnail.banner(current_y, Mm(3.0));
+        thumbnail.code_with_fallback(current_y, page.margin, checksum.size);
+
+        thumbnail
+    }
+
+    pub(super) fn key_shard(layout: &ArtifactLayout) -> Self {
+        let page = Page::new(layout.paper_size);
+        let mut thumbnail = Self::new((page.width, page.height), KEY_SHARD_TRIM);
+
+        let mut current_y = page.margin;
+        current_y += thumbnail.push(
+            RegionKind::Text,
+            (page.margin, current_y),
+            (page.width - page.margin * 2.0, HEADER_HEIGHT - page.margin),
+        );
+        for code in &layout.codes {
+            current_y += thumbnail.banner(current_y, Mm(1.0));
+            current_y += thumbnail.code_with_fallback(current_y, page.margin, code.size);
+        }
+        current_y += thumbnail.push(
+            RegionKind::CutLine,
+            (Mm(0.0), current_y),
+            (page.width, CUT_LINE_HEIGHT),
+        );
+        current_y += thumbnail.banner(current_y, Mm(1.0));
+
+        // The codewords are always printed at the bottom of the page.
+        let codewords_y = page.height - page.margin - CODEWORDS_HEIGHT;
+        let codewords_y = if codewords_y > current_y {
+            codewords_y
+        } else {
+            current_y
+        };
+        thumbnail.push(
+            RegionKind::Text,
+            (page.margin, codewords_y),
+            (page.width - page.margin * 2.0, CODEWORDS_HEIGHT),
+        );
+
+        thumbnail
+    }
+
+    /// Render the thumbnail as an SVG image (sized in millimetres, so it
+    /// can be scaled freely by the viewer).
+    pub fn to_svg(&self) -> String {
+        let mut svg = format!(
+            concat!(
+                r##"<svg xmlns="http://www.w3.org/2000/svg" width="{w}mm" height="{h}mm" viewBox="0 0 {w} {h}">"##,
+                r##"<rect width="{w}" height="{h}" fill="#ffffff" stroke="#999999" stroke-width="0.5"/>"##,
+            ),
+            w = self.width.0,
+            h = self.height.0,
+        );
+        for region in &self.regions {
+            let (x, y, w, h) = (region.x.0, region.y.0, region.width.0, region.height.0);
+            match region.kind {
+                RegionKind::Banner => svg.push_str(&format!(
+                    r##"<rect x="{x}" y="{y}" width="{w}" height="{h}" fill="{}"/>"##,
+                    self.trim
+                )),
+                RegionKind::Text | RegionKind::Fallback => {
+                    // Draw one bar for each (approximate) line of text.
+                    let mut line_y = y + 1.0;
+                    while line_y + 2.0 <= y + h {
+                        svg.push_str(&format!(
+                            r##"<rect x="{x}" y="{line_y}" width="{w}" height="2" fill="#cccccc"/>"##,
+                        ));
+                        line_y += 4.0;
+                    }
+                }
+                RegionKind::Code => {
+                    // The quiet zone is left blank, and the only detail drawn
+                    // are the three (data-independent) finder patterns.
+                    let (inset, finder) = (w * 0.1, w * 0.2);
+                    svg.push_str(&format!(
+                        r##"<rect x="{}" y="{}" width="{}" height="{}" fill="#e6e6e6"/>"##,
+                        x + inset,
+                        y + inset,
+                        w - inset * 2.0,
+                        h - inset * 2.0,
+                    ));
+                    for (fx, fy) in [
+                        (x + inset, y + inset),
+                        (x + w - inset - finder, y + inset),
+                        (x + inset, y + h - inset - finder),
+                    ] {
+                        svg.push_str(&format!(
+                            r##"<rect x="{fx}" y="{fy}" width="{finder}" height="{finder}" fill="#666666"/>"##,
+                        ));
+                    }
+                }
+                RegionKind::CutLine => svg.push_str(&format!(
+                    r##"<line x1="{x}" y1="{y1}" x2="{x2}" y2="{y1}" stroke="#999999" stroke-width="0.5" stroke-dasharray="2,2"/>"##,
+                    y1 = y + h / 2.0,
+                    x2 = x + w,
+                )),
+            }
+        }
+        svg.push_str("</svg>\n");
+        svg
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::v0::{
+        pdf::{RenderOptions, Renderer},
+        BackupBuilder,
+    };
+
+    use super::*;
+
+    #[test]
+    fn thumbnail_redacted() {
+        let secret = b"this secret must not appear in the thumbnail";
+        let backup = BackupBuilder::new(2).build(secret).unwrap();
+        let report = Renderer::preview(&backup, &RenderOptions::default()).unwrap();
+
+        let main_document = report.main_document_thumbnail();
+        assert_eq!(
+            main_document
+                .regions
+                .iter()
+                .filter(|region| region.kind == RegionKind::Code)
+                .count(),
+            report.main_document.codes.len()
+        );
+        let key_shard = report.key_shard_thumbnail();
+        assert_eq!(
+            key_shard
+                .regions
+                .iter()
+                .filter(|region| region.kind == RegionKind::Code)
+                .count(),
+            2
+        );
+
+        // Every region must be on the page.
+        for thumbnail in [&main_document, &key_shard] {
+            for region in &thumbnail.regions {
+                assert!(region.x.0 >= 0.0 && region.y.0 >= 0.0, "{:?}", region);
+                assert!(region.x + region.width <= thumbnail.width, "{:?}", region);
+                assert!(region.y + region.height <= thumbnail.height, "{:?}", region);
+            }
+        }
+
+        // Thumbnails of different backups with the same layout are
+        // identical, so they cannot leak anything about the contents.
+        let other = BackupBuilder::new(2)
+            .build(b"a different secret of the same length!!!!!!!")
+            .unwrap();
+        let other = Renderer::preview(&other, &RenderOptions::default()).unwrap();
+        assert_eq!(
+            main_document.to_svg(),
+            other.main_document_thumbnail().to_svg()
+        );
+        assert_eq!(key_shard.to_svg(), other.key_shard_thumbnail().to_svg());
+    }
+}
@@ -0,0 +1,220 @@
+/*
+ * paperback: paper backup generator suitable for long-term storage
+ * Copyright (C) 2018-2022 Aleksa Sarai <cyphar@cyphar.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use crate::v0::Error;
+
+use std::fmt;
+
+use serde::Deserialize;
+
+/// The cipher suite used by default by this version of paperback (see
+/// [`AeadAlgorithm::name`](crate::v0::AeadAlgorithm::name) for the others).
+pub const CIPHER_SUITE: &str = "chacha20-poly1305";
+
+/// Organisation-wide restrictions on the parameters used to create backups.
+///
+/// Policies are stored as JSON documents, such as:
+///
+/// ```json
+/// {
+///   "min-quorum-size": 3,
+///   "min-shards": 5,
+///   "cipher-suite": "chacha20-poly1305",
+///   "forbidden-output-profiles": ["raw"]
+/// }
+/// ```
+///
+/// All fields are optional, and unknown fields are rejected (so that a typo
+/// cannot silently disable a restriction).
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Policy {
+    /// Minimum number of key shards required to recover the secret.
+    pub min_quorum_size: Option<u32>,
+    /// Minimum number of key shards to create.
+    pub min_shards: Option<u32>,
+    /// The cipher suite which must be used.
+    pub cipher_suite: Option<String>,
+    /// Output profiles (such as "pdf" or "raw") which must not be used.
+    pub forbidden_output_profiles: Vec<String>,
+}
+
+/// The parameters of a backup to be checked against a [`Policy`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PolicyParams<'a> {
+    pub quorum_size: u32,
+    pub num_shards: u32,
+    pub cipher_suite: &'a str,
+    pub output_profile: &'a str,
+}
+
+/// A way in which a backup's parameters violate a [`Policy`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PolicyViolation {
+    QuorumSizeTooSmall {
+        quorum_size: u32,
+        minimum: u32,
+    },
+    TooFewShards {
+        num_shards: u32,
+        minimum: u32,
+    },
+    WrongCipherSuite {
+        cipher_suite: String,
+        required: String,
+    },
+    ForbiddenOutputProfile(String),
+}
+
+impl fmt::Display for PolicyViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::QuorumSizeTooSmall {
+                quorum_size,
+                minimum,
+            } => write!(
+                f,
+                "quorum size {} is smaller than the policy minimum of {}",
+                quorum_size, minimum
+            ),
+            Self::TooFewShards {
+                num_shards,
+                minimum,
+            } => write!(
+                f,
+                "{} key shards is fewer than the policy minimum of {}",
+                num_shards, minimum
+            ),
+            Self::WrongCipherSuite {
+                cipher_suite,
+                required,
+            } => write!(
+                f,
+                "cipher suite {} is not the policy-mandated {}",
+                cipher_suite, required
+            ),
+            Self::ForbiddenOutputProfile(profile) => {
+                write!(f, "output profile {} is forbidden by policy", profile)
+            }
+        }
+    }
+}
+
+impl Policy {
+    pub fn from_json<S: AsRef<str>>(json: S) -> Result<Self, Error> {
+        serde_json::from_str(json.as_ref())
+            .map_err(|err| Error::Other(format!("invalid policy: {}", err)))
+    }
+
+    /// Check whether the given backup parameters are permitted by the policy,
+    /// returning every violation found.
+    pub fn check(&self, params: &PolicyParams<'_>) -> Vec<PolicyViolation> {
+        let mut violations = vec![];
+        if let Some(minimum) = self.min_quorum_size {
+            if params.quorum_size < minimum {
+                violations.push(PolicyViolation::QuorumSizeTooSmall {
+                    quorum_size: params.quorum_size,
+                    minimum,
+                });
+            }
+        }
+        if let Some(minimum) = self.min_shards {
+            if params.num_shards < minimum {
+                violations.push(PolicyViolation::TooFewShards {
+                    num_shards: params.num_shards,
+                    minimum,
+                });
+            }
+        }
+        if let Some(ref required) = self.cipher_suite {
+            if params.cipher_suite != required {
+                violations.push(PolicyViolation::WrongCipherSuite {
+                    cipher_suite: params.cipher_suite.to_string(),
+                    required: required.clone(),
+                });
+            }
+        }
+        if self
+            .forbidden_output_profiles
+            .iter()
+            .any(|p| p == params.output_profile)
+        {
+            violations.push(PolicyViolation::ForbiddenOutputProfile(
+                params.output_profile.to_string(),
+            ));
+        }
+        violations
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const POLICY: &str = r#"{
+        "min-quorum-size": 3,
+        "min-shards": 5,
+        "cipher-suite": "chacha20-poly1305",
+        "forbidden-output-profiles": ["raw"]
+    }"#;
+
+    #[test]
+    fn policy_check() {
+        let policy = Policy::from_json(POLICY).unwrap();
+
+        let params = PolicyParams {
+            quorum_size: 3,
+            num_shards: 5,
+            cipher_suite: CIPHER_SUITE,
+            output_profile: "pdf",
+        };
+        assert_eq!(policy.check(&params), vec![]);
+
+        let params = PolicyParams {
+            quorum_size: 2,
+            num_shards: 4,
+            cipher_suite: "rot13",
+            output_profile: "raw",
+        };
+        assert_eq!(
+            policy.check(&params),
+            vec![
+                PolicyViolation::QuorumSizeTooSmall {
+                    quorum_size: 2,
+                    minimum: 3
+                },
+                PolicyViolation::TooFewShards {
+                    num_shards: 4,
+                    minimum: 5
+                },
+                PolicyViolation::WrongCipherSuite {
+                    cipher_suite: "rot13".into(),
+                    required: CIPHER_SUITE.into()
+                },
+                PolicyViolation::ForbiddenOutputProfile("raw".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn policy_parse() {
+        assert_eq!(Policy::from_json("{}").unwrap(), Policy::default());
+        // Typos must not silently disable restrictions.
+        assert!(Policy::from_json(r#"{"min-quorum": 3}"#).is_err());
+    }
+}
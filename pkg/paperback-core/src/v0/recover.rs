@@ -17,20 +17,26 @@
  */
 
 use crate::{
-    shamir::{shard, Dealer},
-    v0::{Error, FromWire, KeyShard, KeyShardBuilder, MainDocument, ShardId, ShardSecret},
+    shamir::{shard, Dealer, ShardConsistency, WeightedShard},
+    v0::{
+        expansion::ExpansionAudit, format_version, is_test_run, Amendment, Backup, BackupBuilder,
+        ChaChaPolyKey, CustodianInfo, DocumentId, Error, FromWire, IdentityId, KeyShard,
+        KeyShardBuilder, LifecycleState, MainDocument, SecretBundle, ShardExpansion, ShardId,
+        ShardSecret, Warning, Warnings, PAPERBACK_VERSION,
+    },
 };
 
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap},
+    fmt,
     hash::{Hash, Hasher},
+    time::{SystemTime, UNIX_EPOCH},
 };
 
-use aead::{Aead, NewAead, Payload};
-use chacha20poly1305::ChaCha20Poly1305;
 use ed25519_dalek::VerifyingKey;
 use multihash::Multihash;
 use once_cell::unsync::OnceCell;
+use zeroize::Zeroizing;
 
 #[derive(Debug, Clone)]
 pub enum Type {
@@ -59,12 +65,21 @@ impl Type {
 impl From<MainDocument> for Type {
     fn from(main: MainDocument) -> Self {
         let id_public_key = main.identity.id_public_key;
-        match id_public_key.verify_strict(
-            &main.inner.signable_bytes(&id_public_key),
-            &main.identity.id_signature,
-        ) {
-            Ok(_) => Type::MainDocument(main),
-            Err(_) => Type::ForgedMainDocument(main),
+        let pq_identity = main.pq_identity.as_ref();
+        let bytes = main
+            .inner
+            .signable_bytes(&id_public_key, pq_identity.map(|pq| &pq.public_key));
+
+        // Hybrid-signed documents are only genuine if both signatures are.
+        let ed25519_ok = id_public_key
+            .verify_strict(&bytes, &main.identity.id_signature)
+            .is_ok();
+        let pq_ok = pq_identity
+            .map(|pq| pq.public_key.verify(&bytes, &pq.signature))
+            .unwrap_or(true);
+        match ed25519_ok && pq_ok {
+            true => Type::MainDocument(main),
+            false => Type::ForgedMainDocument(main),
         }
     }
 }
@@ -153,6 +168,33 @@ impl From<&Type> for GroupId {
 #[derive(Debug, Clone)]
 pub struct Grouping(pub Vec<Vec<Type>>);
 
+// The weight of the key shards counted towards the quorum size -- the total
+// weight of ungrouped key shards, or the number of groups with enough key
+// shards to recover the group's share (see Backup::next_group).
+fn quorum_weight<'a, I>(shards: I) -> u64
+where
+    I: IntoIterator<Item = &'a KeyShard>,
+{
+    let mut weight = 0;
+    let mut groups: HashMap<ShardId, (u64, u32)> = HashMap::new();
+    for shard in shards {
+        match shard.group_id() {
+            None => weight += u64::from(shard.weight()),
+            Some(id) => {
+                groups
+                    .entry(id)
+                    .or_insert((0, shard.member_quorum_size()))
+                    .0 += u64::from(shard.weight())
+            }
+        }
+    }
+    weight
+        + groups
+            .values()
+            .filter(|&&(weight, needed)| weight >= needed.into())
+            .count() as u64
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct UntrustedQuorum {
     untrusted_quorum_size: Option<u32>,
@@ -160,6 +202,63 @@ pub struct UntrustedQuorum {
     untrusted_shards: HashMap<(GroupId, String), KeyShard>,
 }
 
+/// Why a key shard cannot be added to an [`UntrustedQuorum`] (see
+/// [`UntrustedQuorum::check_shard`]).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ShardRejection {
+    /// The key shard's signature is invalid, so it has been forged or
+    /// corrupted.
+    Forged,
+    /// The key shard belongs to a different document.
+    WrongDocument {
+        expected: DocumentId,
+        found: DocumentId,
+    },
+    /// The key shard was signed by a different identity.
+    WrongIdentity {
+        expected: IdentityId,
+        found: IdentityId,
+    },
+    /// The key shard has a different quorum size.
+    WrongQuorumSize { expected: u32, found: u32 },
+    /// The key shard was created by a different version of paperback (or
+    /// with different options, such as a test run).
+    WrongVersion,
+    /// The key shard has already been added to the quorum.
+    Duplicate(ShardId),
+}
+
+impl fmt::Display for ShardRejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Forged => write!(
+                f,
+                "its signature is invalid, so it has been forged or corrupted"
+            ),
+            Self::WrongDocument { expected, found } => write!(
+                f,
+                "it belongs to document {} rather than document {}",
+                found, expected
+            ),
+            Self::WrongIdentity { expected, found } => write!(
+                f,
+                "it was signed by identity {} rather than identity {}",
+                found, expected
+            ),
+            Self::WrongQuorumSize { expected, found } => write!(
+                f,
+                "it has a quorum size of {} rather than {}",
+                found, expected
+            ),
+            Self::WrongVersion => write!(
+                f,
+                "it was created by a different version of paperback (or is from a test run)"
+            ),
+            Self::Duplicate(id) => write!(f, "key shard {} has already been entered", id),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct InconsistentQuorumError {
     pub message: String, // TODO: Switch to an Error...
@@ -203,6 +302,65 @@ impl UntrustedQuorum {
         self.untrusted_shards.len()
     }
 
+    /// Returns the total weight of the key shards in the quorum (see
+    /// [`KeyShard::weight`]), which is what must reach the quorum size. For
+    /// grouped key shards, this is the number of groups with enough key
+    /// shards (see [`KeyShard::member_quorum_size`]).
+    pub fn untrusted_weight(&self) -> u32 {
+        quorum_weight(self.untrusted_shards.values())
+            .try_into()
+            .unwrap_or(u32::MAX)
+    }
+
+    /// Check whether shard can be added to the quorum, so that a bad key
+    /// shard can be reported (and replaced) as soon as it is entered rather
+    /// than when the quorum is validated. The key shard is checked against
+    /// the main document (or, if there is none, the first key shard).
+    pub fn check_shard(&self, shard: &KeyShard) -> Result<(), ShardRejection> {
+        if let Type::ForgedKeyShard(_) = Type::from(shard.clone()) {
+            return Err(ShardRejection::Forged);
+        }
+
+        let group_id = GroupId::from(shard);
+        let (expected, document_id, identity_id) = match (
+            &self.untrusted_main_document,
+            self.untrusted_shards.values().next(),
+        ) {
+            (Some(main), _) => (GroupId::from(main), main.id(), main.identity_id()),
+            (None, Some(other)) => (
+                GroupId::from(other),
+                other.document_id(),
+                other.identity_id(),
+            ),
+            (None, None) => return Ok(()),
+        };
+        if group_id.doc_chksum != expected.doc_chksum {
+            return Err(ShardRejection::WrongDocument {
+                expected: document_id,
+                found: shard.document_id(),
+            });
+        }
+        if group_id.id_public_key != expected.id_public_key {
+            return Err(ShardRejection::WrongIdentity {
+                expected: identity_id,
+                found: shard.identity_id(),
+            });
+        }
+        if group_id.quorum_size != expected.quorum_size {
+            return Err(ShardRejection::WrongQuorumSize {
+                expected: expected.quorum_size,
+                found: group_id.quorum_size,
+            });
+        }
+        if group_id.version != expected.version {
+            return Err(ShardRejection::WrongVersion);
+        }
+        if self.untrusted_shards.contains_key(&(group_id, shard.id())) {
+            return Err(ShardRejection::Duplicate(shard.id()));
+        }
+        Ok(())
+    }
+
     fn group(&self) -> Vec<Vec<Type>> {
         let documents = self
             .untrusted_main_document
@@ -304,14 +462,16 @@ impl UntrustedQuorum {
         assert_eq!(shards.len(), self.untrusted_shards.len());
         // TODO: Maybe make a trait for this -- QuorumVerifiable?
         if let Some(ref main_document) = main_document {
-            // XXX: Should probably support having more shards than needed, and have
-            //      them act as a double-check operation.
-            if main_document.quorum_size() as usize != shards.len() {
+            // Any shards beyond the quorum size are used to cross-verify the
+            // others during recovery (see Quorum::shard_consistency).
+            let weight = quorum_weight(&shards);
+            if weight < main_document.quorum_size().into() {
                 return Err(InconsistentQuorumError {
                     message: format!(
-                        "quorum size required is {} but had {} shards",
+                        "quorum size required is {} but only had {} shards (of total weight {})",
                         main_document.quorum_size(),
-                        shards.len()
+                        shards.len(),
+                        weight
                     ),
                     groups: Grouping(groups),
                 });
@@ -345,6 +505,21 @@ impl UntrustedQuorum {
             }
         }
 
+        let mut warnings = Warnings::new();
+        if format_version(version) != PAPERBACK_VERSION {
+            warnings.push(Warning::FormatVersion {
+                version: format_version(version),
+            });
+        }
+        if is_test_run(version) {
+            warnings.push(Warning::TestRun);
+        }
+        if main_document.is_none() {
+            warnings.push(Warning::UnverifiedSignature(
+                "no main document in quorum -- only the key shard signatures were verified".into(),
+            ));
+        }
+
         Ok(Quorum {
             main_document,
             shards,
@@ -354,6 +529,7 @@ impl UntrustedQuorum {
             id_public_key,
             doc_chksum,
             dealer: OnceCell::new(),
+            warnings,
         })
     }
 }
@@ -374,8 +550,10 @@ pub struct Quorum {
     version: u32,
     id_public_key: VerifyingKey,
     doc_chksum: Multihash,
-    // Lazy-initialised dealer, reconstructed from key shards.
-    dealer: OnceCell<Dealer>,
+    // Lazy-initialised dealer, reconstructed (and cross-verified) from key
+    // shards.
+    dealer: OnceCell<(Dealer, ShardConsistency)>,
+    warnings: Warnings,
 }
 
 impl Quorum {
@@ -383,32 +561,54 @@ impl Quorum {
         self.main_document.is_some()
     }
 
-    fn get_dealer(&self) -> Result<&Dealer, Error> {
+    // Whether the key shards are grouped (see Backup::next_group).
+    fn is_grouped(&self) -> bool {
+        self.shards.iter().any(|shard| shard.group_id().is_some())
+    }
+
+    /// Returns any warnings found while validating the quorum.
+    pub fn warnings(&self) -> &Warnings {
+        &self.warnings
+    }
+
+    fn get_dealer(&self) -> Result<&(Dealer, ShardConsistency), Error> {
         Ok(self.dealer.get_or_try_init(|| {
-            Dealer::recover(
+            if self.is_grouped() {
+                return Dealer::recover_grouped(
+                    self.shards
+                        .iter()
+                        .map(|s| s.inner.shard.clone())
+                        .collect::<Vec<_>>(),
+                );
+            }
+            Dealer::recover_verified(
                 self.shards
                     .iter()
-                    .map(|s| s.inner.shard.clone())
+                    .flat_map(|s| s.inner.shard.shards().to_vec())
                     .collect::<Vec<_>>(),
             )
         })?)
     }
 
-    pub fn recover_document(&self) -> Result<Vec<u8>, Error> {
-        let main_document = self.main_document.clone().ok_or(Error::MissingCapability(
-            "no main document in quorum -- cannot recover",
-        ))?;
-        let shards = self
-            .shards
-            .iter()
-            .map(|s| s.inner.shard.clone())
-            .collect::<Vec<_>>();
-        let secret = ShardSecret::from_wire(Dealer::recover(shards)?.secret())
+    /// Cross-verify the key shards in the quorum against each other.
+    ///
+    /// If the quorum contains more key shards than needed, the surplus shards
+    /// are used to check that every shard agrees on the same secret, so that a
+    /// corrupted (or forged) shard is detected. The secret is recovered only
+    /// from the consistent shards.
+    pub fn shard_consistency(&self) -> Result<&ShardConsistency, Error> {
+        Ok(&self.get_dealer()?.1)
+    }
+
+    // Recover the document key from the key shards.
+    fn doc_key(&self) -> Result<ChaChaPolyKey, Error> {
+        let (dealer, _) = self.get_dealer()?;
+        let secret = ShardSecret::from_wire(Zeroizing::new(dealer.secret()).as_slice())
             .map_err(Error::ShardSecretDecode)?;
 
         // Double-check that the private key agrees with the quorum's public key
         // choice.
-        if let Some(id_keypair) = secret.id_keypair {
+        if let Some(ref id_keypair) = secret.id_keypair {
             if id_keypair.verifying_key() != self.id_public_key {
                 return Err(Error::InvariantViolation(
                     "private key doesn't match quorum public key",
@@ -416,23 +616,317 @@ impl Quorum {
             }
         }
 
+        Ok(secret.doc_key)
+    }
+
+    /// Decrypt the secret contained in the main document.
+    ///
+    /// If the document contains a bundle of secrets (see
+    /// [`MainDocument::is_bundle`]), this returns the encoded bundle, which
+    /// can be decoded with [`Quorum::recover_bundle`].
+    pub fn recover_document(&self) -> Result<Vec<u8>, Error> {
+        let main_document = self.main_document.as_ref().ok_or(Error::MissingCapability(
+            "no main document in quorum -- cannot recover",
+        ))?;
+
         // Decrypt the contents.
-        let aead = ChaCha20Poly1305::new(&secret.doc_key);
-        let payload = Payload {
-            msg: &main_document.inner.ciphertext,
-            aad: &main_document.inner.meta.aad(&self.id_public_key),
-        };
-        aead.decrypt(&main_document.inner.nonce, payload)
-            .map_err(Error::AeadDecryption)
+        let padded = main_document.inner.meta.aead_mode.decrypt(
+            main_document.inner.meta.aead_algorithm,
+            &self.doc_key()?,
+            &main_document.inner.nonce,
+            &main_document.inner.meta.aad(&self.id_public_key),
+            &main_document.inner.ciphertext,
+        )?;
+
+        // Strip the padding and decompress.
+        let compressed = main_document.inner.meta.padding.unpad(padded)?;
+        main_document.inner.meta.compression.decompress(compressed)
+    }
+
+    /// Decrypt the labelled secrets contained in a main document created with
+    /// [`BackupBuilder::build_bundle`].
+    pub fn recover_bundle(&self) -> Result<SecretBundle, Error> {
+        let main_document = self.main_document.as_ref().ok_or(Error::MissingCapability(
+            "no main document in quorum -- cannot recover",
+        ))?;
+        if !main_document.is_bundle() {
+            return Err(Error::MissingCapability(
+                "main document contains a single secret, not a bundle",
+            ));
+        }
+        SecretBundle::from_wire(self.recover_document()?)
+            .map_err(|err| Error::Other(format!("failed to decode secret bundle: {}", err)))
+    }
+
+    /// Decrypt and authenticate a single segment of the main document's
+    /// encrypted contents, without decrypting the rest of the document (see
+    /// [`AeadMode::Chunked`](crate::v0::AeadMode::Chunked)).
+    ///
+    /// Note that the segment is part of the *compressed and padded* secret, so
+    /// it is only a segment of the original secret if the document was
+    /// created without compression.
+    pub fn recover_segment(&self, index: usize) -> Result<Vec<u8>, Error> {
+        let main_document = self.main_document.as_ref().ok_or(Error::MissingCapability(
+            "no main document in quorum -- cannot recover",
+        ))?;
+        main_document.inner.meta.aead_mode.decrypt_segment(
+            main_document.inner.meta.aead_algorithm,
+            &self.doc_key()?,
+            &main_document.inner.nonce,
+            &main_document.inner.meta.aad(&self.id_public_key),
+            &main_document.inner.ciphertext,
+            index,
+        )
+    }
+
+    /// Re-deal the recovered secret as a new backup requiring `quorum_size`
+    /// key shards, without the secret ever leaving the library.
+    ///
+    /// The new backup is the next generation of this one (see
+    /// [`MainDocument::generation`]), and is encrypted with a fresh document
+    /// key, so key shards from this quorum (or any other key shard of the
+    /// previous generation) cannot be used to recover it. All other settings
+    /// of the backup are preserved. Unsealed backups keep the same identity,
+    /// while sealed backups are given a new one (since the private key of the
+    /// sealed identity no longer exists).
+    ///
+    /// The old generation's documents should be destroyed once the new key
+    /// shards have been distributed, as they can still be used to recover the
+    /// secret.
+    pub fn redeal(&self, quorum_size: u32) -> Result<Backup, Error> {
+        self.redeal_inner(quorum_size, self.is_sealed()?, false)
+    }
+
+    /// Re-deal the recovered secret (like [`Quorum::redeal`]) under a new
+    /// identity, for when the identity's private key may have been
+    /// compromised (for instance, if the machine which created the backup was
+    /// compromised).
+    ///
+    /// The new main document and key shards are signed with a fresh identity,
+    /// and the new main document records the old identity as revoked (see
+    /// [`MainDocument::revoked_identity_ids`]), which is printed on the main
+    /// document. Every key shard of the old generation must be replaced, as
+    /// the old documents could have been forged by whoever holds the old
+    /// identity's private key.
+    pub fn rotate_identity(&self, quorum_size: u32) -> Result<Backup, Error> {
+        self.redeal_inner(quorum_size, self.is_sealed()?, true)
+    }
+
+    /// Returns whether the backup is sealed (see [`BackupBuilder::sealed`]),
+    /// meaning that the key shards do not contain the identity's private key.
+    pub fn is_sealed(&self) -> Result<bool, Error> {
+        let (dealer, _) = self.get_dealer()?;
+        let secret = ShardSecret::from_wire(Zeroizing::new(dealer.secret()).as_slice())
+            .map_err(Error::ShardSecretDecode)?;
+        Ok(secret.id_keypair.is_none())
+    }
+
+    /// Re-deal the recovered secret (like [`Quorum::redeal`]) as a sealed
+    /// backup, so that no new key shards can be created once the new key
+    /// shards have been distributed.
+    ///
+    /// The key shards of the unsealed generation contain the identity's
+    /// private key, so the new generation is signed with a fresh identity and
+    /// the old identity is revoked (like [`Quorum::rotate_identity`]).
+    /// Otherwise, a quorum of old key shards could still be used to forge
+    /// documents which appear to belong to the sealed backup.
+    pub fn seal(&self, quorum_size: u32) -> Result<Backup, Error> {
+        if self.is_sealed()? {
+            return Err(Error::Other("backup is already sealed".to_string()));
+        }
+        self.redeal_inner(quorum_size, true, true)
+    }
+
+    /// Re-deal the recovered secret (like [`Quorum::redeal`]) as an unsealed
+    /// backup, so that new key shards can be created by a quorum.
+    ///
+    /// The private key of the sealed identity no longer exists, so the new
+    /// generation is signed with a fresh identity. Note that anyone who
+    /// gathers a quorum of the new key shards will be able to create new key
+    /// shards and sign documents with the new identity, which is why sealed
+    /// backups are usually preferable once a backup has been distributed.
+    pub fn unseal(&self, quorum_size: u32) -> Result<Backup, Error> {
+        if !self.is_sealed()? {
+            return Err(Error::Other("backup is not sealed".to_string()));
+        }
+        self.redeal_inner(quorum_size, false, false)
+    }
+
+    fn redeal_inner(
+        &self,
+        quorum_size: u32,
+        sealed: bool,
+        rotate_identity: bool,
+    ) -> Result<Backup, Error> {
+        let main_document = self.main_document.as_ref().ok_or(Error::MissingCapability(
+            "no main document in quorum -- cannot re-deal",
+        ))?;
+        let secret = self.recover_document()?;
+        let (dealer, _) = self.get_dealer()?;
+        let mut shard_secret = ShardSecret::from_wire(Zeroizing::new(dealer.secret()).as_slice())
+            .map_err(Error::ShardSecretDecode)?;
+        let shard = self.shards[0].inner.shard.primary();
+        let meta = &main_document.inner.meta;
+
+        let mut builder = BackupBuilder::new(quorum_size);
+        builder
+            .sealed(sealed)
+            .padding(meta.padding)
+            .compression(meta.compression)
+            .test_run(is_test_run(meta.version))
+            .bundle(main_document.is_bundle())
+            .created_at_timestamp(meta.created_at)
+            .text_encoding(main_document.text_encoding())
+            .field_size(shard.field_size())
+            .aead_mode(meta.aead_mode)
+            .aead_algorithm(meta.aead_algorithm)
+            .hybrid_signatures(main_document.is_hybrid_signed())
+            .cbor_payload(main_document.has_cbor_payload())
+            .generation(meta.generation.checked_add(1).ok_or_else(|| {
+                Error::Other("backup has reached the maximum generation".to_string())
+            })?);
+        meta.constraints.iter().for_each(|constraint| {
+            builder.constraint(constraint.clone());
+        });
+        if rotate_identity {
+            // Let the builder generate a fresh identity, and revoke this one.
+            let mut revoked_identities = meta.revoked_identities.clone();
+            revoked_identities.push(self.id_public_key);
+            builder.revoked_identities(revoked_identities);
+            return builder.build(secret);
+        }
+        builder.revoked_identities(meta.revoked_identities.clone());
+        if let Some(id_keypair) = shard_secret.id_keypair.take() {
+            builder.id_keypair(id_keypair);
+        }
+        if let Some(pq_keypair) = shard_secret.pq_keypair.take() {
+            // Make sure the seed matches the document's ML-DSA-65 public key.
+            if main_document.pq_identity.as_ref().map(|pq| &pq.public_key)
+                != Some(&pq_keypair.verifying_key())
+            {
+                return Err(Error::InvariantViolation(
+                    "pq_secret_key doesn't match expected pq_public_key",
+                ));
+            }
+            builder.pq_keypair(pq_keypair);
+        }
+        builder.build(secret)
+    }
+
+    /// Sign an [`Amendment`] changing the lifecycle state of the document to
+    /// `state`.
+    ///
+    /// `sequence` must be larger than the sequence number of every previous
+    /// amendment of the document, as the amendment with the largest sequence
+    /// number determines the document's current state (see
+    /// [`lifecycle::current_state`](crate::v0::lifecycle::current_state)).
+    pub fn amend(&self, sequence: u32, state: LifecycleState) -> Result<Amendment, Error> {
+        let (dealer, _) = self.get_dealer()?;
+        let mut secret = ShardSecret::from_wire(Zeroizing::new(dealer.secret()).as_slice())
+            .map_err(Error::ShardSecretDecode)?;
+
+        // Get the private key so we can sign the amendment.
+        let id_keypair = secret.id_keypair.take().ok_or(Error::MissingCapability(
+            "document is sealed -- no amendments allowed",
+        ))?;
+        if id_keypair.verifying_key() != self.id_public_key {
+            return Err(Error::InvariantViolation(
+                "id_secret_key doesn't match expected id_public_key",
+            ));
+        }
+
+        Ok(Amendment::sign(
+            self.doc_chksum,
+            sequence,
+            state,
+            &id_keypair,
+        ))
     }
 
     pub fn new_shard(&self, shard_type: NewShardKind) -> Result<KeyShard, Error> {
+        self.deal_shard(shard_type, None)
+    }
+
+    /// Mint a new key shard (with a random `ShardId`), recording in the key
+    /// shard that it was minted at `expanded_at` by this quorum. The record is
+    /// printed on the key shard PDF, so that key shards added after a backup
+    /// was created can be told apart from the original ones, and is
+    /// counter-signed with the document key (see [`Quorum::audit_expansions`]).
+    ///
+    /// Minted key shards cannot be read by older versions of paperback.
+    pub fn expand_shard(&self, expanded_at: SystemTime) -> Result<KeyShard, Error> {
+        let mut quorum = self.shards.iter().map(KeyShard::id).collect::<Vec<_>>();
+        quorum.sort();
+        quorum.dedup();
+        let expansion = ShardExpansion {
+            expanded_at: expanded_at
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+            quorum,
+        };
+        self.deal_shard(NewShardKind::NewShard, Some(expansion))
+    }
+
+    /// Check the expansion records of the key shards in the quorum which were
+    /// minted by an earlier quorum (see [`Quorum::expand_shard`]) against the
+    /// recovered document key.
+    ///
+    /// A record is only authorised if it was counter-signed by a quorum of
+    /// this backup. Records of key shards minted by older versions of
+    /// paperback (or forged by the holder of a sealed backup's identity) are
+    /// returned as unauthorised.
+    pub fn audit_expansions(&self) -> Result<Vec<ExpansionAudit>, Error> {
+        let minted = self
+            .shards
+            .iter()
+            .filter(|shard| shard.inner.expansion.is_some())
+            .collect::<Vec<_>>();
+        if minted.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let doc_key = self.doc_key()?;
+        minted
+            .into_iter()
+            .filter_map(|shard| {
+                let expansion = shard.inner.expansion.as_ref()?;
+                let shard_id = shard.id();
+                let authorised = match shard.inner.expansion_authorisation {
+                    None => Ok(false),
+                    Some(ref authorisation) => {
+                        expansion.verify(&doc_key, &self.doc_chksum, &shard_id, authorisation)
+                    }
+                };
+                Some(authorised.map(|authorised| ExpansionAudit {
+                    shard_id,
+                    expansion: expansion.clone(),
+                    authorised,
+                }))
+            })
+            .collect()
+    }
+
+    fn deal_shard(
+        &self,
+        shard_type: NewShardKind,
+        expansion: Option<ShardExpansion>,
+    ) -> Result<KeyShard, Error> {
+        // The member dealers of each group are not recovered, so there is no
+        // way to create a member key shard for a group.
+        if self.is_grouped() {
+            return Err(Error::MissingCapability(
+                "document has grouped key shards -- no new key shards allowed",
+            ));
+        }
+
         // Conduct a complete recovery.
-        let dealer = self.get_dealer()?;
-        let secret = ShardSecret::from_wire(dealer.secret()).map_err(Error::ShardSecretDecode)?;
+        let (dealer, _) = self.get_dealer()?;
+        let mut secret = ShardSecret::from_wire(Zeroizing::new(dealer.secret()).as_slice())
+            .map_err(Error::ShardSecretDecode)?;
 
         // Get the private key so we can sign the new shards.
-        let id_keypair = secret.id_keypair.ok_or(Error::MissingCapability(
+        let id_keypair = secret.id_keypair.take().ok_or(Error::MissingCapability(
             "document is sealed -- no new key shards allowed",
         ))?;
 
@@ -445,19 +939,29 @@ impl Quorum {
         }
 
         // Extend new shards.
+        let shard: WeightedShard = match shard_type {
+            NewShardKind::NewShard => dealer.next_shard()?,
+            NewShardKind::ExistingShard(id) => {
+                dealer.shard(shard::parse_id(id).map_err(Error::ShardIdDecode)?)?
+            }
+        }
+        .into();
+
+        // Counter-sign the expansion record with the document key, so that
+        // later quorums can check it was made by a quorum of this backup.
+        let expansion_authorisation = expansion
+            .as_ref()
+            .map(|expansion| expansion.authorise(&secret.doc_key, &self.doc_chksum, &shard.id()))
+            .transpose()?;
+
         Ok(KeyShardBuilder {
             version: self.version,
             doc_chksum: self.doc_chksum,
-            shard: match shard_type {
-                NewShardKind::NewShard => dealer.next_shard(),
-                NewShardKind::ExistingShard(id) => dealer
-                    .shard(shard::parse_id(id).map_err(Error::ShardIdDecode)?)
-                    .ok_or_else(|| {
-                        Error::Other(
-                            "requested shard id has x value of 0 -- refusing to create".to_string(),
-                        )
-                    })?,
-            },
+            shard,
+            custodian: CustodianInfo::default(),
+            expansion,
+            expansion_authorisation,
+            extensions: BTreeMap::new(),
         }
         .sign(&id_keypair))
     }
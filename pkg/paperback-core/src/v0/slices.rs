@@ -0,0 +1,220 @@
+/*
+ * paperback: paper backup generator suitable for long-term storage
+ * Copyright (C) 2018-2022 Aleksa Sarai <cyphar@cyphar.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Splitting secrets which are too large for a single main document.
+//!
+//! A large secret is split into ordered slices, each of which is backed up as
+//! an independent paperback backup (with its own main document and key
+//! shards). The [`SuperManifest`] ties the slices together by listing the
+//! checksum of each slice (in order) and of the whole secret. Slices can be
+//! recovered independently (and at different times), and a [`SliceAssembler`]
+//! uses the super-manifest to put them back together.
+
+use crate::v0::{multihash_short_id, Error, CHECKSUM_ALGORITHM, CHECKSUM_MULTIBASE};
+
+use multihash::{Multihash, MultihashDigest};
+
+/// An ordered list of the slices of a split secret.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SuperManifest {
+    pub(super) secret_len: u64,
+    pub(super) secret_chksum: Multihash,
+    pub(super) slice_chksums: Vec<Multihash>,
+}
+
+impl SuperManifest {
+    pub const ID_LENGTH: usize = 8;
+
+    /// Split a secret into slices of at most `slice_size` bytes, returning the
+    /// super-manifest and the slices (in order). Each slice should then be
+    /// backed up separately.
+    pub fn split(secret: &[u8], slice_size: usize) -> Result<(Self, Vec<Vec<u8>>), Error> {
+        if slice_size == 0 {
+            return Err(Error::Other("slice size must be non-zero".into()));
+        }
+        let slices = match secret.len() {
+            0 => vec![vec![]],
+            _ => secret.chunks(slice_size).map(<[u8]>::to_vec).collect(),
+        };
+        let manifest = Self {
+            secret_len: secret.len() as u64,
+            secret_chksum: CHECKSUM_ALGORITHM.digest(secret),
+            slice_chksums: slices
+                .iter()
+                .map(|slice| CHECKSUM_ALGORITHM.digest(slice))
+                .collect(),
+        };
+        Ok((manifest, slices))
+    }
+
+    /// Short identifier for the split secret, derived from the checksum of
+    /// the whole secret.
+    pub fn id(&self) -> String {
+        multihash_short_id(self.secret_chksum, Self::ID_LENGTH)
+    }
+
+    pub fn secret_len(&self) -> u64 {
+        self.secret_len
+    }
+
+    pub fn num_slices(&self) -> usize {
+        self.slice_chksums.len()
+    }
+
+    pub fn slice_checksum_strings(&self) -> Vec<String> {
+        self.slice_chksums
+            .iter()
+            .map(|chksum| multibase::encode(CHECKSUM_MULTIBASE, chksum.to_bytes()))
+            .collect()
+    }
+}
+
+/// Reassembles a split secret from slices recovered in any order.
+#[derive(Clone, Debug)]
+pub struct SliceAssembler<'a> {
+    manifest: &'a SuperManifest,
+    slices: Vec<Option<Vec<u8>>>,
+}
+
+impl<'a> SliceAssembler<'a> {
+    pub fn new(manifest: &'a SuperManifest) -> Self {
+        Self {
+            manifest,
+            slices: vec![None; manifest.num_slices()],
+        }
+    }
+
+    /// Add a recovered slice, returning its (zero-based) index in the
+    /// secret. Slices are identified by their checksum, so a slice which
+    /// isn't listed in the super-manifest is rejected.
+    pub fn push(&mut self, slice: Vec<u8>) -> Result<usize, Error> {
+        let chksum = CHECKSUM_ALGORITHM.digest(&slice);
+        let mut candidates = self
+            .manifest
+            .slice_chksums
+            .iter()
+            .enumerate()
+            .filter(|(_, slice_chksum)| **slice_chksum == chksum)
+            .map(|(idx, _)| idx)
+            .peekable();
+        if candidates.peek().is_none() {
+            return Err(Error::Other(
+                "slice is not listed in the super-manifest".into(),
+            ));
+        }
+        // Identical slices have identical checksums, so fill the first slot
+        // for this slice which is still missing.
+        let idx = candidates
+            .find(|&idx| self.slices[idx].is_none())
+            .ok_or_else(|| Error::Other("slice has already been added".into()))?;
+        self.slices[idx] = Some(slice);
+        Ok(idx)
+    }
+
+    /// The (zero-based) indices of the slices which have not been added yet.
+    pub fn missing(&self) -> Vec<usize> {
+        self.slices
+            .iter()
+            .enumerate()
+            .filter(|(_, slice)| slice.is_none())
+            .map(|(idx, _)| idx)
+            .collect()
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.slices.iter().all(Option::is_some)
+    }
+
+    /// Concatenate the slices, and verify the result against the checksum of
+    /// the whole secret.
+    pub fn finish(self) -> Result<Vec<u8>, Error> {
+        if !self.is_complete() {
+            return Err(Error::Other(format!(
+                "missing {} of {} slices",
+                self.missing().len(),
+                self.slices.len()
+            )));
+        }
+        let secret = self
+            .slices
+            .into_iter()
+            .flatten()
+            .flatten()
+            .collect::<Vec<_>>();
+        if secret.len() as u64 != self.manifest.secret_len
+            || CHECKSUM_ALGORITHM.digest(&secret) != self.manifest.secret_chksum
+        {
+            return Err(Error::InvariantViolation(
+                "assembled secret doesn't match super-manifest checksum",
+            ));
+        }
+        Ok(secret)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::v0::{FromWire, ToWire};
+
+    #[quickcheck]
+    fn slices_roundtrip(secret: Vec<u8>, slice_size: u8) -> bool {
+        let slice_size = slice_size as usize + 1;
+        let (manifest, slices) = SuperManifest::split(&secret, slice_size).unwrap();
+        let manifest = SuperManifest::from_wire(manifest.to_wire()).unwrap();
+
+        let mut assembler = SliceAssembler::new(&manifest);
+        // Add the slices out of order.
+        for slice in slices.into_iter().rev() {
+            assembler.push(slice).unwrap();
+        }
+        assembler.finish().unwrap() == secret
+    }
+
+    #[test]
+    fn slices_missing() {
+        let secret = (0..100).collect::<Vec<u8>>();
+        let (manifest, slices) = SuperManifest::split(&secret, 30).unwrap();
+        assert_eq!(manifest.num_slices(), 4);
+
+        let mut assembler = SliceAssembler::new(&manifest);
+        assert_eq!(assembler.push(slices[2].clone()).unwrap(), 2);
+        assert!(assembler.push(slices[2].clone()).is_err());
+        assert!(assembler.push(b"not a slice".to_vec()).is_err());
+        assert_eq!(assembler.missing(), vec![0, 1, 3]);
+        assert!(assembler.clone().finish().is_err());
+
+        for idx in [0, 1, 3] {
+            assembler.push(slices[idx].clone()).unwrap();
+        }
+        assert_eq!(assembler.finish().unwrap(), secret);
+    }
+
+    #[test]
+    fn slices_identical() {
+        let secret = vec![0u8; 64];
+        let (manifest, slices) = SuperManifest::split(&secret, 16).unwrap();
+
+        let mut assembler = SliceAssembler::new(&manifest);
+        for (idx, slice) in slices.into_iter().enumerate() {
+            assert_eq!(assembler.push(slice).unwrap(), idx);
+        }
+        assert_eq!(assembler.finish().unwrap(), secret);
+    }
+}
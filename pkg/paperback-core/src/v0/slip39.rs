@@ -0,0 +1,681 @@
+/*
+ * paperback: paper backup generator suitable for long-term storage
+ * Copyright (C) 2018-2022 Aleksa Sarai <cyphar@cyphar.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Interoperability with [SLIP-39] mnemonic shares.
+//!
+//! Many hardware wallets already split their master secret into SLIP-39
+//! shares. [`combine`] recovers the master secret from such a share set so it
+//! can be stored in a paperback backup, and [`split`] does the reverse so that
+//! a secret recovered from paperback can be loaded back into a wallet.
+//!
+//! SLIP-39 uses its own (incompatible) secret sharing scheme over GF(2^8), so
+//! the shares cannot be converted directly into paperback key shards -- the
+//! master secret has to be recovered first.
+//!
+//! [SLIP-39]: https://github.com/satoshilabs/slips/blob/master/slip-0039.md
+
+use crate::v0::{gf256, Error};
+
+use std::{
+    collections::{BTreeMap, HashSet},
+    fmt,
+    str::FromStr,
+};
+
+use hmac::{Hmac, Mac};
+use once_cell::sync::Lazy;
+use rand::{rngs::OsRng, RngCore};
+use sha2::Sha256;
+use subtle::ConstantTimeEq;
+use zeroize::Zeroizing;
+
+static WORDLIST: Lazy<Vec<&'static str>> =
+    Lazy::new(|| include_str!("wordlists/slip39.txt").lines().collect());
+
+const RADIX_BITS: usize = 10;
+const ID_BITS: usize = 15;
+const CHECKSUM_WORDS: usize = 3;
+// Identifier, extendable flag and iteration exponent, followed by the group
+// and member parameters.
+const HEADER_WORDS: usize = 4;
+const MIN_SECRET_LENGTH: usize = 16;
+const MIN_MNEMONIC_WORDS: usize =
+    HEADER_WORDS + (MIN_SECRET_LENGTH * 8 + RADIX_BITS - 1) / RADIX_BITS + CHECKSUM_WORDS;
+const MAX_SHARE_COUNT: usize = 16;
+const MAX_ITERATION_EXPONENT: u8 = 15;
+
+const DIGEST_LENGTH: usize = 4;
+const DIGEST_INDEX: u8 = 254;
+const SECRET_INDEX: u8 = 255;
+
+const BASE_ITERATION_COUNT: u32 = 10000;
+const ROUND_COUNT: u8 = 4;
+
+const CUSTOMIZATION_STRING: &[u8] = b"shamir";
+const CUSTOMIZATION_STRING_EXTENDABLE: &[u8] = b"shamir_extendable";
+
+fn slip39_error(msg: impl Into<String>) -> Error {
+    Error::Slip39(msg.into())
+}
+
+// RS1024 checksum over GF(1024), as defined by SLIP-39.
+fn rs1024_polymod(values: impl IntoIterator<Item = u32>) -> u32 {
+    const GEN: [u32; 10] = [
+        0x00E0_E040,
+        0x01C1_C080,
+        0x0383_8100,
+        0x0707_0200,
+        0x0E0E_0009,
+        0x1C0C_2412,
+        0x3808_6C24,
+        0x3090_FC48,
+        0x21B1_F890,
+        0x03F3_F120,
+    ];
+    values.into_iter().fold(1, |chk, value| {
+        let top = chk >> 20;
+        let chk = ((chk & 0xF_FFFF) << 10) ^ value;
+        GEN.iter()
+            .enumerate()
+            .filter(|(i, _)| (top >> i) & 1 != 0)
+            .fold(chk, |chk, (_, gen)| chk ^ gen)
+    })
+}
+
+fn customization_string(extendable: bool) -> &'static [u8] {
+    if extendable {
+        CUSTOMIZATION_STRING_EXTENDABLE
+    } else {
+        CUSTOMIZATION_STRING
+    }
+}
+
+fn rs1024_checksum(extendable: bool, data: &[u16]) -> [u16; CHECKSUM_WORDS] {
+    let values = customization_string(extendable)
+        .iter()
+        .map(|&b| b as u32)
+        .chain(data.iter().map(|&w| w as u32))
+        .chain([0; CHECKSUM_WORDS]);
+    let polymod = rs1024_polymod(values) ^ 1;
+    let mut checksum = [0; CHECKSUM_WORDS];
+    for (i, word) in checksum.iter_mut().enumerate() {
+        *word = ((polymod >> (RADIX_BITS * (CHECKSUM_WORDS - 1 - i))) & 1023) as u16;
+    }
+    checksum
+}
+
+fn rs1024_verify(extendable: bool, data: &[u16]) -> bool {
+    let values = customization_string(extendable)
+        .iter()
+        .map(|&b| b as u32)
+        .chain(data.iter().map(|&w| w as u32));
+    rs1024_polymod(values) == 1
+}
+
+// Evaluate the polynomial defined by the (distinct) points in shares at x.
+fn interpolate(shares: &[(u8, &[u8])], x: u8) -> Zeroizing<Vec<u8>> {
+    Zeroizing::new(gf256::interpolate(shares, x))
+}
+
+fn share_digest(random: &[u8], secret: &[u8]) -> Result<[u8; DIGEST_LENGTH], Error> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(random)
+        .map_err(|_| Error::InvariantViolation("hmac-sha256 rejected digest key"))?;
+    mac.update(secret);
+    let mut digest = [0u8; DIGEST_LENGTH];
+    digest.copy_from_slice(&mac.finalize().into_bytes()[..DIGEST_LENGTH]);
+    Ok(digest)
+}
+
+fn split_secret(
+    threshold: u8,
+    count: u8,
+    secret: &[u8],
+) -> Result<Vec<(u8, Zeroizing<Vec<u8>>)>, Error> {
+    if threshold == 1 {
+        return Ok((0..count)
+            .map(|x| (x, Zeroizing::new(secret.to_vec())))
+            .collect());
+    }
+
+    let mut random = Zeroizing::new(vec![0u8; secret.len() - DIGEST_LENGTH]);
+    OsRng.fill_bytes(&mut random);
+    let mut digest = Zeroizing::new(share_digest(&random, secret)?.to_vec());
+    digest.extend_from_slice(&random);
+
+    let mut base = (0..threshold - 2)
+        .map(|x| {
+            let mut value = Zeroizing::new(vec![0u8; secret.len()]);
+            OsRng.fill_bytes(&mut value);
+            (x, value)
+        })
+        .collect::<Vec<_>>();
+    base.push((DIGEST_INDEX, digest));
+    base.push((SECRET_INDEX, Zeroizing::new(secret.to_vec())));
+
+    let points = base
+        .iter()
+        .map(|(x, value)| (*x, value.as_slice()))
+        .collect::<Vec<_>>();
+    let mut shares = (threshold - 2..count)
+        .map(|x| (x, interpolate(&points, x)))
+        .collect::<Vec<_>>();
+    base.truncate((threshold - 2) as usize);
+    base.append(&mut shares);
+    Ok(base)
+}
+
+fn recover_secret(threshold: u8, shares: &[(u8, &[u8])]) -> Result<Zeroizing<Vec<u8>>, Error> {
+    if threshold == 1 {
+        return Ok(Zeroizing::new(shares[0].1.to_vec()));
+    }
+    let secret = interpolate(shares, SECRET_INDEX);
+    let digest = interpolate(shares, DIGEST_INDEX);
+    let (expected, random) = digest.split_at(DIGEST_LENGTH);
+    if !bool::from(share_digest(random, &secret)?[..].ct_eq(expected)) {
+        return Err(slip39_error("invalid digest of the shared secret"));
+    }
+    Ok(secret)
+}
+
+// The Feistel network used to encrypt the master secret with the passphrase.
+fn feistel(
+    data: &[u8],
+    passphrase: &[u8],
+    iteration_exponent: u8,
+    identifier: u16,
+    extendable: bool,
+    decrypt: bool,
+) -> Zeroizing<Vec<u8>> {
+    let (left, right) = data.split_at(data.len() / 2);
+    let (mut left, mut right) = (
+        Zeroizing::new(left.to_vec()),
+        Zeroizing::new(right.to_vec()),
+    );
+
+    let mut salt = Vec::new();
+    if !extendable {
+        salt.extend_from_slice(CUSTOMIZATION_STRING);
+        salt.extend_from_slice(&identifier.to_be_bytes());
+    }
+    let iterations = (BASE_ITERATION_COUNT << iteration_exponent) / ROUND_COUNT as u32;
+
+    let mut rounds = (0..ROUND_COUNT).collect::<Vec<_>>();
+    if decrypt {
+        rounds.reverse();
+    }
+    for round in rounds {
+        let mut password = Zeroizing::new(vec![round]);
+        password.extend_from_slice(passphrase);
+        let mut round_salt = Zeroizing::new(salt.clone());
+        round_salt.extend_from_slice(&right);
+
+        let mut f = Zeroizing::new(vec![0u8; right.len()]);
+        pbkdf2::pbkdf2_hmac::<Sha256>(&password, &round_salt, iterations, &mut f);
+        for (l, f) in left.iter_mut().zip(f.iter()) {
+            *l ^= f;
+        }
+        std::mem::swap(&mut left, &mut right);
+    }
+
+    let mut output = right;
+    output.extend_from_slice(&left);
+    output
+}
+
+fn check_passphrase(passphrase: &[u8]) -> Result<(), Error> {
+    match passphrase.iter().all(|b| (32..=126).contains(b)) {
+        true => Ok(()),
+        false => Err(slip39_error(
+            "passphrase must only contain printable ascii characters",
+        )),
+    }
+}
+
+/// A single SLIP-39 mnemonic share.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Share {
+    identifier: u16,
+    extendable: bool,
+    iteration_exponent: u8,
+    group_index: u8,
+    group_threshold: u8,
+    group_count: u8,
+    member_index: u8,
+    member_threshold: u8,
+    value: Zeroizing<Vec<u8>>,
+}
+
+impl Share {
+    /// The random identifier shared by every share of the same secret.
+    pub fn identifier(&self) -> u16 {
+        self.identifier
+    }
+
+    /// The (zero-based) index of the group this share belongs to.
+    pub fn group_index(&self) -> u8 {
+        self.group_index
+    }
+
+    /// The number of groups needed to recover the secret.
+    pub fn group_threshold(&self) -> u8 {
+        self.group_threshold
+    }
+
+    /// The total number of groups.
+    pub fn group_count(&self) -> u8 {
+        self.group_count
+    }
+
+    /// The (zero-based) index of this share within its group.
+    pub fn member_index(&self) -> u8 {
+        self.member_index
+    }
+
+    /// The number of shares from this group needed to recover the group.
+    pub fn member_threshold(&self) -> u8 {
+        self.member_threshold
+    }
+
+    fn data_words(&self) -> Vec<u16> {
+        let header = (self.identifier as u64) << 25
+            | (self.extendable as u64) << 24
+            | (self.iteration_exponent as u64) << 20
+            | (self.group_index as u64) << 16
+            | ((self.group_threshold - 1) as u64) << 12
+            | ((self.group_count - 1) as u64) << 8
+            | (self.member_index as u64) << 4
+            | (self.member_threshold - 1) as u64;
+        let mut words = (0..HEADER_WORDS)
+            .rev()
+            .map(|i| ((header >> (RADIX_BITS * i)) & 1023) as u16)
+            .collect::<Vec<_>>();
+
+        // The value is left-padded with zero bits to a multiple of the radix.
+        let value_bits = self.value.len() * 8;
+        let mut bits = (RADIX_BITS - value_bits % RADIX_BITS) % RADIX_BITS;
+        let mut acc: u32 = 0;
+        for &byte in self.value.iter() {
+            acc = (acc << 8) | byte as u32;
+            bits += 8;
+            while bits >= RADIX_BITS {
+                bits -= RADIX_BITS;
+                words.push(((acc >> bits) & 1023) as u16);
+                acc &= (1 << bits) - 1;
+            }
+        }
+        words
+    }
+
+    /// Encode the share as a SLIP-39 mnemonic.
+    pub fn to_mnemonic(&self) -> Zeroizing<String> {
+        let mut words = self.data_words();
+        let checksum = rs1024_checksum(self.extendable, &words);
+        words.extend_from_slice(&checksum);
+        Zeroizing::new(
+            words
+                .iter()
+                .map(|&w| WORDLIST[w as usize])
+                .collect::<Vec<_>>()
+                .join(" "),
+        )
+    }
+}
+
+impl FromStr for Share {
+    type Err = Error;
+
+    fn from_str(mnemonic: &str) -> Result<Self, Self::Err> {
+        let words = mnemonic
+            .split_whitespace()
+            .map(|word| {
+                let word = word.to_lowercase();
+                WORDLIST
+                    .binary_search(&word.as_str())
+                    .map(|idx| idx as u16)
+                    .map_err(|_| slip39_error(format!("unknown word '{}'", word)))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if words.len() < MIN_MNEMONIC_WORDS {
+            return Err(slip39_error(format!(
+                "mnemonic must be at least {} words long (got {})",
+                MIN_MNEMONIC_WORDS,
+                words.len()
+            )));
+        }
+
+        let value_words = words.len() - HEADER_WORDS - CHECKSUM_WORDS;
+        let padding = (value_words * RADIX_BITS) % 16;
+        if padding > 8 {
+            return Err(slip39_error("invalid mnemonic length"));
+        }
+
+        let header = words[..HEADER_WORDS]
+            .iter()
+            .fold(0u64, |acc, &w| (acc << RADIX_BITS) | w as u64);
+        let field = |shift: usize, bits: usize| ((header >> shift) & ((1 << bits) - 1)) as u8;
+        let extendable = field(24, 1) != 0;
+        if !rs1024_verify(extendable, &words) {
+            return Err(slip39_error("invalid mnemonic checksum"));
+        }
+
+        let mut value = Zeroizing::new(Vec::with_capacity(value_words * RADIX_BITS / 8));
+        let mut acc: u32 = 0;
+        let mut bits = 0;
+        for (i, &word) in words[HEADER_WORDS..words.len() - CHECKSUM_WORDS]
+            .iter()
+            .enumerate()
+        {
+            acc = (acc << RADIX_BITS) | word as u32;
+            bits += RADIX_BITS;
+            if i == 0 {
+                if acc >> (RADIX_BITS - padding) != 0 {
+                    return Err(slip39_error("invalid mnemonic padding"));
+                }
+                bits -= padding;
+            }
+            while bits >= 8 {
+                bits -= 8;
+                value.push((acc >> bits) as u8);
+                acc &= (1 << bits) - 1;
+            }
+        }
+
+        let share = Share {
+            identifier: (header >> (HEADER_WORDS * RADIX_BITS - ID_BITS)) as u16,
+            extendable,
+            iteration_exponent: field(20, 4),
+            group_index: field(16, 4),
+            group_threshold: field(12, 4) + 1,
+            group_count: field(8, 4) + 1,
+            member_index: field(4, 4),
+            member_threshold: field(0, 4) + 1,
+            value,
+        };
+        if share.group_threshold > share.group_count {
+            return Err(slip39_error(format!(
+                "group threshold {} exceeds group count {}",
+                share.group_threshold, share.group_count
+            )));
+        }
+        Ok(share)
+    }
+}
+
+impl fmt::Display for Share {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_mnemonic().as_str())
+    }
+}
+
+/// Recover the master secret from a set of SLIP-39 shares.
+///
+/// Enough shares must be given to meet the threshold of at least
+/// `group_threshold` groups. Any extra shares are ignored.
+pub fn combine(shares: &[Share], passphrase: &[u8]) -> Result<Zeroizing<Vec<u8>>, Error> {
+    check_passphrase(passphrase)?;
+    let first = shares
+        .first()
+        .ok_or_else(|| slip39_error("no shares provided"))?;
+
+    let mut groups: BTreeMap<u8, Vec<&Share>> = BTreeMap::new();
+    let mut seen = HashSet::new();
+    for share in shares {
+        if share.identifier != first.identifier
+            || share.extendable != first.extendable
+            || share.iteration_exponent != first.iteration_exponent
+            || share.group_threshold != first.group_threshold
+            || share.group_count != first.group_count
+            || share.value.len() != first.value.len()
+        {
+            return Err(slip39_error("shares do not belong to the same secret"));
+        }
+        if share.group_index >= share.group_count {
+            return Err(slip39_error(format!(
+                "group index {} exceeds group count {}",
+                share.group_index, share.group_count
+            )));
+        }
+        if !seen.insert((share.group_index, share.member_index)) {
+            // Duplicate shares don't provide any more information.
+            continue;
+        }
+        let group = groups.entry(share.group_index).or_default();
+        if group.first().map_or(false, |other| {
+            other.member_threshold != share.member_threshold
+        }) {
+            return Err(slip39_error(format!(
+                "shares in group {} have inconsistent member thresholds",
+                share.group_index + 1
+            )));
+        }
+        group.push(share);
+    }
+
+    let group_secrets = groups
+        .iter()
+        .filter(|(_, members)| members.len() >= members[0].member_threshold as usize)
+        .take(first.group_threshold as usize)
+        .map(|(&group_index, members)| {
+            let threshold = members[0].member_threshold;
+            let points = members
+                .iter()
+                .take(threshold as usize)
+                .map(|share| (share.member_index, share.value.as_slice()))
+                .collect::<Vec<_>>();
+            Ok((group_index, recover_secret(threshold, &points)?))
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+    if group_secrets.len() < first.group_threshold as usize {
+        return Err(slip39_error(format!(
+            "need {} complete groups to recover the secret (got {})",
+            first.group_threshold,
+            group_secrets.len()
+        )));
+    }
+
+    let points = group_secrets
+        .iter()
+        .map(|(x, value)| (*x, value.as_slice()))
+        .collect::<Vec<_>>();
+    let encrypted = recover_secret(first.group_threshold, &points)?;
+    Ok(feistel(
+        &encrypted,
+        passphrase,
+        first.iteration_exponent,
+        first.identifier,
+        first.extendable,
+        true,
+    ))
+}
+
+/// Split a master secret into SLIP-39 shares.
+///
+/// `groups` gives the `(member_threshold, member_count)` of each group, and
+/// `group_threshold` groups are needed to recover the secret. The returned
+/// shares are in the same order as `groups`.
+pub fn split(
+    master_secret: &[u8],
+    passphrase: &[u8],
+    group_threshold: u8,
+    groups: &[(u8, u8)],
+    iteration_exponent: u8,
+) -> Result<Vec<Vec<Share>>, Error> {
+    check_passphrase(passphrase)?;
+    if master_secret.len() < MIN_SECRET_LENGTH || master_secret.len() % 2 != 0 {
+        return Err(slip39_error(format!(
+            "master secret must be an even number of bytes, at least {} bytes long (got {})",
+            MIN_SECRET_LENGTH,
+            master_secret.len()
+        )));
+    }
+    if iteration_exponent > MAX_ITERATION_EXPONENT {
+        return Err(slip39_error(format!(
+            "iteration exponent must be at most {}",
+            MAX_ITERATION_EXPONENT
+        )));
+    }
+    if groups.is_empty() || groups.len() > MAX_SHARE_COUNT {
+        return Err(slip39_error(format!(
+            "between 1 and {} groups are required",
+            MAX_SHARE_COUNT
+        )));
+    }
+    if group_threshold == 0 || group_threshold as usize > groups.len() {
+        return Err(slip39_error(format!(
+            "group threshold must be between 1 and the number of groups ({})",
+            groups.len()
+        )));
+    }
+    for (idx, &(threshold, count)) in groups.iter().enumerate() {
+        if threshold == 0 || threshold > count || count as usize > MAX_SHARE_COUNT {
+            return Err(slip39_error(format!(
+                "group {} must have between 1 and {} members, with a threshold no larger than the member count",
+                idx + 1,
+                MAX_SHARE_COUNT
+            )));
+        }
+        if threshold == 1 && count > 1 {
+            return Err(slip39_error(format!(
+                "group {} has a threshold of 1 and should only have one member",
+                idx + 1
+            )));
+        }
+    }
+
+    // Only the low bits of the random identifier are used.
+    let identifier = (OsRng.next_u32() & ((1 << ID_BITS) - 1)) as u16;
+    let extendable = true;
+    let encrypted = feistel(
+        master_secret,
+        passphrase,
+        iteration_exponent,
+        identifier,
+        extendable,
+        false,
+    );
+
+    split_secret(group_threshold, groups.len() as u8, &encrypted)?
+        .into_iter()
+        .zip(groups)
+        .map(|((group_index, group_secret), &(threshold, count))| {
+            Ok(split_secret(threshold, count, &group_secret)?
+                .into_iter()
+                .map(|(member_index, value)| Share {
+                    identifier,
+                    extendable,
+                    iteration_exponent,
+                    group_index,
+                    group_threshold,
+                    group_count: groups.len() as u8,
+                    member_index,
+                    member_threshold: threshold,
+                    value,
+                })
+                .collect::<Vec<_>>())
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // Official SLIP-39 test vectors (the passphrase is "TREZOR").
+    const PASSPHRASE: &[u8] = b"TREZOR";
+
+    fn parse(mnemonics: &[&str]) -> Vec<Share> {
+        mnemonics
+            .iter()
+            .map(|mnemonic| mnemonic.parse().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn wordlist() {
+        assert_eq!(WORDLIST.len(), 1 << RADIX_BITS);
+        assert!(WORDLIST.windows(2).all(|w| w[0] < w[1]));
+    }
+
+    #[test]
+    fn combine_single_share() {
+        let shares = parse(&["duckling enlarge academic academic agency result length solution fridge kidney coal piece deal husband erode duke ajar critical decision keyboard"]);
+        assert_eq!(
+            combine(&shares, PASSPHRASE).unwrap().as_slice(),
+            &[
+                0xbb, 0x54, 0xaa, 0xc4, 0xb8, 0x9d, 0xc8, 0x68, 0xba, 0x37, 0xd9, 0xcc, 0x21, 0xb2,
+                0xce, 0xce
+            ]
+        );
+    }
+
+    #[test]
+    fn combine_threshold() {
+        let shares = parse(&[
+            "shadow pistol academic always adequate wildlife fancy gross oasis cylinder mustang wrist rescue view short owner flip making coding armed",
+            "shadow pistol academic acid actress prayer class unknown daughter sweater depict flip twice unkind craft early superior advocate guest smoking",
+        ]);
+        assert_eq!(
+            combine(&shares, PASSPHRASE).unwrap().as_slice(),
+            &[
+                0xb4, 0x3c, 0xeb, 0x7e, 0x57, 0xa0, 0xea, 0x87, 0x66, 0x22, 0x16, 0x24, 0xd0, 0x1b,
+                0x08, 0x64
+            ]
+        );
+        // A single share is not enough.
+        assert!(combine(&shares[..1], PASSPHRASE).is_err());
+    }
+
+    #[test]
+    fn invalid_checksum() {
+        let err = "duckling enlarge academic academic agency result length solution fridge kidney coal piece deal husband erode duke ajar critical decision kidney"
+            .parse::<Share>()
+            .unwrap_err();
+        assert!(
+            matches!(err, Error::Slip39(_)),
+            "unexpected error {:?}",
+            err
+        );
+    }
+
+    #[test]
+    fn split_combine_roundtrip() {
+        let secret = (0..32).collect::<Vec<u8>>();
+        let groups = split(&secret, b"passphrase", 2, &[(1, 1), (2, 3), (3, 5)], 0).unwrap();
+        assert_eq!(groups.iter().map(Vec::len).collect::<Vec<_>>(), [1, 3, 5]);
+
+        // Shares survive being encoded as mnemonics.
+        let reparse = |shares: &[Share]| -> Vec<Share> {
+            shares
+                .iter()
+                .map(|share| share.to_mnemonic().parse().unwrap())
+                .collect()
+        };
+        let mut shares = reparse(&groups[0]);
+        shares.extend(reparse(&groups[2][1..4]));
+        assert_eq!(
+            combine(&shares, b"passphrase").unwrap().as_slice(),
+            &secret[..]
+        );
+        assert_ne!(combine(&shares, b"wrong").unwrap().as_slice(), &secret[..]);
+
+        // Only one complete group.
+        let mut shares = reparse(&groups[1][..1]);
+        shares.extend(reparse(&groups[2][..3]));
+        assert!(combine(&shares, b"passphrase").is_err());
+    }
+}
@@ -0,0 +1,123 @@
+/*
+ * paperback: paper backup generator suitable for long-term storage
+ * Copyright (C) 2018-2022 Aleksa Sarai <cyphar@cyphar.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! References to the archived format specification of each paperback version.
+//!
+//! Paperback documents are meant to be recoverable decades after they were
+//! printed, possibly by someone writing a clean-room implementation of the
+//! format because no working copy of paperback can be found. To help them, the
+//! printed manifest includes a [`SpecReference`] -- the format version and the
+//! checksum of the archived specification (`DESIGN.md` in the paperback
+//! repository) for that version -- so that they can be sure the specification
+//! they found is the one the documents were created with.
+
+use crate::v0::{Error, CHECKSUM_ALGORITHM, CHECKSUM_MULTIBASE, PAPERBACK_VERSION};
+
+use multihash::{Multihash, MultihashDigest};
+
+// BLAKE2b-256 checksums of the archived specification of each format version.
+// These must never be changed once a version has been released.
+const ARCHIVED_SPECS: [(u32, [u8; 32]); 1] = [(
+    0,
+    [
+        0xdf, 0xcb, 0x34, 0xdb, 0x76, 0x79, 0x95, 0xfb, 0x4b, 0xd3, 0xcc, 0x75, 0x83, 0x09, 0xbe,
+        0xea, 0x34, 0x69, 0xad, 0xd4, 0xf0, 0x47, 0x3e, 0x13, 0xb6, 0x40, 0xd5, 0x53, 0xcd, 0xf8,
+        0xc5, 0xa0,
+    ],
+)];
+
+/// The format version and checksum of the archived specification for that
+/// version.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SpecReference {
+    pub(super) version: u32,
+    pub(super) spec_chksum: Multihash,
+}
+
+impl SpecReference {
+    /// The reference for the archived specification of `version`, if it is
+    /// known to this version of paperback.
+    pub fn for_version(version: u32) -> Option<Self> {
+        ARCHIVED_SPECS
+            .iter()
+            .find(|(spec_version, _)| *spec_version == version)
+            .map(|(version, digest)| Self {
+                version: *version,
+                spec_chksum: Multihash::wrap(CHECKSUM_ALGORITHM.into(), digest)
+                    .expect("archived spec digests must fit in a multihash"),
+            })
+    }
+
+    /// The reference for the format version created by this version of
+    /// paperback.
+    pub fn current() -> Self {
+        Self::for_version(PAPERBACK_VERSION)
+            .expect("the current format version must have an archived spec")
+    }
+
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    pub fn spec_checksum(&self) -> &Multihash {
+        &self.spec_chksum
+    }
+
+    pub fn spec_checksum_string(&self) -> String {
+        multibase::encode(CHECKSUM_MULTIBASE, self.spec_chksum.to_bytes())
+    }
+
+    /// Verify that `spec` is the archived specification this reference
+    /// refers to.
+    pub fn verify<B: AsRef<[u8]>>(&self, spec: B) -> Result<(), Error> {
+        let code = multihash::Code::try_from(self.spec_chksum.code())
+            .map_err(|err| Error::Other(format!("unsupported spec checksum: {}", err)))?;
+        if code.digest(spec.as_ref()) != self.spec_chksum {
+            return Err(Error::Other(format!(
+                "specification does not match the archived specification for paperback-v{}",
+                self.version
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::v0::{FromWire, ToWire};
+
+    #[test]
+    fn spec_reference() {
+        let reference = SpecReference::current();
+        assert_eq!(reference.version(), PAPERBACK_VERSION);
+        assert_eq!(SpecReference::for_version(1337), None);
+        assert_eq!(
+            SpecReference::from_wire(reference.to_wire()).unwrap(),
+            reference
+        );
+
+        assert!(reference.verify(b"not the specification").is_err());
+        let fake = SpecReference {
+            version: 0,
+            spec_chksum: CHECKSUM_ALGORITHM.digest(b"specification"),
+        };
+        fake.verify(b"specification").unwrap();
+    }
+}
@@ -0,0 +1,169 @@
+/*
+ * paperback: paper backup generator suitable for long-term storage
+ * Copyright (C) 2018-2022 Aleksa Sarai <cyphar@cyphar.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use crate::v0::{
+    algorithms::Algorithm,
+    constraints::{self, RecoveryConstraint},
+    plausibility::Issue,
+    policy::PolicyViolation,
+};
+
+use std::fmt;
+
+/// A non-fatal problem found during an operation.
+///
+/// Unlike errors, warnings do not stop an operation from completing, but they
+/// usually indicate something the user should know about before relying on the
+/// result.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Warning {
+    /// The document was created with a different paperback format version.
+    FormatVersion { version: u32 },
+    /// The backup parameters provide weaker protection than the user might
+    /// expect.
+    WeakParameters(String),
+    /// A rendered page is close to its maximum capacity.
+    NearCapacity {
+        what: &'static str,
+        used: usize,
+        capacity: usize,
+    },
+    /// A signature could not be verified, usually because the document it
+    /// belongs to was not provided.
+    UnverifiedSignature(String),
+    /// The recovered plaintext does not look like what was expected.
+    ImplausiblePlaintext(Issue),
+    /// The backup violates the configured policy, but the policy was
+    /// explicitly overridden.
+    PolicyOverridden(PolicyViolation),
+    /// A recovery constraint is out of date and should be reviewed.
+    StaleConstraint(RecoveryConstraint),
+    /// The documents were created by a test run, and must not be used as a
+    /// real backup.
+    TestRun,
+    /// Some key shards disagreed with the rest of the quorum during
+    /// cross-verification, and were not used for recovery.
+    InconsistentShards(Vec<String>),
+    /// The document uses an algorithm which is past its review date.
+    AlgorithmSunset(&'static Algorithm),
+    /// A key shard claims to have been minted by a quorum, but its expansion
+    /// record was not counter-signed by a quorum of the backup (see
+    /// [`Quorum::audit_expansions`](crate::v0::Quorum::audit_expansions)).
+    UnauthorisedExpansion(String),
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::FormatVersion { version } => write!(
+                f,
+                "document uses paperback format version {} (current version is {})",
+                version,
+                crate::v0::PAPERBACK_VERSION
+            ),
+            Self::WeakParameters(msg) => write!(f, "weak backup parameters: {}", msg),
+            Self::NearCapacity {
+                what,
+                used,
+                capacity,
+            } => write!(
+                f,
+                "{} is near capacity ({} of {} bytes used)",
+                what, used, capacity
+            ),
+            Self::UnverifiedSignature(msg) => write!(f, "unverified signature: {}", msg),
+            Self::ImplausiblePlaintext(issue) => write!(f, "{}", issue),
+            Self::PolicyOverridden(violation) => write!(f, "policy overridden: {}", violation),
+            Self::StaleConstraint(RecoveryConstraint::ReviewBy(date)) => write!(
+                f,
+                "review date {} has passed -- the backup should be re-verified",
+                constraints::format_date(*date)
+            ),
+            Self::StaleConstraint(constraint) => {
+                write!(f, "recovery constraint {} is stale", constraint)
+            }
+            Self::TestRun => write!(f, "documents were created by a test run -- do not use"),
+            Self::InconsistentShards(ids) => write!(
+                f,
+                "key shards [{}] disagree with the rest of the quorum and may be forged or corrupted",
+                ids.join(" ")
+            ),
+            Self::AlgorithmSunset(algorithm) => write!(
+                f,
+                "this backup uses {} {} parameters from {} (due for review on {}); consider migrating",
+                algorithm.name,
+                algorithm.parameters,
+                algorithm.adopted,
+                constraints::format_date(algorithm.review_after)
+            ),
+            Self::UnauthorisedExpansion(id) => write!(
+                f,
+                "the expansion record of key shard {} was not counter-signed by a quorum -- it may have been minted without authorisation",
+                id
+            ),
+        }
+    }
+}
+
+/// A collection of [`Warning`]s produced by an operation.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Warnings(Vec<Warning>);
+
+impl Warnings {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, warning: Warning) -> &mut Self {
+        self.0.push(warning);
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Warning> {
+        self.0.iter()
+    }
+}
+
+impl Extend<Warning> for Warnings {
+    fn extend<I: IntoIterator<Item = Warning>>(&mut self, iter: I) {
+        self.0.extend(iter)
+    }
+}
+
+impl FromIterator<Warning> for Warnings {
+    fn from_iter<I: IntoIterator<Item = Warning>>(iter: I) -> Self {
+        Self(iter.into_iter().collect())
+    }
+}
+
+impl IntoIterator for Warnings {
+    type Item = Warning;
+    type IntoIter = std::vec::IntoIter<Warning>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
@@ -0,0 +1,230 @@
+/*
+ * paperback: paper backup generator suitable for long-term storage
+ * Copyright (C) 2018-2022 Aleksa Sarai <cyphar@cyphar.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use crate::v0::{
+    airgap::{
+        AirGapRequest, AirGapResponse, ExpandShardsApproval, ExpandShardsRequest, Operation,
+        AIRGAP_NONCE_LENGTH,
+    },
+    wire::{FromWire, ParseError, ParseSection, ToWire},
+};
+
+use unsigned_varint::encode as varuint_encode;
+
+impl ToWire for AirGapRequest {
+    fn to_wire(&self) -> Vec<u8> {
+        let mut bytes = vec![];
+
+        // Encode operation.
+        bytes.extend_from_slice(varuint_encode::u32(
+            self.operation.id(),
+            &mut varuint_encode::u32_buffer(),
+        ));
+
+        // Encode nonce.
+        bytes.extend_from_slice(&self.nonce);
+
+        // Encode payload (length-prefixed).
+        varuint_encode::usize(self.payload.len(), &mut varuint_encode::usize_buffer())
+            .iter()
+            .chain(&self.payload)
+            .for_each(|b| bytes.push(*b));
+
+        bytes
+    }
+}
+
+fn operation(input: &[u8]) -> nom::IResult<&[u8], Operation> {
+    use nom::combinator::map_opt;
+    use unsigned_varint::nom as varuint_nom;
+
+    map_opt(varuint_nom::u32, Operation::from_id)(input)
+}
+
+impl FromWire for AirGapRequest {
+    fn from_wire_partial(input: &[u8]) -> Result<(&[u8], Self), ParseError> {
+        use nom::{bytes::complete::take, combinator::complete, multi::length_data, IResult};
+        use unsigned_varint::nom as varuint_nom;
+
+        type Parsed<'a> = (Operation, &'a [u8], &'a [u8]);
+
+        fn parse(input: &[u8]) -> IResult<&[u8], Parsed<'_>> {
+            let (input, operation) = operation(input)?;
+            let (input, nonce) = take(AIRGAP_NONCE_LENGTH)(input)?;
+            let (input, payload) = length_data(varuint_nom::usize)(input)?;
+
+            Ok((input, (operation, nonce, payload)))
+        }
+        let mut parse = complete(parse);
+
+        let (input, (operation, nonce, payload)) = parse(input).map_err(ParseError::expecting(
+            ParseSection::Payload,
+            "an air-gap request",
+        ))?;
+
+        Ok((
+            input,
+            AirGapRequest {
+                operation,
+                nonce: nonce.try_into().expect("nonce should be the right length"),
+                payload: payload.into(),
+            },
+        ))
+    }
+}
+
+impl ToWire for AirGapResponse {
+    fn to_wire(&self) -> Vec<u8> {
+        let mut bytes = vec![];
+
+        // Encode request checksum.
+        bytes.append(&mut self.request_chksum.to_bytes());
+
+        // Encode operation.
+        bytes.extend_from_slice(varuint_encode::u32(
+            self.operation.id(),
+            &mut varuint_encode::u32_buffer(),
+        ));
+
+        // Encode payload (length-prefixed).
+        varuint_encode::usize(self.payload.len(), &mut varuint_encode::usize_buffer())
+            .iter()
+            .chain(&self.payload)
+            .for_each(|b| bytes.push(*b));
+
+        bytes
+    }
+}
+
+impl FromWire for AirGapResponse {
+    fn from_wire_partial(input: &[u8]) -> Result<(&[u8], Self), ParseError> {
+        use crate::v0::wire::helpers::multihash;
+        use multihash::Multihash;
+        use nom::{combinator::complete, multi::length_data, IResult};
+        use unsigned_varint::nom as varuint_nom;
+
+        fn parse(input: &[u8]) -> IResult<&[u8], (Multihash, Operation, &[u8])> {
+            let (input, request_chksum) = multihash(input)?;
+            let (input, operation) = operation(input)?;
+            let (input, payload) = length_data(varuint_nom::usize)(input)?;
+
+            Ok((input, (request_chksum, operation, payload)))
+        }
+        let mut parse = complete(parse);
+
+        let (input, (request_chksum, operation, payload)) = parse(input).map_err(
+            ParseError::expecting(ParseSection::Payload, "an air-gap response"),
+        )?;
+
+        Ok((
+            input,
+            AirGapResponse {
+                request_chksum,
+                operation,
+                payload: payload.into(),
+            },
+        ))
+    }
+}
+
+impl ToWire for ExpandShardsRequest {
+    fn to_wire(&self) -> Vec<u8> {
+        let mut bytes = vec![];
+
+        // Encode document ID (length-prefixed).
+        varuint_encode::usize(self.document_id.len(), &mut varuint_encode::usize_buffer())
+            .iter()
+            .chain(self.document_id.as_bytes())
+            .for_each(|b| bytes.push(*b));
+
+        // Encode number of shards.
+        bytes.extend_from_slice(varuint_encode::u32(
+            self.num_shards,
+            &mut varuint_encode::u32_buffer(),
+        ));
+
+        bytes
+    }
+}
+
+impl FromWire for ExpandShardsRequest {
+    fn from_wire_partial(input: &[u8]) -> Result<(&[u8], Self), ParseError> {
+        use nom::{combinator::complete, multi::length_data, IResult};
+        use unsigned_varint::nom as varuint_nom;
+
+        fn parse(input: &[u8]) -> IResult<&[u8], (&[u8], u32)> {
+            let (input, document_id) = length_data(varuint_nom::usize)(input)?;
+            let (input, num_shards) = varuint_nom::u32(input)?;
+
+            Ok((input, (document_id, num_shards)))
+        }
+        let mut parse = complete(parse);
+
+        let (input, (document_id, num_shards)) = parse(input).map_err(ParseError::expecting(
+            ParseSection::Payload,
+            "an expansion request",
+        ))?;
+
+        Ok((
+            input,
+            ExpandShardsRequest {
+                document_id: String::from_utf8(document_id.to_vec())
+                    .map_err(|err| format!("document id is not valid UTF-8: {}", err))?,
+                num_shards,
+            },
+        ))
+    }
+}
+
+impl ToWire for ExpandShardsApproval {
+    fn to_wire(&self) -> Vec<u8> {
+        let mut bytes = vec![];
+
+        // Encode key shard checksums (count-prefixed).
+        bytes.extend_from_slice(varuint_encode::usize(
+            self.shard_chksums.len(),
+            &mut varuint_encode::usize_buffer(),
+        ));
+        for chksum in &self.shard_chksums {
+            bytes.append(&mut chksum.to_bytes());
+        }
+
+        bytes
+    }
+}
+
+impl FromWire for ExpandShardsApproval {
+    fn from_wire_partial(input: &[u8]) -> Result<(&[u8], Self), ParseError> {
+        use crate::v0::wire::helpers::multihash;
+        use multihash::Multihash;
+        use nom::{combinator::complete, multi::length_count, IResult};
+        use unsigned_varint::nom as varuint_nom;
+
+        fn parse(input: &[u8]) -> IResult<&[u8], Vec<Multihash>> {
+            length_count(varuint_nom::usize, multihash)(input)
+        }
+        let mut parse = complete(parse);
+
+        let (input, shard_chksums) = parse(input).map_err(ParseError::expecting(
+            ParseSection::Payload,
+            "an expansion approval",
+        ))?;
+
+        Ok((input, ExpandShardsApproval { shard_chksums }))
+    }
+}
@@ -0,0 +1,99 @@
+/*
+ * paperback: paper backup generator suitable for long-term storage
+ * Copyright (C) 2018-2022 Aleksa Sarai <cyphar@cyphar.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use crate::v0::{
+    bundle::{SecretBundle, SecretEntry},
+    wire::{FromWire, ParseError, ParseSection, ToWire},
+};
+
+use unsigned_varint::{encode as varuint_encode, nom as varuint_nom};
+
+impl ToWire for SecretBundle {
+    fn to_wire(&self) -> Vec<u8> {
+        let mut buffer = varuint_encode::usize_buffer();
+        let mut bytes = vec![];
+
+        // Encode entries (count-prefixed, in order).
+        varuint_encode::usize(self.entries.len(), &mut buffer)
+            .iter()
+            .for_each(|b| bytes.push(*b));
+        for entry in &self.entries {
+            // Encode label (length-prefixed).
+            varuint_encode::usize(entry.label.len(), &mut buffer)
+                .iter()
+                .chain(entry.label.as_bytes())
+                .for_each(|b| bytes.push(*b));
+
+            // Encode data (length-prefixed).
+            varuint_encode::usize(entry.data.len(), &mut buffer)
+                .iter()
+                .chain(&entry.data)
+                .for_each(|b| bytes.push(*b));
+        }
+
+        bytes
+    }
+}
+
+impl FromWire for SecretBundle {
+    fn from_wire_partial(input: &[u8]) -> Result<(&[u8], Self), ParseError> {
+        use nom::{
+            combinator::{complete, map_res},
+            multi::{length_count, length_data},
+            sequence::pair,
+            IResult,
+        };
+
+        fn entry(input: &[u8]) -> IResult<&[u8], SecretEntry> {
+            let (input, (label, data)) = pair(
+                map_res(length_data(varuint_nom::usize), |s: &[u8]| {
+                    String::from_utf8(s.to_vec())
+                }),
+                length_data(varuint_nom::usize),
+            )(input)?;
+
+            Ok((
+                input,
+                SecretEntry {
+                    label,
+                    data: data.into(),
+                },
+            ))
+        }
+
+        fn parse(input: &[u8]) -> IResult<&[u8], Vec<SecretEntry>> {
+            length_count(varuint_nom::usize, entry)(input)
+        }
+        let mut parse = complete(parse);
+
+        let (input, entries) = parse(input).map_err(ParseError::expecting(
+            ParseSection::Payload,
+            "secret bundle entries",
+        ))?;
+
+        // Re-check the labels, so that a bundle is only ever constructed with
+        // valid (and unique) labels.
+        let mut bundle = SecretBundle::new();
+        for entry in entries {
+            bundle.check_label(&entry.label)?;
+            bundle.entries.push(entry);
+        }
+
+        Ok((input, bundle))
+    }
+}
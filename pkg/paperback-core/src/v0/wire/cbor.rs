@@ -0,0 +1,482 @@
+/*
+ * paperback: paper backup generator suitable for long-term storage
+ * Copyright (C) 2018-2022 Aleksa Sarai <cyphar@cyphar.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! A minimal implementation of deterministically-encoded CBOR (RFC 8949,
+//! Section 4.2.1), used for the payloads of documents with CBOR_PAYLOAD_FLAG
+//! set.
+//!
+//! Payloads are maps with unsigned integer keys, whose values are unsigned
+//! integers, byte strings (nested structures keep their existing binary
+//! encoding) or arrays of them. Only the deterministic encoding is accepted
+//! (shortest-form integers, definite lengths, keys in ascending order), so
+//! that every payload has exactly one encoding and signatures computed over
+//! the re-encoded payload still verify.
+
+use crate::v0::wire::{ParseError, ParseSection};
+
+use std::collections::BTreeMap;
+
+const MAJOR_UINT: u8 = 0;
+const MAJOR_BYTES: u8 = 2;
+const MAJOR_ARRAY: u8 = 4;
+const MAJOR_MAP: u8 = 5;
+
+// Unknown values are only validated (not interpreted), but we still need to
+// bound the recursion.
+const MAX_DEPTH: usize = 16;
+
+fn encode_head(major: u8, value: u64, bytes: &mut Vec<u8>) {
+    let major = major << 5;
+    match value {
+        0..=23 => bytes.push(major | value as u8),
+        24..=0xff => bytes.extend_from_slice(&[major | 24, value as u8]),
+        0x100..=0xffff => {
+            bytes.push(major | 25);
+            bytes.extend_from_slice(&(value as u16).to_be_bytes());
+        }
+        0x1_0000..=0xffff_ffff => {
+            bytes.push(major | 26);
+            bytes.extend_from_slice(&(value as u32).to_be_bytes());
+        }
+        _ => {
+            bytes.push(major | 27);
+            bytes.extend_from_slice(&value.to_be_bytes());
+        }
+    }
+}
+
+/// Encode an unsigned integer.
+pub(super) fn uint<I: Into<u64>>(value: I) -> Vec<u8> {
+    let mut bytes = vec![];
+    encode_head(MAJOR_UINT, value.into(), &mut bytes);
+    bytes
+}
+
+/// Encode a byte string.
+pub(super) fn bytes<B: AsRef<[u8]>>(data: B) -> Vec<u8> {
+    let data = data.as_ref();
+    let mut bytes = vec![];
+    encode_head(MAJOR_BYTES, data.len() as u64, &mut bytes);
+    bytes.extend_from_slice(data);
+    bytes
+}
+
+/// Encode an array of already-encoded items.
+pub(super) fn array<I: IntoIterator<Item = Vec<u8>>>(items: I) -> Vec<u8> {
+    let items = items.into_iter().collect::<Vec<_>>();
+    let mut bytes = vec![];
+    encode_head(MAJOR_ARRAY, items.len() as u64, &mut bytes);
+    items
+        .into_iter()
+        .for_each(|mut item| bytes.append(&mut item));
+    bytes
+}
+
+/// Encode a map of already-encoded values. Shorter encodings of unsigned
+/// integers sort first, so the (numeric) order of the keys is also the
+/// deterministic (bytewise) order of their encodings.
+pub(super) fn map(entries: &BTreeMap<u64, Vec<u8>>) -> Vec<u8> {
+    let mut bytes = vec![];
+    encode_head(MAJOR_MAP, entries.len() as u64, &mut bytes);
+    entries.iter().for_each(|(key, value)| {
+        encode_head(MAJOR_UINT, *key, &mut bytes);
+        bytes.extend_from_slice(value);
+    });
+    bytes
+}
+
+fn major_name(major: u8) -> String {
+    match major {
+        MAJOR_UINT => "an unsigned integer".to_string(),
+        MAJOR_BYTES => "a byte string".to_string(),
+        MAJOR_ARRAY => "an array".to_string(),
+        MAJOR_MAP => "a map".to_string(),
+        major => format!("cbor major type {}", major),
+    }
+}
+
+// Decode the head of an item, returning its major type and argument.
+fn take_head<'a>(
+    section: ParseSection,
+    expected: &str,
+    input: &'a [u8],
+) -> Result<(&'a [u8], u8, u64), ParseError> {
+    let (&initial, rest) = input
+        .split_first()
+        .ok_or_else(|| ParseError::new(section, input, expected, "end of input"))?;
+    let (major, info) = (initial >> 5, initial & 0x1f);
+    let (len, min) = match info {
+        0..=23 => return Ok((rest, major, info.into())),
+        24 => (1, 24),
+        25 => (2, 0x100),
+        26 => (4, 0x1_0000),
+        27 => (8, 0x1_0000_0000),
+        _ => {
+            return Err(ParseError::new(
+                section,
+                input,
+                expected,
+                format!("indefinite-length or reserved cbor head {:#04x}", initial),
+            ))
+        }
+    };
+    if rest.len() < len {
+        return Err(ParseError::new(section, input, expected, "end of input"));
+    }
+    let value = rest[..len]
+        .iter()
+        .fold(0u64, |value, b| (value << 8) | u64::from(*b));
+    if value < min {
+        return Err(ParseError::new(
+            section,
+            input,
+            expected,
+            "non-shortest cbor integer encoding",
+        ));
+    }
+    Ok((&rest[len..], major, value))
+}
+
+// Split off the contents of a byte string whose head has been decoded.
+fn take_contents<'a>(
+    section: ParseSection,
+    input: &'a [u8],
+    len: u64,
+) -> Result<(&'a [u8], &'a [u8]), ParseError> {
+    match usize::try_from(len) {
+        Ok(len) if len <= input.len() => Ok((&input[len..], &input[..len])),
+        _ => Err(ParseError::new(
+            section,
+            input,
+            format!("{} bytes of data", len),
+            format!("{} bytes", input.len()),
+        )),
+    }
+}
+
+/// A single (validated) data item.
+#[derive(Clone, Copy, Debug)]
+pub(super) struct Item<'a> {
+    section: ParseSection,
+    // The input starting at this item. This is not truncated to the end of the
+    // item, so that errors can be located in the whole input.
+    input: &'a [u8],
+    len: usize,
+}
+
+impl<'a> Item<'a> {
+    fn take_nested(
+        section: ParseSection,
+        input: &'a [u8],
+        depth: usize,
+    ) -> Result<(&'a [u8], Self), ParseError> {
+        let expected = "an unsigned integer, byte string, array or map";
+        let (mut rest, major, value) = take_head(section, expected, input)?;
+        match major {
+            MAJOR_UINT => (),
+            MAJOR_BYTES => rest = take_contents(section, rest, value)?.0,
+            MAJOR_ARRAY | MAJOR_MAP if depth >= MAX_DEPTH => {
+                return Err(ParseError::new(
+                    section,
+                    input,
+                    expected,
+                    "too deeply nested cbor data",
+                ))
+            }
+            MAJOR_ARRAY => {
+                for _ in 0..value {
+                    rest = Self::take_nested(section, rest, depth + 1)?.0;
+                }
+            }
+            MAJOR_MAP => {
+                for _ in 0..value {
+                    rest = Self::take_nested(section, rest, depth + 1)?.0;
+                    rest = Self::take_nested(section, rest, depth + 1)?.0;
+                }
+            }
+            major => return Err(ParseError::new(section, input, expected, major_name(major))),
+        }
+        Ok((
+            rest,
+            Self {
+                section,
+                input,
+                len: input.len() - rest.len(),
+            },
+        ))
+    }
+
+    /// Parse the item at the start of `input`.
+    pub(super) fn take(
+        section: ParseSection,
+        input: &'a [u8],
+    ) -> Result<(&'a [u8], Self), ParseError> {
+        Self::take_nested(section, input, 0)
+    }
+
+    /// Report errors in this item as being in `section`.
+    pub(super) fn in_section(self, section: ParseSection) -> Self {
+        Self { section, ..self }
+    }
+
+    /// The encoding of the whole item.
+    pub(super) fn raw(&self) -> &'a [u8] {
+        &self.input[..self.len]
+    }
+
+    /// An error describing this item as invalid.
+    pub(super) fn invalid<E: Into<String>, F: Into<String>>(
+        &self,
+        expected: E,
+        found: F,
+    ) -> ParseError {
+        ParseError::new(self.section, self.input, expected, found)
+    }
+
+    fn head(&self, expected: &str, major: u8) -> Result<(&'a [u8], u64), ParseError> {
+        match take_head(self.section, expected, self.input)? {
+            (rest, found, value) if found == major => Ok((rest, value)),
+            (_, found, _) => Err(self.invalid(expected, major_name(found))),
+        }
+    }
+
+    /// Decode the item as an unsigned integer.
+    pub(super) fn uint(&self, expected: &str) -> Result<u64, ParseError> {
+        self.head(expected, MAJOR_UINT).map(|(_, value)| value)
+    }
+
+    /// Decode the item as an unsigned integer which fits in a u32.
+    pub(super) fn uint32(&self, expected: &str) -> Result<u32, ParseError> {
+        let value = self.uint(expected)?;
+        u32::try_from(value).map_err(|_| self.invalid(expected, format!("integer {}", value)))
+    }
+
+    /// Decode the item as a byte string.
+    pub(super) fn bytes(&self, expected: &str) -> Result<&'a [u8], ParseError> {
+        let (rest, len) = self.head(expected, MAJOR_BYTES)?;
+        Ok(take_contents(self.section, rest, len)?.1)
+    }
+
+    /// Decode the item as an array.
+    pub(super) fn array(&self, expected: &str) -> Result<Vec<Item<'a>>, ParseError> {
+        let (mut rest, len) = self.head(expected, MAJOR_ARRAY)?;
+        let mut items = vec![];
+        for _ in 0..len {
+            let (next, item) = Self::take(self.section, rest)?;
+            items.push(item);
+            rest = next;
+        }
+        Ok(items)
+    }
+
+    /// Decode the item as a byte string containing data in the (non-CBOR)
+    /// wire format, which must be parsed in its entirety by `parse`.
+    pub(super) fn wire<T, F>(&self, expected: &str, parse: F) -> Result<T, ParseError>
+    where
+        F: FnOnce(&'a [u8]) -> Result<(&'a [u8], T), ParseError>,
+    {
+        let (contents, len) = self.head(expected, MAJOR_BYTES)?;
+        take_contents(self.section, contents, len)?;
+        // Give the parser the rest of the input (rather than only the byte
+        // string) so that the offsets of any errors are correct.
+        let (rest, value) = parse(contents)?;
+        let used = contents.len() - rest.len();
+        if used as u64 != len {
+            return Err(ParseError::new(
+                self.section,
+                rest,
+                format!("{} ({} bytes)", expected, len),
+                format!("{} bytes", used),
+            ));
+        }
+        Ok(value)
+    }
+}
+
+/// A map with unsigned integer keys.
+#[derive(Debug)]
+pub(super) struct Map<'a> {
+    section: ParseSection,
+    input: &'a [u8],
+    entries: BTreeMap<u64, Item<'a>>,
+}
+
+impl<'a> Map<'a> {
+    /// Parse the map at the start of `input`.
+    pub(super) fn take(
+        section: ParseSection,
+        input: &'a [u8],
+    ) -> Result<(&'a [u8], Self), ParseError> {
+        let (mut rest, major, len) = take_head(section, "a cbor map", input)?;
+        if major != MAJOR_MAP {
+            return Err(ParseError::new(
+                section,
+                input,
+                "a cbor map",
+                major_name(major),
+            ));
+        }
+
+        let mut entries = BTreeMap::new();
+        for _ in 0..len {
+            let (next, major, key) = take_head(section, "a map key", rest)?;
+            if major != MAJOR_UINT {
+                return Err(ParseError::new(
+                    section,
+                    rest,
+                    "an unsigned integer map key",
+                    major_name(major),
+                ));
+            }
+            if let Some(last) = entries.keys().next_back() {
+                if key <= *last {
+                    return Err(ParseError::new(
+                        section,
+                        rest,
+                        format!("a map key greater than {}", last),
+                        format!("key {}", key),
+                    ));
+                }
+            }
+            let (next, value) = Item::take(section, next)?;
+            entries.insert(key, value);
+            rest = next;
+        }
+
+        Ok((
+            rest,
+            Self {
+                section,
+                input,
+                entries,
+            },
+        ))
+    }
+
+    /// Remove the value of `key` from the map, if it is present.
+    pub(super) fn remove(&mut self, key: u64) -> Option<Item<'a>> {
+        self.entries.remove(&key)
+    }
+
+    /// Remove the value of `key` from the map, which must be present.
+    pub(super) fn required(&mut self, key: u64, expected: &str) -> Result<Item<'a>, ParseError> {
+        self.remove(key).ok_or_else(|| {
+            ParseError::new(
+                self.section,
+                self.input,
+                format!("{} (field {})", expected, key),
+                "missing field",
+            )
+        })
+    }
+
+    /// Remove the value of `key` from the map, which must be present if and
+    /// only if `present` is set.
+    pub(super) fn optional(
+        &mut self,
+        key: u64,
+        present: bool,
+        expected: &str,
+    ) -> Result<Option<Item<'a>>, ParseError> {
+        match (self.remove(key), present) {
+            (Some(item), true) => Ok(Some(item)),
+            (None, false) => Ok(None),
+            (None, true) => Err(ParseError::new(
+                self.section,
+                self.input,
+                format!("{} (field {})", expected, key),
+                "missing field",
+            )),
+            (Some(item), false) => Err(item.invalid(
+                format!("no {} (field {})", expected, key),
+                "an unexpected field",
+            )),
+        }
+    }
+
+    /// The (encoded) values of all of the keys which were not removed from
+    /// the map, so that fields added by newer versions of paperback are
+    /// preserved.
+    pub(super) fn into_extensions(self) -> BTreeMap<u64, Vec<u8>> {
+        self.entries
+            .into_iter()
+            .map(|(key, item)| (key, item.raw().to_vec()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn cbor_deterministic() {
+        let mut entries = BTreeMap::new();
+        entries.insert(1000, bytes(b"abc"));
+        entries.insert(1, uint(24u32));
+        entries.insert(23, array([uint(0u32), uint(u64::MAX)]));
+        let encoded = map(&entries);
+        assert_eq!(
+            encoded,
+            [
+                &[0xa3, 0x01, 0x18, 0x18, 0x17, 0x82, 0x00, 0x1b][..],
+                &[0xff; 8],
+                &[0x19, 0x03, 0xe8, 0x43, b'a', b'b', b'c'],
+            ]
+            .concat()
+        );
+
+        let (rest, mut decoded) = Map::take(ParseSection::Payload, &encoded).unwrap();
+        assert!(rest.is_empty());
+        assert_eq!(decoded.required(1, "a").unwrap().uint32("a").unwrap(), 24);
+        let array = decoded.required(23, "b").unwrap().array("b").unwrap();
+        assert_eq!(array[1].uint("c").unwrap(), u64::MAX);
+        assert!(decoded.optional(2, false, "d").unwrap().is_none());
+        assert_eq!(decoded.into_extensions(), {
+            let mut extensions = BTreeMap::new();
+            extensions.insert(1000, bytes(b"abc"));
+            extensions
+        });
+    }
+
+    #[test]
+    fn cbor_reject_noncanonical() {
+        for (input, offset) in [
+            // Non-shortest integer.
+            (&[0xa1, 0x01, 0x18, 0x01][..], 2),
+            // Keys out of order.
+            (&[0xa2, 0x02, 0x00, 0x01, 0x00], 3),
+            // Duplicate keys.
+            (&[0xa2, 0x01, 0x00, 0x01, 0x00], 3),
+            // Indefinite-length byte string.
+            (&[0xa1, 0x01, 0x5f, 0x41, 0x00, 0xff], 2),
+            // Text string keys.
+            (&[0xa1, 0x61, b'a', 0x00], 1),
+            // Truncated byte string.
+            (&[0xa1, 0x01, 0x45, 0x00], 3),
+        ] {
+            let err = Map::take(ParseSection::Payload, input)
+                .unwrap_err()
+                .locate(input.len());
+            assert_eq!(err.offset(), Some(offset), "{:x?}: {}", input, err);
+        }
+
+        let nested = [vec![0xa1, 0x01], vec![0x81; MAX_DEPTH + 1], vec![0x00]].concat();
+        assert!(Map::take(ParseSection::Payload, &nested).is_err());
+    }
+}
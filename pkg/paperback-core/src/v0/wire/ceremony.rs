@@ -0,0 +1,121 @@
+/*
+ * paperback: paper backup generator suitable for long-term storage
+ * Copyright (C) 2018-2022 Aleksa Sarai <cyphar@cyphar.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use crate::v0::{
+    ceremony::{Manifest, OperatorSignature},
+    wire::{prefixes::*, FromWire, ParseError, ParseSection, ToWire},
+};
+
+use unsigned_varint::encode as varuint_encode;
+
+impl ToWire for Manifest {
+    fn to_wire(&self) -> Vec<u8> {
+        let mut bytes = vec![];
+
+        // Encode main document checksum.
+        bytes.append(&mut self.document_chksum.to_bytes());
+
+        // Encode key shard checksums (count-prefixed).
+        varuint_encode::usize(
+            self.shard_chksums.len(),
+            &mut varuint_encode::usize_buffer(),
+        )
+        .iter()
+        .for_each(|b| bytes.push(*b));
+        for chksum in &self.shard_chksums {
+            bytes.append(&mut chksum.to_bytes());
+        }
+
+        // Encode operator signatures (count-prefixed).
+        varuint_encode::usize(self.signatures.len(), &mut varuint_encode::usize_buffer())
+            .iter()
+            .for_each(|b| bytes.push(*b));
+        for sig in &self.signatures {
+            // Encode operator's Ed25519 public key.
+            varuint_encode::u32(PREFIX_ED25519_PUB, &mut varuint_encode::u32_buffer())
+                .iter()
+                .chain(sig.operator.as_bytes())
+                .for_each(|b| bytes.push(*b));
+
+            // Encode Ed25519 signature.
+            varuint_encode::u32(PREFIX_ED25519_SIG, &mut varuint_encode::u32_buffer())
+                .iter()
+                .copied()
+                .chain(sig.signature.to_bytes())
+                .for_each(|b| bytes.push(b));
+        }
+
+        bytes
+    }
+}
+
+impl FromWire for Manifest {
+    fn from_wire_partial(input: &[u8]) -> Result<(&[u8], Self), ParseError> {
+        use crate::v0::wire::helpers::{multihash, take_ed25519_pub, take_ed25519_sig};
+        use ed25519_dalek::{Signature, SignatureError, VerifyingKey};
+        use multihash::Multihash;
+        use nom::{combinator::complete, multi::length_count, IResult};
+        use unsigned_varint::nom as varuint_nom;
+
+        type RawSignature = (
+            Result<VerifyingKey, SignatureError>,
+            Result<Signature, SignatureError>,
+        );
+        type Parsed = (Multihash, Vec<Multihash>, Vec<RawSignature>);
+
+        fn signature(input: &[u8]) -> IResult<&[u8], RawSignature> {
+            let (input, operator) = take_ed25519_pub(input)?;
+            let (input, signature) = take_ed25519_sig(input)?;
+
+            Ok((input, (operator, signature)))
+        }
+
+        fn parse(input: &[u8]) -> IResult<&[u8], Parsed> {
+            let (input, document_chksum) = multihash(input)?;
+            let (input, shard_chksums) = length_count(varuint_nom::usize, multihash)(input)?;
+            let (input, signatures) = length_count(varuint_nom::usize, signature)(input)?;
+
+            Ok((input, (document_chksum, shard_chksums, signatures)))
+        }
+        let mut parse = complete(parse);
+
+        let (input, (document_chksum, shard_chksums, signatures)) =
+            parse(input).map_err(ParseError::expecting(ParseSection::Payload, "a manifest"))?;
+
+        let signatures = signatures
+            .into_iter()
+            .map(|(operator, signature)| {
+                Ok(OperatorSignature {
+                    operator: operator
+                        .map_err(|err| ParseError::ed25519("a valid operator public key", err))?,
+                    signature: signature
+                        .map_err(|err| ParseError::ed25519("a valid ed25519 signature", err))?,
+                })
+            })
+            .collect::<Result<Vec<_>, ParseError>>()?;
+
+        Ok((
+            input,
+            Manifest {
+                document_chksum,
+                shard_chksums,
+                signatures,
+            },
+        ))
+    }
+}
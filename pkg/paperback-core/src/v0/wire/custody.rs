@@ -0,0 +1,321 @@
+/*
+ * paperback: paper backup generator suitable for long-term storage
+ * Copyright (C) 2018-2022 Aleksa Sarai <cyphar@cyphar.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use crate::v0::{
+    custody::{CourierBatch, CustodyReceipt, EnvelopeProof, PossessionVerifier, SealedKeyShard},
+    wire::{prefixes::*, FromWire, ParseError, ParseSection, ToWire},
+    ChaChaPolyNonce, CHACHAPOLY_NONCE_LENGTH,
+};
+
+use unsigned_varint::encode as varuint_encode;
+
+impl ToWire for SealedKeyShard {
+    fn to_wire(&self) -> Vec<u8> {
+        let mut buffer = varuint_encode::u64_buffer();
+        let mut bytes = vec![];
+
+        // Encode holder's Ed25519 public key.
+        varuint_encode::u32(PREFIX_ED25519_PUB, &mut varuint_encode::u32_buffer())
+            .iter()
+            .chain(self.holder.as_bytes())
+            .for_each(|b| bytes.push(*b));
+
+        // Encode ephemeral X25519 public key.
+        varuint_encode::u32(PREFIX_X25519_PUB, &mut varuint_encode::u32_buffer())
+            .iter()
+            .chain(&self.ephemeral)
+            .for_each(|b| bytes.push(*b));
+
+        // Encode ChaCha20-Poly1305 nonce.
+        varuint_encode::u64(PREFIX_CHACHA20POLY1305_NONCE, &mut buffer)
+            .iter()
+            .chain(&self.nonce)
+            .for_each(|b| bytes.push(*b));
+        assert_eq!(self.nonce.len(), CHACHAPOLY_NONCE_LENGTH);
+
+        // Encode ChaCha20-Poly1305 ciphertext (length-prefixed).
+        varuint_encode::u64(PREFIX_CHACHA20POLY1305_CIPHERTEXT, &mut buffer)
+            .iter()
+            .chain(varuint_encode::usize(
+                self.ciphertext.len(),
+                &mut varuint_encode::usize_buffer(),
+            ))
+            .chain(&self.ciphertext)
+            .for_each(|b| bytes.push(*b));
+
+        bytes
+    }
+}
+
+impl FromWire for SealedKeyShard {
+    fn from_wire_partial(input: &[u8]) -> Result<(&[u8], Self), ParseError> {
+        use crate::v0::wire::helpers::{
+            take_chachapoly_ciphertext, take_chachapoly_nonce, take_ed25519_pub, take_x25519_pub,
+        };
+        use ed25519_dalek::{SignatureError, VerifyingKey};
+        use nom::{combinator::complete, IResult};
+
+        type Parsed<'a> = (
+            Result<VerifyingKey, SignatureError>,
+            [u8; 32],
+            ChaChaPolyNonce,
+            &'a [u8],
+        );
+
+        fn parse(input: &[u8]) -> IResult<&[u8], Parsed<'_>> {
+            let (input, holder) = take_ed25519_pub(input)?;
+            let (input, ephemeral) = take_x25519_pub(input)?;
+            let (input, nonce) = take_chachapoly_nonce(input)?;
+            let (input, ciphertext) = take_chachapoly_ciphertext(input)?;
+
+            Ok((input, (holder, ephemeral, nonce, ciphertext)))
+        }
+        let mut parse = complete(parse);
+
+        let (input, (holder, ephemeral, nonce, ciphertext)) = parse(input).map_err(
+            ParseError::expecting(ParseSection::Payload, "a sealed key shard"),
+        )?;
+
+        Ok((
+            input,
+            SealedKeyShard {
+                holder: holder
+                    .map_err(|err| ParseError::ed25519("a valid holder public key", err))?,
+                ephemeral,
+                nonce,
+                ciphertext: ciphertext.into(),
+            },
+        ))
+    }
+}
+
+impl ToWire for CustodyReceipt {
+    fn to_wire(&self) -> Vec<u8> {
+        let mut bytes = vec![];
+
+        // Encode shard checksum.
+        bytes.append(&mut self.shard_chksum.to_bytes());
+
+        // Encode holder's Ed25519 public key.
+        varuint_encode::u32(PREFIX_ED25519_PUB, &mut varuint_encode::u32_buffer())
+            .iter()
+            .chain(self.holder.as_bytes())
+            .for_each(|b| bytes.push(*b));
+
+        // Encode note (length-prefixed).
+        varuint_encode::usize(self.note.len(), &mut varuint_encode::usize_buffer())
+            .iter()
+            .chain(self.note.as_bytes())
+            .for_each(|b| bytes.push(*b));
+
+        // Encode Ed25519 signature.
+        varuint_encode::u32(PREFIX_ED25519_SIG, &mut varuint_encode::u32_buffer())
+            .iter()
+            .copied()
+            .chain(self.signature.to_bytes())
+            .for_each(|b| bytes.push(b));
+
+        bytes
+    }
+}
+
+impl FromWire for CustodyReceipt {
+    fn from_wire_partial(input: &[u8]) -> Result<(&[u8], Self), ParseError> {
+        use crate::v0::wire::helpers::{multihash, take_ed25519_pub, take_ed25519_sig};
+        use ed25519_dalek::{Signature, SignatureError, VerifyingKey};
+        use multihash::Multihash;
+        use nom::{combinator::complete, multi::length_data, IResult};
+        use unsigned_varint::nom as varuint_nom;
+
+        type Parsed<'a> = (
+            Multihash,
+            Result<VerifyingKey, SignatureError>,
+            &'a [u8],
+            Result<Signature, SignatureError>,
+        );
+
+        fn parse(input: &[u8]) -> IResult<&[u8], Parsed<'_>> {
+            let (input, shard_chksum) = multihash(input)?;
+            let (input, holder) = take_ed25519_pub(input)?;
+            let (input, note) = length_data(varuint_nom::usize)(input)?;
+            let (input, signature) = take_ed25519_sig(input)?;
+
+            Ok((input, (shard_chksum, holder, note, signature)))
+        }
+        let mut parse = complete(parse);
+
+        let (input, (shard_chksum, holder, note, signature)) = parse(input).map_err(
+            ParseError::expecting(ParseSection::Payload, "a custody receipt"),
+        )?;
+
+        Ok((
+            input,
+            CustodyReceipt {
+                shard_chksum,
+                holder: holder
+                    .map_err(|err| ParseError::ed25519("a valid holder public key", err))?,
+                note: String::from_utf8(note.to_vec())
+                    .map_err(|err| format!("note is not valid UTF-8: {}", err))?,
+                signature: signature
+                    .map_err(|err| ParseError::ed25519("a valid ed25519 signature", err))?,
+            },
+        ))
+    }
+}
+
+impl ToWire for EnvelopeProof {
+    fn to_wire(&self) -> Vec<u8> {
+        let mut bytes = vec![];
+
+        // Encode envelope and shard checksums.
+        bytes.append(&mut self.envelope_chksum.to_bytes());
+        bytes.append(&mut self.shard_chksum.to_bytes());
+
+        // Encode holder's Ed25519 public key.
+        varuint_encode::u32(PREFIX_ED25519_PUB, &mut varuint_encode::u32_buffer())
+            .iter()
+            .chain(self.holder.as_bytes())
+            .for_each(|b| bytes.push(*b));
+
+        // Encode Ed25519 signature.
+        varuint_encode::u32(PREFIX_ED25519_SIG, &mut varuint_encode::u32_buffer())
+            .iter()
+            .copied()
+            .chain(self.signature.to_bytes())
+            .for_each(|b| bytes.push(b));
+
+        bytes
+    }
+}
+
+impl FromWire for EnvelopeProof {
+    fn from_wire_partial(input: &[u8]) -> Result<(&[u8], Self), ParseError> {
+        use crate::v0::wire::helpers::{multihash, take_ed25519_pub, take_ed25519_sig};
+        use ed25519_dalek::{Signature, SignatureError, VerifyingKey};
+        use multihash::Multihash;
+        use nom::{combinator::complete, IResult};
+
+        type Parsed = (
+            Multihash,
+            Multihash,
+            Result<VerifyingKey, SignatureError>,
+            Result<Signature, SignatureError>,
+        );
+
+        fn parse(input: &[u8]) -> IResult<&[u8], Parsed> {
+            let (input, envelope_chksum) = multihash(input)?;
+            let (input, shard_chksum) = multihash(input)?;
+            let (input, holder) = take_ed25519_pub(input)?;
+            let (input, signature) = take_ed25519_sig(input)?;
+
+            Ok((input, (envelope_chksum, shard_chksum, holder, signature)))
+        }
+        let mut parse = complete(parse);
+
+        let (input, (envelope_chksum, shard_chksum, holder, signature)) = parse(input).map_err(
+            ParseError::expecting(ParseSection::Payload, "an envelope proof"),
+        )?;
+
+        Ok((
+            input,
+            EnvelopeProof {
+                envelope_chksum,
+                shard_chksum,
+                holder: holder
+                    .map_err(|err| ParseError::ed25519("a valid holder public key", err))?,
+                signature: signature
+                    .map_err(|err| ParseError::ed25519("a valid ed25519 signature", err))?,
+            },
+        ))
+    }
+}
+
+impl ToWire for CourierBatch {
+    fn to_wire(&self) -> Vec<u8> {
+        let mut bytes = vec![];
+
+        // Encode envelopes and their proofs (count-prefixed).
+        varuint_encode::usize(self.envelopes.len(), &mut varuint_encode::usize_buffer())
+            .iter()
+            .for_each(|b| bytes.push(*b));
+        for (envelope, proof) in &self.envelopes {
+            bytes.append(&mut envelope.to_wire());
+            bytes.append(&mut proof.to_wire());
+        }
+
+        bytes
+    }
+}
+
+impl FromWire for CourierBatch {
+    fn from_wire_partial(input: &[u8]) -> Result<(&[u8], Self), ParseError> {
+        use nom::combinator::complete;
+        use unsigned_varint::nom as varuint_nom;
+
+        let (mut input, num_envelopes) = complete(varuint_nom::usize)(input).map_err(
+            ParseError::expecting(ParseSection::Payload, "an envelope count"),
+        )?;
+
+        let mut batch = CourierBatch::new();
+        for _ in 0..num_envelopes {
+            let (rest, envelope) = SealedKeyShard::from_wire_partial(input)?;
+            let (rest, proof) = EnvelopeProof::from_wire_partial(rest)?;
+            batch.envelopes.push((envelope, proof));
+            input = rest;
+        }
+
+        Ok((input, batch))
+    }
+}
+
+impl ToWire for PossessionVerifier {
+    fn to_wire(&self) -> Vec<u8> {
+        let mut bytes = vec![];
+
+        // Encode shard checksum.
+        bytes.append(&mut self.shard_chksum.to_bytes());
+
+        // Encode possession key.
+        bytes.append(&mut self.key.to_bytes());
+
+        bytes
+    }
+}
+
+impl FromWire for PossessionVerifier {
+    fn from_wire_partial(input: &[u8]) -> Result<(&[u8], Self), ParseError> {
+        use crate::v0::wire::helpers::multihash;
+        use multihash::Multihash;
+        use nom::{combinator::complete, IResult};
+
+        fn parse(input: &[u8]) -> IResult<&[u8], (Multihash, Multihash)> {
+            let (input, shard_chksum) = multihash(input)?;
+            let (input, key) = multihash(input)?;
+
+            Ok((input, (shard_chksum, key)))
+        }
+        let mut parse = complete(parse);
+
+        let (input, (shard_chksum, key)) = parse(input).map_err(ParseError::expecting(
+            ParseSection::Payload,
+            "a possession verifier",
+        ))?;
+
+        Ok((input, PossessionVerifier { shard_chksum, key }))
+    }
+}
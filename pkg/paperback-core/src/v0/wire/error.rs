@@ -0,0 +1,275 @@
+/*
+ * paperback: paper backup generator suitable for long-term storage
+ * Copyright (C) 2018-2022 Aleksa Sarai <cyphar@cyphar.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use crate::ErrorCode;
+
+use std::fmt;
+
+use ed25519_dalek::SignatureError;
+use nom::{error::ErrorKind, Needed};
+
+/// The section of a serialised document in which a [`ParseError`] occurred.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ParseSection {
+    /// The text encoding of the document (such as invalid multibase
+    /// characters), before any of the document itself could be parsed.
+    Encoding,
+    /// Version, flags and other metadata describing the document.
+    Header,
+    /// The contents of the document (such as the encrypted secret or shard).
+    Payload,
+    /// A checksum of the document (or of the document it belongs to).
+    Checksum,
+    /// The signing identity and signatures of the document.
+    Signature,
+}
+
+impl fmt::Display for ParseSection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Encoding => "encoding",
+            Self::Header => "header",
+            Self::Payload => "payload",
+            Self::Checksum => "checksum",
+            Self::Signature => "signature",
+        })
+    }
+}
+
+/// An error encountered while parsing serialised data, describing where the
+/// data was invalid so that user interfaces can point at the problem.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseError {
+    section: ParseSection,
+    expected: String,
+    found: String,
+    // Number of bytes of input left when the error occurred. This is used to
+    // compute the offset once the length of the whole input is known, so that
+    // nested parsers don't need to know where their input started.
+    remaining: Option<usize>,
+    offset: Option<usize>,
+    position: Option<usize>,
+}
+
+impl ParseError {
+    /// A parse error for the data starting at `input` (the unparsed remainder
+    /// of the input).
+    pub(crate) fn new<E: Into<String>, F: Into<String>>(
+        section: ParseSection,
+        input: &[u8],
+        expected: E,
+        found: F,
+    ) -> Self {
+        Self {
+            section,
+            expected: expected.into(),
+            found: found.into(),
+            remaining: Some(input.len()),
+            offset: None,
+            position: None,
+        }
+    }
+
+    /// Convert an error returned by a nom parser for `expected`.
+    pub(crate) fn nom<E: Into<String>>(
+        section: ParseSection,
+        expected: E,
+        err: nom::Err<nom::error::Error<&[u8]>>,
+    ) -> Self {
+        let (remaining, found) = match err {
+            nom::Err::Incomplete(Needed::Size(size)) => {
+                (0, format!("end of input ({} more bytes needed)", size))
+            }
+            nom::Err::Incomplete(Needed::Unknown) => (0, "end of input".to_string()),
+            nom::Err::Error(err) | nom::Err::Failure(err) => (
+                err.input.len(),
+                match (err.code, err.input.first()) {
+                    // complete() reports the start of the data it wrapped.
+                    (ErrorKind::Complete, _) => "truncated data".to_string(),
+                    (_, None) => "end of input".to_string(),
+                    (_, Some(byte)) => format!("byte {:#04x}", byte),
+                },
+            ),
+        };
+        Self {
+            section,
+            expected: expected.into(),
+            found,
+            remaining: Some(remaining),
+            offset: None,
+            position: None,
+        }
+    }
+
+    /// Like [`ParseError::nom`], but returning a function for use with
+    /// `map_err`.
+    pub(crate) fn expecting(
+        section: ParseSection,
+        expected: &'static str,
+    ) -> impl Fn(nom::Err<nom::error::Error<&[u8]>>) -> Self {
+        move |err| Self::nom(section, expected, err)
+    }
+
+    /// An invalid ed25519 key or signature (which can only be detected after
+    /// parsing, so the offset is unknown).
+    pub(crate) fn ed25519(expected: &'static str, err: SignatureError) -> Self {
+        Self {
+            section: ParseSection::Signature,
+            expected: expected.to_string(),
+            found: err.to_string(),
+            remaining: None,
+            offset: None,
+            position: None,
+        }
+    }
+
+    // Override the section of the error.
+    pub(crate) fn in_section(mut self, section: ParseSection) -> Self {
+        self.section = section;
+        self
+    }
+
+    // Resolve the offset of the error, given the length of the whole input.
+    pub(crate) fn locate(mut self, input_len: usize) -> Self {
+        if self.offset.is_none() {
+            self.offset = self
+                .remaining
+                .map(|remaining| input_len.saturating_sub(remaining));
+        }
+        self
+    }
+
+    // Set the character offset of the error in the text the input was
+    // decoded from.
+    pub(crate) fn with_position(mut self, position: Option<usize>) -> Self {
+        self.position = position;
+        self
+    }
+
+    /// The section of the data in which the error occurred.
+    pub fn section(&self) -> ParseSection {
+        self.section
+    }
+
+    /// The byte offset (in the decoded data) at which the error occurred, if
+    /// it is known.
+    pub fn offset(&self) -> Option<usize> {
+        self.offset
+    }
+
+    /// The character offset (in the text the data was decoded from) at which
+    /// the error occurred, if it is known. For multibase text with formatting
+    /// noise, this is the offset after the noise was stripped.
+    pub fn position(&self) -> Option<usize> {
+        self.position
+    }
+
+    /// A description of what the parser expected to find.
+    pub fn expected(&self) -> &str {
+        &self.expected
+    }
+
+    /// A description of what the parser found instead.
+    pub fn found(&self) -> &str {
+        &self.found
+    }
+
+    /// The stable code of this error. See [`ErrorCode`] for more details.
+    pub fn code(&self) -> ErrorCode {
+        match self.section {
+            ParseSection::Encoding => ErrorCode::new(700, "parse-encoding"),
+            ParseSection::Header => ErrorCode::new(701, "parse-header"),
+            ParseSection::Payload => ErrorCode::new(702, "parse-payload"),
+            ParseSection::Checksum => ErrorCode::new(703, "parse-checksum"),
+            ParseSection::Signature => ErrorCode::new(704, "parse-signature"),
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid {}", self.section)?;
+        match (self.offset, self.position) {
+            (Some(offset), Some(position)) => {
+                write!(f, " at byte {} (character {})", offset, position)?
+            }
+            (Some(offset), None) => write!(f, " at byte {}", offset)?,
+            (None, Some(position)) => write!(f, " at character {}", position)?,
+            (None, None) => (),
+        }
+        write!(f, ": expected {}, found {}", self.expected, self.found)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+// Validation helpers (and older parsers) only describe what was wrong.
+impl From<String> for ParseError {
+    fn from(found: String) -> Self {
+        Self {
+            section: ParseSection::Payload,
+            expected: "valid data".to_string(),
+            found,
+            remaining: None,
+            offset: None,
+            position: None,
+        }
+    }
+}
+
+impl From<&str> for ParseError {
+    fn from(found: &str) -> Self {
+        found.to_string().into()
+    }
+}
+
+impl From<ParseError> for String {
+    fn from(err: ParseError) -> Self {
+        err.to_string()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_error_location() {
+        let input = [1u8, 2, 3, 4, 5];
+        let err = ParseError::new(ParseSection::Checksum, &input[3..], "a checksum", "junk");
+        assert_eq!(err.offset(), None);
+
+        let err = err.locate(input.len()).with_position(Some(6));
+        assert_eq!(err.section(), ParseSection::Checksum);
+        assert_eq!(err.offset(), Some(3));
+        assert_eq!(err.position(), Some(6));
+        assert_eq!(
+            err.to_string(),
+            "invalid checksum at byte 3 (character 6): expected a checksum, found junk"
+        );
+
+        let err = ParseError::nom(
+            ParseSection::Header,
+            "a version",
+            nom::Err::Error(nom::error::Error::new(&input[1..], ErrorKind::Verify)),
+        )
+        .locate(input.len());
+        assert_eq!(err.offset(), Some(1));
+        assert_eq!(err.found(), "byte 0x02");
+    }
+}
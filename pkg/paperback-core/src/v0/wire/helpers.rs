@@ -76,6 +76,19 @@ pub(super) fn take_ed25519_pub(
     Ok((input, VerifyingKey::from_bytes(&public_key_arr)))
 }
 
+pub(super) fn take_x25519_pub(input: &[u8]) -> IResult<&[u8], [u8; 32]> {
+    let (input, _) = verify(varuint_nom::u32, |x| *x == PREFIX_X25519_PUB)(input)?;
+    let (input, public_key) = take(32usize)(input)?;
+
+    // This conversion cannot fail, by definition.
+    Ok((
+        input,
+        public_key
+            .try_into()
+            .expect("slice of length 32 should convert to array of length 32"),
+    ))
+}
+
 pub(super) fn take_ed25519_sig(input: &[u8]) -> IResult<&[u8], Result<Signature, SignatureError>> {
     let (input, _) = verify(varuint_nom::u32, |x| *x == PREFIX_ED25519_SIG)(input)?;
     let (input, sig) = take(ed25519_dalek::SIGNATURE_LENGTH)(input)?;
@@ -17,7 +17,8 @@
  */
 
 use crate::v0::{
-    wire::{prefixes::*, FromWire, ToWire},
+    hybrid::{self, PqIdentity, PqSignature, PqSigningKey, PqVerifyingKey},
+    wire::{prefixes::*, FromWire, ParseError, ParseSection, ToWire},
     ChaChaPolyKey, Identity, ShardSecret,
 };
 
@@ -59,7 +60,7 @@ type IdentityParseResult = (
 
 // Internal only -- users can't see Identity.
 impl FromWire for Identity {
-    fn from_wire_partial(input: &[u8]) -> Result<(&[u8], Self), String> {
+    fn from_wire_partial(input: &[u8]) -> Result<(&[u8], Self), ParseError> {
         use crate::v0::wire::helpers::{take_ed25519_pub, take_ed25519_sig};
         use nom::{combinator::complete, IResult};
 
@@ -71,18 +72,92 @@ impl FromWire for Identity {
         }
         let mut parse = complete(parse);
 
-        let (input, (public_key, signature)) = parse(input).map_err(|err| format!("{:?}", err))?;
+        let (rest, (public_key, signature)) = parse(input).map_err(|err| {
+            ParseError::nom(
+                ParseSection::Signature,
+                "an ed25519 public key and signature",
+                err,
+            )
+        })?;
+        let invalid = |expected: &str, err: SignatureError| {
+            ParseError::new(ParseSection::Signature, input, expected, err.to_string())
+        };
 
         Ok((
-            input,
+            rest,
             Identity {
-                id_public_key: public_key.map_err(|err| format!("{:?}", err))?,
-                id_signature: signature.map_err(|err| format!("{:?}", err))?,
+                id_public_key: public_key
+                    .map_err(|err| invalid("a valid ed25519 public key", err))?,
+                id_signature: signature.map_err(|err| invalid("a valid ed25519 signature", err))?,
             },
         ))
     }
 }
 
+// Internal only -- users can't see PqIdentity.
+impl ToWire for PqIdentity {
+    fn to_wire(&self) -> Vec<u8> {
+        let mut buffer = varuint_encode::u64_buffer();
+        let mut bytes = vec![];
+
+        // Encode ML-DSA-65 public key (with multicodec prefix).
+        varuint_encode::u64(PREFIX_MLDSA65_PUB, &mut buffer)
+            .iter()
+            .chain(self.public_key.as_bytes())
+            .for_each(|b| bytes.push(*b));
+
+        // Encode ML-DSA-65 signature.
+        // NOTE: Not actually upstream.
+        varuint_encode::u64(PREFIX_MLDSA65_SIG, &mut buffer)
+            .iter()
+            .chain(self.signature.as_bytes())
+            .for_each(|b| bytes.push(*b));
+
+        bytes
+    }
+}
+
+// Internal only -- users can't see PqIdentity.
+impl FromWire for PqIdentity {
+    fn from_wire_partial(input: &[u8]) -> Result<(&[u8], Self), ParseError> {
+        use nom::{
+            bytes::complete::take,
+            combinator::{complete, map_opt, verify},
+            sequence::preceded,
+            IResult,
+        };
+        use unsigned_varint::nom as varuint_nom;
+
+        fn parse(input: &[u8]) -> IResult<&[u8], PqIdentity> {
+            let (input, public_key) = preceded(
+                verify(varuint_nom::u64, |x| *x == PREFIX_MLDSA65_PUB),
+                map_opt(take(hybrid::PUBLIC_KEY_LENGTH), PqVerifyingKey::from_bytes),
+            )(input)?;
+            let (input, signature) = preceded(
+                verify(varuint_nom::u64, |x| *x == PREFIX_MLDSA65_SIG),
+                map_opt(take(hybrid::SIGNATURE_LENGTH), PqSignature::from_bytes),
+            )(input)?;
+
+            Ok((
+                input,
+                PqIdentity {
+                    public_key,
+                    signature,
+                },
+            ))
+        }
+        let mut parse = complete(parse);
+
+        parse(input).map_err(|err| {
+            ParseError::nom(
+                ParseSection::Signature,
+                "an ML-DSA-65 public key and signature",
+                err,
+            )
+        })
+    }
+}
+
 // Internal only -- users can't see ShardSecret.
 impl ToWire for ShardSecret {
     fn to_wire(&self) -> Vec<u8> {
@@ -110,36 +185,72 @@ impl ToWire for ShardSecret {
             .chain(&id_private_key[..])
             .for_each(|b| bytes.push(*b));
 
+        // Encode ML-DSA-65 seed (only present for unsealed hybrid backups).
+        // NOTE: Not actually upstream.
+        if let Some(pq_keypair) = &self.pq_keypair {
+            varuint_encode::u64(PREFIX_MLDSA65_SEED, &mut buffer)
+                .iter()
+                .chain(pq_keypair.seed())
+                .for_each(|b| bytes.push(*b));
+        }
+
         bytes
     }
 }
 
-type ShardSecretParseResult = (ChaChaPolyKey, Option<SigningKey>);
+type ShardSecretParseResult = (ChaChaPolyKey, Option<SigningKey>, Option<PqSigningKey>);
 
 // Internal only -- users can't see ShardSecret.
 impl FromWire for ShardSecret {
-    fn from_wire_partial(input: &[u8]) -> Result<(&[u8], Self), String> {
+    fn from_wire_partial(input: &[u8]) -> Result<(&[u8], Self), ParseError> {
         use crate::v0::wire::helpers::{take_chachapoly_key, take_ed25519_sec};
-        use nom::{combinator::complete, IResult};
+        use nom::{
+            bytes::complete::take,
+            combinator::{complete, cond, map, opt, verify},
+            sequence::preceded,
+            IResult,
+        };
+        use unsigned_varint::nom as varuint_nom;
 
         fn parse(input: &[u8]) -> IResult<&[u8], ShardSecretParseResult> {
             let (input, doc_key) = take_chachapoly_key(input)?;
             let (input, private_key) = take_ed25519_sec(input)?;
+            // Sealed backups never store the ML-DSA-65 seed.
+            let (input, pq_keypair) = cond(
+                private_key.is_some(),
+                opt(preceded(
+                    verify(varuint_nom::u64, |x| *x == PREFIX_MLDSA65_SEED),
+                    map(take(hybrid::SEED_LENGTH), |seed: &[u8]| {
+                        // This conversion cannot fail, by definition.
+                        PqSigningKey::from_seed(seed.try_into().expect(&format!(
+                            "slice of length {} should convert to array of length {}",
+                            seed.len(),
+                            hybrid::SEED_LENGTH
+                        )))
+                    }),
+                )),
+            )(input)?;
 
             Ok((
                 input,
-                (doc_key, private_key.as_ref().map(SigningKey::from_bytes)),
+                (
+                    doc_key,
+                    private_key.as_ref().map(SigningKey::from_bytes),
+                    pq_keypair.flatten(),
+                ),
             ))
         }
         let mut parse = complete(parse);
 
-        let (input, (doc_key, id_keypair)) = parse(input).map_err(|err| format!("{:?}", err))?;
+        let (input, (doc_key, id_keypair, pq_keypair)) = parse(input)
+            .map_err(|err| ParseError::nom(ParseSection::Payload, "a shard secret", err))?;
 
         Ok((
             input,
             ShardSecret {
                 doc_key,
                 id_keypair,
+                pq_keypair,
             },
         ))
     }
@@ -161,6 +272,11 @@ mod test {
                     (None, None) => true,
                     _ => false,
                 }
+                && match (&self.pq_keypair, &other.pq_keypair) {
+                    (Some(left), Some(right)) => left.seed() == right.seed(),
+                    (None, None) => true,
+                    _ => false,
+                }
         }
     }
 
@@ -181,7 +297,20 @@ mod test {
     }
 
     #[quickcheck]
-    fn shard_secret_roundtrip(_: u32, sealed: bool) -> bool {
+    fn pq_identity_roundtrip(data: Vec<u8>) -> bool {
+        let pq_keypair = PqSigningKey::generate(&mut OsRng);
+
+        let identity = PqIdentity {
+            public_key: pq_keypair.verifying_key(),
+            signature: pq_keypair.sign(&data),
+        };
+        let identity2 = PqIdentity::from_wire(identity.to_wire()).unwrap();
+
+        identity == identity2
+    }
+
+    #[quickcheck]
+    fn shard_secret_roundtrip(_: u32, sealed: bool, hybrid: bool) -> bool {
         let mut doc_key = ChaChaPolyKey::default();
         OsRng.fill_bytes(&mut doc_key);
 
@@ -191,6 +320,10 @@ mod test {
                 true => None,
                 false => Some(SigningKey::generate(&mut OsRng)),
             },
+            pq_keypair: match !sealed && hybrid {
+                false => None,
+                true => Some(PqSigningKey::generate(&mut OsRng)),
+            },
         };
         let secret2 = ShardSecret::from_wire(secret.to_wire()).unwrap();
 
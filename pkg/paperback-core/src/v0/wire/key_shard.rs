@@ -17,17 +17,274 @@
  */
 
 use crate::{
-    shamir::Shard,
+    shamir::WeightedShard,
     v0::{
-        wire::{prefixes::*, FromWire, ToWire},
-        ChaChaPolyNonce, EncryptedKeyShard, Identity, KeyShard, KeyShardBuilder,
-        CHACHAPOLY_NONCE_LENGTH, CHECKSUM_ALGORITHM,
+        expansion::AUTHORISATION_LENGTH,
+        format_version,
+        passphrase::{PassphraseHeader, SALT_LENGTH},
+        wire::{cbor, prefixes::*, FromWire, ParseError, ParseSection, ToWire},
+        Argon2Params, ChaChaPolyNonce, CustodianInfo, EncryptedKeyShard, Identity, KeyShard,
+        KeyShardBuilder, ShardExpansion, CBOR_PAYLOAD_FLAG, CHACHAPOLY_NONCE_LENGTH,
+        CHECKSUM_ALGORITHM,
     },
 };
 
-use multihash::Multihash;
+use std::collections::BTreeMap;
+
 use unsigned_varint::{encode as varuint_encode, nom as varuint_nom};
 
+// Tag of the (0, tag) prefix of the custodian details of a key shard. The
+// identity following the shard data never starts with a zero byte, so key
+// shards without custodian details are encoded exactly as they were before
+// custodian details were supported.
+const CUSTODIAN_TAG: u8 = 1;
+
+// Tag of the (0, tag) prefix of the expansion record of a key shard, which
+// follows the custodian details (if any).
+const EXPANSION_TAG: u8 = 2;
+
+// Tag of the (0, tag) prefix of the counter-signature of the expansion record
+// of a key shard, which directly follows the expansion record.
+const EXPANSION_AUTHORISATION_TAG: u8 = 3;
+
+// Keys of the fields in the CBOR payload of KeyShardBuilder (see
+// CBOR_PAYLOAD_FLAG). The custodian details, expansion record and its
+// counter-signature are omitted when they are unset.
+const SHARD_DOC_CHECKSUM: u64 = 1;
+const SHARD_DATA: u64 = 2;
+const SHARD_CUSTODIAN: u64 = 3;
+const SHARD_EXPANSION: u64 = 4;
+const SHARD_EXPANSION_AUTHORISATION: u64 = 5;
+
+// Internal only -- users can't see the encoding of CustodianInfo.
+#[doc(hidden)]
+impl ToWire for CustodianInfo {
+    fn to_wire(&self) -> Vec<u8> {
+        let mut bytes = vec![];
+
+        // Encode each field (length-prefixed, empty if unset).
+        for (_, value) in self.fields() {
+            let value = value.unwrap_or_default();
+            varuint_encode::usize(value.len(), &mut varuint_encode::usize_buffer())
+                .iter()
+                .chain(value.as_bytes())
+                .for_each(|b| bytes.push(*b));
+        }
+
+        bytes
+    }
+}
+
+// Internal only -- users can't see the encoding of CustodianInfo.
+#[doc(hidden)]
+impl FromWire for CustodianInfo {
+    fn from_wire_partial(input: &[u8]) -> Result<(&[u8], Self), ParseError> {
+        use nom::{
+            combinator::{complete, map_res},
+            multi::length_data,
+            sequence::tuple,
+            IResult,
+        };
+
+        fn field(input: &[u8]) -> IResult<&[u8], Option<String>> {
+            map_res(length_data(varuint_nom::usize), |s: &[u8]| {
+                String::from_utf8(s.to_vec()).map(|s| Some(s).filter(|s| !s.is_empty()))
+            })(input)
+        }
+
+        fn parse(input: &[u8]) -> IResult<&[u8], CustodianInfo> {
+            let (input, (name, contact, note)) = tuple((field, field, field))(input)?;
+
+            Ok((
+                input,
+                CustodianInfo {
+                    name,
+                    contact,
+                    note,
+                },
+            ))
+        }
+        let mut parse = complete(parse);
+
+        let (rest, custodian) = parse(input).map_err(ParseError::expecting(
+            ParseSection::Payload,
+            "custodian details",
+        ))?;
+        custodian.validate().map_err(|err| {
+            ParseError::new(ParseSection::Payload, input, "valid custodian details", err)
+        })?;
+
+        let input = rest;
+
+        Ok((input, custodian))
+    }
+}
+
+// Internal only -- users can't see the encoding of ShardExpansion.
+#[doc(hidden)]
+impl ToWire for ShardExpansion {
+    fn to_wire(&self) -> Vec<u8> {
+        let mut bytes = vec![];
+
+        // Encode expansion time.
+        varuint_encode::u64(self.expanded_at, &mut varuint_encode::u64_buffer())
+            .iter()
+            .for_each(|b| bytes.push(*b));
+
+        // Encode the key shard ids of the quorum (length-prefixed).
+        varuint_encode::usize(self.quorum.len(), &mut varuint_encode::usize_buffer())
+            .iter()
+            .for_each(|b| bytes.push(*b));
+        for id in &self.quorum {
+            varuint_encode::usize(id.len(), &mut varuint_encode::usize_buffer())
+                .iter()
+                .chain(id.as_bytes())
+                .for_each(|b| bytes.push(*b));
+        }
+
+        bytes
+    }
+}
+
+// Internal only -- users can't see the encoding of ShardExpansion.
+#[doc(hidden)]
+impl FromWire for ShardExpansion {
+    fn from_wire_partial(input: &[u8]) -> Result<(&[u8], Self), ParseError> {
+        use nom::{
+            combinator::{complete, map_res},
+            multi::{length_count, length_data},
+            IResult,
+        };
+
+        fn id(input: &[u8]) -> IResult<&[u8], String> {
+            map_res(length_data(varuint_nom::usize), |s: &[u8]| {
+                String::from_utf8(s.to_vec())
+            })(input)
+        }
+
+        fn parse(input: &[u8]) -> IResult<&[u8], ShardExpansion> {
+            let (input, expanded_at) = varuint_nom::u64(input)?;
+            let (input, quorum) = length_count(varuint_nom::usize, id)(input)?;
+
+            Ok((
+                input,
+                ShardExpansion {
+                    expanded_at,
+                    quorum,
+                },
+            ))
+        }
+        let mut parse = complete(parse);
+
+        let (rest, expansion) = parse(input).map_err(ParseError::expecting(
+            ParseSection::Payload,
+            "an expansion record",
+        ))?;
+        expansion.validate().map_err(|err| {
+            ParseError::new(
+                ParseSection::Payload,
+                input,
+                "a valid expansion record",
+                err,
+            )
+        })?;
+
+        let input = rest;
+
+        Ok((input, expansion))
+    }
+}
+
+impl KeyShardBuilder {
+    fn to_cbor(&self) -> Vec<u8> {
+        let mut entries = self.extensions.clone();
+        entries.insert(SHARD_DOC_CHECKSUM, cbor::bytes(self.doc_chksum.to_bytes()));
+        entries.insert(SHARD_DATA, cbor::bytes(self.shard.to_wire()));
+        if !self.custodian.is_empty() {
+            entries.insert(SHARD_CUSTODIAN, cbor::bytes(self.custodian.to_wire()));
+        }
+        if let Some(ref expansion) = self.expansion {
+            entries.insert(SHARD_EXPANSION, cbor::bytes(expansion.to_wire()));
+        }
+        if let Some(ref authorisation) = self.expansion_authorisation {
+            entries.insert(SHARD_EXPANSION_AUTHORISATION, cbor::bytes(authorisation));
+        }
+        cbor::map(&entries)
+    }
+
+    fn from_cbor(version: u32, input: &[u8]) -> Result<(&[u8], Self), ParseError> {
+        use crate::v0::wire::helpers::multihash;
+        use nom::combinator::complete;
+
+        let (input, mut map) = cbor::Map::take(ParseSection::Payload, input)?;
+
+        let doc_chksum = map
+            .required(SHARD_DOC_CHECKSUM, "a document checksum")?
+            .in_section(ParseSection::Checksum)
+            .wire("a document checksum", |input| {
+                complete(multihash)(input).map_err(ParseError::expecting(
+                    ParseSection::Checksum,
+                    "a document checksum",
+                ))
+            })?;
+        let shard = map
+            .required(SHARD_DATA, "shard data")?
+            .wire("shard data", WeightedShard::from_wire_partial)?;
+
+        let custodian = match map.remove(SHARD_CUSTODIAN) {
+            None => CustodianInfo::default(),
+            Some(item) => {
+                let custodian = item.wire("custodian details", CustodianInfo::from_wire_partial)?;
+                if custodian.is_empty() {
+                    return Err(item.invalid("custodian details", "empty custodian details"));
+                }
+                custodian
+            }
+        };
+        let expansion = map
+            .remove(SHARD_EXPANSION)
+            .map(|item| item.wire("an expansion record", ShardExpansion::from_wire_partial))
+            .transpose()?;
+        let expansion_authorisation = match map.remove(SHARD_EXPANSION_AUTHORISATION) {
+            None => None,
+            Some(item) => {
+                let item = item.in_section(ParseSection::Signature);
+                let bytes = item.bytes("an expansion counter-signature")?;
+                if expansion.is_none() {
+                    return Err(item.invalid(
+                        "an expansion record before its counter-signature",
+                        "a counter-signature without an expansion record",
+                    ));
+                }
+                let authorisation =
+                    <[u8; AUTHORISATION_LENGTH]>::try_from(bytes).map_err(|_| {
+                        item.invalid(
+                            format!(
+                                "a {}-byte expansion counter-signature",
+                                AUTHORISATION_LENGTH
+                            ),
+                            format!("{} bytes", bytes.len()),
+                        )
+                    })?;
+                Some(authorisation)
+            }
+        };
+
+        Ok((
+            input,
+            Self {
+                version,
+                doc_chksum,
+                shard,
+                custodian,
+                expansion,
+                expansion_authorisation,
+                extensions: map.into_extensions(),
+            },
+        ))
+    }
+}
+
 // Internal only -- users can't see KeyShardBuilder.
 #[doc(hidden)]
 impl ToWire for KeyShardBuilder {
@@ -39,6 +296,12 @@ impl ToWire for KeyShardBuilder {
             .iter()
             .for_each(|b| bytes.push(*b));
 
+        // The rest of the key shard is a single map for CBOR payloads.
+        if self.version & CBOR_PAYLOAD_FLAG != 0 {
+            bytes.append(&mut self.to_cbor());
+            return bytes;
+        }
+
         // Encode multihash checksum.
         self.doc_chksum
             .to_bytes()
@@ -48,6 +311,24 @@ impl ToWire for KeyShardBuilder {
         // Encode shard data.
         bytes.append(&mut self.shard.to_wire());
 
+        // Encode custodian details (only present if any are set).
+        if !self.custodian.is_empty() {
+            bytes.extend_from_slice(&[0, CUSTODIAN_TAG]);
+            bytes.append(&mut self.custodian.to_wire());
+        }
+
+        // Encode expansion record (only present for minted key shards).
+        if let Some(ref expansion) = self.expansion {
+            bytes.extend_from_slice(&[0, EXPANSION_TAG]);
+            bytes.append(&mut expansion.to_wire());
+        }
+
+        // Encode the counter-signature of the expansion record (if any).
+        if let Some(ref authorisation) = self.expansion_authorisation {
+            bytes.extend_from_slice(&[0, EXPANSION_AUTHORISATION_TAG]);
+            bytes.extend_from_slice(authorisation);
+        }
+
         bytes
     }
 }
@@ -55,20 +336,72 @@ impl ToWire for KeyShardBuilder {
 // Internal only -- users can't see KeyShardBuilder.
 #[doc(hidden)]
 impl FromWire for KeyShardBuilder {
-    fn from_wire_partial(input: &[u8]) -> Result<(&[u8], Self), String> {
+    fn from_wire_partial(input: &[u8]) -> Result<(&[u8], Self), ParseError> {
         use crate::v0::wire::helpers::multihash;
-        use nom::{combinator::complete, IResult};
-
-        fn parse(input: &[u8]) -> IResult<&[u8], (u32, Multihash)> {
-            let (input, version) = varuint_nom::u32(input)?;
-            let (input, doc_chksum) = multihash(input)?;
-
-            Ok((input, (version, doc_chksum.to_owned())))
+        use nom::{
+            bytes::complete::{tag, take},
+            combinator::complete,
+        };
+
+        let (input, version) = complete(varuint_nom::u32)(input)
+            .map_err(ParseError::expecting(ParseSection::Header, "a version"))?;
+        if version & CBOR_PAYLOAD_FLAG != 0 {
+            return Self::from_cbor(version, input);
         }
-        let mut parse = complete(parse);
-
-        let (input, (version, doc_chksum)) = parse(input).map_err(|err| format!("{:?}", err))?;
-        let (input, shard) = Shard::from_wire_partial(input)?;
+        let (input, doc_chksum) = complete(multihash)(input).map_err(ParseError::expecting(
+            ParseSection::Checksum,
+            "a document checksum",
+        ))?;
+        let (input, shard) = WeightedShard::from_wire_partial(input)?;
+
+        let (input, custodian) =
+            match tag::<_, _, nom::error::Error<&[u8]>>([0, CUSTODIAN_TAG])(input) {
+                Ok((input, _)) => {
+                    let (rest, custodian) = CustodianInfo::from_wire_partial(input)?;
+                    if custodian.is_empty() {
+                        return Err(ParseError::new(
+                            ParseSection::Payload,
+                            input,
+                            "custodian details after the custodian prefix",
+                            "empty custodian details",
+                        ));
+                    }
+                    (rest, custodian)
+                }
+                Err(_) => (input, CustodianInfo::default()),
+            };
+
+        let (input, expansion) =
+            match tag::<_, _, nom::error::Error<&[u8]>>([0, EXPANSION_TAG])(input) {
+                Ok((input, _)) => {
+                    let (input, expansion) = ShardExpansion::from_wire_partial(input)?;
+                    (input, Some(expansion))
+                }
+                Err(_) => (input, None),
+            };
+
+        let (input, expansion_authorisation) =
+            match tag::<_, _, nom::error::Error<&[u8]>>([0, EXPANSION_AUTHORISATION_TAG])(input) {
+                Ok((rest, _)) => {
+                    if expansion.is_none() {
+                        return Err(ParseError::new(
+                            ParseSection::Signature,
+                            input,
+                            "an expansion record before its counter-signature",
+                            "a counter-signature without an expansion record",
+                        ));
+                    }
+                    let (rest, authorisation) =
+                        take(AUTHORISATION_LENGTH)(rest).map_err(ParseError::expecting(
+                            ParseSection::Signature,
+                            "an expansion counter-signature",
+                        ))?;
+                    let mut bytes = [0u8; AUTHORISATION_LENGTH];
+                    bytes.copy_from_slice(authorisation);
+                    (rest, Some(bytes))
+                }
+                Err(_) => (input, None),
+            };
 
         Ok((
             input,
@@ -76,6 +409,10 @@ impl FromWire for KeyShardBuilder {
                 version,
                 doc_chksum,
                 shard,
+                custodian,
+                expansion,
+                expansion_authorisation,
+                extensions: BTreeMap::new(),
             },
         ))
     }
@@ -97,18 +434,30 @@ impl ToWire for KeyShard {
 /// Internal only -- users should use EncryptedKeyShard's FromWire.
 #[doc(hidden)]
 impl FromWire for KeyShard {
-    fn from_wire_partial(input: &[u8]) -> Result<(&[u8], Self), String> {
+    fn from_wire_partial(input: &[u8]) -> Result<(&[u8], Self), ParseError> {
+        let start = input;
         let (input, inner) = KeyShardBuilder::from_wire_partial(input)?;
         let (input, identity) = Identity::from_wire_partial(input)?;
 
-        if inner.doc_chksum.code() != CHECKSUM_ALGORITHM.into() {
-            return Err("document checksum must be Blake2b-256".to_string());
+        // The version is the first field of the key shard, followed by the
+        // document checksum.
+        if format_version(inner.version) != 0 {
+            return Err(ParseError::new(
+                ParseSection::Header,
+                start,
+                "key shard version 0",
+                format!("version {}", format_version(inner.version)),
+            ));
         }
 
-        if inner.version != 0 {
-            return Err(format!(
-                "key shard version must be '0' not '{}'",
-                inner.version
+        if inner.doc_chksum.code() != u64::from(CHECKSUM_ALGORITHM) {
+            let version_len =
+                varuint_encode::u32(inner.version, &mut varuint_encode::u32_buffer()).len();
+            return Err(ParseError::new(
+                ParseSection::Checksum,
+                &start[version_len..],
+                "a Blake2b-256 document checksum",
+                format!("multihash code {:#x}", inner.doc_chksum.code()),
             ));
         }
 
@@ -116,11 +465,92 @@ impl FromWire for KeyShard {
     }
 }
 
+// Internal only -- users can't see PassphraseHeader.
+#[doc(hidden)]
+impl ToWire for PassphraseHeader {
+    fn to_wire(&self) -> Vec<u8> {
+        let mut bytes = vec![];
+
+        // Encode prefix.
+        varuint_encode::u64(PREFIX_ARGON2ID_PARAMS, &mut varuint_encode::u64_buffer())
+            .iter()
+            .for_each(|b| bytes.push(*b));
+
+        // Encode Argon2id parameters.
+        let params = &self.params;
+        for value in [params.memory_kib, params.iterations, params.parallelism] {
+            varuint_encode::u32(value, &mut varuint_encode::u32_buffer())
+                .iter()
+                .for_each(|b| bytes.push(*b));
+        }
+
+        // Encode salt.
+        bytes.extend_from_slice(&self.salt);
+
+        bytes
+    }
+}
+
+// Internal only -- users can't see PassphraseHeader.
+#[doc(hidden)]
+impl FromWire for PassphraseHeader {
+    fn from_wire_partial(input: &[u8]) -> Result<(&[u8], Self), ParseError> {
+        use nom::{
+            bytes::complete::take,
+            combinator::{complete, verify},
+            IResult,
+        };
+
+        fn parse(input: &[u8]) -> IResult<&[u8], PassphraseHeader> {
+            let (input, _) = verify(varuint_nom::u64, |x| *x == PREFIX_ARGON2ID_PARAMS)(input)?;
+            let (input, memory_kib) = varuint_nom::u32(input)?;
+            let (input, iterations) = varuint_nom::u32(input)?;
+            let (input, parallelism) = varuint_nom::u32(input)?;
+            let (input, salt) = take(SALT_LENGTH)(input)?;
+
+            Ok((
+                input,
+                PassphraseHeader {
+                    params: Argon2Params {
+                        memory_kib,
+                        iterations,
+                        parallelism,
+                    },
+                    salt: salt.try_into().expect("salt should be SALT_LENGTH bytes"),
+                },
+            ))
+        }
+        let mut parse = complete(parse);
+
+        let (rest, header) = parse(input).map_err(ParseError::expecting(
+            ParseSection::Header,
+            "an Argon2id passphrase header",
+        ))?;
+        // Reject costly parameters before anything tries to derive a key.
+        header.params.check_bounds().map_err(|err| {
+            ParseError::new(
+                ParseSection::Header,
+                input,
+                "Argon2id parameters within the supported bounds",
+                err,
+            )
+        })?;
+        Ok((rest, header))
+    }
+}
+
 impl ToWire for EncryptedKeyShard {
     fn to_wire(&self) -> Vec<u8> {
         let mut buffer = varuint_encode::u64_buffer();
         let mut bytes = vec![];
 
+        // Encode passphrase header (only present for hardened shards). Older
+        // versions of paperback expect the nonce first, and will refuse to
+        // parse hardened shards.
+        if let Some(ref header) = self.passphrase {
+            bytes.append(&mut header.to_wire());
+        }
+
         // Encode ChaCha20-Poly1305 nonce.
         varuint_encode::u64(PREFIX_CHACHA20POLY1305_NONCE, &mut buffer)
             .iter()
@@ -143,7 +573,7 @@ impl ToWire for EncryptedKeyShard {
 }
 
 impl FromWire for EncryptedKeyShard {
-    fn from_wire_partial(input: &[u8]) -> Result<(&[u8], Self), String> {
+    fn from_wire_partial(input: &[u8]) -> Result<(&[u8], Self), ParseError> {
         use crate::v0::wire::helpers::{take_chachapoly_ciphertext, take_chachapoly_nonce};
         use nom::{combinator::complete, IResult};
 
@@ -155,11 +585,22 @@ impl FromWire for EncryptedKeyShard {
         }
         let mut parse = complete(parse);
 
-        let (input, (nonce, ciphertext)) = parse(input).map_err(|err| format!("{:?}", err))?;
+        let has_header =
+            varuint_nom::u64(input).is_ok_and(|(_, prefix)| prefix == PREFIX_ARGON2ID_PARAMS);
+        let (input, passphrase) = match PassphraseHeader::from_wire_partial(input) {
+            Ok((input, header)) => (input, Some(header)),
+            Err(err) if has_header => return Err(err),
+            Err(_) => (input, None),
+        };
+        let (input, (nonce, ciphertext)) = parse(input).map_err(ParseError::expecting(
+            ParseSection::Payload,
+            "a nonce and ciphertext",
+        ))?;
 
         Ok((
             input,
             EncryptedKeyShard {
+                passphrase,
                 nonce,
                 ciphertext: ciphertext.into(),
             },
@@ -171,6 +612,8 @@ impl FromWire for EncryptedKeyShard {
 mod test {
     use super::*;
 
+    use crate::v0::passphrase::{MAX_ITERATIONS, MAX_MEMORY_KIB, MAX_PARALLELISM};
+
     #[quickcheck]
     fn key_shard_builder_roundtrip(inner: KeyShardBuilder) -> bool {
         let inner2 = KeyShardBuilder::from_wire(inner.to_wire()).unwrap();
@@ -188,4 +631,74 @@ mod test {
         let shard2 = EncryptedKeyShard::from_wire(shard.to_wire()).unwrap();
         shard == shard2
     }
+
+    // Encode a passphrase-hardened key shard with the given Argon2id
+    // parameters, which need not be within the bounds.
+    fn hardened_shard_wire(params: Argon2Params) -> Vec<u8> {
+        EncryptedKeyShard {
+            passphrase: Some(PassphraseHeader {
+                params,
+                salt: [0x5a; SALT_LENGTH],
+            }),
+            nonce: Default::default(),
+            ciphertext: vec![0xff; 32],
+        }
+        .to_wire()
+    }
+
+    fn assert_params_rejected(params: Argon2Params) {
+        let wire = hardened_shard_wire(params);
+        assert!(PassphraseHeader::from_wire_partial(&wire).is_err());
+        let err = EncryptedKeyShard::from_wire(wire).unwrap_err();
+        assert_eq!(err.section(), ParseSection::Header);
+    }
+
+    const MAX_PARAMS: Argon2Params = Argon2Params {
+        memory_kib: MAX_MEMORY_KIB,
+        iterations: MAX_ITERATIONS,
+        parallelism: MAX_PARALLELISM,
+    };
+
+    #[test]
+    fn passphrase_params_at_bounds() {
+        let wire = hardened_shard_wire(MAX_PARAMS);
+        let shard = EncryptedKeyShard::from_wire(wire).unwrap();
+        assert_eq!(shard.passphrase.unwrap().params, MAX_PARAMS);
+    }
+
+    #[test]
+    fn passphrase_memory_bound() {
+        assert_params_rejected(Argon2Params {
+            memory_kib: MAX_MEMORY_KIB + 1,
+            ..MAX_PARAMS
+        });
+        assert_params_rejected(Argon2Params {
+            memory_kib: u32::MAX,
+            ..MAX_PARAMS
+        });
+    }
+
+    #[test]
+    fn passphrase_iterations_bound() {
+        assert_params_rejected(Argon2Params {
+            iterations: MAX_ITERATIONS + 1,
+            ..MAX_PARAMS
+        });
+        assert_params_rejected(Argon2Params {
+            iterations: u32::MAX,
+            ..MAX_PARAMS
+        });
+    }
+
+    #[test]
+    fn passphrase_parallelism_bound() {
+        assert_params_rejected(Argon2Params {
+            parallelism: 0,
+            ..MAX_PARAMS
+        });
+        assert_params_rejected(Argon2Params {
+            parallelism: MAX_PARALLELISM + 1,
+            ..MAX_PARAMS
+        });
+    }
 }
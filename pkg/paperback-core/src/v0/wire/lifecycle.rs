@@ -0,0 +1,142 @@
+/*
+ * paperback: paper backup generator suitable for long-term storage
+ * Copyright (C) 2018-2022 Aleksa Sarai <cyphar@cyphar.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use crate::v0::{
+    lifecycle::{Amendment, LifecycleState},
+    wire::{prefixes::*, FromWire, ParseError, ParseSection, ToWire},
+};
+
+use nom::{
+    combinator::{complete, fail, map, map_res},
+    multi::length_data,
+    IResult,
+};
+use unsigned_varint::{encode as varuint_encode, nom as varuint_nom};
+
+impl ToWire for LifecycleState {
+    fn to_wire(&self) -> Vec<u8> {
+        let mut bytes = vec![];
+
+        // Encode state kind.
+        varuint_encode::u32(self.id(), &mut varuint_encode::u32_buffer())
+            .iter()
+            .for_each(|b| bytes.push(*b));
+
+        // Encode superseding document ID (length-prefixed).
+        if let Self::SupersededBy(id) = self {
+            varuint_encode::usize(id.len(), &mut varuint_encode::usize_buffer())
+                .iter()
+                .chain(id.as_bytes())
+                .for_each(|b| bytes.push(*b));
+        }
+
+        bytes
+    }
+}
+
+fn lifecycle_state(input: &[u8]) -> IResult<&[u8], LifecycleState> {
+    let (input, id) = varuint_nom::u32(input)?;
+    match id {
+        0 => Ok((input, LifecycleState::Active)),
+        1 => map(
+            map_res(length_data(varuint_nom::usize), |s: &[u8]| {
+                String::from_utf8(s.to_vec())
+            }),
+            LifecycleState::SupersededBy,
+        )(input),
+        2 => Ok((input, LifecycleState::Revoked)),
+        _ => fail(input),
+    }
+}
+
+impl FromWire for LifecycleState {
+    fn from_wire_partial(input: &[u8]) -> Result<(&[u8], Self), ParseError> {
+        let mut parse = complete(lifecycle_state);
+
+        let (input, state) = parse(input).map_err(ParseError::expecting(
+            ParseSection::Payload,
+            "a lifecycle state",
+        ))?;
+        Ok((input, state))
+    }
+}
+
+impl ToWire for Amendment {
+    fn to_wire(&self) -> Vec<u8> {
+        let mut bytes = vec![];
+
+        // Encode document checksum.
+        bytes.append(&mut self.doc_chksum.to_bytes());
+
+        // Encode sequence number.
+        varuint_encode::u32(self.sequence, &mut varuint_encode::u32_buffer())
+            .iter()
+            .for_each(|b| bytes.push(*b));
+
+        // Encode lifecycle state.
+        bytes.append(&mut self.state.to_wire());
+
+        // Encode Ed25519 signature.
+        varuint_encode::u32(PREFIX_ED25519_SIG, &mut varuint_encode::u32_buffer())
+            .iter()
+            .copied()
+            .chain(self.signature.to_bytes())
+            .for_each(|b| bytes.push(b));
+
+        bytes
+    }
+}
+
+impl FromWire for Amendment {
+    fn from_wire_partial(input: &[u8]) -> Result<(&[u8], Self), ParseError> {
+        use crate::v0::wire::helpers::{multihash, take_ed25519_sig};
+        use ed25519_dalek::{Signature, SignatureError};
+        use multihash::Multihash;
+
+        type Parsed = (
+            Multihash,
+            u32,
+            LifecycleState,
+            Result<Signature, SignatureError>,
+        );
+
+        fn parse(input: &[u8]) -> IResult<&[u8], Parsed> {
+            let (input, doc_chksum) = multihash(input)?;
+            let (input, sequence) = varuint_nom::u32(input)?;
+            let (input, state) = lifecycle_state(input)?;
+            let (input, signature) = take_ed25519_sig(input)?;
+
+            Ok((input, (doc_chksum, sequence, state, signature)))
+        }
+        let mut parse = complete(parse);
+
+        let (input, (doc_chksum, sequence, state, signature)) =
+            parse(input).map_err(ParseError::expecting(ParseSection::Payload, "an amendment"))?;
+
+        Ok((
+            input,
+            Amendment {
+                doc_chksum,
+                sequence,
+                state,
+                signature: signature
+                    .map_err(|err| ParseError::ed25519("a valid ed25519 signature", err))?,
+            },
+        ))
+    }
+}
@@ -17,12 +17,301 @@
  */
 
 use crate::v0::{
-    wire::{prefixes::*, FromWire, ToWire},
-    ChaChaPolyNonce, Identity, MainDocument, MainDocumentBuilder, MainDocumentMeta,
+    format_version,
+    wire::{cbor, prefixes::*, FromWire, ParseError, ParseSection, ToWire},
+    AeadAlgorithm, AeadMode, ChaChaPolyNonce, Compression, Identity, MainDocument,
+    MainDocumentBuilder, MainDocumentMeta, PaddingScheme, PqIdentity, RecoveryConstraint,
+    AEAD_ALGORITHM_FLAG, CBOR_PAYLOAD_FLAG, CHUNKED_AEAD_FLAG, COMPRESSION_FLAG, CREATED_AT_FLAG,
+    GENERATION_FLAG, HYBRID_SIGNATURE_FLAG, PADDING_FLAG, RECOVERY_CONSTRAINTS_FLAG,
+    REVOKED_IDENTITIES_FLAG,
 };
 
+use std::collections::BTreeMap;
+
+use ed25519_dalek::VerifyingKey;
 use unsigned_varint::{encode as varuint_encode, nom as varuint_nom};
 
+// Keys of the fields in the CBOR payload of MainDocumentMeta (see
+// CBOR_PAYLOAD_FLAG). Optional fields are omitted when they are unset, and
+// must be present if and only if the corresponding version flag is set.
+const META_QUORUM_SIZE: u64 = 1;
+const META_PADDING: u64 = 2;
+const META_COMPRESSION: u64 = 3;
+const META_DICTIONARY: u64 = 4;
+const META_CONSTRAINTS: u64 = 5;
+const META_SEGMENT_SIZE: u64 = 6;
+const META_GENERATION: u64 = 7;
+const META_CREATED_AT: u64 = 8;
+const META_AEAD_ALGORITHM: u64 = 9;
+const META_REVOKED_IDENTITIES: u64 = 10;
+
+impl ToWire for RecoveryConstraint {
+    fn to_wire(&self) -> Vec<u8> {
+        let mut bytes = vec![];
+
+        // Encode constraint kind.
+        varuint_encode::u32(self.id(), &mut varuint_encode::u32_buffer())
+            .iter()
+            .for_each(|b| bytes.push(*b));
+
+        // Encode constraint value.
+        match self {
+            Self::NotBefore(date) | Self::ReviewBy(date) => {
+                varuint_encode::u64(*date, &mut varuint_encode::u64_buffer())
+                    .iter()
+                    .for_each(|b| bytes.push(*b))
+            }
+            Self::RequiresPresence(value) | Self::Location(value) => {
+                varuint_encode::usize(value.len(), &mut varuint_encode::usize_buffer())
+                    .iter()
+                    .chain(value.as_bytes())
+                    .for_each(|b| bytes.push(*b))
+            }
+        }
+
+        bytes
+    }
+}
+
+impl FromWire for RecoveryConstraint {
+    fn from_wire_partial(input: &[u8]) -> Result<(&[u8], Self), ParseError> {
+        use nom::{
+            combinator::{complete, fail, map, map_res},
+            multi::length_data,
+            IResult,
+        };
+
+        fn text(input: &[u8]) -> IResult<&[u8], String> {
+            map_res(length_data(varuint_nom::usize), |s: &[u8]| {
+                String::from_utf8(s.to_vec())
+            })(input)
+        }
+
+        fn parse(input: &[u8]) -> IResult<&[u8], RecoveryConstraint> {
+            let (input, id) = varuint_nom::u32(input)?;
+            match id {
+                0 => map(varuint_nom::u64, RecoveryConstraint::NotBefore)(input),
+                1 => map(varuint_nom::u64, RecoveryConstraint::ReviewBy)(input),
+                2 => map(text, RecoveryConstraint::RequiresPresence)(input),
+                3 => map(text, RecoveryConstraint::Location)(input),
+                _ => fail(input),
+            }
+        }
+        let mut parse = complete(parse);
+
+        let (input, constraint) = parse(input)
+            .map_err(|err| ParseError::nom(ParseSection::Header, "a recovery constraint", err))?;
+        Ok((input, constraint))
+    }
+}
+
+impl MainDocumentMeta {
+    fn to_cbor(&self) -> Vec<u8> {
+        let mut entries = self.extensions.clone();
+        entries.insert(META_QUORUM_SIZE, cbor::uint(self.quorum_size));
+        if self.padding != PaddingScheme::None {
+            entries.insert(META_PADDING, cbor::uint(self.padding.id()));
+        }
+        if self.compression != Compression::None {
+            entries.insert(META_COMPRESSION, cbor::uint(self.compression.id()));
+            entries.insert(
+                META_DICTIONARY,
+                cbor::uint(self.compression.dictionary_id()),
+            );
+        }
+        if !self.constraints.is_empty() {
+            entries.insert(
+                META_CONSTRAINTS,
+                cbor::array(self.constraints.iter().map(|c| cbor::bytes(c.to_wire()))),
+            );
+        }
+        if let AeadMode::Chunked { segment_size } = self.aead_mode {
+            entries.insert(META_SEGMENT_SIZE, cbor::uint(segment_size));
+        }
+        if self.generation != 0 {
+            entries.insert(META_GENERATION, cbor::uint(self.generation));
+        }
+        if let Some(created_at) = self.created_at {
+            entries.insert(META_CREATED_AT, cbor::uint(created_at));
+        }
+        if self.aead_algorithm != AeadAlgorithm::default() {
+            entries.insert(META_AEAD_ALGORITHM, cbor::uint(self.aead_algorithm.id()));
+        }
+        if !self.revoked_identities.is_empty() {
+            entries.insert(
+                META_REVOKED_IDENTITIES,
+                cbor::array(
+                    self.revoked_identities
+                        .iter()
+                        .map(|key| cbor::bytes(key.as_bytes())),
+                ),
+            );
+        }
+        cbor::map(&entries)
+    }
+
+    fn from_cbor(version: u32, input: &[u8]) -> Result<(&[u8], Self), ParseError> {
+        let (input, mut map) = cbor::Map::take(ParseSection::Header, input)?;
+
+        let quorum_size = map
+            .required(META_QUORUM_SIZE, "a quorum size")?
+            .uint32("a quorum size")?;
+
+        let padding = match map.optional(
+            META_PADDING,
+            version & PADDING_FLAG != 0,
+            "a padding scheme",
+        )? {
+            None => PaddingScheme::None,
+            Some(item) => PaddingScheme::from_id(item.uint32("a padding scheme")?)
+                .filter(|&padding| padding != PaddingScheme::None)
+                .ok_or_else(|| {
+                    item.invalid("a padding scheme", "an unknown or no padding scheme")
+                })?,
+        };
+
+        let compression = match map.optional(
+            META_COMPRESSION,
+            version & COMPRESSION_FLAG != 0,
+            "a compression algorithm",
+        )? {
+            None => Compression::None,
+            Some(item) => Compression::from_ids(
+                item.uint32("a compression algorithm")?,
+                map.required(META_DICTIONARY, "a compression dictionary")?
+                    .uint32("a compression dictionary")?,
+            )
+            .filter(|&compression| compression != Compression::None)
+            .ok_or_else(|| {
+                item.invalid(
+                    "a compression algorithm and dictionary",
+                    "an unknown or no compression algorithm or dictionary",
+                )
+            })?,
+        };
+
+        let constraints = match map.optional(
+            META_CONSTRAINTS,
+            version & RECOVERY_CONSTRAINTS_FLAG != 0,
+            "recovery constraints",
+        )? {
+            None => Vec::new(),
+            Some(item) => {
+                let constraints = item
+                    .array("recovery constraints")?
+                    .iter()
+                    .map(|item| {
+                        item.wire(
+                            "a recovery constraint",
+                            RecoveryConstraint::from_wire_partial,
+                        )
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                if constraints.is_empty() {
+                    return Err(item.invalid("recovery constraints", "an empty list"));
+                }
+                constraints
+            }
+        };
+
+        let aead_mode = match map.optional(
+            META_SEGMENT_SIZE,
+            version & CHUNKED_AEAD_FLAG != 0,
+            "a segment size",
+        )? {
+            None => AeadMode::Single,
+            Some(item) => match item.uint32("a non-zero segment size")? {
+                0 => return Err(item.invalid("a non-zero segment size", "0")),
+                segment_size => AeadMode::Chunked { segment_size },
+            },
+        };
+
+        let generation = match map.optional(
+            META_GENERATION,
+            version & GENERATION_FLAG != 0,
+            "a generation",
+        )? {
+            None => 0,
+            Some(item) => match item.uint32("a non-zero generation")? {
+                0 => return Err(item.invalid("a non-zero generation", "0")),
+                generation => generation,
+            },
+        };
+
+        let created_at = map
+            .optional(
+                META_CREATED_AT,
+                version & CREATED_AT_FLAG != 0,
+                "a creation time",
+            )?
+            .map(|item| item.uint("a creation time"))
+            .transpose()?;
+
+        let aead_algorithm = match map.optional(
+            META_AEAD_ALGORITHM,
+            version & AEAD_ALGORITHM_FLAG != 0,
+            "an aead algorithm",
+        )? {
+            None => AeadAlgorithm::default(),
+            Some(item) => AeadAlgorithm::from_id(item.uint32("an aead algorithm")?)
+                .filter(|&algorithm| algorithm != AeadAlgorithm::default())
+                .ok_or_else(|| {
+                    item.invalid(
+                        "a non-default aead algorithm",
+                        "an unknown or default aead algorithm",
+                    )
+                })?,
+        };
+
+        let revoked_identities = match map.optional(
+            META_REVOKED_IDENTITIES,
+            version & REVOKED_IDENTITIES_FLAG != 0,
+            "revoked identities",
+        )? {
+            None => Vec::new(),
+            Some(item) => {
+                let revoked_identities = item
+                    .array("revoked identities")?
+                    .iter()
+                    .map(|item| {
+                        let key = item.bytes("a revoked ed25519 public key")?;
+                        let key = <&[u8; 32]>::try_from(key).map_err(|_| {
+                            item.invalid(
+                                "a revoked ed25519 public key (32 bytes)",
+                                format!("{} bytes", key.len()),
+                            )
+                        })?;
+                        VerifyingKey::from_bytes(key).map_err(|err| {
+                            item.invalid("a valid revoked ed25519 public key", err.to_string())
+                        })
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                if revoked_identities.is_empty() {
+                    return Err(item.invalid("revoked identities", "an empty list"));
+                }
+                revoked_identities
+            }
+        };
+
+        Ok((
+            input,
+            Self {
+                version,
+                quorum_size,
+                padding,
+                compression,
+                constraints,
+                aead_mode,
+                generation,
+                created_at,
+                aead_algorithm,
+                revoked_identities,
+                extensions: map.into_extensions(),
+            },
+        ))
+    }
+}
+
 // Internal only -- users can't see MainDocumentMeta.
 #[doc(hidden)]
 impl ToWire for MainDocumentMeta {
@@ -35,11 +324,91 @@ impl ToWire for MainDocumentMeta {
             .iter()
             .for_each(|b| bytes.push(*b));
 
+        // The rest of the metadata is a single map for CBOR payloads.
+        if self.version & CBOR_PAYLOAD_FLAG != 0 {
+            bytes.append(&mut self.to_cbor());
+            return bytes;
+        }
+
         // Encode quorum size.
         varuint_encode::u32(self.quorum_size, &mut buffer)
             .iter()
             .for_each(|b| bytes.push(*b));
 
+        // Encode padding scheme (only present for padded documents).
+        if self.padding != PaddingScheme::None {
+            varuint_encode::u32(self.padding.id(), &mut buffer)
+                .iter()
+                .for_each(|b| bytes.push(*b));
+        }
+
+        // Encode compression algorithm and dictionary (only present for
+        // compressed documents).
+        if self.compression != Compression::None {
+            varuint_encode::u32(self.compression.id(), &mut buffer)
+                .iter()
+                .for_each(|b| bytes.push(*b));
+            varuint_encode::u32(self.compression.dictionary_id(), &mut buffer)
+                .iter()
+                .for_each(|b| bytes.push(*b));
+        }
+
+        // Encode recovery constraints (count-prefixed, only present if there
+        // are any).
+        if !self.constraints.is_empty() {
+            varuint_encode::usize(self.constraints.len(), &mut varuint_encode::usize_buffer())
+                .iter()
+                .for_each(|b| bytes.push(*b));
+            self.constraints
+                .iter()
+                .for_each(|c| bytes.append(&mut c.to_wire()));
+        }
+
+        // Encode segment size (only present for chunked documents).
+        if let AeadMode::Chunked { segment_size } = self.aead_mode {
+            varuint_encode::u32(segment_size, &mut buffer)
+                .iter()
+                .for_each(|b| bytes.push(*b));
+        }
+
+        // Encode generation (only present for re-dealt documents).
+        if self.generation != 0 {
+            varuint_encode::u32(self.generation, &mut buffer)
+                .iter()
+                .for_each(|b| bytes.push(*b));
+        }
+
+        // Encode creation time (only present if it was recorded).
+        if let Some(created_at) = self.created_at {
+            varuint_encode::u64(created_at, &mut varuint_encode::u64_buffer())
+                .iter()
+                .for_each(|b| bytes.push(*b));
+        }
+
+        // Encode AEAD algorithm (only present if it isn't the default).
+        if self.aead_algorithm != AeadAlgorithm::default() {
+            varuint_encode::u32(self.aead_algorithm.id(), &mut buffer)
+                .iter()
+                .for_each(|b| bytes.push(*b));
+        }
+
+        // Encode revoked identities (count-prefixed, only present if the
+        // identity has been rotated).
+        if !self.revoked_identities.is_empty() {
+            varuint_encode::usize(
+                self.revoked_identities.len(),
+                &mut varuint_encode::usize_buffer(),
+            )
+            .iter()
+            .for_each(|b| bytes.push(*b));
+            self.revoked_identities.iter().for_each(|key| {
+                varuint_encode::u32(PREFIX_ED25519_PUB, &mut buffer)
+                    .iter()
+                    .chain(key.as_bytes())
+                    .for_each(|b| bytes.push(*b))
+            });
+        }
+
         bytes
     }
 }
@@ -47,8 +416,21 @@ impl ToWire for MainDocumentMeta {
 // Internal only -- users can't see MainDocumentMeta.
 #[doc(hidden)]
 impl FromWire for MainDocumentMeta {
-    fn from_wire_partial(input: &[u8]) -> Result<(&[u8], Self), String> {
-        use nom::{combinator::complete, IResult};
+    fn from_wire_partial(input: &[u8]) -> Result<(&[u8], Self), ParseError> {
+        use crate::v0::wire::helpers::take_ed25519_pub;
+        use nom::{
+            combinator::{complete, map_opt, verify},
+            sequence::pair,
+            IResult,
+        };
+
+        // CBOR payloads only share the version with the binary encoding.
+        let peeked: IResult<&[u8], u32> = varuint_nom::u32(input);
+        if let Ok((rest, version)) = peeked {
+            if version & CBOR_PAYLOAD_FLAG != 0 {
+                return MainDocumentMeta::from_cbor(version, rest);
+            }
+        }
 
         fn parse(input: &[u8]) -> IResult<&[u8], MainDocumentMeta> {
             let (input, version) = varuint_nom::u32(input)?;
@@ -57,13 +439,114 @@ impl FromWire for MainDocumentMeta {
             let meta = MainDocumentMeta {
                 version,
                 quorum_size,
+                padding: PaddingScheme::None,
+                compression: Compression::None,
+                constraints: Vec::new(),
+                aead_mode: AeadMode::Single,
+                generation: 0,
+                created_at: None,
+                aead_algorithm: AeadAlgorithm::default(),
+                revoked_identities: Vec::new(),
+                extensions: BTreeMap::new(),
             };
 
             Ok((input, meta))
         }
         let mut parse = complete(parse);
 
-        let (input, meta) = parse(input).map_err(|err| format!("{:?}", err))?;
+        fn header(
+            expected: &'static str,
+        ) -> impl Fn(nom::Err<nom::error::Error<&[u8]>>) -> ParseError {
+            ParseError::expecting(ParseSection::Header, expected)
+        }
+
+        let (mut input, mut meta) = parse(input).map_err(header("a version and quorum size"))?;
+
+        if meta.version & PADDING_FLAG != 0 {
+            let (rest, padding) = complete(verify(
+                map_opt(varuint_nom::u32, PaddingScheme::from_id),
+                |&padding| padding != PaddingScheme::None,
+            ))(input)
+            .map_err(header("a padding scheme"))?;
+            meta.padding = padding;
+            input = rest;
+        }
+
+        if meta.version & COMPRESSION_FLAG != 0 {
+            let (rest, compression) = complete(verify(
+                map_opt(
+                    pair(varuint_nom::u32, varuint_nom::u32),
+                    |(id, dictionary_id)| Compression::from_ids(id, dictionary_id),
+                ),
+                |&compression| compression != Compression::None,
+            ))(input)
+            .map_err(header("a compression algorithm and dictionary"))?;
+            meta.compression = compression;
+            input = rest;
+        }
+
+        if meta.version & RECOVERY_CONSTRAINTS_FLAG != 0 {
+            let (rest, num_constraints) =
+                complete(verify(varuint_nom::usize, |&num| num != 0))(input)
+                    .map_err(header("a non-zero recovery constraint count"))?;
+            input = rest;
+            for _ in 0..num_constraints {
+                let (rest, constraint) = RecoveryConstraint::from_wire_partial(input)?;
+                meta.constraints.push(constraint);
+                input = rest;
+            }
+        }
+
+        if meta.version & CHUNKED_AEAD_FLAG != 0 {
+            let (rest, segment_size) = complete(verify(varuint_nom::u32, |&size| size != 0))(input)
+                .map_err(header("a non-zero segment size"))?;
+            meta.aead_mode = AeadMode::Chunked { segment_size };
+            input = rest;
+        }
+
+        if meta.version & GENERATION_FLAG != 0 {
+            let (rest, generation) = complete(verify(varuint_nom::u32, |&gen| gen != 0))(input)
+                .map_err(header("a non-zero generation"))?;
+            meta.generation = generation;
+            input = rest;
+        }
+
+        if meta.version & CREATED_AT_FLAG != 0 {
+            let (rest, created_at) =
+                complete(varuint_nom::u64)(input).map_err(header("a creation time"))?;
+            meta.created_at = Some(created_at);
+            input = rest;
+        }
+
+        if meta.version & AEAD_ALGORITHM_FLAG != 0 {
+            let (rest, aead_algorithm) = complete(verify(
+                map_opt(varuint_nom::u32, AeadAlgorithm::from_id),
+                |&algorithm| algorithm != AeadAlgorithm::default(),
+            ))(input)
+            .map_err(header("a non-default aead algorithm"))?;
+            meta.aead_algorithm = aead_algorithm;
+            input = rest;
+        }
+
+        if meta.version & REVOKED_IDENTITIES_FLAG != 0 {
+            let (rest, num_revoked) = complete(verify(varuint_nom::usize, |&num| num != 0))(input)
+                .map_err(header("a non-zero revoked identity count"))?;
+            input = rest;
+            for _ in 0..num_revoked {
+                let (rest, revoked) = complete(take_ed25519_pub)(input)
+                    .map_err(header("a revoked ed25519 public key"))?;
+                meta.revoked_identities.push(revoked.map_err(|err| {
+                    ParseError::new(
+                        ParseSection::Header,
+                        input,
+                        "a valid revoked ed25519 public key",
+                        err.to_string(),
+                    )
+                })?);
+                input = rest;
+            }
+        }
+
         Ok((input, meta))
     }
 }
@@ -101,7 +584,7 @@ impl ToWire for MainDocumentBuilder {
 // Internal only -- users can't see MainDocumentBuilder.
 #[doc(hidden)]
 impl FromWire for MainDocumentBuilder {
-    fn from_wire_partial(input: &[u8]) -> Result<(&[u8], Self), String> {
+    fn from_wire_partial(input: &[u8]) -> Result<(&[u8], Self), ParseError> {
         use crate::v0::wire::helpers::{take_chachapoly_ciphertext, take_chachapoly_nonce};
         use nom::{combinator::complete, IResult};
 
@@ -114,7 +597,8 @@ impl FromWire for MainDocumentBuilder {
         let mut parse = complete(parse);
 
         let (input, meta) = MainDocumentMeta::from_wire_partial(input)?;
-        let (input, (nonce, ciphertext)) = parse(input).map_err(|err| format!("{:?}", err))?;
+        let (input, (nonce, ciphertext)) = parse(input)
+            .map_err(|err| ParseError::nom(ParseSection::Payload, "a nonce and ciphertext", err))?;
 
         Ok((
             input,
@@ -134,23 +618,59 @@ impl ToWire for MainDocument {
         bytes.append(&mut self.inner.to_wire());
         bytes.append(&mut self.identity.to_wire());
 
+        // Encode post-quantum identity (only present for hybrid-signed documents).
+        if let Some(pq_identity) = &self.pq_identity {
+            bytes.append(&mut pq_identity.to_wire());
+        }
+
         bytes
     }
 }
 
 impl FromWire for MainDocument {
-    fn from_wire_partial(input: &[u8]) -> Result<(&[u8], Self), String> {
+    fn from_wire_partial(input: &[u8]) -> Result<(&[u8], Self), ParseError> {
+        let start = input;
         let (input, inner) = MainDocumentBuilder::from_wire_partial(input)?;
+        let signature = input;
         let (input, identity) = Identity::from_wire_partial(input)?;
+        let (input, pq_identity) = match inner.meta.version & HYBRID_SIGNATURE_FLAG {
+            0 => (input, None),
+            _ => {
+                let (input, pq_identity) = PqIdentity::from_wire_partial(input)?;
+                (input, Some(pq_identity))
+            }
+        };
 
-        if inner.meta.version != 0 {
-            return Err(format!(
-                "main document version must be '0' not '{}'",
-                inner.meta.version
+        if format_version(inner.meta.version) != 0 {
+            // The version is the first field of the document.
+            return Err(ParseError::new(
+                ParseSection::Header,
+                start,
+                "main document version 0",
+                format!("version {}", format_version(inner.meta.version)),
+            ));
+        }
+        if inner
+            .meta
+            .revoked_identities
+            .contains(&identity.id_public_key)
+        {
+            return Err(ParseError::new(
+                ParseSection::Signature,
+                signature,
+                "a current signing identity",
+                "an identity revoked by the main document",
             ));
         }
 
-        Ok((input, MainDocument { inner, identity }))
+        Ok((
+            input,
+            MainDocument {
+                inner,
+                identity,
+                pq_identity,
+            },
+        ))
     }
 }
 
@@ -158,6 +678,97 @@ impl FromWire for MainDocument {
 mod test {
     use super::*;
 
+    #[quickcheck]
+    fn recovery_constraint_roundtrip(constraint: RecoveryConstraint) -> bool {
+        RecoveryConstraint::from_wire(constraint.to_wire()).unwrap() == constraint
+    }
+
+    #[test]
+    fn main_document_cbor_extensions() {
+        use crate::v0::{recover::Type, BackupBuilder, GENERATION_FLAG};
+        use ed25519_dalek::SigningKey;
+
+        let backup = BackupBuilder::new(2)
+            .cbor_payload(true)
+            .build(b"secret")
+            .unwrap();
+        let main_document = backup.main_document();
+
+        // Fields added by newer versions of paperback are preserved, so the
+        // signature still verifies.
+        let mut inner = main_document.inner.clone();
+        inner
+            .meta
+            .extensions
+            .insert(100, cbor::array([cbor::uint(1u32), cbor::bytes(b"new")]));
+        let extended = inner.sign(&SigningKey::generate(&mut rand::thread_rng()), None);
+        let decoded = MainDocument::from_wire(extended.to_wire()).unwrap();
+        assert_eq!(decoded, extended);
+        assert!(matches!(Type::from(decoded), Type::MainDocument(_)));
+
+        // Known fields must be present if and only if their flag is set.
+        let mut meta = main_document.inner.meta.clone();
+        meta.version |= GENERATION_FLAG;
+        let err = MainDocumentMeta::from_wire(meta.to_wire()).unwrap_err();
+        assert_eq!(err.section(), ParseSection::Header);
+        assert_eq!(err.found(), "missing field");
+    }
+
+    #[test]
+    fn main_document_constraints_flag() {
+        use crate::v0::BackupBuilder;
+
+        // Documents without constraints don't store a constraint count.
+        let backup = BackupBuilder::new(2).build(b"secret").unwrap();
+        let meta = backup.main_document().inner.meta.clone();
+        assert_eq!(meta.version & RECOVERY_CONSTRAINTS_FLAG, 0);
+
+        let backup = BackupBuilder::new(2)
+            .constraint(RecoveryConstraint::NotBefore(1_700_000_000))
+            .build(b"secret")
+            .unwrap();
+        let constrained = backup.main_document().inner.meta.clone();
+        assert_ne!(constrained.version & RECOVERY_CONSTRAINTS_FLAG, 0);
+        assert!(constrained.to_wire().len() > meta.to_wire().len());
+
+        // The flag must not be set without any constraints.
+        let mut empty = meta.clone();
+        empty.version |= RECOVERY_CONSTRAINTS_FLAG;
+        let mut wire = empty.to_wire();
+        wire.push(0);
+        let err = MainDocumentMeta::from_wire(wire).unwrap_err();
+        assert_eq!(err.section(), ParseSection::Header);
+    }
+
+    #[test]
+    fn main_document_compression_flag() {
+        use crate::v0::BackupBuilder;
+
+        // Uncompressed documents don't store a compression algorithm.
+        let backup = BackupBuilder::new(2).build(b"secret").unwrap();
+        let meta = backup.main_document().inner.meta.clone();
+        assert_eq!(meta.version & COMPRESSION_FLAG, 0);
+
+        let backup = BackupBuilder::new(2)
+            .compression(Compression::Deflate(None))
+            .build(b"secret")
+            .unwrap();
+        let compressed = backup.main_document().inner.meta.clone();
+        assert_ne!(compressed.version & COMPRESSION_FLAG, 0);
+        assert_eq!(
+            MainDocumentMeta::from_wire(compressed.to_wire()).unwrap(),
+            compressed
+        );
+
+        // The flag must not be set for uncompressed documents.
+        let mut uncompressed = meta.clone();
+        uncompressed.version |= COMPRESSION_FLAG;
+        let mut wire = uncompressed.to_wire();
+        wire.extend_from_slice(&[0, 0]);
+        let err = MainDocumentMeta::from_wire(wire).unwrap_err();
+        assert_eq!(err.section(), ParseSection::Header);
+    }
+
     #[quickcheck]
     fn main_document_roundtrip(main: MainDocument) -> bool {
         let main2 = MainDocument::from_wire(main.to_wire()).unwrap();
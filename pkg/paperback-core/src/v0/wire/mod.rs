@@ -16,10 +16,23 @@
  * along with this program.  If not, see <https://www.gnu.org/licenses/>.
  */
 
+mod airgap;
+mod bundle;
+mod cbor;
+mod ceremony;
+mod custody;
+mod error;
 mod helpers;
 mod internal;
 mod key_shard;
+mod lifecycle;
 mod main_document;
+mod slices;
+mod spec;
+
+pub mod words;
+
+pub use error::{ParseError, ParseSection};
 
 pub(crate) mod prefixes {
     // It's easier to read these bytes if they have unconventional groupings.
@@ -28,6 +41,9 @@ pub(crate) mod prefixes {
     /// Prefix for an ed25519 public key.
     pub(crate) const PREFIX_ED25519_PUB: u32 = 0xed;
 
+    /// Prefix for an x25519 public key.
+    pub(super) const PREFIX_X25519_PUB: u32 = 0xec;
+
     /// Prefix for an ed25519 signature.
     // NOTE: Not actually upstream -- see multiformats/multicodec#142.
     pub(super) const PREFIX_ED25519_SIG: u32 = 0xef;
@@ -51,6 +67,21 @@ pub(crate) mod prefixes {
     /// Prefix for a ChaCha20-Poly1305 nonce.
     // NOTE: Entirely our own creation and not remotely upstreamable.
     pub(super) const PREFIX_CHACHA20POLY1305_CIPHERTEXT: u64 = 0xfc_caca20_1305;
+
+    /// Prefix for the Argon2id parameters and salt of a passphrase-hardened key.
+    // NOTE: Entirely our own creation and not remotely upstreamable.
+    pub(super) const PREFIX_ARGON2ID_PARAMS: u64 = 0xfd_a2901d;
+
+    /// Prefix for an ML-DSA-65 public key.
+    pub(crate) const PREFIX_MLDSA65_PUB: u64 = 0x1211;
+
+    /// Prefix for an ML-DSA-65 signature.
+    // NOTE: Entirely our own creation and not remotely upstreamable.
+    pub(super) const PREFIX_MLDSA65_SIG: u64 = 0xfb_3d5a65;
+
+    /// Prefix for the seed of an ML-DSA-65 secret key.
+    // NOTE: Entirely our own creation and not remotely upstreamable.
+    pub(super) const PREFIX_MLDSA65_SEED: u64 = 0xff_3d5a65_536b; // "Sk"
 }
 
 pub fn multibase_strip<S: AsRef<str>>(data: S) -> Result<String, String> {
@@ -72,7 +103,28 @@ pub fn multibase_strip<S: AsRef<str>>(data: S) -> Result<String, String> {
     }
 }
 
-// TODO: Switch the errors from String to a proper thiserror error type.
+/// Options controlling how strictly serialised data is parsed.
+///
+/// The default (permissive) mode is intended for data transcribed or scanned
+/// from degraded real-world documents, and ignores formatting noise such as
+/// whitespace and `-` separators in multibase strings. Strict mode is intended
+/// for adversarial contexts (such as fuzzing or checking documents produced by
+/// someone else), and rejects any input which is not exactly the canonical
+/// encoding that paperback would produce for the parsed value.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ParseOptions {
+    /// Reject trailing data, formatting noise, non-paperback multibase bases
+    /// and any non-canonical encoding of the parsed value.
+    pub strict: bool,
+}
+
+impl ParseOptions {
+    /// The multibase base produced (and, in strict mode, required) by
+    /// paperback.
+    pub const CANONICAL_BASE: multibase::Base = multibase::Base::Base32Z;
+
+    pub const STRICT: Self = Self { strict: true };
+}
 
 pub trait ToWire {
     fn to_wire(&self) -> Vec<u8>;
@@ -81,22 +133,231 @@ pub trait ToWire {
     fn to_wire_multibase(&self, base: multibase::Base) -> String {
         multibase::encode(base, self.to_wire())
     }
+
+    /// Convert a `ToWire`-implementing type to a checksummed word sequence
+    /// (see [`words`]), which is easier to transcribe by hand.
+    fn to_wire_words(&self) -> String {
+        words::encode(self.to_wire())
+    }
+}
+
+// Number of bits encoded by each character of base, for the bases where
+// characters map to a fixed number of bits.
+fn multibase_bits_per_char(base: multibase::Base) -> Option<usize> {
+    use multibase::Base;
+
+    match base {
+        Base::Base2 => Some(1),
+        Base::Base8 => Some(3),
+        Base::Base16Lower | Base::Base16Upper => Some(4),
+        Base::Base32Lower
+        | Base::Base32Upper
+        | Base::Base32PadLower
+        | Base::Base32PadUpper
+        | Base::Base32HexLower
+        | Base::Base32HexUpper
+        | Base::Base32HexPadLower
+        | Base::Base32HexPadUpper
+        | Base::Base32Z => Some(5),
+        Base::Base64 | Base::Base64Pad | Base::Base64Url | Base::Base64UrlPad => Some(6),
+        _ => None,
+    }
+}
+
+// Decode multibase text, and parse it with parse (reporting the character
+// offset of any error in the text).
+fn parse_multibase<T, F>(input: &str, parse: F) -> Result<T, ParseError>
+where
+    F: FnOnce(multibase::Base, &[u8]) -> Result<T, ParseError>,
+{
+    let (base, data) = multibase::decode(input).map_err(|err| {
+        ParseError::new(
+            ParseSection::Encoding,
+            input.as_bytes(),
+            "multibase text",
+            err.to_string(),
+        )
+    })?;
+    parse(base, &data).map_err(|err| {
+        // The first character is the multibase prefix.
+        let position = err.position().or_else(|| {
+            err.offset()
+                .zip(multibase_bits_per_char(base))
+                .map(|(offset, bits)| 1 + offset * 8 / bits)
+        });
+        err.with_position(position)
+    })
 }
 
 pub trait FromWire: Sized {
-    fn from_wire_partial(input: &[u8]) -> Result<(&[u8], Self), String>;
+    fn from_wire_partial(input: &[u8]) -> Result<(&[u8], Self), ParseError>;
 
-    fn from_wire<B: AsRef<[u8]>>(input: B) -> Result<Self, String> {
-        match Self::from_wire_partial(input.as_ref())? {
+    fn from_wire<B: AsRef<[u8]>>(input: B) -> Result<Self, ParseError> {
+        let input = input.as_ref();
+        match Self::from_wire_partial(input).map_err(|err| err.locate(input.len()))? {
             ([], ret) => Ok(ret),
-            _ => Err("trailing bytes left after deseralisation".into()),
+            (rest, _) => Err(ParseError::new(
+                ParseSection::Payload,
+                rest,
+                "end of input",
+                format!("{} trailing bytes", rest.len()),
+            )
+            .locate(input.len())),
         }
     }
 
     /// Parse a zbase32-encoded representation of a `FromWire`-implementing type
     /// as that type.
-    fn from_wire_multibase<S: AsRef<str>>(input: S) -> Result<Self, String> {
-        let (_, data) = multibase::decode(input).map_err(|err| format!("{:?}", err))?;
+    fn from_wire_multibase<S: AsRef<str>>(input: S) -> Result<Self, ParseError> {
+        parse_multibase(input.as_ref(), |_, data| Self::from_wire(data))
+    }
+
+    /// Parse a word sequence representation (see [`words`]) of a
+    /// `FromWire`-implementing type as that type.
+    fn from_wire_words<S: AsRef<str>>(input: S) -> Result<Self, ParseError> {
+        let data = words::decode(input)
+            .map_err(|err| ParseError::from(err).in_section(ParseSection::Encoding))?;
         Self::from_wire(data)
     }
+
+    /// Parse a `FromWire`-implementing type, rejecting any non-canonical
+    /// encodings if `options.strict` is set.
+    fn from_wire_with_options<B: AsRef<[u8]>>(
+        input: B,
+        options: &ParseOptions,
+    ) -> Result<Self, ParseError>
+    where
+        Self: ToWire,
+    {
+        let input = input.as_ref();
+        let ret = Self::from_wire(input)?;
+        if options.strict {
+            let canonical = ret.to_wire();
+            if canonical != input {
+                // Point at the first byte which differs from the canonical
+                // encoding.
+                let offset = canonical
+                    .iter()
+                    .zip(input)
+                    .take_while(|(a, b)| a == b)
+                    .count();
+                return Err(ParseError::new(
+                    ParseSection::Payload,
+                    &input[offset..],
+                    "canonical encoding",
+                    "non-canonical encoding (rejected in strict mode)",
+                )
+                .locate(input.len()));
+            }
+        }
+        Ok(ret)
+    }
+
+    /// Parse a multibase-encoded representation of a `FromWire`-implementing
+    /// type. In permissive mode, formatting noise is stripped (see
+    /// [`multibase_strip`]) before parsing.
+    fn from_wire_multibase_with_options<S: AsRef<str>>(
+        input: S,
+        options: &ParseOptions,
+    ) -> Result<Self, ParseError>
+    where
+        Self: ToWire,
+    {
+        let input = match options.strict {
+            false => multibase_strip(input)
+                .map_err(|err| ParseError::from(err).in_section(ParseSection::Encoding))?,
+            true => input.as_ref().to_string(),
+        };
+        parse_multibase(&input, |base, data| {
+            if options.strict
+                && (base != ParseOptions::CANONICAL_BASE || multibase::encode(base, data) != input)
+            {
+                return Err(ParseError::new(
+                    ParseSection::Encoding,
+                    data,
+                    format!(
+                        "canonical {:?} multibase text",
+                        ParseOptions::CANONICAL_BASE
+                    ),
+                    format!("{:?} text (rejected in strict mode)", base),
+                )
+                .locate(data.len())
+                .with_position(Some(0)));
+            }
+            Self::from_wire_with_options(data, options)
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::v0::{Backup, MainDocument};
+
+    #[test]
+    fn parse_options_strict() {
+        let backup = Backup::new(2, b"secret").unwrap();
+        let main_document = backup.main_document();
+        let encoded = main_document.to_wire_multibase(ParseOptions::CANONICAL_BASE);
+
+        for options in [ParseOptions::default(), ParseOptions::STRICT] {
+            assert_eq!(
+                &MainDocument::from_wire_multibase_with_options(&encoded, &options).unwrap(),
+                main_document
+            );
+        }
+
+        // Formatting noise and other bases are only accepted in permissive mode.
+        let noisy = format!("{} {}", &encoded[..10], &encoded[10..]);
+        let base58 = main_document.to_wire_multibase(multibase::Base::Base58Btc);
+        for input in [noisy, base58] {
+            assert_eq!(
+                &MainDocument::from_wire_multibase_with_options(&input, &Default::default())
+                    .unwrap(),
+                main_document
+            );
+            assert!(
+                MainDocument::from_wire_multibase_with_options(&input, &ParseOptions::STRICT)
+                    .is_err()
+            );
+        }
+
+        // Trailing data is always rejected.
+        let mut trailing = main_document.to_wire();
+        trailing.push(0);
+        assert!(MainDocument::from_wire_with_options(&trailing, &Default::default()).is_err());
+        assert!(MainDocument::from_wire_with_options(&trailing, &ParseOptions::STRICT).is_err());
+    }
+
+    #[test]
+    fn parse_error_offsets() {
+        let backup = Backup::new(2, b"secret").unwrap();
+        let main_document = backup.main_document();
+
+        // Corrupt the prefix of the public key of the signing identity.
+        let identity_offset = main_document.inner.to_wire().len();
+        let mut corrupted = main_document.to_wire();
+        corrupted[identity_offset] ^= 0xff;
+
+        let err = MainDocument::from_wire(&corrupted).unwrap_err();
+        assert_eq!(err.section(), ParseSection::Signature);
+        assert_eq!(err.offset(), Some(identity_offset));
+
+        let encoded = multibase::encode(ParseOptions::CANONICAL_BASE, &corrupted);
+        let err = MainDocument::from_wire_multibase(encoded).unwrap_err();
+        assert_eq!(err.offset(), Some(identity_offset));
+        assert_eq!(err.position(), Some(1 + identity_offset * 8 / 5));
+
+        // Trailing data is reported where the document ends.
+        let mut trailing = main_document.to_wire();
+        trailing.push(0);
+        let err = MainDocument::from_wire(&trailing).unwrap_err();
+        assert_eq!(err.section(), ParseSection::Payload);
+        assert_eq!(err.offset(), Some(trailing.len() - 1));
+
+        // Invalid characters are reported before any parsing is done.
+        let err = MainDocument::from_wire_multibase("hnot zbase32!").unwrap_err();
+        assert_eq!(err.section(), ParseSection::Encoding);
+    }
 }
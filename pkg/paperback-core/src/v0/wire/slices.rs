@@ -0,0 +1,91 @@
+/*
+ * paperback: paper backup generator suitable for long-term storage
+ * Copyright (C) 2018-2022 Aleksa Sarai <cyphar@cyphar.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use crate::v0::{
+    slices::SuperManifest,
+    wire::{FromWire, ParseError, ParseSection, ToWire},
+};
+
+use unsigned_varint::encode as varuint_encode;
+
+impl ToWire for SuperManifest {
+    fn to_wire(&self) -> Vec<u8> {
+        let mut bytes = vec![];
+
+        // Encode secret length.
+        varuint_encode::u64(self.secret_len, &mut varuint_encode::u64_buffer())
+            .iter()
+            .for_each(|b| bytes.push(*b));
+
+        // Encode secret checksum.
+        bytes.append(&mut self.secret_chksum.to_bytes());
+
+        // Encode slice checksums (count-prefixed, in order).
+        varuint_encode::usize(
+            self.slice_chksums.len(),
+            &mut varuint_encode::usize_buffer(),
+        )
+        .iter()
+        .for_each(|b| bytes.push(*b));
+        for chksum in &self.slice_chksums {
+            bytes.append(&mut chksum.to_bytes());
+        }
+
+        bytes
+    }
+}
+
+impl FromWire for SuperManifest {
+    fn from_wire_partial(input: &[u8]) -> Result<(&[u8], Self), ParseError> {
+        use crate::v0::wire::helpers::multihash;
+        use multihash::Multihash;
+        use nom::{combinator::complete, multi::length_count, IResult};
+        use unsigned_varint::nom as varuint_nom;
+
+        fn parse(input: &[u8]) -> IResult<&[u8], (u64, Multihash, Vec<Multihash>)> {
+            let (input, secret_len) = varuint_nom::u64(input)?;
+            let (input, secret_chksum) = multihash(input)?;
+            let (input, slice_chksums) = length_count(varuint_nom::usize, multihash)(input)?;
+
+            Ok((input, (secret_len, secret_chksum, slice_chksums)))
+        }
+        let mut parse = complete(parse);
+
+        let (input, (secret_len, secret_chksum, slice_chksums)) = parse(input).map_err(
+            ParseError::expecting(ParseSection::Payload, "a super-manifest"),
+        )?;
+
+        if slice_chksums.is_empty() {
+            return Err(ParseError::new(
+                ParseSection::Payload,
+                input,
+                "at least one slice checksum",
+                "an empty super-manifest",
+            ));
+        }
+
+        Ok((
+            input,
+            SuperManifest {
+                secret_len,
+                secret_chksum,
+                slice_chksums,
+            },
+        ))
+    }
+}
@@ -0,0 +1,70 @@
+/*
+ * paperback: paper backup generator suitable for long-term storage
+ * Copyright (C) 2018-2022 Aleksa Sarai <cyphar@cyphar.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use crate::v0::{
+    spec::SpecReference,
+    wire::{FromWire, ParseError, ParseSection, ToWire},
+};
+
+use unsigned_varint::encode as varuint_encode;
+
+impl ToWire for SpecReference {
+    fn to_wire(&self) -> Vec<u8> {
+        let mut bytes = vec![];
+
+        // Encode format version.
+        varuint_encode::u32(self.version, &mut varuint_encode::u32_buffer())
+            .iter()
+            .for_each(|b| bytes.push(*b));
+
+        // Encode specification checksum.
+        bytes.append(&mut self.spec_chksum.to_bytes());
+
+        bytes
+    }
+}
+
+impl FromWire for SpecReference {
+    fn from_wire_partial(input: &[u8]) -> Result<(&[u8], Self), ParseError> {
+        use crate::v0::wire::helpers::multihash;
+        use multihash::Multihash;
+        use nom::{combinator::complete, IResult};
+        use unsigned_varint::nom as varuint_nom;
+
+        fn parse(input: &[u8]) -> IResult<&[u8], (u32, Multihash)> {
+            let (input, version) = varuint_nom::u32(input)?;
+            let (input, spec_chksum) = multihash(input)?;
+
+            Ok((input, (version, spec_chksum)))
+        }
+        let mut parse = complete(parse);
+
+        let (input, (version, spec_chksum)) = parse(input).map_err(ParseError::expecting(
+            ParseSection::Payload,
+            "a specification reference",
+        ))?;
+
+        Ok((
+            input,
+            SpecReference {
+                version,
+                spec_chksum,
+            },
+        ))
+    }
+}
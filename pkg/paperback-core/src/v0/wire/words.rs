@@ -0,0 +1,213 @@
+/*
+ * paperback: paper backup generator suitable for long-term storage
+ * Copyright (C) 2018-2022 Aleksa Sarai <cyphar@cyphar.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Encoding of wire data as a sequence of words, for hand transcription.
+//!
+//! z-base-32 strings are hard to copy by hand or read aloud, so data can also
+//! be encoded as words from the BIP-39 English wordlist (the same wordlist
+//! used for key shard codewords). Each word encodes 11 bits of data (the
+//! final data word is padded with zero bits), followed by three checksum
+//! words:
+//!
+//! ```text
+//! <data words...> <checksum> <checksum> <checksum>
+//! ```
+//!
+//! The 33 checksum bits are a flag (set if the padding is a full byte or
+//! longer, which would otherwise be ambiguous) followed by the first 32 bits
+//! of the BLAKE2b-256 hash of the data. When decoding, words are
+//! case-insensitive and can be abbreviated to their first four letters (which
+//! are unique in the wordlist).
+
+use crate::v0::{CHECKSUM_ALGORITHM, CODEWORD_LANGUAGE};
+
+use multihash::MultihashDigest;
+
+const WORD_BITS: usize = 11;
+const WORD_MASK: u32 = (1 << WORD_BITS) - 1;
+const CHECKSUM_WORDS: usize = 3;
+const CHECKSUM_BYTES: usize = 4;
+// Words can be uniquely identified by this many letters.
+const PREFIX_LENGTH: usize = 4;
+
+fn wordlist() -> &'static [&'static str] {
+    CODEWORD_LANGUAGE.wordlist().get_words_by_prefix("")
+}
+
+fn checksum(data: &[u8]) -> [u8; CHECKSUM_BYTES] {
+    let mut checksum = [0u8; CHECKSUM_BYTES];
+    checksum.copy_from_slice(&CHECKSUM_ALGORITHM.digest(data).digest()[..CHECKSUM_BYTES]);
+    checksum
+}
+
+// Number of padding bits needed to fill the final data word.
+fn padding_bits(len: usize) -> usize {
+    (WORD_BITS - (len * 8) % WORD_BITS) % WORD_BITS
+}
+
+fn lookup(word: &str) -> Option<u32> {
+    let word = word.to_lowercase();
+    let words = wordlist();
+    if let Ok(idx) = words.binary_search(&word.as_str()) {
+        return Some(idx as u32);
+    }
+    if word.len() != PREFIX_LENGTH {
+        return None;
+    }
+    let start = words.partition_point(|w| *w < word.as_str());
+    match words.get(start) {
+        Some(w) if w.starts_with(&word) => Some(start as u32),
+        _ => None,
+    }
+}
+
+/// Encode data as a word sequence, separated by single spaces.
+pub fn encode<B: AsRef<[u8]>>(data: B) -> String {
+    let data = data.as_ref();
+    let padding = padding_bits(data.len());
+    let words = wordlist();
+
+    let mut indices = Vec::with_capacity((data.len() * 8 + padding) / WORD_BITS + CHECKSUM_WORDS);
+    let mut acc: u32 = 0;
+    let mut bits = 0;
+    for &byte in data {
+        acc = (acc << 8) | byte as u32;
+        bits += 8;
+        if bits >= WORD_BITS {
+            bits -= WORD_BITS;
+            indices.push((acc >> bits) & WORD_MASK);
+            acc &= (1 << bits) - 1;
+        }
+    }
+    if bits > 0 {
+        indices.push((acc << (WORD_BITS - bits)) & WORD_MASK);
+    }
+
+    let checksum = u32::from_be_bytes(checksum(data)) as u64;
+    let long_padding = (padding >= 8) as u64;
+    let trailer = (long_padding << 32) | checksum;
+    indices.extend(
+        (0..CHECKSUM_WORDS)
+            .rev()
+            .map(|i| ((trailer >> (WORD_BITS * i)) as u32) & WORD_MASK),
+    );
+
+    indices
+        .iter()
+        .map(|&idx| words[idx as usize])
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Returns whether `text` looks like a word sequence (rather than some other
+/// encoding), meaning it is made up entirely of words from the wordlist.
+pub fn is_words<S: AsRef<str>>(text: S) -> bool {
+    let mut words = text.as_ref().split_whitespace().peekable();
+    words.peek().is_some() && words.all(|word| lookup(word).is_some())
+}
+
+/// Decode a word sequence produced by [`encode`], verifying its checksum.
+///
+/// Words may be separated by any whitespace.
+pub fn decode<S: AsRef<str>>(text: S) -> Result<Vec<u8>, String> {
+    let indices = text
+        .as_ref()
+        .split_whitespace()
+        .map(|word| lookup(word).ok_or_else(|| format!("unknown word '{}'", word)))
+        .collect::<Result<Vec<_>, _>>()?;
+    if indices.len() < CHECKSUM_WORDS {
+        return Err(format!(
+            "word sequence must be at least {} words long",
+            CHECKSUM_WORDS
+        ));
+    }
+    let (data_words, trailer_words) = indices.split_at(indices.len() - CHECKSUM_WORDS);
+
+    let trailer = trailer_words
+        .iter()
+        .fold(0u64, |acc, &idx| (acc << WORD_BITS) | idx as u64);
+    let long_padding = (trailer >> 32) as usize;
+    let len = ((data_words.len() * WORD_BITS) / 8)
+        .checked_sub(long_padding)
+        .filter(|len| padding_bits(*len) + len * 8 == data_words.len() * WORD_BITS)
+        .ok_or("invalid word sequence length")?;
+
+    let mut data = Vec::with_capacity(len);
+    let mut acc: u32 = 0;
+    let mut bits = 0;
+    for &idx in data_words {
+        acc = (acc << WORD_BITS) | idx;
+        bits += WORD_BITS;
+        while bits >= 8 && data.len() < len {
+            bits -= 8;
+            data.push((acc >> bits) as u8);
+            acc &= (1 << bits) - 1;
+        }
+    }
+    if acc != 0 {
+        return Err("invalid word sequence padding".into());
+    }
+    if checksum(&data) != (trailer as u32).to_be_bytes() {
+        return Err("invalid word sequence checksum".into());
+    }
+    Ok(data)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use quickcheck_macros::quickcheck;
+
+    #[quickcheck]
+    fn words_roundtrip(data: Vec<u8>) -> bool {
+        let encoded = encode(&data);
+        is_words(&encoded) && decode(&encoded).unwrap() == data
+    }
+
+    #[test]
+    fn words_abbreviated() {
+        let data = b"paperback words";
+        let encoded = encode(data);
+        let abbreviated = encoded
+            .split(' ')
+            .map(|word| word[..word.len().min(PREFIX_LENGTH)].to_uppercase())
+            .collect::<Vec<_>>()
+            .join("\n");
+        assert_eq!(decode(abbreviated).unwrap(), data);
+    }
+
+    #[test]
+    fn words_corrupted() {
+        let data = b"paperback words";
+        let encoded = encode(data);
+        let mut words = encoded.split(' ').collect::<Vec<_>>();
+
+        // Swapping two words is caught by the checksum.
+        words.swap(0, 1);
+        assert!(decode(words.join(" ")).is_err());
+        words.swap(0, 1);
+
+        // As is dropping a word.
+        words.remove(2);
+        assert!(decode(words.join(" ")).is_err());
+
+        assert!(!is_words("hbnd8q3ejk"));
+        assert!(decode("abandon zzzz").is_err());
+    }
+}
@@ -0,0 +1,236 @@
+/*
+ * paperback: paper backup generator suitable for long-term storage
+ * Copyright (C) 2018-2022 Aleksa Sarai <cyphar@cyphar.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+#ifndef PAPERBACK_H
+#define PAPERBACK_H
+
+/* Generated by cbindgen from pkg/paperback-ffi -- do not edit by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/// An argument was invalid (such as a `NULL` pointer or a string which is not
+/// valid UTF-8).
+#define PAPERBACK_ERROR_INVALID_ARGUMENT 900
+
+/// A main document or key shard could not be parsed.
+#define PAPERBACK_ERROR_PARSE 901
+
+/// A key shard could not be decrypted, or does not belong to the backup being
+/// recovered.
+#define PAPERBACK_ERROR_KEY_SHARD 902
+
+/// The key shards do not form a valid quorum for the main document.
+#define PAPERBACK_ERROR_QUORUM 903
+
+/// paperback panicked (this is always a bug).
+#define PAPERBACK_ERROR_PANIC 999
+
+/// A new backup, from which key shards can be generated.
+typedef struct PaperbackBackup PaperbackBackup;
+
+/// A recovery in progress: the main document and the key shards added so far.
+typedef struct PaperbackRecovery PaperbackRecovery;
+
+/// A key shard, along with its codewords.
+typedef struct PaperbackShard PaperbackShard;
+
+/// A buffer of bytes returned by paperback.
+typedef struct PaperbackBuffer {
+  uint8_t *data;
+  size_t len;
+} PaperbackBuffer;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/// The stable code of the last error on this thread (see the
+/// `paperback_core::error_code` documentation and the `PAPERBACK_ERROR_*`
+/// constants), or `0` if no call has failed.
+uint32_t paperback_last_error_code(void);
+
+/// A description of the last error on this thread, or `NULL` if no call has
+/// failed. The string is owned by paperback, and is only valid until the next
+/// call fails on this thread.
+const char *paperback_last_error_message(void);
+
+/// Free a string returned by paperback. The string (which may contain key
+/// shard codewords) is wiped before it is freed.
+///
+/// # Safety
+///
+/// `string` must be `NULL` or a string returned by paperback which has not
+/// already been freed.
+void paperback_string_free(char *string);
+
+/// Free a buffer returned by paperback. The buffer (which may contain a
+/// recovered secret) is wiped before it is freed.
+///
+/// # Safety
+///
+/// `buffer` must have been returned by paperback and not already been freed.
+void paperback_buffer_free(struct PaperbackBuffer buffer);
+
+/// Create a backup of the `secret_len` bytes at `secret`, which needs
+/// `quorum_size` key shards to be recovered. Returns `NULL` on failure.
+///
+/// # Safety
+///
+/// `secret` must point to `secret_len` readable bytes.
+struct PaperbackBackup *paperback_backup_new(const uint8_t *secret,
+                                             size_t secret_len,
+                                             uint32_t quorum_size);
+
+/// Free a backup.
+///
+/// # Safety
+///
+/// `backup` must be `NULL` or a backup returned by paperback which has not
+/// already been freed.
+void paperback_backup_free(struct PaperbackBackup *backup);
+
+/// The text encoding of the main document of the backup (to be freed with
+/// `paperback_string_free`). Returns `NULL` on failure.
+///
+/// # Safety
+///
+/// `backup` must be a valid backup.
+char *paperback_backup_main_document(const struct PaperbackBackup *backup);
+
+/// The document identifier of the backup (to be freed with
+/// `paperback_string_free`). Returns `NULL` on failure.
+///
+/// # Safety
+///
+/// `backup` must be a valid backup.
+char *paperback_backup_document_id(const struct PaperbackBackup *backup);
+
+/// Render the main document of the backup as a PDF, stored in `out` (to be
+/// freed with `paperback_buffer_free`). Returns `0` on success and `-1` on
+/// failure.
+///
+/// # Safety
+///
+/// `backup` must be a valid backup, and `out` must point to a writable
+/// `PaperbackBuffer`.
+int32_t paperback_backup_main_document_pdf(const struct PaperbackBackup *backup,
+                                           struct PaperbackBuffer *out);
+
+/// Generate a new key shard of the backup. Returns `NULL` on failure.
+///
+/// # Safety
+///
+/// `backup` must be a valid backup.
+struct PaperbackShard *paperback_backup_next_shard(const struct PaperbackBackup *backup);
+
+/// Decode a key shard from its text encoding and its codewords (separated by
+/// whitespace). Returns `NULL` on failure.
+///
+/// # Safety
+///
+/// `text` and `codewords` must be NUL-terminated strings.
+struct PaperbackShard *paperback_shard_decode(const char *text, const char *codewords);
+
+/// Free a key shard.
+///
+/// # Safety
+///
+/// `shard` must be `NULL` or a key shard returned by paperback which has not
+/// already been freed.
+void paperback_shard_free(struct PaperbackShard *shard);
+
+/// The text encoding of the key shard, without its codewords (to be freed
+/// with `paperback_string_free`). Returns `NULL` on failure.
+///
+/// # Safety
+///
+/// `shard` must be a valid key shard.
+char *paperback_shard_encode(const struct PaperbackShard *shard);
+
+/// The codewords of the key shard, separated by spaces (to be freed with
+/// `paperback_string_free`). Returns `NULL` on failure.
+///
+/// # Safety
+///
+/// `shard` must be a valid key shard.
+char *paperback_shard_codewords(const struct PaperbackShard *shard);
+
+/// Render the key shard as a PDF, stored in `out` (to be freed with
+/// `paperback_buffer_free`). Returns `0` on success and `-1` on failure.
+///
+/// # Safety
+///
+/// `shard` must be a valid key shard, and `out` must point to a writable
+/// `PaperbackBuffer`.
+int32_t paperback_shard_pdf(const struct PaperbackShard *shard, struct PaperbackBuffer *out);
+
+/// Start recovering the backup with the given main document (in its text
+/// encoding). Returns `NULL` on failure.
+///
+/// # Safety
+///
+/// `main_document` must be a NUL-terminated string.
+struct PaperbackRecovery *paperback_recovery_new(const char *main_document);
+
+/// Free a recovery.
+///
+/// # Safety
+///
+/// `recovery` must be `NULL` or a recovery returned by paperback which has not
+/// already been freed.
+void paperback_recovery_free(struct PaperbackRecovery *recovery);
+
+/// Add a key shard to the recovery, decrypting it with `passphrase` if it is
+/// passphrase protected (otherwise `passphrase` should be `NULL`). Key shards
+/// which cannot be decrypted or which don't belong to the backup are
+/// rejected. Returns `0` on success and `-1` on failure.
+///
+/// # Safety
+///
+/// `recovery` must be a valid recovery, `shard` must be a valid key shard, and
+/// `passphrase` must be `NULL` or a NUL-terminated string.
+int32_t paperback_recovery_add_shard(struct PaperbackRecovery *recovery,
+                                     const struct PaperbackShard *shard,
+                                     const char *passphrase);
+
+/// The number of key shards still needed to recover the backup.
+///
+/// # Safety
+///
+/// `recovery` must be `NULL` or a valid recovery.
+uint32_t paperback_recovery_remaining_shards(const struct PaperbackRecovery *recovery);
+
+/// Recover the secret once enough key shards have been added, storing it in
+/// `out` (to be freed with `paperback_buffer_free`). Returns `0` on success
+/// and `-1` on failure.
+///
+/// # Safety
+///
+/// `recovery` must be a valid recovery, and `out` must point to a writable
+/// `PaperbackBuffer`.
+int32_t paperback_recovery_recover(const struct PaperbackRecovery *recovery,
+                                   struct PaperbackBuffer *out);
+
+#ifdef __cplusplus
+} // extern "C"
+#endif // __cplusplus
+
+#endif /* PAPERBACK_H */
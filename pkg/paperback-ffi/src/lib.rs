@@ -0,0 +1,678 @@
+/*
+ * paperback: paper backup generator suitable for long-term storage
+ * Copyright (C) 2018-2022 Aleksa Sarai <cyphar@cyphar.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! C API for paperback.
+//!
+//! This exposes backup creation, key shard generation and recovery from
+//! [`paperback_core`] through a stable C ABI, for applications which are not
+//! written in Rust. The C header (`include/paperback.h`) is generated from
+//! this file with cbindgen.
+//!
+//! Every object returned by the API is opaque and must be freed with its
+//! `_free` function. Functions report failure by returning `NULL` (or `-1`),
+//! after which the error can be retrieved with [`paperback_last_error_code`]
+//! and [`paperback_last_error_message`]. Documents are passed as text (the
+//! same text printed by `paperback raw`).
+
+extern crate paperback_core;
+use paperback_core::latest as paperback;
+
+use paperback::{
+    pdf::{self, MemorySink, RenderOptions},
+    BackupBuilder, EncryptedKeyShard, FromWire, KeyShardCodewords, MainDocument, ToPdf, ToWire,
+    UntrustedQuorum,
+};
+
+use std::{
+    cell::RefCell,
+    ffi::{c_char, CStr, CString},
+    panic::{self, AssertUnwindSafe},
+    ptr, slice,
+};
+
+use zeroize::Zeroize;
+
+// The same encoding used by "paperback raw".
+const ENCODING_BASE: multibase::Base = multibase::Base::Base32Z;
+
+/// An argument was invalid (such as a `NULL` pointer or a string which is not
+/// valid UTF-8).
+pub const PAPERBACK_ERROR_INVALID_ARGUMENT: u32 = 900;
+/// A main document or key shard could not be parsed.
+pub const PAPERBACK_ERROR_PARSE: u32 = 901;
+/// A key shard could not be decrypted, or does not belong to the backup being
+/// recovered.
+pub const PAPERBACK_ERROR_KEY_SHARD: u32 = 902;
+/// The key shards do not form a valid quorum for the main document.
+pub const PAPERBACK_ERROR_QUORUM: u32 = 903;
+/// paperback panicked (this is always a bug).
+pub const PAPERBACK_ERROR_PANIC: u32 = 999;
+
+// An error, with the stable code it is reported with (see
+// paperback_core::error_code for the codes of paperback-core errors).
+struct Error {
+    code: u32,
+    message: String,
+}
+
+impl Error {
+    fn new<S: Into<String>>(code: u32, message: S) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+impl From<paperback::Error> for Error {
+    fn from(err: paperback::Error) -> Self {
+        Self::new(err.code().code, err.to_string())
+    }
+}
+
+impl From<pdf::Error> for Error {
+    fn from(err: pdf::Error) -> Self {
+        Self::new(err.code().code, err.to_string())
+    }
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<(u32, CString)>> = const { RefCell::new(None) };
+}
+
+// Run f, recording its error (or panic) as the last error and returning
+// failure instead.
+fn ffi_call<T, F: FnOnce() -> Result<T, Error>>(failure: T, f: F) -> T {
+    let err = match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(ret)) => return ret,
+        Ok(Err(err)) => err,
+        Err(_) => Error::new(PAPERBACK_ERROR_PANIC, "paperback panicked"),
+    };
+    // Interior NUL bytes would truncate the message, so drop them.
+    let message = CString::new(err.message.replace('\0', "")).expect("NUL bytes were removed");
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some((err.code, message)));
+    failure
+}
+
+// Borrow the object behind a pointer passed to the API.
+unsafe fn object<'a, T>(ptr: *const T, name: &str) -> Result<&'a T, Error> {
+    ptr.as_ref().ok_or_else(|| {
+        Error::new(
+            PAPERBACK_ERROR_INVALID_ARGUMENT,
+            format!("{} must not be NULL", name),
+        )
+    })
+}
+
+// Borrow a NUL-terminated UTF-8 string passed to the API.
+unsafe fn string<'a>(ptr: *const c_char, name: &str) -> Result<&'a str, Error> {
+    if ptr.is_null() {
+        return Err(Error::new(
+            PAPERBACK_ERROR_INVALID_ARGUMENT,
+            format!("{} must not be NULL", name),
+        ));
+    }
+    CStr::from_ptr(ptr).to_str().map_err(|_| {
+        Error::new(
+            PAPERBACK_ERROR_INVALID_ARGUMENT,
+            format!("{} is not valid UTF-8", name),
+        )
+    })
+}
+
+// Return a string to the caller, to be freed with paperback_string_free.
+fn into_c_string(string: String) -> Result<*mut c_char, Error> {
+    CString::new(string).map(CString::into_raw).map_err(|_| {
+        Error::new(
+            PAPERBACK_ERROR_INVALID_ARGUMENT,
+            "string contains NUL bytes",
+        )
+    })
+}
+
+/// The stable code of the last error on this thread (see the
+/// `paperback_core::error_code` documentation and the `PAPERBACK_ERROR_*`
+/// constants), or `0` if no call has failed.
+#[no_mangle]
+pub extern "C" fn paperback_last_error_code() -> u32 {
+    LAST_ERROR.with(|last| last.borrow().as_ref().map_or(0, |(code, _)| *code))
+}
+
+/// A description of the last error on this thread, or `NULL` if no call has
+/// failed. The string is owned by paperback, and is only valid until the next
+/// call fails on this thread.
+#[no_mangle]
+pub extern "C" fn paperback_last_error_message() -> *const c_char {
+    LAST_ERROR.with(|last| {
+        last.borrow()
+            .as_ref()
+            .map_or(ptr::null(), |(_, message)| message.as_ptr())
+    })
+}
+
+/// Free a string returned by paperback. The string (which may contain key
+/// shard codewords) is wiped before it is freed.
+///
+/// # Safety
+///
+/// `string` must be `NULL` or a string returned by paperback which has not
+/// already been freed.
+#[no_mangle]
+pub unsafe extern "C" fn paperback_string_free(string: *mut c_char) {
+    if !string.is_null() {
+        CString::from_raw(string).into_bytes_with_nul().zeroize();
+    }
+}
+
+/// A buffer of bytes returned by paperback.
+#[repr(C)]
+pub struct PaperbackBuffer {
+    pub data: *mut u8,
+    pub len: usize,
+}
+
+impl PaperbackBuffer {
+    fn new(mut data: Vec<u8>) -> Self {
+        // Copy the data rather than shrinking the vector, which could leave
+        // an unwiped copy of it behind when it is reallocated.
+        let boxed = Box::<[u8]>::from(data.as_slice());
+        data.zeroize();
+        let data = Box::into_raw(boxed);
+        Self {
+            data: data as *mut u8,
+            len: data.len(),
+        }
+    }
+}
+
+/// Free a buffer returned by paperback. The buffer (which may contain a
+/// recovered secret) is wiped before it is freed.
+///
+/// # Safety
+///
+/// `buffer` must have been returned by paperback and not already been freed.
+#[no_mangle]
+pub unsafe extern "C" fn paperback_buffer_free(buffer: PaperbackBuffer) {
+    if !buffer.data.is_null() {
+        Box::from_raw(ptr::slice_from_raw_parts_mut(buffer.data, buffer.len)).zeroize();
+    }
+}
+
+// Store a buffer in the output argument out.
+unsafe fn write_buffer(out: *mut PaperbackBuffer, data: Vec<u8>) -> Result<i32, Error> {
+    if out.is_null() {
+        return Err(Error::new(
+            PAPERBACK_ERROR_INVALID_ARGUMENT,
+            "output buffer must not be NULL",
+        ));
+    }
+    out.write(PaperbackBuffer::new(data));
+    Ok(0)
+}
+
+// Render document as a PDF.
+fn render_pdf<T: ToPdf>(document: &T) -> Result<Vec<u8>, Error> {
+    let mut sink = MemorySink::new();
+    document.render_to(&mut sink, "document.pdf", &RenderOptions::default())?;
+    Ok(sink
+        .into_artifacts()
+        .into_iter()
+        .next()
+        .map(|(_, pdf)| pdf)
+        .unwrap_or_default())
+}
+
+/// A new backup, from which key shards can be generated.
+pub struct PaperbackBackup(paperback::Backup);
+
+/// Create a backup of the `secret_len` bytes at `secret`, which needs
+/// `quorum_size` key shards to be recovered. Returns `NULL` on failure.
+///
+/// # Safety
+///
+/// `secret` must point to `secret_len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn paperback_backup_new(
+    secret: *const u8,
+    secret_len: usize,
+    quorum_size: u32,
+) -> *mut PaperbackBackup {
+    ffi_call(ptr::null_mut(), || {
+        let secret = match secret_len {
+            0 => &[][..],
+            _ if secret.is_null() => {
+                return Err(Error::new(
+                    PAPERBACK_ERROR_INVALID_ARGUMENT,
+                    "secret must not be NULL",
+                ))
+            }
+            _ => slice::from_raw_parts(secret, secret_len),
+        };
+        let backup = BackupBuilder::new(quorum_size).build(secret)?;
+        Ok(Box::into_raw(Box::new(PaperbackBackup(backup))))
+    })
+}
+
+/// Free a backup.
+///
+/// # Safety
+///
+/// `backup` must be `NULL` or a backup returned by paperback which has not
+/// already been freed.
+#[no_mangle]
+pub unsafe extern "C" fn paperback_backup_free(backup: *mut PaperbackBackup) {
+    if !backup.is_null() {
+        drop(Box::from_raw(backup));
+    }
+}
+
+/// The text encoding of the main document of the backup (to be freed with
+/// `paperback_string_free`). Returns `NULL` on failure.
+///
+/// # Safety
+///
+/// `backup` must be a valid backup.
+#[no_mangle]
+pub unsafe extern "C" fn paperback_backup_main_document(
+    backup: *const PaperbackBackup,
+) -> *mut c_char {
+    ffi_call(ptr::null_mut(), || {
+        let backup = object(backup, "backup")?;
+        into_c_string(backup.0.main_document().to_wire_multibase(ENCODING_BASE))
+    })
+}
+
+/// The document identifier of the backup (to be freed with
+/// `paperback_string_free`). Returns `NULL` on failure.
+///
+/// # Safety
+///
+/// `backup` must be a valid backup.
+#[no_mangle]
+pub unsafe extern "C" fn paperback_backup_document_id(
+    backup: *const PaperbackBackup,
+) -> *mut c_char {
+    ffi_call(ptr::null_mut(), || {
+        into_c_string(object(backup, "backup")?.0.main_document().id())
+    })
+}
+
+/// Render the main document of the backup as a PDF, stored in `out` (to be
+/// freed with `paperback_buffer_free`). Returns `0` on success and `-1` on
+/// failure.
+///
+/// # Safety
+///
+/// `backup` must be a valid backup, and `out` must point to a writable
+/// `PaperbackBuffer`.
+#[no_mangle]
+pub unsafe extern "C" fn paperback_backup_main_document_pdf(
+    backup: *const PaperbackBackup,
+    out: *mut PaperbackBuffer,
+) -> i32 {
+    ffi_call(-1, || {
+        let pdf = render_pdf(object(backup, "backup")?.0.main_document())?;
+        write_buffer(out, pdf)
+    })
+}
+
+/// A key shard, along with its codewords.
+pub struct PaperbackShard {
+    shard: EncryptedKeyShard,
+    codewords: KeyShardCodewords,
+}
+
+/// Generate a new key shard of the backup. Returns `NULL` on failure.
+///
+/// # Safety
+///
+/// `backup` must be a valid backup.
+#[no_mangle]
+pub unsafe extern "C" fn paperback_backup_next_shard(
+    backup: *const PaperbackBackup,
+) -> *mut PaperbackShard {
+    ffi_call(ptr::null_mut(), || {
+        let (shard, codewords) = object(backup, "backup")?.0.next_shard()?.encrypt()?;
+        Ok(Box::into_raw(Box::new(PaperbackShard { shard, codewords })))
+    })
+}
+
+/// Decode a key shard from its text encoding and its codewords (separated by
+/// whitespace). Returns `NULL` on failure.
+///
+/// # Safety
+///
+/// `text` and `codewords` must be NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn paperback_shard_decode(
+    text: *const c_char,
+    codewords: *const c_char,
+) -> *mut PaperbackShard {
+    ffi_call(ptr::null_mut(), || {
+        let shard =
+            EncryptedKeyShard::from_wire_multibase(string(text, "text")?).map_err(|err| {
+                Error::new(
+                    PAPERBACK_ERROR_PARSE,
+                    format!("failed to parse key shard: {}", err),
+                )
+            })?;
+        let codewords = string(codewords, "codewords")?
+            .split_whitespace()
+            .map(str::to_owned)
+            .collect::<Vec<_>>();
+        Ok(Box::into_raw(Box::new(PaperbackShard { shard, codewords })))
+    })
+}
+
+/// Free a key shard.
+///
+/// # Safety
+///
+/// `shard` must be `NULL` or a key shard returned by paperback which has not
+/// already been freed.
+#[no_mangle]
+pub unsafe extern "C" fn paperback_shard_free(shard: *mut PaperbackShard) {
+    if !shard.is_null() {
+        drop(Box::from_raw(shard));
+    }
+}
+
+/// The text encoding of the key shard, without its codewords (to be freed
+/// with `paperback_string_free`). Returns `NULL` on failure.
+///
+/// # Safety
+///
+/// `shard` must be a valid key shard.
+#[no_mangle]
+pub unsafe extern "C" fn paperback_shard_encode(shard: *const PaperbackShard) -> *mut c_char {
+    ffi_call(ptr::null_mut(), || {
+        into_c_string(
+            object(shard, "shard")?
+                .shard
+                .to_wire_multibase(ENCODING_BASE),
+        )
+    })
+}
+
+/// The codewords of the key shard, separated by spaces (to be freed with
+/// `paperback_string_free`). Returns `NULL` on failure.
+///
+/// # Safety
+///
+/// `shard` must be a valid key shard.
+#[no_mangle]
+pub unsafe extern "C" fn paperback_shard_codewords(shard: *const PaperbackShard) -> *mut c_char {
+    ffi_call(ptr::null_mut(), || {
+        into_c_string(object(shard, "shard")?.codewords.join(" "))
+    })
+}
+
+/// Render the key shard as a PDF, stored in `out` (to be freed with
+/// `paperback_buffer_free`). Returns `0` on success and `-1` on failure.
+///
+/// # Safety
+///
+/// `shard` must be a valid key shard, and `out` must point to a writable
+/// `PaperbackBuffer`.
+#[no_mangle]
+pub unsafe extern "C" fn paperback_shard_pdf(
+    shard: *const PaperbackShard,
+    out: *mut PaperbackBuffer,
+) -> i32 {
+    ffi_call(-1, || {
+        let shard = object(shard, "shard")?;
+        write_buffer(out, render_pdf(&(&shard.shard, &shard.codewords))?)
+    })
+}
+
+/// A recovery in progress: the main document and the key shards added so far.
+pub struct PaperbackRecovery(UntrustedQuorum);
+
+/// Start recovering the backup with the given main document (in its text
+/// encoding). Returns `NULL` on failure.
+///
+/// # Safety
+///
+/// `main_document` must be a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn paperback_recovery_new(
+    main_document: *const c_char,
+) -> *mut PaperbackRecovery {
+    ffi_call(ptr::null_mut(), || {
+        let main_document =
+            MainDocument::from_wire_multibase(string(main_document, "main_document")?).map_err(
+                |err| {
+                    Error::new(
+                        PAPERBACK_ERROR_PARSE,
+                        format!("failed to parse main document: {}", err),
+                    )
+                },
+            )?;
+        let mut quorum = UntrustedQuorum::new();
+        quorum.main_document(main_document);
+        Ok(Box::into_raw(Box::new(PaperbackRecovery(quorum))))
+    })
+}
+
+/// Free a recovery.
+///
+/// # Safety
+///
+/// `recovery` must be `NULL` or a recovery returned by paperback which has not
+/// already been freed.
+#[no_mangle]
+pub unsafe extern "C" fn paperback_recovery_free(recovery: *mut PaperbackRecovery) {
+    if !recovery.is_null() {
+        drop(Box::from_raw(recovery));
+    }
+}
+
+/// Add a key shard to the recovery, decrypting it with `passphrase` if it is
+/// passphrase protected (otherwise `passphrase` should be `NULL`). Key shards
+/// which cannot be decrypted or which don't belong to the backup are
+/// rejected. Returns `0` on success and `-1` on failure.
+///
+/// # Safety
+///
+/// `recovery` must be a valid recovery, `shard` must be a valid key shard, and
+/// `passphrase` must be `NULL` or a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn paperback_recovery_add_shard(
+    recovery: *mut PaperbackRecovery,
+    shard: *const PaperbackShard,
+    passphrase: *const c_char,
+) -> i32 {
+    ffi_call(-1, || {
+        let recovery = recovery.as_mut().ok_or_else(|| {
+            Error::new(
+                PAPERBACK_ERROR_INVALID_ARGUMENT,
+                "recovery must not be NULL",
+            )
+        })?;
+        let PaperbackShard { shard, codewords } = object(shard, "shard")?;
+        let shard = match passphrase.is_null() {
+            true => shard.decrypt(codewords),
+            false => shard
+                .decrypt_with_passphrase(codewords, string(passphrase, "passphrase")?.as_bytes()),
+        }
+        .map_err(|err| {
+            Error::new(
+                PAPERBACK_ERROR_KEY_SHARD,
+                format!("failed to decrypt key shard: {}", err),
+            )
+        })?;
+        recovery.0.check_shard(&shard).map_err(|rejection| {
+            Error::new(
+                PAPERBACK_ERROR_KEY_SHARD,
+                format!("key shard rejected: {}", rejection),
+            )
+        })?;
+        recovery.0.push_shard(shard);
+        Ok(0)
+    })
+}
+
+/// The number of key shards still needed to recover the backup.
+///
+/// # Safety
+///
+/// `recovery` must be `NULL` or a valid recovery.
+#[no_mangle]
+pub unsafe extern "C" fn paperback_recovery_remaining_shards(
+    recovery: *const PaperbackRecovery,
+) -> u32 {
+    ffi_call(0, || {
+        let quorum = &object(recovery, "recovery")?.0;
+        Ok(quorum
+            .quorum_size()
+            .unwrap_or_default()
+            .saturating_sub(quorum.untrusted_weight()))
+    })
+}
+
+/// Recover the secret once enough key shards have been added, storing it in
+/// `out` (to be freed with `paperback_buffer_free`). Returns `0` on success
+/// and `-1` on failure.
+///
+/// # Safety
+///
+/// `recovery` must be a valid recovery, and `out` must point to a writable
+/// `PaperbackBuffer`.
+#[no_mangle]
+pub unsafe extern "C" fn paperback_recovery_recover(
+    recovery: *const PaperbackRecovery,
+    out: *mut PaperbackBuffer,
+) -> i32 {
+    ffi_call(-1, || {
+        let quorum = object(recovery, "recovery")?
+            .0
+            .clone()
+            .validate()
+            .map_err(|err| {
+                Error::new(
+                    PAPERBACK_ERROR_QUORUM,
+                    format!("quorum failed to validate: {}", err.message),
+                )
+            })?;
+        write_buffer(out, quorum.recover_document()?)
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // Take ownership of a string returned by paperback.
+    unsafe fn take_string(string: *mut c_char) -> String {
+        assert!(!string.is_null(), "call failed: {:?}", last_error());
+        let owned = CStr::from_ptr(string).to_str().unwrap().to_owned();
+        paperback_string_free(string);
+        owned
+    }
+
+    fn last_error() -> (u32, String) {
+        let message = paperback_last_error_message();
+        assert!(!message.is_null());
+        let message = unsafe { CStr::from_ptr(message) };
+        (
+            paperback_last_error_code(),
+            message.to_str().unwrap().to_owned(),
+        )
+    }
+
+    #[test]
+    fn backup_and_recover() {
+        unsafe {
+            let secret = b"secret";
+            let backup = paperback_backup_new(secret.as_ptr(), secret.len(), 2);
+            assert!(!backup.is_null());
+            let main_document = take_string(paperback_backup_main_document(backup));
+            let shards = (0..3)
+                .map(|_| {
+                    let shard = paperback_backup_next_shard(backup);
+                    assert!(!shard.is_null());
+                    (
+                        take_string(paperback_shard_encode(shard)),
+                        take_string(paperback_shard_codewords(shard)),
+                        shard,
+                    )
+                })
+                .collect::<Vec<_>>();
+
+            let mut pdf = PaperbackBuffer {
+                data: ptr::null_mut(),
+                len: 0,
+            };
+            assert_eq!(paperback_shard_pdf(shards[0].2, &mut pdf), 0);
+            assert!(slice::from_raw_parts(pdf.data, pdf.len).starts_with(b"%PDF"));
+            paperback_buffer_free(pdf);
+            paperback_backup_free(backup);
+
+            let main_document = CString::new(main_document).unwrap();
+            let recovery = paperback_recovery_new(main_document.as_ptr());
+            assert!(!recovery.is_null());
+            assert_eq!(paperback_recovery_remaining_shards(recovery), 2);
+            for (text, codewords, shard) in &shards[1..] {
+                let (text, codewords) = (
+                    CString::new(text.as_str()).unwrap(),
+                    CString::new(codewords.as_str()).unwrap(),
+                );
+                let decoded = paperback_shard_decode(text.as_ptr(), codewords.as_ptr());
+                assert!(!decoded.is_null());
+                assert_eq!(
+                    paperback_recovery_add_shard(recovery, decoded, ptr::null()),
+                    0
+                );
+                paperback_shard_free(decoded);
+                paperback_shard_free(*shard);
+            }
+            paperback_shard_free(shards[0].2);
+            assert_eq!(paperback_recovery_remaining_shards(recovery), 0);
+
+            let mut recovered = PaperbackBuffer {
+                data: ptr::null_mut(),
+                len: 0,
+            };
+            assert_eq!(paperback_recovery_recover(recovery, &mut recovered), 0);
+            assert_eq!(slice::from_raw_parts(recovered.data, recovered.len), secret);
+            paperback_buffer_free(recovered);
+            paperback_recovery_free(recovery);
+        }
+    }
+
+    #[test]
+    fn report_errors() {
+        unsafe {
+            let main_document = CString::new("not a main document").unwrap();
+            assert!(paperback_recovery_new(main_document.as_ptr()).is_null());
+            assert_eq!(last_error().0, PAPERBACK_ERROR_PARSE);
+
+            assert!(paperback_recovery_new(ptr::null()).is_null());
+            let (code, message) = last_error();
+            assert_eq!(code, PAPERBACK_ERROR_INVALID_ARGUMENT);
+            assert_eq!(message, "main_document must not be NULL");
+
+            let secret = b"secret";
+            assert!(paperback_backup_new(secret.as_ptr(), secret.len(), 0).is_null());
+            assert_eq!(
+                last_error().0,
+                paperback_core::shamir::Error::ZeroThreshold.code().code
+            );
+        }
+    }
+}
@@ -0,0 +1,380 @@
+/*
+ * paperback: paper backup generator suitable for long-term storage
+ * Copyright (C) 2018-2022 Aleksa Sarai <cyphar@cyphar.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Browser bindings for paperback.
+//!
+//! This exposes enough of [`paperback_core`] (through `wasm-bindgen`) for a
+//! static web page to create and recover backups entirely client-side, so the
+//! secret never leaves the browser. Documents are passed to and from the page
+//! as text (the same text printed by `paperback raw`) or as the data scanned
+//! from their QR codes, and can be rendered as SVG pages or have their QR codes
+//! drawn onto a canvas.
+
+extern crate paperback_core;
+use paperback_core::latest as paperback;
+
+use paperback::{
+    pdf::{qr, RenderOptions},
+    BackupBuilder, EncryptedKeyShard, FromWire, KeyShardCodewords, ToPdf, ToWire, UntrustedQuorum,
+};
+
+use wasm_bindgen::{prelude::*, Clamped, JsCast};
+use web_sys::{CanvasRenderingContext2d, HtmlCanvasElement, ImageData};
+
+// The same encoding used by "paperback raw".
+const ENCODING_BASE: multibase::Base = multibase::Base::Base32Z;
+
+// Space (in pixels) left between the QR codes drawn onto a canvas.
+const CANVAS_CODE_GAP: u32 = 16;
+
+/// The main document of a backup.
+#[wasm_bindgen]
+#[derive(Clone)]
+pub struct MainDocument(paperback::MainDocument);
+
+#[wasm_bindgen]
+impl MainDocument {
+    /// Decode a main document from its text encoding.
+    pub fn decode(text: &str) -> Result<MainDocument, JsError> {
+        paperback::MainDocument::from_wire_multibase(text)
+            .map(Self)
+            .map_err(|err| JsError::new(&format!("failed to parse main document: {}", err)))
+    }
+
+    /// Decode a main document from the data scanned from each of its QR codes
+    /// (in any order).
+    #[wasm_bindgen(js_name = fromQrCodes)]
+    pub fn from_qr_codes(codes: Vec<String>) -> Result<MainDocument, JsError> {
+        let mut joiner = qr::Joiner::new();
+        for code in codes {
+            joiner.add_qr_part(code)?;
+        }
+        if !joiner.complete() {
+            return Err(JsError::new(&format!(
+                "{} more main document qr codes are needed (missing codes: {})",
+                joiner
+                    .remaining()
+                    .map(|n| n.to_string())
+                    .unwrap_or_else(|| "an unknown number of".to_string()),
+                joiner
+                    .missing()
+                    .iter()
+                    .map(|idx| (idx + 1).to_string())
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            )));
+        }
+        paperback::MainDocument::from_wire(joiner.combine_parts()?)
+            .map(Self)
+            .map_err(|err| JsError::new(&format!("failed to parse main document: {}", err)))
+    }
+
+    /// The text encoding of the main document.
+    pub fn encode(&self) -> String {
+        self.0.to_wire_multibase(ENCODING_BASE)
+    }
+
+    /// The document identifier.
+    #[wasm_bindgen(getter)]
+    pub fn id(&self) -> String {
+        self.0.id()
+    }
+
+    /// The checksum printed on the main document.
+    #[wasm_bindgen(getter)]
+    pub fn checksum(&self) -> String {
+        self.0.checksum_string()
+    }
+
+    /// The number of key shards needed to recover the document.
+    #[wasm_bindgen(getter, js_name = quorumSize)]
+    pub fn quorum_size(&self) -> u32 {
+        self.0.quorum_size()
+    }
+
+    /// Render the main document as SVG images (one for each page).
+    #[wasm_bindgen(js_name = toSvg)]
+    pub fn to_svg(&self) -> Result<Vec<String>, JsError> {
+        Ok(self.0.to_svg_with_options(&RenderOptions::default())?)
+    }
+
+    /// Draw the QR codes of the main document onto canvas (which is resized to
+    /// fit them), at the size they are printed at the given DPI.
+    #[wasm_bindgen(js_name = drawCodes)]
+    pub fn draw_codes(&self, canvas: &HtmlCanvasElement, dpi: u32) -> Result<(), JsError> {
+        draw_codes(&self.0, canvas, dpi)
+    }
+}
+
+/// A key shard of a backup, along with its codewords.
+#[wasm_bindgen]
+#[derive(Clone)]
+pub struct KeyShard {
+    shard: EncryptedKeyShard,
+    codewords: KeyShardCodewords,
+}
+
+#[wasm_bindgen]
+impl KeyShard {
+    /// Decode a key shard from its text encoding (or the data scanned from its
+    /// QR code) and its codewords (separated by whitespace).
+    pub fn decode(text: &str, codewords: &str) -> Result<KeyShard, JsError> {
+        let shard = EncryptedKeyShard::from_wire_multibase(text)
+            .map_err(|err| JsError::new(&format!("failed to parse key shard: {}", err)))?;
+        let codewords = codewords
+            .split_whitespace()
+            .map(str::to_owned)
+            .collect::<Vec<_>>();
+        Ok(Self { shard, codewords })
+    }
+
+    /// The text encoding of the key shard (without its codewords).
+    pub fn encode(&self) -> String {
+        self.shard.to_wire_multibase(ENCODING_BASE)
+    }
+
+    /// The codewords needed to decrypt the key shard.
+    #[wasm_bindgen(getter)]
+    pub fn codewords(&self) -> Vec<String> {
+        self.codewords.clone()
+    }
+
+    /// The checksum printed on the key shard.
+    #[wasm_bindgen(getter)]
+    pub fn checksum(&self) -> String {
+        self.shard.checksum_string()
+    }
+
+    /// Whether a passphrase is needed (as well as the codewords) to decrypt
+    /// the key shard.
+    #[wasm_bindgen(getter, js_name = isPassphraseProtected)]
+    pub fn is_passphrase_protected(&self) -> bool {
+        self.shard.is_passphrase_protected()
+    }
+
+    /// Render the key shard as SVG images (one for each page). Key shards
+    /// protected with a passphrase cannot be rendered.
+    #[wasm_bindgen(js_name = toSvg)]
+    pub fn to_svg(&self) -> Result<Vec<String>, JsError> {
+        Ok((&self.shard, &self.codewords).to_svg_with_options(&RenderOptions::default())?)
+    }
+
+    /// Draw the QR codes of the key shard onto canvas (which is resized to fit
+    /// them), at the size they are printed at the given DPI.
+    #[wasm_bindgen(js_name = drawCodes)]
+    pub fn draw_codes(&self, canvas: &HtmlCanvasElement, dpi: u32) -> Result<(), JsError> {
+        draw_codes(&(&self.shard, &self.codewords), canvas, dpi)
+    }
+
+    // Decrypt the key shard, with passphrase if it is passphrase protected.
+    fn decrypt(&self, passphrase: Option<&str>) -> Result<paperback::KeyShard, String> {
+        match passphrase {
+            Some(passphrase) => self
+                .shard
+                .decrypt_with_passphrase(&self.codewords, passphrase.as_bytes()),
+            None => self.shard.decrypt(&self.codewords),
+        }
+    }
+}
+
+/// A newly created backup.
+#[wasm_bindgen]
+pub struct Backup {
+    main_document: MainDocument,
+    key_shards: Vec<KeyShard>,
+}
+
+#[wasm_bindgen]
+impl Backup {
+    /// The main document of the backup.
+    #[wasm_bindgen(getter, js_name = mainDocument)]
+    pub fn main_document(&self) -> MainDocument {
+        self.main_document.clone()
+    }
+
+    /// The number of key shards created for the backup.
+    #[wasm_bindgen(getter, js_name = numKeyShards)]
+    pub fn num_key_shards(&self) -> usize {
+        self.key_shards.len()
+    }
+
+    /// The key shard with the given index (from 0).
+    #[wasm_bindgen(js_name = keyShard)]
+    pub fn key_shard(&self, index: usize) -> Option<KeyShard> {
+        self.key_shards.get(index).cloned()
+    }
+}
+
+/// Create a backup of secret, with num_shards key shards of which quorum_size
+/// are needed to recover it.
+#[wasm_bindgen]
+pub fn deal(secret: &[u8], quorum_size: u32, num_shards: u32) -> Result<Backup, JsError> {
+    if num_shards < quorum_size {
+        return Err(JsError::new(&format!(
+            "number of key shards {} must not be smaller than the quorum size {}",
+            num_shards, quorum_size
+        )));
+    }
+    let backup = BackupBuilder::new(quorum_size).build(secret)?;
+    let key_shards = (0..num_shards)
+        .map(|_| {
+            let (shard, codewords) = backup.next_shard()?.encrypt()?;
+            Ok(KeyShard { shard, codewords })
+        })
+        .collect::<Result<Vec<_>, paperback::Error>>()?;
+    Ok(Backup {
+        main_document: MainDocument(backup.main_document().clone()),
+        key_shards,
+    })
+}
+
+/// A recovery in progress: the main document and the key shards entered so
+/// far.
+#[wasm_bindgen]
+pub struct Recovery(UntrustedQuorum);
+
+#[wasm_bindgen]
+impl Recovery {
+    /// Start recovering the backup with the given main document.
+    #[wasm_bindgen(constructor)]
+    pub fn new(main_document: &MainDocument) -> Recovery {
+        let mut quorum = UntrustedQuorum::new();
+        quorum.main_document(main_document.0.clone());
+        Self(quorum)
+    }
+
+    /// Add a key shard (with its passphrase, if it is passphrase protected).
+    /// Key shards which cannot be decrypted or which don't belong to the
+    /// backup are rejected, so that they can be replaced.
+    #[wasm_bindgen(js_name = addKeyShard)]
+    pub fn add_key_shard(
+        &mut self,
+        key_shard: &KeyShard,
+        passphrase: Option<String>,
+    ) -> Result<(), JsError> {
+        let shard = key_shard
+            .decrypt(passphrase.as_deref())
+            .map_err(|err| JsError::new(&format!("failed to decrypt key shard: {}", err)))?;
+        self.0
+            .check_shard(&shard)
+            .map_err(|rejection| JsError::new(&format!("key shard rejected: {}", rejection)))?;
+        self.0.push_shard(shard);
+        Ok(())
+    }
+
+    /// The number of key shards still needed to recover the backup.
+    #[wasm_bindgen(getter, js_name = remainingKeyShards)]
+    pub fn remaining_key_shards(&self) -> u32 {
+        self.0
+            .quorum_size()
+            .unwrap_or_default()
+            .saturating_sub(self.0.untrusted_weight())
+    }
+
+    /// Recover the secret, once enough key shards have been added.
+    pub fn recover(self) -> Result<Vec<u8>, JsError> {
+        let quorum = self
+            .0
+            .validate()
+            .map_err(|err| JsError::new(&format!("quorum failed to validate: {}", err.message)))?;
+        Ok(quorum.recover_document()?)
+    }
+}
+
+// Draw the QR codes of document onto canvas side by side, resizing the canvas
+// to fit them.
+fn draw_codes<T: ToPdf>(document: &T, canvas: &HtmlCanvasElement, dpi: u32) -> Result<(), JsError> {
+    let codes = document
+        .qr_codes_to_png_with_options(&RenderOptions::default(), dpi)?
+        .iter()
+        .map(|png| decode_png(png))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let width = codes.iter().map(|(width, _, _)| width).sum::<u32>()
+        + CANVAS_CODE_GAP * codes.len().saturating_sub(1) as u32;
+    let height = codes
+        .iter()
+        .map(|(_, height, _)| *height)
+        .max()
+        .unwrap_or_default();
+    canvas.set_width(width);
+    canvas.set_height(height);
+
+    let context = canvas
+        .get_context("2d")
+        .ok()
+        .flatten()
+        .and_then(|context| context.dyn_into::<CanvasRenderingContext2d>().ok())
+        .ok_or_else(|| JsError::new("failed to get 2d context of canvas"))?;
+    let mut x = 0;
+    for (width, height, pixels) in codes {
+        let image = ImageData::new_with_u8_clamped_array_and_sh(Clamped(&pixels), width, height)
+            .map_err(|_| JsError::new("failed to create image of qr code"))?;
+        context
+            .put_image_data(&image, x as f64, 0.0)
+            .map_err(|_| JsError::new("failed to draw qr code onto canvas"))?;
+        x += width + CANVAS_CODE_GAP;
+    }
+    Ok(())
+}
+
+// Decode a rendered QR code into its width, height and RGBA pixels.
+fn decode_png(png: &[u8]) -> Result<(u32, u32, Vec<u8>), JsError> {
+    let mut decoder = png::Decoder::new(png);
+    decoder.set_transformations(png::Transformations::EXPAND);
+    let mut reader = decoder.read_info()?;
+    let mut pixels = vec![0; reader.output_buffer_size()];
+    let frame = reader.next_frame(&mut pixels)?;
+    if (frame.color_type, frame.bit_depth) != (png::ColorType::Rgba, png::BitDepth::Eight) {
+        return Err(JsError::new(&format!(
+            "unsupported qr code image format {:?} ({:?})",
+            frame.color_type, frame.bit_depth
+        )));
+    }
+    pixels.truncate(frame.buffer_size());
+    Ok((frame.width, frame.height, pixels))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn deal_and_recover() {
+        let backup = deal(b"secret", 2, 3).unwrap();
+        assert_eq!(backup.num_key_shards(), 3);
+        assert!(backup.key_shard(3).is_none());
+
+        // Documents survive being passed to the page as text.
+        let main_document = backup.main_document();
+        let main_document = MainDocument::decode(&main_document.encode()).unwrap();
+        assert_eq!(main_document.id(), backup.main_document().id());
+        assert_eq!(main_document.quorum_size(), 2);
+
+        let mut recovery = Recovery::new(&main_document);
+        assert_eq!(recovery.remaining_key_shards(), 2);
+        for index in [0, 2] {
+            let key_shard = backup.key_shard(index).unwrap();
+            let key_shard =
+                KeyShard::decode(&key_shard.encode(), &key_shard.codewords().join(" ")).unwrap();
+            recovery.add_key_shard(&key_shard, None).unwrap();
+        }
+        assert_eq!(recovery.remaining_key_shards(), 0);
+        assert_eq!(recovery.recover().unwrap(), b"secret");
+    }
+}
@@ -0,0 +1,205 @@
+/*
+ * paperback: paper backup generator suitable for long-term storage
+ * Copyright (C) 2018-2022 Aleksa Sarai <cyphar@cyphar.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use anyhow::{anyhow, Context, Error};
+use clap::{Arg, ArgAction, ArgMatches, Command};
+extern crate paperback_core;
+use paperback_core::latest as paperback;
+
+use paperback::{
+    airgap::{AirGapRequest, AirGapResponse, ExpandShardsApproval, ExpandShardsRequest, Operation},
+    pdf::{DirectorySink, RenderOptions},
+    ToPdf,
+};
+
+pub(crate) fn read_request() -> Result<AirGapRequest, Error> {
+    let request: AirGapRequest = crate::read_multibase_qr("Enter an air-gap request code")?;
+    // TODO: Ask the user to input the checksum...
+    println!("Air-gap request checksum: {}", request.checksum_string());
+    Ok(request)
+}
+
+pub(crate) fn read_response() -> Result<AirGapResponse, Error> {
+    let response: AirGapResponse = crate::read_multibase_qr("Enter an air-gap response code")?;
+    // TODO: Ask the user to input the checksum...
+    println!("Air-gap response checksum: {}", response.checksum_string());
+    Ok(response)
+}
+
+pub(crate) fn save_request(request: &AirGapRequest) -> Result<(), Error> {
+    let path = format!("airgap-request-{}.pdf", request.id());
+    request.render_to(
+        &mut DirectorySink::new("."),
+        &path,
+        &RenderOptions::default(),
+    )?;
+    println!("Wrote air-gap request {} to {}.", request.id(), path);
+    Ok(())
+}
+
+pub(crate) fn save_response(response: &AirGapResponse) -> Result<(), Error> {
+    let path = format!("airgap-response-{}.pdf", response.request_id());
+    response.render_to(
+        &mut DirectorySink::new("."),
+        &path,
+        &RenderOptions::default(),
+    )?;
+    println!(
+        "Wrote air-gap response to request {} to {}.",
+        response.request_id(),
+        path
+    );
+    Ok(())
+}
+
+// paperback-cli airgap request expand-shards --document-id <DOCUMENT ID> -n <SHARDS>
+fn request_expand_shards_cli() -> Command {
+    Command::new("expand-shards")
+        .about(r#"Request new key shards for a document from the offline machine (see "expand-shards --request")."#)
+        .arg(
+            Arg::new("document-id")
+                .long("document-id")
+                .value_name("DOCUMENT ID")
+                .help("Identifier of the document to create new key shards for.")
+                .action(ArgAction::Set)
+                .required(true),
+        )
+        .arg(
+            Arg::new("new-shards")
+                .short('n')
+                .long("new-shards")
+                .value_name("NUM SHARDS")
+                .help("Number of new shards to create.")
+                .action(ArgAction::Set)
+                .required(true),
+        )
+}
+
+fn request_expand_shards(matches: &ArgMatches) -> Result<(), Error> {
+    let document_id = matches
+        .get_one::<String>("document-id")
+        .context("required --document-id argument not provided")?;
+    let num_shards: u32 = matches
+        .get_one::<String>("new-shards")
+        .context("required --new-shards argument not provided")?
+        .parse()
+        .context("--new-shards argument was not an unsigned integer")?;
+
+    save_request(&AirGapRequest::new(&ExpandShardsRequest {
+        document_id: document_id.clone(),
+        num_shards,
+    }))
+}
+
+fn request_cli() -> Command {
+    Command::new("request")
+        .about("Create an air-gap request to carry out an operation on the offline machine.")
+        // paperback-cli airgap request expand-shards --document-id <DOCUMENT ID> -n <SHARDS>
+        .subcommand(request_expand_shards_cli())
+}
+
+fn request(app: &mut Command, matches: &ArgMatches) -> Result<(), Error> {
+    match matches.subcommand() {
+        Some(("expand-shards", sub_matches)) => request_expand_shards(sub_matches),
+        Some((subcommand, _)) => {
+            // We should never end up here.
+            app.print_help()?;
+            Err(anyhow!(
+                "unknown subcommand 'airgap request {}'",
+                subcommand
+            ))
+        }
+        None => {
+            app.print_help()?;
+            Err(anyhow!("no 'airgap request' subcommand specified"))
+        }
+    }
+}
+
+// paperback-cli airgap check-response --interactive
+fn check_response_cli() -> Command {
+    Command::new("check-response")
+        .about("Check that an air-gap response answers an air-gap request, and show the result.")
+        .arg(
+            Arg::new("interactive")
+                .long("interactive")
+                .help("Ask for data stored in QR codes interactively rather than scanning images.")
+                .action(ArgAction::SetTrue)
+                // TODO: Make this optional.
+                .required(true),
+        )
+}
+
+fn check_response(_matches: &ArgMatches) -> Result<(), Error> {
+    let request = read_request()?;
+    let response = read_response()?;
+    response
+        .verify(&request)
+        .context("air-gap response does not answer the request")?;
+
+    println!(
+        "Response to '{}' request {}.",
+        request.operation(),
+        request.id()
+    );
+    match request.operation() {
+        Operation::ExpandShards => {
+            let payload: ExpandShardsRequest = request.payload()?;
+            let approval: ExpandShardsApproval = response.payload(&request)?;
+            let checksums = approval.shard_checksum_strings();
+            if checksums.len() != payload.num_shards as usize {
+                return Err(anyhow!(
+                    "{} new key shards were requested but {} were created",
+                    payload.num_shards,
+                    checksums.len()
+                ));
+            }
+            println!("New key shards of document {}:", payload.document_id);
+            for checksum in checksums {
+                println!("  {}", checksum);
+            }
+        }
+        operation => return Err(anyhow!("unsupported air-gap operation '{}'", operation)),
+    }
+    Ok(())
+}
+
+pub(crate) fn submatch(app: &mut Command, matches: &ArgMatches) -> Result<(), Error> {
+    match matches.subcommand() {
+        Some(("request", sub_matches)) => request(app, sub_matches),
+        Some(("check-response", sub_matches)) => check_response(sub_matches),
+        Some((subcommand, _)) => {
+            // We should never end up here.
+            app.print_help()?;
+            Err(anyhow!("unknown subcommand 'airgap {}'", subcommand))
+        }
+        None => {
+            app.print_help()?;
+            Err(anyhow!("no 'airgap' subcommand specified"))
+        }
+    }
+}
+
+pub(crate) fn subcommands() -> Command {
+    Command::new("airgap")
+        .about("Move requests and responses across an air gap as printed QR codes.")
+        // paperback-cli airgap request ...
+        .subcommand(request_cli())
+        // paperback-cli airgap check-response --interactive
+        .subcommand(check_response_cli())
+}
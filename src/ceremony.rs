@@ -0,0 +1,179 @@
+/*
+ * paperback: paper backup generator suitable for long-term storage
+ * Copyright (C) 2018-2022 Aleksa Sarai <cyphar@cyphar.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use crate::raw::{read_oneline_file, ENCODING_BASE};
+
+use anyhow::{anyhow, Context, Error};
+use clap::{Arg, ArgAction, ArgMatches, Command};
+
+extern crate paperback_core;
+use paperback_core::latest as paperback;
+
+use paperback::{
+    ceremony::Manifest,
+    custody::{self, SigningKey},
+    FromWire, ToWire,
+};
+
+pub(crate) fn operator_key_arg() -> Arg {
+    Arg::new("operator-key")
+        .long("operator-key")
+        .value_name("KEY PATH")
+        .help(r#"Path to the secret key of an operator present during the backup, used to sign the backup manifest ("-" to read from stdin). Can be specified multiple times, once for each operator."#)
+        .action(ArgAction::Append)
+        .allow_hyphen_values(true)
+}
+
+fn read_operator_key(path: &str) -> Result<SigningKey, Error> {
+    Ok(custody::decode_secret_key(
+        read_oneline_file("Operator Secret Key", path)?.trim(),
+    )?)
+}
+
+/// Sign the manifest with every key given with `--operator-key`, returning
+/// whether any operator signed the manifest.
+pub(crate) fn sign_manifest(matches: &ArgMatches, manifest: &mut Manifest) -> Result<bool, Error> {
+    let key_paths = matches
+        .get_many::<String>("operator-key")
+        .unwrap_or_default()
+        .collect::<Vec<_>>();
+    for path in &key_paths {
+        manifest.sign(&read_operator_key(path)?);
+    }
+    Ok(!key_paths.is_empty())
+}
+
+fn manifest_arg() -> Arg {
+    Arg::new("MANIFEST")
+        .help(r#"Path to the backup manifest ("-" to read from stdin)."#)
+        .action(ArgAction::Set)
+        .allow_hyphen_values(true)
+        .required(true)
+}
+
+fn read_manifest(path: &str) -> Result<Manifest, Error> {
+    Manifest::from_wire_multibase(read_oneline_file("Manifest", path)?.trim())
+        .context("decode manifest")
+}
+
+// paperback-cli raw manifest sign --key <KEY> MANIFEST
+fn sign_cli() -> Command {
+    Command::new("sign")
+        .about("Add an operator's signature to a backup manifest.")
+        .arg(
+            Arg::new("key")
+                .long("key")
+                .value_name("KEY PATH")
+                .help(r#"Path to the operator's secret key ("-" to read from stdin)."#)
+                .action(ArgAction::Set)
+                .allow_hyphen_values(true)
+                .required(true),
+        )
+        .arg(manifest_arg())
+}
+
+fn sign(matches: &ArgMatches) -> Result<(), Error> {
+    let key = read_operator_key(matches.get_one::<String>("key").context("--key required")?)?;
+    let mut manifest = read_manifest(
+        matches
+            .get_one::<String>("MANIFEST")
+            .context("required MANIFEST argument not provided")?,
+    )?;
+
+    manifest.sign(&key);
+    println!("{}", manifest.to_wire_multibase(ENCODING_BASE));
+    Ok(())
+}
+
+// paperback-cli raw manifest verify [--operators <DIRECTORY>] MANIFEST
+fn verify_cli() -> Command {
+    Command::new("verify")
+        .about("Verify the operator signatures of a backup manifest.")
+        .arg(
+            Arg::new("operators")
+                .long("operators")
+                .value_name("DIRECTORY")
+                .help("Path to a JSON directory of operator public keys (in the same format as a holder directory). Every signature must be from an operator in the directory.")
+                .action(ArgAction::Set),
+        )
+        .arg(manifest_arg())
+}
+
+fn verify(matches: &ArgMatches) -> Result<(), Error> {
+    let operators = matches
+        .get_one::<String>("operators")
+        .map(|path| crate::holder::read_directory(path))
+        .transpose()?;
+    let manifest = read_manifest(
+        matches
+            .get_one::<String>("MANIFEST")
+            .context("required MANIFEST argument not provided")?,
+    )?;
+
+    manifest.verify().context("verifying manifest")?;
+
+    println!("Manifest-Hash: {}", manifest.hash_string());
+    println!("Main-Document: {}", manifest.document_checksum_string());
+    for chksum in manifest.shard_checksum_strings() {
+        println!("Key-Shard: {}", chksum);
+    }
+    if manifest.signatures().is_empty() {
+        return Err(anyhow!("manifest has not been signed by any operators"));
+    }
+    for sig in manifest.signatures() {
+        let operator = custody::encode_public_key(sig.operator());
+        match operators {
+            Some(ref operators) => {
+                let known = operators.find_key(sig.operator()).ok_or_else(|| {
+                    anyhow!(
+                        "manifest was signed by an operator not in the directory: {}",
+                        operator
+                    )
+                })?;
+                println!("Signed-By: {} ({})", known.name, operator);
+            }
+            None => println!("Signed-By: {}", operator),
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn submatch(app: &mut Command, matches: &ArgMatches) -> Result<(), Error> {
+    match matches.subcommand() {
+        Some(("sign", sub_matches)) => sign(sub_matches),
+        Some(("verify", sub_matches)) => verify(sub_matches),
+        Some((subcommand, _)) => {
+            // We should never end up here.
+            app.print_help()?;
+            Err(anyhow!("unknown subcommand 'raw manifest {}'", subcommand))
+        }
+        None => {
+            app.print_help()?;
+            Err(anyhow!("no 'raw manifest' subcommand specified"))
+        }
+    }
+}
+
+pub(crate) fn subcommands() -> Command {
+    Command::new("manifest")
+        .about("Sign and verify the manifest of artifacts created for a backup.")
+        // paperback-cli raw manifest sign --key <KEY> MANIFEST
+        .subcommand(sign_cli())
+        // paperback-cli raw manifest verify [--operators <DIRECTORY>] MANIFEST
+        .subcommand(verify_cli())
+}
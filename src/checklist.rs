@@ -0,0 +1,83 @@
+/*
+ * paperback: paper backup generator suitable for long-term storage
+ * Copyright (C) 2018-2022 Aleksa Sarai <cyphar@cyphar.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use std::{fs, io, time::SystemTime};
+
+use anyhow::{Context, Error};
+use clap::{Arg, ArgAction, ArgMatches};
+
+extern crate paperback_core;
+use paperback_core::latest as paperback;
+
+use paperback::checklist::{ChecklistStep, RecoveryChecklist};
+
+pub(crate) fn checklist_arg() -> Arg {
+    Arg::new("checklist")
+        .long("checklist")
+        .value_name("REPORT PATH")
+        .help("Guide the recovery through a checklist of ceremony steps, and write a timestamped report of each completed step to the given path. The report is updated after every step, so a record is kept even if the recovery fails.")
+        .action(ArgAction::Set)
+}
+
+/// The checklist for a recovery, which is a no-op unless `--checklist` was
+/// given.
+pub(crate) struct Checklist {
+    report_path: Option<String>,
+    checklist: RecoveryChecklist,
+}
+
+impl Checklist {
+    pub(crate) fn new(matches: &ArgMatches) -> Self {
+        Self {
+            report_path: matches.get_one::<String>("checklist").cloned(),
+            checklist: RecoveryChecklist::new(),
+        }
+    }
+
+    /// Record a completed step, and update the report.
+    pub(crate) fn record(&mut self, step: ChecklistStep) -> Result<(), Error> {
+        let report_path = match &self.report_path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        say!("[checklist] {}", step);
+        self.checklist.record(step, SystemTime::now());
+        fs::write(report_path, self.checklist.report())
+            .with_context(|| format!("failed to write checklist report '{}'", report_path))
+    }
+
+    /// Ask the operator to confirm that the recovery materials have been
+    /// destroyed. If they don't confirm, the step is left incomplete.
+    pub(crate) fn confirm_destroyed(&mut self) -> Result<(), Error> {
+        if self.report_path.is_none() {
+            return Ok(());
+        }
+        crate::output::prompt("[checklist] Destroy the recovery materials (decrypted shard codewords, scratch notes), then type 'destroyed' to confirm: ")?;
+        let mut answer = String::new();
+        io::stdin().read_line(&mut answer)?;
+        if answer.trim() == "destroyed" {
+            self.record(ChecklistStep::MaterialsDestroyed)?;
+        } else {
+            say!("[checklist] Recovery materials not confirmed as destroyed.");
+        }
+        if !self.checklist.is_complete() {
+            eprintln!("warning: recovery ceremony checklist is incomplete");
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,104 @@
+/*
+ * paperback: paper backup generator suitable for long-term storage
+ * Copyright (C) 2018-2022 Aleksa Sarai <cyphar@cyphar.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use anyhow::{anyhow, Context, Error};
+use clap::{Arg, ArgAction, ArgMatches, Command};
+
+extern crate paperback_core;
+use paperback_core::latest as paperback;
+
+use paperback::{ceremony::Manifest, fleet::Fleet, FromWire, MainDocument};
+
+use std::{fs, time::SystemTime};
+
+// paperback-cli fleet-verify [--format csv|json] [--output <PATH>] DIRECTORY
+pub(crate) fn subcommand() -> Command {
+    Command::new("fleet-verify")
+        .about("Verify every stored manifest and escrowed main document in a directory, and produce a consolidated report of their health for compliance reviews.")
+        .arg(
+            Arg::new("output")
+                .long("output")
+                .value_name("PATH")
+                .help(r#"Path to write the report to ("-" to write to stdout)."#)
+                .action(ArgAction::Set)
+                .allow_hyphen_values(true)
+                .default_value("-"),
+        )
+        .arg(
+            Arg::new("DIRECTORY")
+                .help("Directory containing manifests (manifest-<ID>.txt) and main documents, each stored as a single multibase string in a .txt file.")
+                .action(ArgAction::Set)
+                .required(true),
+        )
+}
+
+pub(crate) fn submatch(matches: &ArgMatches) -> Result<(), Error> {
+    let dir = matches
+        .get_one::<String>("DIRECTORY")
+        .context("required DIRECTORY argument not provided")?;
+
+    let mut paths = fs::read_dir(dir)
+        .with_context(|| format!("failed to read directory '{}'", dir))?
+        .map(|entry| Ok(entry?.path()))
+        .collect::<Result<Vec<_>, std::io::Error>>()?;
+    paths.retain(|path| path.extension().map_or(false, |ext| ext == "txt"));
+    paths.sort();
+
+    let mut fleet = Fleet::new();
+    for path in &paths {
+        let source = path.display().to_string();
+        let data =
+            fs::read_to_string(path).with_context(|| format!("failed to read '{}'", source))?;
+        let data = data.trim();
+        if let Ok(manifest) = Manifest::from_wire_multibase(data) {
+            fleet.add_manifest(source, manifest);
+        } else if let Ok(main_document) = MainDocument::from_wire_multibase(data) {
+            fleet.add_main_document(source, main_document);
+        } else {
+            fleet.add_unreadable(source, "not a manifest or main document");
+        }
+    }
+
+    let report = fleet.report(SystemTime::now());
+    // The report is written as CSV unless JSON output was requested.
+    match matches
+        .get_one::<String>("output")
+        .map(String::as_str)
+        .unwrap_or("-")
+    {
+        "-" if crate::output::is_json() => crate::output::emit(&report)?,
+        "-" => print!("{}", report.to_csv()),
+        path => fs::write(
+            path,
+            match crate::output::is_json() {
+                true => report.to_json() + "\n",
+                false => report.to_csv(),
+            },
+        )
+        .with_context(|| format!("failed to write report to '{}'", path))?,
+    }
+
+    match report.is_healthy() {
+        true => Ok(()),
+        false => Err(anyhow!(
+            "{} of {} backups failed verification",
+            report.entries.iter().filter(|entry| !entry.healthy).count(),
+            report.entries.len()
+        )),
+    }
+}
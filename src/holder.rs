@@ -0,0 +1,641 @@
+/*
+ * paperback: paper backup generator suitable for long-term storage
+ * Copyright (C) 2018-2022 Aleksa Sarai <cyphar@cyphar.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use crate::raw::{read_oneline_file, ENCODING_BASE};
+
+use anyhow::{anyhow, Context, Error};
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use serde::Serialize;
+
+extern crate paperback_core;
+use paperback_core::latest as paperback;
+
+use paperback::{
+    ceremony::{DecryptedShardHealth, Manifest},
+    custody::{
+        self, CourierBatch, CustodyReceipt, EnvelopeProof, HolderDirectory, PossessionChallenge,
+        PossessionResponse, PossessionVerifier, SealedKeyShard, SigningKey,
+    },
+    DocumentId, EncryptedKeyShard, FromWire, ToWire,
+};
+
+pub(crate) fn holders_arg() -> Arg {
+    Arg::new("holders")
+        .long("holders")
+        .value_name("DIRECTORY")
+        .help("Path to a JSON holder directory. Key shards are encrypted to each holder's public key (in directory order).")
+        .action(ArgAction::Set)
+}
+
+pub(crate) fn read_directory(path: &str) -> Result<HolderDirectory, Error> {
+    let json = std::fs::read_to_string(path)
+        .with_context(|| format!("failed to read holder directory '{}'", path))?;
+    HolderDirectory::from_json(json)
+        .with_context(|| format!("failed to load holder directory '{}'", path))
+}
+
+fn read_holder_key(path: &str) -> Result<SigningKey, Error> {
+    Ok(custody::decode_secret_key(
+        read_oneline_file("Holder Secret Key", path)?.trim(),
+    )?)
+}
+
+fn read_shard(path: &str) -> Result<EncryptedKeyShard, Error> {
+    EncryptedKeyShard::from_wire_multibase(read_oneline_file("Shard Data", path)?.trim())
+        .context("decode shard")
+}
+
+// paperback-cli raw holder keygen
+fn keygen_cli() -> Command {
+    Command::new("keygen").about("Generate a new holder keypair.")
+}
+
+fn keygen(_matches: &ArgMatches) -> Result<(), Error> {
+    let key = custody::generate_secret_key();
+    println!(
+        "Public-Key: {}",
+        custody::encode_public_key(&key.verifying_key())
+    );
+    println!("Secret-Key: {}", custody::encode_secret_key(&key));
+    Ok(())
+}
+
+fn key_arg() -> Arg {
+    Arg::new("key")
+        .long("key")
+        .value_name("KEY PATH")
+        .help(r#"Path to the holder's secret key ("-" to read from stdin)."#)
+        .action(ArgAction::Set)
+        .allow_hyphen_values(true)
+        .required(true)
+}
+
+fn shard_arg() -> Arg {
+    Arg::new("SHARD")
+        .help(r#"Path to the key shard ("-" to read from stdin)."#)
+        .action(ArgAction::Set)
+        .allow_hyphen_values(true)
+        .required(true)
+}
+
+// paperback-cli raw holder open --key <KEY> SHARD
+fn open_cli() -> Command {
+    Command::new("open")
+        .about("Decrypt a key shard which was encrypted to a holder's key.")
+        .arg(key_arg())
+        .arg(shard_arg())
+}
+
+fn open(matches: &ArgMatches) -> Result<(), Error> {
+    let key = read_holder_key(matches.get_one::<String>("key").context("--key required")?)?;
+    let sealed_path = matches
+        .get_one::<String>("SHARD")
+        .context("required SHARD argument not provided")?;
+    let sealed = SealedKeyShard::from_wire_multibase(
+        read_oneline_file("Sealed Shard Data", sealed_path)?.trim(),
+    )
+    .context("decode sealed shard")?;
+
+    let shard = sealed.open(&key).context("opening sealed shard")?;
+    println!("Checksum: {}", shard.checksum_string());
+    println!("\n{}", shard.to_wire_multibase(ENCODING_BASE));
+    Ok(())
+}
+
+// paperback-cli raw holder receipt --key <KEY> [--note <NOTE>] SHARD
+fn receipt_cli() -> Command {
+    Command::new("receipt")
+        .about("Sign a chain-of-custody receipt for a key shard.")
+        .arg(key_arg())
+        .arg(
+            Arg::new("note")
+                .long("note")
+                .value_name("NOTE")
+                .help("Free-form statement to include in the receipt.")
+                .action(ArgAction::Set)
+                .default_value("received"),
+        )
+        .arg(shard_arg())
+}
+
+fn receipt(matches: &ArgMatches) -> Result<(), Error> {
+    let key = read_holder_key(matches.get_one::<String>("key").context("--key required")?)?;
+    let note = matches
+        .get_one::<String>("note")
+        .context("--note argument not provided")?;
+    let shard = read_shard(
+        matches
+            .get_one::<String>("SHARD")
+            .context("required SHARD argument not provided")?,
+    )?;
+
+    let receipt = CustodyReceipt::sign(&shard, &key, note.as_str());
+    println!("{}", receipt.to_wire_multibase(ENCODING_BASE));
+    Ok(())
+}
+
+// paperback-cli raw holder verify-receipt --holders <DIRECTORY> --receipt <RECEIPT> SHARD
+fn verify_receipt_cli() -> Command {
+    Command::new("verify-receipt")
+        .about("Verify a chain-of-custody receipt for a key shard against a holder directory.")
+        .arg(holders_arg().required(true))
+        .arg(
+            Arg::new("receipt")
+                .long("receipt")
+                .value_name("RECEIPT PATH")
+                .help(r#"Path to the receipt ("-" to read from stdin)."#)
+                .action(ArgAction::Set)
+                .allow_hyphen_values(true)
+                .required(true),
+        )
+        .arg(shard_arg())
+}
+
+fn verify_receipt(matches: &ArgMatches) -> Result<(), Error> {
+    let directory = read_directory(
+        matches
+            .get_one::<String>("holders")
+            .context("--holders argument not provided")?,
+    )?;
+    let receipt = CustodyReceipt::from_wire_multibase(
+        read_oneline_file(
+            "Receipt",
+            matches
+                .get_one::<String>("receipt")
+                .context("--receipt argument not provided")?,
+        )?
+        .trim(),
+    )
+    .context("decode receipt")?;
+    let shard = read_shard(
+        matches
+            .get_one::<String>("SHARD")
+            .context("required SHARD argument not provided")?,
+    )?;
+
+    let holder = directory
+        .find_key(receipt.holder())
+        .ok_or_else(|| anyhow!("receipt was signed by a holder not in the directory"))?;
+    receipt.verify(&shard).context("verifying receipt")?;
+
+    println!(
+        "Receipt for shard {} signed by {}: {}",
+        shard.checksum_string(),
+        holder.name,
+        receipt.note()
+    );
+    Ok(())
+}
+
+fn read_batch(path: &str) -> Result<CourierBatch, Error> {
+    CourierBatch::from_wire_multibase(read_oneline_file("Courier Batch", path)?.trim())
+        .with_context(|| format!("decode courier batch '{}'", path))
+}
+
+// paperback-cli raw holder envelope --key <KEY> --coordinator <PUBLIC KEY> SHARD
+fn envelope_cli() -> Command {
+    Command::new("envelope")
+        .about("Seal a key shard to a coordinator's key for a courier to carry, along with a signed proof of which key shard is inside.")
+        .arg(key_arg())
+        .arg(
+            Arg::new("coordinator")
+                .long("coordinator")
+                .value_name("PUBLIC KEY")
+                .help("Public key of the coordinator who will open the envelope.")
+                .action(ArgAction::Set)
+                .required(true),
+        )
+        .arg(shard_arg())
+}
+
+fn envelope(matches: &ArgMatches) -> Result<(), Error> {
+    let key = read_holder_key(matches.get_one::<String>("key").context("--key required")?)?;
+    let coordinator = custody::decode_public_key(
+        matches
+            .get_one::<String>("coordinator")
+            .context("--coordinator argument not provided")?,
+    )?;
+    let shard = read_shard(
+        matches
+            .get_one::<String>("SHARD")
+            .context("required SHARD argument not provided")?,
+    )?;
+
+    let (envelope, proof) =
+        EnvelopeProof::seal(&shard, &key, &coordinator).context("sealing envelope")?;
+    let mut batch = CourierBatch::new();
+    batch.push(envelope, proof);
+    println!("Checksum: {}", shard.checksum_string());
+    println!("\n{}", batch.to_wire_multibase(ENCODING_BASE));
+    Ok(())
+}
+
+fn batches_arg() -> Arg {
+    Arg::new("BATCH")
+        .help(r#"Paths to courier batches, as produced by "envelope" ("-" to read from stdin)."#)
+        .action(ArgAction::Append)
+        .allow_hyphen_values(true)
+        .required(true)
+}
+
+fn read_batches(matches: &ArgMatches) -> Result<CourierBatch, Error> {
+    let mut batch = CourierBatch::new();
+    for path in matches
+        .get_many::<String>("BATCH")
+        .context("required BATCH argument not provided")?
+    {
+        batch.extend(read_batch(path)?);
+    }
+    Ok(batch)
+}
+
+// paperback-cli raw holder courier-merge BATCH...
+fn courier_merge_cli() -> Command {
+    Command::new("courier-merge")
+        .about("Combine the envelopes collected by a courier into a single batch.")
+        .arg(batches_arg())
+}
+
+fn courier_merge(matches: &ArgMatches) -> Result<(), Error> {
+    let batch = read_batches(matches)?;
+
+    println!("Envelopes: {}", batch.len());
+    println!("\n{}", batch.to_wire_multibase(ENCODING_BASE));
+    Ok(())
+}
+
+// paperback-cli raw holder courier-open --key <KEY> --manifest <MANIFEST> [--expect <CHECKSUM>]... [--holders <DIRECTORY>] BATCH...
+fn courier_open_cli() -> Command {
+    Command::new("courier-open")
+        .about("Check that a courier delivered every expected key shard, and only then open the envelopes with the coordinator's key.")
+        .arg(key_arg().help(r#"Path to the coordinator's secret key ("-" to read from stdin)."#))
+        .arg(
+            Arg::new("manifest")
+                .long("manifest")
+                .value_name("MANIFEST PATH")
+                .help(r#"Path to the backup manifest listing the key shards ("-" to read from stdin)."#)
+                .action(ArgAction::Set)
+                .allow_hyphen_values(true)
+                .required(true),
+        )
+        .arg(
+            Arg::new("expect")
+                .long("expect")
+                .value_name("CHECKSUM")
+                .help("Checksum of a key shard the courier should have collected. If not specified, every key shard in the manifest is expected.")
+                .action(ArgAction::Append),
+        )
+        .arg(holders_arg().help("Path to a JSON holder directory. If specified, every envelope proof must be signed by a holder in the directory."))
+        .arg(batches_arg())
+}
+
+fn courier_open(matches: &ArgMatches) -> Result<(), Error> {
+    let key = read_holder_key(matches.get_one::<String>("key").context("--key required")?)?;
+    let manifest_path = matches
+        .get_one::<String>("manifest")
+        .context("--manifest argument not provided")?;
+    let manifest =
+        Manifest::from_wire_multibase(read_oneline_file("Manifest", manifest_path)?.trim())
+            .context("decode manifest")?;
+    let directory = matches
+        .get_one::<String>("holders")
+        .map(|path| read_directory(path))
+        .transpose()?;
+    let batch = read_batches(matches)?;
+
+    let expected = match matches.get_many::<String>("expect") {
+        None => manifest.shard_checksums().to_vec(),
+        Some(checksums) => checksums
+            .map(|checksum| {
+                manifest
+                    .shard_checksums()
+                    .iter()
+                    .zip(manifest.shard_checksum_strings())
+                    .find(|(_, listed)| listed == checksum)
+                    .map(|(chksum, _)| *chksum)
+                    .ok_or_else(|| anyhow!("key shard {} is not listed in the manifest", checksum))
+            })
+            .collect::<Result<Vec<_>, _>>()?,
+    };
+
+    // Check the whole delivery before opening anything.
+    batch
+        .verify(&expected, &key.verifying_key())
+        .context("verifying courier batch")?;
+    for (_, proof) in batch.envelopes() {
+        let signer = match &directory {
+            Some(directory) => directory
+                .find_key(proof.holder())
+                .map(|holder| holder.name.clone())
+                .ok_or_else(|| {
+                    anyhow!(
+                        "envelope for key shard {} was sealed by a holder not in the directory",
+                        proof.shard_checksum_string()
+                    )
+                })?,
+            None => custody::encode_public_key(proof.holder()),
+        };
+        println!(
+            "Envelope for key shard {} sealed by {}.",
+            proof.shard_checksum_string(),
+            signer
+        );
+    }
+    println!(
+        "All {} expected key shards are present -- opening envelopes.",
+        expected.len()
+    );
+
+    for shard in batch.open(&key).context("opening envelopes")? {
+        println!("\nChecksum: {}", shard.checksum_string());
+        println!("{}", shard.to_wire_multibase(ENCODING_BASE));
+    }
+    Ok(())
+}
+
+// paperback-cli raw holder verifier SHARD
+fn verifier_cli() -> Command {
+    Command::new("verifier")
+        .about("Compute the verifier used to check a holder still has their key shard.")
+        .arg(shard_arg())
+}
+
+fn verifier(matches: &ArgMatches) -> Result<(), Error> {
+    let shard = read_shard(
+        matches
+            .get_one::<String>("SHARD")
+            .context("required SHARD argument not provided")?,
+    )?;
+
+    println!("Checksum: {}", shard.checksum_string());
+    println!(
+        "\n{}",
+        PossessionVerifier::new(&shard).to_wire_multibase(ENCODING_BASE)
+    );
+    Ok(())
+}
+
+// paperback-cli raw holder challenge
+fn challenge_cli() -> Command {
+    Command::new("challenge").about("Generate a random challenge to send to a holder.")
+}
+
+fn challenge(_matches: &ArgMatches) -> Result<(), Error> {
+    println!("{}", PossessionChallenge::new());
+    Ok(())
+}
+
+fn challenge_arg() -> Arg {
+    Arg::new("challenge")
+        .long("challenge")
+        .value_name("CHALLENGE")
+        .help("Challenge issued by the owner of the backup.")
+        .action(ArgAction::Set)
+        .required(true)
+}
+
+fn get_challenge(matches: &ArgMatches) -> Result<PossessionChallenge, Error> {
+    Ok(matches
+        .get_one::<String>("challenge")
+        .context("--challenge argument not provided")?
+        .parse()?)
+}
+
+// paperback-cli raw holder respond --challenge <CHALLENGE> SHARD
+fn respond_cli() -> Command {
+    Command::new("respond")
+        .about("Respond to a challenge, proving possession of a key shard without revealing it.")
+        .arg(challenge_arg())
+        .arg(shard_arg())
+}
+
+fn respond(matches: &ArgMatches) -> Result<(), Error> {
+    let challenge = get_challenge(matches)?;
+    let shard = read_shard(
+        matches
+            .get_one::<String>("SHARD")
+            .context("required SHARD argument not provided")?,
+    )?;
+
+    println!("{}", PossessionResponse::new(&shard, &challenge));
+    Ok(())
+}
+
+// paperback-cli raw holder check --verifier <VERIFIER> --challenge <CHALLENGE> RESPONSE
+fn check_cli() -> Command {
+    Command::new("check")
+        .about("Check a holder's response to a challenge.")
+        .arg(
+            Arg::new("verifier")
+                .long("verifier")
+                .value_name("VERIFIER PATH")
+                .help(r#"Path to the key shard's verifier ("-" to read from stdin)."#)
+                .action(ArgAction::Set)
+                .allow_hyphen_values(true)
+                .required(true),
+        )
+        .arg(challenge_arg())
+        .arg(
+            Arg::new("RESPONSE")
+                .help("Response given by the holder.")
+                .action(ArgAction::Set)
+                .required(true),
+        )
+}
+
+fn check(matches: &ArgMatches) -> Result<(), Error> {
+    let verifier = PossessionVerifier::from_wire_multibase(
+        read_oneline_file(
+            "Verifier",
+            matches
+                .get_one::<String>("verifier")
+                .context("--verifier argument not provided")?,
+        )?
+        .trim(),
+    )
+    .context("decode verifier")?;
+    let challenge = get_challenge(matches)?;
+    let response: PossessionResponse = matches
+        .get_one::<String>("RESPONSE")
+        .context("required RESPONSE argument not provided")?
+        .parse()?;
+
+    verifier
+        .verify(&challenge, &response)
+        .context("checking response")?;
+
+    println!("Holder has key shard {}.", verifier.shard_checksum_string());
+    Ok(())
+}
+
+// paperback-cli holder-verify [--manifest <MANIFEST>] [--no-codewords] [SHARD]
+pub(crate) fn verify_subcommand() -> Command {
+    Command::new("holder-verify")
+        .about("Check the health of a single key shard against the backup manifest, as a key shard holder. Only your own key shard is needed, and nothing can be recovered with it.")
+        .arg(
+            Arg::new("manifest")
+                .long("manifest")
+                .value_name("MANIFEST PATH")
+                .help(r#"Path to the backup manifest ("-" to read from stdin). If not specified, the manifest is read interactively."#)
+                .action(ArgAction::Set)
+                .allow_hyphen_values(true),
+        )
+        .arg(
+            Arg::new("no-codewords")
+                .long("no-codewords")
+                .help("Do not ask for the key shard codewords. Only the key shard checksum is checked against the manifest, not the key shard's contents.")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("SHARD")
+                .help(r#"Path to the key shard ("-" to read from stdin). If not specified, the key shard is read interactively."#)
+                .action(ArgAction::Set)
+                .allow_hyphen_values(true),
+        )
+}
+
+// Result of holder-verify, for --format json.
+#[derive(Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct HolderVerifyReport {
+    document_id: DocumentId,
+    checksum: String,
+    listed: bool,
+    manifest_signers: usize,
+    manifest_signatures_valid: bool,
+    // Not set if the key shard was not decrypted.
+    shard: Option<DecryptedShardHealth>,
+    decrypt_error: Option<String>,
+    healthy: bool,
+}
+
+pub(crate) fn verify_submatch(matches: &ArgMatches) -> Result<(), Error> {
+    let manifest: Manifest = match matches.get_one::<String>("manifest") {
+        Some(path) => Manifest::from_wire_multibase(read_oneline_file("Manifest", path)?.trim())
+            .context("decode manifest")?,
+        None => crate::read_multibase("Enter the backup manifest")?,
+    };
+    let shard: EncryptedKeyShard = match matches.get_one::<String>("SHARD") {
+        Some(path) => read_shard(path)?,
+        None => crate::read_multibase("Enter key shard")?,
+    };
+    say!("Key shard checksum: {}", shard.checksum_string());
+    let codewords = match matches.get_flag("no-codewords") {
+        true => None,
+        false => Some(crate::read_codewords("Key shard codewords")?),
+    };
+
+    let health = manifest.check_shard(&shard, codewords.as_deref());
+    let status = |ok: bool| if ok { "ok" } else { "FAILED" };
+    say!("Document: {}", manifest.document_id());
+    say!("Listed in manifest: {}", status(health.listed));
+    match health.manifest_signers {
+        0 => say!("Manifest signatures: none"),
+        n => say!(
+            "Manifest signatures ({} operators): {}",
+            n,
+            status(health.manifest_signatures_valid)
+        ),
+    }
+    match &health.decrypted {
+        None => say!("Key shard contents: not checked (no codewords)"),
+        Some(Err(err)) => say!("Key shard contents: FAILED (cannot decrypt: {})", err),
+        Some(Ok(decrypted)) => {
+            say!("Key shard id: {}", decrypted.id);
+            say!("Quorum size: {}", decrypted.quorum_size);
+            say!(
+                "Belongs to manifest document: {}",
+                status(decrypted.matches_document)
+            );
+            say!("Key shard signature: {}", status(decrypted.signature_valid));
+            if decrypted.test_run {
+                eprintln!("warning: key shard was created by a test run, and is not a real backup");
+            }
+        }
+    }
+
+    crate::output::emit(&HolderVerifyReport {
+        document_id: manifest.document_id(),
+        checksum: health.checksum.clone(),
+        listed: health.listed,
+        manifest_signers: health.manifest_signers,
+        manifest_signatures_valid: health.manifest_signatures_valid,
+        shard: health.decrypted.clone().and_then(Result::ok),
+        decrypt_error: health.decrypted.clone().and_then(Result::err),
+        healthy: health.is_healthy(),
+    })?;
+    match health.is_healthy() {
+        true => {
+            say!("Key shard is healthy.");
+            Ok(())
+        }
+        false => Err(anyhow!("key shard failed health checks")),
+    }
+}
+
+pub(crate) fn submatch(app: &mut Command, matches: &ArgMatches) -> Result<(), Error> {
+    match matches.subcommand() {
+        Some(("keygen", sub_matches)) => keygen(sub_matches),
+        Some(("open", sub_matches)) => open(sub_matches),
+        Some(("receipt", sub_matches)) => receipt(sub_matches),
+        Some(("verify-receipt", sub_matches)) => verify_receipt(sub_matches),
+        Some(("verifier", sub_matches)) => verifier(sub_matches),
+        Some(("challenge", sub_matches)) => challenge(sub_matches),
+        Some(("respond", sub_matches)) => respond(sub_matches),
+        Some(("check", sub_matches)) => check(sub_matches),
+        Some(("envelope", sub_matches)) => envelope(sub_matches),
+        Some(("courier-merge", sub_matches)) => courier_merge(sub_matches),
+        Some(("courier-open", sub_matches)) => courier_open(sub_matches),
+        Some((subcommand, _)) => {
+            // We should never end up here.
+            app.print_help()?;
+            Err(anyhow!("unknown subcommand 'raw holder {}'", subcommand))
+        }
+        None => {
+            app.print_help()?;
+            Err(anyhow!("no 'raw holder' subcommand specified"))
+        }
+    }
+}
+
+pub(crate) fn subcommands() -> Command {
+    Command::new("holder")
+        .about("Manage key shards owned by holders identified by public keys.")
+        // paperback-cli raw holder keygen
+        .subcommand(keygen_cli())
+        // paperback-cli raw holder open --key <KEY> SHARD
+        .subcommand(open_cli())
+        // paperback-cli raw holder receipt --key <KEY> [--note <NOTE>] SHARD
+        .subcommand(receipt_cli())
+        // paperback-cli raw holder verify-receipt --holders <DIRECTORY> --receipt <RECEIPT> SHARD
+        .subcommand(verify_receipt_cli())
+        // paperback-cli raw holder verifier SHARD
+        .subcommand(verifier_cli())
+        // paperback-cli raw holder challenge
+        .subcommand(challenge_cli())
+        // paperback-cli raw holder respond --challenge <CHALLENGE> SHARD
+        .subcommand(respond_cli())
+        // paperback-cli raw holder check --verifier <VERIFIER> --challenge <CHALLENGE> RESPONSE
+        .subcommand(check_cli())
+        // paperback-cli raw holder envelope --key <KEY> --coordinator <PUBLIC KEY> SHARD
+        .subcommand(envelope_cli())
+        // paperback-cli raw holder courier-merge BATCH...
+        .subcommand(courier_merge_cli())
+        // paperback-cli raw holder courier-open --key <KEY> --manifest <MANIFEST> [--expect <CHECKSUM>]... [--holders <DIRECTORY>] BATCH...
+        .subcommand(courier_open_cli())
+}
@@ -0,0 +1,209 @@
+/*
+ * paperback: paper backup generator suitable for long-term storage
+ * Copyright (C) 2018-2022 Aleksa Sarai <cyphar@cyphar.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use crate::secret::SecretBuf;
+
+use anyhow::{bail, Context, Error};
+use clap::{Arg, ArgAction, ArgMatches};
+
+use std::{
+    fmt,
+    fs::{self, File},
+    io::{self, Read},
+    path::PathBuf,
+    str::FromStr,
+};
+
+/// Where to read secret data from.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum InputSource {
+    /// Standard input (`-`).
+    Stdin,
+    /// A file descriptor inherited from the parent process (`fd:N`).
+    Fd(u32),
+    /// A file.
+    Path(PathBuf),
+}
+
+impl FromStr for InputSource {
+    type Err = String;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        if source.is_empty() {
+            return Err("secret data source must not be empty".into());
+        }
+        Ok(match source.strip_prefix("fd:") {
+            Some(fd) => Self::Fd(
+                fd.parse()
+                    .map_err(|err| format!("invalid file descriptor {:?}: {}", fd, err))?,
+            ),
+            None if source == "-" => Self::Stdin,
+            None => Self::Path(source.into()),
+        })
+    }
+}
+
+impl fmt::Display for InputSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Stdin => write!(f, "stdin"),
+            Self::Fd(fd) => write!(f, "file descriptor {}", fd),
+            Self::Path(path) => write!(f, "{:?}", path),
+        }
+    }
+}
+
+impl InputSource {
+    /// Open the source, failing any read which would go past max_size bytes.
+    pub(crate) fn open(&self, max_size: u64) -> Result<LimitedReader, Error> {
+        let inner: Box<dyn Read> = match self {
+            Self::Stdin => Box::new(io::stdin()),
+            // Opening the file descriptor through /dev/fd (rather than taking
+            // ownership of it directly) means we don't need any unsafe code.
+            #[cfg(unix)]
+            Self::Fd(fd) => Box::new(
+                File::open(format!("/dev/fd/{}", fd))
+                    .with_context(|| format!("failed to open file descriptor {}", fd))?,
+            ),
+            #[cfg(not(unix))]
+            Self::Fd(_) => bail!("reading from a file descriptor is only supported on unix"),
+            Self::Path(path) => Box::new(
+                File::open(path)
+                    .with_context(|| format!("failed to open secret data file {:?}", path))?,
+            ),
+        };
+        Ok(LimitedReader {
+            inner,
+            max_size,
+            remaining: max_size,
+        })
+    }
+
+    /// The size of the secret data, if it is known before it is read (only
+    /// for regular files -- pipes and devices have no useful size).
+    pub(crate) fn size(&self) -> Option<u64> {
+        match self {
+            Self::Path(path) => fs::metadata(path)
+                .ok()
+                .filter(|metadata| metadata.is_file())
+                .map(|metadata| metadata.len()),
+            Self::Stdin | Self::Fd(_) => None,
+        }
+    }
+
+    /// Returns whether the secret data is known to be larger than
+    /// `threshold` bytes, and so should be streamed rather than read into
+    /// memory.
+    pub(crate) fn is_larger_than(&self, threshold: u64) -> bool {
+        self.size().is_some_and(|size| size > threshold)
+    }
+
+    /// Read all of the secret data from the source.
+    pub(crate) fn read(&self, max_size: u64) -> Result<SecretBuf, Error> {
+        SecretBuf::read_from(self.open(max_size)?)
+            .with_context(|| format!("failed to read secret data from {}", self))
+    }
+}
+
+/// A reader which fails (rather than truncating the data, like
+/// [`Read::take`]) if the underlying reader has more than `max_size` bytes.
+pub(crate) struct LimitedReader {
+    inner: Box<dyn Read>,
+    max_size: u64,
+    remaining: u64,
+}
+
+impl Read for LimitedReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        // Always allow one extra byte to be read, so we can tell whether the
+        // data was exactly max_size bytes long.
+        let len = usize::try_from(self.remaining.saturating_add(1))
+            .map_or(buf.len(), |limit| buf.len().min(limit));
+        let n = self.inner.read(&mut buf[..len])?;
+        match self.remaining.checked_sub(n as u64) {
+            Some(remaining) => self.remaining = remaining,
+            None => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "secret data is larger than the maximum size of {} bytes (see --max-input-size)",
+                        self.max_size
+                    ),
+                ))
+            }
+        }
+        Ok(n)
+    }
+}
+
+pub(crate) fn input_arg() -> Arg {
+    Arg::new("input")
+        .long("input")
+        .value_name("SOURCE")
+        .help(r#"Read the secret data to backup from SOURCE, which is either a path, "-" to read from stdin, or "fd:N" to read from the file descriptor N inherited from the calling process (so that secret data can be piped in without being written to disk). Replaces INPUT."#)
+        .value_parser(clap::value_parser!(InputSource))
+        .conflicts_with("INPUT")
+        .action(ArgAction::Set)
+        .allow_hyphen_values(true)
+}
+
+pub(crate) fn max_input_size_arg() -> Arg {
+    Arg::new("max-input-size")
+        .long("max-input-size")
+        .value_name("BYTES")
+        .help("Refuse to backup more than this many bytes of secret data (from each secret, if there are several), so that a mistaken input (such as the wrong device) is not read in its entirety.")
+        .value_parser(clap::value_parser!(u64).range(1..))
+        .default_value("67108864")
+        .action(ArgAction::Set)
+}
+
+pub(crate) fn stream_threshold_arg() -> Arg {
+    Arg::new("stream-threshold")
+        .long("stream-threshold")
+        .value_name("BYTES")
+        .help("Stream the secret data (as with --stream) rather than reading it all into memory if it is a file larger than this many bytes, so that only the encrypted secret (rather than an additional copy of the plaintext) is held in memory while backing up a large secret. Streamed secrets cannot be compressed with --compression auto, and --verify cannot compare the recovered secret against them.")
+        .value_parser(clap::value_parser!(u64))
+        .default_value("16777216")
+        .action(ArgAction::Set)
+}
+
+pub(crate) fn get_stream_threshold(matches: &ArgMatches) -> Result<u64, Error> {
+    matches
+        .get_one::<u64>("stream-threshold")
+        .copied()
+        .context("required --stream-threshold argument not provided")
+}
+
+pub(crate) fn get_max_input_size(matches: &ArgMatches) -> Result<u64, Error> {
+    matches
+        .get_one::<u64>("max-input-size")
+        .copied()
+        .context("required --max-input-size argument not provided")
+}
+
+/// Get the source of the (single) secret to backup, given either as INPUT or
+/// with --input.
+pub(crate) fn get_input(matches: &ArgMatches) -> Result<InputSource, Error> {
+    if let Some(source) = matches.get_one::<InputSource>("input") {
+        return Ok(source.clone());
+    }
+    match matches.get_one::<String>("INPUT") {
+        Some(input) => input.parse().map_err(Error::msg),
+        None => bail!("required INPUT argument not provided"),
+    }
+}
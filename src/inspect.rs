@@ -0,0 +1,392 @@
+/*
+ * paperback: paper backup generator suitable for long-term storage
+ * Copyright (C) 2018-2022 Aleksa Sarai <cyphar@cyphar.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use anyhow::{bail, Context, Error};
+use clap::{Arg, ArgAction, ArgGroup, ArgMatches, Command};
+use serde::Serialize;
+
+extern crate paperback_core;
+use paperback_core::latest as paperback;
+
+use paperback::{
+    algorithms, constraints, lifecycle, AeadMode, Compression, Dictionary, DocumentId,
+    EncryptedKeyShard, IdentityId, MainDocument, PaddingScheme, ShardId, SpecReference,
+    TextEncoding, Type,
+};
+
+// paperback-cli inspect (--spec <SPEC PATH> | --main-document [--amendment <AMENDMENT>]... | --shard)
+pub(crate) fn subcommand() -> Command {
+    Command::new("inspect")
+        .about("Inspect printed paperback documents.")
+        .arg(
+            Arg::new("spec")
+                .long("spec")
+                .value_name("SPEC PATH")
+                .help("Verify that the given file is the archived format specification referenced by the format specification code printed on a manifest (which is read interactively).")
+                .action(ArgAction::Set),
+        )
+        .arg(
+            Arg::new("main-document")
+                .long("main-document")
+                .help("Report the metadata of a main document (which is read interactively) and whether its signature is valid, including the (non-secret) padded size and layout of the secret it stores, without recovering the secret.")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(crate::lifecycle::amendment_arg().requires("main-document"))
+        .arg(
+            Arg::new("shard")
+                .long("shard")
+                .help("Report the metadata of a key shard (which is read interactively, along with its codewords) and whether its signature is valid, without recovering the secret.")
+                .action(ArgAction::SetTrue),
+        )
+        .group(
+            ArgGroup::new("type")
+                .arg("spec")
+                .arg("main-document")
+                .arg("shard")
+                .required(true),
+        )
+}
+
+fn padding_name(padding: PaddingScheme) -> &'static str {
+    match padding {
+        PaddingScheme::None => "none",
+        PaddingScheme::Padme => "padme",
+        PaddingScheme::PowerOfTwo => "power-of-two",
+    }
+}
+
+fn text_encoding_name(encoding: TextEncoding) -> &'static str {
+    match encoding {
+        TextEncoding::Armor => "armor",
+        TextEncoding::Bech32m => "bech32m",
+    }
+}
+
+fn compression_name(compression: Compression) -> &'static str {
+    match compression {
+        Compression::None => "none",
+        Compression::Deflate(None) => "deflate",
+        Compression::Deflate(Some(Dictionary::Pem)) => "deflate-pem",
+        Compression::Deflate(Some(Dictionary::Json)) => "deflate-json",
+    }
+}
+
+// Reports printed for --format json.
+
+#[derive(Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct SpecReport {
+    version: u32,
+    spec_checksum: String,
+    spec_path: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct MainDocumentReport {
+    document_id: DocumentId,
+    checksum: String,
+    version: u32,
+    test_run: bool,
+    generation: u32,
+    created_at: Option<u64>,
+    review_by: Option<u64>,
+    lifecycle_state: String,
+    quorum_size: u32,
+    identity: IdentityId,
+    revoked_identities: Vec<IdentityId>,
+    hybrid_signed: bool,
+    cbor_payload: bool,
+    signature_valid: bool,
+    padded_size: usize,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct ShardReport {
+    shard_id: ShardId,
+    document_id: DocumentId,
+    checksum: String,
+    version: u32,
+    test_run: bool,
+    quorum_size: u32,
+    weight: u32,
+    group_id: Option<ShardId>,
+    identity: IdentityId,
+    signature_valid: bool,
+    passphrase_protected: bool,
+    custodian_name: Option<String>,
+    custodian_contact: Option<String>,
+    custodian_note: Option<String>,
+    expanded_at: Option<String>,
+    expanded_from: Option<Vec<ShardId>>,
+    expansion_countersigned: Option<bool>,
+}
+
+fn inspect_spec(spec_path: &str) -> Result<(), Error> {
+    let reference: SpecReference = crate::read_multibase("Enter the format specification code")?;
+    say!("Format version: paperback-v{}", reference.version());
+    say!(
+        "Specification checksum: {}",
+        reference.spec_checksum_string()
+    );
+    if SpecReference::for_version(reference.version()).as_ref() != Some(&reference) {
+        eprintln!("warning: this version of paperback doesn't know about this specification");
+    }
+
+    let spec = std::fs::read(spec_path)
+        .with_context(|| format!("failed to read specification '{}'", spec_path))?;
+    reference.verify(spec)?;
+    say!("Specification '{}' matches.", spec_path);
+
+    crate::output::emit(&SpecReport {
+        version: reference.version(),
+        spec_checksum: reference.spec_checksum_string(),
+        spec_path: spec_path.to_string(),
+    })
+}
+
+// Everything reported here can be derived from the (public) main document, so
+// it reveals nothing about the secret beyond what the padding already leaks.
+fn inspect_main_document(matches: &ArgMatches) -> Result<(), Error> {
+    let amendments = crate::lifecycle::get_amendments(matches)?;
+    let main_document: MainDocument = crate::read_multibase_qr("Enter a main document code")?;
+    say!("Document ID: {}", main_document.id());
+    say!("Checksum: {}", main_document.checksum_string());
+    say!("Format version: paperback-v{}", main_document.version());
+    if main_document.is_test_run() {
+        say!("Test run: yes (not a real backup)");
+    }
+    say!("Generation: {}", main_document.generation());
+    if let Some(created_at) = main_document.created_at() {
+        say!("Created: {}", constraints::format_datetime(created_at));
+    }
+    if let Some(review_by) = main_document.review_by() {
+        say!("Review by: {}", constraints::format_date(review_by));
+    }
+    let lifecycle_state = lifecycle::current_state(&main_document, &amendments)
+        .context("checking document amendments")?;
+    say!("Lifecycle state: {}", lifecycle_state);
+    say!("Quorum size: {}", main_document.quorum_size());
+    say!("Padding: {}", padding_name(main_document.padding()));
+    say!(
+        "Compression: {}",
+        compression_name(main_document.compression())
+    );
+    say!(
+        "Contents: {}",
+        match main_document.is_bundle() {
+            false => "single secret",
+            true => "labelled secrets",
+        }
+    );
+    say!(
+        "Text encoding: {}",
+        text_encoding_name(main_document.text_encoding())
+    );
+    say!(
+        "Payload encoding: {}",
+        match main_document.has_cbor_payload() {
+            false => "binary",
+            true => "cbor",
+        }
+    );
+    say!("Cipher: {}", main_document.aead_algorithm().name());
+    say!("Identity: {}", main_document.identity_id());
+    let revoked_ids = main_document.revoked_identity_ids();
+    if !revoked_ids.is_empty() {
+        say!("Revoked identities: {}", revoked_ids.join(" "));
+    }
+    say!(
+        "Signatures: {}",
+        match main_document.is_hybrid_signed() {
+            false => "ed25519",
+            true => "ed25519 + ml-dsa-65",
+        }
+    );
+    let forged = matches!(
+        Type::from(main_document.clone()),
+        Type::ForgedMainDocument(_)
+    );
+    say!(
+        "Signature: {}",
+        match forged {
+            true => "INVALID (the document may have been forged)",
+            false => "valid",
+        }
+    );
+    say!("Padded secret size: {} bytes", main_document.padded_len());
+    match main_document.aead_mode() {
+        AeadMode::Single => say!("Encrypted segments: 1"),
+        AeadMode::Chunked { segment_size } => say!(
+            "Encrypted segments: {} (of up to {} bytes)",
+            main_document.num_segments(),
+            segment_size
+        ),
+    }
+    for algorithm in algorithms::for_version(main_document.version()).unwrap_or_default() {
+        say!(
+            "Algorithm: {} {} (review after {})",
+            algorithm.name,
+            algorithm.parameters,
+            constraints::format_date(algorithm.review_after)
+        );
+    }
+    for warning in algorithms::audit(main_document.version(), std::time::SystemTime::now()).iter() {
+        eprintln!("WARNING: {}", warning);
+    }
+    if main_document.compression() == Compression::None {
+        say!(
+            "The secret is at most {} bytes long.",
+            main_document.padded_len()
+        );
+    }
+
+    crate::output::emit(&MainDocumentReport {
+        document_id: main_document.id(),
+        checksum: main_document.checksum_string(),
+        version: main_document.version(),
+        test_run: main_document.is_test_run(),
+        generation: main_document.generation(),
+        created_at: main_document.created_at(),
+        review_by: main_document.review_by(),
+        lifecycle_state: lifecycle_state.to_string(),
+        quorum_size: main_document.quorum_size(),
+        identity: main_document.identity_id(),
+        revoked_identities: revoked_ids,
+        hybrid_signed: main_document.is_hybrid_signed(),
+        cbor_payload: main_document.has_cbor_payload(),
+        signature_valid: !forged,
+        padded_size: main_document.padded_len(),
+    })
+}
+
+fn inspect_shard() -> Result<(), Error> {
+    let encrypted_shard: EncryptedKeyShard = crate::read_multibase("Enter key shard")?;
+    let codewords = crate::read_codewords("Key shard codewords")?;
+    let shard = crate::decrypt_key_shard(&encrypted_shard, &codewords, "Key shard passphrase")
+        .context("decrypting shard")?;
+    let field_size = shard.field_size();
+    say!("Key shard ID: {}", shard.id());
+    say!("Document ID: {}", shard.document_id());
+    say!("Format version: paperback-v{}", shard.version());
+    if shard.is_test_run() {
+        say!("Test run: yes (not a real backup)");
+    }
+    say!("Quorum size: {}", shard.quorum_size());
+    say!("Weight: {}", shard.weight());
+    if let Some(group_id) = shard.group_id() {
+        say!(
+            "Group: {} ({} members needed)",
+            group_id,
+            shard.member_quorum_size()
+        );
+    }
+    let custodian = shard.custodian();
+    for (field, value) in [
+        ("Custodian", &custodian.name),
+        ("Custodian contact", &custodian.contact),
+        ("Custodian note", &custodian.note),
+    ] {
+        if let Some(value) = value {
+            say!("{}: {}", field, value);
+        }
+    }
+    if let Some(expansion) = shard.expansion() {
+        say!(
+            "Minted: {} (by expanding key shards {})",
+            constraints::format_date(expansion.expanded_at),
+            expansion.quorum.join(" ")
+        );
+        say!(
+            "Minting counter-signature: {}",
+            match shard.is_expansion_countersigned() {
+                true => "present (verified during recovery)",
+                false => "MISSING",
+            }
+        );
+    }
+    say!("Identity: {}", shard.identity_id());
+    let forged = matches!(Type::from(shard.clone()), Type::ForgedKeyShard(_));
+    say!(
+        "Signature: {}",
+        match forged {
+            true => "INVALID (the key shard may have been forged)",
+            false => "valid",
+        }
+    );
+    if forged && !custodian.is_empty() {
+        eprintln!("warning: key shard signature is invalid -- the custodian details may have been tampered with");
+    }
+    if let Some(params) = encrypted_shard.passphrase_params() {
+        say!(
+            "Passphrase: Argon2id ({} KiB, {} iterations, {} lanes)",
+            params.memory_kib,
+            params.iterations,
+            params.parallelism
+        );
+    }
+    say!("Field: GF(2^{})", field_size.bits());
+    say!(
+        "Field elements: {} ({} bytes of shared secret)",
+        shard.num_field_elements(),
+        shard.num_field_elements() * field_size.bits() as usize / 8
+    );
+
+    crate::output::emit(&ShardReport {
+        shard_id: shard.id(),
+        document_id: shard.document_id(),
+        checksum: encrypted_shard.checksum_string(),
+        version: shard.version(),
+        test_run: shard.is_test_run(),
+        quorum_size: shard.quorum_size(),
+        weight: shard.weight(),
+        group_id: shard.group_id(),
+        identity: shard.identity_id(),
+        signature_valid: !forged,
+        passphrase_protected: encrypted_shard.is_passphrase_protected(),
+        custodian_name: custodian.name.clone(),
+        custodian_contact: custodian.contact.clone(),
+        custodian_note: custodian.note.clone(),
+        expanded_at: shard
+            .expansion()
+            .map(|expansion| constraints::format_date(expansion.expanded_at)),
+        expanded_from: shard.expansion().map(|expansion| expansion.quorum.clone()),
+        expansion_countersigned: shard
+            .expansion()
+            .map(|_| shard.is_expansion_countersigned()),
+    })
+}
+
+pub(crate) fn submatch(matches: &ArgMatches) -> Result<(), Error> {
+    match matches
+        .get_one::<clap::Id>("type")
+        .context("none of --spec, --main-document or --shard provided")?
+        .as_str()
+    {
+        "spec" => inspect_spec(
+            matches
+                .get_one::<String>("spec")
+                .context("required --spec argument not provided")?,
+        ),
+        "main-document" => inspect_main_document(matches),
+        "shard" => inspect_shard(),
+        // We should never reach here.
+        _ => bail!("none of --spec, --main-document or --shard provided"),
+    }
+}
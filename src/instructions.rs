@@ -0,0 +1,113 @@
+/*
+ * paperback: paper backup generator suitable for long-term storage
+ * Copyright (C) 2018-2022 Aleksa Sarai <cyphar@cyphar.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use crate::{
+    get_language, get_output_format, get_output_sink, get_paper_size, get_render_style,
+    language_arg, output_args, paper_size_arg, read_multibase_qr, render_style_arg,
+};
+
+use anyhow::{Context, Error};
+use clap::{Arg, ArgAction, ArgGroup, ArgMatches, Command};
+
+extern crate paperback_core;
+use paperback_core::latest as paperback;
+
+use paperback::{
+    pdf::{RecoveryInstructions, RenderOptions},
+    MainDocument, ToPdf,
+};
+
+// paperback-cli instructions (--interactive | --document-id <DOCUMENT ID> -n <QUORUM SIZE>)
+pub(crate) fn subcommand() -> Command {
+    Command::new("instructions")
+        .about(r#"Create a sheet of step-by-step recovery instructions for a backup, to be stored alongside its main document. The sheet explains what paperback is, where to download it, which documents are needed and the exact commands to run, so that the backup can be recovered by someone who has never used paperback (such as the executor of an estate). It contains no secret data."#)
+        .arg(
+            Arg::new("interactive")
+                .long("interactive")
+                .help("Read the document identifier and quorum size from the main document, which is asked for interactively.")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("document-id")
+                .long("document-id")
+                .value_name("DOCUMENT ID")
+                .help("Identifier of the document the instructions are for (printed in the top left corner of its main document).")
+                .requires("quorum-size")
+                .action(ArgAction::Set),
+        )
+        .arg(
+            Arg::new("quorum-size")
+                .short('n')
+                .long("quorum-size")
+                .value_name("QUORUM SIZE")
+                .help("Number of key shards required to recover the document.")
+                .value_parser(clap::value_parser!(u32).range(1..))
+                .requires("document-id")
+                .action(ArgAction::Set),
+        )
+        .group(
+            ArgGroup::new("document")
+                .args(["interactive", "document-id"])
+                .required(true),
+        )
+        .args(output_args())
+        .arg(render_style_arg())
+        .arg(paper_size_arg())
+        .arg(language_arg())
+}
+
+pub(crate) fn submatch(matches: &ArgMatches) -> Result<(), Error> {
+    let instructions = match matches.get_one::<String>("document-id") {
+        Some(document_id) => RecoveryInstructions::new(
+            document_id.clone(),
+            *matches
+                .get_one::<u32>("quorum-size")
+                .context("required --quorum-size argument not provided")?,
+        ),
+        None => {
+            let main_document: MainDocument = read_multibase_qr("Enter a main document code")?;
+            say!(
+                "Main document checksum: {}",
+                main_document.checksum_string()
+            );
+            RecoveryInstructions::from(&main_document)
+        }
+    };
+    let render_options = RenderOptions {
+        style: get_render_style(matches)?,
+        output_format: get_output_format(matches)?,
+        paper_size: get_paper_size(matches)?,
+        language: get_language(matches)?,
+        ..Default::default()
+    };
+
+    let mut sink = get_output_sink(matches)?;
+    instructions.render_to(
+        sink.as_mut(),
+        &format!("recovery_instructions-{}.pdf", instructions.document_id()),
+        &render_options,
+    )?;
+    sink.finish()?;
+
+    say!(
+        "Created recovery instructions for document {} ({} key shards required).",
+        instructions.document_id(),
+        instructions.quorum_size()
+    );
+    Ok(())
+}
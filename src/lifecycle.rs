@@ -0,0 +1,129 @@
+/*
+ * paperback: paper backup generator suitable for long-term storage
+ * Copyright (C) 2018-2022 Aleksa Sarai <cyphar@cyphar.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use crate::raw::{read_oneline_file, ENCODING_BASE};
+
+use anyhow::{Context, Error};
+use clap::{Arg, ArgAction, ArgGroup, ArgMatches, Command};
+
+extern crate paperback_core;
+use paperback_core::latest as paperback;
+
+use paperback::{Amendment, FromWire, LifecycleState, ToWire};
+
+pub(crate) fn amendment_arg() -> Arg {
+    Arg::new("amendment")
+        .long("amendment")
+        .value_name("AMENDMENT PATH")
+        .help(r#"Path to a lifecycle amendment of the document (see "amend"). Can be specified multiple times."#)
+        .action(ArgAction::Append)
+}
+
+pub(crate) fn get_amendments(matches: &ArgMatches) -> Result<Vec<Amendment>, Error> {
+    matches
+        .get_many::<String>("amendment")
+        .unwrap_or_default()
+        .map(|path| {
+            Amendment::from_wire_multibase(read_oneline_file("Amendment", path)?.trim())
+                .with_context(|| format!("decode amendment '{}'", path))
+        })
+        .collect()
+}
+
+// paperback-cli amend --interactive (--active | --superseded-by <DOCUMENT ID> | --revoked) [--document-id <DOCUMENT ID>] --sequence <SEQUENCE>
+pub(crate) fn subcommand() -> Command {
+    Command::new("amend")
+        .about(r#"Sign an amendment changing the lifecycle state of a document, using a quorum of its key shards. Amendments should be stored alongside the main document, so that anyone finding several generations of the backup knows which one is authoritative (see "inspect --main-document --amendment"). Sealed documents cannot be amended."#)
+        .arg(
+            Arg::new("interactive")
+                .long("interactive")
+                .help("Ask for data stored in QR codes interactively rather than scanning images.")
+                .action(ArgAction::SetTrue)
+                // TODO: Make this optional.
+                .required(true),
+        )
+        .arg(
+            Arg::new("active")
+                .long("active")
+                .help("Mark the document as the authoritative copy of the secret (undoing an earlier --superseded-by).")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("superseded-by")
+                .long("superseded-by")
+                .value_name("DOCUMENT ID")
+                .help("Mark the document as replaced by the document with the given identifier.")
+                .action(ArgAction::Set),
+        )
+        .arg(
+            Arg::new("revoked")
+                .long("revoked")
+                .help("Mark the document as no longer to be used. Revocation cannot be undone.")
+                .action(ArgAction::SetTrue),
+        )
+        .group(
+            ArgGroup::new("state")
+                .arg("active")
+                .arg("superseded-by")
+                .arg("revoked")
+                .required(true),
+        )
+        .arg(
+            Arg::new("document-id")
+                .long("document-id")
+                .value_name("DOCUMENT ID")
+                .help("Identifier of the document to amend. Key shards of any other document are rejected.")
+                .action(ArgAction::Set),
+        )
+        .arg(
+            Arg::new("sequence")
+                .long("sequence")
+                .value_name("SEQUENCE")
+                .help("Sequence number of the amendment, which must be larger than that of every earlier amendment of the document.")
+                .value_parser(clap::value_parser!(u32))
+                .action(ArgAction::Set)
+                .required(true),
+        )
+}
+
+pub(crate) fn submatch(matches: &ArgMatches) -> Result<(), Error> {
+    let state = if let Some(id) = matches.get_one::<String>("superseded-by") {
+        LifecycleState::SupersededBy(id.clone())
+    } else if matches.get_flag("revoked") {
+        LifecycleState::Revoked
+    } else {
+        LifecycleState::Active
+    };
+    let sequence = *matches
+        .get_one::<u32>("sequence")
+        .context("required --sequence argument not provided")?;
+    let document_id = matches.get_one::<String>("document-id");
+
+    let quorum = crate::read_shard_quorum(matches, document_id.map(String::as_str))?;
+    let amendment = quorum.amend(sequence, state).context("signing amendment")?;
+
+    println!(
+        "Amendment {} of document {}: {}",
+        amendment.sequence(),
+        amendment.document_id(),
+        amendment.state()
+    );
+    println!("Checksum: {}", amendment.checksum_string());
+    println!("\n{}", amendment.to_wire_multibase(ENCODING_BASE));
+    Ok(())
+}
@@ -16,27 +16,712 @@
  * along with this program.  If not, see <https://www.gnu.org/licenses/>.
  */
 
+// Must come first, so that its macros can be used by the other modules.
+#[macro_use]
+mod output;
+
+mod airgap;
+mod ceremony;
+mod checklist;
+mod fleet;
+mod holder;
+mod input;
+mod inspect;
+mod instructions;
+mod lifecycle;
+mod ocr;
+mod plugins;
+mod practice;
+mod printscale;
 mod raw;
+mod scan;
+mod secret;
+mod slices;
+mod slip39;
+mod testvectors;
 
 use std::{
     error::Error as StdError,
     fs::File,
     io,
     io::{prelude::*, BufReader, BufWriter},
+    path::{Path, PathBuf},
 };
 
 use anyhow::{anyhow, bail, ensure, Context, Error};
 use clap::{Arg, ArgAction, ArgGroup, ArgMatches, Command};
+use input::InputSource;
+use secret::SecretBuf;
+use serde::Serialize;
 
 extern crate paperback_core;
+use paperback_core::format::{AnyMainDocument, FormatVersion};
 use paperback_core::latest as paperback;
+use paperback_core::progress::{self, Stage, Tracker};
 
 use paperback::{
-    pdf::qr, wire, Backup, EncryptedKeyShard, FromWire, KeyShard, KeyShardCodewords, MainDocument,
-    NewShardKind, ToPdf, UntrustedQuorum,
+    airgap::{AirGapResponse, ExpandShardsApproval, ExpandShardsRequest},
+    algorithms, armor, bech32m,
+    ceremony::Manifest,
+    checklist::ChecklistStep,
+    codewords, constraints,
+    pdf::{
+        generate::MAIN_DOCUMENT_CODES_PER_PAGE, qr, self_test, DirectorySink, KeyShardSheet,
+        Language, OutputFormat, PaperSize, QrErrorCorrection, RenderOptions, RenderSink,
+        RenderStyle, Renderer, Symbology, ZipSink,
+    },
+    plausibility,
+    policy::PolicyParams,
+    wire, AeadAlgorithm, AeadMode, Argon2Params, Backup, BackupBuilder, Compression, CustodianInfo,
+    Dictionary, EncryptedKeyShard, FieldSize, FromWire, KeyShard, KeyShardCodewords, MainDocument,
+    NewShardKind, PaddingScheme, Policy, Quorum, RecoveryConstraint, SecretBundle, TextEncoding,
+    ToPdf, ToWire, Type, UntrustedQuorum, Warning, Warnings,
 };
 
-// paperback-cli backup [--sealed] -n <QUORUM SIZE> -k <SHARDS> INPUT
+pub(crate) fn padding_arg() -> Arg {
+    Arg::new("padding")
+        .long("padding")
+        .value_name("SCHEME")
+        .help("Pad the secret data before encryption to hide its length.")
+        .value_parser(["none", "padme", "power-of-two"])
+        .default_value("none")
+        .action(ArgAction::Set)
+}
+
+pub(crate) fn get_padding(matches: &ArgMatches) -> Result<PaddingScheme, Error> {
+    Ok(
+        match matches
+            .get_one::<String>("padding")
+            .context("required --padding argument not provided")?
+            .as_str()
+        {
+            "none" => PaddingScheme::None,
+            "padme" => PaddingScheme::Padme,
+            "power-of-two" => PaddingScheme::PowerOfTwo,
+            padding => bail!("unknown padding scheme '{}'", padding),
+        },
+    )
+}
+
+pub(crate) fn compression_arg() -> Arg {
+    Arg::new("compression")
+        .long("compression")
+        .value_name("SCHEME")
+        .help("Compress the secret data before encryption. The deflate-pem and deflate-json schemes use a built-in dictionary to compress small PEM keys and JSON exports more effectively, and auto picks whichever scheme produces the smallest output.")
+        .value_parser(["none", "deflate", "deflate-pem", "deflate-json", "auto"])
+        .default_value("none")
+        .action(ArgAction::Set)
+}
+
+pub(crate) fn get_compression(matches: &ArgMatches, secret: &[u8]) -> Result<Compression, Error> {
+    Ok(
+        match matches
+            .get_one::<String>("compression")
+            .context("required --compression argument not provided")?
+            .as_str()
+        {
+            "none" => Compression::None,
+            "deflate" => Compression::Deflate(None),
+            "deflate-pem" => Compression::Deflate(Some(Dictionary::Pem)),
+            "deflate-json" => Compression::Deflate(Some(Dictionary::Json)),
+            "auto" => Compression::best_for(secret),
+            compression => bail!("unknown compression scheme '{}'", compression),
+        },
+    )
+}
+
+pub(crate) fn cipher_arg() -> Arg {
+    Arg::new("cipher")
+        .long("cipher")
+        .value_name("ALGORITHM")
+        .help("AEAD algorithm used to encrypt the secret data in the main document. Key shards are always encrypted with chacha20-poly1305. Documents encrypted with aes-256-gcm-siv cannot be read by older versions of paperback.")
+        .value_parser(AeadAlgorithm::ALL.map(|algorithm| algorithm.name()))
+        .default_value(AeadAlgorithm::default().name())
+        .action(ArgAction::Set)
+}
+
+pub(crate) fn get_cipher(matches: &ArgMatches) -> Result<AeadAlgorithm, Error> {
+    let name = matches
+        .get_one::<String>("cipher")
+        .context("required --cipher argument not provided")?;
+    AeadAlgorithm::from_name(name).ok_or_else(|| anyhow!("unsupported cipher '{}'", name))
+}
+
+pub(crate) fn field_bits_arg() -> Arg {
+    Arg::new("field-bits")
+        .long("field-bits")
+        .value_name("BITS")
+        .help("Size of the Galois field used to shard the secret. 64-bit and 128-bit fields need half (or a quarter) as many interpolations to recover and have smaller shards for large secrets, but the shards cannot be read by older versions of paperback.")
+        .value_parser(["32", "64", "128"])
+        .default_value("32")
+        .action(ArgAction::Set)
+}
+
+pub(crate) fn get_field_size(matches: &ArgMatches) -> Result<FieldSize, Error> {
+    Ok(
+        match matches
+            .get_one::<String>("field-bits")
+            .context("required --field-bits argument not provided")?
+            .as_str()
+        {
+            "32" => FieldSize::Bits32,
+            "64" => FieldSize::Bits64,
+            "128" => FieldSize::Bits128,
+            bits => bail!("unsupported field size '{}'", bits),
+        },
+    )
+}
+
+pub(crate) fn text_encoding_arg() -> Arg {
+    Arg::new("text-encoding")
+        .long("text-encoding")
+        .value_name("ENCODING")
+        .help("Encoding of the text printed next to each QR code (for when barcode scanning fails). \"bech32m\" prints short lines which each have their own checksum, so typos are located as soon as they are entered, but documents created with it cannot be read by older versions of paperback.")
+        .value_parser(["armor", "bech32m"])
+        .default_value("armor")
+        .action(ArgAction::Set)
+}
+
+// The defaults match Argon2Params::default().
+fn shard_passphrase_args() -> [Arg; 4] {
+    [
+        Arg::new("shard-passphrase-file")
+            .long("shard-passphrase-file")
+            .value_name("PATH")
+            .help(r#"Path to a file containing a passphrase to harden every key shard with ("-" to read from stdin). Each key shard can then only be decrypted with both its codewords and the passphrase, so a stolen quorum of key shards is not enough to recover the secret without it. The passphrase must be memorised (or stored separately) -- without it, the backup cannot be recovered. Key shards created with this option cannot be read by older versions of paperback."#)
+            .action(ArgAction::Set)
+            .allow_hyphen_values(true),
+        Arg::new("argon2-memory")
+            .long("argon2-memory")
+            .value_name("KIB")
+            .help("Memory (in KiB) used by Argon2id to derive the key of each passphrase-hardened key shard.")
+            .value_parser(clap::value_parser!(u32).range(8..))
+            .default_value("65536")
+            .requires("shard-passphrase-file"),
+        Arg::new("argon2-iterations")
+            .long("argon2-iterations")
+            .value_name("N")
+            .help("Number of Argon2id passes used to derive the key of each passphrase-hardened key shard.")
+            .value_parser(clap::value_parser!(u32).range(1..))
+            .default_value("3")
+            .requires("shard-passphrase-file"),
+        Arg::new("argon2-parallelism")
+            .long("argon2-parallelism")
+            .value_name("N")
+            .help("Number of Argon2id lanes used to derive the key of each passphrase-hardened key shard.")
+            .value_parser(clap::value_parser!(u32).range(1..))
+            .default_value("4")
+            .requires("shard-passphrase-file"),
+    ]
+}
+
+fn get_shard_passphrase(matches: &ArgMatches) -> Result<Option<(String, Argon2Params)>, Error> {
+    let path = match matches.get_one::<String>("shard-passphrase-file") {
+        Some(path) => path,
+        None => return Ok(None),
+    };
+    let passphrase =
+        raw::read_oneline_file("Key Shard Passphrase", path).context("read shard passphrase")?;
+    ensure!(!passphrase.is_empty(), "shard passphrase must not be empty");
+
+    let params = Argon2Params {
+        memory_kib: *matches
+            .get_one::<u32>("argon2-memory")
+            .context("required --argon2-memory argument not provided")?,
+        iterations: *matches
+            .get_one::<u32>("argon2-iterations")
+            .context("required --argon2-iterations argument not provided")?,
+        parallelism: *matches
+            .get_one::<u32>("argon2-parallelism")
+            .context("required --argon2-parallelism argument not provided")?,
+    };
+    params.validate()?;
+    Ok(Some((passphrase, params)))
+}
+
+pub(crate) fn get_text_encoding(matches: &ArgMatches) -> Result<TextEncoding, Error> {
+    Ok(
+        match matches
+            .get_one::<String>("text-encoding")
+            .context("required --text-encoding argument not provided")?
+            .as_str()
+        {
+            "armor" => TextEncoding::Armor,
+            "bech32m" => TextEncoding::Bech32m,
+            encoding => bail!("unsupported text encoding '{}'", encoding),
+        },
+    )
+}
+
+pub(crate) fn constraint_arg() -> Arg {
+    Arg::new("constraint")
+        .long("constraint")
+        .value_name("KIND=VALUE")
+        .help("Embed a non-binding recovery constraint hint in the main document. Supported kinds are not-before=YYYY-MM-DD, review-by=YYYY-MM-DD, requires-presence=PARTY and location=PLACE.")
+        .value_parser(|s: &str| s.parse::<RecoveryConstraint>().map_err(|err| err.to_string()))
+        .action(ArgAction::Append)
+}
+
+pub(crate) fn test_run_arg() -> Arg {
+    Arg::new("test-run")
+        .long("test-run")
+        .help("Mark the backup as a test run. Test-run documents are watermarked, and cannot be mistaken for (or combined with) real backups.")
+        .action(ArgAction::SetTrue)
+}
+
+pub(crate) fn get_constraints(matches: &ArgMatches) -> Vec<RecoveryConstraint> {
+    matches
+        .get_many::<RecoveryConstraint>("constraint")
+        .map(|cs| cs.cloned().collect())
+        .unwrap_or_default()
+}
+
+pub(crate) fn print_constraints(main_document: &MainDocument) -> Warnings {
+    if let Some(created_at) = main_document.created_at() {
+        say!(
+            "Backup created: {}",
+            constraints::format_datetime(created_at)
+        );
+    }
+    for constraint in main_document.constraints() {
+        say!("Recovery constraint: {}", constraint);
+    }
+    let now = std::time::SystemTime::now();
+    let mut warnings = constraints::audit(main_document.constraints(), now);
+    warnings.extend(algorithms::audit(main_document.version(), now));
+    warnings
+}
+
+pub(crate) fn policy_args() -> [Arg; 2] {
+    [
+        Arg::new("policy")
+            .long("policy")
+            .value_name("POLICY FILE")
+            .help("Path to a JSON policy file which restricts the backup parameters.")
+            .action(ArgAction::Set),
+        Arg::new("override-policy")
+            .long("override-policy")
+            .help("Create the backup even if it violates the policy (violations are reported as warnings).")
+            .action(ArgAction::SetTrue)
+            .requires("policy"),
+    ]
+}
+
+pub(crate) fn check_policy(
+    matches: &ArgMatches,
+    params: &PolicyParams<'_>,
+) -> Result<Warnings, Error> {
+    let policy_path = match matches.get_one::<String>("policy") {
+        Some(path) => path,
+        None => return Ok(Warnings::new()),
+    };
+    let policy = std::fs::read_to_string(policy_path)
+        .with_context(|| format!("failed to read policy file '{}'", policy_path))
+        .and_then(|json| Policy::from_json(json).map_err(Error::from))
+        .with_context(|| format!("failed to load policy file '{}'", policy_path))?;
+
+    let violations = policy.check(params);
+    if !violations.is_empty() && !matches.get_flag("override-policy") {
+        bail!(
+            "backup violates policy '{}' (use --override-policy to ignore): {}",
+            policy_path,
+            violations
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join("; ")
+        );
+    }
+    Ok(violations
+        .into_iter()
+        .map(Warning::PolicyOverridden)
+        .collect())
+}
+
+pub(crate) fn shard_size_arg() -> Arg {
+    Arg::new("shard-size")
+        .long("shard-size")
+        .value_name("BYTES")
+        .help("Pad the encoded key shard data to at least this many bytes, to hide the shard size across documents.")
+        .action(ArgAction::Set)
+}
+
+pub(crate) fn parity_codes_arg() -> Arg {
+    Arg::new("parity-codes")
+        .long("parity-codes")
+        .value_name("CODES")
+        .help("Add this many parity QR codes to documents whose data is split across several QR codes (main documents and air-gap documents), so that the data can still be recovered if up to this many of the codes are torn, stained or otherwise unreadable. Documents with parity codes cannot be read by older versions of paperback.")
+        .value_parser(clap::value_parser!(usize))
+        .default_value("0")
+        .action(ArgAction::Set)
+}
+
+pub(crate) fn qr_code_args() -> [Arg; 3] {
+    [
+        Arg::new("symbology")
+            .long("symbology")
+            .value_name("SYMBOLOGY")
+            .help("Type of barcode to print data in (Data Matrix codes are read more reliably than large QR codes by some scanners, and tolerate different kinds of damage). The symbology is printed in the header of each document, and recovery reads either kind of code.")
+            .value_parser(["qr", "datamatrix"])
+            .default_value("qr")
+            .action(ArgAction::Set),
+        Arg::new("qr-error-correction")
+            .long("qr-error-correction")
+            .value_name("LEVEL")
+            .help("Error correction level of QR codes (L, M, Q or H, which can be read with roughly 7%, 15%, 25% or 30% of each code damaged). Higher levels are more robust against damage and poor printing, but hold less data in each code.")
+            .value_parser(["l", "m", "q", "h"])
+            .ignore_case(true)
+            .default_value("m")
+            .action(ArgAction::Set),
+        Arg::new("max-qr-version")
+            .long("max-qr-version")
+            .value_name("VERSION")
+            .help("Largest QR code version (from 1 to 40) used for data split across several QR codes (main documents and air-gap documents), which is split into more codes to fit. Codes of smaller versions are printed with larger modules, which are easier to print and scan. Key shards always use a single code of whatever version they need.")
+            .value_parser(clap::value_parser!(u8).range(1..=40))
+            .action(ArgAction::Set),
+    ]
+}
+
+pub(crate) fn get_symbology(matches: &ArgMatches) -> Result<Symbology, Error> {
+    Ok(
+        match matches
+            .get_one::<String>("symbology")
+            .context("required --symbology argument not provided")?
+            .as_str()
+        {
+            "qr" => Symbology::Qr,
+            "datamatrix" => Symbology::DataMatrix,
+            symbology => bail!("unknown symbology '{}'", symbology),
+        },
+    )
+}
+
+pub(crate) fn get_qr_error_correction(matches: &ArgMatches) -> Result<QrErrorCorrection, Error> {
+    Ok(
+        match matches
+            .get_one::<String>("qr-error-correction")
+            .context("required --qr-error-correction argument not provided")?
+            .to_lowercase()
+            .as_str()
+        {
+            "l" => QrErrorCorrection::Low,
+            "m" => QrErrorCorrection::Medium,
+            "q" => QrErrorCorrection::Quartile,
+            "h" => QrErrorCorrection::High,
+            level => bail!("unknown qr error correction level '{}'", level),
+        },
+    )
+}
+
+pub(crate) fn render_style_arg() -> Arg {
+    Arg::new("style")
+        .long("style")
+        .value_name("STYLE")
+        .help("Visual style of the generated documents (ink-saver uses outlines instead of solid fills, high-contrast prints everything in black).")
+        .value_parser(["standard", "ink-saver", "high-contrast"])
+        .default_value("standard")
+        .action(ArgAction::Set)
+}
+
+pub(crate) fn get_render_style(matches: &ArgMatches) -> Result<RenderStyle, Error> {
+    Ok(
+        match matches
+            .get_one::<String>("style")
+            .context("required --style argument not provided")?
+            .as_str()
+        {
+            "standard" => RenderStyle::Standard,
+            "ink-saver" => RenderStyle::InkSaver,
+            "high-contrast" => RenderStyle::HighContrast,
+            style => bail!("unknown render style '{}'", style),
+        },
+    )
+}
+
+pub(crate) fn paper_size_arg() -> Arg {
+    Arg::new("paper-size")
+        .long("paper-size")
+        .value_name("SIZE")
+        .help("Paper size to lay out the generated documents for (a4, a5, letter, or a custom WIDTHxHEIGHT in millimetres, no smaller than A6). By default, key shards are laid out for A5 and all other documents for A4.")
+        .action(ArgAction::Set)
+}
+
+pub(crate) fn language_arg() -> Arg {
+    Arg::new("language")
+        .long("language")
+        .value_name("LANGUAGE")
+        .help("Language to print the instructions and labels of main documents and key shards in (en, de, fr or es). The codes and other machine-readable data are the same in every language.")
+        .value_parser(["en", "de", "fr", "es"])
+        .default_value("en")
+        .action(ArgAction::Set)
+}
+
+pub(crate) fn get_language(matches: &ArgMatches) -> Result<Language, Error> {
+    let code = matches
+        .get_one::<String>("language")
+        .context("required --language argument not provided")?;
+    Language::from_code(code).with_context(|| format!("unknown language '{}'", code))
+}
+
+pub(crate) fn get_paper_size(matches: &ArgMatches) -> Result<Option<PaperSize>, Error> {
+    let size = match matches.get_one::<String>("paper-size") {
+        Some(size) => size,
+        None => return Ok(None),
+    };
+    let paper_size = match size.to_lowercase().as_str() {
+        "a4" => PaperSize::A4,
+        "a5" => PaperSize::A5,
+        "letter" => PaperSize::Letter,
+        custom => {
+            let (width, height) = custom
+                .split_once('x')
+                .with_context(|| format!("unknown paper size '{}'", size))?;
+            PaperSize::Custom {
+                width: width
+                    .parse()
+                    .with_context(|| format!("invalid paper width '{}'", width))?,
+                height: height
+                    .parse()
+                    .with_context(|| format!("invalid paper height '{}'", height))?,
+            }
+        }
+    };
+    if !paper_size.is_supported() {
+        bail!("paper size '{}' is smaller than A6 (105x148mm)", size);
+    }
+    Ok(Some(paper_size))
+}
+
+pub(crate) fn compact_shards_arg() -> Arg {
+    Arg::new("compact-shards")
+        .long("compact-shards")
+        .help("Lay out several key shards on each sheet of paper, separated by cut lines, rather than one key shard per page. As many key shards as fit are placed on each sheet of the --paper-size (A4 by default).")
+        .action(ArgAction::SetTrue)
+}
+
+// Encrypt the key shards of a document for printing. Every key shard is padded
+// to the same length (the largest padded length of any of them, or
+// --shard-size if that is larger), so that they can't be told apart by size.
+fn encrypt_key_shards(
+    shards: &[KeyShard],
+    passphrase: Option<(&[u8], Argon2Params)>,
+    render_options: &RenderOptions,
+) -> Result<Vec<(String, (EncryptedKeyShard, KeyShardCodewords))>, Error> {
+    let padded_len = shards
+        .iter()
+        .map(KeyShard::padded_len)
+        .chain(render_options.key_shard_size)
+        .max()
+        .unwrap_or(0);
+    shards
+        .iter()
+        .map(|s| {
+            let encrypted = match passphrase {
+                Some((passphrase, params)) => {
+                    s.encrypt_padded_with_passphrase(padded_len, passphrase, params)?
+                }
+                None => s.encrypt_padded(padded_len)?,
+            };
+            Ok((s.id(), encrypted))
+        })
+        .collect()
+}
+
+// Render the key shards of a document, either one per page or (with
+// --compact-shards) several to a sheet.
+fn render_key_shards(
+    matches: &ArgMatches,
+    sink: &mut dyn RenderSink,
+    document_id: &str,
+    shards: &[(String, (EncryptedKeyShard, KeyShardCodewords))],
+    passphrase: Option<&[u8]>,
+    render_options: &RenderOptions,
+) -> Result<(), Error> {
+    let progress = Tracker::start(Stage::Rendering, shards.len());
+    if !matches.get_flag("compact-shards") {
+        for (shard_id, (shard, codewords)) in shards {
+            let path_basename = format!("key_shard-{}-{}.pdf", document_id, shard_id);
+            match passphrase {
+                Some(passphrase) => (shard, codewords, passphrase).render_to(
+                    sink,
+                    &path_basename,
+                    render_options,
+                )?,
+                None => (shard, codewords).render_to(sink, &path_basename, render_options)?,
+            }
+            progress.step();
+        }
+        return Ok(());
+    }
+
+    let capacity = KeyShardSheet::capacity(render_options)?;
+    for (idx, chunk) in shards.chunks(capacity).enumerate() {
+        let mut sheet = KeyShardSheet::new();
+        for (_, (shard, codewords)) in chunk {
+            sheet.push(shard, codewords, passphrase);
+        }
+        sheet.render_to(
+            sink,
+            &format!("key_shards-{}-sheet{}.pdf", document_id, idx + 1),
+            render_options,
+        )?;
+        chunk.iter().for_each(|_| progress.step());
+    }
+    Ok(())
+}
+
+pub(crate) fn get_render_options(matches: &ArgMatches) -> Result<RenderOptions, Error> {
+    Ok(RenderOptions {
+        key_shard_size: matches
+            .get_one::<String>("shard-size")
+            .map(|size| size.parse())
+            .transpose()
+            .context("--shard-size argument was not an unsigned integer")?,
+        style: get_render_style(matches)?,
+        parity_codes: *matches
+            .get_one::<usize>("parity-codes")
+            .context("required --parity-codes argument not provided")?,
+        output_format: get_output_format(matches)?,
+        paper_size: get_paper_size(matches)?,
+        language: get_language(matches)?,
+        symbology: get_symbology(matches)?,
+        qr_error_correction: get_qr_error_correction(matches)?,
+        max_qr_version: matches.get_one::<u8>("max-qr-version").copied(),
+    })
+}
+
+pub(crate) fn output_args() -> [Arg; 4] {
+    [
+        Arg::new("output-dir")
+            .long("output-dir")
+            .value_name("DIR")
+            .help("Write the generated documents to this directory (defaults to the current directory).")
+            .value_parser(clap::value_parser!(PathBuf))
+            .action(ArgAction::Set),
+        Arg::new("bundle")
+            .long("bundle")
+            .value_name("ZIP")
+            .help("Write all of the generated documents into a single zip file, rather than to separate files.")
+            .value_parser(clap::value_parser!(PathBuf))
+            .conflicts_with("output-dir")
+            .action(ArgAction::Set),
+        Arg::new("output-format")
+            .long("output-format")
+            .value_name("FORMAT")
+            .help("File format of the generated documents (svg and png write one image for each page, with the same content as the PDF, and png-codes writes one PNG image for each QR code).")
+            .value_parser(["pdf", "svg", "png", "png-codes"])
+            .default_value("pdf")
+            .action(ArgAction::Set),
+        Arg::new("dpi")
+            .long("dpi")
+            .value_name("DPI")
+            .help("Resolution (in dots per inch) of PNG images generated with --output-format png or png-codes.")
+            .value_parser(clap::value_parser!(u32).range(1..))
+            .default_value("300")
+            .action(ArgAction::Set),
+    ]
+}
+
+fn get_dpi(matches: &ArgMatches) -> Result<u32, Error> {
+    matches
+        .get_one::<u32>("dpi")
+        .copied()
+        .context("required --dpi argument not provided")
+}
+
+pub(crate) fn get_output_format(matches: &ArgMatches) -> Result<OutputFormat, Error> {
+    Ok(
+        match matches
+            .get_one::<String>("output-format")
+            .context("required --output-format argument not provided")?
+            .as_str()
+        {
+            "pdf" => OutputFormat::Pdf,
+            "svg" => OutputFormat::Svg,
+            "png" => OutputFormat::Png {
+                dpi: get_dpi(matches)?,
+            },
+            "png-codes" => OutputFormat::PngCodes {
+                dpi: get_dpi(matches)?,
+            },
+            format => bail!("unknown output format '{}'", format),
+        },
+    )
+}
+
+pub(crate) fn get_output_sink(matches: &ArgMatches) -> Result<Box<dyn RenderSink>, Error> {
+    if let Some(path) = matches.get_one::<PathBuf>("bundle") {
+        let file =
+            File::create(path).with_context(|| format!("failed to create bundle {:?}", path))?;
+        return Ok(Box::new(ZipSink::new(BufWriter::new(file))));
+    }
+    Ok(Box::new(DirectorySink::new(
+        matches
+            .get_one::<PathBuf>("output-dir")
+            .map(PathBuf::as_path)
+            .unwrap_or_else(|| ".".as_ref()),
+    )))
+}
+
+pub(crate) fn no_plausibility_checks_arg() -> Arg {
+    Arg::new("no-plausibility-checks")
+        .long("no-plausibility-checks")
+        .help("Do not warn if the recovered secret data looks corrupted or malformed.")
+        .action(ArgAction::SetTrue)
+}
+
+pub(crate) fn plausibility_warnings(matches: &ArgMatches, secret: &[u8]) -> Warnings {
+    if matches.get_flag("no-plausibility-checks") {
+        return Warnings::new();
+    }
+    plausibility::check(secret)
+        .issues
+        .into_iter()
+        .map(Warning::ImplausiblePlaintext)
+        .collect()
+}
+
+pub(crate) fn backup_warnings(backup: &Backup, quorum_size: u32, num_shards: u32) -> Warnings {
+    let mut warnings = backup.warnings().clone();
+    if quorum_size > 1 && num_shards == quorum_size {
+        warnings.push(Warning::WeakParameters(
+            "every key shard is required for recovery, so losing any one of them makes the backup unrecoverable".into(),
+        ));
+    }
+    warnings
+}
+
+pub(crate) fn report_warnings(matches: &ArgMatches, warnings: &Warnings) -> Result<(), Error> {
+    for warning in warnings.iter() {
+        eprintln!("WARNING: {}", warning);
+    }
+    if matches.get_flag("deny-warnings") && !warnings.is_empty() {
+        bail!(
+            "{} warning(s) emitted and --deny-warnings was specified",
+            warnings.len()
+        );
+    }
+    Ok(())
+}
+
+// Print the data in the main document's QR codes, so that it can be entered
+// during recovery without scanning the printed codes. Nothing is printed in
+// JSON mode, so that stdout only contains the JSON result.
+fn print_main_document_codes(
+    main_document: &MainDocument,
+    render_options: &RenderOptions,
+) -> Result<(), Error> {
+    if output::is_json() {
+        return Ok(());
+    }
+    println!("Main Document:");
+    for code in main_document.qr_code_data(render_options)? {
+        println!("{}", code);
+    }
+    Ok(())
+}
+
+// paperback-cli backup [--sealed] [--test-run] [--thumbnails] [--stream] [--profile <PROFILE>] [--operator-key <KEY>]... -n <QUORUM SIZE> (-k <SHARDS> [--weights <WEIGHT>,...] | --group <MEMBER QUORUM/MEMBERS>...) (INPUT | --secret <LABEL>=<PATH>...)
 fn backup_cli() -> Command {
     Command::new("backup")
             .about(r#"Create a paperback backup."#)
@@ -44,6 +729,63 @@ fn backup_cli() -> Command {
                 .long("sealed")
                 .help("Create a sealed backup, which cannot be expanded (have new shards be created) after creation.")
                 .action(ArgAction::SetTrue))
+            .arg(padding_arg())
+            .arg(compression_arg())
+            .arg(cipher_arg())
+            .arg(field_bits_arg())
+            .arg(text_encoding_arg())
+            .args(shard_passphrase_args())
+            .arg(Arg::new("verify")
+                .long("verify")
+                .help("Before writing any documents, check that the backup can be recovered by decoding the data encoded in the QR codes and text of the main document and every key shard, and recovering the secret from it in memory. This does not check the rendered documents themselves; scan the printed documents with `paperback recover --scan` to check those.")
+                .action(ArgAction::SetTrue))
+            .arg(Arg::new("cbor-payload")
+                .long("cbor-payload")
+                .help("Serialise the main document metadata and the key shards as deterministic CBOR, so that fields added by future versions of paperback do not make the documents unreadable. Documents created with this option cannot be read by older versions of paperback.")
+                .action(ArgAction::SetTrue))
+            .arg(Arg::new("hybrid-signatures")
+                .long("hybrid-signatures")
+                .help("Sign the main document with both Ed25519 and ML-DSA-65 (a post-quantum signature scheme), so that it cannot be forged even if Ed25519 is broken. Both signatures are verified during recovery. Key shards are still only signed with Ed25519. Documents created with this option are larger, and cannot be read by older versions of paperback.")
+                .action(ArgAction::SetTrue))
+            .arg(Arg::new("segment-size")
+                .long("segment-size")
+                .value_name("BYTES")
+                .help("Encrypt the secret data in independently-decryptable segments of this size, so that large secrets can be decrypted in parallel and verified piecemeal. Documents created with this option cannot be read by older versions of paperback.")
+                .value_parser(clap::value_parser!(u32).range(1..))
+                .action(ArgAction::Set))
+            .arg(Arg::new("format-version")
+                .long("format-version")
+                .value_name("VERSION")
+                .help("Version of the paperback wire format used for the backup documents. Older versions of paperback cannot read documents created with a newer format version.")
+                .value_parser(clap::value_parser!(FormatVersion))
+                .default_value("latest")
+                .action(ArgAction::Set))
+            .arg(Arg::new("stream")
+                .long("stream")
+                .help("Encrypt the secret data as it is read rather than reading it all into memory first, for large secrets of unknown length (such as those piped through stdin). The secret is always encrypted in segments (see --segment-size), and --compression auto cannot be used.")
+                .action(ArgAction::SetTrue))
+            .arg(constraint_arg())
+            .arg(shard_size_arg())
+            .arg(parity_codes_arg())
+            .args(qr_code_args())
+            .arg(render_style_arg())
+            .arg(paper_size_arg())
+            .arg(language_arg())
+            .arg(compact_shards_arg())
+            .args(output_args())
+            .arg(Arg::new("profile")
+                .long("profile")
+                .value_name("PROFILE")
+                .help("Output profile used to render the documents. Profiles other than \"pdf\" are rendered by an external paperback-render-<PROFILE> plugin found in PATH (see the render-plugins subcommand).")
+                .default_value("pdf")
+                .action(ArgAction::Set))
+            .args(policy_args())
+            .arg(ceremony::operator_key_arg())
+            .arg(test_run_arg())
+            .arg(Arg::new("thumbnails")
+                .long("thumbnails")
+                .help("Also write a redacted thumbnail (thumbnail-<CHECKSUM>.svg) of each document, named after the document checksums listed in the manifest (which is always written when this flag is set).")
+                .action(ArgAction::SetTrue))
             .arg(Arg::new("quorum-size")
                 .short('n')
                 .long("quorum-size")
@@ -57,81 +799,430 @@ fn backup_cli() -> Command {
                 .value_name("NUM SHARDS")
                 .help("Number of shards to create (must not be smaller than --quorum-size).")
                 .action(ArgAction::Set)
-                .required(true))
+                .required_unless_present("group")
+                .conflicts_with("group"))
+            .arg(Arg::new("weights")
+                .long("weights")
+                .value_name("WEIGHT")
+                .help("Comma-separated weight of each shard, which is the number of shards it counts as towards --quorum-size (for instance, --quorum-size 3 --shards 4 --weights 1,1,1,2 lets the last shard holder recover the document with any one other holder). There must be one weight per shard, and the weights must add up to at least --quorum-size. Shards with a weight larger than 1 are larger than other shards and cannot be read by older versions of paperback.")
+                .value_parser(clap::value_parser!(u32).range(1..))
+                .value_delimiter(',')
+                .conflicts_with("group")
+                .action(ArgAction::Append))
+            .arg(Arg::new("group")
+                .long("group")
+                .value_name("MEMBER QUORUM/MEMBERS")
+                .help("Create a group of MEMBERS shards, any MEMBER QUORUM of which together count as one shard towards --quorum-size (for instance, --quorum-size 2 --group 3/5 --group 2/3 --group 1/1 requires any two of 3-of-5, 2-of-3 and 1-of-1 groups). Can be specified multiple times, and replaces --shards. Grouped shards cannot be read by older versions of paperback, and cannot be expanded or re-created.")
+                .value_parser(parse_group)
+                .action(ArgAction::Append))
+            .arg(Arg::new("custodians")
+                .long("custodians")
+                .value_name("JSON PATH")
+                .help(r#"Path to a JSON list of details about the holder of each key shard (in the order the key shards are created), such as [{"name": "alice", "contact": "alice@example.com", "note": "..."}, ...]. The details are printed on (and signed as part of) each key shard. Key shards with details cannot be read by older versions of paperback."#)
+                .action(ArgAction::Set))
+            .arg(Arg::new("secret")
+                .long("secret")
+                .value_name("LABEL=PATH")
+                .help("Add the secret data in the file at PATH to the backup under LABEL. Can be specified multiple times to store several independent secrets (such as a disk encryption key and a password manager export) in one backup, which are all recovered together. Replaces INPUT. Documents with labelled secrets cannot be read by older versions of paperback.")
+                .value_parser(parse_secret)
+                .conflicts_with_all(["INPUT", "input", "stream"])
+                .action(ArgAction::Append))
+            .arg(input::input_arg())
+            .arg(input::max_input_size_arg())
+            .arg(input::stream_threshold_arg())
             .arg(Arg::new("INPUT")
-                .help(r#"Path to file containing secret data to backup ("-" to read from stdin)."#)
+                .help(r#"Path to file containing secret data to backup ("-" to read from stdin, or "fd:N" to read from an inherited file descriptor, as with --input)."#)
                 .action(ArgAction::Set)
                 .allow_hyphen_values(true)
-                .required(true)
+                .required_unless_present_any(["secret", "input"])
                 .index(1))
 }
 
+fn parse_secret(secret: &str) -> Result<(String, InputSource), String> {
+    let (label, path) = secret
+        .split_once('=')
+        .ok_or_else(|| "secret must be of the form <LABEL>=<PATH>".to_string())?;
+    if path.is_empty() {
+        return Err("secret path must not be empty".into());
+    }
+    Ok((label.to_string(), path.parse()?))
+}
+
+pub(crate) fn parse_group(group: &str) -> Result<(u32, u32), String> {
+    let (member_quorum_size, num_members) = group
+        .split_once('/')
+        .ok_or_else(|| "group must be of the form <MEMBER QUORUM>/<MEMBERS>".to_string())?;
+    let member_quorum_size: u32 = member_quorum_size
+        .parse()
+        .map_err(|err| format!("invalid member quorum size: {}", err))?;
+    let num_members: u32 = num_members
+        .parse()
+        .map_err(|err| format!("invalid number of members: {}", err))?;
+    if member_quorum_size == 0 || num_members < member_quorum_size {
+        return Err("member quorum size must be between 1 and the number of members".into());
+    }
+    Ok((member_quorum_size, num_members))
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct ShardResult {
+    id: String,
+    checksum: String,
+}
+
+// Result of backup, for --format json.
+#[derive(Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct BackupResult {
+    document_id: String,
+    checksum: String,
+    quorum_size: u32,
+    shards: Vec<ShardResult>,
+    // Whether the backup passed its self-test (--verify).
+    verified: bool,
+    bundle: Option<PathBuf>,
+    // Paths of the documents written (within the bundle, if there is one).
+    files: Vec<PathBuf>,
+    warnings: Vec<String>,
+}
+
 fn backup(matches: &ArgMatches) -> Result<(), Error> {
     let sealed = matches.get_flag("sealed");
+    let padding = get_padding(matches)?;
+    let render_options = get_render_options(matches)?;
+    let shard_passphrase = get_shard_passphrase(matches)?;
     let quorum_size: u32 = matches
         .get_one::<String>("quorum-size")
         .context("required --quorum-size argument not provided")?
         .parse()
         .context("--quorum-size argument was not an unsigned integer")?;
-    let num_shards: u32 = matches
-        .get_one::<String>("shards")
-        .context("required --quorum-size argument not provided")?
-        .parse()
-        .context("--shards argument was not an unsigned integer")?;
-    let input_path = matches
-        .get_one::<String>("INPUT")
-        .context("required INPUT argument not provided")?;
-
-    let (mut stdin_reader, mut file_reader);
-    let input: &mut dyn Read = if input_path == "-" {
-        stdin_reader = io::stdin();
-        &mut stdin_reader
-    } else {
-        file_reader = File::open(&input_path)
-            .with_context(|| format!("failed to open secret data file '{}'", input_path))?;
-        &mut file_reader
+    ensure!(quorum_size > 0, "--quorum-size must be at least 1");
+    let groups = matches
+        .get_many::<(u32, u32)>("group")
+        .map(|groups| groups.copied().collect::<Vec<_>>())
+        .unwrap_or_default();
+    // Each group counts as one shard towards the quorum size.
+    let num_shards: u32 = match groups.len() {
+        0 => matches
+            .get_one::<String>("shards")
+            .context("required --shards argument not provided")?
+            .parse()
+            .context("--shards argument was not an unsigned integer")?,
+        n => n.try_into().context("too many --group arguments")?,
+    };
+    let weights = match matches.get_many::<u32>("weights") {
+        None => vec![1; num_shards as usize],
+        Some(weights) => weights.copied().collect::<Vec<_>>(),
+    };
+    ensure!(
+        weights.len() == num_shards as usize,
+        "--weights has {} weights but --shards is {}",
+        weights.len(),
+        num_shards
+    );
+    let total_weight = weights.iter().map(|&w| u64::from(w)).sum::<u64>();
+    ensure!(
+        total_weight >= quorum_size.into(),
+        "--weights add up to {} which is smaller than --quorum-size {}",
+        total_weight,
+        quorum_size
+    );
+    let custodians = match matches.get_one::<String>("custodians") {
+        None => Vec::new(),
+        Some(path) => std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read custodian list '{}'", path))
+            .and_then(|json| CustodianInfo::list_from_json(json).map_err(Error::from))
+            .with_context(|| format!("failed to load custodian list '{}'", path))?,
+    };
+    let total_shards = match groups.len() {
+        0 => u64::from(num_shards),
+        _ => groups.iter().map(|&(_, n)| u64::from(n)).sum(),
+    };
+    ensure!(
+        custodians.len() as u64 <= total_shards,
+        "--custodians lists {} custodians but only {} key shards will be created",
+        custodians.len(),
+        total_shards
+    );
+    let profile = matches
+        .get_one::<String>("profile")
+        .map(String::as_str)
+        .unwrap_or("pdf");
+    // Look up the plugin before doing any work, so that a missing plugin
+    // doesn't waste a freshly-generated backup.
+    let plugin = match profile {
+        "pdf" => None,
+        profile => Some(plugins::Plugin::find(profile)?),
     };
-    let mut buffer_input = BufReader::new(input);
 
-    let mut secret = Vec::new();
-    buffer_input
-        .read_to_end(&mut secret)
-        .with_context(|| format!("failed to read secret data from '{}'", input_path))?;
+    let cipher = get_cipher(matches)?;
+    let policy_warnings = check_policy(
+        matches,
+        &PolicyParams {
+            quorum_size,
+            num_shards,
+            cipher_suite: cipher.name(),
+            output_profile: profile,
+        },
+    )?;
 
-    let backup = if sealed {
-        Backup::new_sealed(quorum_size, &secret)
+    let format_version = *matches
+        .get_one::<FormatVersion>("format-version")
+        .context("required --format-version argument not provided")?;
+    let mut builder = match format_version {
+        FormatVersion::V0 => BackupBuilder::new(quorum_size),
+    };
+    builder
+        .sealed(sealed)
+        .padding(padding)
+        .aead_algorithm(cipher)
+        .field_size(get_field_size(matches)?)
+        .text_encoding(get_text_encoding(matches)?)
+        .hybrid_signatures(matches.get_flag("hybrid-signatures"))
+        .cbor_payload(matches.get_flag("cbor-payload"))
+        .test_run(matches.get_flag("test-run"))
+        .created_at(std::time::SystemTime::now());
+    if let Some(&segment_size) = matches.get_one::<u32>("segment-size") {
+        builder.aead_mode(AeadMode::Chunked { segment_size });
+    }
+    for constraint in get_constraints(matches) {
+        builder.constraint(constraint);
+    }
+    // The secret is kept (unless it is streamed) so that --verify can check
+    // the recovered secret against it.
+    let max_input_size = input::get_max_input_size(matches)?;
+    let secret_sources = matches.get_many::<(String, InputSource)>("secret");
+    let (backup, secret) = if let Some(secrets) = secret_sources {
+        let mut bundle = SecretBundle::new();
+        for (label, source) in secrets {
+            let secret = source.read(max_input_size)?;
+            bundle
+                .push(label.as_str(), secret.to_vec())
+                .with_context(|| format!("failed to add secret {:?}", label))?;
+        }
+        let encoded = bundle.to_wire();
+        let backup = builder
+            .compression(get_compression(matches, &encoded)?)
+            .build_bundle(&bundle)?;
+        (backup, Some(SecretBuf::new(encoded)))
     } else {
-        Backup::new(quorum_size, &secret)
-    }?;
+        let source = input::get_input(matches)?;
+        let auto_compression =
+            matches.get_one::<String>("compression").map(String::as_str) == Some("auto");
+        // Large files are streamed automatically, unless the whole secret is
+        // needed to pick the compression scheme.
+        let stream_threshold = input::get_stream_threshold(matches)?;
+        let large_input = !auto_compression && source.is_larger_than(stream_threshold);
+        if large_input && !matches.get_flag("stream") {
+            say!(
+                "Streaming secret data from {} as it is larger than {} bytes (see --stream-threshold).",
+                source,
+                stream_threshold
+            );
+        }
+        if matches.get_flag("stream") || large_input {
+            ensure!(
+                !auto_compression,
+                "--compression auto needs the whole secret data and cannot be used with --stream"
+            );
+            let backup = builder
+                .compression(get_compression(matches, &[])?)
+                .build_from_reader(BufReader::new(source.open(max_input_size)?))
+                .with_context(|| format!("failed to back up secret data from {}", source))?;
+            (backup, None)
+        } else {
+            let secret = source.read(max_input_size)?;
+            let backup = builder
+                .compression(get_compression(matches, &secret)?)
+                .build(&secret)?;
+            (backup, Some(secret))
+        }
+    };
     let main_document = backup.main_document().clone();
-    let shards = (0..num_shards)
-        .map(|_| backup.next_shard().unwrap())
-        .map(|s| (s.id(), s.encrypt().unwrap()))
-        .collect::<Vec<_>>();
-
-    main_document
-        .to_pdf()?
-        .save(&mut BufWriter::new(File::create(format!(
-            "main_document-{}.pdf",
-            main_document.id()
-        ))?))?;
-
-    for (shard_id, (shard, codewords)) in shards {
-        (shard, codewords)
-            .to_pdf()?
-            .save(&mut BufWriter::new(File::create(format!(
-                "key_shard-{}-{}.pdf",
-                main_document.id(),
-                shard_id
-            ))?))?;
+
+    let mut warnings = policy_warnings;
+    warnings.extend(backup_warnings(
+        &backup,
+        quorum_size,
+        total_weight.try_into().unwrap_or(u32::MAX),
+    ));
+    warnings.extend(main_document.warnings(&render_options));
+    report_warnings(matches, &warnings)?;
+
+    // Estimate the effort of recovery, so owners can weigh it against the
+    // chosen quorum size and shard padding.
+    let layout = Renderer::preview(&backup, &render_options)?;
+    let effort = layout.recovery_effort();
+    say!(
+        "Recovering this backup needs {} QR scans, or about {:.0} minutes to type {} characters by hand.",
+        effort.qr_scans,
+        effort.transcription_minutes().ceil(),
+        effort.transcription_chars
+    );
+
+    let shards = if groups.is_empty() {
+        weights
+            .into_iter()
+            .map(|weight| backup.next_weighted_shard(weight))
+            .collect::<Result<Vec<_>, _>>()?
+    } else {
+        let mut shards = Vec::new();
+        for (member_quorum_size, num_members) in groups {
+            let group = backup.next_group(member_quorum_size)?;
+            say!(
+                "Group {}: {} of {} members required.",
+                group.group().id(),
+                member_quorum_size,
+                num_members
+            );
+            shards.extend(backup.next_group_shards(&group, num_members)?);
+        }
+        shards
     }
+    .into_iter()
+    .enumerate()
+    .map(|(idx, shard)| match custodians.get(idx) {
+        Some(custodian) => backup.assign_custodian(&shard, custodian.clone()),
+        None => Ok(shard),
+    })
+    .collect::<Result<Vec<_>, _>>()?;
+    let shards = encrypt_key_shards(
+        &shards,
+        shard_passphrase
+            .as_ref()
+            .map(|(passphrase, params)| (passphrase.as_bytes(), *params)),
+        &render_options,
+    )?;
 
-    Ok(())
+    if matches.get_flag("verify") {
+        let report = self_test(
+            &main_document,
+            &shards
+                .iter()
+                .map(|(_, shard)| shard.clone())
+                .collect::<Vec<_>>(),
+            shard_passphrase
+                .as_ref()
+                .map(|(passphrase, _)| passphrase.as_bytes()),
+            secret.as_deref(),
+            &render_options,
+        )
+        .context("backup failed its self-test -- no documents were written")?;
+        say!(
+            "Self-test passed: recovered {} bytes from document {} using key shards {}.",
+            report.secret_len,
+            report.document_id,
+            report.shard_ids.join(" ")
+        );
+    }
+
+    let mut manifest = Manifest::new(
+        &main_document,
+        &shards
+            .iter()
+            .map(|(_, (s, _))| s.clone())
+            .collect::<Vec<_>>(),
+    );
+    let result = BackupResult {
+        document_id: main_document.id(),
+        checksum: main_document.checksum_string(),
+        quorum_size,
+        shards: shards
+            .iter()
+            .map(|(shard_id, (shard, _))| ShardResult {
+                id: shard_id.clone(),
+                checksum: shard.checksum_string(),
+            })
+            .collect(),
+        verified: matches.get_flag("verify"),
+        bundle: matches.get_one::<PathBuf>("bundle").cloned(),
+        files: Vec::new(),
+        warnings: warnings.iter().map(ToString::to_string).collect(),
+    };
+    let mut sink = output::RecordingSink::new(
+        get_output_sink(matches)?,
+        match matches.get_one::<PathBuf>("bundle") {
+            Some(_) => None,
+            None => Some(
+                matches
+                    .get_one::<PathBuf>("output-dir")
+                    .cloned()
+                    .unwrap_or_else(|| ".".into()),
+            ),
+        },
+    );
+    let thumbnails = matches.get_flag("thumbnails");
+    if ceremony::sign_manifest(matches, &mut manifest)? || thumbnails {
+        sink.write_artifact(
+            &format!("manifest-{}.txt", main_document.id()),
+            format!("{}\n", manifest.to_wire_multibase(raw::ENCODING_BASE)).as_bytes(),
+        )?;
+    }
+    if thumbnails {
+        // Thumbnails are named after the checksums in the manifest, so that
+        // inventory tools can find the thumbnail for each listed document.
+        sink.write_artifact(
+            &format!("thumbnail-{}.svg", manifest.document_checksum_string()),
+            layout.main_document_thumbnail().to_svg().as_bytes(),
+        )?;
+        let key_shard_svg = layout.key_shard_thumbnail().to_svg();
+        for chksum in manifest.shard_checksum_strings() {
+            sink.write_artifact(
+                &format!("thumbnail-{}.svg", chksum),
+                key_shard_svg.as_bytes(),
+            )?;
+        }
+    }
+
+    if let Some(plugin) = plugin {
+        plugin.render_main_document(&main_document, &mut sink)?;
+        plugin.render_manifest(&manifest, &main_document, &mut sink)?;
+        for (shard_id, (shard, codewords)) in &shards {
+            plugin.render_key_shard(&main_document, shard_id, shard, codewords, &mut sink)?;
+        }
+        sink.finish()?;
+        return output::emit(&BackupResult {
+            files: sink.artifacts().to_vec(),
+            ..result
+        });
+    }
+
+    print_main_document_codes(&main_document, &render_options)?;
+    main_document.render_to(
+        &mut sink,
+        &format!("main_document-{}.pdf", main_document.id()),
+        &render_options,
+    )?;
+
+    // The printed manifest includes a reference to the archived format
+    // specification, to help with recovery long after paperback is gone.
+    manifest.render_to(
+        &mut sink,
+        &format!("manifest-{}.pdf", main_document.id()),
+        &render_options,
+    )?;
+
+    render_key_shards(
+        matches,
+        &mut sink,
+        &main_document.id(),
+        &shards,
+        shard_passphrase
+            .as_ref()
+            .map(|(passphrase, _)| passphrase.as_bytes()),
+        &render_options,
+    )?;
+    sink.finish()?;
+
+    output::emit(&BackupResult {
+        files: sink.artifacts().to_vec(),
+        ..result
+    })
 }
 
 fn read_multiline<S: AsRef<str>>(prompt: S) -> Result<String, Error> {
-    print!("{}: ", prompt.as_ref());
-    io::stdout().flush()?;
+    output::prompt(format_args!("{}: ", prompt.as_ref()))?;
 
     let buffer_stdin = BufReader::new(io::stdin());
     Ok(buffer_stdin
@@ -142,40 +1233,273 @@ fn read_multiline<S: AsRef<str>>(prompt: S) -> Result<String, Error> {
         .join("\n"))
 }
 
+enum MultilineData {
+    Armored(Vec<u8>),
+    Text(String),
+}
+
+// Like read_multiline, except that armored data is decoded as each line is
+// entered. A mistyped line is reported (by line number) as soon as it has been
+// entered, and can be re-entered without having to start over.
+fn read_multiline_data<S: AsRef<str>>(prompt: S) -> Result<MultilineData, Error> {
+    output::prompt(format_args!("{}: ", prompt.as_ref()))?;
+
+    let mut lines = io::stdin().lock().lines();
+    let mut next_line = || -> Result<Option<String>, Error> {
+        Ok(lines
+            .next()
+            .transpose()
+            .map_err(|err| anyhow!("failed to read data: {}", err))?
+            .filter(|line| !line.is_empty()))
+    };
+
+    let first_line = match next_line()? {
+        Some(line) if armor::is_armored(&line) => line,
+        first_line => {
+            let mut text = first_line.into_iter().collect::<Vec<_>>();
+            while let Some(line) = next_line()? {
+                text.push(line);
+            }
+            return Ok(MultilineData::Text(text.join("\n")));
+        }
+    };
+
+    let mut decoder = armor::ArmorDecoder::new();
+    let mut data = Vec::new();
+    let mut line = Some(first_line);
+    while let Some(text) = line {
+        match decoder.push_line(text) {
+            Ok(mut line_data) => data.append(&mut line_data),
+            Err(err) => {
+                output::prompt(format_args!(
+                    "{}. Re-enter the line (or enter an empty line to give up): ",
+                    err
+                ))?;
+            }
+        }
+        line = next_line()?;
+    }
+    decoder.finish().context("failed to decode armored data")?;
+    Ok(MultilineData::Armored(data))
+}
+
 fn read_multibase<S: AsRef<str>, T: FromWire>(prompt: S) -> Result<T, Error> {
-    T::from_wire_multibase(
-        wire::multibase_strip(read_multiline(prompt)?)
-            .map_err(|err| anyhow!("failed to strip out non-multibase characters: {}", err))?,
-    )
-    .map_err(|err| anyhow!("failed to parse data: {}", err))
+    let text = match read_multiline_data(prompt)? {
+        MultilineData::Armored(data) => {
+            return T::from_wire(data).map_err(|err| anyhow!("failed to parse data: {}", err))
+        }
+        MultilineData::Text(text) => text,
+    };
+    if wire::words::is_words(&text) {
+        return T::from_wire_words(&text).map_err(|err| anyhow!("failed to parse data: {}", err));
+    }
+    if bech32m::is_bech32m(&text) {
+        let data = bech32m::decode(&text).context("failed to decode bech32m data")?;
+        return T::from_wire(data).map_err(|err| anyhow!("failed to parse data: {}", err));
+    }
+    let text = wire::multibase_strip(text)
+        .map_err(|err| anyhow!("failed to strip out non-multibase characters: {}", err))?;
+    T::from_wire_multibase(&text).map_err(|err| {
+        // Point at the character where the data stopped making sense, so that
+        // typos can be found without re-reading the whole string.
+        let context = err
+            .position()
+            .map(|position| {
+                let start = position.saturating_sub(20);
+                let snippet = text.chars().skip(start).take(41).collect::<String>();
+                format!("\n    {}\n    {}^", snippet, " ".repeat(position - start))
+            })
+            .unwrap_or_default();
+        anyhow!("failed to parse data: {}{}", err, context)
+    })
 }
 
 fn read_codewords<S: AsRef<str>>(prompt: S) -> Result<KeyShardCodewords, Error> {
-    Ok(read_multiline(prompt)?
-        .split_whitespace()
-        .map(|s| s.to_owned())
-        .collect::<Vec<_>>())
+    loop {
+        let codewords = read_multiline(prompt.as_ref())?
+            .split_whitespace()
+            .map(|s| s.to_owned())
+            .collect::<Vec<_>>();
+        if codewords::is_valid(&codewords) {
+            return Ok(codewords);
+        }
+
+        let suggestions = codewords::suggest(&codewords);
+        if suggestions.is_empty() {
+            say!("Codewords are invalid (mistyped or missing words). Please try again.");
+            continue;
+        }
+        say!("Codewords are invalid. Did you mean:");
+        for (idx, suggestion) in suggestions.iter().enumerate() {
+            say!("  [{}] {}", idx + 1, suggestion.join(" "));
+        }
+        output::prompt(format_args!(
+            "Choose a suggestion [1-{}], or press enter to type the codewords again: ",
+            suggestions.len()
+        ))?;
+        let mut choice = String::new();
+        io::stdin().read_line(&mut choice)?;
+        if let Some(suggestion) = choice
+            .trim()
+            .parse::<usize>()
+            .ok()
+            .and_then(|idx| idx.checked_sub(1))
+            .and_then(|idx| suggestions.get(idx))
+        {
+            return Ok(suggestion.clone());
+        }
+    }
+}
+
+// Describe the codes of a main document which have not been entered yet,
+// numbered from 1 and grouped by the page they are printed on.
+pub(crate) fn describe_missing_codes(joiner: &qr::Joiner) -> String {
+    let mut pages: Vec<(usize, Vec<String>)> = vec![];
+    for idx in joiner.missing() {
+        let page = idx / MAIN_DOCUMENT_CODES_PER_PAGE + 1;
+        match pages.last_mut() {
+            Some((last, codes)) if *last == page => codes.push((idx + 1).to_string()),
+            _ => pages.push((page, vec![(idx + 1).to_string()])),
+        }
+    }
+    pages
+        .iter()
+        .map(|(page, codes)| format!("{} (page {})", codes.join(" "), page))
+        .collect::<Vec<_>>()
+        .join(", ")
 }
 
-fn read_multibase_qr<S: AsRef<str>, T: FromWire>(prompt: S) -> Result<T, Error> {
+pub(crate) fn read_multibase_qr<S: AsRef<str>, T: FromWire>(prompt: S) -> Result<T, Error> {
     let prompt = prompt.as_ref();
     let mut joiner = qr::Joiner::new();
     while !joiner.complete() {
-        let part: qr::Part = read_multibase(format!(
-            "{} ({} codes remaining)",
-            prompt,
-            match joiner.remaining() {
-                None => "unknown number of".to_string(),
-                Some(n) => n.to_string(),
-            }
-        ))?;
+        let part: qr::Part = read_multibase(match joiner.remaining() {
+            None => format!("{} (unknown number of codes remaining)", prompt),
+            Some(n) => format!(
+                "{} ({} codes remaining, missing codes {})",
+                prompt,
+                n,
+                describe_missing_codes(&joiner)
+            ),
+        })?;
         joiner.add_part(part)?;
     }
     T::from_wire(joiner.combine_parts()?)
         .map_err(|err| anyhow!("parse inner qr code data: {}", err))
 }
 
-// paperback-cli recover --interactive
+// Decrypt a key shard with its codewords, asking for the passphrase (with
+// prompt) if the shard was hardened with one.
+pub(crate) fn decrypt_key_shard(
+    encrypted_shard: &EncryptedKeyShard,
+    codewords: &KeyShardCodewords,
+    prompt: &str,
+) -> Result<KeyShard, Error> {
+    match encrypted_shard.is_passphrase_protected() {
+        true => {
+            let passphrase = raw::read_oneline_file(prompt, "-").context("read passphrase")?;
+            encrypted_shard.decrypt_with_passphrase(codewords, passphrase.as_bytes())
+        }
+        false => encrypted_shard.decrypt(codewords),
+    }
+    .map_err(|err| anyhow!(err)) // TODO: Fix this once FromWire supports non-String errors.
+}
+
+// Read and decrypt the next key shard for quorum interactively, out of the
+// num_shards key shards expected. Key shards are checked as soon as they have
+// been entered, and if a key shard cannot be decrypted or doesn't belong in the
+// quorum the reason is reported so that it can be re-entered (or replaced).
+pub(crate) fn read_key_shard(
+    quorum: &UntrustedQuorum,
+    num_shards: u32,
+) -> Result<(EncryptedKeyShard, KeyShard), Error> {
+    loop {
+        let encrypted_shard: EncryptedKeyShard = read_multibase(format!(
+            "Quorum contains [{}] key shards.\nEnter key shard {} of {}",
+            quorum
+                .untrusted_shards()
+                .map(KeyShard::id)
+                .collect::<Vec<_>>()
+                .join(" "),
+            quorum.num_untrusted_shards() + 1,
+            num_shards
+        ))?;
+        if let Some(shard) = unlock_key_shard(quorum, &encrypted_shard)? {
+            return Ok((encrypted_shard, shard));
+        }
+        say!("Please enter a different key shard.");
+    }
+}
+
+// Decrypt the (already entered or scanned) next key shard for quorum, asking
+// for its codewords interactively. Returns None (after reporting the reason)
+// if the key shard doesn't belong in the quorum.
+fn unlock_key_shard(
+    quorum: &UntrustedQuorum,
+    encrypted_shard: &EncryptedKeyShard,
+) -> Result<Option<KeyShard>, Error> {
+    let idx = quorum.num_untrusted_shards() as u32;
+    // TODO: Ask the user to input the checksum...
+    say!(
+        "Key shard {} checksum: {}",
+        idx + 1,
+        encrypted_shard.checksum_string()
+    );
+
+    let shard = loop {
+        let codewords = read_codewords(format!("Enter key shard {} codewords", idx + 1))?;
+        match decrypt_key_shard(
+            encrypted_shard,
+            &codewords,
+            &format!("Enter key shard {} passphrase", idx + 1),
+        ) {
+            Ok(shard) => break shard,
+            Err(err) => say!(
+                "Key shard {} could not be decrypted ({}). Check that the codewords (and passphrase, if any) were entered correctly and try again.",
+                idx + 1,
+                err
+            ),
+        }
+    };
+
+    match quorum.check_shard(&shard) {
+        Ok(()) => {
+            say!("Loaded key shard {}.", shard.id());
+            Ok(Some(shard))
+        }
+        Err(rejection) => {
+            say!(
+                "Key shard {} cannot be used because {}.",
+                shard.id(),
+                rejection
+            );
+            Ok(None)
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct SecretResult {
+    // None for single-secret documents.
+    label: Option<String>,
+    size: usize,
+    path: PathBuf,
+}
+
+// Result of recover, for --format json.
+#[derive(Default, Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct RecoverResult {
+    document_id: String,
+    checksum: String,
+    quorum_size: u32,
+    shards: Vec<ShardResult>,
+    secrets: Vec<SecretResult>,
+    warnings: Vec<String>,
+}
+
+// paperback-cli recover (--interactive | --scan <IMAGE>... | --camera [<INDEX>]) [--extra-shards <N>]
 fn recover_cli() -> Command {
     Command::new("recover")
         .about(r#"Recover a paperback backup."#)
@@ -184,12 +1508,24 @@ fn recover_cli() -> Command {
                 .long("interactive")
                 .help("Ask for data stored in QR codes interactively rather than scanning images.")
                 .action(ArgAction::SetTrue)
-                // TODO: Make this optional.
-                .required(true),
+                .required_unless_present_any(["scan", "camera"]),
+        )
+        .arg(scan::scan_arg())
+        .args(scan::camera_args())
+        .arg(no_plausibility_checks_arg())
+        .arg(checklist::checklist_arg())
+        .arg(
+            Arg::new("extra-shards")
+                .long("extra-shards")
+                .value_name("NUM SHARDS")
+                .help("Number of key shards to enter in addition to the quorum size. The extra shards are used to cross-verify the others, detecting (and excluding) forged or corrupted shards.")
+                .value_parser(clap::value_parser!(u32))
+                .default_value("0")
+                .action(ArgAction::Set),
         )
         .arg(
             Arg::new("OUTPUT")
-                .help(r#"Path to write recovered secret data to ("-" to write to stdout)."#)
+                .help(r#"Path to write recovered secret data to ("-" to write to stdout). If the backup contains several labelled secrets, this is a directory to write each secret to (as a file named after its label), and secrets written to stdout are each preceded by a line containing their label."#)
                 .action(ArgAction::Set)
                 .allow_hyphen_values(true)
                 .required(true)
@@ -198,54 +1534,99 @@ fn recover_cli() -> Command {
 }
 
 fn recover(matches: &ArgMatches) -> Result<(), Error> {
-    let interactive = matches.get_flag("interactive");
-    ensure!(interactive, "PDF scanning not yet implemented");
+    let extra_shards = *matches
+        .get_one::<u32>("extra-shards")
+        .context("required --extra-shards argument not provided")?;
+    let scanned = match (scan::get_camera(matches), scan::get_scan_paths(matches)) {
+        (Some((index, timeout)), _) => scan::scan_camera(index, extra_shards, timeout)?,
+        (None, paths) if paths.is_empty() => scan::Scanned::default(),
+        (None, paths) => scan::scan_images(&paths)?,
+    };
     let output_path = matches
         .get_one::<String>("OUTPUT")
         .context("required OUTPUT argument not provided")?;
+    ensure!(
+        !(output::is_json() && output_path == "-"),
+        "--format json cannot be used when writing the recovered secret data to stdout"
+    );
+    let mut checklist = checklist::Checklist::new(matches);
 
-    let main_document: MainDocument = read_multibase_qr("Enter a main document code")?;
+    let main_document: AnyMainDocument = match scanned.main_document {
+        Some(main_document) => {
+            say!("Step 1 of 3: loaded the main document from the scanned images.");
+            main_document.into()
+        }
+        None => {
+            say!("Step 1 of 3: enter the main document. Scan (or type in) each of its QR codes, in any order, followed by an empty line.");
+            read_multibase_qr("Enter a main document code")?
+        }
+    };
+    // The rest of recovery depends on the format version of the main document.
+    let main_document: MainDocument = match main_document {
+        AnyMainDocument::V0(main_document) => main_document,
+    };
+    ensure!(
+        !matches!(
+            Type::from(main_document.clone()),
+            Type::ForgedMainDocument(_)
+        ),
+        "the main document's signature is invalid, so it has been forged or corrupted -- do not trust it"
+    );
     let quorum_size = main_document.quorum_size();
     // TODO: Ask the user to input the checksum...
-    println!(
+    say!(
         "Main document checksum: {}",
         main_document.checksum_string()
     );
+    checklist.record(ChecklistStep::DocumentPresented {
+        id: main_document.id(),
+        checksum: main_document.checksum_string(),
+    })?;
 
-    println!("Document ID: {}", main_document.id());
-    println!("{} key shards required.", quorum_size);
+    say!("Document ID: {}", main_document.id());
+    say!("{} key shards required.", quorum_size);
+    let is_bundle = main_document.is_bundle();
+    let constraint_warnings = print_constraints(&main_document);
+    let num_shards = quorum_size.saturating_add(extra_shards);
 
+    let mut result = RecoverResult {
+        document_id: main_document.id(),
+        checksum: main_document.checksum_string(),
+        quorum_size,
+        ..Default::default()
+    };
+    say!("Step 2 of 3: enter {} key shards. For each key shard, scan (or type in) its QR code followed by an empty line, and then type in its codewords.", num_shards);
     let mut quorum = UntrustedQuorum::new();
     quorum.main_document(main_document);
-    while quorum.num_untrusted_shards() < quorum_size as usize {
-        let idx = quorum.num_untrusted_shards() as u32;
-        let encrypted_shard: EncryptedKeyShard = read_multibase(format!(
-            "Quorum contains [{}] key shards.\nEnter key shard {} of {}",
-            quorum
-                .untrusted_shards()
-                .map(KeyShard::id)
-                .collect::<Vec<_>>()
-                .join(" "),
-            idx + 1,
-            quorum_size
-        ))?;
-        // TODO: Ask the user to input the checksum...
-        println!(
-            "Key shard {} checksum: {}",
-            idx + 1,
-            encrypted_shard.checksum_string()
-        );
-
-        let codewords = read_codewords(format!("Enter key shard {} codewords", idx + 1))?;
-        let shard = encrypted_shard
-            .decrypt(&codewords)
-            .map_err(|err| anyhow!(err)) // TODO: Fix this once FromWire supports non-String errors.
-            .with_context(|| format!("decrypting key shard {}", idx + 1))?;
-
-        println!("Loaded key shard {}.", shard.id());
+    // Scanned key shards are used first, and any remaining key shards are
+    // asked for interactively.
+    let mut scanned_shards = scanned.shards.into_iter();
+    while quorum.untrusted_weight() < num_shards {
+        if quorum.num_untrusted_shards() > 0 {
+            say!(
+                "{} more key shards needed.",
+                num_shards - quorum.untrusted_weight()
+            );
+        }
+        let (encrypted_shard, shard) = match scanned_shards.next() {
+            Some(encrypted_shard) => match unlock_key_shard(&quorum, &encrypted_shard)? {
+                Some(shard) => (encrypted_shard, shard),
+                None => continue,
+            },
+            None => read_key_shard(&quorum, num_shards)?,
+        };
+        checklist.record(ChecklistStep::ShardPresented {
+            id: shard.id(),
+            checksum: encrypted_shard.checksum_string(),
+        })?;
+        result.shards.push(ShardResult {
+            id: shard.id(),
+            checksum: encrypted_shard.checksum_string(),
+        });
         quorum.push_shard(shard);
     }
 
+    say!("Step 3 of 3: recovering the secret.");
     let quorum = quorum.validate().map_err(|err| {
         anyhow!(
             "quorum failed to validate -- possible forgery! {}; groupings: {:?}",
@@ -253,29 +1634,140 @@ fn recover(matches: &ArgMatches) -> Result<(), Error> {
             err.as_groups()
         )
     })?;
+    checklist.record(ChecklistStep::QuorumValidated)?;
 
-    let secret = quorum
-        .recover_document()
-        .context("recovering secret data")?;
-
-    let (mut stdout_writer, mut file_writer);
-    let output_file: &mut dyn Write = if output_path == "-" {
-        stdout_writer = io::stdout();
-        &mut stdout_writer
+    // Single-secret documents are recovered as one unlabelled secret.
+    let (secret, bundle);
+    let secrets: Vec<(&str, &[u8])> = if is_bundle {
+        bundle = quorum
+            .recover_bundle()
+            .context("recovering secret bundle")?;
+        bundle
+            .entries()
+            .iter()
+            .map(|entry| (entry.label(), entry.data()))
+            .collect()
     } else {
-        file_writer = File::create(output_path)
-            .with_context(|| format!("failed to open output file '{}' for writing", output_path))?;
-        &mut file_writer
+        secret = SecretBuf::new(
+            quorum
+                .recover_document()
+                .context("recovering secret data")?,
+        );
+        vec![("", &secret[..])]
     };
 
-    output_file
-        .write_all(&secret)
-        .context("write secret data to file")?;
+    let mut warnings = quorum.warnings().clone();
+    let consistency = quorum
+        .shard_consistency()
+        .context("cross-verifying key shards")?;
+    if num_shards > quorum_size {
+        say!(
+            "Cross-verified key shards: [{}] consistent, [{}] inconsistent.",
+            consistency.consistent().join(" "),
+            consistency.inconsistent().join(" ")
+        );
+    }
+    if !consistency.is_consistent() {
+        warnings.push(Warning::InconsistentShards(
+            consistency.inconsistent().to_vec(),
+        ));
+    }
+    for audit in quorum
+        .audit_expansions()
+        .context("auditing minted key shards")?
+    {
+        match audit.authorised {
+            true => say!(
+                "Key shard {} was minted on {} by a quorum of key shards [{}].",
+                audit.shard_id,
+                constraints::format_date(audit.expansion.expanded_at),
+                audit.expansion.quorum.join(" ")
+            ),
+            false => warnings.push(Warning::UnauthorisedExpansion(audit.shard_id)),
+        }
+    }
+    warnings.extend(constraint_warnings);
+    for (_, secret) in &secrets {
+        warnings.extend(plausibility_warnings(matches, secret));
+    }
+    report_warnings(matches, &warnings)?;
+    result.warnings = warnings.iter().map(ToString::to_string).collect();
+    result.secrets = secrets
+        .iter()
+        .map(|(label, secret)| SecretResult {
+            label: is_bundle.then(|| label.to_string()),
+            size: secret.len(),
+            path: match is_bundle {
+                true => Path::new(output_path).join(label),
+                false => output_path.into(),
+            },
+        })
+        .collect();
+
+    if is_bundle && output_path != "-" {
+        write_secret_bundle(Path::new(output_path), &secrets)?;
+    } else {
+        let (mut stdout_writer, mut file_writer);
+        let output_file: &mut dyn Write = if output_path == "-" {
+            stdout_writer = io::stdout();
+            &mut stdout_writer
+        } else {
+            file_writer = File::create(output_path).with_context(|| {
+                format!("failed to open output file '{}' for writing", output_path)
+            })?;
+            &mut file_writer
+        };
 
+        for (label, secret) in &secrets {
+            if is_bundle {
+                writeln!(output_file, "==> {} ({} bytes) <==", label, secret.len())
+                    .context("write secret label to file")?;
+            }
+            output_file
+                .write_all(secret)
+                .context("write secret data to file")?;
+        }
+    }
+    checklist.record(ChecklistStep::SecretOutput {
+        destination: match output_path.as_str() {
+            "-" => "stdout".to_string(),
+            path => path.to_string(),
+        },
+    })?;
+    checklist.confirm_destroyed()?;
+
+    output::emit(&result)
+}
+
+// Write each of the labelled secrets of a bundle to a file named after its
+// label in the directory dir.
+fn write_secret_bundle(dir: &Path, secrets: &[(&str, &[u8])]) -> Result<(), Error> {
+    std::fs::create_dir_all(dir)
+        .with_context(|| format!("failed to create output directory {:?}", dir))?;
+    for (label, secret) in secrets {
+        // Labels come from the (authenticated) main document, but make sure
+        // they cannot be used to write outside of dir.
+        ensure!(
+            !label.contains(&['/', '\\'][..]) && *label != "." && *label != "..",
+            "secret label {:?} is not a valid file name -- recover to stdout instead",
+            label
+        );
+        let path = dir.join(label);
+        std::fs::write(&path, secret)
+            .with_context(|| format!("failed to write secret {:?} to {:?}", label, path))?;
+        say!(
+            "Secret {:?} ({} bytes) written to {:?}.",
+            label,
+            secret.len(),
+            path
+        );
+    }
     Ok(())
 }
 
-fn new_shards(new_shard_types: impl IntoIterator<Item = NewShardKind>) -> Result<(), Error> {
+// Read a quorum of key shards (without a main document) interactively,
+// checking that they belong to document_id if provided.
+fn read_shard_quorum(matches: &ArgMatches, document_id: Option<&str>) -> Result<Quorum, Error> {
     let mut quorum = UntrustedQuorum::new();
     loop {
         let idx = quorum.num_untrusted_shards() as u32;
@@ -296,22 +1788,33 @@ fn new_shards(new_shard_types: impl IntoIterator<Item = NewShardKind>) -> Result
             ),
         })?;
         // TODO: Ask the user to input the checksum...
-        println!(
+        say!(
             "Key shard {} checksum: {}",
             idx + 1,
             encrypted_shard.checksum_string()
         );
 
         let codewords = read_codewords(format!("Enter key shard {} codewords", idx + 1))?;
-        let shard = encrypted_shard
-            .decrypt(&codewords)
-            .map_err(|err| anyhow!(err)) // TODO: Fix this once FromWire supports non-String errors.
-            .with_context(|| format!("decrypting key shard {}", idx + 1))?;
+        let shard = decrypt_key_shard(
+            &encrypted_shard,
+            &codewords,
+            &format!("Enter key shard {} passphrase", idx + 1),
+        )
+        .with_context(|| format!("decrypting key shard {}", idx + 1))?;
 
-        println!("Loaded key shard {}.", shard.id());
+        say!("Loaded key shard {}.", shard.id());
+        if let Some(document_id) = document_id {
+            ensure!(
+                shard.document_id() == document_id,
+                "key shard {} is for document {} not {}",
+                shard.id(),
+                shard.document_id(),
+                document_id
+            );
+        }
         quorum.push_shard(shard);
 
-        if idx + 1
+        if quorum.untrusted_weight()
             >= quorum
                 .quorum_size()
                 .expect("quorum_size should be set after adding a key shard")
@@ -327,35 +1830,58 @@ fn new_shards(new_shard_types: impl IntoIterator<Item = NewShardKind>) -> Result
             err.as_groups()
         )
     })?;
+    report_warnings(matches, quorum.warnings())?;
+    Ok(quorum)
+}
+
+fn new_shards(
+    matches: &ArgMatches,
+    document_id: Option<&str>,
+    new_shard_types: impl IntoIterator<Item = NewShardKind>,
+    render_options: &RenderOptions,
+    sink: &mut dyn RenderSink,
+) -> Result<Vec<EncryptedKeyShard>, Error> {
+    let quorum = read_shard_quorum(matches, document_id)?;
 
+    // New key shards record that they were minted by this quorum, while
+    // re-created key shards are identical to the originals.
+    let now = std::time::SystemTime::now();
     let new_shards = new_shard_types
         .into_iter()
-        .map(|new| {
-            let s = quorum.new_shard(new).context("minting new key shards")?;
-            Ok((
-                s.document_id(),
-                s.id(),
-                s.encrypt().expect("encrypt new shard"),
-            ))
+        .map(|new| match new {
+            NewShardKind::NewShard => quorum.expand_shard(now),
+            existing => quorum.new_shard(existing),
         })
-        .collect::<Result<Vec<_>, Error>>()?;
+        .collect::<Result<Vec<_>, _>>()
+        .context("minting new key shards")?;
+    // The new key shards all belong to the quorum's document.
+    let document_id = new_shards
+        .first()
+        .map(KeyShard::document_id)
+        .unwrap_or_default();
+    let new_shards = encrypt_key_shards(&new_shards, None, render_options)?;
 
-    for (document_id, shard_id, (shard, codewords)) in new_shards {
-        (shard, codewords)
-            .to_pdf()?
-            .save(&mut BufWriter::new(File::create(format!(
-                "key_shard-{}-{}.pdf",
-                document_id, shard_id
-            ))?))?;
-    }
+    render_key_shards(
+        matches,
+        sink,
+        &document_id,
+        &new_shards,
+        None,
+        render_options,
+    )?;
+    sink.finish()?;
 
-    Ok(())
+    Ok(new_shards
+        .into_iter()
+        .map(|(_, (shard, _))| shard)
+        .collect())
 }
 
-// paperback-cli expand-shards --interactive -n <SHARDS>
+// paperback-cli expand-shards --interactive (-n <SHARDS> | --request)
 fn expand_shards_cli() -> Command {
     Command::new("expand-shards")
-            .about(r#"Create new key shards from a quorum of old key shards. The new key shards are separate to existing key shards, which means you are increasing the number of shards in circulation. This operation is recommended when you wish to add a new key shard holder to an existing quorum (and you are still confident that no more than N-1 shard holders will conspire against you)."#)
+            .visible_alias("expand")
+            .about(r#"Create new key shards from a quorum of old key shards. The new key shards are separate to existing key shards, which means you are increasing the number of shards in circulation. This operation is recommended when you wish to add a new key shard holder to an existing quorum (and you are still confident that no more than N-1 shard holders will conspire against you). Each new key shard records (on its PDF) when it was minted and which key shards it was minted from, and cannot be read by older versions of paperback."#)
             .arg(Arg::new("interactive")
                 .long("interactive")
                 .help(r#"Ask for data stored in QR codes interactively rather than scanning images."#)
@@ -367,17 +1893,61 @@ fn expand_shards_cli() -> Command {
                 .long("new-shards")
                 .value_name("NUM SHARDS")
                 .help(r#"Number of new shards to create."#)
-                .action(ArgAction::Set)
+                .action(ArgAction::Set))
+            .arg(Arg::new("request")
+                .long("request")
+                .help(r#"Create the new shards requested by an air-gap request (see "airgap request expand-shards"), and produce an air-gap response listing the new shards."#)
+                .action(ArgAction::SetTrue))
+            .group(ArgGroup::new("amount")
+                .arg("new-shards")
+                .arg("request")
                 .required(true))
+            .arg(shard_size_arg())
+            .arg(parity_codes_arg())
+            .args(qr_code_args())
+            .arg(render_style_arg())
+            .arg(paper_size_arg())
+            .arg(language_arg())
+            .arg(compact_shards_arg())
+            .args(output_args())
 }
 
 fn expand_shards(matches: &ArgMatches) -> Result<(), Error> {
+    let render_options = get_render_options(matches)?;
+    let mut sink = get_output_sink(matches)?;
+    if matches.get_flag("request") {
+        let request = airgap::read_request()?;
+        let payload: ExpandShardsRequest = request.payload()?;
+        say!(
+            "Request {} for {} new key shards of document {}.",
+            request.id(),
+            payload.num_shards,
+            payload.document_id
+        );
+        let shards = new_shards(
+            matches,
+            Some(&payload.document_id),
+            (0..payload.num_shards).map(|_| NewShardKind::NewShard),
+            &render_options,
+            sink.as_mut(),
+        )?;
+        let response = AirGapResponse::new(&request, &ExpandShardsApproval::new(&shards))?;
+        return airgap::save_response(&response);
+    }
+
     let num_new_shards: u32 = matches
         .get_one::<String>("new-shards")
         .context("required --new-shards argument not provided")?
         .parse()
         .context("--new-shards argument was not an unsigned integer")?;
-    new_shards((0..num_new_shards).map(|_| NewShardKind::NewShard))
+    new_shards(
+        matches,
+        None,
+        (0..num_new_shards).map(|_| NewShardKind::NewShard),
+        &render_options,
+        sink.as_mut(),
+    )?;
+    Ok(())
 }
 
 // paperback-cli recreate-shards --interactive <SHARD-ID>...
@@ -395,6 +1965,14 @@ fn recreate_shards_cli() -> Command {
                 .help(r#"Shard identifier(s) of the shard(s) to recreate."#)
                 .action(ArgAction::Append)
                 .required(true))
+            .arg(shard_size_arg())
+            .arg(parity_codes_arg())
+            .args(qr_code_args())
+            .arg(render_style_arg())
+            .arg(paper_size_arg())
+            .arg(language_arg())
+            .arg(compact_shards_arg())
+            .args(output_args())
 }
 
 fn recreate_shards(matches: &ArgMatches) -> Result<(), Error> {
@@ -403,7 +1981,190 @@ fn recreate_shards(matches: &ArgMatches) -> Result<(), Error> {
         .context("required shard id arguments not given")?
         .cloned()
         .map(NewShardKind::ExistingShard);
-    new_shards(new_shard_list)
+    let render_options = get_render_options(matches)?;
+    let mut sink = get_output_sink(matches)?;
+    new_shards(
+        matches,
+        None,
+        new_shard_list,
+        &render_options,
+        sink.as_mut(),
+    )?;
+    Ok(())
+}
+
+// paperback-cli redeal --interactive [--rotate-identity | --seal | --unseal] -n <QUORUM SIZE> -k <SHARDS>
+fn redeal_cli() -> Command {
+    Command::new("redeal")
+            .about(r#"Re-deal a backup with a different quorum size from a quorum of its key shards (and its main document). The secret never leaves paperback -- it is re-encrypted as the next generation of the backup, with a new main document and new key shards. Key shards of the old generation cannot be combined with the new main document, but the old documents should still be destroyed once the new key shards have been distributed."#)
+            .arg(Arg::new("interactive")
+                .long("interactive")
+                .help(r#"Ask for data stored in QR codes interactively rather than scanning images."#)
+                .action(ArgAction::SetTrue)
+                // TODO: Make this optional.
+                .required(true))
+            .arg(Arg::new("rotate-identity")
+                .long("rotate-identity")
+                .help("Sign the new generation with a fresh identity, and mark the old identity as revoked on the new main document. Use this if the machine which created the backup may have been compromised. Documents created with this option cannot be read by older versions of paperback.")
+                .action(ArgAction::SetTrue))
+            .arg(Arg::new("seal")
+                .long("seal")
+                .help("Re-deal an unsealed backup as a sealed one, so that no new key shards can be created for the new generation. The new generation is signed with a fresh identity, and the old identity is revoked (as in --rotate-identity).")
+                .conflicts_with_all(["rotate-identity", "unseal"])
+                .action(ArgAction::SetTrue))
+            .arg(Arg::new("unseal")
+                .long("unseal")
+                .help("Re-deal a sealed backup as an unsealed one, so that a quorum of the new generation can create new key shards. The new generation is signed with a fresh identity. Anyone who gathers a quorum of the new key shards will be able to create key shards and sign documents with it.")
+                .conflicts_with_all(["rotate-identity", "seal"])
+                .action(ArgAction::SetTrue))
+            .arg(Arg::new("quorum-size")
+                .short('n')
+                .long("quorum-size")
+                .value_name("QUORUM SIZE")
+                .help("Number of shards required to recover the new generation of the document (must not be larger than --shards).")
+                .value_parser(clap::value_parser!(u32))
+                .action(ArgAction::Set)
+                .required(true))
+            .arg(Arg::new("shards")
+                .short('k')
+                .long("shards")
+                .value_name("NUM SHARDS")
+                .help("Number of shards to create for the new generation (must not be smaller than --quorum-size).")
+                .value_parser(clap::value_parser!(u32))
+                .action(ArgAction::Set)
+                .required(true))
+            .arg(shard_size_arg())
+            .arg(parity_codes_arg())
+            .args(qr_code_args())
+            .arg(render_style_arg())
+            .arg(paper_size_arg())
+            .arg(language_arg())
+            .arg(compact_shards_arg())
+            .args(output_args())
+}
+
+fn redeal(matches: &ArgMatches) -> Result<(), Error> {
+    let render_options = get_render_options(matches)?;
+    let new_quorum_size = *matches
+        .get_one::<u32>("quorum-size")
+        .context("required --quorum-size argument not provided")?;
+    let num_shards = *matches
+        .get_one::<u32>("shards")
+        .context("required --shards argument not provided")?;
+
+    let main_document: MainDocument = read_multibase_qr("Enter a main document code")?;
+    let quorum_size = main_document.quorum_size();
+    say!(
+        "Main document checksum: {}",
+        main_document.checksum_string()
+    );
+    say!(
+        "Document ID: {} (generation {})",
+        main_document.id(),
+        main_document.generation()
+    );
+    say!("{} key shards required.", quorum_size);
+
+    let mut quorum = UntrustedQuorum::new();
+    quorum.main_document(main_document);
+    while quorum.untrusted_weight() < quorum_size {
+        let idx = quorum.num_untrusted_shards() as u32;
+        let encrypted_shard: EncryptedKeyShard = read_multibase(format!(
+            "Quorum contains [{}] key shards.\nEnter key shard {} of {}",
+            quorum
+                .untrusted_shards()
+                .map(KeyShard::id)
+                .collect::<Vec<_>>()
+                .join(" "),
+            idx + 1,
+            quorum_size
+        ))?;
+        // TODO: Ask the user to input the checksum...
+        say!(
+            "Key shard {} checksum: {}",
+            idx + 1,
+            encrypted_shard.checksum_string()
+        );
+
+        let codewords = read_codewords(format!("Enter key shard {} codewords", idx + 1))?;
+        let shard = decrypt_key_shard(
+            &encrypted_shard,
+            &codewords,
+            &format!("Enter key shard {} passphrase", idx + 1),
+        )
+        .with_context(|| format!("decrypting key shard {}", idx + 1))?;
+
+        say!("Loaded key shard {}.", shard.id());
+        quorum.push_shard(shard);
+    }
+
+    let quorum = quorum.validate().map_err(|err| {
+        anyhow!(
+            "quorum failed to validate -- possible forgery! {}; groupings: {:?}",
+            err.message,
+            err.as_groups()
+        )
+    })?;
+
+    let backup = if matches.get_flag("seal") {
+        quorum.seal(new_quorum_size)
+    } else if matches.get_flag("unseal") {
+        quorum.unseal(new_quorum_size)
+    } else if matches.get_flag("rotate-identity") {
+        quorum.rotate_identity(new_quorum_size)
+    } else {
+        quorum.redeal(new_quorum_size)
+    }
+    .context("re-dealing secret data")?;
+    let main_document = backup.main_document().clone();
+    say!(
+        "Re-dealt as document {} (generation {}), requiring {} of {} key shards.",
+        main_document.id(),
+        main_document.generation(),
+        new_quorum_size,
+        num_shards
+    );
+    if matches.get_flag("rotate-identity") || matches.get_flag("seal") {
+        say!(
+            "New identity {} replaces revoked identities: {}.",
+            main_document.identity_id(),
+            main_document.revoked_identity_ids().join(" ")
+        );
+    }
+
+    if matches.get_flag("seal") {
+        say!("The new generation is sealed -- no new key shards can be created for it.");
+    }
+
+    let mut warnings = quorum.warnings().clone();
+    warnings.extend(backup_warnings(&backup, new_quorum_size, num_shards));
+    warnings.extend(main_document.warnings(&render_options));
+    if matches.get_flag("unseal") {
+        warnings.push(Warning::WeakParameters(
+            "the new generation is unsealed -- anyone who gathers a quorum of its key shards can create new key shards and sign documents with its identity".into(),
+        ));
+    }
+    report_warnings(matches, &warnings)?;
+
+    print_main_document_codes(&main_document, &render_options)?;
+    let mut sink = get_output_sink(matches)?;
+    main_document.render_to(
+        sink.as_mut(),
+        &format!("main_document-{}.pdf", main_document.id()),
+        &render_options,
+    )?;
+    let shards = encrypt_key_shards(&backup.next_shards(num_shards)?, None, &render_options)?;
+    render_key_shards(
+        matches,
+        sink.as_mut(),
+        &main_document.id(),
+        &shards,
+        None,
+        &render_options,
+    )?;
+    sink.finish()?;
+
+    Ok(())
 }
 
 // paperback-cli reprint --interactive [--main-document|--shard]
@@ -430,6 +2191,13 @@ fn reprint_cli() -> Command {
                 .help(r#"Reprint a paperback key shard."#)
                 .action(ArgAction::SetTrue),
         )
+        .arg(shard_size_arg().requires("shard"))
+        .arg(parity_codes_arg().requires("main-document"))
+        .args(qr_code_args())
+        .arg(render_style_arg())
+        .arg(paper_size_arg())
+        .arg(language_arg())
+        .args(output_args())
         .group(
             ArgGroup::new("type")
                 .arg("main-document")
@@ -441,9 +2209,12 @@ fn reprint_cli() -> Command {
 fn reprint(matches: &ArgMatches) -> Result<(), Error> {
     let interactive = matches.get_flag("interactive");
     ensure!(interactive, "PDF scanning not yet implemented");
+    let render_options = get_render_options(matches)?;
 
     let mut main_document: MainDocument;
     let mut shard_pair: (EncryptedKeyShard, KeyShardCodewords);
+    let shard_passphrase: String;
+    let mut hardened_shard: (&EncryptedKeyShard, &KeyShardCodewords, &[u8]);
     let (pdf, path_basename): (&mut dyn ToPdf, String) = match matches
         .get_one::<clap::Id>("type")
         .context("neither --main-document nor --shard provided")?
@@ -452,7 +2223,7 @@ fn reprint(matches: &ArgMatches) -> Result<(), Error> {
         "main-document" => {
             main_document = read_multibase_qr("Enter a main document code")?;
             // TODO: Ask the user to input the checksum...
-            println!(
+            say!(
                 "Main document checksum: {}",
                 main_document.checksum_string()
             );
@@ -463,24 +2234,54 @@ fn reprint(matches: &ArgMatches) -> Result<(), Error> {
         "shard" => {
             let encrypted_shard: EncryptedKeyShard = read_multibase("Enter key shard")?;
             // TODO: Ask the user to input the checksum...
-            println!("Key shard checksum: {}", encrypted_shard.checksum_string());
+            say!("Key shard checksum: {}", encrypted_shard.checksum_string());
             let codewords = read_codewords("Key shard codewords")?;
 
-            let shard = encrypted_shard
-                .decrypt(codewords.clone())
-                .map_err(|err| anyhow!(err)) // TODO: Fix this once FromWire supports non-String errors.
-                .with_context(|| "decrypting shard")?;
-            let pathname = format!("key-shard-{}-{}.pdf", shard.document_id(), shard.id());
+            // Hardened shards need the passphrase to be re-rendered (and
+            // repadded to --shard-size).
+            if encrypted_shard.is_passphrase_protected() {
+                shard_passphrase = raw::read_oneline_file("Key shard passphrase", "-")
+                    .context("read passphrase")?;
+                let shard = encrypted_shard
+                    .decrypt_with_passphrase(&codewords, shard_passphrase.as_bytes())
+                    .map_err(|err| anyhow!(err)) // TODO: Fix this once FromWire supports non-String errors.
+                    .with_context(|| "decrypting shard")?;
+                let pathname = format!("key-shard-{}-{}.pdf", shard.document_id(), shard.id());
+
+                let encrypted_shard = match render_options.key_shard_size {
+                    Some(target_len) => encrypted_shard.repad_with_passphrase(
+                        &codewords,
+                        shard_passphrase.as_bytes(),
+                        target_len,
+                    )?,
+                    None => encrypted_shard,
+                };
+                shard_pair = (encrypted_shard, codewords);
+                hardened_shard = (&shard_pair.0, &shard_pair.1, shard_passphrase.as_bytes());
+                (&mut hardened_shard, pathname)
+            } else {
+                let shard = encrypted_shard
+                    .decrypt(&codewords)
+                    .map_err(|err| anyhow!(err)) // TODO: Fix this once FromWire supports non-String errors.
+                    .with_context(|| "decrypting shard")?;
+                let pathname = format!("key-shard-{}-{}.pdf", shard.document_id(), shard.id());
 
-            shard_pair = (encrypted_shard, codewords);
-            (&mut shard_pair, pathname)
+                let encrypted_shard = match render_options.key_shard_size {
+                    Some(target_len) => encrypted_shard.repad(&codewords, target_len)?,
+                    None => encrypted_shard,
+                };
+                shard_pair = (encrypted_shard, codewords);
+                (&mut shard_pair, pathname)
+            }
         }
         // We should never reach here.
         _ => bail!("neither --shard nor --main-document type flags passed"),
     };
 
-    pdf.to_pdf()?
-        .save(&mut BufWriter::new(File::create(path_basename)?))?;
+    report_warnings(matches, &pdf.warnings(&render_options))?;
+    let mut sink = get_output_sink(matches)?;
+    pdf.render_to(sink.as_mut(), &path_basename, &render_options)?;
+    sink.finish()?;
 
     Ok(())
 }
@@ -490,42 +2291,96 @@ fn cli() -> Command {
         .version("0.0.0")
         .author("Aleksa Sarai <cyphar@cyphar.com>")
         .about("Operate on a paperback backup using a basic CLI interface.")
-        // paperback-cli backup [--sealed] -n <QUORUM SIZE> -k <SHARDS> INPUT
+        .arg(
+            Arg::new("deny-warnings")
+                .long("deny-warnings")
+                .help("Fail (before writing any output) if any warnings are emitted.")
+                .action(ArgAction::SetTrue)
+                .global(true),
+        )
+        .arg(output::format_arg())
+        .arg(output::progress_arg())
+        // paperback-cli backup [--sealed] [--test-run] [--thumbnails] [--stream] [--profile <PROFILE>] [--operator-key <KEY>]... -n <QUORUM SIZE> (-k <SHARDS> [--weights <WEIGHT>,...] | --group <MEMBER QUORUM/MEMBERS>...) (INPUT | --secret <LABEL>=<PATH>...)
         .subcommand(backup_cli())
-        // paperback-cli recover --interactive
+        // paperback-cli recover (--interactive | --scan <IMAGE>... | --camera [<INDEX>])
         .subcommand(recover_cli())
-        // paperback-cli expand-shards --interactive -n <SHARDS>
+        // paperback-cli expand-shards --interactive (-n <SHARDS> | --request)
         .subcommand(expand_shards_cli())
         // paperback-cli recreate-shards --interactive <SHARD-ID>...
         .subcommand(recreate_shards_cli())
+        // paperback-cli redeal --interactive [--rotate-identity | --seal | --unseal] -n <QUORUM SIZE> -k <SHARDS>
+        .subcommand(redeal_cli())
         // paperback-cli reprint --interactive [--main-document|--shard]
         .subcommand(reprint_cli())
+        // paperback-cli practice [-n <QUORUM SIZE>] [-k <SHARDS>]
+        .subcommand(practice::subcommand())
+        // paperback-cli instructions (--interactive | --document-id <DOCUMENT ID> -n <QUORUM SIZE>)
+        .subcommand(instructions::subcommand())
+        // paperback-cli amend --interactive (--active | --superseded-by <DOCUMENT ID> | --revoked) [--document-id <DOCUMENT ID>] --sequence <SEQUENCE>
+        .subcommand(lifecycle::subcommand())
+        // paperback-cli inspect (--spec <SPEC PATH> | --main-document [--amendment <AMENDMENT>]... | --shard)
+        .subcommand(inspect::subcommand())
+        // paperback-cli holder-verify [--manifest <MANIFEST>] [--no-codewords] [SHARD]
+        .subcommand(holder::verify_subcommand())
+        // paperback-cli fleet-verify [--format csv|json] [--output <PATH>] DIRECTORY
+        .subcommand(fleet::subcommand())
+        // paperback-cli ocr <IMAGE>...
+        .subcommand(ocr::subcommand())
+        // paperback-cli verify-print [--paper-size <SIZE>] [--style <STYLE>] <SCAN>...
+        .subcommand(printscale::subcommand())
+        // paperback-cli render-plugins
+        .subcommand(plugins::subcommand())
+        // paperback-cli testvectors emit [--output <PATH>]
+        .subcommand(testvectors::subcommands())
+        // paperback-cli airgap ...
+        .subcommand(airgap::subcommands())
         // paperback-cli raw ...
         .subcommand(raw::subcommands())
 }
 
 fn main() -> Result<(), Box<dyn StdError>> {
     let mut app = cli();
+    let matches = app.get_matches_mut();
+    output::init(&matches);
 
-    let ret = match app.get_matches_mut().subcommand() {
-        Some(("raw", sub_matches)) => raw::submatch(&mut app, sub_matches),
-        Some(("backup", sub_matches)) => backup(sub_matches),
-        Some(("recover", sub_matches)) => recover(sub_matches),
-        Some(("expand-shards", sub_matches)) => expand_shards(sub_matches),
-        Some(("recreate-shards", sub_matches)) => recreate_shards(sub_matches),
-        Some(("reprint", sub_matches)) => reprint(sub_matches),
-        Some((subcommand, _)) => {
-            // We should never end up here.
-            app.print_help()?;
-            Err(anyhow!("unknown subcommand '{}'", subcommand))
-        }
-        None => {
-            app.print_help()?;
-            Err(anyhow!("no subcommand specified"))
-        }
-    }?;
+    // Draw a progress bar for any long operations run by the subcommand.
+    let progress_bar = output::progress_bar(&matches);
+    let ret = output::check_supported(matches.subcommand_name()).and_then(|_| {
+        progress::with_progress(progress_bar, || match matches.subcommand() {
+            Some(("raw", sub_matches)) => raw::submatch(&mut app, sub_matches),
+            Some(("airgap", sub_matches)) => airgap::submatch(&mut app, sub_matches),
+            Some(("testvectors", sub_matches)) => testvectors::submatch(&mut app, sub_matches),
+            Some(("backup", sub_matches)) => backup(sub_matches),
+            Some(("recover", sub_matches)) => recover(sub_matches),
+            Some(("expand-shards", sub_matches)) => expand_shards(sub_matches),
+            Some(("recreate-shards", sub_matches)) => recreate_shards(sub_matches),
+            Some(("redeal", sub_matches)) => redeal(sub_matches),
+            Some(("reprint", sub_matches)) => reprint(sub_matches),
+            Some(("practice", sub_matches)) => practice::submatch(sub_matches),
+            Some(("instructions", sub_matches)) => instructions::submatch(sub_matches),
+            Some(("amend", sub_matches)) => lifecycle::submatch(sub_matches),
+            Some(("inspect", sub_matches)) => inspect::submatch(sub_matches),
+            Some(("holder-verify", sub_matches)) => holder::verify_submatch(sub_matches),
+            Some(("fleet-verify", sub_matches)) => fleet::submatch(sub_matches),
+            Some(("ocr", sub_matches)) => ocr::submatch(sub_matches),
+            Some(("verify-print", sub_matches)) => printscale::submatch(sub_matches),
+            Some(("render-plugins", _)) => plugins::submatch(),
+            Some((subcommand, _)) => {
+                // We should never end up here.
+                app.print_help()?;
+                Err(anyhow!("unknown subcommand '{}'", subcommand))
+            }
+            None => {
+                app.print_help()?;
+                Err(anyhow!("no subcommand specified"))
+            }
+        })
+    });
+    if let Err(err) = &ret {
+        output::emit_error(err);
+    }
 
-    Ok(ret)
+    Ok(ret?)
 }
 
 #[test]
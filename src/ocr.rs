@@ -0,0 +1,248 @@
+/*
+ * paperback: paper backup generator suitable for long-term storage
+ * Copyright (C) 2018-2022 Aleksa Sarai <cyphar@cyphar.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Reading the text fallback of damaged QR codes from a scan with OCR.
+//!
+//! Every line of the text fallback has its own checksum (see
+//! [`armor`](paperback::armor) and [`bech32m`](paperback::bech32m)), so lines
+//! which were misread can be flagged (and corrected by hand) one at a time,
+//! rather than the whole block having to be typed in.
+
+use anyhow::{Context, Error};
+use clap::{Arg, ArgAction, ArgMatches, Command};
+
+extern crate paperback_core;
+use paperback_core::latest as paperback;
+
+use paperback::{armor, bech32m};
+
+use std::{io, path::PathBuf};
+
+// Characters which OCR commonly confuses in the text fallback.
+const CONFUSABLE: &[&[char]] = &[
+    &['0', 'o', 'd', 'q'],
+    &['1', 'l', 'i', 'j', '7'],
+    &['2', 'z'],
+    &['5', 's'],
+    &['6', 'g', 'b'],
+    &['8', 'b', '3'],
+    &['9', 'g', 'q'],
+    &['c', 'e', 'o'],
+    &['m', 'n', 'w', 'u', 'v'],
+    &['h', 'k', 'n'],
+    &['r', 'n', 't', 'f'],
+    &['x', 'y', 'k'],
+];
+
+/// Checks each line of a block of text fallback, in order.
+#[derive(Clone)]
+enum LineChecker {
+    Armor(armor::ArmorDecoder),
+    // Number of segments checked so far.
+    Bech32m(usize),
+}
+
+impl LineChecker {
+    // The checker for a block starting with line (if it is text fallback).
+    fn for_line(line: &str) -> Option<Self> {
+        if bech32m::is_bech32m(line) {
+            Some(Self::Bech32m(0))
+        } else if armor::is_armored(line) {
+            Some(Self::Armor(armor::ArmorDecoder::new()))
+        } else {
+            None
+        }
+    }
+
+    // Check the next line of the block, returning whether it is the final
+    // line.
+    fn push_line(&mut self, line: &str) -> Result<bool, String> {
+        match self {
+            Self::Armor(decoder) => {
+                decoder.push_line(line).map_err(|err| err.to_string())?;
+                Ok(decoder.is_finished())
+            }
+            Self::Bech32m(num_segments) => {
+                let last = bech32m::check_segment(*num_segments + 1, line)
+                    .map_err(|err| err.to_string())?;
+                *num_segments += 1;
+                Ok(last)
+            }
+        }
+    }
+
+    // Lines (differing from line by one commonly confused character) which
+    // would pass the checksum in place of line.
+    fn suggestions(&self, line: &str) -> Vec<String> {
+        let chars = line.chars().collect::<Vec<_>>();
+        let mut suggestions = Vec::new();
+        for (idx, ch) in chars.iter().enumerate() {
+            let alternatives = CONFUSABLE
+                .iter()
+                .filter(|group| group.contains(ch))
+                .flat_map(|group| group.iter())
+                .filter(|alt| *alt != ch);
+            for alt in alternatives {
+                let mut candidate = chars.clone();
+                candidate[idx] = *alt;
+                let candidate = candidate.into_iter().collect::<String>();
+                if !suggestions.contains(&candidate) && self.clone().push_line(&candidate).is_ok() {
+                    suggestions.push(candidate);
+                }
+            }
+        }
+        suggestions
+    }
+}
+
+// Read the text in the image at path.
+#[cfg(feature = "ocr")]
+fn recognise(path: &std::path::Path) -> Result<String, Error> {
+    use anyhow::anyhow;
+    use leptess::{LepTess, Variable};
+
+    let mut tesseract =
+        LepTess::new(None, "eng").map_err(|err| anyhow!("failed to start tesseract: {}", err))?;
+    // The text fallback only uses these characters, so don't let tesseract
+    // guess anything else.
+    tesseract
+        .set_variable(
+            Variable::TesseditCharWhitelist,
+            "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789-# ",
+        )
+        .map_err(|err| anyhow!("failed to configure tesseract: {}", err))?;
+    tesseract
+        .set_image(path)
+        .map_err(|err| anyhow!("failed to read image {:?}: {}", path, err))?;
+    tesseract
+        .get_utf8_text()
+        .with_context(|| format!("failed to recognise text in {:?}", path))
+}
+
+#[cfg(not(feature = "ocr"))]
+fn recognise(_: &std::path::Path) -> Result<String, Error> {
+    anyhow::bail!("paperback was built without OCR support (rebuild it with --features ocr)")
+}
+
+// Ask for a correction of the misread line (with number line_no in the
+// block), returning None if the line should be skipped.
+fn correct_line(
+    checker: &LineChecker,
+    line_no: usize,
+    line: &str,
+    reason: &str,
+) -> Result<Option<String>, Error> {
+    say!("Line {} was misread ({}):\n  {}", line_no, reason, line);
+    let suggestions = checker.suggestions(line);
+    for (idx, suggestion) in suggestions.iter().enumerate() {
+        say!("  [{}] {}", idx + 1, suggestion);
+    }
+    loop {
+        crate::output::prompt(match suggestions.is_empty() {
+            true => "Type in the corrected line (or an empty line to skip it): ".to_string(),
+            false => format!(
+                "Choose a suggestion [1-{}], or type in the corrected line (or an empty line to skip it): ",
+                suggestions.len()
+            ),
+        })?;
+        let mut input = String::new();
+        io::stdin().read_line(&mut input)?;
+        let input = input.trim();
+        if input.is_empty() {
+            return Ok(None);
+        }
+        let corrected = match input
+            .parse::<usize>()
+            .ok()
+            .and_then(|idx| idx.checked_sub(1))
+            .and_then(|idx| suggestions.get(idx))
+        {
+            Some(suggestion) => suggestion.clone(),
+            None => input.to_string(),
+        };
+        match checker.clone().push_line(&corrected) {
+            Ok(_) => return Ok(Some(corrected)),
+            Err(err) => say!("The corrected line is still invalid ({}).", err),
+        }
+    }
+}
+
+// Split the recognised text into blocks of text fallback, asking for any
+// misread lines to be corrected.
+fn check_blocks(text: &str) -> Result<Vec<Vec<String>>, Error> {
+    let mut blocks = Vec::new();
+    let mut current: Option<(LineChecker, Vec<String>)> = None;
+    for line in text.lines() {
+        let line = line.trim().to_lowercase();
+        if current.is_none() {
+            current = LineChecker::for_line(&line).map(|checker| (checker, Vec::new()));
+        }
+        let (checker, lines) = match current.as_mut() {
+            Some(current) => current,
+            // Anything other than text fallback (such as headings).
+            None => continue,
+        };
+        if line.is_empty() {
+            continue;
+        }
+
+        let line = match checker.clone().push_line(&line) {
+            Ok(_) => line,
+            Err(reason) => match correct_line(checker, lines.len() + 1, &line, &reason)? {
+                Some(corrected) => corrected,
+                None => continue,
+            },
+        };
+        let last = checker.push_line(&line).expect("line was already checked");
+        lines.push(line);
+        if last {
+            blocks.extend(current.take().map(|(_, lines)| lines));
+        }
+    }
+    if let Some((_, lines)) = current {
+        eprintln!(
+            "warning: the last block of text (starting with {:?}) is incomplete",
+            lines.first().map(String::as_str).unwrap_or_default()
+        );
+        blocks.push(lines);
+    }
+    Ok(blocks)
+}
+
+// paperback-cli ocr <IMAGE>...
+pub(crate) fn subcommand() -> Command {
+    Command::new("ocr")
+        .about(r#"Read the text fallback of damaged QR codes from scanned images with OCR (only if paperback was built with --features ocr). Each line is checked against its checksum, and misread lines are flagged so they can be corrected by hand. The checked blocks of text are printed, ready to be pasted into "recover --interactive" (or any other command asking for QR code data)."#)
+        .arg(Arg::new("IMAGES")
+            .help("Scanned images of the documents.")
+            .value_parser(clap::value_parser!(PathBuf))
+            .action(ArgAction::Append)
+            .required(true))
+}
+
+pub(crate) fn submatch(matches: &ArgMatches) -> Result<(), Error> {
+    for path in matches.get_many::<PathBuf>("IMAGES").unwrap_or_default() {
+        let text = recognise(path)?;
+        let blocks = check_blocks(&text).with_context(|| format!("checking text of {:?}", path))?;
+        say!("Found {} blocks of text in {:?}.", blocks.len(), path);
+        for block in blocks {
+            println!("{}\n", block.join("\n"));
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,265 @@
+/*
+ * paperback: paper backup generator suitable for long-term storage
+ * Copyright (C) 2018-2022 Aleksa Sarai <cyphar@cyphar.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Machine-readable output (`--format json`).
+//!
+//! In JSON mode, a command prints a single JSON object describing its result
+//! (or the error it failed with) to stdout. Everything meant for humans --
+//! including the prompts for interactive input -- is printed to stderr
+//! instead, so that scripts can parse stdout without scraping text.
+
+use anyhow::{bail, Error};
+use clap::{builder::PossibleValue, Arg, ArgAction, ArgMatches};
+use serde::Serialize;
+
+extern crate paperback_core;
+use paperback_core::{gf, latest as paperback, progress::Progress, shamir, ErrorCode};
+
+use paperback::{armor::ArmorError, bech32m::Bech32Error, pdf, pdf::RenderSink};
+
+use std::{
+    fmt::Display,
+    io::{self, IsTerminal, Write},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex, PoisonError,
+    },
+    time::{Duration, Instant},
+};
+
+// Set once (by main) before any command is run.
+static JSON: AtomicBool = AtomicBool::new(false);
+// Whether a JSON object has already been printed to stdout.
+static EMITTED: AtomicBool = AtomicBool::new(false);
+
+/// Print a line meant for humans: to stdout normally, or to stderr in JSON
+/// mode (so that it doesn't corrupt the JSON output).
+macro_rules! say {
+    ($($arg:tt)*) => {
+        match $crate::output::is_json() {
+            false => println!($($arg)*),
+            true => eprintln!($($arg)*),
+        }
+    };
+}
+
+pub(crate) fn format_arg() -> Arg {
+    Arg::new("format")
+        .long("format")
+        .value_name("FORMAT")
+        .help("Format of the output. With json, backup, recover, inspect, holder-verify and fleet-verify print a single JSON object describing their result (or error) to stdout, and everything else (including prompts) is printed to stderr. The text report of fleet-verify is a CSV file.")
+        .value_parser([
+            PossibleValue::new("text").alias("csv"),
+            PossibleValue::new("json"),
+        ])
+        .default_value("text")
+        .action(ArgAction::Set)
+        .global(true)
+}
+
+pub(crate) fn progress_arg() -> Arg {
+    Arg::new("no-progress")
+        .long("no-progress")
+        .help("Do not draw a progress bar on stderr during long operations (such as sharding a large secret, recovering it, or rendering many documents). The progress bar is only drawn if stderr is a terminal.")
+        .action(ArgAction::SetTrue)
+        .global(true)
+}
+
+pub(crate) fn init(matches: &ArgMatches) {
+    let json = matches.get_one::<String>("format").map(String::as_str) == Some("json");
+    JSON.store(json, Ordering::Relaxed);
+}
+
+pub(crate) fn is_json() -> bool {
+    JSON.load(Ordering::Relaxed)
+}
+
+// Commands which describe their result with emit() in JSON mode.
+const JSON_SUBCOMMANDS: &[&str] = &[
+    "backup",
+    "recover",
+    "inspect",
+    "holder-verify",
+    "fleet-verify",
+];
+
+/// Fail if JSON output was requested for a command which doesn't support it.
+pub(crate) fn check_supported(subcommand: Option<&str>) -> Result<(), Error> {
+    match subcommand {
+        Some(subcommand) if is_json() && !JSON_SUBCOMMANDS.contains(&subcommand) => {
+            bail!("--format json is not supported by '{}'", subcommand)
+        }
+        _ => Ok(()),
+    }
+}
+
+// Only draw a progress bar for operations which take longer than this, so
+// that quick operations don't flash a bar on the terminal.
+const PROGRESS_DELAY: Duration = Duration::from_millis(500);
+// Number of characters in the progress bar.
+const PROGRESS_WIDTH: usize = 40;
+
+/// A progress callback (for [`paperback_core::progress::with_progress`])
+/// which draws a progress bar on stderr for operations which take a while.
+pub(crate) fn progress_bar(matches: &ArgMatches) -> impl Fn(Progress) + Send + Sync + 'static {
+    let enabled = !matches.get_flag("no-progress") && io::stderr().is_terminal();
+    // When the current operation started, and whether its bar has been drawn.
+    let state = Mutex::new((Instant::now(), false));
+    move |progress| {
+        if !enabled {
+            return;
+        }
+        let mut state = state.lock().unwrap_or_else(PoisonError::into_inner);
+        if progress.done == 0 {
+            *state = (Instant::now(), false);
+        }
+        let (started, drawn) = &mut *state;
+        if !*drawn && started.elapsed() < PROGRESS_DELAY {
+            return;
+        }
+        *drawn = true;
+
+        let filled = ((progress.fraction() * PROGRESS_WIDTH as f64) as usize).min(PROGRESS_WIDTH);
+        let mut stderr = io::stderr().lock();
+        let _ = write!(
+            stderr,
+            "\r{:<10} [{}{}] {:>3}%",
+            progress.stage,
+            "#".repeat(filled),
+            ".".repeat(PROGRESS_WIDTH - filled),
+            (progress.fraction() * 100.0) as u32,
+        );
+        if progress.is_complete() {
+            let _ = writeln!(stderr);
+        }
+        let _ = stderr.flush();
+    }
+}
+
+/// Ask for interactive input with prompt (which is not followed by a
+/// newline).
+pub(crate) fn prompt(prompt: impl Display) -> io::Result<()> {
+    match is_json() {
+        false => {
+            print!("{}", prompt);
+            io::stdout().flush()
+        }
+        true => {
+            eprint!("{}", prompt);
+            io::stderr().flush()
+        }
+    }
+}
+
+/// Print the result of a command, if JSON output was requested.
+pub(crate) fn emit<T: Serialize>(result: &T) -> Result<(), Error> {
+    if is_json() {
+        println!("{}", serde_json::to_string_pretty(result)?);
+        EMITTED.store(true, Ordering::Relaxed);
+    }
+    Ok(())
+}
+
+// Find the stable code of the first paperback error in the chain of err.
+fn error_code(err: &Error) -> Option<ErrorCode> {
+    err.chain().find_map(|cause| {
+        cause
+            .downcast_ref::<paperback::Error>()
+            .map(paperback::Error::code)
+            .or_else(|| cause.downcast_ref::<pdf::Error>().map(pdf::Error::code))
+            .or_else(|| cause.downcast_ref::<ArmorError>().map(ArmorError::code))
+            .or_else(|| cause.downcast_ref::<Bech32Error>().map(Bech32Error::code))
+            .or_else(|| {
+                cause
+                    .downcast_ref::<shamir::Error>()
+                    .map(shamir::Error::code)
+            })
+            .or_else(|| cause.downcast_ref::<gf::Error>().map(gf::Error::code))
+    })
+}
+
+/// Print the error a command failed with, if JSON output was requested. If
+/// the command already printed its result (such as a failed verification
+/// report), the error is only reported on stderr.
+pub(crate) fn emit_error(err: &Error) {
+    #[derive(Serialize)]
+    struct JsonError {
+        #[serde(flatten)]
+        code: Option<ErrorCode>,
+        message: String,
+        causes: Vec<String>,
+    }
+
+    #[derive(Serialize)]
+    struct JsonFailure {
+        error: JsonError,
+    }
+
+    if EMITTED.load(Ordering::Relaxed) {
+        return;
+    }
+    // There is nowhere else to report a failure to print the error.
+    let _ = emit(&JsonFailure {
+        error: JsonError {
+            code: error_code(err),
+            message: err.to_string(),
+            causes: err.chain().skip(1).map(ToString::to_string).collect(),
+        },
+    });
+}
+
+/// A [`RenderSink`] which records the path of every artifact written to it,
+/// so they can be listed in the JSON output.
+pub(crate) struct RecordingSink {
+    inner: Box<dyn RenderSink>,
+    // Directory artifacts are written to (None for bundles).
+    dir: Option<PathBuf>,
+    artifacts: Vec<PathBuf>,
+}
+
+impl RecordingSink {
+    pub(crate) fn new(inner: Box<dyn RenderSink>, dir: Option<PathBuf>) -> Self {
+        Self {
+            inner,
+            dir,
+            artifacts: Vec::new(),
+        }
+    }
+
+    /// Paths of the artifacts written so far (relative to the bundle, if the
+    /// artifacts are written to one).
+    pub(crate) fn artifacts(&self) -> &[PathBuf] {
+        &self.artifacts
+    }
+}
+
+impl RenderSink for RecordingSink {
+    fn write_artifact(&mut self, name: &str, data: &[u8]) -> Result<(), pdf::Error> {
+        self.inner.write_artifact(name, data)?;
+        self.artifacts.push(match &self.dir {
+            Some(dir) => dir.join(name),
+            None => name.into(),
+        });
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), pdf::Error> {
+        self.inner.finish()
+    }
+}
@@ -0,0 +1,326 @@
+/*
+ * paperback: paper backup generator suitable for long-term storage
+ * Copyright (C) 2018-2022 Aleksa Sarai <cyphar@cyphar.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Output profiles implemented by external `paperback-render-<PROFILE>`
+//! executables, so that documents can be produced for exotic media (such as
+//! engraving machines or microfilm) without changes to paperback itself.
+//!
+//! ## Protocol ##
+//! Plugins are found by searching `$PATH` for executables whose names start
+//! with `paperback-render-` (the rest of the name is the profile name passed
+//! to `--profile`). The plugin is run once for every document, with no
+//! arguments. Paperback writes a single JSON request to the plugin's stdin
+//! (and closes it):
+//!
+//! ```json
+//! {
+//!   "protocol-version": 1,
+//!   "profile": "engrave",
+//!   "kind": "key-shard",
+//!   "name": "key_shard-<DOCUMENT ID>-<SHARD ID>",
+//!   "document-id": "<DOCUMENT ID>",
+//!   "id": "<SHARD ID>",
+//!   "checksum": "<CHECKSUM>",
+//!   "test-run": false,
+//!   "data": "<MULTIBASE>",
+//!   "codewords": ["...", "..."]
+//! }
+//! ```
+//!
+//! `kind` is one of `main-document`, `key-shard` or `manifest`. `name` is the
+//! suggested file name (without an extension) of the artifacts, `id` is the
+//! document ID (for main documents and manifests) or shard ID (for key
+//! shards), and `checksum` is the checksum printed on the document (for
+//! manifests, the checksum of the main document). `data` is the document
+//! encoded as a multibase string, in the same form as the input and output of
+//! `paperback raw`. `codewords` is only present for key shards.
+//!
+//! The plugin must write a single JSON response to stdout and exit with a
+//! zero status:
+//!
+//! ```json
+//! {
+//!   "artifacts": [
+//!     { "name": "key_shard-<DOCUMENT ID>-<SHARD ID>.gcode", "data": "<MULTIBASE>" }
+//!   ]
+//! }
+//! ```
+//!
+//! Each artifact is written (with the multibase-decoded `data` as its
+//! contents) to the output directory or bundle, so artifact names must be
+//! plain file names. Anything the plugin writes to stderr is passed through
+//! to the user. A non-zero exit status aborts the backup.
+
+use anyhow::{anyhow, bail, ensure, Context, Error};
+use clap::Command;
+
+extern crate paperback_core;
+use paperback_core::latest as paperback;
+
+use paperback::{
+    ceremony::Manifest, pdf::RenderSink, EncryptedKeyShard, KeyShardCodewords, MainDocument, ToWire,
+};
+
+use std::{
+    env, fs,
+    io::Write,
+    path::{Path, PathBuf},
+    process::{self, Stdio},
+};
+
+use serde::{Deserialize, Serialize};
+
+const PLUGIN_PREFIX: &str = "paperback-render-";
+
+/// Version of the plugin protocol, bumped on incompatible changes.
+const PROTOCOL_VERSION: u32 = 1;
+
+#[derive(Clone, Copy, Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+enum DocumentKind {
+    MainDocument,
+    KeyShard,
+    Manifest,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct RenderRequest<'a> {
+    protocol_version: u32,
+    profile: &'a str,
+    kind: DocumentKind,
+    name: String,
+    document_id: String,
+    id: String,
+    checksum: String,
+    test_run: bool,
+    data: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    codewords: Option<&'a [String]>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RenderResponse {
+    artifacts: Vec<Artifact>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Artifact {
+    name: String,
+    data: String,
+}
+
+/// An output profile implemented by an external executable.
+#[derive(Clone, Debug)]
+pub(crate) struct Plugin {
+    profile: String,
+    path: PathBuf,
+}
+
+impl Plugin {
+    /// Find the plugin implementing `profile` in `$PATH`.
+    pub(crate) fn find(profile: &str) -> Result<Self, Error> {
+        discover()
+            .into_iter()
+            .find(|plugin| plugin.profile == profile)
+            .ok_or_else(|| {
+                anyhow!(
+                    "unknown output profile '{}' (no {}{} executable found in $PATH)",
+                    profile,
+                    PLUGIN_PREFIX,
+                    profile
+                )
+            })
+    }
+
+    pub(crate) fn render_main_document(
+        &self,
+        main_document: &MainDocument,
+        sink: &mut dyn RenderSink,
+    ) -> Result<(), Error> {
+        self.render(
+            &RenderRequest {
+                protocol_version: PROTOCOL_VERSION,
+                profile: &self.profile,
+                kind: DocumentKind::MainDocument,
+                name: format!("main_document-{}", main_document.id()),
+                document_id: main_document.id(),
+                id: main_document.id(),
+                checksum: main_document.checksum_string(),
+                test_run: main_document.is_test_run(),
+                data: main_document.to_wire_multibase(crate::raw::ENCODING_BASE),
+                codewords: None,
+            },
+            sink,
+        )
+    }
+
+    pub(crate) fn render_manifest(
+        &self,
+        manifest: &Manifest,
+        main_document: &MainDocument,
+        sink: &mut dyn RenderSink,
+    ) -> Result<(), Error> {
+        self.render(
+            &RenderRequest {
+                protocol_version: PROTOCOL_VERSION,
+                profile: &self.profile,
+                kind: DocumentKind::Manifest,
+                name: format!("manifest-{}", manifest.document_id()),
+                document_id: manifest.document_id(),
+                id: manifest.document_id(),
+                checksum: manifest.document_checksum_string(),
+                test_run: main_document.is_test_run(),
+                data: manifest.to_wire_multibase(crate::raw::ENCODING_BASE),
+                codewords: None,
+            },
+            sink,
+        )
+    }
+
+    pub(crate) fn render_key_shard(
+        &self,
+        main_document: &MainDocument,
+        shard_id: &str,
+        shard: &EncryptedKeyShard,
+        codewords: &KeyShardCodewords,
+        sink: &mut dyn RenderSink,
+    ) -> Result<(), Error> {
+        self.render(
+            &RenderRequest {
+                protocol_version: PROTOCOL_VERSION,
+                profile: &self.profile,
+                kind: DocumentKind::KeyShard,
+                name: format!("key_shard-{}-{}", main_document.id(), shard_id),
+                document_id: main_document.id(),
+                id: shard_id.to_string(),
+                checksum: shard.checksum_string(),
+                test_run: main_document.is_test_run(),
+                data: shard.to_wire_multibase(crate::raw::ENCODING_BASE),
+                codewords: Some(codewords),
+            },
+            sink,
+        )
+    }
+
+    // Run the plugin to render the requested document, and write the
+    // artifacts it produces to sink.
+    fn render(&self, request: &RenderRequest<'_>, sink: &mut dyn RenderSink) -> Result<(), Error> {
+        let mut child = process::Command::new(&self.path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()
+            .with_context(|| format!("failed to run plugin {:?}", self.path))?;
+        {
+            let mut stdin = child.stdin.take().expect("plugin stdin must be piped");
+            serde_json::to_writer(&mut stdin, request)?;
+            stdin.write_all(b"\n")?;
+        }
+        let output = child
+            .wait_with_output()
+            .with_context(|| format!("failed to run plugin {:?}", self.path))?;
+        ensure!(
+            output.status.success(),
+            "plugin {:?} failed to render {} ({})",
+            self.path,
+            request.name,
+            output.status
+        );
+
+        let response: RenderResponse = serde_json::from_slice(&output.stdout)
+            .with_context(|| format!("plugin {:?} sent an invalid response", self.path))?;
+        for artifact in response.artifacts {
+            if Path::new(&artifact.name).file_name() != Some(artifact.name.as_ref()) {
+                bail!(
+                    "plugin {:?} produced an artifact with an invalid name '{}'",
+                    self.path,
+                    artifact.name
+                );
+            }
+            let (_, data) = multibase::decode(&artifact.data).with_context(|| {
+                format!(
+                    "plugin {:?} produced artifact '{}' with invalid data",
+                    self.path, artifact.name
+                )
+            })?;
+            sink.write_artifact(&artifact.name, &data)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    fs::metadata(path).map_or(false, |m| {
+        m.is_file() && m.permissions().mode() & 0o111 != 0
+    })
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+/// Find every plugin in `$PATH`. If a profile is provided by more than one
+/// executable, the first one in `$PATH` is used (as with shell lookups).
+pub(crate) fn discover() -> Vec<Plugin> {
+    let mut plugins: Vec<Plugin> = Vec::new();
+    for dir in env::split_paths(&env::var_os("PATH").unwrap_or_default()) {
+        let mut entries = match fs::read_dir(&dir) {
+            Ok(entries) => entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.path())
+                .collect::<Vec<_>>(),
+            Err(_) => continue,
+        };
+        entries.sort();
+        for path in entries {
+            let profile = match path
+                .file_stem()
+                .and_then(|name| name.to_str())
+                .and_then(|name| name.strip_prefix(PLUGIN_PREFIX))
+            {
+                Some(profile) if !profile.is_empty() => profile.to_string(),
+                _ => continue,
+            };
+            if is_executable(&path) && !plugins.iter().any(|p| p.profile == profile) {
+                plugins.push(Plugin { profile, path });
+            }
+        }
+    }
+    plugins
+}
+
+// paperback-cli render-plugins
+pub(crate) fn subcommand() -> Command {
+    Command::new("render-plugins")
+        .about("List the output profiles provided by paperback-render-<PROFILE> plugins in $PATH (see --profile).")
+}
+
+pub(crate) fn submatch() -> Result<(), Error> {
+    let plugins = discover();
+    if plugins.is_empty() {
+        println!("No {}<PROFILE> plugins found in $PATH.", PLUGIN_PREFIX);
+    }
+    for plugin in plugins {
+        println!("{}\t{}", plugin.profile, plugin.path.display());
+    }
+    Ok(())
+}
@@ -0,0 +1,168 @@
+/*
+ * paperback: paper backup generator suitable for long-term storage
+ * Copyright (C) 2018-2022 Aleksa Sarai <cyphar@cyphar.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use crate::{
+    get_language, get_output_format, get_output_sink, get_paper_size, get_render_style,
+    language_arg, output_args, paper_size_arg, read_key_shard, read_multibase_qr, render_style_arg,
+};
+
+use anyhow::{anyhow, bail, ensure, Context, Error};
+use clap::{Arg, ArgAction, ArgMatches, Command};
+
+extern crate paperback_core;
+use paperback_core::latest as paperback;
+
+use paperback::{pdf::RenderOptions, BackupBuilder, MainDocument, ToPdf, UntrustedQuorum};
+
+use std::io::{self, Write};
+
+// The fake secret stored in every practice backup. It is printed once the
+// practice recovery succeeds, so the user can see what a recovered secret
+// looks like.
+const PRACTICE_SECRET: &[u8] =
+    b"This is a paperback practice backup. If you can read this, you have successfully recovered it!\n";
+
+// paperback-cli practice [-n <QUORUM SIZE>] [-k <SHARDS>]
+pub(crate) fn subcommand() -> Command {
+    Command::new("practice")
+        .about(r#"Rehearse the recovery procedure. A throwaway test-run backup of a fake secret is created for you to print, and you are then walked through recovering it (as with "recover"), with the result checked automatically. Practice documents are watermarked and protect nothing, so they can be shredded afterwards."#)
+        .args(output_args())
+        .arg(render_style_arg())
+        .arg(paper_size_arg())
+        .arg(language_arg())
+        .arg(
+            Arg::new("quorum-size")
+                .short('n')
+                .long("quorum-size")
+                .value_name("QUORUM SIZE")
+                .help("Number of shards required to recover the practice document (must not be larger than --shards).")
+                .value_parser(clap::value_parser!(u32).range(1..))
+                .default_value("2")
+                .action(ArgAction::Set),
+        )
+        .arg(
+            Arg::new("shards")
+                .short('k')
+                .long("shards")
+                .value_name("NUM SHARDS")
+                .help("Number of practice shards to create (must not be smaller than --quorum-size).")
+                .value_parser(clap::value_parser!(u32).range(1..))
+                .default_value("3")
+                .action(ArgAction::Set),
+        )
+}
+
+fn create(matches: &ArgMatches, quorum_size: u32, num_shards: u32) -> Result<MainDocument, Error> {
+    let backup = BackupBuilder::new(quorum_size)
+        .test_run(true)
+        .build(PRACTICE_SECRET)?;
+    let main_document = backup.main_document().clone();
+    let render_options = RenderOptions {
+        style: get_render_style(matches)?,
+        output_format: get_output_format(matches)?,
+        paper_size: get_paper_size(matches)?,
+        language: get_language(matches)?,
+        ..Default::default()
+    };
+
+    let mut sink = get_output_sink(matches)?;
+    main_document.render_to(
+        sink.as_mut(),
+        &format!("main_document-{}.pdf", main_document.id()),
+        &render_options,
+    )?;
+    for shard in backup.next_shards(num_shards)? {
+        let shard_id = shard.id();
+        shard.encrypt()?.render_to(
+            sink.as_mut(),
+            &format!("key_shard-{}-{}.pdf", main_document.id(), shard_id),
+            &render_options,
+        )?;
+    }
+    sink.finish()?;
+
+    Ok(main_document)
+}
+
+pub(crate) fn submatch(matches: &ArgMatches) -> Result<(), Error> {
+    let quorum_size = *matches
+        .get_one::<u32>("quorum-size")
+        .context("required --quorum-size argument not provided")?;
+    let num_shards = *matches
+        .get_one::<u32>("shards")
+        .context("required --shards argument not provided")?;
+    ensure!(
+        num_shards >= quorum_size,
+        "--shards {} must not be smaller than --quorum-size {}",
+        num_shards,
+        quorum_size
+    );
+
+    let practice_document = create(matches, quorum_size, num_shards)?;
+    println!(
+        "Created practice document {} with {} key shards ({} required for recovery).",
+        practice_document.id(),
+        num_shards,
+        quorum_size
+    );
+    println!("Print the main document and key shards, and hand the key shards out as you would for a real backup.");
+    print!("Press enter once you are ready to recover the practice document.");
+    io::stdout().flush()?;
+    io::stdin().read_line(&mut String::new())?;
+
+    // Walk through the same steps as "recover".
+    let main_document: MainDocument = read_multibase_qr("Enter a main document code")?;
+    println!(
+        "Main document checksum: {}",
+        main_document.checksum_string()
+    );
+    if main_document.id() != practice_document.id() {
+        bail!(
+            "main document {} is not the practice document {} -- use the documents created by this practice run",
+            main_document.id(),
+            practice_document.id()
+        );
+    }
+    println!("Document ID: {}", main_document.id());
+    println!("{} key shards required.", quorum_size);
+
+    let mut quorum = UntrustedQuorum::new();
+    quorum.main_document(main_document);
+    while quorum.untrusted_weight() < quorum_size {
+        let (_, shard) = read_key_shard(&quorum, quorum_size)?;
+        quorum.push_shard(shard);
+    }
+    let quorum = quorum.validate().map_err(|err| {
+        anyhow!(
+            "quorum failed to validate -- were the key shards mixed up with another document? {}",
+            err.message
+        )
+    })?;
+    let secret = quorum
+        .recover_document()
+        .context("recovering practice secret data")?;
+
+    ensure!(
+        secret == PRACTICE_SECRET,
+        "recovered secret data does not match the practice secret"
+    );
+    println!("\nPractice recovery succeeded! The recovered secret data was:\n");
+    println!("{}", String::from_utf8_lossy(&secret).trim_end());
+    println!("\nThe practice documents protect nothing and can now be destroyed.");
+    Ok(())
+}
@@ -0,0 +1,205 @@
+/*
+ * paperback: paper backup generator suitable for long-term storage
+ * Copyright (C) 2018-2022 Aleksa Sarai <cyphar@cyphar.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Detecting documents which were printed scaled, from scans of them.
+//!
+//! Printing with "fit to page" (or "shrink to printable area") silently
+//! shrinks every QR code, which is a common cause of codes failing to scan
+//! years later. The scanned main document is laid out again, and the printed
+//! module size of each of its data codes (measured from the finder patterns
+//! of the code) is compared to the size it was laid out with. The scan of
+//! each page is assumed to cover the whole sheet of paper, which gives both
+//! the paper size (from the aspect ratio of the scan) and the scale of the
+//! scan.
+
+use anyhow::{anyhow, Error};
+use clap::{Arg, ArgAction, ArgMatches, Command};
+
+extern crate paperback_core;
+use paperback_core::latest as paperback;
+
+use paperback::{
+    pdf::{qr::Part, CodeLayout, PaperSize, RenderOptions},
+    FromWire,
+};
+
+use crate::scan::{self, ScannedImage, Scanner};
+
+use std::path::PathBuf;
+
+// Paper sizes a scanned page is matched against (by its aspect ratio) if no
+// --paper-size is given. A5 has the same aspect ratio as A4, so scans of
+// either are treated as A4.
+const PAPER_SIZES: &[(&str, PaperSize)] = &[("A4", PaperSize::A4), ("Letter", PaperSize::Letter)];
+// Scans whose aspect ratio differs from that of the paper by more than this
+// fraction are probably cropped (or are not of a whole page).
+const ASPECT_TOLERANCE: f64 = 0.03;
+// Pages printed more than this fraction smaller (or larger) than their actual
+// size are reported.
+const SCALE_TOLERANCE: f64 = 0.03;
+
+// paperback-cli verify-print [--paper-size <SIZE>] [--style <STYLE>] <SCAN>...
+pub(crate) fn subcommand() -> Command {
+    Command::new("verify-print")
+        .about("Check scans of a printed main document for signs that it was printed scaled (such as with \"fit to page\"), which makes its QR codes smaller and harder to scan. Each page must be scanned in full (without cropping) so that the size of the paper can be detected.")
+        .arg(crate::paper_size_arg()
+            .help("Paper size the main document was printed on (a4, a5, letter, or a custom WIDTHxHEIGHT in millimetres). By default, the paper size is detected from each scanned page (A5 pages cannot be told apart from A4 pages, so pass --paper-size a5 for them)."))
+        .arg(crate::render_style_arg()
+            .help("Visual style the main document was printed in (standard, ink-saver or high-contrast)."))
+        .arg(crate::language_arg()
+            .help("Language the main document was printed in (en, de, fr or es)."))
+        .arg(Arg::new("SCANS")
+            .help("Scans of every page of the main document (PNG or JPEG images, or scanned PDFs).")
+            .value_parser(clap::value_parser!(PathBuf))
+            .action(ArgAction::Append)
+            .required(true))
+}
+
+// The short and long sides of a rectangle.
+fn sides((width, height): (f64, f64)) -> (f64, f64) {
+    (width.min(height), width.max(height))
+}
+
+// The short and long sides of the paper, in millimetres.
+fn paper_sides(paper_size: PaperSize) -> (f64, f64) {
+    let (width, height) = paper_size.dimensions();
+    sides((width.0, height.0))
+}
+
+// The name of the paper size the scanned image is closest to (by its aspect
+// ratio), and the paper size itself.
+fn detect_paper_size(image: &ScannedImage) -> (String, PaperSize) {
+    let (short, long) = sides((image.dimensions.0 as f64, image.dimensions.1 as f64));
+    let aspect_error = |paper_size: PaperSize| {
+        let (paper_short, paper_long) = paper_sides(paper_size);
+        (long / short - paper_long / paper_short).abs()
+    };
+    let (name, paper_size) = PAPER_SIZES
+        .iter()
+        .min_by(|(_, a), (_, b)| aspect_error(*a).total_cmp(&aspect_error(*b)))
+        .expect("PAPER_SIZES is not empty");
+    (name.to_string(), *paper_size)
+}
+
+// Returns whether the scanned image has the aspect ratio of paper_size.
+fn matches_paper(image: &ScannedImage, paper_size: PaperSize) -> bool {
+    let (short, long) = sides((image.dimensions.0 as f64, image.dimensions.1 as f64));
+    let (paper_short, paper_long) = paper_sides(paper_size);
+    let paper_aspect = paper_long / paper_short;
+    ((long / short - paper_aspect) / paper_aspect).abs() <= ASPECT_TOLERANCE
+}
+
+// The scale (relative to the laid out size) that the main document data codes
+// in the scanned image were printed at, on paper of paper_size. The layout of
+// the data codes is the same for all of them, apart from their number of
+// modules.
+fn printed_scale(image: &ScannedImage, paper_size: PaperSize, layout: &CodeLayout) -> Option<f64> {
+    let (short, _) = sides((image.dimensions.0 as f64, image.dimensions.1 as f64));
+    let (paper_short, _) = paper_sides(paper_size);
+    let mm_per_pixel = paper_short / short;
+
+    let scales = image
+        .codes
+        .iter()
+        .filter(|code| Part::from_wire_multibase(&code.content).is_ok())
+        .map(|code| {
+            let printed_module_size = code.size / code.modules as f64 * mm_per_pixel;
+            let layout = CodeLayout {
+                modules: code.modules,
+                ..layout.clone()
+            };
+            printed_module_size / layout.module_size().0
+        })
+        .collect::<Vec<_>>();
+    match scales.len() {
+        0 => None,
+        n => Some(scales.iter().sum::<f64>() / n as f64),
+    }
+}
+
+pub(crate) fn submatch(matches: &ArgMatches) -> Result<(), Error> {
+    let mut images = Vec::new();
+    let mut scanner = Scanner::new();
+    for path in matches.get_many::<PathBuf>("SCANS").unwrap_or_default() {
+        for image in scan::locate_file(path)? {
+            for code in &image.codes {
+                scanner.add_code(code.content.clone())?;
+            }
+            images.push(image);
+        }
+    }
+    let main_document = scanner
+        .finish()?
+        .main_document
+        .ok_or_else(|| anyhow!("no main document found -- scan every page of the main document"))?;
+
+    let paper_size = crate::get_paper_size(matches)?;
+    let (style, language) = (
+        crate::get_render_style(matches)?,
+        crate::get_language(matches)?,
+    );
+    for image in &images {
+        let (name, paper_size) = match paper_size {
+            Some(paper_size) => {
+                let (width, height) = paper_size.dimensions();
+                (format!("{}x{}mm", width.0, height.0), paper_size)
+            }
+            None => detect_paper_size(image),
+        };
+        if !matches_paper(image, paper_size) {
+            eprintln!(
+                "warning: skipping {} (it is not a full-page scan of {} paper)",
+                image.source, name
+            );
+            continue;
+        }
+
+        let layouts = main_document.qr_code_layouts(&RenderOptions {
+            paper_size: Some(paper_size),
+            style,
+            language,
+            ..Default::default()
+        })?;
+        let scale = match layouts
+            .first()
+            .and_then(|layout| printed_scale(image, paper_size, layout))
+        {
+            Some(scale) => scale,
+            // Pages without any main document data codes.
+            None => continue,
+        };
+        say!(
+            "{}: {} paper, printed at {:.0}% of its actual size.",
+            image.source,
+            name,
+            scale * 100.0
+        );
+        if (scale - 1.0).abs() > SCALE_TOLERANCE {
+            eprintln!(
+                "warning: {} was printed {} -- reprint it at 100% (\"actual size\") on {} paper, with \"fit to page\" and \"shrink to printable area\" disabled",
+                image.source,
+                match scale < 1.0 {
+                    true => "smaller than its actual size",
+                    false => "larger than its actual size",
+                },
+                name
+            );
+        }
+    }
+    Ok(())
+}
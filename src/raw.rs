@@ -28,9 +28,9 @@ use clap::{Arg, ArgAction, ArgMatches, Command};
 extern crate paperback_core;
 use paperback_core::latest as paperback;
 
-const ENCODING_BASE: multibase::Base = multibase::Base::Base32Z;
+pub(crate) const ENCODING_BASE: multibase::Base = multibase::Base::Base32Z;
 
-// paperback-cli raw backup [--sealed] --quorum-size <QUORUM SIZE> --shards <SHARDS> INPUT
+// paperback-cli raw backup [--sealed] [--test-run] [--shard-encoding multibase|words] [--holders <DIRECTORY>] [--operator-key <KEY>]... --quorum-size <QUORUM SIZE> --shards <SHARDS> INPUT
 fn raw_backup_cli() -> Command {
     Command::new("backup")
                 .about("Create a new paperback backup.")
@@ -38,6 +38,14 @@ fn raw_backup_cli() -> Command {
                     .long("sealed")
                     .help("Create a sealed backup, which cannot be expanded (have new shards be created) after creation.")
                     .action(ArgAction::SetTrue))
+                .arg(crate::padding_arg())
+                .arg(crate::compression_arg())
+                .arg(crate::cipher_arg())
+                .arg(crate::constraint_arg())
+                .args(crate::policy_args())
+                .arg(crate::holder::holders_arg())
+                .arg(crate::ceremony::operator_key_arg())
+                .arg(crate::test_run_arg())
                 .arg(Arg::new("quorum-size")
                     .short('n')
                     .long("quorum-size")
@@ -52,6 +60,12 @@ fn raw_backup_cli() -> Command {
                     .help("Number of shards to create (must not be smaller than --quorum-size).")
                     .action(ArgAction::Set)
                     .required(true))
+                .arg(Arg::new("shard-encoding")
+                    .long("shard-encoding")
+                    .value_name("ENCODING")
+                    .help("Text encoding of the key shard data. \"words\" encodes the data as a checksummed sequence of words, which is easier to copy by hand or read aloud than the default z-base-32 string.")
+                    .value_parser(["multibase", "words"])
+                    .default_value("multibase"))
                 .arg(Arg::new("INPUT")
                     .help(r#"Path to file containing secret data to backup ("-" to read from stdin)."#)
                     .action(ArgAction::Set)
@@ -61,9 +75,19 @@ fn raw_backup_cli() -> Command {
 }
 
 fn raw_backup(matches: &ArgMatches) -> Result<(), Error> {
-    use paperback::{Backup, ToWire};
+    use paperback::{
+        ceremony::Manifest, custody::SealedKeyShard, policy::PolicyParams, BackupBuilder, ToWire,
+    };
 
     let sealed = matches.get_flag("sealed");
+    let padding = crate::get_padding(matches)?;
+    let encode_shard = |shard: &dyn ToWire| match matches
+        .get_one::<String>("shard-encoding")
+        .map(String::as_str)
+    {
+        Some("words") => shard.to_wire_words(),
+        _ => shard.to_wire_multibase(ENCODING_BASE),
+    };
     let quorum_size: u32 = matches
         .get_one::<String>("quorum-size")
         .context("required --quorum-size argument not provided")?
@@ -77,10 +101,34 @@ fn raw_backup(matches: &ArgMatches) -> Result<(), Error> {
     let input_path = matches
         .get_one::<String>("INPUT")
         .context("required INPUT argument not provided")?;
+    let holders = matches
+        .get_one::<String>("holders")
+        .map(|path| crate::holder::read_directory(path))
+        .transpose()?;
 
     if num_shards < quorum_size {
         return Err(anyhow!("invalid arguments: number of shards cannot be smaller than quorum size (such a backup is unrecoverable)"));
     }
+    if let Some(ref holders) = holders {
+        if holders.holders.len() < num_shards as usize {
+            return Err(anyhow!(
+                "invalid arguments: holder directory only lists {} holders but {} shards were requested",
+                holders.holders.len(),
+                num_shards
+            ));
+        }
+    }
+
+    let cipher = crate::get_cipher(matches)?;
+    let mut warnings = crate::check_policy(
+        matches,
+        &PolicyParams {
+            quorum_size,
+            num_shards,
+            cipher_suite: cipher.name(),
+            output_profile: "raw",
+        },
+    )?;
 
     let (mut stdin_reader, mut file_reader);
     let input: &mut dyn Read = if input_path == "-" {
@@ -91,24 +139,37 @@ fn raw_backup(matches: &ArgMatches) -> Result<(), Error> {
             .with_context(|| format!("failed to open secret data file '{}'", input_path))?;
         &mut file_reader
     };
-    let mut buffer_input = BufReader::new(input);
-
-    let mut secret = Vec::new();
-    buffer_input
-        .read_to_end(&mut secret)
+    let secret = crate::secret::SecretBuf::read_from(input)
         .with_context(|| format!("failed to read secret data from '{}'", input_path))?;
 
-    let backup = if sealed {
-        Backup::new_sealed(quorum_size, &secret)
-    } else {
-        Backup::new(quorum_size, &secret)
-    }?;
+    let mut builder = BackupBuilder::new(quorum_size);
+    builder
+        .sealed(sealed)
+        .padding(padding)
+        .compression(crate::get_compression(matches, &secret)?)
+        .aead_algorithm(cipher)
+        .test_run(matches.get_flag("test-run"))
+        .created_at(std::time::SystemTime::now());
+    for constraint in crate::get_constraints(matches) {
+        builder.constraint(constraint);
+    }
+    let backup = builder.build(&secret)?;
     let main_document = backup.main_document().clone();
-    let shards = (0..num_shards)
-        .map(|_| backup.next_shard().unwrap())
+    warnings.extend(crate::backup_warnings(&backup, quorum_size, num_shards));
+    crate::report_warnings(matches, &warnings)?;
+
+    let shards = backup
+        .next_shards(num_shards)?
+        .into_iter()
         .map(|s| s.encrypt().unwrap())
         .collect::<Vec<_>>();
 
+    let mut manifest = Manifest::new(
+        &main_document,
+        &shards.iter().map(|(s, _)| s.clone()).collect::<Vec<_>>(),
+    );
+    let signed = crate::ceremony::sign_manifest(matches, &mut manifest)?;
+
     println!("----- BEGIN MAIN DOCUMENT -----");
     println!("Document-ID: {}", main_document.id());
     println!("Checksum: {}", main_document.checksum_string());
@@ -122,18 +183,32 @@ fn raw_backup(matches: &ArgMatches) -> Result<(), Error> {
         println!("Shard-ID: {}", decrypted_shard.id());
         println!("Checksum: {}", shard.checksum_string());
         println!("Keywords: {}", keyword.join(" "));
-        println!("\n{}", shard.to_wire_multibase(ENCODING_BASE));
+        match holders {
+            Some(ref holders) => {
+                let holder = &holders.holders[i];
+                let sealed = SealedKeyShard::seal(shard, &holder.public_key)?;
+                println!("Holder: {}", holder.name);
+                println!("\n{}", encode_shard(&sealed));
+            }
+            None => println!("\n{}", encode_shard(shard)),
+        }
         println!("----- END SHARD {} OF {} -----", i + 1, quorum_size);
     }
 
+    if signed {
+        println!("----- BEGIN MANIFEST -----");
+        println!("Manifest-Hash: {}", manifest.hash_string());
+        println!("\n{}", manifest.to_wire_multibase(ENCODING_BASE));
+        println!("----- END MANIFEST -----");
+    }
+
     Ok(())
 }
 
-fn read_oneline_file(prompt: &str, path_or_stdin: &str) -> Result<String, Error> {
+pub(crate) fn read_oneline_file(prompt: &str, path_or_stdin: &str) -> Result<String, Error> {
     let (mut stdin_reader, mut file_reader);
     let input: &mut dyn Read = if path_or_stdin == "-" {
-        print!("{}: ", prompt);
-        io::stdout().flush()?;
+        crate::output::prompt(format_args!("{}: ", prompt))?;
         stdin_reader = io::stdin();
         &mut stdin_reader
     } else {
@@ -148,7 +223,7 @@ fn read_oneline_file(prompt: &str, path_or_stdin: &str) -> Result<String, Error>
         .ok_or_else(|| anyhow!("no lines read"))??)
 }
 
-// paperback-cli raw restore --main-document <MAIN DOCUMENT> (--shards <SHARD>)... OUTPUT
+// paperback-cli raw restore [--strict] --main-document <MAIN DOCUMENT> (--shards <SHARD>)... OUTPUT
 fn raw_restore_cli() -> Command {
     Command::new("restore")
         .about("Restore the secret data from a paperback backup.")
@@ -172,6 +247,13 @@ fn raw_restore_cli() -> Command {
                 .allow_hyphen_values(true)
                 .required(true),
         )
+        .arg(crate::no_plausibility_checks_arg())
+        .arg(
+            Arg::new("strict")
+                .long("strict")
+                .help("Reject any main document or shard data which is not exactly in the canonical encoding produced by paperback (for checking documents from an untrusted source).")
+                .action(ArgAction::SetTrue),
+        )
         .arg(
             Arg::new("OUTPUT")
                 .help(r#"Path to write recovered secret data to ("-" to write to stdout)."#)
@@ -183,7 +265,9 @@ fn raw_restore_cli() -> Command {
 }
 
 fn raw_restore(matches: &ArgMatches) -> Result<(), Error> {
-    use paperback::{EncryptedKeyShard, FromWire, MainDocument, UntrustedQuorum};
+    use paperback::{
+        wire, EncryptedKeyShard, FromWire, MainDocument, ParseOptions, UntrustedQuorum,
+    };
 
     let main_document_path = matches
         .get_one::<String>("main_document")
@@ -195,24 +279,33 @@ fn raw_restore(matches: &ArgMatches) -> Result<(), Error> {
         .get_one::<String>("OUTPUT")
         .context("required OUTPUT argument not provided")?;
 
-    let main_document = MainDocument::from_wire_multibase(
+    let parse_options = ParseOptions {
+        strict: matches.get_flag("strict"),
+    };
+
+    let main_document = MainDocument::from_wire_multibase_with_options(
         read_oneline_file("Main Document Data", main_document_path)
             .context("open main document")?,
+        &parse_options,
     )
-    .map_err(|err| anyhow!(err)) // TODO: Fix this once FromWire supports non-String errors.
     .context("decode main document")?;
 
     println!("Document ID: {}", main_document.id());
     println!("Document Checksum: {}", main_document.checksum_string());
+    let constraint_warnings = crate::print_constraints(&main_document);
 
     let mut quorum = UntrustedQuorum::new();
     quorum.main_document(main_document);
     for (idx, shard_path) in shard_paths.enumerate() {
-        let encrypted_shard = EncryptedKeyShard::from_wire_multibase(
-            read_oneline_file(&format!("Shard {} Data", idx + 1), shard_path)
-                .with_context(|| format!("read shard {}", idx + 1))?,
-        )
-        .map_err(|err| anyhow!(err)) // TODO: Fix this once FromWire supports non-String errors.
+        let shard_data = read_oneline_file(&format!("Shard {} Data", idx + 1), shard_path)
+            .with_context(|| format!("read shard {}", idx + 1))?;
+        let encrypted_shard = match wire::words::is_words(&shard_data) {
+            true => wire::words::decode(&shard_data)
+                .and_then(|data| EncryptedKeyShard::from_wire_with_options(data, &parse_options)),
+            false => {
+                EncryptedKeyShard::from_wire_multibase_with_options(shard_data, &parse_options)
+            }
+        }
         .with_context(|| format!("decode shard {}", idx + 1))?;
 
         println!("Shard Checksum: {}", encrypted_shard.checksum_string());
@@ -225,10 +318,12 @@ fn raw_restore(matches: &ArgMatches) -> Result<(), Error> {
             .split_whitespace()
             .map(|s| s.to_owned())
             .collect::<Vec<_>>();
-        let shard = encrypted_shard
-            .decrypt(&codewords)
-            .map_err(|err| anyhow!(err)) // TODO: Fix this once FromWire supports non-String errors.
-            .with_context(|| format!("decrypting shard {}", idx + 1))?;
+        let shard = crate::decrypt_key_shard(
+            &encrypted_shard,
+            &codewords,
+            &format!("Shard {} Passphrase", idx + 1),
+        )
+        .with_context(|| format!("decrypting shard {}", idx + 1))?;
         quorum.push_shard(shard);
     }
 
@@ -240,9 +335,16 @@ fn raw_restore(matches: &ArgMatches) -> Result<(), Error> {
         )
     })?;
 
-    let secret = quorum
-        .recover_document()
-        .context("recovering secret data")?;
+    let secret = crate::secret::SecretBuf::new(
+        quorum
+            .recover_document()
+            .context("recovering secret data")?,
+    );
+
+    let mut warnings = quorum.warnings().clone();
+    warnings.extend(constraint_warnings);
+    warnings.extend(crate::plausibility_warnings(matches, &secret));
+    crate::report_warnings(matches, &warnings)?;
 
     let (mut stdout_writer, mut file_writer);
     let output_file: &mut dyn Write = if output_path == "-" {
@@ -304,7 +406,6 @@ fn raw_expand(matches: &ArgMatches) -> Result<(), Error> {
             read_oneline_file(&format!("Shard {} Data", idx + 1), shard_path)
                 .with_context(|| format!("read shard {}", idx + 1))?,
         )
-        .map_err(|err| anyhow!(err)) // TODO: Fix this once FromWire supports non-String errors.
         .with_context(|| format!("decode shard {}", idx + 1))?;
 
         print!("Shard {} Codeword: ", idx + 1);
@@ -317,10 +418,12 @@ fn raw_expand(matches: &ArgMatches) -> Result<(), Error> {
             .map(|s| s.to_owned())
             .collect::<Vec<_>>();
 
-        let shard = encrypted_shard
-            .decrypt(&codewords)
-            .map_err(|err| anyhow!(err)) // TODO: Fix this once FromWire supports non-String errors.
-            .with_context(|| format!("decrypting shard {}", idx + 1))?;
+        let shard = crate::decrypt_key_shard(
+            &encrypted_shard,
+            &codewords,
+            &format!("Shard {} Passphrase", idx + 1),
+        )
+        .with_context(|| format!("decrypting shard {}", idx + 1))?;
         quorum.push_shard(shard);
     }
 
@@ -331,6 +434,7 @@ fn raw_expand(matches: &ArgMatches) -> Result<(), Error> {
             err.as_groups()
         )
     })?;
+    crate::report_warnings(matches, quorum.warnings())?;
 
     let new_shards = (0..num_new_shards)
         .map(|_| {
@@ -360,6 +464,10 @@ pub(crate) fn submatch(app: &mut Command, matches: &ArgMatches) -> Result<(), Er
         Some(("backup", sub_matches)) => raw_backup(sub_matches),
         Some(("restore", sub_matches)) => raw_restore(sub_matches),
         Some(("expand", sub_matches)) => raw_expand(sub_matches),
+        Some(("holder", sub_matches)) => crate::holder::submatch(app, sub_matches),
+        Some(("manifest", sub_matches)) => crate::ceremony::submatch(app, sub_matches),
+        Some(("slices", sub_matches)) => crate::slices::submatch(app, sub_matches),
+        Some(("slip39", sub_matches)) => crate::slip39::submatch(app, sub_matches),
         Some((subcommand, _)) => {
             // We should never end up here.
             app.print_help()?;
@@ -375,10 +483,18 @@ pub(crate) fn submatch(app: &mut Command, matches: &ArgMatches) -> Result<(), Er
 pub(crate) fn subcommands() -> Command {
     Command::new("raw")
             .about("Operate using raw text data, rather than on PDF documents. This mode is not recommended for general use, since it might be more complicated for inexperienced users to recover the document.")
-            // paperback-cli raw backup [--sealed] --quorum-size <QUORUM SIZE> --shards <SHARDS> INPUT
+            // paperback-cli raw backup [--sealed] [--test-run] [--shard-encoding multibase|words] [--holders <DIRECTORY>] [--operator-key <KEY>]... --quorum-size <QUORUM SIZE> --shards <SHARDS> INPUT
             .subcommand(raw_backup_cli())
-            // paperback-cli raw restore --main-document <MAIN DOCUMENT> (--shards <SHARD>)... OUTPUT
+            // paperback-cli raw restore [--strict] --main-document <MAIN DOCUMENT> (--shards <SHARD>)... OUTPUT
             .subcommand(raw_restore_cli())
             // paperback-cli raw expand --new-shards <N> (--shards <SHARD>)...
             .subcommand(raw_expand_cli())
+            // paperback-cli raw holder ...
+            .subcommand(crate::holder::subcommands())
+            // paperback-cli raw manifest ...
+            .subcommand(crate::ceremony::subcommands())
+            // paperback-cli raw slices ...
+            .subcommand(crate::slices::subcommands())
+            // paperback-cli raw slip39 ...
+            .subcommand(crate::slip39::subcommands())
 }
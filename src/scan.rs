@@ -0,0 +1,513 @@
+/*
+ * paperback: paper backup generator suitable for long-term storage
+ * Copyright (C) 2018-2022 Aleksa Sarai <cyphar@cyphar.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Reading QR codes directly from photos or scans of paperback documents.
+//!
+//! Scanned PDFs are read by extracting the images embedded in each page
+//! (which is how scanners store each scanned page), rather than by rendering
+//! the pages, so that no external PDF renderer is needed.
+
+use anyhow::{anyhow, bail, Context, Error};
+use clap::{Arg, ArgAction, ArgMatches};
+use image::{DynamicImage, GrayImage, ImageFormat, RgbImage};
+use rxing::BarcodeFormat;
+
+extern crate paperback_core;
+use paperback_core::latest as paperback;
+
+use paperback::{
+    pdf::qr::{Joiner, Part},
+    EncryptedKeyShard, FromWire, MainDocument,
+};
+
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+pub(crate) fn scan_arg() -> Arg {
+    Arg::new("scan")
+        .long("scan")
+        .value_name("IMAGE")
+        .help("Read the QR codes of the main document and key shards from each IMAGE (a PNG or JPEG photo or scan, or a scanned PDF with any number of pages, each of which may contain several QR codes). Anything which could not be found in the images (including the codewords of each key shard) is asked for interactively.")
+        .value_parser(clap::value_parser!(PathBuf))
+        .num_args(1..)
+        .action(ArgAction::Append)
+}
+
+pub(crate) fn camera_args() -> [Arg; 2] {
+    [
+        Arg::new("camera")
+            .long("camera")
+            .value_name("INDEX")
+            .help("Read the QR codes of the main document and key shards live from a camera (the first video device, unless INDEX is given), until the main document and enough key shards have been captured. Anything which could not be captured (including the codewords of each key shard) is asked for interactively.")
+            .value_parser(clap::value_parser!(u32))
+            .num_args(0..=1)
+            .default_missing_value("0")
+            .conflicts_with("scan")
+            .action(ArgAction::Set),
+        Arg::new("camera-timeout")
+            .long("camera-timeout")
+            .value_name("SECONDS")
+            .help("Stop capturing from the camera after this many seconds, even if some codes are still missing.")
+            .value_parser(clap::value_parser!(u64))
+            .default_value("600")
+            .requires("camera")
+            .action(ArgAction::Set),
+    ]
+}
+
+pub(crate) fn get_camera(matches: &ArgMatches) -> Option<(u32, Duration)> {
+    let index = *matches.get_one::<u32>("camera")?;
+    let timeout = matches
+        .get_one::<u64>("camera-timeout")
+        .copied()
+        .unwrap_or_default();
+    Some((index, Duration::from_secs(timeout)))
+}
+
+pub(crate) fn get_scan_paths(matches: &ArgMatches) -> Vec<PathBuf> {
+    matches
+        .get_many::<PathBuf>("scan")
+        .unwrap_or_default()
+        .cloned()
+        .collect()
+}
+
+/// The paperback documents found in a set of images.
+#[derive(Default)]
+pub(crate) struct Scanned {
+    /// The main document, if all of its (data) QR codes were found.
+    pub(crate) main_document: Option<MainDocument>,
+    /// Every distinct key shard found, in the order they were found.
+    pub(crate) shards: Vec<EncryptedKeyShard>,
+}
+
+/// A QR code found in an image, along with where it was found.
+pub(crate) struct LocatedCode {
+    /// The data stored in the code.
+    pub(crate) content: String,
+    /// The width of the code in modules (excluding the quiet zone).
+    pub(crate) modules: usize,
+    /// The average length of the edges of the code (excluding the quiet
+    /// zone) in the image, in pixels.
+    pub(crate) size: f64,
+}
+
+/// The QR codes found in a single image (such as one page of a scanned PDF).
+pub(crate) struct ScannedImage {
+    /// A description of where the image came from (for messages).
+    pub(crate) source: String,
+    /// The width and height of the image, in pixels.
+    pub(crate) dimensions: (u32, u32),
+    /// Every QR code which could be decoded.
+    pub(crate) codes: Vec<LocatedCode>,
+}
+
+// Decode and locate every QR code found in image (described by source, for
+// warnings).
+fn locate_qr_codes(image: &GrayImage, source: &str) -> Vec<LocatedCode> {
+    let mut image = rqrr::PreparedImage::prepare(image.clone());
+    let grids = image.detect_grids();
+
+    let mut codes = Vec::with_capacity(grids.len());
+    for (idx, grid) in grids.iter().enumerate() {
+        match grid.decode() {
+            Ok((meta, content)) => {
+                // The bounds are the corners of the code, in order.
+                let size = (0..4)
+                    .map(|idx| {
+                        let (a, b) = (grid.bounds[idx], grid.bounds[(idx + 1) % 4]);
+                        f64::from(b.x - a.x).hypot(f64::from(b.y - a.y))
+                    })
+                    .sum::<f64>()
+                    / 4.0;
+                codes.push(LocatedCode {
+                    content,
+                    modules: meta.version.to_size(),
+                    size,
+                });
+            }
+            // A single smudged code shouldn't stop the rest of the image from
+            // being used (parity codes may make up for it).
+            Err(err) => eprintln!(
+                "warning: failed to decode QR code {} in {}: {}",
+                idx + 1,
+                source,
+                err
+            ),
+        }
+    }
+    codes
+}
+
+// Decode the contents of every QR code and Data Matrix code found in image
+// (described by source, for warnings).
+fn decode_image(image: &DynamicImage, source: &str) -> Vec<String> {
+    let luma = image.to_luma8();
+    let (width, height) = luma.dimensions();
+    let mut codes = locate_qr_codes(&luma, source)
+        .into_iter()
+        .map(|code| code.content)
+        .collect::<Vec<_>>();
+
+    // Documents printed with --symbology datamatrix, which rqrr can't read.
+    // Finding no codes at all is reported as an error, so errors are ignored.
+    if let Ok(results) = rxing::helpers::detect_multiple_in_luma(luma.into_raw(), width, height) {
+        codes.extend(
+            results
+                .iter()
+                .filter(|result| *result.getBarcodeFormat() == BarcodeFormat::DATA_MATRIX)
+                .map(|result| result.getText().to_string()),
+        );
+    }
+    codes
+}
+
+// Extract the scanned images embedded in each page of the PDF at path.
+fn pdf_images(path: &Path) -> Result<Vec<(String, DynamicImage)>, Error> {
+    let document =
+        lopdf::Document::load(path).with_context(|| format!("failed to read PDF {:?}", path))?;
+
+    let mut images = Vec::new();
+    for (page_num, page_id) in document.get_pages() {
+        let page_images = document
+            .get_page_images(page_id)
+            .with_context(|| format!("failed to read images of page {} of {:?}", page_num, path))?;
+        for (idx, pdf_image) in page_images.iter().enumerate() {
+            let source = format!("image {} of page {} of {:?}", idx + 1, page_num, path);
+            let filters = pdf_image.filters.as_deref().unwrap_or_default();
+            let image = if filters.iter().any(|filter| filter == "DCTDecode") {
+                image::load_from_memory_with_format(pdf_image.content, ImageFormat::Jpeg)
+                    .with_context(|| format!("failed to decode {}", source))?
+            } else if filters.iter().all(|filter| filter == "FlateDecode") {
+                let stream = document
+                    .get_object(pdf_image.id)
+                    .and_then(lopdf::Object::as_stream)
+                    .with_context(|| format!("failed to read {}", source))?;
+                let data = match filters.is_empty() {
+                    true => stream.content.clone(),
+                    false => stream
+                        .decompressed_content()
+                        .with_context(|| format!("failed to decompress {}", source))?,
+                };
+                let (width, height) = (pdf_image.width as u32, pdf_image.height as u32);
+                let image = match (
+                    pdf_image.color_space.as_deref(),
+                    pdf_image.bits_per_component,
+                ) {
+                    (Some("DeviceGray"), Some(8)) => {
+                        GrayImage::from_raw(width, height, data).map(DynamicImage::ImageLuma8)
+                    }
+                    (Some("DeviceRGB"), Some(8)) => {
+                        RgbImage::from_raw(width, height, data).map(DynamicImage::ImageRgb8)
+                    }
+                    _ => None,
+                };
+                match image {
+                    Some(image) => image,
+                    None => {
+                        eprintln!("warning: skipping {} (unsupported colour space)", source);
+                        continue;
+                    }
+                }
+            } else {
+                eprintln!(
+                    "warning: skipping {} (unsupported compression {}) -- try exporting the page as a PNG or JPEG image instead",
+                    source,
+                    filters.join(", ")
+                );
+                continue;
+            };
+            images.push((source, image));
+        }
+    }
+    Ok(images)
+}
+
+// Read the image (or every scanned image in the PDF) at path.
+fn load_images(path: &Path) -> Result<Vec<(String, DynamicImage)>, Error> {
+    let is_pdf = path
+        .extension()
+        .map_or(false, |ext| ext.eq_ignore_ascii_case("pdf"));
+    if !is_pdf {
+        let image =
+            image::open(path).with_context(|| format!("failed to read image {:?}", path))?;
+        return Ok(vec![(format!("{:?}", path), image)]);
+    }
+
+    let images = pdf_images(path)?;
+    if images.is_empty() {
+        bail!(
+            "PDF {:?} contains no scanned images -- only scanned PDFs can be read",
+            path
+        );
+    }
+    Ok(images)
+}
+
+// Decode the contents of every QR code found in the image (or scanned PDF) at
+// path.
+fn decode_file(path: &Path) -> Result<Vec<String>, Error> {
+    Ok(load_images(path)?
+        .iter()
+        .flat_map(|(source, image)| decode_image(image, source))
+        .collect())
+}
+
+/// Decode and locate the QR codes in each image of the image (or scanned
+/// PDF) at path.
+pub(crate) fn locate_file(path: &Path) -> Result<Vec<ScannedImage>, Error> {
+    Ok(load_images(path)?
+        .into_iter()
+        .map(|(source, image)| {
+            let luma = image.to_luma8();
+            ScannedImage {
+                codes: locate_qr_codes(&luma, &source),
+                dimensions: luma.dimensions(),
+                source,
+            }
+        })
+        .collect())
+}
+
+/// Collects the main document and key shards from QR codes as they are
+/// decoded, ignoring codes it has already seen.
+#[derive(Default)]
+pub(crate) struct Scanner {
+    seen: HashSet<String>,
+    // The codes of each main document found (codes of a different main
+    // document cannot be joined together).
+    joiners: Vec<Joiner>,
+    shards: Vec<EncryptedKeyShard>,
+}
+
+impl Scanner {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a decoded QR code, returning whether it had not been seen before.
+    /// QR codes which are neither part of a main document nor a key shard
+    /// (such as the checksum codes) are ignored.
+    pub(crate) fn add_code(&mut self, code: String) -> Result<bool, Error> {
+        if !self.seen.insert(code.clone()) {
+            return Ok(false);
+        }
+        if let Ok(part) = Part::from_wire_multibase(&code) {
+            if !self
+                .joiners
+                .iter_mut()
+                .any(|joiner| joiner.add_part(part.clone()).is_ok())
+            {
+                let mut joiner = Joiner::new();
+                joiner.add_part(part).context("adding main document code")?;
+                self.joiners.push(joiner);
+            }
+        } else if let Ok(shard) = EncryptedKeyShard::from_wire_multibase(&code) {
+            let checksum = shard.checksum_string();
+            if !self
+                .shards
+                .iter()
+                .any(|other| other.checksum_string() == checksum)
+            {
+                self.shards.push(shard);
+            }
+        }
+        Ok(true)
+    }
+
+    /// The number of distinct key shards found so far.
+    pub(crate) fn num_shards(&self) -> usize {
+        self.shards.len()
+    }
+
+    /// The main document, if all of the codes of exactly one main document
+    /// have been found.
+    #[cfg_attr(not(feature = "camera"), allow(dead_code))]
+    pub(crate) fn main_document(&self) -> Option<MainDocument> {
+        let mut complete = self.joiners.iter().filter(|joiner| joiner.complete());
+        match (complete.next(), complete.next()) {
+            (Some(joiner), None) => joiner
+                .combine_parts()
+                .ok()
+                .and_then(|data| MainDocument::from_wire(data).ok()),
+            _ => None,
+        }
+    }
+
+    /// A description of how much of the main document has been found.
+    pub(crate) fn main_document_progress(&self) -> String {
+        // Report on the main document closest to being complete.
+        self.joiners
+            .iter()
+            .filter_map(|joiner| Some((joiner.num_parts()?, joiner.remaining()?)))
+            .min_by_key(|(_, remaining)| *remaining)
+            .map_or_else(
+                || "no main document codes captured".to_string(),
+                |(num_parts, remaining)| {
+                    format!(
+                        "{} of {} main document data codes captured",
+                        num_parts - remaining,
+                        num_parts
+                    )
+                },
+            )
+    }
+
+    /// The main document and key shards found.
+    pub(crate) fn finish(self) -> Result<Scanned, Error> {
+        let mut main_documents = Vec::new();
+        for joiner in &self.joiners {
+            let remaining = joiner.remaining().unwrap_or_default();
+            if remaining > 0 {
+                // Only complain about a partial main document if there is
+                // nothing better to use.
+                if self.joiners.len() == 1 {
+                    bail!(
+                        "{} more main document codes are needed (missing codes {}) -- scan the rest of the main document",
+                        remaining,
+                        crate::describe_missing_codes(joiner)
+                    );
+                }
+                eprintln!(
+                    "warning: ignoring a partially scanned main document ({} codes missing)",
+                    remaining
+                );
+                continue;
+            }
+            let main_document = MainDocument::from_wire(joiner.combine_parts()?)
+                .map_err(|err| anyhow!("parse scanned main document: {}", err))?;
+            if !main_documents
+                .iter()
+                .any(|other: &MainDocument| other.checksum() == main_document.checksum())
+            {
+                main_documents.push(main_document);
+            }
+        }
+        if main_documents.len() > 1 {
+            bail!(
+                "found several main documents ({}) -- only scan the documents of one backup",
+                main_documents
+                    .iter()
+                    .map(MainDocument::id)
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            );
+        }
+
+        let scanned = Scanned {
+            main_document: main_documents.pop(),
+            shards: self.shards,
+        };
+        say!(
+            "Scanned {} and {} key shards.",
+            match scanned.main_document {
+                Some(_) => "the main document",
+                None => "no main document",
+            },
+            scanned.shards.len()
+        );
+        Ok(scanned)
+    }
+}
+
+/// Decode every QR code in the images (or scanned PDFs) at paths, and sort
+/// them into the main document and key shards they belong to.
+pub(crate) fn scan_images(paths: &[PathBuf]) -> Result<Scanned, Error> {
+    let mut scanner = Scanner::new();
+    for path in paths {
+        let codes = decode_file(path)?;
+        say!("Found {} QR codes in {:?}.", codes.len(), path);
+        for code in codes {
+            scanner
+                .add_code(code)
+                .with_context(|| format!("reading QR codes from {:?}", path))?;
+        }
+        say!(
+            "{}, {} key shards captured.",
+            scanner.main_document_progress(),
+            scanner.num_shards()
+        );
+    }
+    scanner.finish()
+}
+
+/// Decode QR codes from the frames of a video device until the main document
+/// and num_extra_shards more key shards than its quorum size have been found
+/// (or timeout has elapsed), reporting progress as new codes are found.
+#[cfg(feature = "camera")]
+pub(crate) fn scan_camera(
+    index: u32,
+    num_extra_shards: u32,
+    timeout: Duration,
+) -> Result<Scanned, Error> {
+    use nokhwa::{
+        pixel_format::RgbFormat,
+        utils::{CameraIndex, RequestedFormat, RequestedFormatType},
+        Camera,
+    };
+
+    let format = RequestedFormat::new::<RgbFormat>(RequestedFormatType::AbsoluteHighestResolution);
+    let mut camera = Camera::new(CameraIndex::Index(index), format)
+        .with_context(|| format!("failed to open camera {}", index))?;
+    camera
+        .open_stream()
+        .with_context(|| format!("failed to start camera {}", index))?;
+    say!("Hold each QR code of the main document and key shards up to the camera.");
+
+    let mut scanner = Scanner::new();
+    let start = std::time::Instant::now();
+    loop {
+        if let Some(main_document) = scanner.main_document() {
+            let num_shards = main_document.quorum_size().saturating_add(num_extra_shards);
+            if scanner.num_shards() >= num_shards as usize {
+                break;
+            }
+        }
+        if start.elapsed() >= timeout {
+            eprintln!("warning: camera timed out -- enter anything still missing by hand");
+            break;
+        }
+
+        let frame = camera
+            .frame()
+            .context("failed to capture camera frame")?
+            .decode_image::<RgbFormat>()
+            .context("failed to decode camera frame")?;
+        let mut new_codes = false;
+        for code in decode_image(&DynamicImage::ImageRgb8(frame), "camera frame") {
+            new_codes |= scanner.add_code(code)?;
+        }
+        if new_codes {
+            say!(
+                "{}, {} key shards captured.",
+                scanner.main_document_progress(),
+                scanner.num_shards()
+            );
+        }
+    }
+    camera.stop_stream().context("failed to stop camera")?;
+    scanner.finish()
+}
+
+#[cfg(not(feature = "camera"))]
+pub(crate) fn scan_camera(_: u32, _: u32, _: Duration) -> Result<Scanned, Error> {
+    bail!("paperback was built without camera support (rebuild it with --features camera)")
+}
@@ -0,0 +1,108 @@
+/*
+ * paperback: paper backup generator suitable for long-term storage
+ * Copyright (C) 2018-2022 Aleksa Sarai <cyphar@cyphar.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Plaintext secret data held in memory by the CLI.
+
+use std::{
+    fmt,
+    io::{self, ErrorKind, Read},
+    ops::Deref,
+};
+
+use zeroize::{Zeroize, Zeroizing};
+
+// Number of bytes read from a secret data source at a time.
+const READ_CHUNK_SIZE: usize = 64 * 1024;
+
+/// A buffer of plaintext secret data (such as the secret being backed up, or
+/// a recovered secret), which is wiped when dropped and never printed by
+/// `Debug`.
+///
+/// Where the platform allows it, the buffer is also locked into memory so
+/// that the secret is never written to swap. Locking is best-effort -- it
+/// fails if the secret is larger than the locked memory limit of the process
+/// (`RLIMIT_MEMLOCK` on Linux), in which case the secret is only wiped.
+pub(crate) struct SecretBuf {
+    data: Vec<u8>,
+    // Dropped after the data has been wiped (see Drop).
+    _lock: Option<region::LockGuard>,
+}
+
+impl SecretBuf {
+    pub(crate) fn new(data: Vec<u8>) -> Self {
+        // The buffer is never resized, so the locked pages stay valid.
+        let lock = match data.is_empty() {
+            true => None,
+            false => region::lock(data.as_ptr(), data.len()).ok(),
+        };
+        Self { data, _lock: lock }
+    }
+
+    /// Read all of the data from reader. Unlike [`Read::read_to_end`], the
+    /// buffer is grown by copying it to a new allocation and wiping the old
+    /// one, so that no copies of the secret are left behind in freed memory.
+    pub(crate) fn read_from<R: Read>(mut reader: R) -> io::Result<Self> {
+        let mut data = Zeroizing::new(Vec::new());
+        let mut chunk = Zeroizing::new(vec![0; READ_CHUNK_SIZE]);
+        loop {
+            let n = match reader.read(&mut chunk) {
+                Ok(0) => break,
+                Ok(n) => n,
+                Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err),
+            };
+            if data.len() + n > data.capacity() {
+                let mut grown = Zeroizing::new(Vec::with_capacity(
+                    (data.len() + n).max(2 * data.capacity()),
+                ));
+                grown.extend_from_slice(&data);
+                data = grown;
+            }
+            data.extend_from_slice(&chunk[..n]);
+        }
+        Ok(Self::new(std::mem::take(&mut *data)))
+    }
+}
+
+impl Deref for SecretBuf {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+impl AsRef<[u8]> for SecretBuf {
+    fn as_ref(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+impl fmt::Debug for SecretBuf {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SecretBuf")
+            .field("len", &self.data.len())
+            .finish()
+    }
+}
+
+impl Drop for SecretBuf {
+    fn drop(&mut self) {
+        self.data.zeroize();
+    }
+}
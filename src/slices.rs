@@ -0,0 +1,192 @@
+/*
+ * paperback: paper backup generator suitable for long-term storage
+ * Copyright (C) 2018-2022 Aleksa Sarai <cyphar@cyphar.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use crate::raw::{read_oneline_file, ENCODING_BASE};
+
+use std::{
+    fs::{self, File},
+    io::{self, prelude::*},
+};
+
+use anyhow::{anyhow, Context, Error};
+use clap::{Arg, ArgAction, ArgMatches, Command};
+
+extern crate paperback_core;
+use paperback_core::latest as paperback;
+
+use paperback::{
+    slices::{SliceAssembler, SuperManifest},
+    FromWire, ToWire,
+};
+
+// paperback-cli raw slices split --slice-size <BYTES> INPUT
+fn split_cli() -> Command {
+    Command::new("split")
+        .about("Split a secret which is too large for a single backup into slices, each of which should be backed up separately. The slices are written to slice-<ID>-<N>.bin and the super-manifest (needed to reassemble them) to super-manifest-<ID>.txt.")
+        .arg(
+            Arg::new("slice-size")
+                .long("slice-size")
+                .value_name("BYTES")
+                .help("Maximum size of each slice.")
+                .value_parser(clap::value_parser!(usize))
+                .action(ArgAction::Set)
+                .required(true),
+        )
+        .arg(
+            Arg::new("INPUT")
+                .help(r#"Path to file containing secret data to split ("-" to read from stdin)."#)
+                .action(ArgAction::Set)
+                .allow_hyphen_values(true)
+                .required(true)
+                .index(1),
+        )
+}
+
+fn split(matches: &ArgMatches) -> Result<(), Error> {
+    let slice_size = *matches
+        .get_one::<usize>("slice-size")
+        .context("required --slice-size argument not provided")?;
+    let input_path = matches
+        .get_one::<String>("INPUT")
+        .context("required INPUT argument not provided")?;
+
+    let mut secret = Vec::new();
+    if input_path == "-" {
+        io::stdin().read_to_end(&mut secret)
+    } else {
+        File::open(input_path)
+            .with_context(|| format!("failed to open secret data file '{}'", input_path))?
+            .read_to_end(&mut secret)
+    }
+    .with_context(|| format!("failed to read secret data from '{}'", input_path))?;
+
+    let (manifest, slices) = SuperManifest::split(&secret, slice_size)?;
+    fs::write(
+        format!("super-manifest-{}.txt", manifest.id()),
+        format!("{}\n", manifest.to_wire_multibase(ENCODING_BASE)),
+    )?;
+    for (idx, slice) in slices.iter().enumerate() {
+        fs::write(format!("slice-{}-{}.bin", manifest.id(), idx + 1), slice)?;
+    }
+
+    println!("Super-Manifest ID: {}", manifest.id());
+    println!("Slices: {}", manifest.num_slices());
+    Ok(())
+}
+
+// paperback-cli raw slices join --super-manifest <PATH> (--slice <PATH>)... OUTPUT
+fn join_cli() -> Command {
+    Command::new("join")
+        .about("Reassemble a split secret from its recovered slices. The slices can be given in any order, and any missing slices are reported.")
+        .arg(
+            Arg::new("super-manifest")
+                .long("super-manifest")
+                .value_name("PATH")
+                .help(r#"Path to the super-manifest ("-" to read from stdin)."#)
+                .action(ArgAction::Set)
+                .allow_hyphen_values(true)
+                .required(true),
+        )
+        .arg(
+            Arg::new("slices")
+                .long("slice")
+                .value_name("PATH")
+                .help("Path to each recovered slice.")
+                .action(ArgAction::Append),
+        )
+        .arg(
+            Arg::new("OUTPUT")
+                .help(r#"Path to write reassembled secret data to ("-" to write to stdout)."#)
+                .action(ArgAction::Set)
+                .allow_hyphen_values(true)
+                .required(true)
+                .index(1),
+        )
+}
+
+fn join(matches: &ArgMatches) -> Result<(), Error> {
+    let manifest = SuperManifest::from_wire_multibase(
+        read_oneline_file(
+            "Super-Manifest",
+            matches
+                .get_one::<String>("super-manifest")
+                .context("required --super-manifest argument not provided")?,
+        )?
+        .trim(),
+    )
+    .context("decode super-manifest")?;
+    let output_path = matches
+        .get_one::<String>("OUTPUT")
+        .context("required OUTPUT argument not provided")?;
+
+    let mut assembler = SliceAssembler::new(&manifest);
+    for path in matches.get_many::<String>("slices").unwrap_or_default() {
+        let slice = fs::read(path).with_context(|| format!("failed to read slice '{}'", path))?;
+        let idx = assembler
+            .push(slice)
+            .with_context(|| format!("adding slice '{}'", path))?;
+        eprintln!("Slice {} of {}: {}", idx + 1, manifest.num_slices(), path);
+    }
+
+    let missing = assembler.missing();
+    if !missing.is_empty() {
+        return Err(anyhow!(
+            "missing slices {} (of {})",
+            missing
+                .iter()
+                .map(|idx| (idx + 1).to_string())
+                .collect::<Vec<_>>()
+                .join(", "),
+            manifest.num_slices()
+        ));
+    }
+    let secret = assembler.finish()?;
+
+    if output_path == "-" {
+        io::stdout().write_all(&secret)?;
+    } else {
+        fs::write(output_path, &secret)
+            .with_context(|| format!("failed to write secret data to '{}'", output_path))?;
+    }
+    Ok(())
+}
+
+pub(crate) fn submatch(app: &mut Command, matches: &ArgMatches) -> Result<(), Error> {
+    match matches.subcommand() {
+        Some(("split", sub_matches)) => split(sub_matches),
+        Some(("join", sub_matches)) => join(sub_matches),
+        Some((subcommand, _)) => {
+            // We should never end up here.
+            app.print_help()?;
+            Err(anyhow!("unknown subcommand 'raw slices {}'", subcommand))
+        }
+        None => {
+            app.print_help()?;
+            Err(anyhow!("no 'raw slices' subcommand specified"))
+        }
+    }
+}
+
+pub(crate) fn subcommands() -> Command {
+    Command::new("slices")
+        .about("Split secrets which are too large for a single backup across several backups, tied together by a super-manifest.")
+        // paperback-cli raw slices split --slice-size <BYTES> INPUT
+        .subcommand(split_cli())
+        // paperback-cli raw slices join --super-manifest <PATH> (--slice <PATH>)... OUTPUT
+        .subcommand(join_cli())
+}
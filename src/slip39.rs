@@ -0,0 +1,234 @@
+/*
+ * paperback: paper backup generator suitable for long-term storage
+ * Copyright (C) 2018-2022 Aleksa Sarai <cyphar@cyphar.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use crate::raw::read_oneline_file;
+
+use std::{
+    fs::{self, File},
+    io::{self, prelude::*},
+};
+
+use anyhow::{anyhow, Context, Error};
+use clap::{Arg, ArgAction, ArgMatches, Command};
+
+extern crate paperback_core;
+use paperback_core::latest as paperback;
+
+use paperback::slip39::{self, Share};
+
+fn passphrase_arg() -> Arg {
+    Arg::new("passphrase-file")
+        .long("passphrase-file")
+        .value_name("PATH")
+        .help(r#"Path to a file containing the SLIP-39 passphrase ("-" to read from stdin). If not specified, the empty passphrase is used."#)
+        .action(ArgAction::Set)
+        .allow_hyphen_values(true)
+}
+
+fn get_passphrase(matches: &ArgMatches) -> Result<String, Error> {
+    match matches.get_one::<String>("passphrase-file") {
+        Some(path) => read_oneline_file("SLIP-39 Passphrase", path).context("read passphrase"),
+        None => Ok(String::new()),
+    }
+}
+
+// paperback-cli raw slip39 import [--passphrase-file <PATH>] (--share <PATH>)... OUTPUT
+fn import_cli() -> Command {
+    Command::new("import")
+        .about(r#"Recover the master secret from a set of SLIP-39 mnemonic shares (such as those created by a hardware wallet), so that it can be backed up with paperback (for instance, by passing OUTPUT as the INPUT of "paperback backup")."#)
+        .arg(passphrase_arg())
+        .arg(
+            Arg::new("shares")
+                .long("share")
+                .value_name("PATH")
+                .help(r#"Path to each SLIP-39 mnemonic share ("-" to read from stdin)."#)
+                .action(ArgAction::Append)
+                .allow_hyphen_values(true)
+                .required(true),
+        )
+        .arg(
+            Arg::new("OUTPUT")
+                .help(r#"Path to write the master secret to ("-" to write to stdout)."#)
+                .action(ArgAction::Set)
+                .allow_hyphen_values(true)
+                .required(true)
+                .index(1),
+        )
+}
+
+fn import(matches: &ArgMatches) -> Result<(), Error> {
+    let passphrase = get_passphrase(matches)?;
+    let output_path = matches
+        .get_one::<String>("OUTPUT")
+        .context("required OUTPUT argument not provided")?;
+
+    let shares = matches
+        .get_many::<String>("shares")
+        .context("required --share argument not provided")?
+        .enumerate()
+        .map(|(idx, path)| {
+            let share = read_oneline_file(&format!("SLIP-39 Share {}", idx + 1), path)
+                .with_context(|| format!("read share {}", idx + 1))?
+                .parse::<Share>()
+                .with_context(|| format!("decode share {}", idx + 1))?;
+            eprintln!(
+                "Share {}: group {} of {} (member {}), identifier {}",
+                idx + 1,
+                share.group_index() + 1,
+                share.group_count(),
+                share.member_index() + 1,
+                share.identifier()
+            );
+            Ok(share)
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    let secret = slip39::combine(&shares, passphrase.as_bytes())?;
+
+    if output_path == "-" {
+        io::stdout().write_all(&secret)?;
+    } else {
+        fs::write(output_path, &secret)
+            .with_context(|| format!("failed to write secret data to '{}'", output_path))?;
+    }
+    Ok(())
+}
+
+// paperback-cli raw slip39 export [--passphrase-file <PATH>] [--iteration-exponent <EXPONENT>] --group-threshold <GROUPS> (--group <MEMBER THRESHOLD/MEMBERS>)... INPUT
+fn export_cli() -> Command {
+    Command::new("export")
+        .about("Split a secret (such as one recovered from a paperback backup) into SLIP-39 mnemonic shares, so that it can be loaded into a hardware wallet which supports SLIP-39.")
+        .arg(passphrase_arg())
+        .arg(
+            Arg::new("iteration-exponent")
+                .long("iteration-exponent")
+                .value_name("EXPONENT")
+                .help("Exponent of the number of PBKDF2 iterations used to encrypt the secret with the passphrase (10000 * 2^EXPONENT iterations).")
+                .value_parser(clap::value_parser!(u8).range(0..=15))
+                .default_value("1"),
+        )
+        .arg(
+            Arg::new("group-threshold")
+                .long("group-threshold")
+                .value_name("GROUPS")
+                .help("Number of groups needed to recover the secret.")
+                .value_parser(clap::value_parser!(u8).range(1..=16))
+                .default_value("1"),
+        )
+        .arg(
+            Arg::new("group")
+                .long("group")
+                .value_name("MEMBER THRESHOLD/MEMBERS")
+                .help("Create a group of MEMBERS shares, any MEMBER THRESHOLD of which recover the group (for instance, --group-threshold 2 --group 1/1 --group 2/3 requires the 1-of-1 group and two shares of the 2-of-3 group). Can be specified up to 16 times.")
+                .value_parser(crate::parse_group)
+                .action(ArgAction::Append)
+                .required(true),
+        )
+        .arg(
+            Arg::new("INPUT")
+                .help(r#"Path to file containing the secret to split ("-" to read from stdin). The secret must be an even number of bytes, at least 16 bytes long."#)
+                .action(ArgAction::Set)
+                .allow_hyphen_values(true)
+                .required(true)
+                .index(1),
+        )
+}
+
+fn export(matches: &ArgMatches) -> Result<(), Error> {
+    let passphrase = get_passphrase(matches)?;
+    let iteration_exponent = *matches
+        .get_one::<u8>("iteration-exponent")
+        .context("required --iteration-exponent argument not provided")?;
+    let group_threshold = *matches
+        .get_one::<u8>("group-threshold")
+        .context("required --group-threshold argument not provided")?;
+    let groups = matches
+        .get_many::<(u32, u32)>("group")
+        .context("required --group argument not provided")?
+        .map(|&(threshold, count)| {
+            Ok((
+                u8::try_from(threshold).context("member threshold too large")?,
+                u8::try_from(count).context("too many members")?,
+            ))
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+    let input_path = matches
+        .get_one::<String>("INPUT")
+        .context("required INPUT argument not provided")?;
+
+    let mut secret = Vec::new();
+    if input_path == "-" {
+        io::stdin().read_to_end(&mut secret)
+    } else {
+        File::open(input_path)
+            .with_context(|| format!("failed to open secret data file '{}'", input_path))?
+            .read_to_end(&mut secret)
+    }
+    .with_context(|| format!("failed to read secret data from '{}'", input_path))?;
+
+    let shares = slip39::split(
+        &secret,
+        passphrase.as_bytes(),
+        group_threshold,
+        &groups,
+        iteration_exponent,
+    )?;
+
+    println!(
+        "{} of {} groups are needed to recover the secret.",
+        group_threshold,
+        groups.len()
+    );
+    for (idx, (members, (threshold, count))) in shares.iter().zip(&groups).enumerate() {
+        println!(
+            "\nGroup {} ({} of {} shares needed):",
+            idx + 1,
+            threshold,
+            count
+        );
+        for share in members {
+            println!("{}", share.to_mnemonic().as_str());
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn submatch(app: &mut Command, matches: &ArgMatches) -> Result<(), Error> {
+    match matches.subcommand() {
+        Some(("import", sub_matches)) => import(sub_matches),
+        Some(("export", sub_matches)) => export(sub_matches),
+        Some((subcommand, _)) => {
+            // We should never end up here.
+            app.print_help()?;
+            Err(anyhow!("unknown subcommand 'raw slip39 {}'", subcommand))
+        }
+        None => {
+            app.print_help()?;
+            Err(anyhow!("no 'raw slip39' subcommand specified"))
+        }
+    }
+}
+
+pub(crate) fn subcommands() -> Command {
+    Command::new("slip39")
+        .about("Convert between SLIP-39 mnemonic shares and the secret data stored in paperback backups.")
+        // paperback-cli raw slip39 import [--passphrase-file <PATH>] (--share <PATH>)... OUTPUT
+        .subcommand(import_cli())
+        // paperback-cli raw slip39 export [--passphrase-file <PATH>] [--iteration-exponent <EXPONENT>] --group-threshold <GROUPS> (--group <MEMBER THRESHOLD/MEMBERS>)... INPUT
+        .subcommand(export_cli())
+}
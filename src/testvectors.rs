@@ -0,0 +1,78 @@
+/*
+ * paperback: paper backup generator suitable for long-term storage
+ * Copyright (C) 2018-2022 Aleksa Sarai <cyphar@cyphar.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use anyhow::{anyhow, Context, Error};
+use clap::{Arg, ArgAction, ArgMatches, Command};
+
+extern crate paperback_core;
+use paperback_core::latest as paperback;
+
+use paperback::testvectors;
+
+use std::fs;
+
+// paperback-cli testvectors emit [--output <PATH>]
+fn emit_cli() -> Command {
+    Command::new("emit")
+        .about("Write the canonical test vectors (deterministic backups and their expected encodings) as JSON, for validating independent implementations.")
+        .arg(
+            Arg::new("output")
+                .long("output")
+                .value_name("PATH")
+                .help(r#"Path to write the test vectors to ("-" to write to stdout)."#)
+                .action(ArgAction::Set)
+                .allow_hyphen_values(true)
+                .default_value("-"),
+        )
+}
+
+fn emit(matches: &ArgMatches) -> Result<(), Error> {
+    let output = testvectors::test_vectors()?.to_json() + "\n";
+    match matches
+        .get_one::<String>("output")
+        .map(String::as_str)
+        .unwrap_or("-")
+    {
+        "-" => print!("{}", output),
+        path => fs::write(path, output)
+            .with_context(|| format!("failed to write test vectors to '{}'", path))?,
+    }
+    Ok(())
+}
+
+pub(crate) fn submatch(app: &mut Command, matches: &ArgMatches) -> Result<(), Error> {
+    match matches.subcommand() {
+        Some(("emit", sub_matches)) => emit(sub_matches),
+        Some((subcommand, _)) => {
+            // We should never end up here.
+            app.print_help()?;
+            Err(anyhow!("unknown subcommand 'testvectors {}'", subcommand))
+        }
+        None => {
+            app.print_help()?;
+            Err(anyhow!("no 'testvectors' subcommand specified"))
+        }
+    }
+}
+
+pub(crate) fn subcommands() -> Command {
+    Command::new("testvectors")
+        .about("Canonical test vectors for other implementations of paperback.")
+        // paperback-cli testvectors emit [--output <PATH>]
+        .subcommand(emit_cli())
+}
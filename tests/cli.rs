@@ -0,0 +1,334 @@
+/*
+ * paperback: paper backup generator suitable for long-term storage
+ * Copyright (C) 2018-2022 Aleksa Sarai <cyphar@cyphar.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use image::{imageops, Rgb, RgbImage};
+
+use std::{
+    fs,
+    io::Write,
+    process::{Command, Output, Stdio},
+};
+
+// Run paperback with args, writing stdin to its standard input.
+fn paperback(args: &[&str], stdin: &[u8]) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_paperback"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(stdin).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn backup_json_output() {
+    let dir = std::env::temp_dir().join(format!("paperback-cli-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_paperback"))
+        .args(["backup", "--format", "json", "--no-progress"])
+        .args(["--quorum-size", "2", "--shards", "3", "--output-dir"])
+        .arg(&dir)
+        .arg("-")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(b"secret data")
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    assert!(
+        output.status.success(),
+        "backup failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    // Nothing other than the result (such as the main document's codes) is
+    // printed to stdout.
+    let result: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(result["quorum-size"], 2);
+    assert_eq!(result["shards"].as_array().unwrap().len(), 3);
+    assert!(!result["files"].as_array().unwrap().is_empty());
+}
+
+#[test]
+fn verify_print_scale() {
+    let dir = std::env::temp_dir().join(format!("paperback-print-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let dir_arg = dir.to_str().unwrap();
+
+    let output = paperback(
+        &[
+            "backup",
+            "--no-progress",
+            "--quorum-size",
+            "2",
+            "--shards",
+            "2",
+            "--output-format",
+            "png",
+            "--dpi",
+            "150",
+            "--output-dir",
+            dir_arg,
+            "-",
+        ],
+        b"secret data",
+    );
+    assert!(
+        output.status.success(),
+        "backup failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let page = fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .find(|path| {
+            let name = path.file_name().unwrap().to_string_lossy();
+            name.starts_with("main_document-") && name.ends_with(".png")
+        })
+        .unwrap();
+
+    // The warnings printed by verify-print for the scan at path.
+    let verify_print = |path: &str| {
+        let output = paperback(&["verify-print", "--no-progress", path], b"");
+        assert!(
+            output.status.success(),
+            "verify-print failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        String::from_utf8(output.stderr).unwrap()
+    };
+    assert!(!verify_print(page.to_str().unwrap()).contains("warning"));
+
+    // Shrink the page (as printing with "fit to page" does).
+    let image = image::open(&page).unwrap().to_rgb8();
+    let (width, height) = image.dimensions();
+    let shrunk = imageops::resize(
+        &image,
+        width * 9 / 10,
+        height * 9 / 10,
+        imageops::FilterType::Triangle,
+    );
+    let mut scaled = RgbImage::from_pixel(width, height, Rgb([0xff; 3]));
+    imageops::overlay(
+        &mut scaled,
+        &shrunk,
+        i64::from(width / 20),
+        i64::from(height / 20),
+    );
+    let scaled_page = dir.join("scaled.png");
+    scaled.save(&scaled_page).unwrap();
+    let warnings = verify_print(scaled_page.to_str().unwrap());
+    fs::remove_dir_all(&dir).unwrap();
+    assert!(warnings.contains("smaller than its actual size"));
+}
+
+// Run a backup with the "stub" output profile, provided by a stub
+// paperback-render-stub plugin which appends each request it is sent to
+// requests.jsonl (in the returned directory) and then runs the shell code in
+// response. The documents are written to the "out" subdirectory.
+#[cfg(unix)]
+fn plugin_backup(test: &str, response: &str) -> (std::path::PathBuf, Output) {
+    use std::{env, os::unix::fs::PermissionsExt};
+
+    let dir = env::temp_dir().join(format!("paperback-plugin-{}-{}", test, std::process::id()));
+    let (bin, out) = (dir.join("bin"), dir.join("out"));
+    fs::create_dir_all(&bin).unwrap();
+    fs::create_dir_all(&out).unwrap();
+    let plugin = bin.join("paperback-render-stub");
+    fs::write(
+        &plugin,
+        format!(
+            "#!/bin/sh\ncat >>'{}'\n{}\n",
+            dir.join("requests.jsonl").display(),
+            response
+        ),
+    )
+    .unwrap();
+    fs::set_permissions(&plugin, fs::Permissions::from_mode(0o755)).unwrap();
+    let path = env::join_paths(
+        [bin]
+            .into_iter()
+            .chain(env::split_paths(&env::var_os("PATH").unwrap_or_default())),
+    )
+    .unwrap();
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_paperback"))
+        .env("PATH", path)
+        .args(["backup", "--format", "json", "--no-progress"])
+        .args(["--profile", "stub", "--quorum-size", "2", "--shards", "3"])
+        .arg("--output-dir")
+        .arg(&out)
+        .arg("-")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(b"secret data")
+        .unwrap();
+    (dir, child.wait_with_output().unwrap())
+}
+
+#[cfg(unix)]
+#[test]
+fn plugin_request_response() {
+    let (dir, output) = plugin_backup(
+        "render",
+        r#"printf '{"artifacts": [{"name": "doc-%s.txt", "data": "f68656c6c6f"}]}' $$"#,
+    );
+    let requests = fs::read_to_string(dir.join("requests.jsonl")).unwrap();
+    let artifacts = fs::read_dir(dir.join("out"))
+        .unwrap()
+        .map(|entry| fs::read(entry.unwrap().path()).unwrap())
+        .collect::<Vec<_>>();
+    fs::remove_dir_all(&dir).unwrap();
+    assert!(
+        output.status.success(),
+        "backup failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let result: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let document_id = result["document-id"].as_str().unwrap();
+    let shards = result["shards"].as_array().unwrap();
+
+    // One request is sent for each document, in order.
+    let requests = requests
+        .lines()
+        .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+        .collect::<Vec<_>>();
+    let kinds = requests
+        .iter()
+        .map(|request| request["kind"].as_str().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        kinds,
+        [
+            "main-document",
+            "manifest",
+            "key-shard",
+            "key-shard",
+            "key-shard"
+        ]
+    );
+    for request in &requests {
+        assert_eq!(request["protocol-version"], 1);
+        assert_eq!(request["profile"], "stub");
+        assert_eq!(request["document-id"], document_id);
+        assert_eq!(request["test-run"], false);
+        assert!(multibase::decode(request["data"].as_str().unwrap()).is_ok());
+    }
+    assert_eq!(requests[0]["id"], document_id);
+    assert_eq!(requests[0]["checksum"], result["checksum"]);
+    assert_eq!(
+        requests[0]["name"],
+        format!("main_document-{}", document_id)
+    );
+    assert!(requests[0].get("codewords").is_none());
+    for (request, shard) in requests[2..].iter().zip(shards) {
+        assert_eq!(request["id"], shard["id"]);
+        assert_eq!(request["checksum"], shard["checksum"]);
+        assert_eq!(
+            request["name"],
+            format!(
+                "key_shard-{}-{}",
+                document_id,
+                shard["id"].as_str().unwrap()
+            )
+        );
+        assert!(!request["codewords"].as_array().unwrap().is_empty());
+    }
+
+    // The decoded data of every artifact is written to the output directory.
+    assert_eq!(artifacts.len(), 5);
+    assert!(artifacts.iter().all(|data| data == b"hello"));
+    assert_eq!(result["files"].as_array().unwrap().len(), 5);
+}
+
+#[cfg(unix)]
+#[test]
+fn plugin_invalid_artifact_names() {
+    // The names are expanded by the plugin, which is run from the bin
+    // subdirectory of the test directory.
+    for (test, name) in [
+        ("relative", "../escaped"),
+        ("absolute", r#"$(cd "$(dirname "$0")/.." && pwd)/escaped"#),
+    ] {
+        let (dir, output) = plugin_backup(
+            test,
+            &format!(
+                r#"printf '{{"artifacts": [{{"name": "%s", "data": "f68656c6c6f"}}]}}' "{}""#,
+                name
+            ),
+        );
+        let escaped = dir.join("escaped").exists();
+        let name = match test {
+            "absolute" => dir.join("escaped").to_string_lossy().into_owned(),
+            _ => name.to_string(),
+        };
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(!output.status.success());
+        assert!(
+            !escaped,
+            "artifact '{}' was written outside --output-dir",
+            name
+        );
+        let result: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        let message = result["error"]["message"].as_str().unwrap();
+        assert!(
+            message.contains(&format!("invalid name '{}'", name)),
+            "unexpected error: {}",
+            message
+        );
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn plugin_failure() {
+    let (dir, output) = plugin_backup("failure", "echo 'stub plugin failed' >&2; exit 3");
+    let requests = fs::read_to_string(dir.join("requests.jsonl")).unwrap();
+    let written = fs::read_dir(dir.join("out")).unwrap().count();
+    fs::remove_dir_all(&dir).unwrap();
+    assert!(!output.status.success());
+
+    // The backup is aborted after the first document.
+    assert_eq!(requests.lines().count(), 1);
+    assert_eq!(written, 0);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("stub plugin failed"));
+    let result: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let message = result["error"]["message"].as_str().unwrap();
+    assert!(
+        message.contains("failed to render main_document-"),
+        "unexpected error: {}",
+        message
+    );
+}